@@ -0,0 +1,79 @@
+use std::{env, path::PathBuf, process::ExitCode};
+
+use limebase::{server::server::Server, Database, Options};
+
+const USAGE: &str = "\
+usage: limebase-server DBFILE [-p PORT]
+
+Open the database DBFILE, creating it if it does not exist, and serve it to PostgreSQL clients
+such as psql on 127.0.0.1 at PORT, 5432 by default.
+";
+
+const DEFAULT_PORT: u16 = 5432;
+/// The pages of the database kept in memory, shared by every session.
+const POOL_SIZE: usize = 4096;
+/// The pages of sort runs and spilled hash tables kept in memory.
+const TEMP_POOL_SIZE: usize = 1024;
+
+struct Args {
+    db_path: PathBuf,
+    port: u16,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprint!("limebase-server: {message}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("limebase-server: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The arguments, or None if help was asked for.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut db_path = None;
+    let mut port = DEFAULT_PORT;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-p" => match args.next() {
+                Some(value) => {
+                    port = value.parse().map_err(|_| format!("invalid port {value}"))?;
+                }
+                None => return Err("-p needs a port".to_string()),
+            },
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if db_path.is_none() => db_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    let db_path = db_path.ok_or_else(|| "missing the database file".to_string())?;
+    Ok(Some(Args { db_path, port }))
+}
+
+fn run(args: &Args) -> anyhow::Result<()> {
+    let options = Options::new()
+        .with_pool_size(POOL_SIZE)
+        .with_temp_pool_size(TEMP_POOL_SIZE);
+    let db = Database::open(&args.db_path, options)?;
+    let server = Server::bind(&db, ("127.0.0.1", args.port))?;
+    eprintln!(
+        "limebase-server {} listening on {}",
+        env!("CARGO_PKG_VERSION"),
+        server.local_addr()?
+    );
+    server.serve()?;
+    Ok(())
+}
@@ -1,270 +1,732 @@
 use std::{
-    collections::LinkedList,
-    ops::DerefMut,
+    collections::{BTreeSet, LinkedList},
+    ops::Deref,
     sync::{
-        atomic::{self, AtomicUsize},
-        Mutex, RwLock, TryLockError,
+        atomic::{self, AtomicU64, AtomicUsize},
+        Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, TryLockError,
     },
 };
 
 use dashmap::DashMap;
 
 use crate::{
-    storage::disk::{DiskManager, LimeBaseDiskManager},
+    buffer::page_guard::{ReadPageGuard, WritePageGuard},
+    recovery::log_manager::LogManager,
+    storage::{
+        disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+        page::{
+            bytes,
+            free_page_bitmap::{self, FreePageBitmap},
+            page::FrameState,
+        },
+    },
     Page, PageId,
 };
 
+/// Counts of what the fetches of a buffer pool did, since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Fetches of pages that were in the pool.
+    pub hits: u64,
+    /// Fetches of pages that were not.
+    pub misses: u64,
+    /// Pages read from the disk.
+    pub reads: u64,
+    /// Pages written to the disk, when evicted or flushed.
+    pub writes: u64,
+}
+
+impl BufferPoolStats {
+    /// What happened between `earlier` and these counts.
+    pub fn since(self, earlier: BufferPoolStats) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits - earlier.hits,
+            misses: self.misses - earlier.misses,
+            reads: self.reads - earlier.reads,
+            writes: self.writes - earlier.writes,
+        }
+    }
+}
+
+impl std::ops::Add for BufferPoolStats {
+    type Output = BufferPoolStats;
+
+    fn add(self, other: BufferPoolStats) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            reads: self.reads + other.reads,
+            writes: self.writes + other.writes,
+        }
+    }
+}
+
 pub trait BufferPoolManager {
     /// Get the size of the buffer pool.
     fn get_pool_size(&self) -> usize;
+    /// Get the counts of hits, misses, reads and writes so far.
+    fn stats(&self) -> BufferPoolStats;
     /// Get the all pages in the buffer pool.
     fn get_pages(&self) -> &[RwLock<Page>];
     /// Create a new page in the buffer pool, returning the page_id and the page,
     /// or None if all frames are currently in use and not evictable (in another word, pinned)
     /// Return Err if a disk manager emits an error.
     fn new_page(&self) -> anyhow::Result<Option<(PageId, &RwLock<Page>)>>;
-    /// Fetch the requested page from the buffer pool. Return None if page_id needs to be fetched from the disk
+    /// Fetch the requested page from the buffer pool and pin it. Return None if page_id needs to be fetched from the disk
     /// but all frames are curently in use and not evictable (in another word, pinned).
     /// Return Err if a disk manager emits an error.
     fn fetch_page(&self, page_id: PageId) -> anyhow::Result<Option<&RwLock<Page>>>;
     /// Unpin the target page from the buffer pool. If page_id is not in the buffer pool or its pin count is already 0, return false.
     fn unpin_page(&self, page_id: PageId, is_dirty: bool) -> bool;
-    /// Use the DiskManager::write_page to flush a page to the disk, REGARDLESS of the dirty flag.
-    /// Unset the dirty flag of the page after flushing.
+    /// Use the DiskManager::write_page to flush a page to the disk if it is dirty, and unset the
+    /// dirty flag. Return false if page_id is not in the buffer pool.
     /// Return Err if a disk manager emits an error.
     fn flush_page(&self, page_id: PageId) -> anyhow::Result<bool>;
-    /// Flush all the pages in the buffer pool to disk.
+    /// Flush all the dirty pages in the buffer pool to disk.
     /// Return Err if a disk manager emits an error.
     fn flush_all_pages(&self) -> anyhow::Result<()>;
-    /// Delete a page from the buffer pool. If page_id is not in the buffer pool, do nothing and return true. If the
-    /// page is pinned and cannot be deleted, return false immediately.
+    /// Delete a page and free its page id for a later new_page to reuse. If page_id is not in the buffer pool, only
+    /// free it and return true. If the page is pinned and cannot be deleted, return false immediately.
     fn delete_page(&self, page_id: PageId) -> bool;
+    /// Delete every page of `page_ids`, like [`Self::delete_page`].
+    /// Return Err at the first page that is pinned, leaving it and the pages after it.
+    fn delete_pages(&self, page_ids: &[PageId]) -> anyhow::Result<()> {
+        for &page_id in page_ids {
+            if !self.delete_page(page_id) {
+                anyhow::bail!("failed to free {page_id:?}: the page is pinned");
+            }
+        }
+        Ok(())
+    }
+    /// The log that changes to the pages are appended to, if they are logged. A
+    /// [`WritePageGuard`] appends the change made through it when it is dropped.
+    fn log_manager(&self) -> Option<&LogManager> {
+        None
+    }
+
+    /// Create a new page and return it pinned and write-latched.
+    /// Return Err if all frames are pinned or a disk manager emits an error.
+    fn new_page_write(&self) -> anyhow::Result<WritePageGuard<'_, Self>> {
+        let Some((page_id, page)) = self.new_page()? else {
+            anyhow::bail!("failed to create a new page: all frames in the buffer pool are pinned");
+        };
+        Ok(WritePageGuard::new_page(
+            self,
+            page_id,
+            page.write().unwrap(),
+        ))
+    }
+    /// Fetch a page and return it pinned and read-latched.
+    /// Return Err if all frames are pinned or a disk manager emits an error.
+    fn fetch_page_read(&self, page_id: PageId) -> anyhow::Result<ReadPageGuard<'_, Self>> {
+        let Some(page) = self.fetch_page(page_id)? else {
+            anyhow::bail!("failed to fetch {page_id:?}: all frames in the buffer pool are pinned");
+        };
+        Ok(ReadPageGuard::new(self, page_id, page.read().unwrap()))
+    }
+    /// Fetch a page and return it pinned and read-latched, or None without waiting if the page
+    /// is write-latched.
+    /// Return Err if all frames are pinned or a disk manager emits an error.
+    fn try_fetch_page_read(
+        &self,
+        page_id: PageId,
+    ) -> anyhow::Result<Option<ReadPageGuard<'_, Self>>> {
+        let Some(page) = self.fetch_page(page_id)? else {
+            anyhow::bail!("failed to fetch {page_id:?}: all frames in the buffer pool are pinned");
+        };
+        match page.try_read() {
+            Ok(guard) => Ok(Some(ReadPageGuard::new(self, page_id, guard))),
+            Err(TryLockError::WouldBlock) => {
+                self.unpin_page(page_id, false);
+                Ok(None)
+            }
+            Err(TryLockError::Poisoned(_)) => anyhow::bail!("poisoned lock"),
+        }
+    }
+    /// Fetch a page and return it pinned and write-latched.
+    /// Return Err if all frames are pinned or a disk manager emits an error.
+    fn fetch_page_write(&self, page_id: PageId) -> anyhow::Result<WritePageGuard<'_, Self>> {
+        let Some(page) = self.fetch_page(page_id)? else {
+            anyhow::bail!("failed to fetch {page_id:?}: all frames in the buffer pool are pinned");
+        };
+        Ok(WritePageGuard::new(self, page_id, page.write().unwrap()))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct FrameId(usize);
 
+/// What [`BufferPoolManagerImpl::evict_page`] found.
+enum Eviction<'a> {
+    /// A frame that was emptied.
+    Evicted(FrameId),
+    /// A dirty page that has to be written back before it can be evicted.
+    Dirty(PageId, RwLockReadGuard<'a, Page>),
+    /// Only dirty pages latched by others could be evicted.
+    Busy,
+    /// Every page is pinned.
+    None,
+}
+
 impl FrameId {
     pub fn new(id: usize) -> Self {
         Self(id)
     }
 }
 
-pub struct BufferPoolManagerImpl<'a> {
+/// Pages are pinned and unpinned through the [`FrameState`] of their frame without taking the
+/// page latch, so that a thread holding latches (for example while crabbing down a B+ tree)
+/// never waits for another thread to release a latch just to unpin a page. Changing which page
+/// a frame holds is serialized by `latch`.
+pub struct BufferPoolManagerImpl<D: DiskManager = LimeBaseDiskManager> {
     pages: Box<[RwLock<Page>]>,
+    frames: Box<[Arc<FrameState>]>,
     next_page_id: AtomicUsize,
+    /// Deleted pages, reused by `new_page` lowest first before the file grows. The set is kept
+    /// in `free_page_map` too if the pool has one, and otherwise only lives in memory: pages
+    /// deleted before the pool is dropped then stay unused in the file for good.
+    free_pages: Mutex<BTreeSet<PageId>>,
+    free_page_map: Option<FreePageMap>,
     page_table: DashMap<PageId, FrameId>,
     // NOTE: is there lock-free linked list in Rust?
     /// list of free frames that don't have any pages on them.
     free_list: Mutex<LinkedList<FrameId>>,
-    disk_manager: &'a LimeBaseDiskManager,
+    /// Held while a page is brought into or removed from a frame, so that no page is ever
+    /// loaded into two frames at once.
+    latch: Mutex<()>,
+    disk_manager: Arc<D>,
+    /// The log that dirty pages are written behind, if their changes are logged.
+    log_manager: Option<Arc<LogManager>>,
+    stats: Counters,
+    /// The statements using the pool, if it is a [`TempBufferPool`].
+    statements: Mutex<usize>,
+}
+
+/// Where the free pages of a pool are persisted, see [`BufferPoolManagerImpl::with_free_page_map`].
+struct FreePageMap {
+    /// The page and the offset in it of the id of the first bitmap page, 0 if there is none yet.
+    root: (PageId, usize),
+    /// The bitmap pages of the list, in order. Held while a bit is changed.
+    bitmaps: Mutex<Vec<PageId>>,
+}
+
+/// The counters behind [`BufferPoolStats`].
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    reads: AtomicU64,
+    writes: AtomicU64,
 }
 
-impl<'a> BufferPoolManagerImpl<'a> {
-    pub fn new(pool_size: usize, disk_manager: &'a LimeBaseDiskManager) -> Self {
+impl Counters {
+    fn add(counter: &AtomicU64) {
+        counter.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+}
+
+/// A buffer pool over temporary space, for pages that only live while a query runs.
+pub type TempBufferPool = BufferPoolManagerImpl<TempDiskManager>;
+
+impl<D: DiskManager> BufferPoolManagerImpl<D> {
+    /// A pool of `pool_size` pages over `disk_manager`, which is shared if it is given as an
+    /// [`Arc`].
+    pub fn new(pool_size: usize, disk_manager: impl Into<Arc<D>>) -> Self {
+        let disk_manager = disk_manager.into();
         let mut pages = Vec::with_capacity(pool_size);
         for _ in 0..pool_size {
             pages.push(Page::new(disk_manager.page_size()));
         }
         let pages = pages.into_boxed_slice();
+        let frames = pages
+            .iter()
+            .map(|page| Arc::clone(page.read().unwrap().frame_state()))
+            .collect();
         let free_list = (0..pool_size).map(FrameId::new).collect();
         Self {
             pages,
-            next_page_id: AtomicUsize::new(0),
+            frames,
+            // Pages that already exist on disk are never reallocated.
+            next_page_id: AtomicUsize::new(disk_manager.num_pages()),
+            free_pages: Mutex::new(BTreeSet::new()),
+            free_page_map: None,
             page_table: DashMap::new(),
             free_list: Mutex::new(free_list),
+            latch: Mutex::new(()),
             disk_manager,
+            log_manager: None,
+            stats: Counters::default(),
+            statements: Mutex::new(0),
         }
     }
 
-    fn free_frame(&self) -> Option<FrameId> {
-        let mut free_list = self.free_list.lock().unwrap();
-        free_list.pop_front()
+    /// Write pages only once the log holds their changes: before a dirty page is written,
+    /// `log_manager` is flushed up to the LSN stamped on the page.
+    pub fn with_log_manager(mut self, log_manager: Arc<LogManager>) -> Self {
+        self.log_manager = Some(log_manager);
+        self
     }
 
-    fn evict_page(&self) -> anyhow::Result<Option<FrameId>> {
-        for page in self.get_pages() {
-            let mut page_guard = match page.try_write() {
-                Ok(page_guard) => page_guard,
-                Err(TryLockError::WouldBlock) => continue,
-                Err(TryLockError::Poisoned(_)) => anyhow::bail!("poisoned lock"),
-            };
+    /// Persist the free pages in a list of [`FreePageBitmap`] pages, and load the pages freed
+    /// so far from it. The id of the first bitmap page is kept at `root_offset` of the
+    /// `root_page_id` page, which is only read if it exists already: the list is started when
+    /// a page is first freed.
+    pub fn with_free_page_map(
+        mut self,
+        root_page_id: PageId,
+        root_offset: usize,
+    ) -> anyhow::Result<Self> {
+        let num_pages = self.next_page_id.load(atomic::Ordering::Acquire);
+        let capacity = FreePageBitmap::<&[u8]>::capacity(self.disk_manager.page_size());
+        let mut bitmaps = Vec::new();
+        let mut free_pages = BTreeSet::new();
+        if root_page_id.as_usize() < num_pages {
+            let first = bytes::read_u64(self.fetch_page_read(root_page_id)?.data(), root_offset);
+            let mut next = (first != 0).then(|| PageId::from_u64(first));
+            while let Some(page_id) = next {
+                let guard = self.fetch_page_read(page_id)?;
+                let bitmap = FreePageBitmap::new(guard.data());
+                let start = bitmaps.len() * capacity;
+                free_pages.extend(
+                    bitmap
+                        .free_indexes()
+                        .map(|index| start + index)
+                        .take_while(|&page_id| page_id < num_pages)
+                        .map(PageId::new),
+                );
+                next = bitmap.next_page_id();
+                bitmaps.push(page_id);
+            }
+        }
+        self.free_pages = Mutex::new(free_pages);
+        self.free_page_map = Some(FreePageMap {
+            root: (root_page_id, root_offset),
+            bitmaps: Mutex::new(bitmaps),
+        });
+        Ok(self)
+    }
 
-            if page_guard.is_pinned() {
-                continue;
+    pub fn disk_manager(&self) -> &D {
+        &self.disk_manager
+    }
+
+    /// Look up the frame holding `page_id`. The frame id is copied out so that the page table
+    /// shard is not locked while the caller works on the frame. Callers must re-check the page
+    /// id of the frame, which may have been reused for another page in the meantime.
+    fn frame_of(&self, page_id: PageId) -> Option<FrameId> {
+        self.page_table.get(&page_id).map(|frame_id| *frame_id)
+    }
+
+    /// Pin `page_id` if it is resident.
+    fn try_pin(&self, page_id: PageId) -> Option<&RwLock<Page>> {
+        let frame_id = self.frame_of(page_id)?;
+        self.frames[frame_id.0]
+            .try_pin(page_id)
+            .then(|| &self.pages[frame_id.0])
+    }
+
+    /// Take a free frame, or evict an unpinned page. The frame is returned reserved and without
+    /// a page, along with `latch`, which is released while a dirty victim is written back.
+    fn take_frame<'l>(
+        &'l self,
+        mut latch: MutexGuard<'l, ()>,
+    ) -> anyhow::Result<(MutexGuard<'l, ()>, Option<FrameId>)> {
+        loop {
+            let free_frame = self.free_list.lock().unwrap().pop_front();
+            if let Some(frame_id) = free_frame {
+                let reserved = self.frames[frame_id.0].try_reserve();
+                debug_assert!(reserved);
+                return Ok((latch, Some(frame_id)));
+            }
+            match self.evict_page() {
+                Eviction::Evicted(frame_id) => return Ok((latch, Some(frame_id))),
+                Eviction::Dirty(page_id, page_guard) => {
+                    // The read latch keeps the frame from being reused while the page is
+                    // written, and the page stays resident for others to pin meanwhile. Once
+                    // it is clean, it is evicted on the next round.
+                    drop(latch);
+                    let result = self.flush_page_with_guard(page_id, &page_guard);
+                    drop(page_guard);
+                    result?;
+                    latch = self.latch.lock().unwrap();
+                }
+                Eviction::Busy => {
+                    drop(latch);
+                    std::thread::yield_now();
+                    latch = self.latch.lock().unwrap();
+                }
+                Eviction::None => return Ok((latch, None)),
             }
+        }
+    }
 
-            let Some(page_id) = page_guard.page_id() else {
+    /// Evict a clean unpinned page, or else read-latch a dirty one for the caller to write
+    /// back without `latch`. Must be called with `latch` held.
+    fn evict_page(&self) -> Eviction<'_> {
+        let mut dirty = None;
+        let mut busy = false;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let Some(page_id) = frame.page_id() else {
                 continue;
             };
-
-            if page_guard.is_dirty() {
-                self.flush_page_with_guard(page_id, &mut page_guard)?;
+            // A reserved frame cannot be dirtied, as that takes a pin.
+            if !frame.try_reserve() {
+                continue;
+            }
+            if frame.is_dirty() {
+                frame.release(0);
+                if dirty.is_none() {
+                    // Not waited for: the page may be write-latched by a thread that waits
+                    // for `latch` in turn.
+                    match self.pages[i].try_read() {
+                        Ok(page_guard) => dirty = Some((page_id, page_guard)),
+                        Err(_) => busy = true,
+                    }
+                }
+                continue;
             }
 
             let Some((_, frame_id)) = self.page_table.remove(&page_id) else {
                 panic!("page_id is not in the page table");
             };
-            page_guard.deallocate_page();
-            self.deallocate_page(page_id);
+            frame.set_page_id(None);
 
-            return Ok(Some(frame_id));
+            return Eviction::Evicted(frame_id);
         }
 
-        Ok(None)
+        match dirty {
+            Some((page_id, page_guard)) => Eviction::Dirty(page_id, page_guard),
+            None if busy => Eviction::Busy,
+            None => Eviction::None,
+        }
     }
 
     fn flush_page_with_guard(
         &self,
         page_id: PageId,
-        page_guard: &mut impl DerefMut<Target = Page>,
+        page_guard: &impl Deref<Target = Page>,
     ) -> anyhow::Result<()> {
-        self.disk_manager.write_page(page_id, page_guard.data())?;
-        page_guard.clear_dirty();
+        let frame = page_guard.frame_state();
+        // The page may have been flushed or deleted since its frame was looked up.
+        if page_guard.page_id() != Some(page_id) || !frame.take_dirty() {
+            return Ok(());
+        }
+        if let Err(err) = self
+            .flush_log_for(page_guard)
+            .and_then(|()| self.disk_manager.write_page(page_id, page_guard.data()))
+        {
+            frame.set_dirty(true);
+            return Err(err);
+        }
+        Counters::add(&self.stats.writes);
 
         Ok(())
     }
 
-    fn allocate_page(&self) -> PageId {
+    /// Enforce the WAL rule: flush the log up to the LSN of `page` before the page is written.
+    fn flush_log_for(&self, page: &Page) -> anyhow::Result<()> {
+        let Some(log_manager) = &self.log_manager else {
+            return Ok(());
+        };
+        let lsn = page.lsn();
+        if lsn > log_manager.flushed_lsn() {
+            log_manager.flush(lsn)?;
+        }
+        Ok(())
+    }
+
+    /// Bring `page_id`, which must be allocated and unused, into a frame as a zeroed page and
+    /// pin it. Return None if all frames are pinned.
+    fn new_page_at(&self, page_id: PageId) -> anyhow::Result<Option<&RwLock<Page>>> {
+        let latch = self.latch.lock().unwrap();
+        let (_latch, Some(frame_id)) = self.take_frame(latch)? else {
+            return Ok(None);
+        };
+
+        let page = &self.pages[frame_id.0];
+        let frame = &self.frames[frame_id.0];
+        page.write().unwrap().data_mut().fill(0);
+        frame.set_page_id(Some(page_id));
+        // A new page must reach the disk on eviction even if the caller never modifies it,
+        // otherwise fetching it again would read past the end of the file.
+        frame.set_dirty(true);
+        frame.release(1);
+        self.page_table.insert(page_id, frame_id);
+
+        Ok(Some(page))
+    }
+
+    /// Take the lowest free page, or else grow the file. Must be called without `latch`, as
+    /// the free-page map is written through the pool.
+    fn allocate_page(&self) -> anyhow::Result<PageId> {
+        let free_page = self.free_pages.lock().unwrap().pop_first();
+        if let Some(page_id) = free_page {
+            if let Err(err) = self.mark_free(page_id, false) {
+                self.free_pages.lock().unwrap().insert(page_id);
+                return Err(err);
+            }
+            return Ok(page_id);
+        }
         let page_id = self.next_page_id.fetch_add(1, atomic::Ordering::AcqRel);
-        PageId::new(page_id)
+        Ok(PageId::new(page_id))
     }
 
-    fn deallocate_page(&self, _page_id: PageId) {
-        // currently noop
+    /// Free `page_id` for `allocate_page` to reuse. Must be called without `latch`.
+    fn deallocate_page(&self, page_id: PageId) {
+        // A page that was never allocated must not be handed out by `allocate_page`.
+        if page_id.as_usize() >= self.next_page_id.load(atomic::Ordering::Acquire) {
+            return;
+        }
+        // If the map cannot be written, the page is still reused until the pool is dropped,
+        // and only leaks in the file.
+        let _ = self.mark_free(page_id, true);
+        self.free_pages.lock().unwrap().insert(page_id);
+    }
+
+    /// Record in the free-page map, if the pool has one, whether `page_id` is free. The list of
+    /// bitmap pages is extended to cover a page that is freed.
+    fn mark_free(&self, page_id: PageId, free: bool) -> anyhow::Result<()> {
+        let Some(map) = &self.free_page_map else {
+            return Ok(());
+        };
+        let capacity = FreePageBitmap::<&[u8]>::capacity(self.disk_manager.page_size());
+        let (position, index) = (page_id.as_usize() / capacity, page_id.as_usize() % capacity);
+        let mut bitmaps = map.bitmaps.lock().unwrap();
+        if !free && position >= bitmaps.len() {
+            // Freed while the map could not be extended, so never recorded.
+            return Ok(());
+        }
+        while bitmaps.len() <= position {
+            self.push_bitmap(map, &mut bitmaps)?;
+        }
+
+        let (offset, mask) = FreePageBitmap::<&[u8]>::bit_location(index);
+        let mut guard = self.fetch_page_write(bitmaps[position])?;
+        let byte = guard.data()[offset];
+        guard.write_at(offset, &[if free { byte | mask } else { byte & !mask }]);
+
+        Ok(())
+    }
+
+    /// Append an empty bitmap page to the free-page map.
+    fn push_bitmap(&self, map: &FreePageMap, bitmaps: &mut Vec<PageId>) -> anyhow::Result<()> {
+        // Taken from the end of the file rather than from the free pages, whose bits it holds.
+        let page_id = PageId::new(self.next_page_id.fetch_add(1, atomic::Ordering::AcqRel));
+        let Some(page) = self.new_page_at(page_id)? else {
+            self.free_pages.lock().unwrap().insert(page_id);
+            anyhow::bail!("failed to create a new page: all frames in the buffer pool are pinned");
+        };
+        let mut guard = WritePageGuard::new_page(self, page_id, page.write().unwrap());
+        FreePageBitmap::init(guard.data_mut());
+        drop(guard);
+
+        let (link_page_id, link_offset) = match bitmaps.last() {
+            Some(&last) => (last, free_page_bitmap::NEXT_PAGE_ID_OFFSET),
+            None => map.root,
+        };
+        self.fetch_page_write(link_page_id)?
+            .write_at(link_offset, &page_id.to_u64().to_le_bytes());
+        bitmaps.push(page_id);
+
+        Ok(())
+    }
+}
+
+impl TempBufferPool {
+    /// Start a statement that may spill into the pool. Once the last running statement is
+    /// done, the temp space is recycled, so that it does not keep growing with every statement
+    /// that spills.
+    pub fn begin_statement(&self) -> TempStatement<'_> {
+        *self.statements.lock().unwrap() += 1;
+        TempStatement { pool: self }
+    }
+
+    /// Discard every page without writing it back and truncate the temporary file, so that
+    /// the next statement starts from an empty temp space. Fail if a page is still pinned.
+    /// Pages that are not pinned may still be read later by a running statement, so this must
+    /// only be called once no statement uses the pool.
+    fn recycle(&self) -> anyhow::Result<()> {
+        if self.frames.iter().any(|frame| frame.is_pinned()) {
+            anyhow::bail!("cannot recycle temp space while pages are pinned");
+        }
+        self.page_table.clear();
+        for frame in self.frames.iter() {
+            frame.set_page_id(None);
+            frame.set_dirty(false);
+        }
+        *self.free_list.lock().unwrap() = (0..self.frames.len()).map(FrameId::new).collect();
+        self.next_page_id.store(0, atomic::Ordering::Release);
+        self.free_pages.lock().unwrap().clear();
+        self.disk_manager.truncate()?;
+
+        Ok(())
     }
 }
 
-impl<'a> BufferPoolManager for BufferPoolManagerImpl<'a> {
+/// A statement running on a [`TempBufferPool`], from [`TempBufferPool::begin_statement`]. It
+/// must outlive the executors of the statement.
+pub struct TempStatement<'p> {
+    pool: &'p TempBufferPool,
+}
+
+impl Drop for TempStatement<'_> {
+    fn drop(&mut self) {
+        // The count stays locked while recycling, so that no statement starts meanwhile.
+        let mut statements = self.pool.statements.lock().unwrap();
+        *statements -= 1;
+        if *statements == 0 {
+            // A page left pinned keeps the temp space as it is, to be recycled next time.
+            let _ = self.pool.recycle();
+        }
+    }
+}
+
+impl<D: DiskManager> BufferPoolManager for BufferPoolManagerImpl<D> {
     fn get_pool_size(&self) -> usize {
         self.pages.len()
     }
 
+    fn stats(&self) -> BufferPoolStats {
+        let load = |counter: &AtomicU64| counter.load(atomic::Ordering::Relaxed);
+        BufferPoolStats {
+            hits: load(&self.stats.hits),
+            misses: load(&self.stats.misses),
+            reads: load(&self.stats.reads),
+            writes: load(&self.stats.writes),
+        }
+    }
+
     fn get_pages(&self) -> &[RwLock<Page>] {
         &self.pages
     }
 
     fn new_page(&self) -> anyhow::Result<Option<(PageId, &RwLock<Page>)>> {
-        let freed_frame = self.free_frame();
-        let frame_id = match freed_frame {
-            Some(frame_id) => frame_id,
-            None => {
-                // evict a page if possible
-                let Some(frame_id) = self.evict_page()? else {
-                    return Ok(None);
-                };
-
-                frame_id
+        let page_id = self.allocate_page()?;
+        match self.new_page_at(page_id) {
+            Ok(Some(page)) => Ok(Some((page_id, page))),
+            result => {
+                self.deallocate_page(page_id);
+                result.map(|_| None)
             }
-        };
-
-        let page_id = self.allocate_page();
-        let page = &self.pages[frame_id.0];
-        {
-            let mut page_guard = page.write().unwrap();
-            page_guard.allocate_page(page_id);
-            self.page_table.insert(page_id, frame_id);
-
-            drop(page_guard);
         }
-
-        Ok(Some((page_id, page)))
     }
 
     fn fetch_page(&self, page_id: PageId) -> anyhow::Result<Option<&RwLock<Page>>> {
-        if let Some(frame_id) = self.page_table.get(&page_id) {
-            let page = &self.pages[frame_id.0];
+        if let Some(page) = self.try_pin(page_id) {
+            Counters::add(&self.stats.hits);
             return Ok(Some(page));
         }
 
-        let frame_id = match self.free_frame() {
-            Some(frame_id) => frame_id,
-            None => {
-                // evict a page if possible
-                let Some(frame_id) = self.evict_page()? else {
-                    return Ok(None);
-                };
-
-                frame_id
-            }
+        let latch = self.latch.lock().unwrap();
+        // Another thread may have loaded the page while we were waiting for the latch.
+        if let Some(page) = self.try_pin(page_id) {
+            Counters::add(&self.stats.hits);
+            return Ok(Some(page));
+        }
+        let (_latch, Some(frame_id)) = self.take_frame(latch)? else {
+            Counters::add(&self.stats.misses);
+            return Ok(None);
         };
+        // Or while the latch was released to write back a victim.
+        if let Some(page) = self.try_pin(page_id) {
+            self.free_list.lock().unwrap().push_back(frame_id);
+            self.frames[frame_id.0].release(0);
+            Counters::add(&self.stats.hits);
+            return Ok(Some(page));
+        }
+        Counters::add(&self.stats.misses);
 
+        let page = &self.pages[frame_id.0];
+        let frame = &self.frames[frame_id.0];
+        if let Err(err) = self
+            .disk_manager
+            .read_page(page_id, page.write().unwrap().data_mut())
         {
-            let mut page_guard = self.pages[frame_id.0].write().unwrap();
-
-            self.disk_manager
-                .read_page(page_id, page_guard.data_mut())?;
-
-            page_guard.allocate_page(page_id);
-            self.page_table.insert(page_id, frame_id);
-
-            drop(page_guard);
+            self.free_list.lock().unwrap().push_back(frame_id);
+            frame.release(0);
+            return Err(err);
         }
+        Counters::add(&self.stats.reads);
+        frame.set_page_id(Some(page_id));
+        frame.release(1);
+        self.page_table.insert(page_id, frame_id);
 
-        Ok(Some(&self.pages[frame_id.0]))
+        Ok(Some(page))
     }
 
     fn unpin_page(&self, page_id: PageId, is_dirty: bool) -> bool {
-        let Some(frame_id) = self.page_table.get(&page_id) else {
+        let Some(frame_id) = self.frame_of(page_id) else {
             // the page is not in the page table
             return false;
         };
-        let mut page_guard = self.pages[frame_id.0].write().unwrap();
-        if !page_guard.is_pinned() {
+        let frame = &self.frames[frame_id.0];
+        if frame.page_id() != Some(page_id) || !frame.is_pinned() {
             return false;
         }
-        page_guard.unpin();
+        // Mark the page dirty before unpinning it, so that it cannot be evicted in between.
         if is_dirty {
-            page_guard.set_dirty();
+            frame.set_dirty(true);
         }
 
-        true
+        frame.unpin()
     }
 
     fn flush_page(&self, page_id: PageId) -> anyhow::Result<bool> {
-        let Some(frame_id) = self.page_table.get(&page_id) else {
+        let Some(frame_id) = self.frame_of(page_id) else {
             return Ok(false);
         };
-        let mut page_guard = self.pages[frame_id.0].write().unwrap();
-        self.flush_page_with_guard(page_id, &mut page_guard)?;
+        let page_guard = self.pages[frame_id.0].read().unwrap();
+        if page_guard.page_id() != Some(page_id) {
+            return Ok(false);
+        }
+        self.flush_page_with_guard(page_id, &page_guard)?;
 
         Ok(true)
     }
 
     fn flush_all_pages(&self) -> anyhow::Result<()> {
-        let guards = self.pages.iter().map(|page| page.write().unwrap());
-        for guard in guards {
-            let Some(page_id) = guard.page_id() else {
+        for (page, frame) in self.pages.iter().zip(self.frames.iter()) {
+            // Checked before latching, so that clean pages being written to are not waited for.
+            // A write guard marks its page dirty before it releases the latch.
+            if !frame.is_dirty() {
+                continue;
+            }
+            let page_guard = page.read().unwrap();
+            let Some(page_id) = page_guard.page_id() else {
                 continue;
             };
-            self.disk_manager.write_page(page_id, guard.data())?;
+            self.flush_page_with_guard(page_id, &page_guard)?;
         }
 
         Ok(())
     }
 
     fn delete_page(&self, page_id: PageId) -> bool {
-        let Some(frame_id) = self.page_table.get(&page_id) else {
-            return false;
-        };
-        let mut page_guard = self.pages[frame_id.0].write().unwrap();
-        if page_guard.is_pinned() {
-            return false;
-        }
-
-        let mut free_list = self.free_list.lock().unwrap();
-        free_list.push_back(*frame_id);
+        let latch = self.latch.lock().unwrap();
+        if let Some(frame_id) = self.frame_of(page_id) {
+            let frame = &self.frames[frame_id.0];
+            if !frame.try_reserve() {
+                return false;
+            }
 
-        self.page_table.remove(&page_id);
+            self.page_table.remove(&page_id);
+            frame.set_page_id(None);
+            frame.set_dirty(false);
+            frame.release(0);
+            self.free_list.lock().unwrap().push_back(frame_id);
+        }
+        drop(latch);
         self.deallocate_page(page_id);
-        page_guard.deallocate_page();
 
-        drop(page_guard);
         true
     }
+
+    fn log_manager(&self) -> Option<&LogManager> {
+        self.log_manager.as_deref()
+    }
 }
 
-impl Drop for BufferPoolManagerImpl<'_> {
+impl<D: DiskManager> Drop for BufferPoolManagerImpl<D> {
     fn drop(&mut self) {
         self.flush_all_pages().unwrap();
     }
@@ -272,7 +734,15 @@ impl Drop for BufferPoolManagerImpl<'_> {
 
 #[cfg(test)]
 mod tests {
-    use crate::storage::page::page::DEFAULT_PAGE_SIZE;
+    use crate::{
+        recovery::log_record::PageDelta,
+        storage::{
+            index::b_plus_tree::{BPlusTree, BulkLoadConfig},
+            page::page::{DEFAULT_PAGE_SIZE, PAGE_LSN_OFFSET},
+        },
+    };
+
+    use std::sync::mpsc;
 
     use super::*;
 
@@ -282,7 +752,7 @@ mod tests {
         let filename = tempdir.path().join("test.db");
         const BUFFER_POOL_SIZE: usize = 10;
         let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, filename).unwrap();
-        let buffer_pool_manager = BufferPoolManagerImpl::new(BUFFER_POOL_SIZE, &disk_manager);
+        let buffer_pool_manager = BufferPoolManagerImpl::new(BUFFER_POOL_SIZE, disk_manager);
 
         let ret = buffer_pool_manager.new_page().unwrap();
 
@@ -381,7 +851,7 @@ mod tests {
         let filename = tempdir.path().join("test.db");
         const BUFFER_POOL_SIZE: usize = 10;
         let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, filename).unwrap();
-        let bpm = BufferPoolManagerImpl::new(BUFFER_POOL_SIZE, &disk_manager);
+        let bpm = BufferPoolManagerImpl::new(BUFFER_POOL_SIZE, disk_manager);
 
         // The buffer pool is empty. We should be able to create a new page.
         let (page_id0, page0) = bpm
@@ -466,4 +936,355 @@ mod tests {
             .expect("We should be able to fetch page 0 after unpinning one page.");
         assert_eq!(data, &page0.read().unwrap().data()[0..data.len()]);
     }
+
+    #[test]
+    fn test_page_guard_unpins_on_drop() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, filename).unwrap();
+        let bpm = BufferPoolManagerImpl::new(1, disk_manager);
+
+        let page_id0 = {
+            let mut guard = bpm.new_page_write().unwrap();
+            guard.data_mut()[0] = 42;
+            guard.page_id()
+        };
+        // The guard has been dropped, so its frame can be reused.
+        let page_id1 = bpm.new_page_write().unwrap().page_id();
+        assert_ne!(page_id0, page_id1);
+
+        // The modification reached the disk when page 0 was evicted.
+        let guard = bpm.fetch_page_read(page_id0).unwrap();
+        assert_eq!(guard.data()[0], 42);
+        assert!(guard.is_pinned());
+        // The only frame is pinned by the read guard.
+        assert!(bpm.fetch_page_read(page_id1).is_err());
+        drop(guard);
+        assert!(bpm.fetch_page_read(page_id1).is_ok());
+    }
+
+    #[test]
+    fn test_temp_pool_keeps_spills_out_of_the_database() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let temp_disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, TempDiskManager::path_for(&filename)).unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+
+        let entries = || (0..20000u64).rev().map(|key| (key, key));
+        let config = BulkLoadConfig::new().with_sort_memory(1000);
+        let tree = BPlusTree::<u64, u64, _>::create(&bpm).unwrap();
+        tree.bulk_load_with_temp(&temp, entries(), config).unwrap();
+        assert_eq!(tree.get(&1234).unwrap(), Some(1234));
+        bpm.flush_all_pages().unwrap();
+        let db_pages = bpm.disk_manager().num_pages();
+        assert!(temp.disk_manager().num_pages() > 4);
+
+        // Spilling to the database file instead leaves it larger by the pages of the runs.
+        let spilling_disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("spill.db")).unwrap();
+        let spilling_bpm = BufferPoolManagerImpl::new(4, spilling_disk_manager);
+        let tree = BPlusTree::<u64, u64, _>::create(&spilling_bpm).unwrap();
+        tree.bulk_load(entries(), config).unwrap();
+        spilling_bpm.flush_all_pages().unwrap();
+        assert!(spilling_bpm.disk_manager().num_pages() > db_pages + 4);
+
+        // The temp space starts over once the last statement using it is done, without writing
+        // anything back.
+        let statement = temp.begin_statement();
+        let other = temp.begin_statement();
+        drop(temp.new_page_write().unwrap());
+        drop(statement);
+        assert!(temp.disk_manager().num_pages() > 4);
+        drop(other);
+        assert_eq!(temp.disk_manager().num_pages(), 0);
+        // The pages the runs freed are forgotten as well.
+        for i in 0..2 {
+            let (page_id, _) = temp.new_page().unwrap().unwrap();
+            assert_eq!(page_id, PageId::new(i));
+            assert!(temp.unpin_page(page_id, false));
+        }
+        assert_eq!(bpm.disk_manager().num_pages(), db_pages);
+    }
+
+    #[test]
+    fn test_deleted_pages_are_reused() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(2, disk_manager);
+        let page_ids: Vec<_> = (0..4)
+            .map(|_| bpm.new_page_write().unwrap().page_id())
+            .collect();
+
+        // A pinned page is not deleted, an evicted one is freed all the same.
+        let guard = bpm.fetch_page_read(page_ids[2]).unwrap();
+        assert!(!bpm.delete_page(page_ids[2]));
+        drop(guard);
+        assert!(bpm.delete_page(page_ids[2]));
+        assert!(bpm.delete_page(page_ids[0]));
+        // Deleting twice or deleting a page that was never allocated frees nothing more.
+        assert!(bpm.delete_page(page_ids[0]));
+        assert!(bpm.delete_page(PageId::new(100)));
+
+        let reused: Vec<_> = (0..3)
+            .map(|_| bpm.new_page_write().unwrap().page_id())
+            .collect();
+        assert_eq!(reused, [page_ids[0], page_ids[2], PageId::new(4)]);
+    }
+
+    #[test]
+    fn test_free_page_map_survives_reopen() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let open = || {
+            let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+            BufferPoolManagerImpl::new(4, disk_manager)
+                .with_free_page_map(PageId::new(0), 64)
+                .unwrap()
+        };
+
+        let bpm = open();
+        // The root page, then pages to free.
+        let page_ids: Vec<_> = (0..10)
+            .map(|_| bpm.new_page_write().unwrap().page_id())
+            .collect();
+        for i in [8, 3, 5] {
+            assert!(bpm.delete_page(page_ids[i]));
+        }
+        // The bitmap page comes from the end of the file.
+        let bitmap_page_id = PageId::new(10);
+        assert_eq!(
+            bytes::read_u64(bpm.fetch_page_read(page_ids[0]).unwrap().data(), 64),
+            bitmap_page_id.to_u64()
+        );
+        drop(bpm);
+
+        let bpm = open();
+        let reused = bpm.new_page_write().unwrap().page_id();
+        assert_eq!(reused, page_ids[3]);
+        drop(bpm);
+
+        // A reused page is no longer free once reopened.
+        let bpm = open();
+        let reused: Vec<_> = (0..3)
+            .map(|_| bpm.new_page_write().unwrap().page_id())
+            .collect();
+        assert_eq!(reused, [page_ids[5], page_ids[8], PageId::new(11)]);
+    }
+
+    #[test]
+    fn test_log_is_flushed_before_pages() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+        let log_manager = Arc::new(LogManager::open(LogManager::path_for(&filename)).unwrap());
+        let bpm = BufferPoolManagerImpl::new(1, disk_manager).with_log_manager(log_manager.clone());
+
+        // A write guard logs the change made through it and stamps the page with its LSN.
+        let page_id0 = {
+            let mut guard = bpm.new_page_write().unwrap();
+            guard.data_mut()[100] = 1;
+            guard.page_id()
+        };
+        let lsn = log_manager.last_lsn();
+        assert_eq!(bpm.fetch_page_read(page_id0).unwrap().lsn(), lsn);
+        // A guard that changes nothing logs nothing.
+        bpm.fetch_page_write(page_id0).unwrap().data_mut()[100] = 1;
+        assert_eq!(log_manager.last_lsn(), lsn);
+
+        // Flushing a page flushes the log up to the LSN of the page.
+        assert_eq!(log_manager.flushed_lsn(), 0);
+        assert!(bpm.flush_page(page_id0).unwrap());
+        assert!(log_manager.flushed_lsn() >= lsn);
+        let records = log_manager.records().unwrap();
+        assert_eq!(records.last().unwrap().lsn, lsn);
+        let delta = PageDelta::decode(&records.last().unwrap().payload).unwrap();
+        assert_eq!(delta.page_id, page_id0);
+        assert_eq!(delta.ranges, [(100, vec![1])]);
+
+        // A page whose changes are already durable does not sync the log again.
+        bpm.fetch_page_write(page_id0).unwrap().data_mut()[101] = 1;
+        log_manager.flush_all().unwrap();
+        let syncs = log_manager.stats().syncs;
+        bpm.flush_page(page_id0).unwrap();
+        assert_eq!(log_manager.stats().syncs, syncs);
+
+        // Evicting a dirty page flushes the log as well.
+        bpm.new_page_write().unwrap().data_mut()[100] = 2;
+        let lsn = log_manager.last_lsn();
+        assert!(log_manager.flushed_lsn() < lsn);
+        bpm.fetch_page_read(page_id0).unwrap();
+        assert_eq!(log_manager.flushed_lsn(), lsn);
+
+        // A page stamped past the end of the log cannot be written.
+        let page = bpm.fetch_page(page_id0).unwrap().unwrap();
+        page.write().unwrap().set_lsn(lsn + 1);
+        bpm.unpin_page(page_id0, true);
+        assert!(bpm.flush_page(page_id0).is_err());
+        page.write().unwrap().set_lsn(lsn);
+    }
+
+    #[test]
+    fn test_write_guard_logs_each_change_once() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+        let log_manager = Arc::new(LogManager::open(LogManager::path_for(&filename)).unwrap());
+        let bpm = BufferPoolManagerImpl::new(1, disk_manager).with_log_manager(log_manager.clone());
+        let page_id = bpm.new_page_write().unwrap().page_id();
+        let last_delta = || {
+            log_manager.flush_all().unwrap();
+            let records = log_manager.records().unwrap();
+            PageDelta::decode(&records.last().unwrap().payload).unwrap()
+        };
+
+        // Ranges written in place are logged as they are, and the page copied on the first
+        // mutable borrow after them covers the rest, however often it is borrowed.
+        {
+            let mut guard = bpm.fetch_page_write(page_id).unwrap();
+            guard.write_at(100, &[1, 2]);
+            guard.write_at(200, &[3]);
+            guard.data_mut()[100] = 4;
+            guard.data_mut()[300] = 5;
+            guard.write_at(400, &[6]);
+        }
+        let delta = last_delta();
+        assert_eq!(
+            delta.ranges,
+            [
+                (100, vec![1, 2]),
+                (200, vec![3]),
+                (100, vec![4]),
+                (300, vec![5]),
+                (400, vec![6])
+            ]
+        );
+        let mut data = vec![0; DEFAULT_PAGE_SIZE];
+        delta.apply(&mut data);
+        assert_eq!(
+            data[PAGE_LSN_OFFSET + 8..],
+            bpm.fetch_page_read(page_id).unwrap().data()[PAGE_LSN_OFFSET + 8..]
+        );
+
+        // Writing in place alone logs just the range.
+        bpm.fetch_page_write(page_id)
+            .unwrap()
+            .write_at(500, &[7, 8, 9]);
+        assert_eq!(last_delta().ranges, [(500, vec![7, 8, 9])]);
+    }
+
+    /// A disk manager whose first write waits until the test lets it go on.
+    struct GatedDiskManager {
+        inner: LimeBaseDiskManager,
+        gate: Mutex<Option<(mpsc::Sender<()>, mpsc::Receiver<()>)>>,
+    }
+
+    impl DiskManager for GatedDiskManager {
+        fn new(page_size: usize, filename: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+            Ok(Self {
+                inner: LimeBaseDiskManager::new(page_size, filename)?,
+                gate: Mutex::new(None),
+            })
+        }
+
+        fn page_size(&self) -> usize {
+            self.inner.page_size()
+        }
+
+        fn num_pages(&self) -> usize {
+            self.inner.num_pages()
+        }
+
+        fn read_page(&self, page_id: PageId, data: &mut [u8]) -> anyhow::Result<()> {
+            self.inner.read_page(page_id, data)
+        }
+
+        fn write_page(&self, page_id: PageId, data: &[u8]) -> anyhow::Result<()> {
+            let gate = self.gate.lock().unwrap().take();
+            if let Some((started, resume)) = gate {
+                started.send(()).unwrap();
+                resume.recv().unwrap();
+            }
+            self.inner.write_page(page_id, data)
+        }
+
+        fn sync(&self) -> anyhow::Result<()> {
+            self.inner.sync()
+        }
+    }
+
+    #[test]
+    fn test_eviction_writes_without_the_latch() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            GatedDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let (started_tx, started) = mpsc::channel();
+        let (resume, resume_rx) = mpsc::channel();
+        *disk_manager.gate.lock().unwrap() = Some((started_tx, resume_rx));
+        let bpm = BufferPoolManagerImpl::new(1, disk_manager);
+        let page_id0 = {
+            let mut guard = bpm.new_page_write().unwrap();
+            guard.data_mut()[100] = 1;
+            guard.page_id()
+        };
+
+        std::thread::scope(|scope| {
+            // Evicts page 0, whose write waits.
+            let evicting = scope.spawn(|| bpm.new_page_write().unwrap().page_id());
+            started.recv().unwrap();
+            // Meanwhile the pool can still be used: page 0 is still resident, and deleting a
+            // page takes the latch.
+            assert_eq!(bpm.fetch_page_read(page_id0).unwrap().data()[100], 1);
+            assert!(bpm.delete_page(PageId::new(7)));
+            resume.send(()).unwrap();
+            assert_ne!(evicting.join().unwrap(), page_id0);
+        });
+        assert_eq!(bpm.fetch_page_read(page_id0).unwrap().data()[100], 1);
+    }
+
+    #[test]
+    fn test_stats() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(2, disk_manager);
+        let mut page_ids = Vec::new();
+        for _ in 0..3 {
+            let guard = bpm.new_page_write().unwrap();
+            page_ids.push(guard.page_id());
+        }
+        // The third page evicted the first, which had to be written.
+        let before = bpm.stats();
+        assert_eq!(
+            before,
+            BufferPoolStats {
+                writes: 1,
+                ..BufferPoolStats::default()
+            }
+        );
+
+        drop(bpm.fetch_page_read(page_ids[2]).unwrap());
+        drop(bpm.fetch_page_read(page_ids[0]).unwrap());
+        assert_eq!(
+            bpm.stats().since(before),
+            BufferPoolStats {
+                hits: 1,
+                misses: 1,
+                reads: 1,
+                writes: 1,
+            }
+        );
+
+        // Only dirty pages are written: page 0 was just read back, and page 1, which was never
+        // written, is clean once flushed.
+        let before = bpm.stats();
+        bpm.flush_all_pages().unwrap();
+        assert_eq!(bpm.stats().since(before).writes, 1);
+        assert!(bpm.flush_page(page_ids[1]).unwrap());
+        bpm.flush_all_pages().unwrap();
+        assert_eq!(bpm.stats().since(before).writes, 1);
+    }
 }
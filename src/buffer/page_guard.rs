@@ -0,0 +1,162 @@
+//! RAII guards that hold both the latch and the pin of a buffer-pool page.
+//!
+//! Dropping a guard first releases the latch and then unpins the page, so that layers built on
+//! top of the buffer pool cannot forget to unpin a page or unpin it while still latching it.
+//!
+//! If the buffer pool has a log, a write guard also logs the change made through it as a
+//! [`PageDelta`] before it releases the latch, and stamps the page with the LSN of the record.
+//! The page is copied on its first mutable borrow to be diffed on drop; callers that know
+//! which bytes they change use [`WritePageGuard::write_at`] instead, which logs just those.
+//! The guard of a new page always logs it, even unchanged, so that redo zeroes whatever page
+//! the file held under its id before.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager, recovery::log_record::PageDelta, Page, PageId,
+};
+
+pub struct ReadPageGuard<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    page_id: PageId,
+    guard: Option<RwLockReadGuard<'a, Page>>,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> ReadPageGuard<'a, B> {
+    pub(crate) fn new(bpm: &'a B, page_id: PageId, guard: RwLockReadGuard<'a, Page>) -> Self {
+        Self {
+            bpm,
+            page_id,
+            guard: Some(guard),
+        }
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Deref for ReadPageGuard<'_, B> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Drop for ReadPageGuard<'_, B> {
+    fn drop(&mut self) {
+        self.guard.take();
+        self.bpm.unpin_page(self.page_id, false);
+    }
+}
+
+/// A latched and pinned page that is reported dirty on drop once it has been mutably borrowed.
+pub struct WritePageGuard<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    page_id: PageId,
+    guard: Option<RwLockWriteGuard<'a, Page>>,
+    is_dirty: bool,
+    /// The data of the page before it was first mutably borrowed, if its changes are logged.
+    before: Option<Box<[u8]>>,
+    /// The ranges written with `write_at` before `before` was taken, in order, if the changes
+    /// are logged.
+    written: Vec<(usize, Vec<u8>)>,
+    /// Whether the page was just allocated.
+    new_page: bool,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> WritePageGuard<'a, B> {
+    pub(crate) fn new(bpm: &'a B, page_id: PageId, guard: RwLockWriteGuard<'a, Page>) -> Self {
+        Self {
+            bpm,
+            page_id,
+            guard: Some(guard),
+            is_dirty: false,
+            before: None,
+            written: Vec::new(),
+            new_page: false,
+        }
+    }
+
+    /// The guard of a page just allocated and zeroed.
+    pub(crate) fn new_page(bpm: &'a B, page_id: PageId, guard: RwLockWriteGuard<'a, Page>) -> Self {
+        let mut guard = Self::new(bpm, page_id, guard);
+        guard.new_page = true;
+        guard
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    /// Write `bytes` at `offset` in the page and log that range, without copying the page.
+    pub fn write_at(&mut self, offset: usize, bytes: &[u8]) {
+        let page = self.guard.as_mut().unwrap();
+        page.data_mut()[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.is_dirty = true;
+        // Once the page is copied, the diff covers the range.
+        if self.before.is_none() && self.bpm.log_manager().is_some() {
+            self.written.push((offset, bytes.to_vec()));
+        }
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Deref for WritePageGuard<'_, B> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> DerefMut for WritePageGuard<'_, B> {
+    fn deref_mut(&mut self) -> &mut Page {
+        let page = self.guard.as_mut().unwrap();
+        if self.before.is_none() && self.bpm.log_manager().is_some() {
+            self.before = Some(page.data().into());
+        }
+        self.is_dirty = true;
+        page
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Drop for WritePageGuard<'_, B> {
+    fn drop(&mut self) {
+        if let Some(log_manager) = self.bpm.log_manager() {
+            let page = self.guard.as_mut().unwrap();
+            // The ranges written before the copy come first, as the diff may overwrite them.
+            let mut ranges = std::mem::take(&mut self.written);
+            if let Some(delta) = self
+                .before
+                .take()
+                .and_then(|before| PageDelta::diff(self.page_id, &before, page.data()))
+            {
+                ranges.extend(delta.ranges);
+            }
+            let delta = (self.new_page || !ranges.is_empty()).then_some(PageDelta {
+                page_id: self.page_id,
+                new_page: self.new_page,
+                ranges,
+            });
+            if let Some(delta) = delta {
+                // Buffered, since a drop cannot fail: the buffer pool flushes the log up to the
+                // LSN before it writes the page.
+                let lsn = log_manager
+                    .append_buffered(&delta.encode())
+                    .expect("a page delta fits in a log record");
+                page.set_lsn(lsn);
+            }
+        }
+        // Marked dirty before the latch is released, so that a flush that skips clean pages
+        // cannot write the page from before the change and then miss the change.
+        if self.is_dirty {
+            self.guard.as_ref().unwrap().frame_state().set_dirty(true);
+        }
+        self.guard.take();
+        self.bpm.unpin_page(self.page_id, self.is_dirty);
+    }
+}
@@ -0,0 +1,5 @@
+#[allow(clippy::module_inception)]
+pub mod catalog;
+pub mod schema;
+pub mod statistics;
+pub mod table_index;
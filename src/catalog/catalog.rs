@@ -0,0 +1,1256 @@
+//! The system catalog: the tables and indexes of a database, stored in the database itself.
+//!
+//! Metadata lives in four system tables, which are ordinary [`TableHeap`]s whose rows are
+//! encoded like user tuples:
+//!
+//! - `lime_tables(oid, name, heap_page_id)`
+//! - `lime_columns(table_oid, position, name, type_id, type_length, type_scale, nullable)`
+//! - `lime_indexes(oid, name, table_oid, column_positions, kind, is_unique, header_page_id)`,
+//!   with the positions of the indexed columns [encoded](super::statistics::encode_values) as
+//!   BYTEA
+//! - `lime_statistics(table_oid, column_position, row_count, null_fraction, distinct_count,
+//!   most_common_values, most_common_frequencies, histogram_bounds)`, the
+//!   [statistics](super::statistics) of the last `ANALYZE`, with the lists of values
+//!   [encoded](super::statistics::encode_values) as BYTEA
+//!
+//! The system tables describe themselves, so they can be looked up like any other table. They
+//! are bootstrapped right after the file header, the first page of the file, which records
+//! where they start, the next free OID and where the free pages of the file are listed:
+//!
+//! | offset | size | field                            |
+//! |--------|------|----------------------------------|
+//! | 0      | 16   | common page header               |
+//! | 16     | 4    | magic                            |
+//! | 20     | 4    | format version                   |
+//! | 24     | 8    | next OID                         |
+//! | 32     | 8    | `lime_tables` header page id     |
+//! | 40     | 8    | `lime_columns` header page id    |
+//! | 48     | 8    | `lime_indexes` header page id    |
+//! | 56     | 8    | `lime_statistics` header page id |
+//! | 64     | 8    | first free-page bitmap page id   |
+//!
+//! Every row is loaded into memory on open. Changes are written through the buffer pool
+//! before the in-memory maps are updated, and are serialized by the lock on those maps.
+//!
+//! The pages of a dropped table or index are freed once nothing reads them anymore. Plans and
+//! executors hold the [`TableInfo`] and [`IndexInfo`] of what they read, so the catalog keeps
+//! the info of what it drops and frees the pages once it holds the last reference.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock, RwLockWriteGuard},
+};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    storage::{
+        index::{b_plus_tree::BulkLoadConfig, index::IndexKind},
+        page::{
+            bytes,
+            page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+        },
+        table::{
+            free_space_map::SharedFsm,
+            record_id::RecordId,
+            table_heap::TableHeap,
+            tuple::{Tuple, TupleRef},
+        },
+    },
+    types::{data_type::DataType, value::Value},
+    PageId,
+};
+
+use super::{
+    schema::{Column, Schema},
+    statistics::{decode_values, encode_values, ColumnStatistics, TableStatistics},
+    table_index::{check_key_types, TableIndex},
+};
+
+/// Identifies a table or an index for its whole life, unlike its name.
+pub type Oid = u64;
+
+pub const LIME_TABLES_OID: Oid = 1;
+pub const LIME_COLUMNS_OID: Oid = 2;
+pub const LIME_INDEXES_OID: Oid = 3;
+pub const LIME_STATISTICS_OID: Oid = 4;
+/// OIDs below this are reserved for system tables.
+const FIRST_USER_OID: Oid = 100;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"LIME");
+const FORMAT_VERSION: u32 = 4;
+
+const MAGIC_OFFSET: usize = COMMON_HEADER_SIZE;
+const VERSION_OFFSET: usize = MAGIC_OFFSET + 4;
+const NEXT_OID_OFFSET: usize = VERSION_OFFSET + 4;
+const TABLES_PAGE_ID_OFFSET: usize = NEXT_OID_OFFSET + 8;
+const COLUMNS_PAGE_ID_OFFSET: usize = TABLES_PAGE_ID_OFFSET + 8;
+const INDEXES_PAGE_ID_OFFSET: usize = COLUMNS_PAGE_ID_OFFSET + 8;
+const STATISTICS_PAGE_ID_OFFSET: usize = INDEXES_PAGE_ID_OFFSET + 8;
+/// The offset in the file header of the id of the first page of the free-page map of the file,
+/// see `BufferPoolManagerImpl::with_free_page_map`.
+pub const FREE_PAGE_MAP_OFFSET: usize = STATISTICS_PAGE_ID_OFFSET + 8;
+
+/// The page every database file starts with.
+pub fn file_header_page_id() -> PageId {
+    PageId::new(0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub oid: Oid,
+    pub name: String,
+    pub schema: Schema,
+    /// The header page of the table's heap.
+    pub heap_page_id: PageId,
+}
+
+impl TableInfo {
+    /// Whether the table is one of the catalog's own tables, which users cannot modify.
+    pub fn is_system(&self) -> bool {
+        self.oid < FIRST_USER_OID
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub oid: Oid,
+    pub name: String,
+    pub table_oid: Oid,
+    /// The positions of the indexed columns in the table's schema, in key order.
+    pub columns: Vec<usize>,
+    pub kind: IndexKind,
+    pub unique: bool,
+    pub header_page_id: PageId,
+}
+
+#[derive(Default)]
+struct Entries {
+    tables: HashMap<Oid, Arc<TableInfo>>,
+    indexes: HashMap<Oid, Arc<IndexInfo>>,
+    /// Lowercased names, since names are compared case-insensitively like column names.
+    table_names: HashMap<String, Oid>,
+    index_names: HashMap<String, Oid>,
+    statistics: HashMap<Oid, Arc<TableStatistics>>,
+}
+
+impl Entries {
+    fn add_table(&mut self, info: TableInfo) -> Arc<TableInfo> {
+        let info = Arc::new(info);
+        self.table_names
+            .insert(info.name.to_ascii_lowercase(), info.oid);
+        self.tables.insert(info.oid, info.clone());
+        info
+    }
+
+    fn add_index(&mut self, info: IndexInfo) -> Arc<IndexInfo> {
+        let info = Arc::new(info);
+        self.index_names
+            .insert(info.name.to_ascii_lowercase(), info.oid);
+        self.indexes.insert(info.oid, info.clone());
+        info
+    }
+
+    fn name_is_taken(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.table_names.contains_key(&name) || self.index_names.contains_key(&name)
+    }
+}
+
+/// What every [`Catalog`] over the same database shares: the rows of the system tables and the
+/// free-space maps of the heaps. It does not borrow the buffer pool, so that it can be kept
+/// next to it, and a catalog is opened on it with [`Catalog::from_shared`].
+pub struct SharedCatalog {
+    /// The header pages of `lime_tables`, `lime_columns`, `lime_indexes` and `lime_statistics`.
+    system_heaps: [PageId; 4],
+    entries: RwLock<Entries>,
+    /// The free-space map of every heap opened so far by its header page, shared by all the
+    /// handles on the heap so that their inserts do not overwrite each other's entries.
+    fsms: Mutex<HashMap<PageId, SharedFsm>>,
+    /// The tables and indexes dropped so far whose pages are not freed yet.
+    dropped: Mutex<Vec<Dropped>>,
+}
+
+/// A dropped table or index, whose pages are freed once nothing reads them anymore.
+enum Dropped {
+    Table(Arc<TableInfo>),
+    /// An index, with the types of its key since its table may be gone.
+    Index(Arc<IndexInfo>, Vec<DataType>),
+}
+
+impl Dropped {
+    /// Whether the catalog holds the last reference to the info, which nobody can clone
+    /// anymore since it was removed from the catalog.
+    fn is_unused(&self) -> bool {
+        match self {
+            Dropped::Table(info) => Arc::strong_count(info) == 1,
+            Dropped::Index(info, _) => Arc::strong_count(info) == 1,
+        }
+    }
+}
+
+pub struct Catalog<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    tables: TableHeap<'a, B>,
+    columns: TableHeap<'a, B>,
+    indexes: TableHeap<'a, B>,
+    statistics: TableHeap<'a, B>,
+    shared: Arc<SharedCatalog>,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> Catalog<'a, B> {
+    /// Bootstrap the catalog of a new database, whose buffer pool has not allocated any page.
+    pub fn create(bpm: &'a B) -> anyhow::Result<Self> {
+        let mut header_guard = bpm.new_page_write()?;
+        if header_guard.page_id() != file_header_page_id() {
+            anyhow::bail!("cannot bootstrap a catalog into a database that already has pages");
+        }
+        let tables = TableHeap::create(bpm)?;
+        let columns = TableHeap::create(bpm)?;
+        let indexes = TableHeap::create(bpm)?;
+        let statistics = TableHeap::create(bpm)?;
+
+        let header = header_guard.data_mut();
+        bytes::write_u32(header, PAGE_TYPE_OFFSET, PageType::FileHeader as u32);
+        bytes::write_u32(header, MAGIC_OFFSET, MAGIC);
+        bytes::write_u32(header, VERSION_OFFSET, FORMAT_VERSION);
+        bytes::write_u64(header, NEXT_OID_OFFSET, FIRST_USER_OID);
+        for (offset, heap) in [
+            (TABLES_PAGE_ID_OFFSET, &tables),
+            (COLUMNS_PAGE_ID_OFFSET, &columns),
+            (INDEXES_PAGE_ID_OFFSET, &indexes),
+            (STATISTICS_PAGE_ID_OFFSET, &statistics),
+        ] {
+            bytes::write_u64(header, offset, heap.header_page_id().to_u64());
+        }
+        drop(header_guard);
+
+        let catalog = Self::new(bpm, [tables, columns, indexes, statistics]);
+        let mut entries = catalog.shared.entries.write().unwrap();
+        for (oid, name, heap) in [
+            (LIME_TABLES_OID, "lime_tables", &catalog.tables),
+            (LIME_COLUMNS_OID, "lime_columns", &catalog.columns),
+            (LIME_INDEXES_OID, "lime_indexes", &catalog.indexes),
+            (LIME_STATISTICS_OID, "lime_statistics", &catalog.statistics),
+        ] {
+            let info = TableInfo {
+                oid,
+                name: name.to_string(),
+                schema: system_schema(oid),
+                heap_page_id: heap.header_page_id(),
+            };
+            catalog.insert_table_rows(&info)?;
+            entries.add_table(info);
+        }
+        drop(entries);
+
+        Ok(catalog)
+    }
+
+    fn new(bpm: &'a B, system_heaps: [TableHeap<'a, B>; 4]) -> Self {
+        let shared = SharedCatalog {
+            system_heaps: system_heaps.each_ref().map(TableHeap::header_page_id),
+            entries: RwLock::new(Entries::default()),
+            fsms: Mutex::new(
+                system_heaps
+                    .iter()
+                    .map(|heap| (heap.header_page_id(), heap.free_space_map().shared()))
+                    .collect(),
+            ),
+            dropped: Mutex::new(Vec::new()),
+        };
+        let [tables, columns, indexes, statistics] = system_heaps;
+        Self {
+            bpm,
+            tables,
+            columns,
+            indexes,
+            statistics,
+            shared: Arc::new(shared),
+        }
+    }
+
+    /// Open a catalog on `shared`, from [`Catalog::shared`] of a catalog over `bpm`. Nothing
+    /// is read from disk, and changes made through either catalog are seen by both.
+    pub fn from_shared(bpm: &'a B, shared: Arc<SharedCatalog>) -> Self {
+        let fsms = shared.fsms.lock().unwrap();
+        let [tables, columns, indexes, statistics] = shared
+            .system_heaps
+            .map(|page_id| TableHeap::open_with_fsm(bpm, page_id, fsms[&page_id].clone()));
+        drop(fsms);
+        Self {
+            bpm,
+            tables,
+            columns,
+            indexes,
+            statistics,
+            shared,
+        }
+    }
+
+    /// The state shared by the catalogs opened on it with [`Catalog::from_shared`].
+    pub fn shared(&self) -> Arc<SharedCatalog> {
+        self.shared.clone()
+    }
+
+    /// Load the catalog of an existing database.
+    pub fn open(bpm: &'a B) -> anyhow::Result<Self> {
+        let guard = bpm.fetch_page_read(file_header_page_id())?;
+        let header = guard.data();
+        if PageType::of(header) != Some(PageType::FileHeader)
+            || bytes::read_u32(header, MAGIC_OFFSET) != MAGIC
+        {
+            anyhow::bail!("not a limebase database file");
+        }
+        let version = bytes::read_u32(header, VERSION_OFFSET);
+        if version != FORMAT_VERSION {
+            anyhow::bail!("unsupported database format version {version}");
+        }
+        let heap_at = |offset| PageId::from_u64(bytes::read_u64(header, offset));
+        let catalog = Self::new(
+            bpm,
+            [
+                TableHeap::open(bpm, heap_at(TABLES_PAGE_ID_OFFSET))?,
+                TableHeap::open(bpm, heap_at(COLUMNS_PAGE_ID_OFFSET))?,
+                TableHeap::open(bpm, heap_at(INDEXES_PAGE_ID_OFFSET))?,
+                TableHeap::open(bpm, heap_at(STATISTICS_PAGE_ID_OFFSET))?,
+            ],
+        );
+        drop(guard);
+        catalog.load()?;
+
+        Ok(catalog)
+    }
+
+    /// Read every row of the system tables into memory.
+    fn load(&self) -> anyhow::Result<()> {
+        let mut columns: HashMap<Oid, Vec<(i64, Column)>> = HashMap::new();
+        let columns_schema = system_schema(LIME_COLUMNS_OID);
+        for row in self.columns.iter()? {
+            let (_, data) = row?;
+            let values = Tuple::from_bytes(&columns_schema, &data)?.into_values();
+            let (table_oid, position) = (int(&values[0])? as Oid, int(&values[1])?);
+            let data_type = decode_type(&values[3..6])?;
+            let mut column = Column::new(text(&values[2])?, data_type);
+            column.nullable = values[6].as_bool().unwrap_or(true);
+            columns
+                .entry(table_oid)
+                .or_default()
+                .push((position, column));
+        }
+
+        let mut entries = self.shared.entries.write().unwrap();
+        let tables_schema = system_schema(LIME_TABLES_OID);
+        for row in self.tables.iter()? {
+            let (_, data) = row?;
+            let values = Tuple::from_bytes(&tables_schema, &data)?.into_values();
+            let oid = int(&values[0])? as Oid;
+            let mut table_columns = columns.remove(&oid).unwrap_or_default();
+            table_columns.sort_by_key(|(position, _)| *position);
+            entries.add_table(TableInfo {
+                oid,
+                name: text(&values[1])?.to_string(),
+                schema: Schema::new(table_columns.into_iter().map(|(_, c)| c).collect()),
+                heap_page_id: PageId::from_u64(int(&values[2])? as u64),
+            });
+        }
+
+        let indexes_schema = system_schema(LIME_INDEXES_OID);
+        for row in self.indexes.iter()? {
+            let (_, data) = row?;
+            let values = Tuple::from_bytes(&indexes_schema, &data)?.into_values();
+            entries.add_index(IndexInfo {
+                oid: int(&values[0])? as Oid,
+                name: text(&values[1])?.to_string(),
+                table_oid: int(&values[2])? as Oid,
+                columns: decode_values(bytea(&values[3])?, DataType::Int32)?
+                    .iter()
+                    .map(|position| Ok(int(position)? as usize))
+                    .collect::<anyhow::Result<_>>()?,
+                kind: decode_index_kind(int(&values[4])?)?,
+                unique: values[5].as_bool().unwrap_or(false),
+                header_page_id: PageId::from_u64(int(&values[6])? as u64),
+            });
+        }
+
+        let statistics_schema = system_schema(LIME_STATISTICS_OID);
+        let mut statistics: HashMap<Oid, TableStatistics> = HashMap::new();
+        for row in self.statistics.iter()? {
+            let (_, data) = row?;
+            let values = Tuple::from_bytes(&statistics_schema, &data)?.into_values();
+            let table_oid = int(&values[0])? as Oid;
+            let Some(table) = entries.tables.get(&table_oid) else {
+                continue;
+            };
+            let position = int(&values[1])? as usize;
+            let data_type = table.schema.column(position).data_type;
+            let table_statistics = statistics.entry(table_oid).or_insert(TableStatistics {
+                row_count: int(&values[2])? as u64,
+                columns: Vec::new(),
+            });
+            let column = ColumnStatistics {
+                null_fraction: float(&values[3])?,
+                distinct_count: int(&values[4])? as u64,
+                most_common_values: decode_values(bytea(&values[5])?, data_type)?,
+                most_common_frequencies: decode_values(bytea(&values[6])?, DataType::Float64)?
+                    .iter()
+                    .map(float)
+                    .collect::<anyhow::Result<_>>()?,
+                histogram_bounds: decode_values(bytea(&values[7])?, data_type)?,
+            };
+            table_statistics.columns.push(column);
+            if table_statistics.columns.len() != position + 1 {
+                anyhow::bail!("statistics of table {table_oid} are out of order in the catalog");
+            }
+        }
+        for (oid, table_statistics) in statistics {
+            entries.statistics.insert(oid, Arc::new(table_statistics));
+        }
+
+        Ok(())
+    }
+
+    /// Take the next OID from the file header.
+    fn allocate_oid(&self) -> anyhow::Result<Oid> {
+        let mut guard = self.bpm.fetch_page_write(file_header_page_id())?;
+        let oid = bytes::read_u64(guard.data(), NEXT_OID_OFFSET);
+        guard.write_at(NEXT_OID_OFFSET, &(oid + 1).to_le_bytes());
+        Ok(oid)
+    }
+
+    /// Write the `lime_tables` row and the `lime_columns` rows of `info`.
+    fn insert_table_rows(&self, info: &TableInfo) -> anyhow::Result<()> {
+        let row = Tuple::new(vec![
+            Value::Int64(info.oid as i64),
+            Value::Varchar(info.name.clone()),
+            Value::Int64(info.heap_page_id.to_u64() as i64),
+        ]);
+        self.tables
+            .insert_tuple(&row.to_bytes(&system_schema(LIME_TABLES_OID))?)?;
+
+        let columns_schema = system_schema(LIME_COLUMNS_OID);
+        for (position, column) in info.schema.columns().iter().enumerate() {
+            let mut values = vec![
+                Value::Int64(info.oid as i64),
+                Value::Int32(position as i32),
+                Value::Varchar(column.name.clone()),
+            ];
+            values.extend(encode_type(column.data_type));
+            values.push(Value::Boolean(column.nullable));
+            self.columns
+                .insert_tuple(&Tuple::new(values).to_bytes(&columns_schema)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the rows of `heap` whose column `column` holds `oid`.
+    fn delete_rows(
+        &self,
+        heap: &TableHeap<'a, B>,
+        schema_oid: Oid,
+        column: usize,
+        oid: Oid,
+    ) -> anyhow::Result<()> {
+        let schema = system_schema(schema_oid);
+        let mut matching: Vec<RecordId> = Vec::new();
+        for row in heap.iter()? {
+            let (rid, data) = row?;
+            let values = Tuple::from_bytes(&schema, &data)?.into_values();
+            if int(&values[column])? as Oid == oid {
+                matching.push(rid);
+            }
+        }
+        for rid in matching {
+            heap.mark_delete(rid)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create an empty table. Fail if a table or an index is already named `name`.
+    pub fn create_table(&self, name: &str, schema: Schema) -> anyhow::Result<Arc<TableInfo>> {
+        let mut entries = self.shared.entries.write().unwrap();
+        if entries.name_is_taken(name) {
+            anyhow::bail!("relation \"{name}\" already exists");
+        }
+        for (i, column) in schema.columns().iter().enumerate() {
+            if schema.index_of(&column.name) != Some(i) {
+                anyhow::bail!("column \"{}\" specified more than once", column.name);
+            }
+        }
+        let heap = TableHeap::create(self.bpm)?;
+        let info = TableInfo {
+            oid: self.allocate_oid()?,
+            name: name.to_string(),
+            schema,
+            heap_page_id: heap.header_page_id(),
+        };
+        self.insert_table_rows(&info)?;
+        self.shared
+            .fsms
+            .lock()
+            .unwrap()
+            .insert(info.heap_page_id, heap.free_space_map().shared());
+
+        Ok(entries.add_table(info))
+    }
+
+    /// Drop the table named `name` and its indexes, returning whether it existed. Their pages
+    /// are freed once the queries that started before the drop are done reading them; see
+    /// [`Self::free_dropped`].
+    pub fn drop_table(&self, name: &str) -> anyhow::Result<bool> {
+        let mut entries = self.shared.entries.write().unwrap();
+        let Some(&oid) = entries.table_names.get(&name.to_ascii_lowercase()) else {
+            return Ok(false);
+        };
+        if oid < FIRST_USER_OID {
+            anyhow::bail!("cannot drop system table \"{name}\"");
+        }
+        let index_oids: Vec<Oid> = entries
+            .indexes
+            .values()
+            .filter(|index| index.table_oid == oid)
+            .map(|index| index.oid)
+            .collect();
+        for index_oid in index_oids {
+            self.drop_index_locked(&mut entries, index_oid)?;
+        }
+        self.delete_rows(&self.statistics, LIME_STATISTICS_OID, 0, oid)?;
+        self.delete_rows(&self.columns, LIME_COLUMNS_OID, 0, oid)?;
+        self.delete_rows(&self.tables, LIME_TABLES_OID, 0, oid)?;
+        entries.statistics.remove(&oid);
+        if let Some(info) = entries.tables.remove(&oid) {
+            entries.table_names.remove(&info.name.to_ascii_lowercase());
+            self.shared.fsms.lock().unwrap().remove(&info.heap_page_id);
+            self.shared
+                .dropped
+                .lock()
+                .unwrap()
+                .push(Dropped::Table(info));
+        }
+        drop(entries);
+        self.free_dropped()?;
+
+        Ok(true)
+    }
+
+    /// Free the pages of the tables and indexes dropped so far that nothing reads anymore.
+    /// The rest are freed by a later call, once the queries reading them are done.
+    pub fn free_dropped(&self) -> anyhow::Result<()> {
+        let mut dropped = self.shared.dropped.lock().unwrap();
+        let mut i = 0;
+        while i < dropped.len() {
+            if !dropped[i].is_unused() {
+                i += 1;
+                continue;
+            }
+            // Taken out even if freeing fails, so that pages already freed are never walked
+            // again after they have been reused.
+            match dropped.swap_remove(i) {
+                Dropped::Table(info) => {
+                    TableHeap::open(self.bpm, info.heap_page_id)?.free_pages()?;
+                }
+                Dropped::Index(info, key_types) => {
+                    TableIndex::open(self.bpm, &info, key_types)?.free_pages()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up a table by its name, compared case-insensitively.
+    pub fn table(&self, name: &str) -> Option<Arc<TableInfo>> {
+        let entries = self.shared.entries.read().unwrap();
+        let oid = entries.table_names.get(&name.to_ascii_lowercase())?;
+        entries.tables.get(oid).cloned()
+    }
+
+    pub fn table_by_oid(&self, oid: Oid) -> Option<Arc<TableInfo>> {
+        self.shared
+            .entries
+            .read()
+            .unwrap()
+            .tables
+            .get(&oid)
+            .cloned()
+    }
+
+    /// Every table, system tables included, in OID order.
+    pub fn tables(&self) -> Vec<Arc<TableInfo>> {
+        let mut tables: Vec<_> = self
+            .shared
+            .entries
+            .read()
+            .unwrap()
+            .tables
+            .values()
+            .cloned()
+            .collect();
+        tables.sort_by_key(|table| table.oid);
+        tables
+    }
+
+    /// The buffer pool the tables and indexes are read through.
+    pub fn buffer_pool(&self) -> &'a B {
+        self.bpm
+    }
+
+    /// Open a handle on the heap holding the rows of `table`. Its free-space map is read
+    /// from disk on first use only, and shared by every handle from then on.
+    pub fn table_heap(&self, table: &TableInfo) -> anyhow::Result<TableHeap<'a, B>> {
+        let mut fsms = self.shared.fsms.lock().unwrap();
+        if let Some(fsm) = fsms.get(&table.heap_page_id) {
+            return Ok(TableHeap::open_with_fsm(
+                self.bpm,
+                table.heap_page_id,
+                fsm.clone(),
+            ));
+        }
+        let heap = TableHeap::open(self.bpm, table.heap_page_id)?;
+        fsms.insert(table.heap_page_id, heap.free_space_map().shared());
+
+        Ok(heap)
+    }
+
+    /// Create an index on the columns `columns` of the table `table`, in key order, and fill it
+    /// with the rows the table already has. The keys are sorted on the buffer pool of the
+    /// catalog; [`Self::create_index_with_temp`] sorts them on temp space instead.
+    pub fn create_index(
+        &self,
+        name: &str,
+        table: &str,
+        columns: &[&str],
+        kind: IndexKind,
+        unique: bool,
+    ) -> anyhow::Result<Arc<IndexInfo>> {
+        self.create_index_with_temp(self.bpm, name, table, columns, kind, unique)
+    }
+
+    /// Like [`Self::create_index`], but sort the keys on `temp`, such as a
+    /// [`TempBufferPool`](crate::buffer::buffer_pool_manager::TempBufferPool).
+    ///
+    /// The rows are streamed from the heap into the index without holding the lock on the
+    /// catalog, which is only taken again to record the index once it is built.
+    pub fn create_index_with_temp<T: BufferPoolManager + ?Sized>(
+        &self,
+        temp: &T,
+        name: &str,
+        table: &str,
+        columns: &[&str],
+        kind: IndexKind,
+        unique: bool,
+    ) -> anyhow::Result<Arc<IndexInfo>> {
+        let table = {
+            let entries = self.shared.entries.read().unwrap();
+            if entries.name_is_taken(name) {
+                anyhow::bail!("relation \"{name}\" already exists");
+            }
+            let Some(table) = entries
+                .table_names
+                .get(&table.to_ascii_lowercase())
+                .and_then(|oid| entries.tables.get(oid))
+                .cloned()
+            else {
+                anyhow::bail!("table \"{table}\" does not exist");
+            };
+            table
+        };
+        let positions = columns
+            .iter()
+            .map(|column| {
+                table.schema.index_of(column).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "column \"{column}\" of table \"{}\" does not exist",
+                        table.name
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let key_types: Vec<DataType> = positions
+            .iter()
+            .map(|&position| table.schema.column(position).data_type)
+            .collect();
+        check_key_types(&key_types)?;
+
+        let heap = self.table_heap(&table)?;
+        let rows = heap.iter()?.map(|row| {
+            let (rid, data) = row?;
+            let tuple = TupleRef::new(&table.schema, &data)?;
+            let key = positions
+                .iter()
+                .map(|&position| tuple.value(position))
+                .collect::<anyhow::Result<_>>()?;
+            Ok((key, rid))
+        });
+        let index = TableIndex::build(
+            self.bpm,
+            temp,
+            kind,
+            unique,
+            key_types,
+            rows,
+            BulkLoadConfig::new(),
+        )?;
+
+        let mut entries = self.shared.entries.write().unwrap();
+        if entries.name_is_taken(name) {
+            anyhow::bail!("relation \"{name}\" already exists");
+        }
+        if !entries.tables.contains_key(&table.oid) {
+            anyhow::bail!("table \"{}\" does not exist", table.name);
+        }
+        let info = IndexInfo {
+            oid: self.allocate_oid()?,
+            name: name.to_string(),
+            table_oid: table.oid,
+            columns: positions,
+            kind,
+            unique,
+            header_page_id: index.header_page_id(),
+        };
+        let positions: Vec<Value> = info
+            .columns
+            .iter()
+            .map(|&position| Value::Int32(position as i32))
+            .collect();
+        let row = Tuple::new(vec![
+            Value::Int64(info.oid as i64),
+            Value::Varchar(info.name.clone()),
+            Value::Int64(info.table_oid as i64),
+            Value::Bytea(encode_values(&positions, DataType::Int32)?),
+            Value::Int16(encode_index_kind(kind)),
+            Value::Boolean(unique),
+            Value::Int64(info.header_page_id.to_u64() as i64),
+        ]);
+        self.indexes
+            .insert_tuple(&row.to_bytes(&system_schema(LIME_INDEXES_OID))?)?;
+
+        Ok(entries.add_index(info))
+    }
+
+    /// Drop the index named `name`, returning whether it existed. Like the pages of a dropped
+    /// table, its pages are freed once the queries that started before the drop are done.
+    pub fn drop_index(&self, name: &str) -> anyhow::Result<bool> {
+        let mut entries = self.shared.entries.write().unwrap();
+        let Some(&oid) = entries.index_names.get(&name.to_ascii_lowercase()) else {
+            return Ok(false);
+        };
+        self.drop_index_locked(&mut entries, oid)?;
+        drop(entries);
+        self.free_dropped()?;
+        Ok(true)
+    }
+
+    fn drop_index_locked(
+        &self,
+        entries: &mut RwLockWriteGuard<'_, Entries>,
+        oid: Oid,
+    ) -> anyhow::Result<()> {
+        self.delete_rows(&self.indexes, LIME_INDEXES_OID, 0, oid)?;
+        if let Some(info) = entries.indexes.remove(&oid) {
+            entries.index_names.remove(&info.name.to_ascii_lowercase());
+            let table = &entries.tables[&info.table_oid];
+            let key_types = info
+                .columns
+                .iter()
+                .map(|&position| table.schema.column(position).data_type)
+                .collect();
+            self.shared
+                .dropped
+                .lock()
+                .unwrap()
+                .push(Dropped::Index(info, key_types));
+        }
+        Ok(())
+    }
+
+    /// Look up an index by its name, compared case-insensitively.
+    pub fn index(&self, name: &str) -> Option<Arc<IndexInfo>> {
+        let entries = self.shared.entries.read().unwrap();
+        let oid = entries.index_names.get(&name.to_ascii_lowercase())?;
+        entries.indexes.get(oid).cloned()
+    }
+
+    pub fn index_by_oid(&self, oid: Oid) -> Option<Arc<IndexInfo>> {
+        self.shared
+            .entries
+            .read()
+            .unwrap()
+            .indexes
+            .get(&oid)
+            .cloned()
+    }
+
+    /// The indexes on the table `table_oid`, in OID order.
+    pub fn table_indexes(&self, table_oid: Oid) -> Vec<Arc<IndexInfo>> {
+        let mut indexes: Vec<_> = self
+            .shared
+            .entries
+            .read()
+            .unwrap()
+            .indexes
+            .values()
+            .filter(|index| index.table_oid == table_oid)
+            .cloned()
+            .collect();
+        indexes.sort_by_key(|index| index.oid);
+        indexes
+    }
+
+    /// Open the index structure `index` describes.
+    pub fn open_index(&self, index: &IndexInfo) -> anyhow::Result<TableIndex<'a>> {
+        let Some(table) = self.table_by_oid(index.table_oid) else {
+            anyhow::bail!("the table of index \"{}\" does not exist", index.name);
+        };
+        let key_types = index
+            .columns
+            .iter()
+            .map(|&position| table.schema.column(position).data_type)
+            .collect();
+        TableIndex::open(self.bpm, index, key_types)
+    }
+
+    /// The statistics of the last `ANALYZE` of the table `table_oid`, if it was analyzed.
+    pub fn table_statistics(&self, table_oid: Oid) -> Option<Arc<TableStatistics>> {
+        self.shared
+            .entries
+            .read()
+            .unwrap()
+            .statistics
+            .get(&table_oid)
+            .cloned()
+    }
+
+    /// Replace the statistics of the table `table_oid`, which must have one entry for each of
+    /// its columns.
+    pub fn set_table_statistics(
+        &self,
+        table_oid: Oid,
+        statistics: TableStatistics,
+    ) -> anyhow::Result<()> {
+        let mut entries = self.shared.entries.write().unwrap();
+        let Some(table) = entries.tables.get(&table_oid).cloned() else {
+            anyhow::bail!("table {table_oid} does not exist");
+        };
+        if statistics.columns.len() != table.schema.column_count() {
+            anyhow::bail!(
+                "table \"{}\" has {} columns, not {}",
+                table.name,
+                table.schema.column_count(),
+                statistics.columns.len()
+            );
+        }
+        self.delete_rows(&self.statistics, LIME_STATISTICS_OID, 0, table_oid)?;
+        let schema = system_schema(LIME_STATISTICS_OID);
+        for (position, (column, column_statistics)) in table
+            .schema
+            .columns()
+            .iter()
+            .zip(&statistics.columns)
+            .enumerate()
+        {
+            let frequencies: Vec<Value> = column_statistics
+                .most_common_frequencies
+                .iter()
+                .map(|&frequency| Value::Float64(frequency))
+                .collect();
+            let row = Tuple::new(vec![
+                Value::Int64(table_oid as i64),
+                Value::Int32(position as i32),
+                Value::Int64(statistics.row_count as i64),
+                Value::Float64(column_statistics.null_fraction),
+                Value::Int64(column_statistics.distinct_count as i64),
+                Value::Bytea(encode_values(
+                    &column_statistics.most_common_values,
+                    column.data_type,
+                )?),
+                Value::Bytea(encode_values(&frequencies, DataType::Float64)?),
+                Value::Bytea(encode_values(
+                    &column_statistics.histogram_bounds,
+                    column.data_type,
+                )?),
+            ]);
+            self.statistics.insert_tuple(&row.to_bytes(&schema)?)?;
+        }
+        entries.statistics.insert(table_oid, Arc::new(statistics));
+        Ok(())
+    }
+}
+
+/// The schema of the system table `oid`.
+fn system_schema(oid: Oid) -> Schema {
+    let columns = match oid {
+        LIME_TABLES_OID => vec![
+            Column::new("oid", DataType::Int64).not_null(),
+            Column::new("name", DataType::Varchar(None)).not_null(),
+            Column::new("heap_page_id", DataType::Int64).not_null(),
+        ],
+        LIME_COLUMNS_OID => vec![
+            Column::new("table_oid", DataType::Int64).not_null(),
+            Column::new("position", DataType::Int32).not_null(),
+            Column::new("name", DataType::Varchar(None)).not_null(),
+            Column::new("type_id", DataType::Int16).not_null(),
+            Column::new("type_length", DataType::Int32),
+            Column::new("type_scale", DataType::Int16),
+            Column::new("nullable", DataType::Boolean).not_null(),
+        ],
+        LIME_INDEXES_OID => vec![
+            Column::new("oid", DataType::Int64).not_null(),
+            Column::new("name", DataType::Varchar(None)).not_null(),
+            Column::new("table_oid", DataType::Int64).not_null(),
+            Column::new("column_positions", DataType::Bytea).not_null(),
+            Column::new("kind", DataType::Int16).not_null(),
+            Column::new("is_unique", DataType::Boolean).not_null(),
+            Column::new("header_page_id", DataType::Int64).not_null(),
+        ],
+        LIME_STATISTICS_OID => vec![
+            Column::new("table_oid", DataType::Int64).not_null(),
+            Column::new("column_position", DataType::Int32).not_null(),
+            Column::new("row_count", DataType::Int64).not_null(),
+            Column::new("null_fraction", DataType::Float64).not_null(),
+            Column::new("distinct_count", DataType::Int64).not_null(),
+            Column::new("most_common_values", DataType::Bytea).not_null(),
+            Column::new("most_common_frequencies", DataType::Bytea).not_null(),
+            Column::new("histogram_bounds", DataType::Bytea).not_null(),
+        ],
+        _ => unreachable!("{oid} is not a system table"),
+    };
+    Schema::new(columns)
+}
+
+/// The `type_id`, `type_length` and `type_scale` columns of `lime_columns`. The length is the
+/// maximum length of a VARCHAR or the precision of a DECIMAL.
+fn encode_type(data_type: DataType) -> [Value; 3] {
+    let (length, scale) = match data_type {
+        DataType::Varchar(Some(len)) => (Value::Int32(len as i32), Value::Null),
+        DataType::Decimal { precision, scale } => {
+            (Value::Int32(precision as i32), Value::Int16(scale as i16))
+        }
+        _ => (Value::Null, Value::Null),
+    };
+    [Value::Int16(data_type.type_id() as i16), length, scale]
+}
+
+fn decode_type(values: &[Value]) -> anyhow::Result<DataType> {
+    let length = values[1].as_i64();
+    let data_type = match int(&values[0])? {
+        1 => DataType::Boolean,
+        2 => DataType::Int8,
+        3 => DataType::Int16,
+        4 => DataType::Int32,
+        5 => DataType::Int64,
+        6 => DataType::Float64,
+        7 => DataType::Decimal {
+            precision: length.unwrap_or(DataType::MAX_DECIMAL_PRECISION as i64) as u8,
+            scale: values[2].as_i64().unwrap_or(0) as u8,
+        },
+        8 => DataType::Varchar(length.map(|len| len as u32)),
+        9 => DataType::Bytea,
+        10 => DataType::Date,
+        11 => DataType::Timestamp,
+        type_id => anyhow::bail!("unknown type id {type_id} in the catalog"),
+    };
+    Ok(data_type)
+}
+
+fn encode_index_kind(kind: IndexKind) -> i16 {
+    match kind {
+        IndexKind::BPlusTree => 0,
+        IndexKind::Hash => 1,
+    }
+}
+
+fn decode_index_kind(kind: i64) -> anyhow::Result<IndexKind> {
+    match kind {
+        0 => Ok(IndexKind::BPlusTree),
+        1 => Ok(IndexKind::Hash),
+        _ => anyhow::bail!("unknown index kind {kind} in the catalog"),
+    }
+}
+
+fn int(value: &Value) -> anyhow::Result<i64> {
+    value
+        .as_i64()
+        .ok_or_else(|| anyhow::anyhow!("expected an integer in the catalog, found {value}"))
+}
+
+fn float(value: &Value) -> anyhow::Result<f64> {
+    match value {
+        Value::Float64(f) => Ok(*f),
+        _ => anyhow::bail!("expected a float in the catalog, found {value}"),
+    }
+}
+
+fn bytea(value: &Value) -> anyhow::Result<&[u8]> {
+    match value {
+        Value::Bytea(bytes) => Ok(bytes),
+        _ => anyhow::bail!("expected bytes in the catalog, found {value}"),
+    }
+}
+
+fn text(value: &Value) -> anyhow::Result<&str> {
+    value
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("expected a string in the catalog, found {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, ops::Bound};
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    fn users_statistics() -> TableStatistics {
+        let column = |null_fraction, distinct_count| ColumnStatistics {
+            null_fraction,
+            distinct_count,
+            most_common_values: Vec::new(),
+            most_common_frequencies: Vec::new(),
+            histogram_bounds: Vec::new(),
+        };
+        TableStatistics {
+            row_count: 100,
+            columns: vec![
+                ColumnStatistics {
+                    most_common_values: vec![Value::Int64(7)],
+                    most_common_frequencies: vec![0.02],
+                    histogram_bounds: vec![Value::Int64(0), Value::Int64(25), Value::Int64(49)],
+                    ..column(0.0, 50)
+                },
+                ColumnStatistics {
+                    histogram_bounds: vec![
+                        Value::Varchar("user-0".to_string()),
+                        Value::Varchar("user-99".to_string()),
+                    ],
+                    ..column(0.0, 100)
+                },
+                column(1.0, 0),
+            ],
+        }
+    }
+
+    fn users_schema() -> Schema {
+        Schema::new(vec![
+            Column::new("id", DataType::Int64).not_null(),
+            Column::new("name", DataType::Varchar(Some(32))),
+            Column::new(
+                "balance",
+                DataType::Decimal {
+                    precision: 12,
+                    scale: 2,
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_bootstrap_and_reopen() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        let (users_oid, index_oid) = {
+            let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &path).unwrap();
+            let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+            let catalog = Catalog::create(&bpm).unwrap();
+            assert_eq!(
+                catalog.table("LIME_TABLES").unwrap().schema,
+                system_schema(LIME_TABLES_OID)
+            );
+
+            let users = catalog.create_table("users", users_schema()).unwrap();
+            assert!(users.oid >= FIRST_USER_OID);
+            assert!(catalog.create_table("Users", users_schema()).is_err());
+            catalog
+                .create_table(
+                    "orders",
+                    Schema::new(vec![Column::new("id", DataType::Int32)]),
+                )
+                .unwrap();
+            assert!(catalog.drop_table("orders").unwrap());
+            assert!(!catalog.drop_table("orders").unwrap());
+            assert!(catalog.drop_table("lime_columns").is_err());
+
+            let heap = catalog.table_heap(&users).unwrap();
+            for i in 0..100 {
+                let row = Tuple::new(vec![
+                    Value::Int64(i % 50),
+                    Value::Varchar(format!("user-{i}")),
+                    Value::Null,
+                ]);
+                heap.insert_tuple(&row.to_bytes(&users.schema).unwrap())
+                    .unwrap();
+            }
+            let index = catalog
+                .create_index("users_id", "users", &["id"], IndexKind::BPlusTree, false)
+                .unwrap();
+            assert!(catalog
+                .create_index("users_id_unique", "users", &["id"], IndexKind::Hash, true)
+                .is_err());
+            catalog
+                .create_index(
+                    "users_name",
+                    "users",
+                    &["name", "id"],
+                    IndexKind::Hash,
+                    true,
+                )
+                .unwrap();
+
+            assert!(catalog.table_statistics(users.oid).is_none());
+            let mut statistics = users_statistics();
+            statistics.row_count = 1;
+            catalog
+                .set_table_statistics(users.oid, statistics.clone())
+                .unwrap();
+            // Analyzing again replaces the rows of the previous statistics.
+            catalog
+                .set_table_statistics(users.oid, users_statistics())
+                .unwrap();
+            statistics.columns.pop();
+            assert!(catalog.set_table_statistics(users.oid, statistics).is_err());
+            (users.oid, index.oid)
+        };
+
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &path).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        let catalog = Catalog::open(&bpm).unwrap();
+        assert!(catalog.table("orders").is_none());
+        let users = catalog.table("users").unwrap();
+        assert_eq!(users.oid, users_oid);
+        assert_eq!(users.schema, users_schema());
+        assert_eq!(catalog.table_by_oid(users_oid), Some(users.clone()));
+        assert_eq!(
+            catalog
+                .tables()
+                .iter()
+                .map(|table| table.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "lime_tables",
+                "lime_columns",
+                "lime_indexes",
+                "lime_statistics",
+                "users"
+            ]
+        );
+        assert_eq!(
+            catalog.table_statistics(users_oid).as_deref(),
+            Some(&users_statistics())
+        );
+
+        let info = catalog.index("USERS_ID").unwrap();
+        assert_eq!(info.oid, index_oid);
+        assert_eq!(catalog.index_by_oid(index_oid), Some(info.clone()));
+        let names = catalog.index("users_name").unwrap();
+        assert_eq!(names.columns, [1, 0]);
+        assert_eq!(
+            catalog.table_indexes(users_oid),
+            vec![info.clone(), names.clone()]
+        );
+        let index = catalog.open_index(&info).unwrap();
+        assert_eq!(index.get(&[Value::Int64(7)]).unwrap().len(), 2);
+        let in_range = index
+            .scan(
+                Bound::Excluded(&[Value::Int64(10)]),
+                Bound::Included(&[Value::Int64(12)]),
+            )
+            .unwrap()
+            .count();
+        assert_eq!(in_range, 4);
+        let index = catalog.open_index(&names).unwrap();
+        let key = [Value::Varchar("user-57".to_string()), Value::Int64(7)];
+        assert_eq!(index.get(&key).unwrap().len(), 1);
+
+        // A new OID is never handed out twice, even across restarts.
+        let orders = catalog
+            .create_table(
+                "orders",
+                Schema::new(vec![Column::new("id", DataType::Int32)]),
+            )
+            .unwrap();
+        assert!(orders.oid > index_oid);
+
+        assert!(catalog.drop_table("users").unwrap());
+        assert!(catalog.index("users_id").is_none());
+        assert!(catalog.table_indexes(users_oid).is_empty());
+        assert!(catalog.table_statistics(users_oid).is_none());
+    }
+
+    #[test]
+    fn test_table_heap_handles_share_free_space_map() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let users = catalog.create_table("users", users_schema()).unwrap();
+
+        // Two handles, as two sessions would open, both appending heap pages to the map.
+        let heaps = [
+            catalog.table_heap(&users).unwrap(),
+            catalog.table_heap(&users).unwrap(),
+        ];
+        let tuple = vec![0xab; 1000];
+        for _ in 0..40 {
+            for heap in &heaps {
+                heap.insert_tuple(&tuple).unwrap();
+            }
+        }
+        drop(heaps);
+
+        let catalog = Catalog::open(&bpm).unwrap();
+        let heap = catalog.table_heap(&users).unwrap();
+        let page_ids: HashSet<_> = heap
+            .iter()
+            .unwrap()
+            .map(|row| row.unwrap().0.page_id)
+            .collect();
+        assert!(page_ids.len() > 8);
+        assert_eq!(heap.free_space_map().page_count(), page_ids.len());
+        assert!(page_ids.iter().all(|page_id| heap
+            .free_space_map()
+            .recorded_free_space(*page_id)
+            .is_some()));
+    }
+
+    #[test]
+    fn test_unique_index() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let users = catalog.create_table("users", users_schema()).unwrap();
+        let heap = catalog.table_heap(&users).unwrap();
+        let row = Tuple::new(vec![Value::Int64(1), Value::Null, Value::Null]);
+        let rid = heap
+            .insert_tuple(&row.to_bytes(&users.schema).unwrap())
+            .unwrap();
+
+        for (name, kind) in [
+            ("by_tree", IndexKind::BPlusTree),
+            ("by_hash", IndexKind::Hash),
+        ] {
+            let info = catalog
+                .create_index(name, "users", &["id"], kind, true)
+                .unwrap();
+            let index = catalog.open_index(&info).unwrap();
+            assert_eq!(index.kind(), kind);
+            let key = [Value::Int64(1)];
+            assert_eq!(index.get(&key).unwrap(), vec![rid]);
+            assert!(!index.insert(&key, rid).unwrap());
+            assert!(index.remove(&key, rid).unwrap());
+            assert!(index.get(&key).unwrap().is_empty());
+        }
+        assert!(catalog.drop_index("by_hash").unwrap());
+        assert!(!catalog.drop_index("by_hash").unwrap());
+        assert_eq!(catalog.table_indexes(users.oid).len(), 1);
+    }
+}
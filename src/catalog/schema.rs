@@ -0,0 +1,123 @@
+use crate::types::data_type::DataType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+impl Column {
+    pub fn new(name: impl Into<String>, data_type: DataType) -> Self {
+        Self {
+            name: name.into(),
+            data_type,
+            nullable: true,
+        }
+    }
+
+    pub fn not_null(self) -> Self {
+        Self {
+            nullable: false,
+            ..self
+        }
+    }
+}
+
+/// Describes the columns of a tuple and where each column lives in its encoding.
+///
+/// An encoded tuple starts with a null bitmap of one bit per column, followed by the
+/// fixed-length part, in which every column has a slot of [`DataType::fixed_size`] bytes at a
+/// fixed offset, and ends with the variable-length part. Variable-length columns store the
+/// offset and the length of their bytes in their fixed-length slot, so any column can be
+/// decoded without looking at the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    columns: Vec<Column>,
+    /// Offset of each column's fixed-length slot from the start of the tuple.
+    offsets: Vec<usize>,
+    /// Size of the null bitmap and the fixed-length part together.
+    fixed_len: usize,
+}
+
+impl Schema {
+    pub fn new(columns: Vec<Column>) -> Self {
+        let mut offset = Self::bitmap_len_for(columns.len());
+        let mut offsets = Vec::with_capacity(columns.len());
+        for column in &columns {
+            offsets.push(offset);
+            offset += column.data_type.fixed_size();
+        }
+        Self {
+            columns,
+            offsets,
+            fixed_len: offset,
+        }
+    }
+
+    fn bitmap_len_for(column_count: usize) -> usize {
+        column_count.div_ceil(8)
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn column(&self, index: usize) -> &Column {
+        &self.columns[index]
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The index of the column named `name`, compared case-insensitively.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|column| column.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn bitmap_len(&self) -> usize {
+        Self::bitmap_len_for(self.columns.len())
+    }
+
+    pub fn fixed_len(&self) -> usize {
+        self.fixed_len
+    }
+
+    pub fn offset_of(&self, index: usize) -> usize {
+        self.offsets[index]
+    }
+
+    /// A schema made of the columns at `indices`, in that order.
+    pub fn project(&self, indices: &[usize]) -> Self {
+        Self::new(indices.iter().map(|i| self.columns[*i].clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let schema = Schema::new(vec![
+            Column::new("id", DataType::Int64).not_null(),
+            Column::new("name", DataType::Varchar(Some(32))),
+            Column::new("flag", DataType::Boolean),
+        ]);
+        assert_eq!(schema.bitmap_len(), 1);
+        assert_eq!(schema.offset_of(0), 1);
+        assert_eq!(schema.offset_of(1), 9);
+        assert_eq!(schema.offset_of(2), 17);
+        assert_eq!(schema.fixed_len(), 18);
+        assert_eq!(schema.index_of("NAME"), Some(1));
+        assert_eq!(schema.index_of("missing"), None);
+        assert!(!schema.column(0).nullable);
+
+        let projected = schema.project(&[2, 0]);
+        assert_eq!(projected.column(0).name, "flag");
+        assert_eq!(projected.offset_of(1), 2);
+    }
+}
@@ -0,0 +1,89 @@
+//! What `ANALYZE` learned about the rows of a table, which the optimizer uses to estimate how
+//! many rows a condition keeps.
+//!
+//! The row count, the fraction of NULLs and the number of distinct values are computed over
+//! every row. The most common values and the histogram come from a random sample: the values
+//! that appear in the sample markedly more often than the average are kept with their
+//! frequencies, and the bounds of the histogram split the other non-NULL values of the sample
+//! into buckets of equal numbers of values, so that every bucket holds about the same fraction
+//! of the rows.
+
+use crate::{
+    catalog::schema::{Column, Schema},
+    storage::table::tuple::Tuple,
+    types::{data_type::DataType, value::Value},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStatistics {
+    pub row_count: u64,
+    /// One for every column of the table, in order.
+    pub columns: Vec<ColumnStatistics>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStatistics {
+    /// The fraction of the rows whose value is NULL.
+    pub null_fraction: f64,
+    /// The estimated number of distinct non-NULL values.
+    pub distinct_count: u64,
+    /// The most common non-NULL values, most common first.
+    pub most_common_values: Vec<Value>,
+    /// The fraction of all the rows that have each of `most_common_values`.
+    pub most_common_frequencies: Vec<f64>,
+    /// The ascending bounds of the buckets of the values other than the most common ones:
+    /// bucket `i` holds the values between bounds `i` and `i + 1`. Empty if there are fewer
+    /// than two.
+    pub histogram_bounds: Vec<Value>,
+}
+
+impl ColumnStatistics {
+    /// The fraction of the rows whose value is neither NULL nor one of the most common values.
+    pub fn histogram_fraction(&self) -> f64 {
+        let common: f64 = self.most_common_frequencies.iter().sum();
+        (1.0 - self.null_fraction - common).max(0.0)
+    }
+}
+
+/// Encode a list of values of `data_type` as a count followed by a tuple of them, for the
+/// BYTEA columns of `lime_statistics`.
+pub fn encode_values(values: &[Value], data_type: DataType) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = (values.len() as u32).to_le_bytes().to_vec();
+    bytes.extend(Tuple::new(values.to_vec()).to_bytes(&list_schema(values.len(), data_type))?);
+    Ok(bytes)
+}
+
+pub fn decode_values(bytes: &[u8], data_type: DataType) -> anyhow::Result<Vec<Value>> {
+    let Some((count, tuple)) = bytes.split_first_chunk::<4>() else {
+        anyhow::bail!("truncated list of values in the catalog");
+    };
+    let schema = list_schema(u32::from_le_bytes(*count) as usize, data_type);
+    Ok(Tuple::from_bytes(&schema, tuple)?.into_values())
+}
+
+fn list_schema(len: usize, data_type: DataType) -> Schema {
+    Schema::new(
+        (0..len)
+            .map(|i| Column::new(format!("v{i}"), data_type))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_values() {
+        let values = vec![
+            Value::Varchar("a".to_string()),
+            Value::Varchar("bc".to_string()),
+        ];
+        let data_type = DataType::Varchar(Some(4));
+        let bytes = encode_values(&values, data_type).unwrap();
+        assert_eq!(decode_values(&bytes, data_type).unwrap(), values);
+        let empty = encode_values(&[], DataType::Int32).unwrap();
+        assert!(decode_values(&empty, DataType::Int32).unwrap().is_empty());
+        assert!(decode_values(&bytes[..2], data_type).is_err());
+    }
+}
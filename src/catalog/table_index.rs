@@ -0,0 +1,957 @@
+//! The physical side of an index recorded in the catalog.
+//!
+//! Catalog indexes map the values of one or more columns, the key, to the [`RecordId`] of their
+//! row. B+ tree indexes store the key [encoded](EncodedKey) so that it sorts like the values do,
+//! which works for every type and for composite keys alike. Non-unique indexes append the record
+//! id to the encoded key so that equal values still have distinct keys, and find them with a
+//! range scan. Hash indexes store a 128-bit digest of the encoded key, with the record ids of the
+//! rows of each digest, so they too work for any key, unique or not. Distinct keys are not
+//! expected to ever share a digest, but since callers recheck the rows an index returns, a
+//! collision would only cost a unique index a spurious violation. Keys with a NULL are not
+//! indexed.
+//!
+//! Each value of a key is encoded as a marker byte followed by the order-preserving encoding of
+//! the value in its column's type, and the key ends with a terminator byte below the marker:
+//!
+//! | type                 | encoding                                          |
+//! |----------------------|---------------------------------------------------|
+//! | BOOLEAN              | 1 byte                                            |
+//! | integers             | 8 bytes, as an `i64` [`IndexKey`]                 |
+//! | DOUBLE               | 8 bytes, ordered like `f64::total_cmp`            |
+//! | DECIMAL              | 16 bytes, the mantissa at the column's scale      |
+//! | VARCHAR, BYTEA       | the bytes, escaped and terminated like `Vec<u8>`  |
+//! | DATE, TIMESTAMP      | 4 or 8 bytes, the days or microseconds            |
+//!
+//! Every value is self-delimiting, so the keys that start with the values of a prefix of the
+//! columns are exactly those that start with the encoding of the prefix. The byte after it is
+//! the marker of the next value or the terminator, never `0xff`, which lets a scan be bounded by
+//! a prefix of the key.
+
+use std::ops::Bound;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    storage::{
+        index::{
+            b_plus_tree::{BPlusTree, BulkLoadConfig},
+            extendible_hash_table::ExtendibleHashTable,
+            index::{Index, IndexKind},
+            index_key::IndexKey,
+        },
+        table::record_id::RecordId,
+    },
+    types::{data_type::DataType, decimal::Decimal, value::Value},
+    PageId,
+};
+
+use super::catalog::IndexInfo;
+
+const VALUE_MARKER: u8 = 0x01;
+const KEY_END: u8 = 0x00;
+/// Sorts after every key that starts with the prefix it follows.
+const PREFIX_END: u8 = 0xff;
+
+pub type RecordIds<'i> = Box<dyn Iterator<Item = anyhow::Result<RecordId>> + 'i>;
+pub type IndexEntries<'i> = Box<dyn Iterator<Item = anyhow::Result<(EncodedKey, RecordId)>> + 'i>;
+
+/// The encoding of an entry of a B+ tree catalog index, which compares like the entry does.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EncodedKey(Vec<u8>);
+
+/// The key is never part of a larger one, so it takes all of the bytes it is decoded from.
+impl IndexKey for EncodedKey {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+
+    fn decode(buf: &[u8]) -> (Self, usize) {
+        (Self(buf.to_vec()), buf.len())
+    }
+}
+
+pub struct TableIndex<'a> {
+    entries: Entries<'a>,
+    /// The types of the indexed columns, in key order.
+    key_types: Vec<DataType>,
+    unique: bool,
+}
+
+enum Entries<'a> {
+    Tree(Box<dyn Index<EncodedKey, RecordId> + 'a>),
+    Hash(Box<dyn HashEntries + 'a>),
+}
+
+/// The hash table of a hash index, with the type of its buffer pool erased. Its keys are the
+/// [digests](hash_digest) of the index keys, each with the rows of every key of that digest.
+trait HashEntries {
+    fn header_page_id(&self) -> PageId;
+
+    fn get(&self, digest: u128) -> anyhow::Result<Vec<RecordId>>;
+
+    /// Add the entry of row `rid`, returning false if it is present, or if `unique` and the
+    /// digest is.
+    fn insert(&self, digest: u128, rid: RecordId, unique: bool) -> anyhow::Result<bool>;
+
+    fn remove(&self, digest: u128, rid: RecordId) -> anyhow::Result<bool>;
+
+    fn free_pages(&self) -> anyhow::Result<()>;
+}
+
+impl<B: BufferPoolManager + ?Sized> HashEntries for ExtendibleHashTable<'_, u128, RecordId, B> {
+    fn header_page_id(&self) -> PageId {
+        ExtendibleHashTable::header_page_id(self)
+    }
+
+    fn get(&self, digest: u128) -> anyhow::Result<Vec<RecordId>> {
+        self.get_all(&digest)
+    }
+
+    fn insert(&self, digest: u128, rid: RecordId, unique: bool) -> anyhow::Result<bool> {
+        if unique {
+            ExtendibleHashTable::insert(self, &digest, &rid)
+        } else {
+            self.insert_entry(&digest, &rid)
+        }
+    }
+
+    fn remove(&self, digest: u128, rid: RecordId) -> anyhow::Result<bool> {
+        self.remove_entry(&digest, &rid)
+    }
+
+    fn free_pages(&self) -> anyhow::Result<()> {
+        ExtendibleHashTable::free_pages(self)
+    }
+}
+
+impl<'a> TableIndex<'a> {
+    /// Open the index `info` describes, whose columns have the types `key_types`.
+    pub fn open<B: BufferPoolManager + ?Sized>(
+        bpm: &'a B,
+        info: &IndexInfo,
+        key_types: Vec<DataType>,
+    ) -> anyhow::Result<Self> {
+        let entries = match info.kind {
+            IndexKind::BPlusTree => {
+                Entries::Tree(Box::new(BPlusTree::open(bpm, info.header_page_id)?))
+            }
+            IndexKind::Hash => Entries::Hash(Box::new(
+                ExtendibleHashTable::<u128, RecordId, B>::open(bpm, info.header_page_id)?,
+            )),
+        };
+        Ok(Self {
+            entries,
+            key_types,
+            unique: info.unique,
+        })
+    }
+
+    /// Create an index over the keys of the existing `rows`, read as they are added. A B+ tree
+    /// sorts them on `temp` before it is loaded bottom-up. On error, the pages of the index are
+    /// freed.
+    pub fn build<B, T>(
+        bpm: &'a B,
+        temp: &T,
+        kind: IndexKind,
+        unique: bool,
+        key_types: Vec<DataType>,
+        rows: impl IntoIterator<Item = anyhow::Result<(Vec<Value>, RecordId)>>,
+        config: BulkLoadConfig,
+    ) -> anyhow::Result<Self>
+    where
+        B: BufferPoolManager + ?Sized,
+        T: BufferPoolManager + ?Sized,
+    {
+        check_key_types(&key_types)?;
+        // The loaders take plain entries, so the first error ends them and is returned after.
+        let mut error = None;
+        let mut rows = rows.into_iter().map_while(|row| match row {
+            Ok(row) => Some(row),
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        });
+
+        let (entries, result) = match kind {
+            IndexKind::BPlusTree => {
+                let tree = BPlusTree::create(bpm)?;
+                let mut encode_error = None;
+                let entries = rows.map_while(|(values, rid)| {
+                    match tree_entry(&key_types, unique, &values, rid) {
+                        Ok(entry) => Some(entry.map(|key| (key, rid))),
+                        Err(e) => {
+                            encode_error = Some(e);
+                            None
+                        }
+                    }
+                });
+                let result = tree.bulk_load_with_temp(temp, entries.flatten(), config);
+                let result = result.and_then(|()| encode_error.map_or(Ok(()), Err));
+                (Entries::Tree(Box::new(tree)), result)
+            }
+            IndexKind::Hash => {
+                let table = ExtendibleHashTable::<u128, RecordId, B>::create(bpm)?;
+                let result = rows.try_for_each(|(values, rid)| {
+                    let Some(digest) = hash_digest(&key_types, &values)? else {
+                        return Ok(());
+                    };
+                    if !HashEntries::insert(&table, digest, rid, unique)? {
+                        anyhow::bail!("cannot build a unique index over duplicate keys");
+                    }
+                    Ok(())
+                });
+                (Entries::Hash(Box::new(table)), result)
+            }
+        };
+        let index = Self {
+            entries,
+            key_types,
+            unique,
+        };
+        if let Err(e) = result.and_then(|()| error.map_or(Ok(()), Err)) {
+            index.free_pages()?;
+            return Err(e);
+        }
+        Ok(index)
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        match &self.entries {
+            Entries::Tree(index) => index.kind(),
+            Entries::Hash(_) => IndexKind::Hash,
+        }
+    }
+
+    pub fn header_page_id(&self) -> PageId {
+        match &self.entries {
+            Entries::Tree(index) => index.header_page_id(),
+            Entries::Hash(index) => index.header_page_id(),
+        }
+    }
+
+    /// Free every page of the index for the buffer pool to reuse. Nobody else may use it.
+    pub fn free_pages(&self) -> anyhow::Result<()> {
+        match &self.entries {
+            Entries::Tree(index) => index.free_pages(),
+            Entries::Hash(index) => index.free_pages(),
+        }
+    }
+
+    /// Add the entry of row `rid` with the key `key`, the values of the indexed columns. Return
+    /// false if a unique index already has `key`.
+    pub fn insert(&self, key: &[Value], rid: RecordId) -> anyhow::Result<bool> {
+        match &self.entries {
+            Entries::Tree(index) => match tree_entry(&self.key_types, self.unique, key, rid)? {
+                Some(entry) => index.insert(&entry, &rid),
+                None => Ok(true),
+            },
+            Entries::Hash(index) => match hash_digest(&self.key_types, key)? {
+                Some(digest) => index.insert(digest, rid, self.unique),
+                None => Ok(true),
+            },
+        }
+    }
+
+    /// Remove the entry of row `rid`, returning whether it was present.
+    pub fn remove(&self, key: &[Value], rid: RecordId) -> anyhow::Result<bool> {
+        match &self.entries {
+            Entries::Tree(index) => {
+                let Some(entry) = tree_entry(&self.key_types, self.unique, key, rid)? else {
+                    return Ok(false);
+                };
+                if self.unique && index.get(&entry)? != Some(rid) {
+                    return Ok(false);
+                }
+                index.remove(&entry)
+            }
+            Entries::Hash(index) => match hash_digest(&self.key_types, key)? {
+                Some(digest) => index.remove(digest, rid),
+                None => Ok(false),
+            },
+        }
+    }
+
+    /// The rows whose key starts with `prefix`, the values of the first of the indexed columns.
+    /// A hash index takes the whole key. Nothing matches a NULL or a value that no value of its
+    /// column equals.
+    pub fn get(&self, prefix: &[Value]) -> anyhow::Result<Vec<RecordId>> {
+        match &self.entries {
+            Entries::Tree(index) if self.unique && prefix.len() == self.key_types.len() => {
+                let Some(mut key) = encode_prefix(&self.key_types, prefix) else {
+                    return Ok(Vec::new());
+                };
+                key.push(KEY_END);
+                Ok(index.get(&EncodedKey(key))?.into_iter().collect())
+            }
+            Entries::Tree(_) => {
+                if encode_prefix(&self.key_types, prefix).is_none() {
+                    return Ok(Vec::new());
+                }
+                self.scan(Bound::Included(prefix), Bound::Included(prefix))?
+                    .collect()
+            }
+            Entries::Hash(index) => {
+                if prefix.len() != self.key_types.len() {
+                    anyhow::bail!("a hash index is looked up by its whole key");
+                }
+                match encode_prefix(&self.key_types, prefix) {
+                    Some(key) => index.get(digest(&key)),
+                    None => Ok(Vec::new()),
+                }
+            }
+        }
+    }
+
+    /// The rows whose keys lie between the bounds, in key order. Each bound is a prefix of the
+    /// key, which the keys that start with it are equal to.
+    pub fn scan(
+        &self,
+        lower: Bound<&[Value]>,
+        upper: Bound<&[Value]>,
+    ) -> anyhow::Result<RecordIds<'_>> {
+        Ok(Box::new(
+            self.scan_entries(self.lower_bound(lower)?, upper)?
+                .map(|entry| entry.map(|(_, rid)| rid)),
+        ))
+    }
+
+    /// Where a scan with the lower bound `lower` starts, for [`Self::scan_entries`].
+    pub fn lower_bound(&self, lower: Bound<&[Value]>) -> anyhow::Result<Bound<EncodedKey>> {
+        Ok(match lower {
+            Bound::Included(prefix) => Bound::Included(EncodedKey(self.bound(prefix)?)),
+            Bound::Excluded(prefix) => {
+                let mut key = self.bound(prefix)?;
+                key.push(PREFIX_END);
+                Bound::Excluded(EncodedKey(key))
+            }
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+
+    /// The entries whose keys lie between the bounds, in entry order. The lower bound is an
+    /// encoded entry rather than a key, so that a scan can resume after the last entry it
+    /// returned.
+    pub fn scan_entries(
+        &self,
+        lower: Bound<EncodedKey>,
+        upper: Bound<&[Value]>,
+    ) -> anyhow::Result<IndexEntries<'_>> {
+        let Entries::Tree(index) = &self.entries else {
+            anyhow::bail!("hash indexes only support equality lookups");
+        };
+        let upper = match upper {
+            Bound::Included(prefix) => {
+                let mut key = self.bound(prefix)?;
+                key.push(PREFIX_END);
+                Bound::Excluded(EncodedKey(key))
+            }
+            Bound::Excluded(prefix) => Bound::Excluded(EncodedKey(self.bound(prefix)?)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        index.scan(lower, upper)
+    }
+
+    /// The encoding of `prefix` as a bound of a scan.
+    fn bound(&self, prefix: &[Value]) -> anyhow::Result<Vec<u8>> {
+        if prefix.len() > self.key_types.len() {
+            anyhow::bail!(
+                "cannot bound a key of {} columns by {} values",
+                self.key_types.len(),
+                prefix.len()
+            );
+        }
+        encode_prefix(&self.key_types, prefix).ok_or_else(|| {
+            anyhow::anyhow!("cannot bound an index scan by {}", display_values(prefix))
+        })
+    }
+}
+
+/// `value` as a value of a column of `data_type` for an index key, or None if it is NULL or no
+/// value of the column is equal to it. Integers are widened to `i64` and decimals take the scale
+/// of the column, so that equal values have the same encoding.
+pub fn exact_key(value: &Value, data_type: DataType) -> Option<Value> {
+    match (value, data_type) {
+        (value, data_type) if data_type.is_integer() => value.as_i64().map(Value::Int64),
+        // Zero has a single encoding, as -0 equals 0.
+        (Value::Float64(v), DataType::Float64) => Some(Value::Float64(v + 0.0)),
+        (Value::Decimal(d), DataType::Decimal { scale, .. }) => {
+            let rescaled = d.rescale(scale)?;
+            (rescaled == *d).then_some(Value::Decimal(rescaled))
+        }
+        (value, DataType::Decimal { scale, .. }) => {
+            let d = Decimal::from_i64(value.as_i64()?);
+            d.rescale(scale).map(Value::Decimal)
+        }
+        (Value::Null, _) => None,
+        (value, data_type) => (value.data_type().map(|t| t.type_id()) == Some(data_type.type_id()))
+            .then(|| value.clone()),
+    }
+}
+
+/// Fail if an index cannot have keys of `key_types`.
+pub fn check_key_types(key_types: &[DataType]) -> anyhow::Result<()> {
+    if key_types.is_empty() {
+        anyhow::bail!("an index needs at least one column");
+    }
+    Ok(())
+}
+
+fn check_key_len(key_types: &[DataType], values: &[Value]) -> anyhow::Result<()> {
+    if values.len() != key_types.len() {
+        anyhow::bail!(
+            "expected a key of {} columns, got {}",
+            key_types.len(),
+            values.len()
+        );
+    }
+    Ok(())
+}
+
+/// The entry of a B+ tree index for the key `values` of row `rid`, or None if the key has a
+/// NULL.
+fn tree_entry(
+    key_types: &[DataType],
+    unique: bool,
+    values: &[Value],
+    rid: RecordId,
+) -> anyhow::Result<Option<EncodedKey>> {
+    check_key_len(key_types, values)?;
+    if values.iter().any(Value::is_null) {
+        return Ok(None);
+    }
+    let Some(mut key) = encode_prefix(key_types, values) else {
+        anyhow::bail!("cannot index {}", display_values(values));
+    };
+    key.push(KEY_END);
+    if !unique {
+        rid.encode(&mut key);
+    }
+    Ok(Some(EncodedKey(key)))
+}
+
+/// The digest a hash index stores for the key `values`, or None if the key has a NULL.
+fn hash_digest(key_types: &[DataType], values: &[Value]) -> anyhow::Result<Option<u128>> {
+    check_key_len(key_types, values)?;
+    if values.iter().any(Value::is_null) {
+        return Ok(None);
+    }
+    let Some(key) = encode_prefix(key_types, values) else {
+        anyhow::bail!("cannot index {}", display_values(values));
+    };
+    Ok(Some(digest(&key)))
+}
+
+/// Hash an encoded key with 128-bit FNV-1a, wide enough that distinct keys are not expected to
+/// ever collide.
+fn digest(key: &[u8]) -> u128 {
+    let mut hash = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58du128;
+    for &byte in key {
+        hash ^= byte as u128;
+        hash = hash.wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+    }
+    hash
+}
+
+/// The encoding of the values of the first of the columns of `key_types`, or None if one of
+/// them has no [exact](exact_key) value in its column.
+fn encode_prefix(key_types: &[DataType], values: &[Value]) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    for (value, data_type) in values.iter().zip(key_types) {
+        buf.push(VALUE_MARKER);
+        match exact_key(value, *data_type)? {
+            Value::Boolean(v) => buf.push(v as u8),
+            Value::Int64(v) => v.encode(&mut buf),
+            Value::Float64(v) => {
+                let bits = v.to_bits();
+                let ordered = if bits >> 63 == 1 {
+                    !bits
+                } else {
+                    bits | 1 << 63
+                };
+                ordered.encode(&mut buf);
+            }
+            Value::Decimal(d) => {
+                let ordered = (d.mantissa() as u128) ^ (1 << 127);
+                buf.extend_from_slice(&ordered.to_be_bytes());
+            }
+            Value::Varchar(s) => s.encode(&mut buf),
+            Value::Bytea(b) => b.encode(&mut buf),
+            Value::Date(d) => d.0.encode(&mut buf),
+            Value::Timestamp(t) => t.0.encode(&mut buf),
+            Value::Null | Value::Int8(_) | Value::Int16(_) | Value::Int32(_) => {
+                unreachable!("exact keys are not NULL and their integers are widened")
+            }
+        }
+    }
+    Some(buf)
+}
+
+fn display_values(values: &[Value]) -> String {
+    let values: Vec<String> = values.iter().map(Value::to_string).collect();
+    format!("({})", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+            page::slotted_page::SlotId,
+        },
+    };
+
+    use super::*;
+
+    fn rid(i: u32) -> RecordId {
+        RecordId::new(PageId::new(1 + i as usize / 100), SlotId::new(i % 100))
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Varchar(s.to_string())
+    }
+
+    #[test]
+    fn test_composite_keys() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let key_types = vec![DataType::Varchar(Some(16)), DataType::Int32];
+        // Names that share prefixes and hold zero bytes, which the encoding escapes.
+        let names = ["b", "a\0", "ab", "a", "", "b\0b"];
+        let rows: Vec<_> = (0..60)
+            .map(|i| {
+                let key = vec![text(names[i as usize % names.len()]), Value::Int32(i / 6)];
+                Ok((key, rid(i as u32)))
+            })
+            .chain([Ok((vec![Value::Null, Value::Int32(0)], rid(99)))])
+            .collect();
+        let index = TableIndex::build(
+            &bpm,
+            &bpm,
+            IndexKind::BPlusTree,
+            false,
+            key_types,
+            rows,
+            BulkLoadConfig::new(),
+        )
+        .unwrap();
+
+        // A prefix of the key finds every entry that starts with it, in key order.
+        assert_eq!(index.get(&[text("a")]).unwrap().len(), 10);
+        assert_eq!(index.get(&[text("")]).unwrap().len(), 10);
+        assert_eq!(index.get(&[text("a"), Value::Int32(3)]).unwrap(), [rid(21)]);
+        // A probe of another integer type equals the column's values.
+        assert_eq!(index.get(&[text("a"), Value::Int64(3)]).unwrap(), [rid(21)]);
+        assert!(index.get(&[text("c")]).unwrap().is_empty());
+        assert!(index.get(&[Value::Null]).unwrap().is_empty());
+
+        let scan = |lower: Bound<&[Value]>, upper: Bound<&[Value]>| -> Vec<RecordId> {
+            index
+                .scan(lower, upper)
+                .unwrap()
+                .collect::<anyhow::Result<_>>()
+                .unwrap()
+        };
+        let all = scan(Bound::Unbounded, Bound::Unbounded);
+        assert_eq!(all.len(), 60);
+        // "" < "a" < "a\0" < "ab" < "b" < "b\0b", and then by the second column.
+        let first: Vec<u32> = all[..10].iter().map(|rid| rid.slot_id.as_u32()).collect();
+        assert_eq!(first, [4, 10, 16, 22, 28, 34, 40, 46, 52, 58]);
+        assert_eq!(all[10], rid(3));
+
+        let a = [text("a")];
+        let ab = [text("ab")];
+        assert_eq!(
+            scan(Bound::Excluded(&a), Bound::Included(&ab)).len(),
+            20,
+            "\"a\\0\" and \"ab\""
+        );
+        assert_eq!(scan(Bound::Included(&a), Bound::Excluded(&ab)).len(), 20);
+        let a5 = [text("a"), Value::Int32(5)];
+        assert_eq!(
+            scan(Bound::Included(&a5), Bound::Included(&a)),
+            [rid(33), rid(39), rid(45), rid(51), rid(57)]
+        );
+
+        // Resuming after the last entry returned skips nothing and repeats nothing.
+        let mut resumed = Vec::new();
+        let mut lower = index.lower_bound(Bound::Included(&a)).unwrap();
+        loop {
+            let batch: Vec<_> = index
+                .scan_entries(lower.clone(), Bound::Included(&ab))
+                .unwrap()
+                .take(3)
+                .collect::<anyhow::Result<_>>()
+                .unwrap();
+            let Some((last, _)) = batch.last() else {
+                break;
+            };
+            lower = Bound::Excluded(last.clone());
+            resumed.extend(batch.into_iter().map(|(_, rid)| rid));
+        }
+        assert_eq!(resumed, scan(Bound::Included(&a), Bound::Included(&ab)));
+        assert_eq!(resumed.len(), 30);
+
+        let key = [text("a"), Value::Int32(3)];
+        assert!(index.insert(&key, rid(98)).unwrap());
+        assert_eq!(index.get(&key).unwrap(), [rid(21), rid(98)]);
+        assert!(index.remove(&key, rid(21)).unwrap());
+        assert!(!index.remove(&key, rid(21)).unwrap());
+        assert_eq!(index.get(&key).unwrap(), [rid(98)]);
+        // NULL keys are not indexed.
+        assert!(index
+            .insert(&[Value::Null, Value::Int32(1)], rid(97))
+            .unwrap());
+        assert!(!index
+            .remove(&[Value::Null, Value::Int32(1)], rid(97))
+            .unwrap());
+        assert!(index.insert(&[text("a")], rid(96)).is_err());
+    }
+
+    #[test]
+    fn test_unique_keys_of_every_type() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let decimal = |s| Value::Decimal(Decimal::parse(s).unwrap());
+        let columns = [
+            (
+                DataType::Decimal {
+                    precision: 10,
+                    scale: 2,
+                },
+                vec![
+                    decimal("-3.5"),
+                    decimal("-0.01"),
+                    decimal("0"),
+                    decimal("12.25"),
+                ],
+            ),
+            (
+                DataType::Float64,
+                vec![
+                    Value::Float64(f64::NEG_INFINITY),
+                    Value::Float64(-2.5),
+                    Value::Float64(-0.0),
+                    Value::Float64(1e-300),
+                    Value::Float64(7.0),
+                ],
+            ),
+            (
+                DataType::Bytea,
+                vec![
+                    Value::Bytea(vec![]),
+                    Value::Bytea(vec![0]),
+                    Value::Bytea(vec![0, 0xff]),
+                    Value::Bytea(vec![1]),
+                ],
+            ),
+            (
+                DataType::Timestamp,
+                vec![
+                    Value::Timestamp(crate::types::date_time::Timestamp(-5)),
+                    Value::Timestamp(crate::types::date_time::Timestamp(0)),
+                    Value::Timestamp(crate::types::date_time::Timestamp(5)),
+                ],
+            ),
+            (
+                DataType::Boolean,
+                vec![Value::Boolean(false), Value::Boolean(true)],
+            ),
+        ];
+        for (data_type, values) in columns {
+            // Inserted out of order, the values come back sorted.
+            let rows = values
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, value)| Ok((vec![value.clone()], rid(i as u32))));
+            let index = TableIndex::build(
+                &bpm,
+                &bpm,
+                IndexKind::BPlusTree,
+                true,
+                vec![data_type],
+                rows,
+                BulkLoadConfig::new(),
+            )
+            .unwrap();
+            let sorted: Vec<RecordId> = index
+                .scan(Bound::Unbounded, Bound::Unbounded)
+                .unwrap()
+                .collect::<anyhow::Result<_>>()
+                .unwrap();
+            let expected: Vec<RecordId> = (0..values.len() as u32).map(rid).collect();
+            assert_eq!(sorted, expected, "{data_type}");
+            for (i, value) in values.iter().enumerate() {
+                assert_eq!(index.get(&[value.clone()]).unwrap(), [rid(i as u32)]);
+                assert!(!index.insert(&[value.clone()], rid(99)).unwrap());
+            }
+        }
+
+        // Equal decimals of another scale, and integers, find the same key; a value the column
+        // cannot hold finds nothing.
+        let index = TableIndex::build(
+            &bpm,
+            &bpm,
+            IndexKind::BPlusTree,
+            true,
+            vec![DataType::Decimal {
+                precision: 10,
+                scale: 2,
+            }],
+            [Ok((vec![decimal("2.5")], rid(0)))],
+            BulkLoadConfig::new(),
+        )
+        .unwrap();
+        assert_eq!(index.get(&[decimal("2.500")]).unwrap(), [rid(0)]);
+        assert!(index.get(&[decimal("2.501")]).unwrap().is_empty());
+        let index = TableIndex::build(
+            &bpm,
+            &bpm,
+            IndexKind::BPlusTree,
+            true,
+            vec![DataType::Decimal {
+                precision: 10,
+                scale: 0,
+            }],
+            [Ok((vec![decimal("7")], rid(0)))],
+            BulkLoadConfig::new(),
+        )
+        .unwrap();
+        assert_eq!(index.get(&[Value::Int16(7)]).unwrap(), [rid(0)]);
+        // Zero has one key whatever its sign.
+        let index = TableIndex::build(
+            &bpm,
+            &bpm,
+            IndexKind::BPlusTree,
+            true,
+            vec![DataType::Float64],
+            [Ok((vec![Value::Float64(-0.0)], rid(0)))],
+            BulkLoadConfig::new(),
+        )
+        .unwrap();
+        assert_eq!(index.get(&[Value::Float64(0.0)]).unwrap(), [rid(0)]);
+
+        let duplicates = [1, 2, 1].map(|i| Ok((vec![Value::Int64(i)], rid(i as u32))));
+        assert!(TableIndex::build(
+            &bpm,
+            &bpm,
+            IndexKind::BPlusTree,
+            true,
+            vec![DataType::Int64],
+            duplicates,
+            BulkLoadConfig::new(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_hash_keys() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let build = |unique, key_types, rows: Vec<Vec<Value>>| {
+            let rows = rows
+                .into_iter()
+                .enumerate()
+                .map(|(i, key)| Ok((key, rid(i as u32))));
+            TableIndex::build(
+                &bpm,
+                &bpm,
+                IndexKind::Hash,
+                unique,
+                key_types,
+                rows,
+                BulkLoadConfig::new(),
+            )
+        };
+
+        let index = build(
+            true,
+            vec![DataType::Int32],
+            (0..10).map(|i| vec![Value::Int32(i)]).collect(),
+        )
+        .unwrap();
+        assert_eq!(index.kind(), IndexKind::Hash);
+        assert_eq!(index.get(&[Value::Int64(4)]).unwrap(), [rid(4)]);
+        assert!(index.get(&[Value::Int64(1 << 40)]).unwrap().is_empty());
+        assert!(!index.insert(&[Value::Int32(4)], rid(50)).unwrap());
+        assert!(!index.remove(&[Value::Int32(4)], rid(50)).unwrap());
+        assert!(index.remove(&[Value::Int32(4)], rid(4)).unwrap());
+        assert!(index.get(&[Value::Int32(4)]).unwrap().is_empty());
+        assert!(index.scan(Bound::Unbounded, Bound::Unbounded).is_err());
+
+        // Non-unique keys of any type keep every row.
+        let names = ["ann", "bob", "ann", "cy", "ann"];
+        let index = build(
+            false,
+            vec![DataType::Varchar(None)],
+            names.iter().map(|name| vec![text(name)]).collect(),
+        )
+        .unwrap();
+        let mut rids = index.get(&[text("ann")]).unwrap();
+        rids.sort();
+        assert_eq!(rids, [rid(0), rid(2), rid(4)]);
+        assert!(index.insert(&[text("ann")], rid(9)).unwrap());
+        assert!(!index.insert(&[text("ann")], rid(9)).unwrap());
+        assert!(index.remove(&[text("ann")], rid(2)).unwrap());
+        assert!(!index.remove(&[text("ann")], rid(2)).unwrap());
+        let mut rids = index.get(&[text("ann")]).unwrap();
+        rids.sort();
+        assert_eq!(rids, [rid(0), rid(4), rid(9)]);
+        assert!(index.get(&[text("dee")]).unwrap().is_empty());
+        assert!(index.insert(&[Value::Null], rid(10)).unwrap());
+        assert!(index.get(&[Value::Null]).unwrap().is_empty());
+
+        // Composite keys are looked up by all their columns.
+        let index = build(
+            true,
+            vec![DataType::Int32, DataType::Varchar(None)],
+            vec![
+                vec![Value::Int32(1), text("a")],
+                vec![Value::Int32(1), text("b")],
+                vec![Value::Int32(2), text("a")],
+            ],
+        )
+        .unwrap();
+        assert_eq!(index.get(&[Value::Int32(1), text("b")]).unwrap(), [rid(1)]);
+        assert!(index.get(&[Value::Int32(2), text("b")]).unwrap().is_empty());
+        assert_eq!(
+            index.get(&[Value::Int32(1)]).unwrap_err().to_string(),
+            "a hash index is looked up by its whole key"
+        );
+        assert!(!index.insert(&[Value::Int32(2), text("a")], rid(7)).unwrap());
+
+        assert_eq!(
+            build(
+                true,
+                vec![DataType::Varchar(None)],
+                names.iter().map(|name| vec![text(name)]).collect(),
+            )
+            .err()
+            .unwrap()
+            .to_string(),
+            "cannot build a unique index over duplicate keys"
+        );
+    }
+
+    #[test]
+    fn test_build_streams_rows_through_temp() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let temp_disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db.tmp")).unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let config = BulkLoadConfig::new().with_sort_memory(100);
+        let rows = (0..2000).map(|i| {
+            let key = vec![text(&format!("key-{:04}", (i * 7919) % 2000))];
+            Ok((key, rid(i)))
+        });
+        let index = TableIndex::build(
+            &bpm,
+            &temp,
+            IndexKind::BPlusTree,
+            true,
+            vec![DataType::Varchar(None)],
+            rows,
+            config,
+        )
+        .unwrap();
+        // The sorted runs spilled to the temp pool rather than the database.
+        assert!(temp.disk_manager().num_pages() > 0);
+        let low = [text("key-0100")];
+        let high = [text("key-0199")];
+        let keys = index
+            .scan(Bound::Included(&low), Bound::Included(&high))
+            .unwrap();
+        assert_eq!(keys.count(), 100);
+
+        // An error reading the rows stops the build and is returned.
+        let rows = (0..2000).map(|i| {
+            if i == 1500 {
+                anyhow::bail!("cannot read row {i}");
+            }
+            Ok((vec![text(&format!("key-{i}"))], rid(i)))
+        });
+        let error = TableIndex::build(
+            &bpm,
+            &temp,
+            IndexKind::BPlusTree,
+            true,
+            vec![DataType::Varchar(None)],
+            rows,
+            config,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error.to_string(), "cannot read row 1500");
+    }
+
+    #[test]
+    fn test_failed_builds_free_their_pages() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let keys = |n: u32| (0..n).map(|i| Ok((vec![text(&format!("key-{i:04}"))], rid(i))));
+        let cases = [
+            (
+                IndexKind::BPlusTree,
+                DataType::Varchar(None),
+                keys(2000).chain(keys(1)).collect::<Vec<_>>(),
+                "cannot bulk load duplicate keys into a B+ tree",
+            ),
+            (
+                IndexKind::BPlusTree,
+                DataType::Varchar(None),
+                keys(2000)
+                    .chain([Ok((vec![text(&"x".repeat(30000))], rid(0)))])
+                    .collect(),
+                "key of 30004 bytes exceeds the maximum of 1003 bytes for this B+ tree",
+            ),
+            (
+                IndexKind::BPlusTree,
+                DataType::Varchar(None),
+                keys(2000)
+                    .chain([Err(anyhow::anyhow!("cannot read the row"))])
+                    .collect(),
+                "cannot read the row",
+            ),
+            (
+                IndexKind::Hash,
+                DataType::Int32,
+                (0..2000)
+                    .chain([0])
+                    .map(|i| Ok((vec![Value::Int32(i)], rid(i as u32))))
+                    .collect(),
+                "cannot build a unique index over duplicate keys",
+            ),
+        ];
+        for (i, (kind, key_type, rows, error)) in cases.into_iter().enumerate() {
+            let disk_manager =
+                LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join(format!("{i}.db")))
+                    .unwrap();
+            let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+            let result = TableIndex::build(
+                &bpm,
+                &bpm,
+                kind,
+                true,
+                vec![key_type],
+                rows,
+                BulkLoadConfig::new(),
+            );
+            assert_eq!(result.err().unwrap().to_string(), error);
+            // Every page the build allocated is reused before the file grows.
+            let page_ids: Vec<_> = (0..200)
+                .map(|_| bpm.new_page_write().unwrap().page_id().as_usize())
+                .collect();
+            assert_eq!(page_ids, (0..200).collect::<Vec<_>>(), "{error}");
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod connection;
+#[allow(clippy::module_inception)]
+pub mod database;
+pub mod row;
+pub mod statement;
@@ -0,0 +1,136 @@
+//! Running SQL against a [`Database`].
+
+use crate::{
+    catalog::schema::Schema,
+    execution::{
+        builder::build_executor,
+        context::ExecutorContext,
+        ddl::{execute_ddl, is_ddl},
+    },
+    optimizer::optimizer::Optimizer,
+    planner::{binder::Binder, logical_plan::LogicalPlan},
+    sql::{
+        ast,
+        parser::{parse, parse_prepared},
+    },
+    types::value::Value,
+};
+
+use super::{
+    database::Database,
+    row::{FromRow, Rows},
+    statement::{Params, Statement},
+};
+
+/// A handle to run statements on a [`Database`] with.
+///
+/// Every statement is parsed, bound, optimized and run as in the shell. Statements that change
+/// the database flush the log once they are done, so that what is reported done survives a
+/// crash. Errors in the SQL are [`SqlError`](crate::sql::error::SqlError)s, which
+/// can be rendered against the statement.
+#[derive(Clone, Copy)]
+pub struct Connection<'db> {
+    db: &'db Database,
+}
+
+impl<'db> Connection<'db> {
+    pub(super) fn new(db: &'db Database) -> Self {
+        Self { db }
+    }
+
+    pub fn database(&self) -> &'db Database {
+        self.db
+    }
+
+    /// Run a single statement with `params` and return the number of rows it inserted,
+    /// updated or deleted, which is 0 for other statements.
+    pub fn execute(&self, sql: &str, params: impl Params) -> anyhow::Result<u64> {
+        self.prepare(sql)?.execute(params)
+    }
+
+    /// Run every statement of `sql`, which take no parameters, until one fails.
+    pub fn execute_batch(&self, sql: &str) -> anyhow::Result<()> {
+        for statement in parse(sql)? {
+            self.run(&statement, Vec::new())?.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Run a single statement with `params` and return its rows.
+    pub fn query(&self, sql: &str, params: impl Params) -> anyhow::Result<Rows<'db>> {
+        self.prepare(sql)?.query(params)
+    }
+
+    /// Run a single query with `params` and convert each of its rows with [`FromRow`].
+    pub fn query_as<T: FromRow>(&self, sql: &str, params: impl Params) -> anyhow::Result<Vec<T>> {
+        self.query(sql, params)?.decode().collect()
+    }
+
+    /// Parse a single statement to run later, any number of times, with different parameters.
+    pub fn prepare(&self, sql: &str) -> anyhow::Result<Statement<'db>> {
+        let (statement, parameters) = parse_prepared(sql)?;
+        Ok(Statement::new(*self, statement, parameters))
+    }
+
+    /// The columns `statement` produces with `parameters`, found by binding it without running
+    /// it. Modifications produce the number of rows they changed, and other statements without
+    /// rows none.
+    pub(crate) fn describe(
+        &self,
+        statement: &ast::Statement,
+        parameters: Vec<Value>,
+    ) -> anyhow::Result<Schema> {
+        let plan = Binder::new(&self.db.catalog())
+            .with_parameters(parameters)
+            .bind(statement)?;
+        Ok(plan.schema())
+    }
+
+    /// Bind `statement` to `parameters` and start running it.
+    pub(crate) fn run(
+        &self,
+        statement: &ast::Statement,
+        parameters: Vec<Value>,
+    ) -> anyhow::Result<Rows<'db>> {
+        let catalog = &self.db.catalog();
+        let options = self.db.options();
+        // Declared first to be dropped last, once the executor is done with the temp space.
+        let temp_statement = self.db.temp().begin_statement();
+        let plan = Binder::new(catalog)
+            .with_parameters(parameters)
+            .bind(statement)?;
+        if is_ddl(&plan) {
+            execute_ddl(catalog, self.db.temp(), &plan, &options.analyze)?;
+            self.db.commit()?;
+            return Ok(Rows::empty());
+        }
+
+        let plan = Optimizer::new(catalog)
+            .with_work_mem(options.work_mem)
+            .optimize(plan)?;
+        let ctx = ExecutorContext::new(catalog, self.db.temp()).with_work_mem(options.work_mem);
+        let mut executor = build_executor(&ctx, &plan)?;
+        executor.init()?;
+        if !matches!(
+            plan,
+            LogicalPlan::Insert { .. } | LogicalPlan::Update { .. } | LogicalPlan::Delete { .. }
+        ) {
+            return Ok(Rows::new(executor, temp_statement));
+        }
+
+        // Modifications run to the end here, to be committed before they are reported done.
+        let mut rows = Vec::new();
+        while let Some(tuple) = executor.next()? {
+            rows.push(tuple.into_values());
+        }
+        let schema = executor.schema().clone();
+        drop(executor);
+        self.db.commit()?;
+        let count = rows
+            .first()
+            .and_then(|row| row.first())
+            .and_then(Value::as_i64)
+            .unwrap_or(0);
+        Ok(Rows::changed(&schema, rows, count as u64))
+    }
+}
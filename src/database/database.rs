@@ -0,0 +1,583 @@
+//! A database opened in-process, for programs that embed limebase.
+//!
+//! [`Database::open`] wires up the disk manager, the buffer pools and the catalog that the
+//! rest of the crate takes as borrowed pieces, and owns them all. Statements are run through a
+//! [`Connection`]:
+//!
+//! ```no_run
+//! use limebase::{Database, Options};
+//!
+//! let db = Database::open("app.db", Options::new())?;
+//! let conn = db.connect();
+//! conn.execute("CREATE TABLE users (id BIGINT NOT NULL, name TEXT)", ())?;
+//! conn.execute("INSERT INTO users VALUES ($1, $2)", (1, "alice"))?;
+//! for row in conn.query("SELECT name FROM users WHERE id = ?", (1,))? {
+//!     let name: String = row?.get(0)?;
+//!     println!("{name}");
+//! }
+//! # anyhow::Ok(())
+//! ```
+
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+    catalog::catalog::{file_header_page_id, Catalog, SharedCatalog, FREE_PAGE_MAP_OFFSET},
+    execution::{analyze::AnalyzeConfig, context::DEFAULT_WORK_MEM},
+    recovery::{
+        log_manager::LogManager,
+        recovery::{checkpoint, commit, recover},
+    },
+    storage::{
+        disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+        page::page::DEFAULT_PAGE_SIZE,
+    },
+};
+
+use super::connection::Connection;
+
+/// Options for [`Database::open`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// The pages of the database kept in memory.
+    pub pool_size: usize,
+    /// The pages of sort runs and spilled hash tables kept in memory.
+    pub temp_pool_size: usize,
+    /// The number of tuples a single operator may keep in memory.
+    pub work_mem: usize,
+    /// Create the database if the file does not exist or is empty.
+    pub create_if_missing: bool,
+    /// How `ANALYZE` samples tables.
+    pub analyze: AnalyzeConfig,
+    /// The bytes the write-ahead log grows to before a statement that changes the database
+    /// checkpoints it.
+    pub checkpoint_size: u64,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            pool_size: 1024,
+            temp_pool_size: 256,
+            work_mem: DEFAULT_WORK_MEM,
+            create_if_missing: true,
+            analyze: AnalyzeConfig::new(),
+            checkpoint_size: 16 << 20,
+        }
+    }
+
+    pub fn with_pool_size(self, pool_size: usize) -> Self {
+        assert!(pool_size > 0, "pool_size must be positive");
+        Self { pool_size, ..self }
+    }
+
+    pub fn with_temp_pool_size(self, temp_pool_size: usize) -> Self {
+        assert!(temp_pool_size > 0, "temp_pool_size must be positive");
+        Self {
+            temp_pool_size,
+            ..self
+        }
+    }
+
+    pub fn with_work_mem(self, work_mem: usize) -> Self {
+        assert!(work_mem > 0, "work_mem must be positive");
+        Self { work_mem, ..self }
+    }
+
+    pub fn with_create_if_missing(self, create_if_missing: bool) -> Self {
+        Self {
+            create_if_missing,
+            ..self
+        }
+    }
+
+    pub fn with_analyze(self, analyze: AnalyzeConfig) -> Self {
+        Self { analyze, ..self }
+    }
+
+    pub fn with_checkpoint_size(self, checkpoint_size: u64) -> Self {
+        Self {
+            checkpoint_size,
+            ..self
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Database {
+    bpm: BufferPoolManagerImpl,
+    temp: TempBufferPool,
+    log: Arc<LogManager>,
+    catalog: Arc<SharedCatalog>,
+    options: Options,
+}
+
+impl Database {
+    /// Open the database in the file at `path`, creating it if it does not exist and
+    /// [`Options::create_if_missing`] is set. The write-ahead log is kept in a file next to it
+    /// with `.wal` appended to its name, and sort runs and spilled hash tables in one with
+    /// `.tmp` appended. Changes in the log that did not reach the file before the database
+    /// was last closed are redone.
+    pub fn open(path: impl AsRef<Path>, options: Options) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let exists = path.metadata().is_ok_and(|metadata| metadata.len() > 0);
+        if !exists && !options.create_if_missing {
+            anyhow::bail!("database {} does not exist", path.display());
+        }
+        let log_path = LogManager::path_for(path);
+        if !exists && log_path.exists() {
+            // The log of a database that is gone, which must not be replayed into a new one.
+            std::fs::remove_file(&log_path)?;
+        }
+        let log = Arc::new(LogManager::open(log_path)?);
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, path)?;
+        if exists {
+            recover(&disk_manager, &log)?;
+        }
+        let bpm = BufferPoolManagerImpl::new(options.pool_size, disk_manager)
+            .with_log_manager(log.clone())
+            .with_free_page_map(file_header_page_id(), FREE_PAGE_MAP_OFFSET)?;
+        let temp = TempBufferPool::new(
+            options.temp_pool_size,
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, TempDiskManager::path_for(path))?,
+        );
+        let catalog = if exists {
+            Catalog::open(&bpm)?.shared()
+        } else {
+            Catalog::create(&bpm)?.shared()
+        };
+        // The redone pages and the new catalog are on disk from here, and the log is empty.
+        checkpoint(&bpm)?;
+        Ok(Self {
+            bpm,
+            temp,
+            log,
+            catalog,
+            options,
+        })
+    }
+
+    /// A new connection to run statements with.
+    pub fn connect(&self) -> Connection<'_> {
+        Connection::new(self)
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// The catalog, read through the buffer pool of the database. Every catalog of a database
+    /// sees the changes made through the others.
+    pub fn catalog(&self) -> Catalog<'_, BufferPoolManagerImpl> {
+        Catalog::from_shared(&self.bpm, self.catalog.clone())
+    }
+
+    /// The buffer pool of temporary pages for operators that spill.
+    pub fn temp(&self) -> &TempBufferPool {
+        &self.temp
+    }
+
+    /// The write-ahead log that dirty pages are written behind.
+    pub fn log(&self) -> &LogManager {
+        &self.log
+    }
+
+    /// Write every dirty page to the database file and truncate the log, which then only has
+    /// to be redone from here.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        checkpoint(&self.bpm)
+    }
+
+    /// Make the changes made so far durable, and checkpoint once the log has grown past
+    /// [`Options::checkpoint_size`]. The pages of the tables and indexes dropped earlier are
+    /// freed first if the queries that read them are done.
+    pub(super) fn commit(&self) -> anyhow::Result<()> {
+        self.catalog().free_dropped()?;
+        commit(&self.bpm)?;
+        if self.log.size() > self.options.checkpoint_size {
+            checkpoint(&self.bpm)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        // A database closed cleanly leaves nothing in the log to redo. If this fails, the log
+        // is redone when the database is opened again. No query is running anymore, so the
+        // pages of every dropped table and index can be freed.
+        let _ = self.catalog().free_dropped();
+        let _ = checkpoint(&self.bpm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManager, named_params, params,
+        recovery::log_manager::INVALID_LSN, sql::error::SqlError, storage::table::tuple::Tuple,
+        types::value::Value, FromRow, Page, PageId, Row,
+    };
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i64,
+        name: Option<String>,
+    }
+
+    impl FromRow for User {
+        fn from_row(row: &Row) -> anyhow::Result<Self> {
+            Ok(Self {
+                id: row.get("id")?,
+                name: row.get("name")?,
+            })
+        }
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_database() {
+        assert_send_sync::<Database>();
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        let error = Database::open(&path, Options::new().with_create_if_missing(false))
+            .err()
+            .unwrap();
+        assert!(error.to_string().ends_with("test.db does not exist"));
+
+        let options = Options::new().with_pool_size(16).with_temp_pool_size(4);
+        {
+            let db = Database::open(&path, options).unwrap();
+            let conn = db.connect();
+            conn.execute_batch(
+                "CREATE TABLE users (id BIGINT NOT NULL, name TEXT, score DOUBLE);
+                 CREATE UNIQUE INDEX users_id ON users (id);",
+            )
+            .unwrap();
+            let insert = conn
+                .prepare("INSERT INTO users VALUES ($1, $2, $3)")
+                .unwrap();
+            assert_eq!(insert.parameter_count(), 3);
+            for (id, name) in [(1, Some("alice")), (2, None), (3, Some("carol"))] {
+                assert_eq!(insert.execute((id, name, id as f64 / 2.0)).unwrap(), 1);
+            }
+            assert_eq!(
+                conn.execute(
+                    "UPDATE users SET name = :name WHERE id = :id OR name = :name",
+                    named_params! { ":name": "bob", "id": 2 },
+                )
+                .unwrap(),
+                1
+            );
+        }
+
+        // Everything is in the file once the database is dropped.
+        let db = Database::open(&path, options.with_create_if_missing(false)).unwrap();
+        assert_eq!(db.log().path(), LogManager::path_for(&path));
+        assert!(db.log().path().exists());
+        let conn = db.connect();
+        let mut rows = conn
+            .query(
+                "SELECT id, name, score FROM users WHERE id > ? ORDER BY id",
+                [1],
+            )
+            .unwrap();
+        assert_eq!(rows.columns(), ["id", "name", "score"]);
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 2);
+        assert_eq!(row.get::<i8>("ID").unwrap(), 2);
+        assert_eq!(row.get::<String>("name").unwrap(), "bob");
+        assert_eq!(row.get::<f64>(2).unwrap(), 1.0);
+        assert_eq!(row.value(1).unwrap(), &Value::Varchar("bob".to_string()));
+        assert_eq!(
+            rows.next()
+                .unwrap()
+                .unwrap()
+                .get::<Option<String>>(1)
+                .unwrap(),
+            Some("carol".to_string())
+        );
+        assert!(rows.next().is_none());
+
+        assert_eq!(
+            conn.query_as::<User>("SELECT * FROM users WHERE id <= ?", params![2])
+                .unwrap(),
+            vec![
+                User {
+                    id: 1,
+                    name: Some("alice".to_string())
+                },
+                User {
+                    id: 2,
+                    name: Some("bob".to_string())
+                },
+            ]
+        );
+        let count = conn
+            .prepare("SELECT count(*), max(id) FROM users WHERE name <> ?")
+            .unwrap();
+        assert_eq!(
+            count.query_as::<(i64, i64)>(["alice"]).unwrap(),
+            vec![(2, 3)]
+        );
+        assert_eq!(count.query_as::<(i64, i64)>(["bob"]).unwrap(), vec![(2, 3)]);
+        assert_eq!(
+            conn.execute("DELETE FROM users WHERE score < $1", (1.0,))
+                .unwrap(),
+            1
+        );
+
+        // Errors in the SQL keep their span, and the values must match the parameters.
+        let error = conn.query("SELECT nope FROM users", ()).err().unwrap();
+        let error = error.downcast_ref::<SqlError>().unwrap();
+        assert_eq!(error.message, "column \"nope\" does not exist");
+        assert_eq!(
+            conn.execute("SELECT ?, ?", (1,)).unwrap_err().to_string(),
+            "the statement has 2 parameters, but 1 values were given"
+        );
+        assert_eq!(
+            conn.execute("SELECT :a", named_params! { ":b": 1 })
+                .unwrap_err()
+                .to_string(),
+            "the statement has no parameter :b"
+        );
+        let error = conn
+            .query_as::<(i32,)>("SELECT name FROM users", ())
+            .unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "cannot get column \"name\": cannot convert VARCHAR bob to i32"
+        );
+    }
+
+    #[test]
+    fn test_temp_space_is_recycled_after_each_statement() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = Options::new().with_temp_pool_size(4).with_work_mem(8);
+        let db = Database::open(tempdir.path().join("test.db"), options).unwrap();
+        let conn = db.connect();
+        conn.execute_batch("CREATE TABLE t (id BIGINT NOT NULL, name TEXT)")
+            .unwrap();
+        let insert = conn.prepare("INSERT INTO t VALUES ($1, $2)").unwrap();
+        for id in 0..200 {
+            insert.execute((id, format!("name {id}"))).unwrap();
+        }
+        let temp_pages = || db.temp().disk_manager().num_pages();
+
+        // The sort spills its runs while its rows are read, and they are gone afterwards.
+        let mut rows = conn
+            .query("SELECT id FROM t ORDER BY name DESC", ())
+            .unwrap();
+        assert_eq!(rows.next().unwrap().unwrap().get::<i64>(0).unwrap(), 99);
+        assert!(temp_pages() > 0);
+        drop(rows);
+        assert_eq!(temp_pages(), 0);
+    }
+
+    #[test]
+    fn test_indexes_on_text_and_composite_keys() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        {
+            let db = Database::open(&path, Options::new()).unwrap();
+            let conn = db.connect();
+            conn.execute_batch("CREATE TABLE t (id BIGINT NOT NULL, name TEXT)")
+                .unwrap();
+            let insert = conn.prepare("INSERT INTO t VALUES ($1, $2)").unwrap();
+            for id in 0..3000 {
+                insert.execute((id, format!("name {}", id % 100))).unwrap();
+            }
+            conn.execute_batch(
+                "CREATE INDEX t_name ON t (name);
+                 CREATE UNIQUE INDEX t_name_id ON t (name, id);",
+            )
+            .unwrap();
+
+            let error = insert.execute((3, "name 3")).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "duplicate key value violates unique constraint \"t_name_id\""
+            );
+            assert_eq!(insert.execute((3, "name 4")).unwrap(), 1);
+            assert_eq!(
+                conn.execute("UPDATE t SET name = 'renamed' WHERE name = 'name 7'", ())
+                    .unwrap(),
+                30
+            );
+        }
+
+        let db = Database::open(&path, Options::new()).unwrap();
+        let conn = db.connect();
+        let explain = conn
+            .query_as::<(String,)>("EXPLAIN SELECT id FROM t WHERE name = 'renamed'", ())
+            .unwrap();
+        assert!(
+            explain
+                .iter()
+                .any(|(line,)| line.contains("IndexScan t using t_name [renamed, renamed]")),
+            "{explain:?}"
+        );
+        let ids = |sql: &str| conn.query_as::<(i64,)>(sql, ()).unwrap().len();
+        assert_eq!(ids("SELECT id FROM t WHERE name = 'renamed'"), 30);
+        assert_eq!(ids("SELECT id FROM t WHERE name = 'name 7'"), 0);
+        assert_eq!(ids("SELECT id FROM t WHERE name = 'name 4'"), 31);
+    }
+
+    #[test]
+    fn test_pages_are_written_behind_the_log() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let db = Database::open(
+            tempdir.path().join("test.db"),
+            Options::new().with_pool_size(8),
+        )
+        .unwrap();
+        db.connect()
+            .execute_batch("CREATE TABLE t (id BIGINT NOT NULL, name TEXT)")
+            .unwrap();
+        let catalog = db.catalog();
+        let table = catalog.table("t").unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        // Far more rows than the buffer pool holds, so that dirty pages are evicted.
+        for id in 0..2000 {
+            let tuple = Tuple::new(vec![Value::Int64(id), Value::Varchar("x".repeat(100))]);
+            heap.insert_tuple(&tuple.to_bytes(&table.schema).unwrap())
+                .unwrap();
+        }
+
+        // Every page that reached the file was logged, and its record is durable, while the
+        // records of the pages still in memory need not be yet.
+        let log = db.log();
+        let disk_manager = catalog.buffer_pool().disk_manager();
+        assert!(disk_manager.num_pages() > 8 * 2);
+        assert!(log.flushed_lsn() < log.last_lsn());
+        let mut page = Page::new_raw(DEFAULT_PAGE_SIZE);
+        let mut logged = 0;
+        for page_id in 0..disk_manager.num_pages() {
+            disk_manager
+                .read_page(PageId::new(page_id), page.data_mut())
+                .unwrap();
+            assert!(page.lsn() <= log.flushed_lsn());
+            logged += usize::from(page.lsn() != INVALID_LSN);
+        }
+        assert!(logged > 8);
+    }
+
+    #[test]
+    fn test_commit_flushes_only_the_log() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        let db = Database::open(&path, Options::new()).unwrap();
+        let conn = db.connect();
+        conn.execute_batch("CREATE TABLE t (id BIGINT NOT NULL, name TEXT)")
+            .unwrap();
+        let insert = conn.prepare("INSERT INTO t VALUES ($1, $2)").unwrap();
+        let before = db.catalog().buffer_pool().stats();
+        for id in 0..50 {
+            insert.execute((id, "x".repeat(100))).unwrap();
+            // Committed once the log is, without writing a page.
+            assert_eq!(db.log().flushed_lsn(), db.log().last_lsn());
+            assert_eq!(db.catalog().buffer_pool().stats().since(before).writes, 0);
+        }
+        drop(insert);
+        // Crash: the pages never reach the file, so the rows are redone from the log.
+        std::mem::forget(db);
+
+        let db = Database::open(&path, Options::new()).unwrap();
+        let conn = db.connect();
+        assert_eq!(
+            conn.query_as::<(i64,)>("SELECT count(*) FROM t", ())
+                .unwrap(),
+            [(50,)]
+        );
+    }
+
+    #[test]
+    fn test_dropped_pages_are_freed_once_unread() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let db = Database::open(tempdir.path().join("test.db"), Options::new()).unwrap();
+        let conn = db.connect();
+        let fill = || {
+            conn.execute_batch(
+                "CREATE TABLE t (id BIGINT NOT NULL, name TEXT);
+                 CREATE INDEX t_id ON t (id);
+                 CREATE UNIQUE INDEX t_id_hash ON t USING HASH (id);",
+            )
+            .unwrap();
+            let insert = conn.prepare("INSERT INTO t VALUES ($1, $2)").unwrap();
+            for id in 0..300 {
+                // Every tenth row is stored in an overflow chain.
+                let len = if id % 10 == 0 { 10_000 } else { 100 };
+                insert.execute((id, "x".repeat(len))).unwrap();
+            }
+        };
+        let num_pages = || db.catalog().buffer_pool().disk_manager().num_pages();
+        let empty = num_pages();
+        fill();
+        db.flush().unwrap();
+        let pages = num_pages();
+
+        // A query that started before the drop still reads the pages.
+        let mut rows = conn.query("SELECT id FROM t", ()).unwrap();
+        let first: i64 = rows.next().unwrap().unwrap().get(0).unwrap();
+        conn.execute_batch("DROP TABLE t").unwrap();
+        let rest: Vec<i64> = rows.map(|row| row.unwrap().get(0).unwrap()).collect();
+        assert_eq!(rest.len() + 1, 300);
+        assert!(!rest.contains(&first));
+
+        // Once it is done, the next statement frees them, and the table fits in them again,
+        // next to the few pages of u.
+        conn.execute_batch("CREATE TABLE u (id BIGINT)").unwrap();
+        fill();
+        db.flush().unwrap();
+        assert!(pages - empty > 50);
+        assert!(
+            num_pages() - pages < 5,
+            "{} pages after {pages}",
+            num_pages()
+        );
+        assert_eq!(
+            conn.query_as::<(i64,)>("SELECT count(*) FROM t WHERE id = 10", ())
+                .unwrap(),
+            [(1,)]
+        );
+    }
+
+    #[test]
+    fn test_log_is_checkpointed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        let log_path = LogManager::path_for(&path);
+        let options = Options::new().with_checkpoint_size(64 << 10);
+        {
+            let db = Database::open(&path, options).unwrap();
+            let conn = db.connect();
+            conn.execute_batch("CREATE TABLE t (id BIGINT NOT NULL, name TEXT)")
+                .unwrap();
+            let insert = conn.prepare("INSERT INTO t VALUES ($1, $2)").unwrap();
+            for id in 0..2000 {
+                insert.execute((id, "x".repeat(100))).unwrap();
+                // Each statement logs far less than a checkpoint's worth.
+                assert!(db.log().size() < 2 * options.checkpoint_size);
+            }
+        }
+        // Closing the database left only the checkpoint in the log.
+        let size = std::fs::metadata(log_path).unwrap().len();
+        assert!(size < 100, "{size}");
+
+        let db = Database::open(&path, options).unwrap();
+        let conn = db.connect();
+        assert_eq!(
+            conn.query_as::<(i64,)>("SELECT count(*) FROM t", ())
+                .unwrap(),
+            [(2000,)]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! The rows of a query and their conversion into Rust values.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::{
+    buffer::buffer_pool_manager::TempStatement,
+    catalog::schema::Schema,
+    execution::executor::BoxedExecutor,
+    types::{
+        data_type::DataType,
+        date_time::{Date, Timestamp},
+        decimal::Decimal,
+        value::Value,
+    },
+};
+
+/// The rows of a statement, produced as they are iterated.
+///
+/// A query runs a step at a time as its rows are taken, so an error can come with any row;
+/// none come after the first error.
+pub struct Rows<'db> {
+    columns: Arc<[String]>,
+    types: Vec<DataType>,
+    source: Source<'db>,
+    /// The number of rows a modification changed.
+    changed: Option<u64>,
+}
+
+enum Source<'db> {
+    Executor {
+        executor: BoxedExecutor<'db>,
+        /// The statement the executor runs as on the temp pool, declared after it to be
+        /// dropped after it.
+        _temp_statement: TempStatement<'db>,
+    },
+    /// The rows of a modification, which has already run.
+    Buffered(std::vec::IntoIter<Vec<Value>>),
+    Done,
+}
+
+impl<'db> Rows<'db> {
+    /// The rows of an executor that has been initialized, which runs as `temp_statement`.
+    pub(super) fn new(executor: BoxedExecutor<'db>, temp_statement: TempStatement<'db>) -> Self {
+        Self {
+            columns: column_names(executor.schema()),
+            types: column_types(executor.schema()),
+            source: Source::Executor {
+                executor,
+                _temp_statement: temp_statement,
+            },
+            changed: None,
+        }
+    }
+
+    /// No rows and no columns, for statements such as `CREATE TABLE`.
+    pub(super) fn empty() -> Self {
+        Self {
+            columns: Arc::from([]),
+            types: Vec::new(),
+            source: Source::Done,
+            changed: None,
+        }
+    }
+
+    /// The rows of a modification, which changed `count` rows.
+    pub(super) fn changed(schema: &Schema, rows: Vec<Vec<Value>>, count: u64) -> Self {
+        Self {
+            columns: column_names(schema),
+            types: column_types(schema),
+            source: Source::Buffered(rows.into_iter()),
+            changed: Some(count),
+        }
+    }
+
+    /// The names of the columns.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The types of the columns.
+    pub fn column_types(&self) -> &[DataType] {
+        &self.types
+    }
+
+    /// Convert each row with [`FromRow`].
+    pub fn decode<T: FromRow>(self) -> impl Iterator<Item = anyhow::Result<T>> + 'db {
+        self.map(|row| T::from_row(&row?))
+    }
+
+    /// Run to the end and return the number of rows changed, as
+    /// [`Connection::execute`](super::connection::Connection::execute) does.
+    pub(crate) fn finish(mut self) -> anyhow::Result<u64> {
+        if let Some(changed) = self.changed {
+            return Ok(changed);
+        }
+        for row in &mut self {
+            row?;
+        }
+        Ok(0)
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = anyhow::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = match &mut self.source {
+            Source::Executor { executor, .. } => match executor.next() {
+                Ok(Some(tuple)) => Ok(tuple.into_values()),
+                Ok(None) => Err(None),
+                Err(error) => Err(Some(error)),
+            },
+            Source::Buffered(rows) => rows.next().ok_or(None),
+            Source::Done => Err(None),
+        };
+        match values {
+            Ok(values) => Some(Ok(Row {
+                columns: self.columns.clone(),
+                values,
+            })),
+            Err(error) => {
+                // Drop the executor, which releases what it holds, as soon as it is done. That
+                // includes its temp space.
+                self.source = Source::Done;
+                error.map(Err)
+            }
+        }
+    }
+}
+
+/// A row of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// The value of a column, by position from 0 or by name, converted with [`FromValue`].
+    pub fn get<T: FromValue>(&self, index: impl RowIndex) -> anyhow::Result<T> {
+        let index = index.index(self)?;
+        T::from_value(&self.values[index])
+            .with_context(|| format!("cannot get column \"{}\"", self.columns[index]))
+    }
+
+    /// The value of a column, by position from 0 or by name.
+    pub fn value(&self, index: impl RowIndex) -> anyhow::Result<&Value> {
+        Ok(&self.values[index.index(self)?])
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    /// The names of the columns.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// What a column of a [`Row`] can be looked up by.
+pub trait RowIndex {
+    /// The position of the column in `row`.
+    fn index(&self, row: &Row) -> anyhow::Result<usize>;
+}
+
+impl RowIndex for usize {
+    fn index(&self, row: &Row) -> anyhow::Result<usize> {
+        if *self >= row.len() {
+            anyhow::bail!(
+                "column index {self} is out of range, the row has {} columns",
+                row.len()
+            );
+        }
+        Ok(*self)
+    }
+}
+
+/// The first column with the name, in any case.
+impl RowIndex for &str {
+    fn index(&self, row: &Row) -> anyhow::Result<usize> {
+        row.columns
+            .iter()
+            .position(|column| column.eq_ignore_ascii_case(self))
+            .ok_or_else(|| anyhow::anyhow!("the row has no column \"{self}\""))
+    }
+}
+
+/// A Rust value that a value of a column can be converted into.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> anyhow::Result<Self>;
+}
+
+/// The error of converting `value` into `to`.
+fn cannot_convert(value: &Value, to: &str) -> anyhow::Error {
+    match value.data_type() {
+        Some(data_type) => anyhow::anyhow!("cannot convert {data_type} {value} to {to}"),
+        None => anyhow::anyhow!("cannot convert NULL to {to}, which needs an Option"),
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty),*) => {
+        $(
+            /// Any integer in the range of the type.
+            impl FromValue for $ty {
+                fn from_value(value: &Value) -> anyhow::Result<Self> {
+                    let v = value
+                        .as_i64()
+                        .ok_or_else(|| cannot_convert(value, stringify!($ty)))?;
+                    <$ty>::try_from(v).map_err(|_| {
+                        anyhow::anyhow!("{v} is out of range for {}", stringify!($ty))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// Floats, and integers and decimals, which may be rounded.
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Float64(v) => Ok(*v),
+            Value::Decimal(d) => Ok(d.to_f64()),
+            value => match value.as_i64() {
+                Some(v) => Ok(v as f64),
+                None => Err(cannot_convert(value, "f64")),
+            },
+        }
+    }
+}
+
+/// Decimals and integers.
+impl FromValue for Decimal {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Decimal(d) => Ok(*d),
+            value => match value.as_i64() {
+                Some(v) => Ok(Decimal::from_i64(v)),
+                None => Err(cannot_convert(value, "Decimal")),
+            },
+        }
+    }
+}
+
+macro_rules! from_variant {
+    ($($ty:ty => $variant:ident),*) => {
+        $(
+            impl FromValue for $ty {
+                fn from_value(value: &Value) -> anyhow::Result<Self> {
+                    match value {
+                        Value::$variant(v) => Ok(v.clone()),
+                        value => Err(cannot_convert(value, stringify!($ty))),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_variant!(
+    bool => Boolean,
+    String => Varchar,
+    Vec<u8> => Bytea,
+    Date => Date,
+    Timestamp => Timestamp
+);
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+/// NULL is None.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// A Rust value that a whole row can be converted into, such as a struct with a field per
+/// column:
+///
+/// ```
+/// use limebase::{FromRow, Row};
+///
+/// struct User {
+///     id: i64,
+///     name: Option<String>,
+/// }
+///
+/// impl FromRow for User {
+///     fn from_row(row: &Row) -> anyhow::Result<Self> {
+///         Ok(Self {
+///             id: row.get("id")?,
+///             name: row.get("name")?,
+///         })
+///     }
+/// }
+/// ```
+///
+/// Tuples take the columns in order.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> anyhow::Result<Self>;
+}
+
+impl FromRow for Row {
+    fn from_row(row: &Row) -> anyhow::Result<Self> {
+        Ok(row.clone())
+    }
+}
+
+macro_rules! tuple_from_row {
+    ($($name:ident: $index:tt),+) => {
+        impl<$($name: FromValue),+> FromRow for ($($name,)+) {
+            fn from_row(row: &Row) -> anyhow::Result<Self> {
+                Ok(($(row.get::<$name>($index)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(A: 0);
+tuple_from_row!(A: 0, B: 1);
+tuple_from_row!(A: 0, B: 1, C: 2);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
+fn column_names(schema: &Schema) -> Arc<[String]> {
+    schema
+        .columns()
+        .iter()
+        .map(|column| column.name.clone())
+        .collect()
+}
+
+fn column_types(schema: &Schema) -> Vec<DataType> {
+    schema
+        .columns()
+        .iter()
+        .map(|column| column.data_type)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_value() {
+        assert_eq!(i64::from_value(&Value::Int16(-3)).unwrap(), -3);
+        assert_eq!(
+            u8::from_value(&Value::Int32(300)).unwrap_err().to_string(),
+            "300 is out of range for u8"
+        );
+        assert_eq!(
+            i64::from_value(&Value::Null).unwrap_err().to_string(),
+            "cannot convert NULL to i64, which needs an Option"
+        );
+        assert_eq!(Option::<i64>::from_value(&Value::Null).unwrap(), None);
+        assert_eq!(f64::from_value(&Value::Int32(2)).unwrap(), 2.0);
+        assert_eq!(
+            f64::from_value(&Value::Decimal(Decimal::new(125, 2))).unwrap(),
+            1.25
+        );
+        assert_eq!(
+            Decimal::from_value(&Value::Int64(4)).unwrap(),
+            Decimal::from_i64(4)
+        );
+        assert_eq!(
+            String::from_value(&Value::Boolean(true))
+                .unwrap_err()
+                .to_string(),
+            "cannot convert BOOLEAN true to String"
+        );
+
+        let row = Row {
+            columns: Arc::from(["id".to_string(), "name".to_string()]),
+            values: vec![Value::Int64(1), Value::Null],
+        };
+        assert_eq!(<(i64, Option<String>)>::from_row(&row).unwrap(), (1, None));
+        assert_eq!(
+            row.get::<i64>(2).unwrap_err().to_string(),
+            "column index 2 is out of range, the row has 2 columns"
+        );
+        assert_eq!(
+            row.get::<i64>("missing").unwrap_err().to_string(),
+            "the row has no column \"missing\""
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Prepared statements and the values of their parameters.
+//!
+//! A statement marks its parameters with `$1`, `$2`, ... by position, with `?` for the
+//! position after the last one so far, or with `:name`, which shares one position among all
+//! its uses. Values are given by position with a tuple, an array, a slice or [`params!`], or by
+//! name with [`named_params!`]:
+//!
+//! ```no_run
+//! # use limebase::{named_params, Database, Options};
+//! # let db = Database::open("app.db", Options::new())?;
+//! let conn = db.connect();
+//! conn.execute("UPDATE users SET name = ? WHERE id = ?", ("bob", 2))?;
+//! conn.execute(
+//!     "DELETE FROM users WHERE name = :name OR id = :id",
+//!     named_params! { ":name": "carol", ":id": 3 },
+//! )?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! [`params!`]: crate::params
+//! [`named_params!`]: crate::named_params
+
+use crate::{
+    sql::ast,
+    types::{
+        date_time::{Date, Timestamp},
+        decimal::Decimal,
+        value::Value,
+    },
+};
+
+use super::{
+    connection::Connection,
+    row::{FromRow, Rows},
+};
+
+/// A statement parsed once to run any number of times.
+pub struct Statement<'db> {
+    connection: Connection<'db>,
+    statement: ast::Statement,
+    /// The names of the parameters by position, None for those without one.
+    parameters: Vec<Option<String>>,
+}
+
+impl<'db> Statement<'db> {
+    pub(super) fn new(
+        connection: Connection<'db>,
+        statement: ast::Statement,
+        parameters: Vec<Option<String>>,
+    ) -> Self {
+        Self {
+            connection,
+            statement,
+            parameters,
+        }
+    }
+
+    /// The number of positions of parameters, which is the highest `$n` if it is above the
+    /// others.
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// The name of the parameter at `position`, counted from 0, without its `:`.
+    pub fn parameter_name(&self, position: usize) -> Option<&str> {
+        self.parameters.get(position)?.as_deref()
+    }
+
+    /// Run the statement with `params` and return the number of rows it inserted, updated or
+    /// deleted, which is 0 for other statements.
+    pub fn execute(&self, params: impl Params) -> anyhow::Result<u64> {
+        self.query(params)?.finish()
+    }
+
+    /// Run the statement with `params` and return its rows.
+    pub fn query(&self, params: impl Params) -> anyhow::Result<Rows<'db>> {
+        let values = params.bind(&self.parameters)?;
+        self.connection.run(&self.statement, values)
+    }
+
+    /// Run the statement with `params` and convert each of its rows with [`FromRow`].
+    pub fn query_as<T: FromRow>(&self, params: impl Params) -> anyhow::Result<Vec<T>> {
+        self.query(params)?.decode().collect()
+    }
+}
+
+/// A Rust value that can be the value of a parameter.
+pub trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+macro_rules! to_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl ToValue for $ty {
+                fn to_value(&self) -> Value {
+                    Value::$variant((*self).into())
+                }
+            }
+        )*
+    };
+}
+
+to_value! {
+    bool => Boolean,
+    i8 => Int8,
+    i16 => Int16,
+    i32 => Int32,
+    i64 => Int64,
+    u8 => Int16,
+    u16 => Int32,
+    u32 => Int64,
+    f32 => Float64,
+    f64 => Float64,
+    Decimal => Decimal,
+    Date => Date,
+    Timestamp => Timestamp,
+}
+
+impl ToValue for str {
+    fn to_value(&self) -> Value {
+        Value::Varchar(self.to_string())
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value {
+        Value::Varchar(self.clone())
+    }
+}
+
+impl ToValue for [u8] {
+    fn to_value(&self) -> Value {
+        Value::Bytea(self.to_vec())
+    }
+}
+
+impl ToValue for Vec<u8> {
+    fn to_value(&self) -> Value {
+        Value::Bytea(self.clone())
+    }
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+/// None is NULL.
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToValue::to_value)
+    }
+}
+
+impl<T: ToValue + ?Sized> ToValue for &T {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+/// The values of the parameters of a statement.
+pub trait Params {
+    /// The values by position, for a statement whose parameters have `names` by position.
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>>;
+}
+
+/// No parameters.
+impl Params for () {
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+        positional(names, Vec::new())
+    }
+}
+
+impl<T: ToValue> Params for &[T] {
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+        positional(names, self.iter().map(ToValue::to_value).collect())
+    }
+}
+
+impl<T: ToValue, const N: usize> Params for [T; N] {
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+        self.as_slice().bind(names)
+    }
+}
+
+impl<T: ToValue> Params for Vec<T> {
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+        self.as_slice().bind(names)
+    }
+}
+
+/// Values by name, with or without the `:` of the name.
+impl<T: ToValue> Params for &[(&str, T)] {
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+        let mut values = vec![None; names.len()];
+        for (name, value) in self {
+            let name = name.strip_prefix(':').unwrap_or(name);
+            let position = names
+                .iter()
+                .position(|n| n.as_deref() == Some(name))
+                .ok_or_else(|| anyhow::anyhow!("the statement has no parameter :{name}"))?;
+            values[position] = Some(value.to_value());
+        }
+        values
+            .into_iter()
+            .zip(names)
+            .enumerate()
+            .map(|(position, (value, name))| {
+                value.ok_or_else(|| match name {
+                    Some(name) => anyhow::anyhow!("no value for parameter :{name}"),
+                    None => anyhow::anyhow!(
+                        "no value for parameter ${}, which can only be given by position",
+                        position + 1
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T: ToValue, const N: usize> Params for [(&str, T); N] {
+    fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+        self.as_slice().bind(names)
+    }
+}
+
+macro_rules! tuple_params {
+    ($($name:ident: $index:tt),+) => {
+        impl<$($name: ToValue),+> Params for ($($name,)+) {
+            fn bind(self, names: &[Option<String>]) -> anyhow::Result<Vec<Value>> {
+                positional(names, vec![$(self.$index.to_value()),+])
+            }
+        }
+    };
+}
+
+tuple_params!(A: 0);
+tuple_params!(A: 0, B: 1);
+tuple_params!(A: 0, B: 1, C: 2);
+tuple_params!(A: 0, B: 1, C: 2, D: 3);
+tuple_params!(A: 0, B: 1, C: 2, D: 3, E: 4);
+tuple_params!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+tuple_params!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+tuple_params!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
+fn positional(names: &[Option<String>], values: Vec<Value>) -> anyhow::Result<Vec<Value>> {
+    if values.len() != names.len() {
+        anyhow::bail!(
+            "the statement has {} parameters, but {} values were given",
+            names.len(),
+            values.len()
+        );
+    }
+    Ok(values)
+}
+
+/// Values of parameters by position, of any types: `params![1, "alice", None::<i64>]`.
+#[macro_export]
+macro_rules! params {
+    () => {
+        &[] as &[&dyn $crate::database::statement::ToValue]
+    };
+    ($($value:expr),+ $(,)?) => {
+        &[$(&$value as &dyn $crate::database::statement::ToValue),+]
+            as &[&dyn $crate::database::statement::ToValue]
+    };
+}
+
+/// Values of parameters by name, of any types: `named_params! { ":id": 1, ":name": "alice" }`.
+#[macro_export]
+macro_rules! named_params {
+    () => {
+        &[] as &[(&str, &dyn $crate::database::statement::ToValue)]
+    };
+    ($($name:literal: $value:expr),+ $(,)?) => {
+        &[$(($name, &$value as &dyn $crate::database::statement::ToValue)),+]
+            as &[(&str, &dyn $crate::database::statement::ToValue)]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let names = [None, Some("id".to_string()), None];
+        assert_eq!(
+            (1i64, "a", None::<bool>).bind(&names).unwrap(),
+            vec![
+                Value::Int64(1),
+                Value::Varchar("a".to_string()),
+                Value::Null
+            ]
+        );
+        assert_eq!(
+            crate::params![7u8, 2.5f32, vec![1u8]].bind(&names).unwrap(),
+            vec![Value::Int16(7), Value::Float64(2.5), Value::Bytea(vec![1])]
+        );
+        assert!([1, 2].bind(&names).is_err());
+
+        let names = [Some("a".to_string()), Some("b".to_string())];
+        assert_eq!(
+            [(":b", 2), ("a", 1)].bind(&names).unwrap(),
+            vec![Value::Int32(1), Value::Int32(2)]
+        );
+        assert_eq!(
+            [(":a", 1)].bind(&names).unwrap_err().to_string(),
+            "no value for parameter :b"
+        );
+        assert_eq!(
+            [(":a", 1)].bind(&[None]).unwrap_err().to_string(),
+            "the statement has no parameter :a"
+        );
+        assert_eq!(
+            crate::named_params! {}
+                .bind(&[None])
+                .unwrap_err()
+                .to_string(),
+            "no value for parameter $1, which can only be given by position"
+        );
+    }
+}
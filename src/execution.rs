@@ -0,0 +1,28 @@
+pub mod aggregate;
+pub mod analyze;
+pub mod builder;
+pub mod context;
+pub mod ddl;
+pub mod delete;
+pub mod eval;
+pub mod executor;
+pub mod explain;
+pub mod filter;
+pub mod hash_aggregate;
+pub mod hash_join;
+pub mod hyperloglog;
+pub mod index_maintenance;
+pub mod index_nested_loop_join;
+pub mod index_scan;
+pub mod insert;
+pub mod join;
+pub mod key;
+pub mod limit;
+pub mod nested_loop_join;
+pub mod projection;
+pub mod seq_scan;
+pub mod sort;
+pub mod sort_aggregate;
+pub mod sort_merge_join;
+pub mod update;
+pub mod values;
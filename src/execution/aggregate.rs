@@ -0,0 +1,405 @@
+//! The state of the groups of an aggregation, which the hash and the sort-based aggregation
+//! executors share.
+//!
+//! Aggregates follow SQL: they skip NULL arguments, `count` of no rows is 0 and every other
+//! aggregate of no rows is NULL. `sum` of integers is exact until it is returned, and fails if
+//! it does not fit INT64. A DISTINCT aggregate remembers the [encoded](super::key) arguments it
+//! has seen in its group and skips the repeated ones.
+
+use std::collections::HashSet;
+
+use crate::{
+    planner::expr::{AggregateExpr, AggregateFunction, Expr},
+    storage::table::tuple::Tuple,
+    types::{data_type::DataType, decimal::Decimal, value::Value},
+};
+
+use super::{
+    eval::{compare, evaluate},
+    key::encode_value,
+};
+
+/// One group: its values of the group-by expressions and the state of every aggregate.
+pub struct Group {
+    values: Vec<Value>,
+    states: Vec<AggregateState>,
+}
+
+impl Group {
+    /// An empty group with the given group-by values, NULL where its grouping set has none.
+    pub fn new(values: Vec<Value>, aggregates: &[AggregateExpr]) -> Self {
+        Self {
+            values,
+            states: aggregates.iter().map(AggregateState::new).collect(),
+        }
+    }
+
+    /// Add an input row to the group.
+    pub fn update(&mut self, aggregates: &[AggregateExpr], row: &[Value]) -> anyhow::Result<()> {
+        for (state, aggregate) in self.states.iter_mut().zip(aggregates) {
+            state.update(aggregate, row)?;
+        }
+        Ok(())
+    }
+
+    /// The output row: the group-by values followed by the aggregates.
+    pub fn finish(self, aggregates: &[AggregateExpr]) -> anyhow::Result<Tuple> {
+        let mut values = self.values;
+        for (state, aggregate) in self.states.into_iter().zip(aggregates) {
+            values.push(state.finish(aggregate)?);
+        }
+        Ok(Tuple::new(values))
+    }
+}
+
+/// The values of `group_by` in grouping set `set`, NULL outside it, and the key of the group
+/// they form, which includes `set_index` so that groups of different sets never merge.
+pub fn group_values(
+    group_by: &[Expr],
+    set_index: usize,
+    set: &[usize],
+    row: &[Value],
+) -> anyhow::Result<(Vec<u8>, Vec<Value>)> {
+    let mut key = (set_index as u32).to_le_bytes().to_vec();
+    let mut values = vec![Value::Null; group_by.len()];
+    for &position in set {
+        let value = evaluate(&group_by[position], row)?;
+        encode_value(&value, &mut key);
+        values[position] = value;
+    }
+    Ok((key, values))
+}
+
+struct AggregateState {
+    accumulator: Accumulator,
+    /// The arguments seen so far, for DISTINCT aggregates.
+    seen: Option<HashSet<Vec<u8>>>,
+}
+
+enum Accumulator {
+    Count(i64),
+    /// The sum of integers, which cannot overflow an i128 before the count overflows.
+    IntSum {
+        sum: i128,
+        count: i64,
+    },
+    DecimalSum {
+        sum: Decimal,
+        count: i64,
+    },
+    FloatSum {
+        sum: f64,
+        count: i64,
+    },
+    /// The least or greatest argument so far, NULL before the first.
+    Extreme(Value),
+}
+
+impl AggregateState {
+    fn new(aggregate: &AggregateExpr) -> Self {
+        let arg_type = aggregate.arg.as_ref().map(|arg| arg.data_type);
+        let accumulator = match (aggregate.func, arg_type) {
+            (AggregateFunction::Count, _) => Accumulator::Count(0),
+            (AggregateFunction::Min | AggregateFunction::Max, _) => {
+                Accumulator::Extreme(Value::Null)
+            }
+            (_, Some(DataType::Decimal { scale, .. })) => Accumulator::DecimalSum {
+                sum: Decimal::new(0, scale),
+                count: 0,
+            },
+            (_, Some(DataType::Float64)) => Accumulator::FloatSum { sum: 0.0, count: 0 },
+            _ => Accumulator::IntSum { sum: 0, count: 0 },
+        };
+        Self {
+            accumulator,
+            seen: aggregate.distinct.then(HashSet::new),
+        }
+    }
+
+    fn update(&mut self, aggregate: &AggregateExpr, row: &[Value]) -> anyhow::Result<()> {
+        let Some(arg) = &aggregate.arg else {
+            // count(*)
+            if let Accumulator::Count(count) = &mut self.accumulator {
+                *count += 1;
+            }
+            return Ok(());
+        };
+        let value = evaluate(arg, row)?;
+        if value.is_null() {
+            return Ok(());
+        }
+        if let Some(seen) = &mut self.seen {
+            let mut key = Vec::new();
+            encode_value(&value, &mut key);
+            if !seen.insert(key) {
+                return Ok(());
+            }
+        }
+
+        match &mut self.accumulator {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::IntSum { sum, count } => {
+                *sum += value.as_i64().unwrap() as i128;
+                *count += 1;
+            }
+            Accumulator::DecimalSum { sum, count } => {
+                let Value::Decimal(d) = value else {
+                    unreachable!("the argument is a DECIMAL")
+                };
+                *sum = sum
+                    .checked_add(&d)
+                    .ok_or_else(|| anyhow::anyhow!("numeric field overflow"))?;
+                *count += 1;
+            }
+            Accumulator::FloatSum { sum, count } => {
+                let Value::Float64(f) = value else {
+                    unreachable!("the argument is a FLOAT64")
+                };
+                *sum += f;
+                *count += 1;
+            }
+            Accumulator::Extreme(extreme) => {
+                let wanted = match aggregate.func {
+                    AggregateFunction::Min => std::cmp::Ordering::Less,
+                    _ => std::cmp::Ordering::Greater,
+                };
+                if extreme.is_null() || compare(&value, extreme) == Some(wanted) {
+                    *extreme = value;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, aggregate: &AggregateExpr) -> anyhow::Result<Value> {
+        let (sum, count) = match self.accumulator {
+            Accumulator::Count(count) => return Ok(Value::Int64(count)),
+            Accumulator::Extreme(value) => return Ok(value),
+            Accumulator::IntSum { count: 0, .. }
+            | Accumulator::DecimalSum { count: 0, .. }
+            | Accumulator::FloatSum { count: 0, .. } => return Ok(Value::Null),
+            Accumulator::IntSum { sum, count } => {
+                if aggregate.func == AggregateFunction::Sum {
+                    let sum = i64::try_from(sum)
+                        .map_err(|_| anyhow::anyhow!("{} out of range", aggregate.data_type))?;
+                    return Ok(Value::Int64(sum));
+                }
+                (sum as f64, count)
+            }
+            Accumulator::DecimalSum { sum, count } => {
+                if aggregate.func == AggregateFunction::Sum {
+                    return Ok(Value::Decimal(sum));
+                }
+                (sum.to_f64(), count)
+            }
+            Accumulator::FloatSum { sum, count } => {
+                if aggregate.func == AggregateFunction::Sum {
+                    return Ok(Value::Float64(sum));
+                }
+                (sum, count)
+            }
+        };
+        // avg
+        Ok(Value::Float64(sum / count as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `func(arg)` over the first column, of type `arg_type`.
+    fn aggregate(func: AggregateFunction, distinct: bool, arg_type: DataType) -> AggregateExpr {
+        let data_type = match func {
+            AggregateFunction::Count => DataType::Int64,
+            AggregateFunction::Avg => DataType::Float64,
+            AggregateFunction::Sum if arg_type.is_integer() => DataType::Int64,
+            _ => arg_type,
+        };
+        AggregateExpr {
+            func,
+            arg: Some(Expr::column(0, arg_type)),
+            distinct,
+            data_type,
+        }
+    }
+
+    /// The aggregates over a group of one-column rows.
+    fn run(aggregates: &[AggregateExpr], column: &[Value]) -> anyhow::Result<Vec<Value>> {
+        let mut group = Group::new(Vec::new(), aggregates);
+        for value in column {
+            group.update(aggregates, std::slice::from_ref(value))?;
+        }
+        Ok(group.finish(aggregates)?.into_values())
+    }
+
+    #[test]
+    fn test_avg() {
+        let avg = [aggregate(AggregateFunction::Avg, false, DataType::Int32)];
+        let ints = |values: &[Option<i32>]| -> Vec<Value> {
+            values
+                .iter()
+                .map(|value| value.map_or(Value::Null, Value::Int32))
+                .collect()
+        };
+        // The average of integers is not rounded to one, and NULLs are not counted.
+        assert_eq!(
+            run(&avg, &ints(&[Some(1), Some(2)])).unwrap(),
+            [Value::Float64(1.5)]
+        );
+        assert_eq!(
+            run(&avg, &ints(&[Some(1), None, Some(2), Some(4)])).unwrap(),
+            [Value::Float64(7.0 / 3.0)]
+        );
+        assert_eq!(run(&avg, &ints(&[None, None])).unwrap(), [Value::Null]);
+        assert_eq!(run(&avg, &[]).unwrap(), [Value::Null]);
+
+        // Sums of integers are exact beyond INT64, so only the sum itself can be out of range.
+        let aggregates = [
+            aggregate(AggregateFunction::Avg, false, DataType::Int64),
+            aggregate(AggregateFunction::Sum, false, DataType::Int64),
+        ];
+        let big = [
+            Value::Int64(i64::MAX),
+            Value::Int64(i64::MAX),
+            Value::Int64(-i64::MAX),
+        ];
+        assert_eq!(
+            run(&aggregates, &big).unwrap(),
+            [
+                Value::Float64(i64::MAX as f64 / 3.0),
+                Value::Int64(i64::MAX)
+            ]
+        );
+        let err = run(&aggregates, &big[..2]).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{err}");
+
+        let decimal = DataType::Decimal {
+            precision: 10,
+            scale: 2,
+        };
+        let aggregates = [
+            aggregate(AggregateFunction::Avg, false, decimal),
+            aggregate(AggregateFunction::Sum, false, decimal),
+        ];
+        let values = [
+            Value::Decimal(Decimal::new(150, 2)),
+            Value::Null,
+            Value::Decimal(Decimal::new(225, 2)),
+        ];
+        assert_eq!(
+            run(&aggregates, &values).unwrap(),
+            [Value::Float64(1.875), Value::Decimal(Decimal::new(375, 2))]
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        let extremes = |data_type| {
+            [
+                aggregate(AggregateFunction::Min, false, data_type),
+                aggregate(AggregateFunction::Max, false, data_type),
+            ]
+        };
+        let ints = extremes(DataType::Int64);
+        assert_eq!(run(&ints, &[]).unwrap(), [Value::Null, Value::Null]);
+        assert_eq!(
+            run(&ints, &[Value::Null, Value::Null]).unwrap(),
+            [Value::Null, Value::Null]
+        );
+        let values = [
+            Value::Null,
+            Value::Int64(3),
+            Value::Null,
+            Value::Int64(-2),
+            Value::Int64(7),
+        ];
+        assert_eq!(
+            run(&ints, &values).unwrap(),
+            [Value::Int64(-2), Value::Int64(7)]
+        );
+
+        let strings = extremes(DataType::Varchar(None));
+        let values = ["b", "", "c", "a"].map(|s| Value::Varchar(s.into()));
+        let mut with_null = vec![Value::Null];
+        with_null.extend(values);
+        assert_eq!(
+            run(&strings, &with_null).unwrap(),
+            [Value::Varchar("".into()), Value::Varchar("c".into())]
+        );
+    }
+
+    #[test]
+    fn test_distinct() {
+        let aggregates = [
+            AggregateExpr {
+                func: AggregateFunction::Count,
+                arg: None,
+                distinct: false,
+                data_type: DataType::Int64,
+            },
+            aggregate(AggregateFunction::Count, false, DataType::Int64),
+            aggregate(AggregateFunction::Count, true, DataType::Int64),
+            aggregate(AggregateFunction::Sum, true, DataType::Int64),
+            aggregate(AggregateFunction::Avg, true, DataType::Int64),
+        ];
+        let values = [1, 1, 2, -1, 2, 3, 1].map(|v| match v {
+            -1 => Value::Null,
+            v => Value::Int64(v),
+        });
+        assert_eq!(
+            run(&aggregates, &values).unwrap(),
+            [
+                Value::Int64(7),
+                Value::Int64(6),
+                Value::Int64(3),
+                Value::Int64(6),
+                Value::Float64(2.0),
+            ]
+        );
+        // Every group sees the values afresh.
+        assert_eq!(
+            run(&aggregates[2..3], &values[..2]).unwrap(),
+            [Value::Int64(1)]
+        );
+        assert_eq!(
+            run(&aggregates, &[Value::Null]).unwrap(),
+            [
+                Value::Int64(1),
+                Value::Int64(0),
+                Value::Int64(0),
+                Value::Null,
+                Value::Null
+            ]
+        );
+
+        // Values are distinct by their value, not by their text.
+        let strings = [aggregate(
+            AggregateFunction::Count,
+            true,
+            DataType::Varchar(None),
+        )];
+        let values = ["a", "A", "a ", "a"].map(|s| Value::Varchar(s.into()));
+        assert_eq!(run(&strings, &values).unwrap(), [Value::Int64(3)]);
+    }
+
+    #[test]
+    fn test_group_values() {
+        let group_by = [
+            Expr::column(0, DataType::Int64),
+            Expr::column(1, DataType::Int64),
+        ];
+        let row = [Value::Int64(1), Value::Int64(2)];
+        let (both, values) = group_values(&group_by, 0, &[0, 1], &row).unwrap();
+        assert_eq!(values, row);
+        let (second, values) = group_values(&group_by, 1, &[1], &row).unwrap();
+        assert_eq!(values, [Value::Null, Value::Int64(2)]);
+        assert_ne!(both, second);
+
+        // Two sets that happen to produce the same values still form different groups.
+        let (empty, values) = group_values(&group_by, 2, &[], &row).unwrap();
+        let (other_empty, _) = group_values(&group_by, 3, &[], &row).unwrap();
+        assert_eq!(values, [Value::Null, Value::Null]);
+        assert_ne!(empty, other_empty);
+    }
+}
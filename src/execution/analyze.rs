@@ -0,0 +1,327 @@
+//! `ANALYZE`: computes the [statistics](crate::catalog::statistics) of a table in one pass
+//! over its heap and stores them in the catalog.
+//!
+//! Every row is counted, its NULLs are counted and its values go into a [`HyperLogLog`] per
+//! column, but only a fixed-size uniform sample of the rows is kept, by reservoir sampling:
+//! the `i`-th row replaces a random one of the sample with probability `sample_size / i`. The
+//! most common values and the histograms come from the sorted sample. The random numbers come
+//! from a fixed seed, so analyzing the same table twice gives the same statistics.
+
+use std::cmp::Ordering;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{Catalog, TableInfo},
+        statistics::{ColumnStatistics, TableStatistics},
+    },
+    storage::table::tuple::Tuple,
+    types::value::Value,
+};
+
+use super::{eval::compare, hyperloglog::HyperLogLog};
+
+/// Options for [`analyze_table`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalyzeConfig {
+    /// The number of rows sampled for the most common values and the histograms.
+    pub sample_size: usize,
+    /// The most common values kept per column.
+    pub most_common_values: usize,
+    /// The number of buckets of the histograms.
+    pub histogram_buckets: usize,
+}
+
+impl AnalyzeConfig {
+    /// Sample 30000 rows, which is what PostgreSQL samples for 100 histogram buckets.
+    pub fn new() -> Self {
+        Self {
+            sample_size: 30_000,
+            most_common_values: 10,
+            histogram_buckets: 100,
+        }
+    }
+
+    pub fn with_sample_size(self, sample_size: usize) -> Self {
+        assert!(sample_size > 0, "sample_size must be positive");
+        Self {
+            sample_size,
+            ..self
+        }
+    }
+
+    pub fn with_most_common_values(self, most_common_values: usize) -> Self {
+        Self {
+            most_common_values,
+            ..self
+        }
+    }
+
+    pub fn with_histogram_buckets(self, histogram_buckets: usize) -> Self {
+        assert!(histogram_buckets > 0, "histogram_buckets must be positive");
+        Self {
+            histogram_buckets,
+            ..self
+        }
+    }
+}
+
+impl Default for AnalyzeConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compute the statistics of `table` and store them in the catalog, replacing the previous.
+pub fn analyze_table<B: BufferPoolManager + ?Sized>(
+    catalog: &Catalog<'_, B>,
+    table: &TableInfo,
+    config: &AnalyzeConfig,
+) -> anyhow::Result<TableStatistics> {
+    let heap = catalog.table_heap(table)?;
+    let columns = table.schema.column_count();
+    let mut random = SplitMix64(0x5eed);
+    let mut row_count: u64 = 0;
+    let mut null_counts = vec![0u64; columns];
+    let mut sketches = vec![HyperLogLog::new(); columns];
+    let mut sample: Vec<Vec<Value>> = Vec::new();
+    for row in heap.iter()? {
+        let (_, data) = row?;
+        let values = Tuple::from_bytes(&table.schema, &data)?.into_values();
+        for (column, value) in values.iter().enumerate() {
+            if value.is_null() {
+                null_counts[column] += 1;
+            } else {
+                sketches[column].insert(value);
+            }
+        }
+        row_count += 1;
+        if sample.len() < config.sample_size {
+            sample.push(values);
+        } else {
+            let slot = random.below(row_count) as usize;
+            if slot < config.sample_size {
+                sample[slot] = values;
+            }
+        }
+    }
+
+    let complete = sample.len() as u64 == row_count;
+    let statistics = TableStatistics {
+        row_count,
+        columns: (0..columns)
+            .map(|column| {
+                let values: Vec<Value> = sample
+                    .iter()
+                    .map(|row| row[column].clone())
+                    .filter(|value| !value.is_null())
+                    .collect();
+                column_statistics(
+                    values,
+                    sample.len(),
+                    null_counts[column],
+                    row_count,
+                    &sketches[column],
+                    complete,
+                    config,
+                )
+            })
+            .collect(),
+    };
+    catalog.set_table_statistics(table.oid, statistics.clone())?;
+    Ok(statistics)
+}
+
+/// The statistics of a column from the non-NULL `values` of a sample of `sample_size` rows.
+/// The sample has every row if `complete`.
+fn column_statistics(
+    mut values: Vec<Value>,
+    sample_size: usize,
+    null_count: u64,
+    row_count: u64,
+    sketch: &HyperLogLog,
+    complete: bool,
+    config: &AnalyzeConfig,
+) -> ColumnStatistics {
+    if row_count == 0 {
+        return ColumnStatistics {
+            null_fraction: 0.0,
+            distinct_count: 0,
+            most_common_values: Vec::new(),
+            most_common_frequencies: Vec::new(),
+            histogram_bounds: Vec::new(),
+        };
+    }
+    values.sort_by(|a, b| compare(a, b).unwrap_or(Ordering::Equal));
+    // Each distinct value of the sample with the number of times it appears.
+    let mut groups: Vec<(Value, usize)> = Vec::new();
+    for value in values {
+        match groups.last_mut() {
+            Some((last, count)) if compare(last, &value) == Some(Ordering::Equal) => *count += 1,
+            _ => groups.push((value, 1)),
+        }
+    }
+
+    let non_null_count = row_count - null_count;
+    let distinct_count = if complete {
+        groups.len() as u64
+    } else {
+        sketch.estimate().clamp(groups.len() as u64, non_null_count)
+    };
+
+    // A value is common if it appears markedly more often than the average value of the
+    // sample, or always when the list can hold every value of the table.
+    let mut by_count: Vec<usize> = (0..groups.len()).collect();
+    by_count.sort_by(|&a, &b| groups[b].1.cmp(&groups[a].1).then(a.cmp(&b)));
+    let keep_all = complete && groups.len() <= config.most_common_values;
+    let sampled: usize = groups.iter().map(|(_, count)| count).sum();
+    let average = sampled as f64 / groups.len().max(1) as f64;
+    let common: Vec<usize> = by_count
+        .into_iter()
+        .take(config.most_common_values)
+        .take_while(|&i| keep_all || (groups[i].1 > 1 && groups[i].1 as f64 > 1.25 * average))
+        .collect();
+    let most_common_values = common.iter().map(|&i| groups[i].0.clone()).collect();
+    let most_common_frequencies = common
+        .iter()
+        .map(|&i| groups[i].1 as f64 / sample_size as f64)
+        .collect();
+
+    // The bounds split the other values into buckets of about the same number of values.
+    let rest: Vec<(Value, usize)> = groups
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !common.contains(i))
+        .map(|(_, group)| group)
+        .collect();
+    let mut histogram_bounds = Vec::new();
+    if rest.len() >= 2 {
+        let mut expanded: Vec<&Value> = Vec::new();
+        for (value, count) in &rest {
+            expanded.extend(std::iter::repeat(value).take(*count));
+        }
+        let buckets = config.histogram_buckets.min(rest.len() - 1);
+        let last = expanded.len() - 1;
+        for i in 0..=buckets {
+            let bound = expanded[i * last / buckets];
+            let repeated = histogram_bounds
+                .last()
+                .is_some_and(|previous| compare(previous, bound) == Some(Ordering::Equal));
+            if !repeated {
+                histogram_bounds.push(bound.clone());
+            }
+        }
+        if histogram_bounds.len() < 2 {
+            histogram_bounds.clear();
+        }
+    }
+
+    ColumnStatistics {
+        null_fraction: null_count as f64 / row_count as f64,
+        distinct_count,
+        most_common_values,
+        most_common_frequencies,
+        histogram_bounds,
+    }
+}
+
+/// A small pseudo-random generator, enough to pick which rows to sample.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::schema::{Column, Schema},
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_analyze_table() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let table = catalog
+            .create_table(
+                "t",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("skewed", DataType::Int32),
+                    Column::new("flag", DataType::Boolean),
+                ]),
+            )
+            .unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        // Half the rows have skewed = 0 and a tenth have it NULL.
+        for i in 0..5000i64 {
+            let skewed = match i % 10 {
+                0 => Value::Null,
+                1..=5 => Value::Int32(0),
+                _ => Value::Int32(i as i32),
+            };
+            let row = Tuple::new(vec![Value::Int64(i), skewed, Value::Boolean(i % 4 == 0)]);
+            heap.insert_tuple(&row.to_bytes(&table.schema).unwrap())
+                .unwrap();
+        }
+
+        let config = AnalyzeConfig::new()
+            .with_sample_size(1000)
+            .with_histogram_buckets(10);
+        let statistics = analyze_table(&catalog, &table, &config).unwrap();
+        assert_eq!(
+            catalog.table_statistics(table.oid).as_deref(),
+            Some(&statistics)
+        );
+        assert_eq!(statistics.row_count, 5000);
+        let [id, skewed, flag] = &statistics.columns[..] else {
+            panic!()
+        };
+
+        assert_eq!(id.null_fraction, 0.0);
+        assert!((4800..=5200).contains(&id.distinct_count));
+        assert!(id.most_common_values.is_empty());
+        assert_eq!(id.histogram_bounds.len(), 11);
+        assert!(id.histogram_bounds[0].as_i64().unwrap() < 250);
+        assert!(id.histogram_bounds[10].as_i64().unwrap() > 4750);
+
+        assert_eq!(skewed.null_fraction, 0.1);
+        assert_eq!(skewed.most_common_values, vec![Value::Int32(0)]);
+        assert!((skewed.most_common_frequencies[0] - 0.5).abs() < 0.05);
+        assert!((skewed.histogram_fraction() - 0.4).abs() < 0.05);
+
+        // Every value is common when there are few.
+        assert_eq!(flag.most_common_values, vec![Value::Boolean(false)]);
+        let small = analyze_table(&catalog, &table, &AnalyzeConfig::new()).unwrap();
+        let flag = &small.columns[2];
+        assert_eq!(flag.distinct_count, 2);
+        assert_eq!(
+            flag.most_common_values,
+            vec![Value::Boolean(false), Value::Boolean(true)]
+        );
+        assert_eq!(flag.most_common_frequencies, vec![0.75, 0.25]);
+        assert!(flag.histogram_bounds.is_empty());
+    }
+}
@@ -0,0 +1,707 @@
+//! Turns a bound [`LogicalPlan`] into a tree of executors.
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::catalog::Oid,
+    planner::{
+        expr::{Expr, ExprKind},
+        logical_plan::LogicalPlan,
+    },
+};
+
+use super::{
+    context::ExecutorContext, delete::DeleteExecutor, executor::BoxedExecutor,
+    explain::ExplainExecutor, filter::FilterExecutor, hash_aggregate::HashAggregateExecutor,
+    hash_join::HashJoinExecutor, index_maintenance::IndexMaintenance,
+    index_scan::IndexScanExecutor, insert::InsertExecutor, join::EquiJoinKeys,
+    limit::LimitExecutor, nested_loop_join::NestedLoopJoinExecutor, projection::ProjectionExecutor,
+    seq_scan::SeqScanExecutor, sort::SortExecutor, update::UpdateExecutor, values::ValuesExecutor,
+};
+
+/// Build the executor of `plan`. Statements that change the schema or, like ANALYZE, the
+/// statistics are run against the catalog directly by [`execute_ddl`](super::ddl::execute_ddl)
+/// and have no executor.
+///
+/// Joins with an equality between their inputs are hash joins, and the others nested-loop
+/// joins. Aggregations and DISTINCT are hash aggregations, and a projection of columns of a
+/// sequential scan is left to the scan.
+pub fn build_executor<'a, B: BufferPoolManager + ?Sized, T: BufferPoolManager + ?Sized>(
+    ctx: &ExecutorContext<'_, 'a, B, T>,
+    plan: &LogicalPlan,
+) -> anyhow::Result<BoxedExecutor<'a>> {
+    build_executor_with(ctx, plan, &mut |_, executor| executor)
+}
+
+/// Build the executor of `plan` like [`build_executor`], passing the executor of every
+/// operator through `wrap` once those of its inputs are built, for example to measure it.
+pub fn build_executor_with<'a, B: BufferPoolManager + ?Sized, T: BufferPoolManager + ?Sized>(
+    ctx: &ExecutorContext<'_, 'a, B, T>,
+    plan: &LogicalPlan,
+    wrap: &mut dyn FnMut(&LogicalPlan, BoxedExecutor<'a>) -> BoxedExecutor<'a>,
+) -> anyhow::Result<BoxedExecutor<'a>> {
+    let catalog = ctx.catalog;
+    let executor: BoxedExecutor<'a> = match plan {
+        LogicalPlan::SeqScan { table } => Box::new(SeqScanExecutor::new(
+            table.clone(),
+            catalog.table_heap(table)?,
+        )),
+        LogicalPlan::IndexScan {
+            table,
+            index,
+            lower,
+            upper,
+        } => Box::new(IndexScanExecutor::new(
+            table.clone(),
+            catalog.table_heap(table)?,
+            index.clone(),
+            catalog.open_index(index)?,
+            lower.clone(),
+            upper.clone(),
+        )),
+        LogicalPlan::Values { rows, schema } => {
+            Box::new(ValuesExecutor::new(rows.clone(), schema.clone()))
+        }
+        LogicalPlan::Filter { input, predicate } => Box::new(FilterExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            predicate.clone(),
+        )),
+        LogicalPlan::Projection {
+            input,
+            exprs,
+            schema,
+        } => match (input.as_ref(), projected_columns(exprs)) {
+            // A scan narrowed to some columns decodes only those, which leaves the projection
+            // nothing to do.
+            (LogicalPlan::SeqScan { table }, Some(columns)) => {
+                let scan = SeqScanExecutor::new(table.clone(), catalog.table_heap(table)?)
+                    .with_columns(columns, schema.clone());
+                wrap(input, Box::new(scan))
+            }
+            _ => Box::new(ProjectionExecutor::new(
+                build_executor_with(ctx, input, wrap)?,
+                exprs.clone(),
+                schema.clone(),
+            )),
+        },
+        LogicalPlan::Limit {
+            input,
+            limit,
+            offset,
+        } => Box::new(LimitExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            *limit,
+            *offset,
+        )),
+        LogicalPlan::Insert { table, input } => Box::new(InsertExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            table.clone(),
+            catalog.table_heap(table)?,
+            IndexMaintenance::open(catalog, table)?,
+            reads_table(input, table.oid),
+        )),
+        LogicalPlan::Update {
+            table,
+            input,
+            assignments,
+        } => Box::new(UpdateExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            table.clone(),
+            catalog.table_heap(table)?,
+            IndexMaintenance::open(catalog, table)?,
+            assignments.clone(),
+        )),
+        LogicalPlan::Delete { table, input } => Box::new(DeleteExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            catalog.table_heap(table)?,
+            IndexMaintenance::open(catalog, table)?,
+        )),
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            condition,
+        } => {
+            let left_width = left.schema().column_count();
+            let left = build_executor_with(ctx, left, wrap)?;
+            let right = build_executor_with(ctx, right, wrap)?;
+            let keys = EquiJoinKeys::split(condition.as_ref(), left_width);
+            if keys.is_empty() {
+                Box::new(NestedLoopJoinExecutor::new(
+                    left,
+                    right,
+                    *join_type,
+                    condition.clone(),
+                ))
+            } else {
+                Box::new(HashJoinExecutor::new(
+                    left,
+                    right,
+                    *join_type,
+                    keys,
+                    ctx.temp,
+                    ctx.work_mem,
+                ))
+            }
+        }
+        LogicalPlan::Aggregate {
+            input,
+            group_by,
+            grouping_sets,
+            aggregates,
+            schema,
+        } => Box::new(HashAggregateExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            group_by.clone(),
+            grouping_sets.clone(),
+            aggregates.clone(),
+            schema.clone(),
+            ctx.temp,
+            ctx.work_mem,
+        )),
+        LogicalPlan::Distinct { input } => {
+            let schema = input.schema();
+            let columns: Vec<Expr> = schema
+                .columns()
+                .iter()
+                .enumerate()
+                .map(|(i, column)| Expr::column(i, column.data_type))
+                .collect();
+            let set = (0..columns.len()).collect();
+            Box::new(HashAggregateExecutor::new(
+                build_executor_with(ctx, input, wrap)?,
+                columns,
+                vec![set],
+                Vec::new(),
+                schema,
+                ctx.temp,
+                ctx.work_mem,
+            ))
+        }
+        LogicalPlan::Sort { input, keys } => Box::new(SortExecutor::new(
+            build_executor_with(ctx, input, wrap)?,
+            keys.clone(),
+            ctx.temp,
+            ctx.work_mem,
+        )),
+        LogicalPlan::CreateTable { .. }
+        | LogicalPlan::DropTable { .. }
+        | LogicalPlan::CreateIndex { .. }
+        | LogicalPlan::DropIndex { .. }
+        | LogicalPlan::Analyze { .. } => {
+            anyhow::bail!("{} has no executor", plan.describe())
+        }
+        LogicalPlan::Explain {
+            input,
+            analyze,
+            format,
+        } => Box::new(ExplainExecutor::new(ctx, input, *analyze, *format)?),
+    };
+
+    Ok(wrap(plan, executor))
+}
+
+/// The name of the executor [`build_executor`] picks for the operator at the top of `plan`.
+pub fn executor_name(plan: &LogicalPlan) -> &'static str {
+    match plan {
+        LogicalPlan::SeqScan { .. } => "SeqScan",
+        LogicalPlan::IndexScan { .. } => "IndexScan",
+        LogicalPlan::Values { .. } => "Values",
+        LogicalPlan::Filter { .. } => "Filter",
+        LogicalPlan::Projection { .. } => "Projection",
+        LogicalPlan::Limit { .. } => "Limit",
+        LogicalPlan::Insert { .. } => "Insert",
+        LogicalPlan::Update { .. } => "Update",
+        LogicalPlan::Delete { .. } => "Delete",
+        LogicalPlan::Join {
+            left, condition, ..
+        } => {
+            if EquiJoinKeys::split(condition.as_ref(), left.schema().column_count()).is_empty() {
+                "NestedLoopJoin"
+            } else {
+                "HashJoin"
+            }
+        }
+        LogicalPlan::Aggregate { .. } | LogicalPlan::Distinct { .. } => "HashAggregate",
+        LogicalPlan::Sort { .. } => "Sort",
+        LogicalPlan::Explain { .. } => "Explain",
+        LogicalPlan::CreateTable { .. } => "CreateTable",
+        LogicalPlan::DropTable { .. } => "DropTable",
+        LogicalPlan::CreateIndex { .. } => "CreateIndex",
+        LogicalPlan::DropIndex { .. } => "DropIndex",
+        LogicalPlan::Analyze { .. } => "Analyze",
+    }
+}
+
+/// The columns `exprs` read, if each of them is a column.
+fn projected_columns(exprs: &[Expr]) -> Option<Vec<usize>> {
+    exprs
+        .iter()
+        .map(|expr| match expr.kind {
+            ExprKind::Column(index) => Some(index),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `plan` scans the table `oid`.
+fn reads_table(plan: &LogicalPlan, oid: Oid) -> bool {
+    match plan {
+        LogicalPlan::SeqScan { table } | LogicalPlan::IndexScan { table, .. } => table.oid == oid,
+        plan => plan
+            .children()
+            .into_iter()
+            .any(|child| reads_table(child, oid)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        optimizer::optimizer::Optimizer,
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+            table::tuple::Tuple,
+        },
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::*;
+
+    type Context<'c, 'a> = ExecutorContext<'c, 'a, BufferPoolManagerImpl, TempBufferPool>;
+
+    fn run(ctx: &Context, sql: &str) -> anyhow::Result<Vec<Vec<Value>>> {
+        let statement = parse_statement(sql).map_err(|e| anyhow::anyhow!(e.render(sql)))?;
+        let plan = Binder::new(ctx.catalog)
+            .bind(&statement)
+            .map_err(|e| anyhow::anyhow!(e.render(sql)))?;
+        let plan = Optimizer::new(ctx.catalog)
+            .with_work_mem(ctx.work_mem)
+            .optimize(plan)?;
+        let mut executor = build_executor(ctx, &plan)?;
+        executor.init()?;
+        let mut rows = Vec::new();
+        while let Some(tuple) = executor.next()? {
+            rows.push(tuple.into_values());
+        }
+        Ok(rows)
+    }
+
+    fn count(n: i64) -> Vec<Vec<Value>> {
+        vec![vec![Value::Int64(n)]]
+    }
+
+    fn ids(rows: Vec<Vec<Value>>) -> Vec<i64> {
+        rows.iter().map(|row| row[0].as_i64().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_modify_and_query() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        // Far fewer frames than the table has pages.
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        catalog
+            .create_table(
+                "items",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("name", DataType::Varchar(Some(100))),
+                    Column::new("qty", DataType::Int32),
+                ]),
+            )
+            .unwrap();
+        catalog
+            .create_index("items_pkey", "items", &["id"], IndexKind::BPlusTree, true)
+            .unwrap();
+        catalog
+            .create_index("items_qty", "items", &["qty"], IndexKind::BPlusTree, false)
+            .unwrap();
+
+        for i in 0..500 {
+            let sql = format!(
+                "INSERT INTO items VALUES ({i}, '{}', {})",
+                "x".repeat(i % 50),
+                i % 10
+            );
+            assert_eq!(run(&ctx, &sql).unwrap(), count(1));
+        }
+        assert_eq!(
+            run(&ctx, "INSERT INTO items (id) VALUES (7)")
+                .unwrap_err()
+                .to_string(),
+            "duplicate key value violates unique constraint \"items_pkey\""
+        );
+        assert_eq!(
+            run(
+                &ctx,
+                "SELECT id, length(name) FROM items WHERE qty = 3 AND id >= 400 LIMIT 3 OFFSET 1"
+            )
+            .unwrap(),
+            vec![
+                vec![Value::Int64(413), Value::Int64(13)],
+                vec![Value::Int64(423), Value::Int64(23)],
+                vec![Value::Int64(433), Value::Int64(33)],
+            ]
+        );
+
+        // Grow every row so that many move to other pages, and change the keys of both indexes.
+        assert_eq!(
+            run(
+                &ctx,
+                "UPDATE items SET name = name || 'yyyyyyyyyyyyyyyyyyyy', id = id + 1000, qty = qty + 1"
+            )
+            .unwrap(),
+            count(500)
+        );
+        assert_eq!(
+            run(&ctx, "UPDATE items SET id = 1001 WHERE id = 1000")
+                .unwrap_err()
+                .to_string(),
+            "duplicate key value violates unique constraint \"items_pkey\""
+        );
+        assert_eq!(
+            run(&ctx, "DELETE FROM items WHERE qty = 10").unwrap(),
+            count(50)
+        );
+        assert_eq!(run(&ctx, "SELECT id FROM items").unwrap().len(), 450);
+
+        // The indexes point at the current rows.
+        let table = catalog.table("items").unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        let pkey = catalog
+            .open_index(&catalog.index("items_pkey").unwrap())
+            .unwrap();
+        let rid = pkey.get(&[Value::Int64(1123)]).unwrap()[0];
+        let tuple =
+            Tuple::from_bytes(&table.schema, &heap.get_tuple(rid).unwrap().unwrap()).unwrap();
+        assert_eq!(tuple.value(0), &Value::Int64(1123));
+        assert!(pkey.get(&[Value::Int64(123)]).unwrap().is_empty());
+        let qty = catalog
+            .open_index(&catalog.index("items_qty").unwrap())
+            .unwrap();
+        assert_eq!(qty.get(&[Value::Int64(4)]).unwrap().len(), 50);
+        assert!(qty.get(&[Value::Int64(10)]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_insert_select_from_same_table() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        catalog
+            .create_table("t", Schema::new(vec![Column::new("a", DataType::Int32)]))
+            .unwrap();
+        run(&ctx, "INSERT INTO t VALUES (1), (2), (NULL)").unwrap();
+        assert_eq!(
+            run(&ctx, "INSERT INTO t SELECT a + 10 FROM t").unwrap(),
+            count(3)
+        );
+        let mut rows = run(&ctx, "SELECT a FROM t WHERE a IS NOT NULL").unwrap();
+        rows.sort_by_key(|row| row[0].as_i64());
+        assert_eq!(ids(rows), vec![1, 2, 11, 12]);
+        assert_eq!(
+            run(&ctx, "SELECT 1 / 0").unwrap_err().to_string(),
+            "division by zero"
+        );
+        let rows = run(&ctx, "SELECT a FROM t ORDER BY a DESC").unwrap();
+        // NULLs come first in descending order, as in PostgreSQL.
+        assert_eq!(rows[..2], [vec![Value::Null], vec![Value::Null]]);
+        assert_eq!(ids(rows[2..].to_vec()), vec![12, 11, 2, 1]);
+    }
+
+    #[test]
+    fn test_join() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        // Fewer tuples than the build side has, so that hash joins spill.
+        let ctx = ExecutorContext::new(&catalog, &temp).with_work_mem(8);
+        catalog
+            .create_table(
+                "emp",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("dept", DataType::Int32),
+                ]),
+            )
+            .unwrap();
+        catalog
+            .create_table(
+                "dept",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int32),
+                    Column::new("title", DataType::Varchar(Some(20))),
+                ]),
+            )
+            .unwrap();
+        // Departments 0 to 10, or NULL for every twelfth employee.
+        let emps: Vec<String> = (0..100)
+            .map(|i| match i % 12 {
+                11 => format!("({i}, NULL)"),
+                dept => format!("({i}, {dept})"),
+            })
+            .collect();
+        run(&ctx, &format!("INSERT INTO emp VALUES {}", emps.join(", "))).unwrap();
+        // Departments 0 to 9 and 11, and one with a NULL id.
+        let depts: Vec<String> = (0..10)
+            .chain([11])
+            .map(|i| format!("({i}, 'd{i}')"))
+            .collect();
+        run(
+            &ctx,
+            &format!(
+                "INSERT INTO dept VALUES {}, (NULL, 'none')",
+                depts.join(", ")
+            ),
+        )
+        .unwrap();
+
+        let join = |kind: &str, on: &str| {
+            let sql = format!("SELECT e.id, d.title FROM emp e {kind} JOIN dept d ON {on}");
+            run(&ctx, &sql).unwrap()
+        };
+        assert_eq!(join("", "e.dept = d.id").len(), 84);
+        assert_eq!(join("LEFT", "e.dept = d.id").len(), 100);
+        assert_eq!(join("RIGHT", "e.dept = d.id").len(), 86);
+        assert_eq!(join("FULL", "e.dept = d.id").len(), 102);
+        // Without an equality, a nested-loop join.
+        assert_eq!(join("", "e.dept > d.id").len(), 446);
+        assert_eq!(join("FULL", "e.dept > d.id").len(), 446 + 9 + 8 + 2);
+
+        let mut rows = join("FULL", "e.dept = d.id AND e.id < 12");
+        rows.retain(|row| row[0].is_null() || row[0].as_i64() == Some(10));
+        rows.sort_by_key(|row| row[1].as_str().map(str::to_string));
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Int64(10), Value::Null],
+                vec![Value::Null, Value::Varchar("d11".into())],
+                vec![Value::Null, Value::Varchar("none".into())],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        // Fewer groups than the queries have, so that aggregations spill.
+        let ctx = ExecutorContext::new(&catalog, &temp).with_work_mem(4);
+        catalog
+            .create_table(
+                "sales",
+                Schema::new(vec![
+                    Column::new("region", DataType::Varchar(Some(10))),
+                    Column::new("year", DataType::Int32),
+                    Column::new(
+                        "amount",
+                        DataType::Decimal {
+                            precision: 10,
+                            scale: 2,
+                        },
+                    ),
+                ]),
+            )
+            .unwrap();
+        let rows: Vec<String> = (0..60)
+            .map(|i| format!("('r{}', {}, {}.50)", i % 6, 2020 + i % 3, i))
+            .collect();
+        run(
+            &ctx,
+            &format!("INSERT INTO sales VALUES {}", rows.join(", ")),
+        )
+        .unwrap();
+
+        let mut rows = run(
+        &ctx,
+        "SELECT region, count(*), sum(amount) FROM sales GROUP BY region HAVING sum(amount) > 300",
+    )
+    .unwrap();
+        rows.sort_by_key(|row| row[0].to_string());
+        let rendered: Vec<String> = rows
+            .iter()
+            .map(|row| format!("{} {} {}", row[0], row[1], row[2]))
+            .collect();
+        assert_eq!(
+            rendered,
+            vec!["r3 10 305.00", "r4 10 315.00", "r5 10 325.00"]
+        );
+
+        assert_eq!(
+            run(&ctx, "SELECT count(DISTINCT year), avg(year) FROM sales").unwrap(),
+            vec![vec![Value::Int64(3), Value::Float64(2021.0)]]
+        );
+        assert_eq!(
+            run(
+                &ctx,
+                "SELECT count(*), sum(year) FROM sales WHERE year > 3000"
+            )
+            .unwrap(),
+            vec![vec![Value::Int64(0), Value::Null]]
+        );
+        assert_eq!(
+            run(&ctx, "SELECT DISTINCT region, year FROM sales")
+                .unwrap()
+                .len(),
+            6
+        );
+        // The region decides the year: 6 groups by both, 6 by region and the total.
+        let rows = run(
+            &ctx,
+            "SELECT region, year, count(*) FROM sales GROUP BY ROLLUP (region, year)",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 13);
+        assert!(rows.contains(&vec![Value::Null, Value::Null, Value::Int64(60)]));
+    }
+
+    #[test]
+    fn test_subquery() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        catalog
+            .create_table(
+                "users",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int32).not_null(),
+                    Column::new("dept", DataType::Int32),
+                ]),
+            )
+            .unwrap();
+        catalog
+            .create_table(
+                "depts",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int32).not_null(),
+                    Column::new("name", DataType::Varchar(Some(10))),
+                ]),
+            )
+            .unwrap();
+        run(
+            &ctx,
+            "INSERT INTO users VALUES (1, 1), (2, 1), (3, 2), (4, NULL), (5, 2), (6, 1)",
+        )
+        .unwrap();
+        run(
+            &ctx,
+            "INSERT INTO depts VALUES (1, 'eng'), (2, 'ops'), (3, 'empty')",
+        )
+        .unwrap();
+
+        let query = |sql: &str| {
+            let mut ids = ids(run(&ctx, sql).unwrap());
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            query(
+                "SELECT id FROM users WHERE EXISTS \
+                 (SELECT * FROM depts WHERE depts.id = users.dept AND depts.name = 'ops')"
+            ),
+            vec![3, 5]
+        );
+        assert_eq!(
+            query("SELECT id FROM users u WHERE NOT EXISTS (SELECT 1 FROM depts d WHERE d.id = u.dept)"),
+            vec![4]
+        );
+        assert_eq!(
+            query("SELECT id FROM depts WHERE id IN (SELECT DISTINCT dept FROM users)"),
+            vec![1, 2]
+        );
+        assert_eq!(
+            query("SELECT id FROM users WHERE dept IN (SELECT id FROM depts WHERE name <> 'eng') AND id > 3"),
+            vec![5]
+        );
+        // NOT IN of a subquery with a NULL is never true.
+        assert_eq!(
+            query("SELECT id FROM depts WHERE id NOT IN (SELECT dept FROM users)"),
+            Vec::<i64>::new()
+        );
+        assert_eq!(
+            query("SELECT id FROM depts WHERE NOT id IN (SELECT dept FROM users WHERE dept IS NOT NULL)"),
+            vec![3]
+        );
+        // A NULL operand is not in a non-empty subquery, but is not in an empty one.
+        assert_eq!(
+            query("SELECT id FROM users WHERE dept NOT IN (SELECT id FROM depts WHERE id > 1)"),
+            vec![1, 2, 6]
+        );
+        assert_eq!(
+            query("SELECT id FROM users WHERE dept NOT IN (SELECT id FROM depts WHERE id > 5)"),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        // Nested subqueries, the inner one correlated with the middle one.
+        assert_eq!(
+            query(
+                "SELECT id FROM depts d WHERE EXISTS (SELECT * FROM users u WHERE u.dept = d.id \
+                 AND NOT EXISTS (SELECT * FROM users v WHERE v.id > u.id AND v.dept = u.dept))"
+            ),
+            vec![1, 2]
+        );
+
+        let error = run(
+            &ctx,
+            "SELECT id FROM depts WHERE EXISTS \
+             (SELECT count(*) FROM users WHERE users.dept = depts.id)",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "correlated subqueries are only supported in the WHERE of the subquery"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{buffer::buffer_pool_manager::BufferPoolManager, catalog::catalog::Catalog};
+
+/// The number of tuples an operator keeps in memory before it spills, unless configured
+/// otherwise.
+pub const DEFAULT_WORK_MEM: usize = 4096;
+
+/// What executors need besides their plan: the catalog to open tables and indexes with, and a
+/// buffer pool of temporary pages for operators that spill, such as hash joins and sorts.
+pub struct ExecutorContext<'c, 'a, B: BufferPoolManager + ?Sized, T: BufferPoolManager + ?Sized> {
+    pub catalog: &'c Catalog<'a, B>,
+    pub temp: &'a T,
+    /// The number of tuples a single operator may keep in memory.
+    pub work_mem: usize,
+}
+
+impl<'c, 'a, B: BufferPoolManager + ?Sized, T: BufferPoolManager + ?Sized>
+    ExecutorContext<'c, 'a, B, T>
+{
+    pub fn new(catalog: &'c Catalog<'a, B>, temp: &'a T) -> Self {
+        Self {
+            catalog,
+            temp,
+            work_mem: DEFAULT_WORK_MEM,
+        }
+    }
+
+    pub fn with_work_mem(self, work_mem: usize) -> Self {
+        assert!(work_mem > 0, "work_mem must be positive");
+        Self { work_mem, ..self }
+    }
+}
@@ -0,0 +1,134 @@
+//! Statements that change the schema or, like ANALYZE, the statistics. They have no executor
+//! and run against the catalog directly.
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager, catalog::catalog::Catalog,
+    planner::logical_plan::LogicalPlan,
+};
+
+use super::analyze::{analyze_table, AnalyzeConfig};
+
+/// Whether `plan` is run by [`execute_ddl`] rather than by an executor.
+pub fn is_ddl(plan: &LogicalPlan) -> bool {
+    matches!(
+        plan,
+        LogicalPlan::CreateTable { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::DropIndex { .. }
+            | LogicalPlan::Analyze { .. }
+    )
+}
+
+/// Run `plan`, which must be a statement without an executor, against the catalog. `CREATE
+/// INDEX` sorts the keys on `temp`.
+pub fn execute_ddl<B: BufferPoolManager + ?Sized, T: BufferPoolManager + ?Sized>(
+    catalog: &Catalog<'_, B>,
+    temp: &T,
+    plan: &LogicalPlan,
+    config: &AnalyzeConfig,
+) -> anyhow::Result<()> {
+    match plan {
+        LogicalPlan::CreateTable {
+            name,
+            schema,
+            if_not_exists,
+        } => {
+            if !(*if_not_exists && catalog.table(name).is_some()) {
+                catalog.create_table(name, schema.clone())?;
+            }
+        }
+        LogicalPlan::DropTable { name, if_exists } => {
+            if !catalog.drop_table(name)? && !if_exists {
+                anyhow::bail!("table \"{name}\" does not exist");
+            }
+        }
+        LogicalPlan::CreateIndex {
+            name,
+            table,
+            columns,
+            kind,
+            unique,
+        } => {
+            let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+            catalog.create_index_with_temp(temp, name, table, &columns, *kind, *unique)?;
+        }
+        LogicalPlan::DropIndex { name, if_exists } => {
+            if !catalog.drop_index(name)? && !if_exists {
+                anyhow::bail!("index \"{name}\" does not exist");
+            }
+        }
+        LogicalPlan::Analyze { tables } => {
+            for table in tables {
+                analyze_table(catalog, table, config)?;
+            }
+        }
+        _ => anyhow::bail!("{} is run by an executor", plan.describe()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_execute_ddl() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let temp_disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db.tmp")).unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let run = |sql: &str| {
+            let plan = Binder::new(&catalog)
+                .bind(&parse_statement(sql).unwrap())
+                .unwrap();
+            assert!(is_ddl(&plan));
+            execute_ddl(&catalog, &temp, &plan, &AnalyzeConfig::new()).map_err(|e| e.to_string())
+        };
+
+        run("CREATE TABLE t (id BIGINT NOT NULL, v INT)").unwrap();
+        let table = catalog.table("t").unwrap();
+        assert_eq!(
+            run("CREATE TABLE t (id BIGINT)").unwrap_err(),
+            "relation \"t\" already exists"
+        );
+        run("CREATE TABLE IF NOT EXISTS t (id BIGINT)").unwrap();
+        assert_eq!(catalog.table("t").unwrap(), table);
+
+        run("CREATE INDEX t_id ON t (id)").unwrap();
+        run("CREATE UNIQUE INDEX t_v_id ON t (v, id)").unwrap();
+        assert_eq!(catalog.index("t_v_id").unwrap().columns, [1, 0]);
+        assert_eq!(catalog.table_indexes(table.oid).len(), 2);
+        run("ANALYZE t").unwrap();
+        assert_eq!(catalog.table_statistics(table.oid).unwrap().row_count, 0);
+        run("DROP INDEX t_id").unwrap();
+        assert_eq!(
+            run("DROP INDEX t_id").unwrap_err(),
+            "index \"t_id\" does not exist"
+        );
+        run("DROP INDEX IF EXISTS t_id").unwrap();
+        run("DROP INDEX t_v_id").unwrap();
+
+        run("DROP TABLE t").unwrap();
+        assert!(catalog.table("t").is_none());
+        assert_eq!(
+            run("DROP TABLE t").unwrap_err(),
+            "table \"t\" does not exist"
+        );
+        run("DROP TABLE IF EXISTS t").unwrap();
+    }
+}
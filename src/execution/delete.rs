@@ -0,0 +1,151 @@
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::schema::Schema,
+    planner::logical_plan::modification_schema,
+    storage::table::{table_heap::TableHeap, tuple::Tuple},
+    types::value::Value,
+};
+
+use super::{
+    executor::{BoxedExecutor, Executor},
+    index_maintenance::IndexMaintenance,
+};
+
+/// Deletes the rows its child reads from a table, then produces the number of deleted rows.
+pub struct DeleteExecutor<'a, B: BufferPoolManager + ?Sized> {
+    child: BoxedExecutor<'a>,
+    heap: TableHeap<'a, B>,
+    indexes: IndexMaintenance<'a>,
+    schema: Schema,
+    done: bool,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> DeleteExecutor<'a, B> {
+    pub fn new(
+        child: BoxedExecutor<'a>,
+        heap: TableHeap<'a, B>,
+        indexes: IndexMaintenance<'a>,
+    ) -> Self {
+        Self {
+            child,
+            heap,
+            indexes,
+            schema: modification_schema(),
+            done: false,
+        }
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Executor for DeleteExecutor<'_, B> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.done = false;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+
+        let mut count = 0;
+        while let Some(tuple) = self.child.next()? {
+            let Some(rid) = tuple.rid() else {
+                anyhow::bail!("DELETE input has no record ids");
+            };
+            if self.heap.mark_delete(rid)? {
+                self.indexes.remove(tuple.values(), rid)?;
+                count += 1;
+            }
+        }
+
+        Ok(Some(Tuple::new(vec![Value::Int64(count)])))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::catalog::Catalog,
+        execution::{filter::FilterExecutor, values::ValuesExecutor},
+        planner::expr::Expr,
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{
+        super::index_maintenance::tests::{
+            check_indexes, create_table, lookup, rows, run, scan, Row,
+        },
+        *,
+    };
+
+    #[test]
+    fn test_delete() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let initial: Vec<Row> = (1..=10)
+            .map(|id| (id, (id != 5).then_some(id % 3), Some(id * 10), "row"))
+            .collect();
+        let table = create_table(&catalog, &initial);
+
+        let delete = |predicate: Option<Expr>| {
+            let child = match predicate {
+                Some(predicate) => Box::new(FilterExecutor::new(scan(&catalog, &table), predicate)),
+                None => scan(&catalog, &table),
+            };
+            let indexes = IndexMaintenance::open(&catalog, &table).unwrap();
+            let heap = catalog.table_heap(&table).unwrap();
+            run(DeleteExecutor::new(child, heap, indexes))
+        };
+        let grp_is = |grp: i64| {
+            Expr::binary(
+                BinaryOp::Eq,
+                Expr::column(1, DataType::Int64),
+                Expr::literal(Value::Int64(grp), DataType::Int64),
+                DataType::Boolean,
+            )
+        };
+
+        let before = rows(&catalog, &table);
+        assert_eq!(delete(Some(grp_is(1))).unwrap(), 4);
+        let after = rows(&catalog, &table);
+        assert_eq!(after.len(), 6);
+        assert!(after.iter().all(|(_, row)| row[1] != Value::Int64(1)));
+        assert!(lookup(&catalog, "t_grp", &[Value::Int64(1)]).is_empty());
+        for id in [1, 4, 7, 10] {
+            assert!(lookup(&catalog, "t_id", &[Value::Int64(id)]).is_empty());
+            assert!(lookup(&catalog, "t_code", &[Value::Int64(id * 10)]).is_empty());
+        }
+        assert_eq!(lookup(&catalog, "t_id", &[Value::Int64(2)]), [before[1].0]);
+        check_indexes(&catalog, &table);
+        assert_eq!(delete(Some(grp_is(1))).unwrap(), 0);
+
+        // Rows with a NULL key, which that index has no entry for, are deleted all the same.
+        assert_eq!(delete(None).unwrap(), 6);
+        assert!(rows(&catalog, &table).is_empty());
+        check_indexes(&catalog, &table);
+
+        // Deleting needs the record ids of the rows.
+        let values = ValuesExecutor::new(
+            vec![vec![Expr::literal(Value::Int64(1), DataType::Int64)]],
+            table.schema.clone(),
+        );
+        let indexes = IndexMaintenance::open(&catalog, &table).unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        let err = run(DeleteExecutor::new(Box::new(values), heap, indexes)).unwrap_err();
+        assert_eq!(err.to_string(), "DELETE input has no record ids");
+    }
+}
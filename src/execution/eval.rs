@@ -0,0 +1,602 @@
+//! Evaluation of bound expressions over a row of values.
+//!
+//! The binder has already made every operand of an operator the same type, so evaluation only
+//! has to follow SQL's rules for NULL and report values that do not fit their type. Comparisons
+//! and boolean operators use three-valued logic: a NULL operand makes the result NULL unless
+//! the other operand decides it, as in `false AND NULL`.
+
+use std::cmp::Ordering;
+
+use crate::{
+    planner::expr::{Expr, ExprKind, ScalarFunction},
+    sql::ast::BinaryOp,
+    types::{
+        data_type::DataType,
+        date_time::{Date, Timestamp},
+        decimal::Decimal,
+        value::Value,
+    },
+};
+
+/// Evaluate `expr` over `row`, whose values its column references index.
+pub fn evaluate(expr: &Expr, row: &[Value]) -> anyhow::Result<Value> {
+    let value = match &expr.kind {
+        ExprKind::Column(index) => row[*index].clone(),
+        ExprKind::Literal(value) => value.clone(),
+        ExprKind::OuterColumn(_) | ExprKind::Exists(_) | ExprKind::InSubquery { .. } => {
+            anyhow::bail!("subquery {expr} must be turned into a join before it is evaluated")
+        }
+        ExprKind::Not(inner) => match evaluate(inner, row)? {
+            Value::Null => Value::Null,
+            value => Value::Boolean(!as_bool(&value)?),
+        },
+        ExprKind::Negate(inner) => negate(evaluate(inner, row)?)?,
+        ExprKind::Binary { op, left, right } => match op {
+            BinaryOp::And | BinaryOp::Or => {
+                // The left operand alone decides `false AND x` and `true OR x`.
+                let decisive = *op == BinaryOp::Or;
+                let left = evaluate(left, row)?;
+                if !left.is_null() && as_bool(&left)? == decisive {
+                    return Ok(left);
+                }
+                let right = evaluate(right, row)?;
+                match (left, right) {
+                    (_, right) if !right.is_null() && as_bool(&right)? == decisive => right,
+                    (Value::Null, _) | (_, Value::Null) => Value::Null,
+                    (_, right) => right,
+                }
+            }
+            _ => binary(
+                *op,
+                evaluate(left, row)?,
+                evaluate(right, row)?,
+                expr.data_type,
+            )?,
+        },
+        ExprKind::IsNull { expr, negated } => {
+            Value::Boolean(evaluate(expr, row)?.is_null() != *negated)
+        }
+        ExprKind::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let value = evaluate(expr, row)?;
+            if value.is_null() {
+                return Ok(Value::Null);
+            }
+            let mut saw_null = false;
+            let mut found = false;
+            for item in list {
+                match compare(&value, &evaluate(item, row)?) {
+                    Some(Ordering::Equal) => {
+                        found = true;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => saw_null = true,
+                }
+            }
+            if found {
+                Value::Boolean(!negated)
+            } else if saw_null {
+                Value::Null
+            } else {
+                Value::Boolean(*negated)
+            }
+        }
+        ExprKind::Like {
+            expr,
+            pattern,
+            negated,
+        } => match (evaluate(expr, row)?, evaluate(pattern, row)?) {
+            (Value::Varchar(s), Value::Varchar(pattern)) => {
+                Value::Boolean(like(&s, &pattern) != *negated)
+            }
+            _ => Value::Null,
+        },
+        ExprKind::Cast(inner) => cast(evaluate(inner, row)?, expr.data_type)?,
+        ExprKind::Function { func, args } => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, row))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            function(*func, args)?
+        }
+    };
+
+    Ok(value)
+}
+
+/// Evaluate a predicate, treating NULL as false as WHERE does.
+pub fn evaluate_predicate(expr: &Expr, row: &[Value]) -> anyhow::Result<bool> {
+    match evaluate(expr, row)? {
+        Value::Null => Ok(false),
+        value => as_bool(&value),
+    }
+}
+
+/// Compare two values of the same type, or return None if either is NULL.
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        (Value::Float64(a), Value::Float64(b)) => Some(a.total_cmp(b)),
+        (Value::Decimal(a), Value::Decimal(b)) => Some(a.cmp(b)),
+        (Value::Varchar(a), Value::Varchar(b)) => Some(a.cmp(b)),
+        (Value::Bytea(a), Value::Bytea(b)) => Some(a.cmp(b)),
+        (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+        (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+        (a, b) => Some(a.as_i64()?.cmp(&b.as_i64()?)),
+    }
+}
+
+fn as_bool(value: &Value) -> anyhow::Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow::anyhow!("expected a boolean, got {value}"))
+}
+
+/// Convert `value` to `to`, failing if it does not fit.
+pub fn cast(value: Value, to: DataType) -> anyhow::Result<Value> {
+    let invalid = |value: &dyn std::fmt::Display| {
+        anyhow::anyhow!("invalid input syntax for type {to}: \"{value}\"")
+    };
+    let value = match (value, to) {
+        (Value::Null, _) => Value::Null,
+        (value, DataType::Varchar(max_len)) => {
+            let s = match value {
+                Value::Varchar(s) => s,
+                value => value.to_string(),
+            };
+            if max_len.map_or(false, |max_len| s.chars().count() > max_len as usize) {
+                anyhow::bail!("value too long for type {to}");
+            }
+            Value::Varchar(s)
+        }
+        (Value::Varchar(s), _) => {
+            let text = s.trim();
+            match to {
+                DataType::Boolean => Value::Boolean(match text.to_ascii_lowercase().as_str() {
+                    "true" | "t" | "yes" | "y" | "on" | "1" => true,
+                    "false" | "f" | "no" | "n" | "off" | "0" => false,
+                    _ => return Err(invalid(&s)),
+                }),
+                _ if to.is_integer() => integer(text.parse::<i64>().map_err(|_| invalid(&s))?, to)?,
+                DataType::Float64 => Value::Float64(text.parse().map_err(|_| invalid(&s))?),
+                DataType::Decimal { .. } => {
+                    decimal(Decimal::parse(text).ok_or_else(|| invalid(&s))?, to)?
+                }
+                DataType::Date => Value::Date(Date::parse(text).ok_or_else(|| invalid(&s))?),
+                DataType::Timestamp => {
+                    Value::Timestamp(Timestamp::parse(text).ok_or_else(|| invalid(&s))?)
+                }
+                DataType::Bytea => Value::Bytea(match s.strip_prefix("\\x") {
+                    Some(hex) => parse_hex(hex).ok_or_else(|| invalid(&s))?,
+                    None => s.into_bytes(),
+                }),
+                _ => unreachable!("{to} is handled above"),
+            }
+        }
+        (value, to) if to.is_integer() => match value {
+            Value::Float64(f) => {
+                let rounded = f.round();
+                if !(i64::MIN as f64..=i64::MAX as f64).contains(&rounded) {
+                    anyhow::bail!("{to} out of range");
+                }
+                integer(rounded as i64, to)?
+            }
+            Value::Decimal(d) => {
+                let rounded = d
+                    .rescale(0)
+                    .and_then(|d| i64::try_from(d.mantissa()).ok())
+                    .ok_or_else(|| anyhow::anyhow!("{to} out of range"))?;
+                integer(rounded, to)?
+            }
+            value => match value.as_i64() {
+                Some(v) => integer(v, to)?,
+                None => anyhow::bail!("cannot cast {value} to {to}"),
+            },
+        },
+        (value, DataType::Float64) => Value::Float64(match value {
+            Value::Float64(f) => f,
+            Value::Decimal(d) => d.to_f64(),
+            value => match value.as_i64() {
+                Some(v) => v as f64,
+                None => anyhow::bail!("cannot cast {value} to {to}"),
+            },
+        }),
+        (value, DataType::Decimal { scale, .. }) => {
+            let d = match value {
+                Value::Decimal(d) => d,
+                Value::Float64(f) => Decimal::from_f64(f, scale)
+                    .ok_or_else(|| anyhow::anyhow!("numeric field overflow"))?,
+                value => match value.as_i64() {
+                    Some(v) => Decimal::from_i64(v),
+                    None => anyhow::bail!("cannot cast {value} to {to}"),
+                },
+            };
+            decimal(d, to)?
+        }
+        (Value::Date(d), DataType::Timestamp) => Value::Timestamp(Timestamp::from_date(d)),
+        (Value::Timestamp(t), DataType::Date) => Value::Date(t.date()),
+        (value, to) if value.data_type() == Some(to) => value,
+        (value, to) => anyhow::bail!("cannot cast {value} to {to}"),
+    };
+
+    Ok(value)
+}
+
+/// An integer of type `to`, failing if `v` is out of its range.
+fn integer(v: i64, to: DataType) -> anyhow::Result<Value> {
+    let out_of_range = || anyhow::anyhow!("{to} out of range");
+    Ok(match to {
+        DataType::Int8 => Value::Int8(v.try_into().map_err(|_| out_of_range())?),
+        DataType::Int16 => Value::Int16(v.try_into().map_err(|_| out_of_range())?),
+        DataType::Int32 => Value::Int32(v.try_into().map_err(|_| out_of_range())?),
+        DataType::Int64 => Value::Int64(v),
+        _ => unreachable!("{to} is not an integer type"),
+    })
+}
+
+/// A decimal fitted to `to`, failing if its integer part has too many digits.
+fn decimal(d: Decimal, to: DataType) -> anyhow::Result<Value> {
+    let DataType::Decimal { precision, scale } = to else {
+        unreachable!("{to} is not a decimal type");
+    };
+    d.fit(precision, scale)
+        .map(Value::Decimal)
+        .ok_or_else(|| anyhow::anyhow!("numeric field overflow"))
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn negate(value: Value) -> anyhow::Result<Value> {
+    Ok(match value {
+        Value::Null => Value::Null,
+        Value::Float64(f) => Value::Float64(-f),
+        Value::Decimal(d) => Value::Decimal(Decimal::new(-d.mantissa(), d.scale())),
+        value => {
+            let data_type = value.data_type().unwrap();
+            match value.as_i64() {
+                Some(v) => integer(
+                    v.checked_neg()
+                        .ok_or_else(|| anyhow::anyhow!("{data_type} out of range"))?,
+                    data_type,
+                )?,
+                None => anyhow::bail!("cannot negate {value}"),
+            }
+        }
+    })
+}
+
+/// Apply a comparison, arithmetic or concatenation operator whose result has `data_type`.
+fn binary(op: BinaryOp, left: Value, right: Value, data_type: DataType) -> anyhow::Result<Value> {
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+    if op.is_comparison() {
+        let ordering = compare(&left, &right)
+            .ok_or_else(|| anyhow::anyhow!("cannot compare {left} with {right}"))?;
+        let result = match op {
+            BinaryOp::Eq => ordering == Ordering::Equal,
+            BinaryOp::NotEq => ordering != Ordering::Equal,
+            BinaryOp::Lt => ordering == Ordering::Less,
+            BinaryOp::LtEq => ordering != Ordering::Greater,
+            BinaryOp::Gt => ordering == Ordering::Greater,
+            BinaryOp::GtEq => ordering != Ordering::Less,
+            _ => unreachable!(),
+        };
+        return Ok(Value::Boolean(result));
+    }
+    if op == BinaryOp::Concat {
+        return match (left, right) {
+            (Value::Varchar(mut a), Value::Varchar(b)) => {
+                a.push_str(&b);
+                Ok(Value::Varchar(a))
+            }
+            (a, b) => anyhow::bail!("cannot concatenate {a} and {b}"),
+        };
+    }
+
+    let division_by_zero = || anyhow::anyhow!("division by zero");
+    match (left, right) {
+        (Value::Float64(a), Value::Float64(b)) => Ok(Value::Float64(match op {
+            BinaryOp::Plus => a + b,
+            BinaryOp::Minus => a - b,
+            BinaryOp::Multiply => a * b,
+            BinaryOp::Divide if b == 0.0 => return Err(division_by_zero()),
+            BinaryOp::Divide => a / b,
+            BinaryOp::Modulo if b == 0.0 => return Err(division_by_zero()),
+            BinaryOp::Modulo => a % b,
+            _ => unreachable!(),
+        })),
+        (Value::Decimal(a), Value::Decimal(b)) => {
+            let DataType::Decimal { scale, .. } = data_type else {
+                anyhow::bail!("decimal arithmetic producing {data_type}");
+            };
+            let result = match op {
+                BinaryOp::Plus => a.checked_add(&b),
+                BinaryOp::Minus => a.checked_sub(&b),
+                BinaryOp::Multiply => a.checked_mul(&b),
+                BinaryOp::Divide if b.mantissa() == 0 => return Err(division_by_zero()),
+                BinaryOp::Divide => a.checked_div(&b, scale),
+                BinaryOp::Modulo if b.mantissa() == 0 => return Err(division_by_zero()),
+                BinaryOp::Modulo => {
+                    let scale = a.scale().max(b.scale());
+                    a.rescale(scale)
+                        .zip(b.rescale(scale))
+                        .map(|(a, b)| Decimal::new(a.mantissa() % b.mantissa(), scale))
+                }
+                _ => unreachable!(),
+            };
+            decimal(
+                result.ok_or_else(|| anyhow::anyhow!("numeric field overflow"))?,
+                data_type,
+            )
+        }
+        (a, b) => {
+            let (Some(x), Some(y)) = (a.as_i64(), b.as_i64()) else {
+                anyhow::bail!("operator {} does not apply to {a} and {b}", op.symbol());
+            };
+            let result = match op {
+                BinaryOp::Plus => x.checked_add(y),
+                BinaryOp::Minus => x.checked_sub(y),
+                BinaryOp::Multiply => x.checked_mul(y),
+                BinaryOp::Divide if y == 0 => return Err(division_by_zero()),
+                BinaryOp::Divide => x.checked_div(y),
+                BinaryOp::Modulo if y == 0 => return Err(division_by_zero()),
+                BinaryOp::Modulo => x.checked_rem(y),
+                _ => unreachable!(),
+            };
+            integer(
+                result.ok_or_else(|| anyhow::anyhow!("{data_type} out of range"))?,
+                data_type,
+            )
+        }
+    }
+}
+
+fn function(func: ScalarFunction, args: Vec<Value>) -> anyhow::Result<Value> {
+    if func == ScalarFunction::Coalesce {
+        return Ok(args
+            .into_iter()
+            .find(|arg| !arg.is_null())
+            .unwrap_or(Value::Null));
+    }
+    let mut args = args.into_iter();
+    let arg = args.next().unwrap_or(Value::Null);
+    Ok(match (func, arg) {
+        (_, Value::Null) => Value::Null,
+        (ScalarFunction::Lower, Value::Varchar(s)) => Value::Varchar(s.to_lowercase()),
+        (ScalarFunction::Upper, Value::Varchar(s)) => Value::Varchar(s.to_uppercase()),
+        (ScalarFunction::Length, Value::Varchar(s)) => Value::Int64(s.chars().count() as i64),
+        (ScalarFunction::Abs, Value::Float64(f)) => Value::Float64(f.abs()),
+        (ScalarFunction::Abs, Value::Decimal(d)) => {
+            Value::Decimal(Decimal::new(d.mantissa().abs(), d.scale()))
+        }
+        (ScalarFunction::Abs, value) => match value.as_i64() {
+            Some(v) if v < 0 => negate(value)?,
+            Some(_) => value,
+            None => anyhow::bail!("abs() does not apply to {value}"),
+        },
+        (func, value) => anyhow::bail!("{}() does not apply to {value}", func.name()),
+    })
+}
+
+/// Match `s` against a LIKE pattern, in which `%` matches any sequence of characters, `_` any
+/// one character and `\` makes the next character match itself.
+fn like(s: &str, pattern: &str) -> bool {
+    enum Token {
+        Any,
+        One,
+        Char(char),
+    }
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Any,
+            '_' => Token::One,
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            c => Token::Char(c),
+        });
+    }
+
+    // matches[j]: whether the characters seen so far match the first j tokens.
+    let mut matches = vec![false; tokens.len() + 1];
+    matches[0] = true;
+    for j in 0..tokens.len() {
+        matches[j + 1] = matches[j] && matches!(tokens[j], Token::Any);
+    }
+    for c in s.chars() {
+        let mut next = vec![false; tokens.len() + 1];
+        for (j, token) in tokens.iter().enumerate() {
+            next[j + 1] = match token {
+                Token::Any => next[j] || matches[j + 1],
+                Token::One => matches[j],
+                Token::Char(p) => matches[j] && *p == c,
+            };
+        }
+        matches = next;
+    }
+    matches[tokens.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(v: i32) -> Expr {
+        Expr::literal(Value::Int32(v), DataType::Int32)
+    }
+
+    fn null() -> Expr {
+        Expr::literal(Value::Null, DataType::Boolean)
+    }
+
+    fn boolean(b: bool) -> Expr {
+        Expr::literal(Value::Boolean(b), DataType::Boolean)
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let and = |l, r| Expr::binary(BinaryOp::And, l, r, DataType::Boolean);
+        let or = |l, r| Expr::binary(BinaryOp::Or, l, r, DataType::Boolean);
+        assert_eq!(
+            evaluate(&and(null(), boolean(false)), &[]).unwrap(),
+            Value::Boolean(false)
+        );
+        assert_eq!(
+            evaluate(&and(null(), boolean(true)), &[]).unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            evaluate(&or(null(), boolean(true)), &[]).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            evaluate(&or(boolean(false), null()), &[]).unwrap(),
+            Value::Null
+        );
+
+        let in_list = |list| {
+            Expr::new(
+                ExprKind::InList {
+                    expr: Box::new(int(1)),
+                    list,
+                    negated: false,
+                },
+                DataType::Boolean,
+            )
+        };
+        let int_null = Expr::literal(Value::Null, DataType::Int32);
+        assert_eq!(
+            evaluate(&in_list(vec![int_null.clone(), int(1)]), &[]).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            evaluate(&in_list(vec![int_null, int(2)]), &[]).unwrap(),
+            Value::Null
+        );
+        assert!(!evaluate_predicate(&null(), &[]).unwrap());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let row = [Value::Int32(i32::MAX), Value::Int32(0)];
+        let column = |i| Expr::column(i, DataType::Int32);
+        let add = Expr::binary(BinaryOp::Plus, column(0), int(1), DataType::Int32);
+        assert_eq!(
+            evaluate(&add, &row).unwrap_err().to_string(),
+            "INT32 out of range"
+        );
+        let div = Expr::binary(BinaryOp::Divide, int(7), column(1), DataType::Int32);
+        assert_eq!(
+            evaluate(&div, &row).unwrap_err().to_string(),
+            "division by zero"
+        );
+        let div = Expr::binary(BinaryOp::Divide, int(-7), int(2), DataType::Int32);
+        assert_eq!(evaluate(&div, &row).unwrap(), Value::Int32(-3));
+
+        let decimal_type = DataType::Decimal {
+            precision: 38,
+            scale: 6,
+        };
+        let dec = |s| {
+            let d = Decimal::parse(s).unwrap();
+            Expr::literal(
+                Value::Decimal(d),
+                DataType::Decimal {
+                    precision: 38,
+                    scale: d.scale(),
+                },
+            )
+        };
+        let div = Expr::binary(BinaryOp::Divide, dec("1.00"), dec("3.00"), decimal_type);
+        assert_eq!(evaluate(&div, &[]).unwrap().to_string(), "0.333333");
+        let modulo = Expr::binary(BinaryOp::Modulo, dec("7.50"), dec("2.00"), decimal_type);
+        assert_eq!(evaluate(&modulo, &[]).unwrap().to_string(), "1.500000");
+    }
+
+    #[test]
+    fn test_cast() {
+        let varchar = |s: &str| Value::Varchar(s.to_string());
+        assert_eq!(
+            cast(varchar(" 42 "), DataType::Int16).unwrap(),
+            Value::Int16(42)
+        );
+        assert_eq!(
+            cast(varchar("abc"), DataType::Int32)
+                .unwrap_err()
+                .to_string(),
+            "invalid input syntax for type INT32: \"abc\""
+        );
+        assert_eq!(
+            cast(Value::Int64(300), DataType::Int8)
+                .unwrap_err()
+                .to_string(),
+            "INT8 out of range"
+        );
+        assert_eq!(
+            cast(varchar("abcd"), DataType::Varchar(Some(3)))
+                .unwrap_err()
+                .to_string(),
+            "value too long for type VARCHAR(3)"
+        );
+        assert_eq!(
+            cast(Value::Float64(2.5), DataType::Int32).unwrap(),
+            Value::Int32(3)
+        );
+        assert_eq!(
+            cast(
+                Value::Decimal(Decimal::parse("123.456").unwrap()),
+                DataType::Decimal {
+                    precision: 5,
+                    scale: 2
+                }
+            )
+            .unwrap()
+            .to_string(),
+            "123.46"
+        );
+        assert_eq!(
+            cast(varchar("2024-02-29 12:00:00"), DataType::Date)
+                .unwrap_err()
+                .to_string(),
+            "invalid input syntax for type DATE: \"2024-02-29 12:00:00\""
+        );
+        assert_eq!(
+            cast(
+                cast(varchar("2024-02-29 12:00:00"), DataType::Timestamp).unwrap(),
+                DataType::Date
+            )
+            .unwrap()
+            .to_string(),
+            "2024-02-29"
+        );
+        assert_eq!(
+            cast(varchar("\\xdead"), DataType::Bytea).unwrap(),
+            Value::Bytea(vec![0xde, 0xad])
+        );
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like("hello", "h%o"));
+        assert!(like("hello", "_ell_"));
+        assert!(like("", "%"));
+        assert!(!like("hello", "h_o"));
+        assert!(like("50%", "50\\%"));
+        assert!(!like("500", "50\\%"));
+        assert!(like("abcabc", "%bc%c"));
+    }
+}
@@ -0,0 +1,22 @@
+//! The interface of query operators.
+//!
+//! Operators follow the Volcano model: every executor pulls tuples from its children one at a
+//! time with [`Executor::next`], so a pipeline of scans, filters and projections needs memory
+//! for only one tuple per operator. Executors reach table pages only through the buffer pool
+//! and never hold a page between calls to `next`, so plans work with a bounded pool.
+
+use crate::{catalog::schema::Schema, storage::table::tuple::Tuple};
+
+pub trait Executor {
+    /// Prepare to produce tuples from the start. Called before the first [`Executor::next`],
+    /// and again to rescan, for example for the inner side of a nested-loop join.
+    fn init(&mut self) -> anyhow::Result<()>;
+
+    /// The next tuple, or None when there are no more.
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>>;
+
+    /// The columns of the tuples the executor produces.
+    fn schema(&self) -> &Schema;
+}
+
+pub type BoxedExecutor<'a> = Box<dyn Executor + 'a>;
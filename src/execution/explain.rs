@@ -0,0 +1,482 @@
+//! `EXPLAIN`: the tree of executors a plan runs as, with the rows and cost the
+//! [cost model](crate::optimizer::cost) estimates for each.
+//!
+//! `EXPLAIN ANALYZE` also runs the plan, with every executor wrapped in one that measures it,
+//! and reports for each operator:
+//!
+//! | field   | meaning                                                              |
+//! |---------|----------------------------------------------------------------------|
+//! | rows    | the rows it produced, over all loops                                 |
+//! | loops   | how many times it was started, like the inner side of a nested loop  |
+//! | time    | the time spent in it, its inputs included                            |
+//! | buffers | fetches that hit and missed the buffer pools, and the pages they read and wrote, its inputs included |
+//!
+//! The buffer counts are those of the database and temporary pools together, so work done
+//! by other queries at the same time is counted too. The output has one row per operator in
+//! the text format, indented under its parent, and a single row in the JSON format.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    buffer::buffer_pool_manager::{BufferPoolManager, BufferPoolStats},
+    catalog::schema::Schema,
+    optimizer::cost::{CostModel, Estimate},
+    planner::logical_plan::{explain_schema, LogicalPlan},
+    sql::ast::ExplainFormat,
+    storage::table::tuple::Tuple,
+    types::value::Value,
+};
+
+use super::{
+    builder::{build_executor_with, executor_name},
+    context::ExecutorContext,
+    executor::{BoxedExecutor, Executor},
+};
+
+/// What an operator did while EXPLAIN ANALYZE ran it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OperatorMetrics {
+    pub rows: u64,
+    pub loops: u64,
+    pub time: Duration,
+    pub buffers: BufferPoolStats,
+}
+
+/// An operator of an explained plan.
+#[derive(Debug, Clone)]
+pub struct ExplainNode {
+    /// The executor that runs it.
+    pub operator: &'static str,
+    /// What it does, like the condition of a join.
+    pub details: String,
+    pub estimate: Estimate,
+    /// Filled in as the plan runs, if it is analyzed.
+    pub metrics: Option<Rc<RefCell<OperatorMetrics>>>,
+    pub children: Vec<ExplainNode>,
+}
+
+type Metrics = HashMap<*const LogicalPlan, Rc<RefCell<OperatorMetrics>>>;
+
+impl ExplainNode {
+    /// The tree of `plan`, with the metrics of the operators in `metrics` by their address.
+    fn new<B: BufferPoolManager + ?Sized>(
+        plan: &LogicalPlan,
+        model: &CostModel<'_, '_, B>,
+        metrics: &Metrics,
+    ) -> anyhow::Result<Self> {
+        let description = plan.describe();
+        let details = description
+            .split_once(' ')
+            .map_or("", |(_, details)| details);
+        Ok(Self {
+            operator: executor_name(plan),
+            details: details.to_string(),
+            estimate: model.estimate(plan)?,
+            metrics: metrics.get(&(plan as *const _)).cloned(),
+            children: plan
+                .children()
+                .into_iter()
+                .map(|child| Self::new(child, model, metrics))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    fn reset(&self) {
+        if let Some(metrics) = &self.metrics {
+            *metrics.borrow_mut() = OperatorMetrics::default();
+        }
+        for child in &self.children {
+            child.reset();
+        }
+    }
+
+    fn write_text(&self, depth: usize, lines: &mut Vec<String>) {
+        let mut line = format!("{}{}", "  ".repeat(depth), self.operator);
+        if !self.details.is_empty() {
+            line += &format!(" {}", self.details);
+        }
+        line += &format!(
+            "  (cost={:.2} rows={:.0})",
+            self.estimate.cost, self.estimate.rows
+        );
+        if let Some(metrics) = &self.metrics {
+            let metrics = metrics.borrow();
+            let buffers = metrics.buffers;
+            line += &format!(
+                " (actual rows={} loops={} time={:.3}ms) (buffers hit={} miss={} read={} written={})",
+                metrics.rows,
+                metrics.loops,
+                millis(metrics.time),
+                buffers.hits,
+                buffers.misses,
+                buffers.reads,
+                buffers.writes
+            );
+        }
+        lines.push(line);
+        for child in &self.children {
+            child.write_text(depth + 1, lines);
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let mut fields = vec![
+            ("operator", Json::String(self.operator.to_string())),
+            ("details", Json::String(self.details.clone())),
+            (
+                "estimated_rows",
+                Json::Number(format!("{:.0}", self.estimate.rows)),
+            ),
+            (
+                "estimated_cost",
+                Json::Number(format!("{:.2}", self.estimate.cost)),
+            ),
+        ];
+        if let Some(metrics) = &self.metrics {
+            let metrics = metrics.borrow();
+            let buffers = metrics.buffers;
+            let count = |n: u64| Json::Number(n.to_string());
+            fields.extend([
+                ("actual_rows", count(metrics.rows)),
+                ("actual_loops", count(metrics.loops)),
+                (
+                    "actual_time_ms",
+                    Json::Number(format!("{:.3}", millis(metrics.time))),
+                ),
+                (
+                    "buffers",
+                    Json::Object(vec![
+                        ("hits", count(buffers.hits)),
+                        ("misses", count(buffers.misses)),
+                        ("reads", count(buffers.reads)),
+                        ("writes", count(buffers.writes)),
+                    ]),
+                ),
+            ]);
+        }
+        fields.push((
+            "children",
+            Json::Array(self.children.iter().map(Self::to_json).collect()),
+        ));
+        Json::Object(fields)
+    }
+}
+
+/// Produces the explanation of a plan, after running it for EXPLAIN ANALYZE.
+pub struct ExplainExecutor<'a> {
+    root: ExplainNode,
+    /// The instrumented executor of the plan, for EXPLAIN ANALYZE.
+    child: Option<BoxedExecutor<'a>>,
+    format: ExplainFormat,
+    schema: Schema,
+    /// The rows left to produce, once the plan has run.
+    output: Option<VecDeque<String>>,
+}
+
+impl<'a> ExplainExecutor<'a> {
+    pub fn new<B: BufferPoolManager + ?Sized, T: BufferPoolManager + ?Sized>(
+        ctx: &ExecutorContext<'_, 'a, B, T>,
+        plan: &LogicalPlan,
+        analyze: bool,
+        format: ExplainFormat,
+    ) -> anyhow::Result<Self> {
+        let model = CostModel::new(ctx.catalog, ctx.work_mem);
+        let mut metrics = Metrics::new();
+        let child = if analyze {
+            let bpm = ctx.catalog.buffer_pool();
+            let temp = ctx.temp;
+            let stats: Rc<dyn Fn() -> BufferPoolStats + 'a> =
+                Rc::new(move || bpm.stats() + temp.stats());
+            let child = build_executor_with(ctx, plan, &mut |plan, executor| {
+                let operator_metrics = Rc::new(RefCell::new(OperatorMetrics::default()));
+                metrics.insert(plan as *const _, operator_metrics.clone());
+                Box::new(InstrumentedExecutor {
+                    inner: executor,
+                    metrics: operator_metrics,
+                    stats: stats.clone(),
+                })
+            })?;
+            Some(child)
+        } else {
+            None
+        };
+        Ok(Self {
+            root: ExplainNode::new(plan, &model, &metrics)?,
+            child,
+            format,
+            schema: explain_schema(),
+            output: None,
+        })
+    }
+
+    /// Run the plan if it is analyzed and render the explanation.
+    fn explain(&mut self) -> anyhow::Result<VecDeque<String>> {
+        let mut execution_time = None;
+        if let Some(child) = &mut self.child {
+            self.root.reset();
+            let start = Instant::now();
+            child.init()?;
+            while child.next()?.is_some() {}
+            execution_time = Some(start.elapsed());
+        }
+        Ok(match self.format {
+            ExplainFormat::Text => {
+                let mut lines = Vec::new();
+                self.root.write_text(0, &mut lines);
+                if let Some(time) = execution_time {
+                    lines.push(format!("Execution time: {:.3}ms", millis(time)));
+                }
+                lines.into()
+            }
+            ExplainFormat::Json => {
+                let mut fields = vec![("plan", self.root.to_json())];
+                if let Some(time) = execution_time {
+                    fields.push((
+                        "execution_time_ms",
+                        Json::Number(format!("{:.3}", millis(time))),
+                    ));
+                }
+                let mut document = String::new();
+                Json::Object(fields).write(0, &mut document);
+                VecDeque::from([document])
+            }
+        })
+    }
+}
+
+impl Executor for ExplainExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.output = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.output.is_none() {
+            self.output = Some(self.explain()?);
+        }
+        let line = self.output.as_mut().unwrap().pop_front();
+        Ok(line.map(|line| Tuple::new(vec![Value::Varchar(line)])))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// Measures the executor it wraps into the metrics of its operator.
+struct InstrumentedExecutor<'a> {
+    inner: BoxedExecutor<'a>,
+    metrics: Rc<RefCell<OperatorMetrics>>,
+    /// The current counts of the buffer pools.
+    stats: Rc<dyn Fn() -> BufferPoolStats + 'a>,
+}
+
+impl InstrumentedExecutor<'_> {
+    fn measure<R>(&mut self, f: impl FnOnce(&mut BoxedExecutor<'_>) -> R) -> R {
+        let before = (self.stats)();
+        let start = Instant::now();
+        let result = f(&mut self.inner);
+        let mut metrics = self.metrics.borrow_mut();
+        metrics.time += start.elapsed();
+        metrics.buffers = metrics.buffers + (self.stats)().since(before);
+        result
+    }
+}
+
+impl Executor for InstrumentedExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.metrics.borrow_mut().loops += 1;
+        self.measure(|inner| inner.init())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        let tuple = self.measure(|inner| inner.next())?;
+        if tuple.is_some() {
+            self.metrics.borrow_mut().rows += 1;
+        }
+        Ok(tuple)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.inner.schema()
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Just enough JSON to write the explanation, indented by two spaces.
+enum Json {
+    /// Already formatted.
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn write(&self, depth: usize, out: &mut String) {
+        let indent = |depth: usize| "  ".repeat(depth);
+        match self {
+            Json::Number(number) => out.push_str(number),
+            Json::String(string) => write_string(string, out),
+            Json::Array(items) if items.is_empty() => out.push_str("[]"),
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&indent(depth + 1));
+                    item.write(depth + 1, out);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&indent(depth));
+                out.push(']');
+            }
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    out.push_str(&indent(depth + 1));
+                    write_string(key, out);
+                    out.push_str(": ");
+                    value.write(depth + 1, out);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&indent(depth));
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_string(string: &str, out: &mut String) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        optimizer::optimizer::Optimizer,
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{super::builder::build_executor, *};
+
+    #[test]
+    fn test_explain() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        for name in ["a", "b"] {
+            catalog
+                .create_table(
+                    name,
+                    Schema::new(vec![
+                        Column::new("id", DataType::Int64).not_null(),
+                        Column::new("v", DataType::Int32),
+                    ]),
+                )
+                .unwrap();
+        }
+        let run = |sql: &str| {
+            let statement = parse_statement(sql).unwrap();
+            let plan = Binder::new(&catalog).bind(&statement).unwrap();
+            let plan = Optimizer::new(&catalog).optimize(plan).unwrap();
+            let mut executor = build_executor(&ctx, &plan).unwrap();
+            executor.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = executor.next().unwrap() {
+                rows.push(match &tuple.values()[0] {
+                    Value::Varchar(row) => row.clone(),
+                    value => format!("{value:?}"),
+                });
+            }
+            rows
+        };
+        run("INSERT INTO a VALUES (1, 10), (2, 20), (3, 30)");
+        run("INSERT INTO b VALUES (1, 1), (1, 2), (3, 3), (4, 4)");
+
+        let join = "SELECT a.v, b.v FROM a JOIN b ON a.id = b.id WHERE b.v < 4";
+        assert_eq!(
+            run(&format!("EXPLAIN {join}")),
+            vec![
+                "Projection #3, #1  (cost=2.00 rows=1)",
+                "  HashJoin Inner on (#2 = #0)  (cost=2.00 rows=1)",
+                "    Filter (#1 < 4)  (cost=1.00 rows=2)",
+                "      SeqScan b  (cost=1.00 rows=6)",
+                "    SeqScan a  (cost=1.00 rows=4)",
+            ]
+        );
+
+        let rows = run(&format!("EXPLAIN ANALYZE {join}"));
+        assert_eq!(rows.len(), 6);
+        let actual = |line: &str| {
+            let start = line.find("(actual").unwrap();
+            line[start..line.find(" time=").unwrap()].to_string()
+        };
+        let actuals: Vec<String> = rows[..5].iter().map(|row| actual(row)).collect();
+        assert_eq!(
+            actuals,
+            vec![
+                "(actual rows=3 loops=1",
+                "(actual rows=3 loops=1",
+                "(actual rows=3 loops=1",
+                "(actual rows=4 loops=1",
+                "(actual rows=3 loops=1",
+            ]
+        );
+        // The counts of an operator include those of its inputs.
+        assert!(rows[1].ends_with("(buffers hit=2 miss=2 read=2 written=1)"));
+        assert!(rows[3].ends_with("(buffers hit=0 miss=2 read=2 written=1)"));
+        assert!(rows[4].ends_with("(buffers hit=2 miss=0 read=0 written=0)"));
+        assert!(rows[5].starts_with("Execution time: "));
+
+        // Analyzing a modification runs it.
+        run("EXPLAIN ANALYZE DELETE FROM b WHERE v = 1");
+        assert!(run("EXPLAIN ANALYZE SELECT * FROM b")[0].contains("(actual rows=3 loops=1"));
+
+        let json = run(&format!("EXPLAIN (FORMAT JSON) {join}"));
+        assert_eq!(json.len(), 1);
+        assert!(json[0].starts_with("{\n  \"plan\": {\n    \"operator\": \"Projection\",\n"));
+        assert!(json[0].contains("\"details\": \"Inner on (#2 = #0)\""));
+        assert!(!json[0].contains("actual_rows"));
+        let json = run(&format!("EXPLAIN (ANALYZE, FORMAT JSON) {join}"));
+        assert!(json[0].contains("\"actual_loops\": 1,"));
+        assert!(json[0].contains("\"buffers\": {\n"));
+        assert!(json[0].contains("\"execution_time_ms\": "));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{catalog::schema::Schema, planner::expr::Expr, storage::table::tuple::Tuple};
+
+use super::{
+    eval::evaluate_predicate,
+    executor::{BoxedExecutor, Executor},
+};
+
+/// Passes on the tuples of its child for which the predicate is true. The tuples keep their
+/// record ids, so modifications can run on top of a filter.
+pub struct FilterExecutor<'a> {
+    child: BoxedExecutor<'a>,
+    predicate: Expr,
+}
+
+impl<'a> FilterExecutor<'a> {
+    pub fn new(child: BoxedExecutor<'a>, predicate: Expr) -> Self {
+        Self { child, predicate }
+    }
+}
+
+impl Executor for FilterExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        while let Some(tuple) = self.child.next()? {
+            if evaluate_predicate(&self.predicate, tuple.values())? {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.child.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        catalog::schema::Column,
+        sql::ast::BinaryOp,
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::{super::values::ValuesExecutor, *};
+
+    fn input(keys: &[Option<i64>]) -> BoxedExecutor<'static> {
+        let rows = keys
+            .iter()
+            .map(|key| {
+                let key = key.map_or(Value::Null, Value::Int64);
+                vec![Expr::literal(key, DataType::Int64)]
+            })
+            .collect();
+        let schema = Schema::new(vec![Column::new("k", DataType::Int64)]);
+        Box::new(ValuesExecutor::new(rows, schema))
+    }
+
+    fn collect(executor: &mut impl Executor) -> Vec<Value> {
+        executor.init().unwrap();
+        let mut values = Vec::new();
+        while let Some(tuple) = executor.next().unwrap() {
+            values.extend(tuple.into_values());
+        }
+        values
+    }
+
+    #[test]
+    fn test_filter() {
+        let greater = Expr::binary(
+            BinaryOp::Gt,
+            Expr::column(0, DataType::Int64),
+            Expr::literal(Value::Int64(2), DataType::Int64),
+            DataType::Boolean,
+        );
+        let keys = [Some(1), None, Some(3), Some(2), Some(4)];
+        let mut filter = FilterExecutor::new(input(&keys), greater.clone());
+        assert_eq!(filter.schema().column_count(), 1);
+        // NULL is not true, so the row with a NULL key is filtered out as well.
+        for _ in 0..2 {
+            assert_eq!(collect(&mut filter), [Value::Int64(3), Value::Int64(4)]);
+        }
+
+        let mut filter = FilterExecutor::new(input(&[Some(1), Some(2)]), greater);
+        assert!(collect(&mut filter).is_empty());
+        let null = Expr::literal(Value::Null, DataType::Boolean);
+        let mut filter = FilterExecutor::new(input(&keys), null);
+        assert!(collect(&mut filter).is_empty());
+    }
+}
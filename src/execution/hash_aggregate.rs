@@ -0,0 +1,319 @@
+//! Aggregation with a hash table of groups.
+//!
+//! Every input row is added to one group of every grouping set. The hash table holds at most
+//! `work_mem` groups: once it is full, a row whose group is not in it is written, with the
+//! number of its grouping set, to one of [`FAN_OUT`] partitions of temporary pages by the hash
+//! of its group. A group is therefore either entirely in the table or entirely in one
+//! partition. After the input, the groups of the table are output, and every partition is
+//! aggregated the same way, with a different hash function for the partitions it spills in
+//! turn. Each pass puts `work_mem` new groups into its table, so the passes always end.
+//!
+//! Spilled records are the grouping set as a little-endian u32 followed by the encoded row.
+//!
+//! Groups are output in no particular order. An empty grouping set has a group even without
+//! input rows, so that `SELECT count(*) FROM t` of an empty table is 0.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::schema::Schema,
+    planner::expr::{AggregateExpr, Expr},
+    storage::{spill::SpillFile, table::tuple::Tuple},
+    types::value::Value,
+};
+
+use super::{
+    aggregate::{group_values, Group},
+    executor::{BoxedExecutor, Executor},
+    key::hash_partition,
+};
+
+/// The number of partitions the rows that do not fit are split into by each pass.
+pub const FAN_OUT: usize = 16;
+
+pub struct HashAggregateExecutor<'a, T: BufferPoolManager + ?Sized> {
+    input: BoxedExecutor<'a>,
+    group_by: Vec<Expr>,
+    grouping_sets: Vec<Vec<usize>>,
+    aggregates: Vec<AggregateExpr>,
+    schema: Schema,
+    temp: &'a T,
+    work_mem: usize,
+    /// The output rows of the last pass.
+    output: VecDeque<Tuple>,
+    /// Spilled partitions that are yet to be aggregated, with the depth of their pass.
+    partitions: Vec<(SpillFile<'a, T>, u32)>,
+}
+
+/// The hash table of one pass over the input or a partition.
+struct Pass<'a, T: BufferPoolManager + ?Sized> {
+    groups: Vec<Group>,
+    positions: HashMap<Vec<u8>, usize>,
+    /// Created when the first row does not fit.
+    spilled: Vec<SpillFile<'a, T>>,
+    depth: u32,
+}
+
+impl<'a, T: BufferPoolManager + ?Sized> HashAggregateExecutor<'a, T> {
+    /// Aggregate `input` by each of `grouping_sets`, keeping at most `work_mem` groups in
+    /// memory and spilling to `temp` beyond them.
+    pub fn new(
+        input: BoxedExecutor<'a>,
+        group_by: Vec<Expr>,
+        grouping_sets: Vec<Vec<usize>>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+        temp: &'a T,
+        work_mem: usize,
+    ) -> Self {
+        Self {
+            input,
+            group_by,
+            grouping_sets,
+            aggregates,
+            schema,
+            temp,
+            work_mem,
+            output: VecDeque::new(),
+            partitions: Vec::new(),
+        }
+    }
+
+    /// Add a row to its group of `set_index`, or spill it if the group does not fit.
+    fn add(&self, pass: &mut Pass<'a, T>, set_index: usize, tuple: &Tuple) -> anyhow::Result<()> {
+        let set = &self.grouping_sets[set_index];
+        let (key, values) = group_values(&self.group_by, set_index, set, tuple.values())?;
+        if let Some(&position) = pass.positions.get(&key) {
+            return pass.groups[position].update(&self.aggregates, tuple.values());
+        }
+        if pass.groups.len() < self.work_mem {
+            let mut group = Group::new(values, &self.aggregates);
+            group.update(&self.aggregates, tuple.values())?;
+            pass.positions.insert(key, pass.groups.len());
+            pass.groups.push(group);
+            return Ok(());
+        }
+
+        if pass.spilled.is_empty() {
+            pass.spilled = (0..FAN_OUT).map(|_| SpillFile::new(self.temp)).collect();
+        }
+        let mut record = (set_index as u32).to_le_bytes().to_vec();
+        record.extend(tuple.to_bytes(self.input.schema())?);
+        pass.spilled[hash_partition(&key, pass.depth, FAN_OUT)].push(&record)
+    }
+
+    /// Queue the groups of a finished pass for output and keep its partitions.
+    fn finish_pass(&mut self, pass: Pass<'a, T>) -> anyhow::Result<()> {
+        for group in pass.groups {
+            self.output.push_back(group.finish(&self.aggregates)?);
+        }
+        let depth = pass.depth + 1;
+        self.partitions.extend(
+            pass.spilled
+                .into_iter()
+                .filter(|partition| !partition.is_empty())
+                .map(|partition| (partition, depth)),
+        );
+        Ok(())
+    }
+}
+
+impl<T: BufferPoolManager + ?Sized> Executor for HashAggregateExecutor<'_, T> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.input.init()?;
+        self.output.clear();
+        self.partitions.clear();
+
+        let mut pass = Pass::new(0);
+        let mut empty_input = true;
+        while let Some(tuple) = self.input.next()? {
+            empty_input = false;
+            for set_index in 0..self.grouping_sets.len() {
+                self.add(&mut pass, set_index, &tuple)?;
+            }
+        }
+        if empty_input {
+            for set in &self.grouping_sets {
+                if set.is_empty() {
+                    let values = vec![Value::Null; self.group_by.len()];
+                    pass.groups.push(Group::new(values, &self.aggregates));
+                }
+            }
+        }
+        self.finish_pass(pass)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            if let Some(tuple) = self.output.pop_front() {
+                return Ok(Some(tuple));
+            }
+            let Some((partition, depth)) = self.partitions.pop() else {
+                return Ok(None);
+            };
+            let mut pass = Pass::new(depth);
+            for record in partition.into_reader() {
+                let record = record?;
+                let set_index = u32::from_le_bytes(record[..4].try_into().unwrap()) as usize;
+                let tuple = Tuple::from_bytes(self.input.schema(), &record[4..])?;
+                self.add(&mut pass, set_index, &tuple)?;
+            }
+            self.finish_pass(pass)?;
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+impl<T: BufferPoolManager + ?Sized> Pass<'_, T> {
+    fn new(depth: u32) -> Self {
+        Self {
+            groups: Vec::new(),
+            positions: HashMap::new(),
+            spilled: Vec::new(),
+            depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::TempBufferPool,
+        catalog::schema::Column,
+        execution::{sort_aggregate::SortAggregateExecutor, values::ValuesExecutor},
+        planner::expr::AggregateFunction,
+        storage::{
+            disk::{DiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    fn collect(mut executor: BoxedExecutor) -> Vec<String> {
+        executor.init().unwrap();
+        let mut rows = Vec::new();
+        while let Some(tuple) = executor.next().unwrap() {
+            let values: Vec<String> = tuple.values().iter().map(Value::to_string).collect();
+            rows.push(values.join(" "));
+        }
+        rows.sort();
+        rows
+    }
+
+    /// Rows `(g, v)` sorted by `g`: ten rows for each of 19 groups, then ten with a NULL `g`.
+    /// Every thirteenth `v` is NULL.
+    fn input(rows: usize) -> BoxedExecutor<'static> {
+        let rows = (0..rows)
+            .map(|i| {
+                let g = match i / 10 {
+                    19 => Value::Null,
+                    g => Value::Int32(g as i32),
+                };
+                let v = match i % 13 {
+                    0 => Value::Null,
+                    _ => Value::Int64((i % 7) as i64),
+                };
+                vec![
+                    Expr::literal(g, DataType::Int32),
+                    Expr::literal(v, DataType::Int64),
+                ]
+            })
+            .collect();
+        let schema = Schema::new(vec![
+            Column::new("g", DataType::Int32),
+            Column::new("v", DataType::Int64),
+        ]);
+        Box::new(ValuesExecutor::new(rows, schema))
+    }
+
+    fn aggregates() -> Vec<AggregateExpr> {
+        let v = Expr::column(1, DataType::Int64);
+        let aggregate = |func, arg: Option<&Expr>, distinct, data_type| AggregateExpr {
+            func,
+            arg: arg.cloned(),
+            distinct,
+            data_type,
+        };
+        vec![
+            aggregate(AggregateFunction::Count, None, false, DataType::Int64),
+            aggregate(AggregateFunction::Count, Some(&v), false, DataType::Int64),
+            aggregate(AggregateFunction::Count, Some(&v), true, DataType::Int64),
+            aggregate(AggregateFunction::Sum, Some(&v), false, DataType::Int64),
+            aggregate(AggregateFunction::Sum, Some(&v), true, DataType::Int64),
+            aggregate(AggregateFunction::Avg, Some(&v), false, DataType::Float64),
+            aggregate(AggregateFunction::Min, Some(&v), false, DataType::Int64),
+            aggregate(AggregateFunction::Max, Some(&v), false, DataType::Int64),
+        ]
+    }
+
+    fn output_schema() -> Schema {
+        let mut columns = vec![Column::new("g", DataType::Int32)];
+        for (i, aggregate) in aggregates().iter().enumerate() {
+            columns.push(Column::new(&format!("a{i}"), aggregate.data_type));
+        }
+        Schema::new(columns)
+    }
+
+    #[test]
+    fn test_hash_aggregate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let g = vec![Expr::column(0, DataType::Int32)];
+
+        let hash = |rows, sets: Vec<Vec<usize>>, work_mem| {
+            collect(Box::new(HashAggregateExecutor::new(
+                input(rows),
+                g.clone(),
+                sets,
+                aggregates(),
+                output_schema(),
+                &temp,
+                work_mem,
+            )))
+        };
+        let sorted = |rows, group_by: Vec<Expr>| {
+            collect(Box::new(SortAggregateExecutor::new(
+                input(rows),
+                group_by,
+                aggregates(),
+                output_schema(),
+            )))
+        };
+
+        let expected = hash(200, vec![vec![0]], 100);
+        assert_eq!(expected.len(), 20);
+        assert_eq!(expected[0], "0 10 9 7 24 21 2.6666666666666665 0 6");
+        assert_eq!(expected[19], "NULL 10 9 6 21 15 2.3333333333333335 0 5");
+        // The table holds 3 of the 20 groups, so most of them are spilled more than once.
+        assert_eq!(hash(200, vec![vec![0]], 3), expected);
+        assert_eq!(sorted(200, g.clone()), expected);
+
+        // ROLLUP (g)
+        let rollup = hash(200, vec![vec![0], vec![]], 3);
+        assert_eq!(rollup.len(), 21);
+        let total = "NULL 200 184 7 546 21 2.967391304347826 0 6";
+        assert!(rollup.contains(&total.to_string()));
+        assert_eq!(hash(200, vec![vec![]], 3), vec![total]);
+        // Without group-by expressions the output has no column for `g`.
+        let without_g = |row: &str| row.strip_prefix("NULL ").unwrap().to_string();
+        assert_eq!(sorted(200, Vec::new()), vec![without_g(total)]);
+
+        // Without input rows, only the empty grouping set has a group.
+        let empty = "NULL 0 0 0 NULL NULL NULL NULL NULL";
+        assert!(hash(0, vec![vec![0]], 3).is_empty());
+        assert_eq!(hash(0, vec![vec![0], vec![]], 3), vec![empty]);
+        assert!(sorted(0, g.clone()).is_empty());
+        assert_eq!(sorted(0, Vec::new()), vec![without_g(empty)]);
+    }
+}
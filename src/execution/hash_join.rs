@@ -0,0 +1,475 @@
+//! A grace hash join.
+//!
+//! The right input is the build side: its tuples go into a hash table keyed by their
+//! [`join_key`], and the left tuples probe it as they stream by. If the right input has more
+//! than `work_mem` tuples, both inputs are instead split into [`FAN_OUT`] partitions by the
+//! hash of their keys, written to temporary pages with [`SpillFile`], and the pairs of
+//! partitions are joined one after another, as equal keys always land in partitions with the
+//! same number. A build partition that is still too large is split again with another hash
+//! function, up to [`MAX_DEPTH`] times; beyond that its keys are mostly equal, and it is joined
+//! in memory regardless.
+//!
+//! Tuples with a NULL key match nothing, but still belong to a partition so that outer and
+//! anti joins output them.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::schema::Schema,
+    planner::logical_plan::{join_schema, JoinType},
+    storage::{
+        spill::{SpillFile, SpillReader},
+        table::tuple::Tuple,
+    },
+};
+
+use super::{
+    executor::{BoxedExecutor, Executor},
+    join::{condition_holds, join_key, joined, unmatched_left, unmatched_right, EquiJoinKeys},
+    key::hash_partition,
+};
+
+/// The number of partitions an input is split into at a time.
+pub const FAN_OUT: usize = 16;
+/// The number of times a partition is split again at most.
+pub const MAX_DEPTH: u32 = 3;
+
+pub struct HashJoinExecutor<'a, T: BufferPoolManager + ?Sized> {
+    left: BoxedExecutor<'a>,
+    right: BoxedExecutor<'a>,
+    join_type: JoinType,
+    keys: EquiJoinKeys,
+    schema: Schema,
+    temp: &'a T,
+    work_mem: usize,
+    phase: Phase,
+    table: HashTable,
+    probe: Probe<'a, T>,
+    /// Spilled partitions that are yet to be joined.
+    partitions: Vec<Partition<'a, T>>,
+    pending: VecDeque<Tuple>,
+}
+
+enum Phase {
+    Probe,
+    /// Output the build tuples without a match, from this position on.
+    UnmatchedRight(usize),
+    NextPartition,
+    Done,
+}
+
+/// Where the probe tuples of the current hash table come from.
+enum Probe<'a, T: BufferPoolManager + ?Sized> {
+    Left,
+    Spilled(SpillReader<'a, T>),
+}
+
+#[derive(Default)]
+struct HashTable {
+    /// The build tuples and whether they have matched.
+    tuples: Vec<(Tuple, bool)>,
+    /// The positions of the build tuples with each key. Tuples with a NULL key are not here.
+    buckets: HashMap<Vec<u8>, Vec<usize>>,
+}
+
+impl HashTable {
+    fn build(tuples: Vec<(Option<Vec<u8>>, Tuple)>) -> Self {
+        let mut table = Self::default();
+        for (pos, (key, tuple)) in tuples.into_iter().enumerate() {
+            if let Some(key) = key {
+                table.buckets.entry(key).or_default().push(pos);
+            }
+            table.tuples.push((tuple, false));
+        }
+        table
+    }
+}
+
+/// A pair of partitions with the same number, hashed with the hash function of `depth`.
+struct Partition<'a, T: BufferPoolManager + ?Sized> {
+    build: SpillFile<'a, T>,
+    probe: SpillFile<'a, T>,
+    depth: u32,
+}
+
+impl<'a, T: BufferPoolManager + ?Sized> HashJoinExecutor<'a, T> {
+    /// Join by the equalities of `keys`, building a hash table over the right input and
+    /// spilling to `temp` beyond `work_mem` build tuples.
+    pub fn new(
+        left: BoxedExecutor<'a>,
+        right: BoxedExecutor<'a>,
+        join_type: JoinType,
+        keys: EquiJoinKeys,
+        temp: &'a T,
+        work_mem: usize,
+    ) -> Self {
+        let schema = join_schema(left.schema(), right.schema(), join_type);
+        Self {
+            left,
+            right,
+            join_type,
+            keys,
+            schema,
+            temp,
+            work_mem,
+            phase: Phase::Done,
+            table: HashTable::default(),
+            probe: Probe::Left,
+            partitions: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn new_partitions(&self, depth: u32) -> Vec<Partition<'a, T>> {
+        (0..FAN_OUT)
+            .map(|_| Partition {
+                build: SpillFile::new(self.temp),
+                probe: SpillFile::new(self.temp),
+                depth,
+            })
+            .collect()
+    }
+
+    /// Build the hash table over the right input, or partition both inputs if it is too large.
+    fn build(&mut self) -> anyhow::Result<()> {
+        let mut tuples = Vec::new();
+        let mut partitions: Option<Vec<Partition<'a, T>>> = None;
+        while let Some(tuple) = self.right.next()? {
+            let key = join_key(&self.keys.right, tuple.values())?;
+            if let Some(partitions) = &mut partitions {
+                let partition = &mut partitions[partition_of(key.as_deref(), 0)];
+                partition
+                    .build
+                    .push(&tuple.to_bytes(self.right.schema())?)?;
+                continue;
+            }
+            tuples.push((key, tuple));
+            if tuples.len() > self.work_mem {
+                let mut new_partitions = self.new_partitions(0);
+                for (key, tuple) in tuples.drain(..) {
+                    new_partitions[partition_of(key.as_deref(), 0)]
+                        .build
+                        .push(&tuple.to_bytes(self.right.schema())?)?;
+                }
+                partitions = Some(new_partitions);
+            }
+        }
+
+        let Some(mut partitions) = partitions else {
+            self.table = HashTable::build(tuples);
+            self.probe = Probe::Left;
+            self.phase = Phase::Probe;
+            return Ok(());
+        };
+        while let Some(tuple) = self.left.next()? {
+            let key = join_key(&self.keys.left, tuple.values())?;
+            partitions[partition_of(key.as_deref(), 0)]
+                .probe
+                .push(&tuple.to_bytes(self.left.schema())?)?;
+        }
+        self.partitions = partitions;
+        self.phase = Phase::NextPartition;
+        Ok(())
+    }
+
+    /// Build the hash table over a spilled partition, or split it again if it is too large.
+    fn load_partition(&mut self, partition: Partition<'a, T>) -> anyhow::Result<()> {
+        let right_schema = self.right.schema();
+        let left_schema = self.left.schema();
+        if partition.build.len() > self.work_mem && partition.depth < MAX_DEPTH {
+            let depth = partition.depth + 1;
+            let mut partitions = self.new_partitions(depth);
+            for bytes in partition.build.into_reader() {
+                let tuple = Tuple::from_bytes(right_schema, &bytes?)?;
+                let key = join_key(&self.keys.right, tuple.values())?;
+                partitions[partition_of(key.as_deref(), depth)]
+                    .build
+                    .push(&tuple.to_bytes(right_schema)?)?;
+            }
+            for bytes in partition.probe.into_reader() {
+                let bytes = bytes?;
+                let tuple = Tuple::from_bytes(left_schema, &bytes)?;
+                let key = join_key(&self.keys.left, tuple.values())?;
+                partitions[partition_of(key.as_deref(), depth)]
+                    .probe
+                    .push(&bytes)?;
+            }
+            self.partitions.extend(partitions);
+            return Ok(());
+        }
+
+        let mut tuples = Vec::with_capacity(partition.build.len());
+        for bytes in partition.build.into_reader() {
+            let tuple = Tuple::from_bytes(right_schema, &bytes?)?;
+            tuples.push((join_key(&self.keys.right, tuple.values())?, tuple));
+        }
+        self.table = HashTable::build(tuples);
+        self.probe = Probe::Spilled(partition.probe.into_reader());
+        self.phase = Phase::Probe;
+        Ok(())
+    }
+
+    fn next_probe_tuple(&mut self) -> anyhow::Result<Option<Tuple>> {
+        match &mut self.probe {
+            Probe::Left => self.left.next(),
+            Probe::Spilled(reader) => reader
+                .next()
+                .transpose()?
+                .map(|bytes| Tuple::from_bytes(self.left.schema(), &bytes))
+                .transpose(),
+        }
+    }
+
+    /// Queue the output for a probe tuple.
+    fn probe(&mut self, left: Tuple) -> anyhow::Result<()> {
+        let key = join_key(&self.keys.left, left.values())?;
+        let positions = key
+            .and_then(|key| self.table.buckets.get(&key))
+            .map_or(&[][..], |positions| positions);
+        let mut matched = false;
+        for &pos in positions {
+            let (right, right_matched) = &mut self.table.tuples[pos];
+            if !condition_holds(self.keys.residual.as_ref(), &left, right)? {
+                continue;
+            }
+            matched = true;
+            if !self.join_type.outputs_right() {
+                break;
+            }
+            *right_matched = true;
+            self.pending.push_back(joined(&left, right.clone()));
+        }
+        if matched && self.join_type == JoinType::Semi {
+            self.pending.push_back(left);
+        } else if !matched && self.join_type.keeps_unmatched_left() {
+            let right_width = self.right.schema().column_count();
+            self.pending
+                .push_back(unmatched_left(self.join_type, left, right_width));
+        }
+        Ok(())
+    }
+}
+
+impl<T: BufferPoolManager + ?Sized> Executor for HashJoinExecutor<'_, T> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.left.init()?;
+        self.right.init()?;
+        self.table = HashTable::default();
+        self.partitions.clear();
+        self.pending.clear();
+        self.build()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            if let Some(tuple) = self.pending.pop_front() {
+                return Ok(Some(tuple));
+            }
+            match self.phase {
+                Phase::Probe => match self.next_probe_tuple()? {
+                    Some(left) => self.probe(left)?,
+                    None => {
+                        self.probe = Probe::Left;
+                        self.phase = Phase::UnmatchedRight(0);
+                    }
+                },
+                Phase::UnmatchedRight(pos) => {
+                    if !self.join_type.keeps_unmatched_right() {
+                        self.phase = Phase::NextPartition;
+                        continue;
+                    }
+                    let tuples = &mut self.table.tuples;
+                    let Some(offset) = tuples[pos..].iter().position(|(_, matched)| !matched)
+                    else {
+                        self.phase = Phase::NextPartition;
+                        continue;
+                    };
+                    self.phase = Phase::UnmatchedRight(pos + offset + 1);
+                    let right = std::mem::replace(&mut tuples[pos + offset].0, Tuple::new(vec![]));
+                    let left_width = self.left.schema().column_count();
+                    return Ok(Some(unmatched_right(left_width, right)));
+                }
+                Phase::NextPartition => {
+                    self.table = HashTable::default();
+                    match self.partitions.pop() {
+                        Some(partition) => self.load_partition(partition)?,
+                        None => self.phase = Phase::Done,
+                    }
+                }
+                Phase::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// The partition of a key among [`FAN_OUT`] by the hash function of `depth`. NULL keys match
+/// nothing, so any partition will do.
+fn partition_of(key: Option<&[u8]>, depth: u32) -> usize {
+    key.map_or(0, |key| hash_partition(key, depth, FAN_OUT))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::TempBufferPool,
+        planner::expr::Expr,
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{
+        super::join::test_util::{collect, input, rows, Row},
+        *,
+    };
+
+    /// Join `l` and `r` on `l.k = r.k`, as sorted rows of space-separated values.
+    fn join(
+        temp: &TempBufferPool,
+        work_mem: usize,
+        join_type: JoinType,
+        l: &[Row],
+        r: &[Row],
+    ) -> Vec<String> {
+        let equal = Expr::binary(
+            BinaryOp::Eq,
+            Expr::column(0, DataType::Int64),
+            Expr::column(2, DataType::Int64),
+            DataType::Boolean,
+        );
+        let keys = EquiJoinKeys::split(Some(&equal), 2);
+        let mut join = HashJoinExecutor::new(input(l), input(r), join_type, keys, temp, work_mem);
+        collect(&mut join)
+    }
+
+    /// What joining `l` and `r` on `l.k = r.k` outputs, by comparing every pair.
+    fn reference(join_type: JoinType, l: &[Row], r: &[Row]) -> Vec<String> {
+        let show = |(key, name): &Row| match key {
+            Some(key) => format!("{key} {name}"),
+            None => format!("NULL {name}"),
+        };
+        let matches = |a: &Row, b: &Row| a.0.is_some() && a.0 == b.0;
+        let mut rows = Vec::new();
+        for a in l {
+            let matched: Vec<&Row> = r.iter().filter(|b| matches(a, b)).collect();
+            match join_type {
+                JoinType::Semi if !matched.is_empty() => rows.push(show(a)),
+                JoinType::Anti if matched.is_empty() => rows.push(show(a)),
+                JoinType::Semi | JoinType::Anti => {}
+                _ => {
+                    rows.extend(matched.iter().map(|b| format!("{} {}", show(a), show(b))));
+                    if matched.is_empty() && join_type.keeps_unmatched_left() {
+                        rows.push(format!("{} NULL NULL", show(a)));
+                    }
+                }
+            }
+        }
+        if join_type.keeps_unmatched_right() {
+            for b in r.iter().filter(|b| !l.iter().any(|a| matches(a, b))) {
+                rows.push(format!("NULL NULL {}", show(b)));
+            }
+        }
+        rows.sort();
+        rows
+    }
+
+    fn temp_pool(tempdir: &tempfile::TempDir) -> TempBufferPool {
+        let disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.tmp")).unwrap();
+        TempBufferPool::new(8, disk_manager)
+    }
+
+    #[test]
+    fn test_hash_join() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let temp = temp_pool(&tempdir);
+        let l = rows(&[
+            (Some(1), "l1"),
+            (Some(2), "l2"),
+            (Some(2), "l2b"),
+            (None, "ln"),
+            (Some(4), "l4"),
+        ]);
+        let r = rows(&[
+            (Some(2), "r2"),
+            (Some(2), "r2b"),
+            (Some(3), "r3"),
+            (None, "rn"),
+            (Some(1), "r1"),
+        ]);
+
+        let inner = [
+            "1 l1 1 r1",
+            "2 l2 2 r2",
+            "2 l2 2 r2b",
+            "2 l2b 2 r2",
+            "2 l2b 2 r2b",
+        ];
+        let unmatched_left = ["4 l4 NULL NULL", "NULL ln NULL NULL"];
+        let unmatched_right = ["NULL NULL 3 r3", "NULL NULL NULL rn"];
+        for join_type in [
+            JoinType::Inner,
+            JoinType::Left,
+            JoinType::Right,
+            JoinType::Full,
+            JoinType::Semi,
+            JoinType::Anti,
+        ] {
+            let mut expected = match join_type {
+                JoinType::Inner => inner.to_vec(),
+                JoinType::Left => [&inner[..], &unmatched_left].concat(),
+                JoinType::Right => [&inner[..], &unmatched_right].concat(),
+                JoinType::Full => [&inner[..], &unmatched_left, &unmatched_right].concat(),
+                JoinType::Semi => vec!["1 l1", "2 l2", "2 l2b"],
+                JoinType::Anti => vec!["4 l4", "NULL ln"],
+            };
+            expected.sort();
+            assert_eq!(
+                join(&temp, 16, join_type, &l, &r),
+                expected,
+                "{join_type:?}"
+            );
+            assert_eq!(reference(join_type, &l, &r), expected, "{join_type:?}");
+        }
+        // Nothing was spilled.
+        assert_eq!(temp.disk_manager().num_pages(), 0);
+    }
+
+    #[test]
+    fn test_spilled_hash_join() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let temp = temp_pool(&tempdir);
+        let key = |i: i64, modulus: i64| (i % 11 != 0).then_some(i % modulus);
+        let l: Vec<Row> = (0..300).map(|i| (key(i, 40), format!("l{i}"))).collect();
+        // Key 7 fills half of the build side, so that its partition is still too large after
+        // MAX_DEPTH splits and gets joined in memory anyway.
+        let r: Vec<Row> = (0..200)
+            .map(|i| match i % 2 {
+                0 => (Some(7), format!("r{i}")),
+                _ => (key(i, 60), format!("r{i}")),
+            })
+            .collect();
+
+        for join_type in [
+            JoinType::Inner,
+            JoinType::Left,
+            JoinType::Right,
+            JoinType::Full,
+            JoinType::Semi,
+            JoinType::Anti,
+        ] {
+            let expected = reference(join_type, &l, &r);
+            assert!(!expected.is_empty());
+            assert_eq!(join(&temp, 4, join_type, &l, &r), expected, "{join_type:?}");
+        }
+        assert!(temp.disk_manager().num_pages() > 0);
+    }
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{Catalog, IndexInfo, TableInfo},
+        table_index::TableIndex,
+    },
+    storage::table::record_id::RecordId,
+    types::value::Value,
+};
+
+/// Keeps every index of one table in sync with the rows the modification executors write.
+///
+/// Unique indexes are checked with [`IndexMaintenance::check_unique`] before the heap is
+/// touched, so a rejected row leaves neither the heap nor any index changed.
+pub struct IndexMaintenance<'a> {
+    indexes: Vec<(Arc<IndexInfo>, TableIndex<'a>)>,
+}
+
+impl<'a> IndexMaintenance<'a> {
+    pub fn open<B: BufferPoolManager + ?Sized>(
+        catalog: &Catalog<'a, B>,
+        table: &TableInfo,
+    ) -> anyhow::Result<Self> {
+        let indexes = catalog
+            .table_indexes(table.oid)
+            .into_iter()
+            .map(|info| {
+                let index = catalog.open_index(&info)?;
+                Ok((info, index))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { indexes })
+    }
+
+    /// The key of a row in `info`, or None if it has a NULL, which is not indexed.
+    fn key(info: &IndexInfo, values: &[Value]) -> Option<Vec<Value>> {
+        let key: Vec<Value> = info
+            .columns
+            .iter()
+            .map(|&position| values[position].clone())
+            .collect();
+        (!key.iter().any(Value::is_null)).then_some(key)
+    }
+
+    /// Fail if storing `values` would duplicate the key of another row in a unique index. `rid`
+    /// is the row's own record id when it is being updated.
+    pub fn check_unique(&self, values: &[Value], rid: Option<RecordId>) -> anyhow::Result<()> {
+        for (info, index) in self.indexes.iter().filter(|(info, _)| info.unique) {
+            let Some(key) = Self::key(info, values) else {
+                continue;
+            };
+            if index.get(&key)?.into_iter().any(|other| Some(other) != rid) {
+                anyhow::bail!(
+                    "duplicate key value violates unique constraint \"{}\"",
+                    info.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&self, values: &[Value], rid: RecordId) -> anyhow::Result<()> {
+        for (info, index) in &self.indexes {
+            if let Some(key) = Self::key(info, values) {
+                if !index.insert(&key, rid)? {
+                    anyhow::bail!(
+                        "duplicate key value violates unique constraint \"{}\"",
+                        info.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, values: &[Value], rid: RecordId) -> anyhow::Result<()> {
+        for (info, index) in &self.indexes {
+            if let Some(key) = Self::key(info, values) {
+                index.remove(&key, rid)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the entries of a row from its old values and record id to its new ones, touching
+    /// only the indexes whose entry changes.
+    pub fn update(
+        &self,
+        old: &[Value],
+        old_rid: RecordId,
+        new: &[Value],
+        new_rid: RecordId,
+    ) -> anyhow::Result<()> {
+        for (info, index) in &self.indexes {
+            let old_key = Self::key(info, old);
+            let new_key = Self::key(info, new);
+            if old_key == new_key && old_rid == new_rid {
+                continue;
+            }
+            if let Some(key) = old_key {
+                index.remove(&key, old_rid)?;
+            }
+            if let Some(key) = new_key {
+                if !index.insert(&key, new_rid)? {
+                    anyhow::bail!(
+                        "duplicate key value violates unique constraint \"{}\"",
+                        info.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Bound;
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::schema::{Column, Schema},
+        execution::{
+            executor::{BoxedExecutor, Executor},
+            seq_scan::SeqScanExecutor,
+        },
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            index::index::IndexKind,
+            page::{page::DEFAULT_PAGE_SIZE, slotted_page::SlotId},
+            table::tuple::Tuple,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    /// A row of the test table: `id`, `grp`, `code` and `name`.
+    pub type Row<'s> = (i64, Option<i64>, Option<i64>, &'s str);
+
+    pub fn values((id, grp, code, name): Row) -> Vec<Value> {
+        vec![
+            Value::Int64(id),
+            grp.map_or(Value::Null, Value::Int64),
+            code.map_or(Value::Null, Value::Int64),
+            Value::Varchar(name.to_string()),
+        ]
+    }
+
+    /// Create table `t` with `rows`, a unique B+ tree index `t_id` on `id`, a non-unique B+ tree
+    /// index `t_grp` on `grp` and a unique hash index `t_code` on `code`.
+    pub fn create_table(catalog: &Catalog<BufferPoolManagerImpl>, rows: &[Row]) -> Arc<TableInfo> {
+        let schema = Schema::new(vec![
+            Column::new("id", DataType::Int64),
+            Column::new("grp", DataType::Int64),
+            Column::new("code", DataType::Int64),
+            Column::new("name", DataType::Varchar(None)),
+        ]);
+        let table = catalog.create_table("t", schema).unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        for &row in rows {
+            let data = Tuple::new(values(row)).to_bytes(&table.schema).unwrap();
+            heap.insert_tuple(&data).unwrap();
+        }
+        for (name, column, kind, unique) in [
+            ("t_id", "id", IndexKind::BPlusTree, true),
+            ("t_grp", "grp", IndexKind::BPlusTree, false),
+            ("t_code", "code", IndexKind::Hash, true),
+        ] {
+            catalog
+                .create_index(name, "t", &[column], kind, unique)
+                .unwrap();
+        }
+        table
+    }
+
+    pub fn scan<'a>(
+        catalog: &Catalog<'a, BufferPoolManagerImpl>,
+        table: &Arc<TableInfo>,
+    ) -> BoxedExecutor<'a> {
+        let heap = catalog.table_heap(table).unwrap();
+        Box::new(SeqScanExecutor::new(table.clone(), heap))
+    }
+
+    /// The rows of `table` with their record ids, in heap order.
+    pub fn rows(
+        catalog: &Catalog<BufferPoolManagerImpl>,
+        table: &Arc<TableInfo>,
+    ) -> Vec<(RecordId, Vec<Value>)> {
+        let mut scan = scan(catalog, table);
+        scan.init().unwrap();
+        let mut rows = Vec::new();
+        while let Some(tuple) = scan.next().unwrap() {
+            rows.push((tuple.rid().unwrap(), tuple.into_values()));
+        }
+        rows
+    }
+
+    /// The count a modification executor produces.
+    pub fn run(mut executor: impl Executor) -> anyhow::Result<i64> {
+        executor.init()?;
+        let count = executor.next()?.unwrap().value(0).as_i64().unwrap();
+        assert!(executor.next()?.is_none());
+        Ok(count)
+    }
+
+    pub fn lookup(
+        catalog: &Catalog<BufferPoolManagerImpl>,
+        index: &str,
+        key: &[Value],
+    ) -> Vec<RecordId> {
+        let info = catalog.index(index).unwrap();
+        catalog.open_index(&info).unwrap().get(key).unwrap()
+    }
+
+    /// Check that every index of `table` has an entry for exactly the rows without a NULL key.
+    pub fn check_indexes(catalog: &Catalog<BufferPoolManagerImpl>, table: &Arc<TableInfo>) {
+        let rows = rows(catalog, table);
+        for info in catalog.table_indexes(table.oid) {
+            let index = catalog.open_index(&info).unwrap();
+            let mut indexed = 0;
+            for (rid, values) in &rows {
+                let Some(key) = IndexMaintenance::key(&info, values) else {
+                    continue;
+                };
+                indexed += 1;
+                let rids = index.get(&key).unwrap();
+                if info.unique {
+                    assert_eq!(rids, [*rid], "{} entry of {values:?}", info.name);
+                } else {
+                    assert!(rids.contains(rid), "{} entry of {values:?}", info.name);
+                }
+            }
+            if index.kind().supports_range() {
+                let entries = index.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+                assert_eq!(entries.count(), indexed, "{} entries", info.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_maintenance() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let table = create_table(&catalog, &[(1, Some(10), Some(100), "a")]);
+        let indexes = IndexMaintenance::open(&catalog, &table).unwrap();
+        let (rid, old) = rows(&catalog, &table).pop().unwrap();
+        let other = RecordId::new(rid.page_id, SlotId::new(rid.slot_id.as_u32() + 1));
+
+        // A row may keep its own key, but not take another row's, in any unique index.
+        indexes.check_unique(&old, Some(rid)).unwrap();
+        let err = indexes.check_unique(&old, Some(other)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "duplicate key value violates unique constraint \"t_id\""
+        );
+        let err = indexes
+            .check_unique(&values((2, Some(10), Some(100), "b")), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("\"t_code\""));
+        // NULL keys are neither checked nor indexed, and duplicates of a non-unique key are fine.
+        let new = values((2, Some(10), None, "b"));
+        indexes.check_unique(&new, None).unwrap();
+        indexes.insert(&new, other).unwrap();
+        assert_eq!(lookup(&catalog, "t_grp", &[Value::Int64(10)]), [rid, other]);
+        assert_eq!(lookup(&catalog, "t_id", &[Value::Int64(2)]), [other]);
+
+        // Updating moves only the entries that change.
+        let moved = RecordId::new(rid.page_id, SlotId::new(rid.slot_id.as_u32() + 2));
+        let updated = values((1, Some(20), Some(100), "a"));
+        indexes.update(&old, rid, &updated, moved).unwrap();
+        assert_eq!(lookup(&catalog, "t_id", &[Value::Int64(1)]), [moved]);
+        assert_eq!(lookup(&catalog, "t_code", &[Value::Int64(100)]), [moved]);
+        assert_eq!(lookup(&catalog, "t_grp", &[Value::Int64(10)]), [other]);
+        assert_eq!(lookup(&catalog, "t_grp", &[Value::Int64(20)]), [moved]);
+
+        let err = indexes.insert(&updated, other).unwrap_err();
+        assert!(err.to_string().contains("unique constraint"));
+        indexes.remove(&updated, moved).unwrap();
+        indexes.remove(&new, other).unwrap();
+        for (index, key) in [("t_id", 1), ("t_id", 2), ("t_grp", 10), ("t_code", 100)] {
+            assert!(lookup(&catalog, index, &[Value::Int64(key)]).is_empty());
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{IndexInfo, TableInfo},
+        schema::Schema,
+        table_index::TableIndex,
+    },
+    planner::{
+        expr::Expr,
+        logical_plan::{join_schema, JoinType},
+    },
+    storage::table::{
+        record_id::RecordId,
+        table_heap::{HeapTuple, TableHeap},
+        tuple::Tuple,
+    },
+    types::value::Value,
+    PageId,
+};
+
+use super::{
+    eval::{compare, evaluate},
+    executor::{BoxedExecutor, Executor},
+    join::{condition_holds, joined, unmatched_left, unmatched_right},
+};
+
+/// Joins every left tuple with the tuples of a table whose first indexed column equals a key
+/// computed from the left tuple, found by probing the index, for which the condition holds as
+/// well. The index can be a B+ tree or a hash index, and a NULL key matches nothing.
+///
+/// RIGHT and FULL joins remember the record ids of the table tuples that matched, and scan the
+/// table at the end for the others.
+pub struct IndexNestedLoopJoinExecutor<'a, B: BufferPoolManager + ?Sized> {
+    left: BoxedExecutor<'a>,
+    table: Arc<TableInfo>,
+    heap: TableHeap<'a, B>,
+    info: Arc<IndexInfo>,
+    index: TableIndex<'a>,
+    /// The probe key, over the left tuple.
+    key: Expr,
+    join_type: JoinType,
+    /// Over the left tuple followed by the table tuple.
+    condition: Option<Expr>,
+    schema: Schema,
+    /// The left tuple being joined, whether it has matched so far, and its probe key.
+    current: Option<(Tuple, bool, Value)>,
+    /// The rows the index returned for the current key and that are yet to be joined.
+    probed: VecDeque<RecordId>,
+    matched_rids: HashSet<RecordId>,
+    /// The scan for unmatched table tuples: the next page and the tuples of the last one.
+    unmatched_scan: Option<(Option<PageId>, VecDeque<HeapTuple>)>,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> IndexNestedLoopJoinExecutor<'a, B> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        left: BoxedExecutor<'a>,
+        table: Arc<TableInfo>,
+        heap: TableHeap<'a, B>,
+        info: Arc<IndexInfo>,
+        index: TableIndex<'a>,
+        key: Expr,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    ) -> Self {
+        let schema = join_schema(left.schema(), &table.schema, join_type);
+        Self {
+            left,
+            table,
+            heap,
+            info,
+            index,
+            key,
+            join_type,
+            condition,
+            schema,
+            current: None,
+            probed: VecDeque::new(),
+            matched_rids: HashSet::new(),
+            unmatched_scan: None,
+        }
+    }
+
+    /// Take the next left tuple and probe the index with its key. Return false if the left
+    /// input is exhausted.
+    fn probe_next(&mut self) -> anyhow::Result<bool> {
+        while let Some(left) = self.left.next()? {
+            let key = evaluate(&self.key, left.values())?;
+            if key.is_null() && !self.join_type.keeps_unmatched_left() {
+                continue;
+            }
+            self.probed
+                .extend(self.index.get(std::slice::from_ref(&key))?);
+            self.current = Some((left, false, key));
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// The next table tuple that no left tuple matched.
+    fn next_unmatched(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.unmatched_scan.is_none() {
+            self.unmatched_scan = Some((Some(self.heap.first_page_id()?), VecDeque::new()));
+        }
+        let (next_page_id, buffered) = self.unmatched_scan.as_mut().unwrap();
+        loop {
+            if let Some((rid, data)) = buffered.pop_front() {
+                if self.matched_rids.contains(&rid) {
+                    continue;
+                }
+                let right = Tuple::from_bytes(&self.table.schema, &data)?;
+                let left_width = self.left.schema().column_count();
+                return Ok(Some(unmatched_right(left_width, right)));
+            }
+            let Some(page_id) = next_page_id.take() else {
+                return Ok(None);
+            };
+            let (tuples, next) = self.heap.read_page(page_id)?;
+            buffered.extend(tuples);
+            *next_page_id = next;
+        }
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Executor for IndexNestedLoopJoinExecutor<'_, B> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.left.init()?;
+        self.current = None;
+        self.probed.clear();
+        self.matched_rids.clear();
+        self.unmatched_scan = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            if self.current.is_none() && !self.probe_next()? {
+                if self.join_type.keeps_unmatched_right() {
+                    return self.next_unmatched();
+                }
+                return Ok(None);
+            }
+            let (left, matched, key) = self.current.as_mut().unwrap();
+
+            let Some(rid) = self.probed.pop_front() else {
+                let (left, matched, _) = self.current.take().unwrap();
+                if !matched && self.join_type.keeps_unmatched_left() {
+                    let right_width = self.table.schema.column_count();
+                    return Ok(Some(unmatched_left(self.join_type, left, right_width)));
+                }
+                continue;
+            };
+            let Some(data) = self.heap.get_tuple(rid)? else {
+                continue;
+            };
+            let right = Tuple::from_bytes(&self.table.schema, &data)?;
+            // The row may have changed since its index entry was read.
+            if compare(right.value(self.info.columns[0]), key) != Some(Ordering::Equal)
+                || !condition_holds(self.condition.as_ref(), left, &right)?
+            {
+                continue;
+            }
+            *matched = true;
+            match self.join_type {
+                JoinType::Semi => {
+                    self.probed.clear();
+                    return Ok(self.current.take().map(|(left, ..)| left));
+                }
+                JoinType::Anti => {
+                    self.probed.clear();
+                    self.current = None;
+                }
+                _ => {
+                    if self.join_type.keeps_unmatched_right() {
+                        self.matched_rids.insert(rid);
+                    }
+                    return Ok(Some(joined(left, right)));
+                }
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::{catalog::Catalog, schema::Column},
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{
+        super::join::test_util::{collect, input},
+        *,
+    };
+
+    const JOIN_TYPES: [JoinType; 6] = [
+        JoinType::Inner,
+        JoinType::Left,
+        JoinType::Right,
+        JoinType::Full,
+        JoinType::Semi,
+        JoinType::Anti,
+    ];
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Column::new("k", DataType::Int64),
+            Column::new("name", DataType::Varchar(None)),
+        ])
+    }
+
+    /// Create table `name` with `rows` and an index on its `k` column.
+    fn create_table(
+        catalog: &Catalog<BufferPoolManagerImpl>,
+        name: &str,
+        rows: &[(Option<i64>, &str)],
+        kind: IndexKind,
+        unique: bool,
+    ) {
+        let table = catalog.create_table(name, schema()).unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        for &(key, x) in rows {
+            let key = key.map_or(Value::Null, Value::Int64);
+            let tuple = Tuple::new(vec![key, Value::Varchar(x.to_string())]);
+            heap.insert_tuple(&tuple.to_bytes(&table.schema).unwrap())
+                .unwrap();
+        }
+        catalog
+            .create_index(&format!("{name}_k"), name, &["k"], kind, unique)
+            .unwrap();
+    }
+
+    /// Join `left` with table `name` on `left.k = name.k` and `condition`, as sorted rows of
+    /// space-separated values.
+    fn join(
+        catalog: &Catalog<BufferPoolManagerImpl>,
+        left: BoxedExecutor,
+        name: &str,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    ) -> Vec<String> {
+        let table = catalog.table(name).unwrap();
+        let info = catalog.index(&format!("{name}_k")).unwrap();
+        let mut join = IndexNestedLoopJoinExecutor::new(
+            left,
+            table.clone(),
+            catalog.table_heap(&table).unwrap(),
+            info.clone(),
+            catalog.open_index(&info).unwrap(),
+            Expr::column(0, DataType::Int64),
+            join_type,
+            condition,
+        );
+        collect(&mut join)
+    }
+
+    #[test]
+    fn test_index_nested_loop_join() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        create_table(
+            &catalog,
+            "r",
+            &[
+                (Some(2), "r2"),
+                (Some(2), "r2b"),
+                (Some(3), "r3"),
+                (None, "rn"),
+                (Some(1), "r1"),
+            ],
+            IndexKind::BPlusTree,
+            false,
+        );
+        let l = [
+            (Some(1), "l1"),
+            (Some(2), "l2"),
+            (Some(2), "l2b"),
+            (None, "ln"),
+            (Some(4), "l4"),
+        ];
+
+        let inner = [
+            "1 l1 1 r1",
+            "2 l2 2 r2",
+            "2 l2 2 r2b",
+            "2 l2b 2 r2",
+            "2 l2b 2 r2b",
+        ];
+        let unmatched_left = ["4 l4 NULL NULL", "NULL ln NULL NULL"];
+        let unmatched_right = ["NULL NULL 3 r3", "NULL NULL NULL rn"];
+        for join_type in JOIN_TYPES {
+            let mut expected = match join_type {
+                JoinType::Inner => inner.to_vec(),
+                JoinType::Left => [&inner[..], &unmatched_left].concat(),
+                JoinType::Right => [&inner[..], &unmatched_right].concat(),
+                JoinType::Full => [&inner[..], &unmatched_left, &unmatched_right].concat(),
+                JoinType::Semi => vec!["1 l1", "2 l2", "2 l2b"],
+                JoinType::Anti => vec!["4 l4", "NULL ln"],
+            };
+            expected.sort();
+            assert_eq!(
+                join(&catalog, input(&l), "r", join_type, None),
+                expected,
+                "{join_type:?}"
+            );
+        }
+
+        // The condition is checked on top of the key, over the left tuple and the table tuple.
+        let condition = Expr::binary(
+            BinaryOp::NotEq,
+            Expr::column(3, DataType::Varchar(None)),
+            Expr::literal(Value::Varchar("r2b".into()), DataType::Varchar(None)),
+            DataType::Boolean,
+        );
+        assert_eq!(
+            join(&catalog, input(&l), "r", JoinType::Right, Some(condition)),
+            [
+                "1 l1 1 r1",
+                "2 l2 2 r2",
+                "2 l2b 2 r2",
+                "NULL NULL 2 r2b",
+                "NULL NULL 3 r3",
+                "NULL NULL NULL rn",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_nested_loop_join_with_hash_index() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        create_table(
+            &catalog,
+            "h",
+            &[(Some(1), "h1"), (Some(2), "h2"), (None, "hn")],
+            IndexKind::Hash,
+            true,
+        );
+        let l = [
+            (Some(2), "l2"),
+            (Some(2), "l2b"),
+            (None, "ln"),
+            (Some(5), "l5"),
+        ];
+
+        for (join_type, expected) in [
+            (JoinType::Inner, vec!["2 l2 2 h2", "2 l2b 2 h2"]),
+            (
+                JoinType::Full,
+                vec![
+                    "2 l2 2 h2",
+                    "2 l2b 2 h2",
+                    "5 l5 NULL NULL",
+                    "NULL NULL 1 h1",
+                    "NULL NULL NULL hn",
+                    "NULL ln NULL NULL",
+                ],
+            ),
+            (JoinType::Semi, vec!["2 l2", "2 l2b"]),
+            (JoinType::Anti, vec!["5 l5", "NULL ln"]),
+        ] {
+            assert_eq!(
+                join(&catalog, input(&l), "h", join_type, None),
+                expected,
+                "{join_type:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::{cmp::Ordering, collections::VecDeque, ops::Bound, sync::Arc};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{IndexInfo, TableInfo},
+        schema::Schema,
+        table_index::{EncodedKey, TableIndex},
+    },
+    storage::table::{record_id::RecordId, table_heap::TableHeap, tuple::Tuple},
+    types::value::Value,
+};
+
+use super::{eval::compare, executor::Executor};
+
+/// The number of index entries read from the index at a time.
+const BATCH_SIZE: usize = 64;
+
+/// Produces the tuples of a table whose first indexed column lies between two bounds, in key
+/// order.
+///
+/// Entries are read from the index in batches, each starting after the last entry of the one
+/// before, so no index iterator is kept across calls to `next` and the table can be modified
+/// on top of the scan. Every fetched tuple is checked against the bounds again, since it may
+/// have changed after its entry was read. Hash indexes only support `lower == upper`.
+pub struct IndexScanExecutor<'a, B: BufferPoolManager + ?Sized> {
+    table: Arc<TableInfo>,
+    heap: TableHeap<'a, B>,
+    info: Arc<IndexInfo>,
+    index: TableIndex<'a>,
+    lower: Bound<Value>,
+    upper: Bound<Value>,
+    /// Where the next batch of entries starts.
+    resume: Bound<EncodedKey>,
+    buffered: VecDeque<RecordId>,
+    done: bool,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> IndexScanExecutor<'a, B> {
+    pub fn new(
+        table: Arc<TableInfo>,
+        heap: TableHeap<'a, B>,
+        info: Arc<IndexInfo>,
+        index: TableIndex<'a>,
+        lower: Bound<Value>,
+        upper: Bound<Value>,
+    ) -> Self {
+        Self {
+            table,
+            heap,
+            info,
+            index,
+            lower,
+            upper,
+            resume: Bound::Unbounded,
+            buffered: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Whether `key`, the first indexed column of a tuple, lies between the bounds. NULL never
+    /// does.
+    fn in_bounds(&self, key: &Value) -> bool {
+        let above = match &self.lower {
+            Bound::Included(lower) => matches!(
+                compare(key, lower),
+                Some(Ordering::Greater | Ordering::Equal)
+            ),
+            Bound::Excluded(lower) => compare(key, lower) == Some(Ordering::Greater),
+            Bound::Unbounded => !key.is_null(),
+        };
+        let below = match &self.upper {
+            Bound::Included(upper) => {
+                matches!(compare(key, upper), Some(Ordering::Less | Ordering::Equal))
+            }
+            Bound::Excluded(upper) => compare(key, upper) == Some(Ordering::Less),
+            Bound::Unbounded => true,
+        };
+        above && below
+    }
+
+    fn load_next_batch(&mut self) -> anyhow::Result<()> {
+        if !self.index.kind().supports_range() {
+            let (Bound::Included(lower), Bound::Included(upper)) = (&self.lower, &self.upper)
+            else {
+                anyhow::bail!(
+                    "{:?} index \"{}\" cannot scan a range",
+                    self.index.kind(),
+                    self.info.name
+                );
+            };
+            if lower == upper {
+                self.buffered
+                    .extend(self.index.get(std::slice::from_ref(lower))?);
+            }
+            self.done = true;
+            return Ok(());
+        }
+
+        let upper = self.upper.as_ref().map(std::slice::from_ref);
+        let mut last = None;
+        for entry in self
+            .index
+            .scan_entries(self.resume.clone(), upper)?
+            .take(BATCH_SIZE)
+        {
+            let (key, rid) = entry?;
+            self.buffered.push_back(rid);
+            last = Some(key);
+        }
+        match last {
+            Some(last) => self.resume = Bound::Excluded(last),
+            None => self.done = true,
+        }
+        Ok(())
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Executor for IndexScanExecutor<'_, B> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.resume = if self.index.kind().supports_range() {
+            let lower = self.lower.as_ref().map(std::slice::from_ref);
+            self.index.lower_bound(lower)?
+        } else {
+            Bound::Unbounded
+        };
+        self.buffered.clear();
+        self.done = false;
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            let Some(rid) = self.buffered.pop_front() else {
+                if self.done {
+                    return Ok(None);
+                }
+                self.load_next_batch()?;
+                continue;
+            };
+            let Some(data) = self.heap.get_tuple(rid)? else {
+                continue;
+            };
+            let tuple = Tuple::from_bytes(&self.table.schema, &data)?;
+            if self.in_bounds(tuple.value(self.info.columns[0])) {
+                return Ok(Some(tuple.with_rid(rid)));
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.table.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::{catalog::Catalog, schema::Column},
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_index_scan() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let schema = Schema::new(vec![
+            Column::new("k", DataType::Int32),
+            Column::new("v", DataType::Int64),
+        ]);
+        let table = catalog.create_table("t", schema.clone()).unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        // Every key appears three times, more often than a batch holds in total.
+        for i in 0..300 {
+            let tuple = Tuple::new(vec![Value::Int32((i * 7) % 100), Value::Int64(i as i64)]);
+            heap.insert_tuple(&tuple.to_bytes(&schema).unwrap())
+                .unwrap();
+        }
+        heap.insert_tuple(
+            &Tuple::new(vec![Value::Null, Value::Int64(-1)])
+                .to_bytes(&schema)
+                .unwrap(),
+        )
+        .unwrap();
+        catalog
+            .create_index("t_k", "t", &["k"], IndexKind::BPlusTree, false)
+            .unwrap();
+        catalog
+            .create_index("t_v", "t", &["v"], IndexKind::Hash, true)
+            .unwrap();
+
+        let scan = |index: &str, lower, upper| {
+            let info = catalog.index(index).unwrap();
+            let mut executor = IndexScanExecutor::new(
+                table.clone(),
+                catalog.table_heap(&table).unwrap(),
+                info.clone(),
+                catalog.open_index(&info).unwrap(),
+                lower,
+                upper,
+            );
+            executor.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = executor.next().unwrap() {
+                assert!(tuple.rid().is_some());
+                rows.push(tuple.into_values());
+            }
+            rows
+        };
+
+        let key = |key: i64| Value::Int64(key);
+        let rows = scan("t_k", Bound::Excluded(key(10)), Bound::Included(key(40)));
+        assert_eq!(rows.len(), 90);
+        let keys: Vec<i64> = rows.iter().map(|row| row[0].as_i64().unwrap()).collect();
+        assert!(keys.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!((keys[0], keys[89]), (11, 40));
+        assert_eq!(scan("t_k", Bound::Unbounded, Bound::Unbounded).len(), 300);
+
+        let rows = scan("t_v", Bound::Included(key(42)), Bound::Included(key(42)));
+        assert_eq!(rows, vec![vec![Value::Int32(94), Value::Int64(42)]]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{catalog::TableInfo, schema::Schema},
+    planner::logical_plan::modification_schema,
+    storage::table::{table_heap::TableHeap, tuple::Tuple},
+    types::value::Value,
+};
+
+use super::{
+    executor::{BoxedExecutor, Executor},
+    index_maintenance::IndexMaintenance,
+};
+
+/// Inserts the tuples of its child into a table and its indexes, then produces the number of
+/// inserted rows.
+///
+/// If the child reads the table being inserted into, as in `INSERT INTO t SELECT * FROM t`, its
+/// tuples are collected before the first insert so the scan never sees the new rows.
+pub struct InsertExecutor<'a, B: BufferPoolManager + ?Sized> {
+    child: BoxedExecutor<'a>,
+    table: Arc<TableInfo>,
+    heap: TableHeap<'a, B>,
+    indexes: IndexMaintenance<'a>,
+    materialize: bool,
+    schema: Schema,
+    done: bool,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> InsertExecutor<'a, B> {
+    pub fn new(
+        child: BoxedExecutor<'a>,
+        table: Arc<TableInfo>,
+        heap: TableHeap<'a, B>,
+        indexes: IndexMaintenance<'a>,
+        materialize: bool,
+    ) -> Self {
+        Self {
+            child,
+            table,
+            heap,
+            indexes,
+            materialize,
+            schema: modification_schema(),
+            done: false,
+        }
+    }
+
+    fn insert(&self, tuple: Tuple) -> anyhow::Result<()> {
+        let data = tuple.to_bytes(&self.table.schema)?;
+        self.indexes.check_unique(tuple.values(), None)?;
+        let rid = self.heap.insert_tuple(&data)?;
+        self.indexes.insert(tuple.values(), rid)
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Executor for InsertExecutor<'_, B> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.done = false;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+
+        let mut count = 0;
+        if self.materialize {
+            let mut tuples = Vec::new();
+            while let Some(tuple) = self.child.next()? {
+                tuples.push(tuple);
+            }
+            for tuple in tuples {
+                self.insert(tuple)?;
+                count += 1;
+            }
+        } else {
+            while let Some(tuple) = self.child.next()? {
+                self.insert(tuple)?;
+                count += 1;
+            }
+        }
+
+        Ok(Some(Tuple::new(vec![Value::Int64(count)])))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::catalog::Catalog,
+        execution::{projection::ProjectionExecutor, values::ValuesExecutor},
+        planner::expr::Expr,
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{
+        super::index_maintenance::tests::{
+            check_indexes, create_table, lookup, rows, run, scan, values, Row,
+        },
+        *,
+    };
+
+    fn insert<'a>(
+        catalog: &Catalog<'a, BufferPoolManagerImpl>,
+        table: &Arc<TableInfo>,
+        child: BoxedExecutor<'a>,
+        materialize: bool,
+    ) -> anyhow::Result<i64> {
+        let indexes = IndexMaintenance::open(catalog, table)?;
+        let heap = catalog.table_heap(table)?;
+        run(InsertExecutor::new(
+            child,
+            table.clone(),
+            heap,
+            indexes,
+            materialize,
+        ))
+    }
+
+    fn input(table: &TableInfo, rows: &[Row]) -> BoxedExecutor<'static> {
+        let types: Vec<DataType> = table.schema.columns().iter().map(|c| c.data_type).collect();
+        let rows = rows
+            .iter()
+            .map(|&row| {
+                values(row)
+                    .into_iter()
+                    .zip(&types)
+                    .map(|(value, data_type)| Expr::literal(value, *data_type))
+                    .collect()
+            })
+            .collect();
+        Box::new(ValuesExecutor::new(rows, table.schema.clone()))
+    }
+
+    #[test]
+    fn test_insert() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let table = create_table(&catalog, &[]);
+
+        let new_rows = [
+            (1, Some(10), Some(100), "a"),
+            (2, Some(10), Some(200), "b"),
+            (3, None, None, "c"),
+            (4, Some(20), None, "d"),
+        ];
+        let count = insert(&catalog, &table, input(&table, &new_rows), false).unwrap();
+        assert_eq!(count, 4);
+        let stored = rows(&catalog, &table);
+        assert_eq!(
+            stored
+                .iter()
+                .map(|(_, row)| row.clone())
+                .collect::<Vec<_>>(),
+            new_rows.map(values)
+        );
+        check_indexes(&catalog, &table);
+        assert_eq!(
+            lookup(&catalog, "t_grp", &[Value::Int64(10)]),
+            [stored[0].0, stored[1].0]
+        );
+        assert_eq!(
+            lookup(&catalog, "t_code", &[Value::Int64(200)]),
+            [stored[1].0]
+        );
+
+        // A row that violates a unique index is rejected before anything is written, whichever
+        // index it violates.
+        for row in [(5, Some(30), Some(100), "e"), (1, Some(30), Some(500), "e")] {
+            let err = insert(&catalog, &table, input(&table, &[row]), false).unwrap_err();
+            assert!(err.to_string().contains("unique constraint"), "{err}");
+            assert_eq!(rows(&catalog, &table), stored);
+            assert!(lookup(&catalog, "t_grp", &[Value::Int64(30)]).is_empty());
+            check_indexes(&catalog, &table);
+        }
+
+        // Inserting the table into itself reads it completely first.
+        let shift = |column: usize, by: i64| {
+            Expr::binary(
+                BinaryOp::Plus,
+                Expr::column(column, DataType::Int64),
+                Expr::literal(Value::Int64(by), DataType::Int64),
+                DataType::Int64,
+            )
+        };
+        let exprs = vec![
+            shift(0, 10),
+            Expr::column(1, DataType::Int64),
+            shift(2, 1000),
+            Expr::column(3, DataType::Varchar(None)),
+        ];
+        let child = ProjectionExecutor::new(scan(&catalog, &table), exprs, table.schema.clone());
+        assert_eq!(insert(&catalog, &table, Box::new(child), true).unwrap(), 4);
+        assert_eq!(rows(&catalog, &table).len(), 8);
+        assert_eq!(lookup(&catalog, "t_grp", &[Value::Int64(10)]).len(), 4);
+        assert_eq!(lookup(&catalog, "t_id", &[Value::Int64(11)]).len(), 1);
+        check_indexes(&catalog, &table);
+    }
+}
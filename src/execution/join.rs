@@ -0,0 +1,441 @@
+//! What the join executors have in common.
+//!
+//! Whatever the algorithm, a join condition is evaluated over the left row followed by the
+//! right row, and a pair joins only if it is true, so a comparison with NULL never matches. The
+//! join type decides what comes out:
+//!
+//! | join type | matching pair  | left row without a match | right row without a match |
+//! |-----------|----------------|--------------------------|---------------------------|
+//! | INNER     | left ++ right  | -                        | -                         |
+//! | LEFT      | left ++ right  | left ++ NULLs            | -                         |
+//! | RIGHT     | left ++ right  | -                        | NULLs ++ right            |
+//! | FULL      | left ++ right  | left ++ NULLs            | NULLs ++ right            |
+//! | SEMI      | left, once     | -                        | -                         |
+//! | ANTI      | -              | left                     | -                         |
+//!
+//! Hash and sort-merge joins match rows by the equalities of the condition, as
+//! [`EquiJoinKeys`] splits them off, and evaluate only the rest on the pairs with equal keys.
+//! They compare keys in the encoding of [`join_key`], which is None for keys with a NULL.
+
+use crate::{
+    planner::{
+        expr::{Expr, ExprKind},
+        logical_plan::JoinType,
+    },
+    sql::ast::BinaryOp,
+    storage::table::tuple::Tuple,
+    types::value::Value,
+};
+
+use super::{
+    eval::{evaluate, evaluate_predicate},
+    key::encode_value,
+};
+
+/// A join condition split into equalities between the two inputs and the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquiJoinKeys {
+    /// Expressions over the left row.
+    pub left: Vec<Expr>,
+    /// Expressions over the right row, each equal to the left one at the same position.
+    pub right: Vec<Expr>,
+    /// The rest of the condition, over the joined row.
+    pub residual: Option<Expr>,
+}
+
+impl EquiJoinKeys {
+    /// Split the equalities off `condition`, a condition over rows of `left_width` left columns
+    /// followed by the right columns.
+    pub fn split(condition: Option<&Expr>, left_width: usize) -> Self {
+        let mut keys = Self {
+            left: Vec::new(),
+            right: Vec::new(),
+            residual: None,
+        };
+        let Some(condition) = condition else {
+            return keys;
+        };
+        let is_left = |expr: &Expr| {
+            let columns = expr.columns();
+            !columns.is_empty() && columns.iter().all(|&column| column < left_width)
+        };
+        let is_right = |expr: &Expr| {
+            let columns = expr.columns();
+            !columns.is_empty() && columns.iter().all(|&column| column >= left_width)
+        };
+        let mut residual = Vec::new();
+        for conjunct in condition.clone().split_conjunction() {
+            let ExprKind::Binary {
+                op: BinaryOp::Eq,
+                left,
+                right,
+            } = &conjunct.kind
+            else {
+                residual.push(conjunct);
+                continue;
+            };
+            let (left, right) = if is_left(left) && is_right(right) {
+                (left, right)
+            } else if is_right(left) && is_left(right) {
+                (right, left)
+            } else {
+                residual.push(conjunct);
+                continue;
+            };
+            keys.left.push(left.as_ref().clone());
+            keys.right
+                .push(right.as_ref().clone().map_columns(&|i| i - left_width));
+        }
+        keys.residual = Expr::conjunction(residual);
+        keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+}
+
+/// The values of `exprs` over `row`, encoded so that two keys are equal exactly when every
+/// pair of values is, or None if any of them is NULL.
+pub fn join_key(exprs: &[Expr], row: &[Value]) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut key = Vec::new();
+    for expr in exprs {
+        let value = evaluate(expr, row)?;
+        if value.is_null() {
+            return Ok(None);
+        }
+        encode_value(&value, &mut key);
+    }
+    Ok(Some(key))
+}
+
+/// Whether `condition` is true for the pair, or there is none.
+pub fn condition_holds(
+    condition: Option<&Expr>,
+    left: &Tuple,
+    right: &Tuple,
+) -> anyhow::Result<bool> {
+    let Some(condition) = condition else {
+        return Ok(true);
+    };
+    let row: Vec<Value> = left
+        .values()
+        .iter()
+        .chain(right.values())
+        .cloned()
+        .collect();
+    evaluate_predicate(condition, &row)
+}
+
+/// The output for a matching pair of a join type that outputs the right columns.
+pub fn joined(left: &Tuple, right: Tuple) -> Tuple {
+    let mut values = left.values().to_vec();
+    values.extend(right.into_values());
+    Tuple::new(values)
+}
+
+/// The output for a left row without a match, if [`JoinType::keeps_unmatched_left`].
+pub fn unmatched_left(join_type: JoinType, left: Tuple, right_width: usize) -> Tuple {
+    if !join_type.outputs_right() {
+        return left;
+    }
+    let mut values = left.into_values();
+    values.resize(values.len() + right_width, Value::Null);
+    Tuple::new(values)
+}
+
+/// The output for a right row without a match, if [`JoinType::keeps_unmatched_right`].
+pub fn unmatched_right(left_width: usize, right: Tuple) -> Tuple {
+    let mut values = vec![Value::Null; left_width];
+    values.extend(right.into_values());
+    Tuple::new(values)
+}
+
+/// Fixtures shared by the tests of the join executors.
+#[cfg(test)]
+pub(super) mod test_util {
+    use crate::{
+        catalog::schema::{Column, Schema},
+        planner::expr::Expr,
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::super::{
+        executor::{BoxedExecutor, Executor},
+        values::ValuesExecutor,
+    };
+
+    /// A `(k, name)` row.
+    pub type Row = (Option<i64>, String);
+
+    pub fn rows(rows: &[(Option<i64>, &str)]) -> Vec<Row> {
+        rows.iter()
+            .map(|&(key, name)| (key, name.to_string()))
+            .collect()
+    }
+
+    /// An input of `(k, name)` rows.
+    pub fn input<S: AsRef<str>>(rows: &[(Option<i64>, S)]) -> BoxedExecutor<'static> {
+        let schema = Schema::new(vec![
+            Column::new("k", DataType::Int64),
+            Column::new("name", DataType::Varchar(None)),
+        ]);
+        let rows = rows
+            .iter()
+            .map(|(key, name)| {
+                vec![
+                    Expr::literal(key.map_or(Value::Null, Value::Int64), DataType::Int64),
+                    Expr::literal(
+                        Value::Varchar(name.as_ref().to_string()),
+                        DataType::Varchar(None),
+                    ),
+                ]
+            })
+            .collect();
+        Box::new(ValuesExecutor::new(rows, schema))
+    }
+
+    /// The output of `executor`, as sorted rows of space-separated values.
+    pub fn collect(executor: &mut dyn Executor) -> Vec<String> {
+        executor.init().unwrap();
+        let mut rows = Vec::new();
+        while let Some(tuple) = executor.next().unwrap() {
+            let values: Vec<String> = tuple.values().iter().map(Value::to_string).collect();
+            rows.push(values.join(" "));
+        }
+        rows.sort();
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        execution::{
+            executor::BoxedExecutor, hash_join::HashJoinExecutor,
+            index_nested_loop_join::IndexNestedLoopJoinExecutor,
+            nested_loop_join::NestedLoopJoinExecutor, seq_scan::SeqScanExecutor,
+            sort_merge_join::SortMergeJoinExecutor,
+        },
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{test_util::collect, *};
+
+    const JOIN_TYPES: [JoinType; 6] = [
+        JoinType::Inner,
+        JoinType::Left,
+        JoinType::Right,
+        JoinType::Full,
+        JoinType::Semi,
+        JoinType::Anti,
+    ];
+
+    /// Join `l` and `r` on `l.a = r.b` and `condition`, with every algorithm.
+    fn join_all(
+        catalog: &Catalog<BufferPoolManagerImpl>,
+        temp: &TempBufferPool,
+        work_mem: usize,
+        join_type: JoinType,
+        residual: Option<Expr>,
+    ) -> Vec<Vec<String>> {
+        let scan = |name: &str| -> BoxedExecutor {
+            let table = catalog.table(name).unwrap();
+            let heap = catalog.table_heap(&table).unwrap();
+            Box::new(SeqScanExecutor::new(table, heap))
+        };
+        let equal = Expr::binary(
+            BinaryOp::Eq,
+            Expr::column(0, DataType::Int64),
+            Expr::column(2, DataType::Int64),
+            DataType::Boolean,
+        );
+        let condition = Expr::conjunction([Some(equal), residual].into_iter().flatten().collect());
+        let keys = EquiJoinKeys::split(condition.as_ref(), 2);
+        assert_eq!(keys.left.len(), 1);
+
+        let table = catalog.table("r").unwrap();
+        let info = catalog.index("r_b").unwrap();
+        let index_join = IndexNestedLoopJoinExecutor::new(
+            scan("l"),
+            table.clone(),
+            catalog.table_heap(&table).unwrap(),
+            info.clone(),
+            catalog.open_index(&info).unwrap(),
+            Expr::column(0, DataType::Int64),
+            join_type,
+            condition.clone(),
+        );
+        let executors: Vec<BoxedExecutor> = vec![
+            Box::new(NestedLoopJoinExecutor::new(
+                scan("l"),
+                scan("r"),
+                join_type,
+                condition.clone(),
+            )),
+            Box::new(index_join),
+            Box::new(HashJoinExecutor::new(
+                scan("l"),
+                scan("r"),
+                join_type,
+                keys.clone(),
+                temp,
+                work_mem,
+            )),
+            Box::new(SortMergeJoinExecutor::new(
+                scan("l"),
+                scan("r"),
+                join_type,
+                keys,
+                temp,
+                work_mem,
+            )),
+        ];
+        executors
+            .into_iter()
+            .map(|mut executor| collect(executor.as_mut()))
+            .collect()
+    }
+
+    fn create_tables(catalog: &Catalog<BufferPoolManagerImpl>, l: &[String], r: &[String]) {
+        for (name, key, rows) in [("l", "a", l), ("r", "b", r)] {
+            let table = catalog
+                .create_table(
+                    name,
+                    Schema::new(vec![
+                        Column::new(key, DataType::Int64),
+                        Column::new("x", DataType::Varchar(None)),
+                    ]),
+                )
+                .unwrap();
+            let heap = catalog.table_heap(&table).unwrap();
+            for row in rows {
+                let (key, x) = row.split_once(' ').unwrap();
+                let key = key.parse().map_or(Value::Null, Value::Int64);
+                let tuple = Tuple::new(vec![key, Value::Varchar(x.to_string())]);
+                heap.insert_tuple(&tuple.to_bytes(&table.schema).unwrap())
+                    .unwrap();
+            }
+        }
+        catalog
+            .create_index("r_b", "r", &["b"], IndexKind::BPlusTree, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_join_types() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let temp_disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db.tmp")).unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let rows = |rows: &[&str]| rows.iter().map(|row| row.to_string()).collect::<Vec<_>>();
+        create_tables(
+            &catalog,
+            &rows(&["1 l1", "2 l2", "2 l2b", "NULL ln", "4 l4"]),
+            &rows(&["2 r2", "2 r2b", "3 r3", "NULL rn", "1 r1"]),
+        );
+
+        let inner = [
+            "1 l1 1 r1",
+            "2 l2 2 r2",
+            "2 l2 2 r2b",
+            "2 l2b 2 r2",
+            "2 l2b 2 r2b",
+        ];
+        let unmatched_left = ["4 l4 NULL NULL", "NULL ln NULL NULL"];
+        let unmatched_right = ["NULL NULL 3 r3", "NULL NULL NULL rn"];
+        let expected = |parts: &[&[&str]]| {
+            let mut rows: Vec<String> = parts.concat().iter().map(|row| row.to_string()).collect();
+            rows.sort();
+            rows
+        };
+        for join_type in JOIN_TYPES {
+            let expected = match join_type {
+                JoinType::Inner => expected(&[&inner]),
+                JoinType::Left => expected(&[&inner, &unmatched_left]),
+                JoinType::Right => expected(&[&inner, &unmatched_right]),
+                JoinType::Full => expected(&[&inner, &unmatched_left, &unmatched_right]),
+                JoinType::Semi => expected(&[&["1 l1", "2 l2", "2 l2b"]]),
+                JoinType::Anti => expected(&[&["4 l4", "NULL ln"]]),
+            };
+            for (algorithm, rows) in join_all(&catalog, &temp, 16, join_type, None)
+                .into_iter()
+                .enumerate()
+            {
+                assert_eq!(
+                    rows, expected,
+                    "{join_type:?} join with algorithm {algorithm}"
+                );
+            }
+        }
+
+        // A residual condition leaves r2b unmatched, which FULL joins then output on its own.
+        let residual = Expr::binary(
+            BinaryOp::NotEq,
+            Expr::column(3, DataType::Varchar(None)),
+            Expr::literal(Value::Varchar("r2b".into()), DataType::Varchar(None)),
+            DataType::Boolean,
+        );
+        let expected = expected(&[
+            &["1 l1 1 r1", "2 l2 2 r2", "2 l2b 2 r2", "NULL NULL 2 r2b"],
+            &unmatched_left,
+            &unmatched_right,
+        ]);
+        for rows in join_all(&catalog, &temp, 16, JoinType::Full, Some(residual)) {
+            assert_eq!(rows, expected);
+        }
+    }
+
+    #[test]
+    fn test_spilling_joins() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let temp_disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db.tmp")).unwrap();
+        let temp = TempBufferPool::new(8, temp_disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let key = |i: usize, modulus: usize| match i % 13 {
+            0 => "NULL".to_string(),
+            _ => (i % modulus).to_string(),
+        };
+        let l: Vec<String> = (0..400).map(|i| format!("{} l{i}", key(i, 50))).collect();
+        // Key 7 is much more frequent than the others, so that its partition splits repeatedly.
+        let r: Vec<String> = (0..300)
+            .map(|i| match i % 3 {
+                0 => format!("7 r{i}"),
+                _ => format!("{} r{i}", key(i, 70)),
+            })
+            .collect();
+        create_tables(&catalog, &l, &r);
+
+        let residual = Expr::binary(
+            BinaryOp::NotEq,
+            Expr::column(1, DataType::Varchar(None)),
+            Expr::literal(Value::Varchar("l57".into()), DataType::Varchar(None)),
+            DataType::Boolean,
+        );
+        for join_type in JOIN_TYPES {
+            let results = join_all(&catalog, &temp, 4, join_type, Some(residual.clone()));
+            assert!(!results[0].is_empty());
+            for rows in &results[1..] {
+                assert_eq!(rows, &results[0], "{join_type:?} join");
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Encoding of values into keys for hash tables and sorting, for joins, grouping and DISTINCT.
+//!
+//! Every value is a tag byte followed by its payload, and variable-length payloads are
+//! prefixed with their length, so a sequence of encoded values can be compared as bytes. Two
+//! encodings are equal exactly when the values are equal as [`compare`](super::eval::compare)
+//! sees them: integers of every width share one encoding, floats have a single zero and a
+//! single NaN, and decimals drop trailing zeros. NULL has an encoding of its own, which
+//! GROUP BY and DISTINCT use to put NULLs together; joins never encode it.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use crate::types::value::Value;
+
+/// Append the encoding of `value` to `key`.
+pub fn encode_value(value: &Value, key: &mut Vec<u8>) {
+    match value {
+        Value::Null => key.push(8),
+        Value::Boolean(v) => {
+            key.push(0);
+            key.push(*v as u8);
+        }
+        Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_) => {
+            key.push(1);
+            key.extend(value.as_i64().unwrap().to_be_bytes());
+        }
+        Value::Float64(v) => {
+            let v = if *v == 0.0 {
+                0.0
+            } else if v.is_nan() {
+                f64::NAN
+            } else {
+                *v
+            };
+            key.push(2);
+            key.extend(v.to_bits().to_be_bytes());
+        }
+        Value::Decimal(d) => {
+            let (mut mantissa, mut scale) = (d.mantissa(), d.scale());
+            while scale > 0 && mantissa % 10 == 0 {
+                mantissa /= 10;
+                scale -= 1;
+            }
+            key.push(3);
+            key.push(scale);
+            key.extend(mantissa.to_be_bytes());
+        }
+        Value::Varchar(s) => {
+            key.push(4);
+            key.extend((s.len() as u32).to_be_bytes());
+            key.extend(s.as_bytes());
+        }
+        Value::Bytea(b) => {
+            key.push(5);
+            key.extend((b.len() as u32).to_be_bytes());
+            key.extend(b);
+        }
+        Value::Date(d) => {
+            key.push(6);
+            key.extend(d.0.to_be_bytes());
+        }
+        Value::Timestamp(t) => {
+            key.push(7);
+            key.extend(t.0.to_be_bytes());
+        }
+    }
+}
+
+/// One of `partitions` partitions for `key`, by a hash function that differs for every `seed`,
+/// so that a partition can be split again with the next seed.
+pub fn hash_partition(key: &[u8], seed: u32, partitions: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    key.hash(&mut hasher);
+    hasher.finish() as usize % partitions
+}
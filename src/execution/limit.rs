@@ -0,0 +1,132 @@
+use crate::{catalog::schema::Schema, storage::table::tuple::Tuple};
+
+use super::executor::{BoxedExecutor, Executor};
+
+/// Skips the first `offset` tuples of its child and then passes on at most `limit`.
+pub struct LimitExecutor<'a> {
+    child: BoxedExecutor<'a>,
+    limit: Option<usize>,
+    offset: usize,
+    skipped: usize,
+    emitted: usize,
+}
+
+impl<'a> LimitExecutor<'a> {
+    pub fn new(child: BoxedExecutor<'a>, limit: Option<usize>, offset: usize) -> Self {
+        Self {
+            child,
+            limit,
+            offset,
+            skipped: 0,
+            emitted: 0,
+        }
+    }
+}
+
+impl Executor for LimitExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.skipped = 0;
+        self.emitted = 0;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        // Stop without pulling from the child once the limit is reached.
+        if self.limit.map_or(false, |limit| self.emitted >= limit) {
+            return Ok(None);
+        }
+        while self.skipped < self.offset {
+            if self.child.next()?.is_none() {
+                return Ok(None);
+            }
+            self.skipped += 1;
+        }
+        let tuple = self.child.next()?;
+        if tuple.is_some() {
+            self.emitted += 1;
+        }
+        Ok(tuple)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.child.schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{
+        catalog::schema::Column,
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::*;
+
+    /// Produces the numbers from 0 up to `len`, counting how many it has produced.
+    struct Numbers {
+        len: i64,
+        next: i64,
+        pulled: Rc<Cell<usize>>,
+        schema: Schema,
+    }
+
+    impl Executor for Numbers {
+        fn init(&mut self) -> anyhow::Result<()> {
+            self.next = 0;
+            Ok(())
+        }
+
+        fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+            if self.next == self.len {
+                return Ok(None);
+            }
+            self.pulled.set(self.pulled.get() + 1);
+            self.next += 1;
+            Ok(Some(Tuple::new(vec![Value::Int64(self.next - 1)])))
+        }
+
+        fn schema(&self) -> &Schema {
+            &self.schema
+        }
+    }
+
+    /// The output of LIMIT `limit` OFFSET `offset` over the numbers up to `len`, and how many
+    /// numbers it pulled.
+    fn limit(len: i64, limit: Option<usize>, offset: usize) -> (Vec<i64>, usize) {
+        let pulled = Rc::new(Cell::new(0));
+        let numbers = Numbers {
+            len,
+            next: 0,
+            pulled: pulled.clone(),
+            schema: Schema::new(vec![Column::new("n", DataType::Int64)]),
+        };
+        let mut executor = LimitExecutor::new(Box::new(numbers), limit, offset);
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            pulled.set(0);
+            executor.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = executor.next().unwrap() {
+                rows.push(tuple.value(0).as_i64().unwrap());
+            }
+            // Once done, it stays done.
+            assert!(executor.next().unwrap().is_none());
+            results.push((rows, pulled.get()));
+        }
+        assert_eq!(results[0], results[1]);
+        results.pop().unwrap()
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(limit(10, Some(3), 0), (vec![0, 1, 2], 3));
+        assert_eq!(limit(10, Some(3), 4), (vec![4, 5, 6], 7));
+        assert_eq!(limit(10, None, 8), (vec![8, 9], 10));
+        assert_eq!(limit(10, Some(0), 2), (vec![], 0));
+        assert_eq!(limit(5, Some(3), 4), (vec![4], 5));
+        assert_eq!(limit(5, Some(3), 7), (vec![], 5));
+        assert_eq!(limit(0, None, 0), (vec![], 0));
+    }
+}
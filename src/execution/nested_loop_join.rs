@@ -0,0 +1,259 @@
+use crate::{
+    catalog::schema::Schema,
+    planner::{
+        expr::Expr,
+        logical_plan::{join_schema, JoinType},
+    },
+    storage::table::tuple::Tuple,
+};
+
+use super::{
+    executor::{BoxedExecutor, Executor},
+    join::{condition_holds, joined, unmatched_left, unmatched_right},
+};
+
+/// Joins every left tuple with every right tuple for which the condition holds, rescanning the
+/// right input once per left tuple, so it needs no memory for either input and takes any
+/// condition.
+///
+/// RIGHT and FULL joins remember which right tuples matched by their position in the right
+/// input, which is the same in every rescan, and scan it once more at the end for the others.
+pub struct NestedLoopJoinExecutor<'a> {
+    left: BoxedExecutor<'a>,
+    right: BoxedExecutor<'a>,
+    join_type: JoinType,
+    condition: Option<Expr>,
+    schema: Schema,
+    phase: Phase,
+    /// The left tuple being joined and whether it has matched so far.
+    current: Option<(Tuple, bool)>,
+    /// The position of the next tuple of the right input.
+    right_pos: usize,
+    /// Whether the right tuple at each position has matched.
+    right_matched: Vec<bool>,
+}
+
+enum Phase {
+    Join,
+    UnmatchedRight,
+    Done,
+}
+
+impl<'a> NestedLoopJoinExecutor<'a> {
+    pub fn new(
+        left: BoxedExecutor<'a>,
+        right: BoxedExecutor<'a>,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    ) -> Self {
+        let schema = join_schema(left.schema(), right.schema(), join_type);
+        Self {
+            left,
+            right,
+            join_type,
+            condition,
+            schema,
+            phase: Phase::Join,
+            current: None,
+            right_pos: 0,
+            right_matched: Vec::new(),
+        }
+    }
+}
+
+impl Executor for NestedLoopJoinExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.left.init()?;
+        self.phase = Phase::Join;
+        self.current = None;
+        self.right_matched.clear();
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            match self.phase {
+                Phase::Join => {}
+                Phase::UnmatchedRight => {
+                    let Some(right) = self.right.next()? else {
+                        self.phase = Phase::Done;
+                        continue;
+                    };
+                    let pos = self.right_pos;
+                    self.right_pos += 1;
+                    if !self.right_matched.get(pos).copied().unwrap_or(false) {
+                        let left_width = self.left.schema().column_count();
+                        return Ok(Some(unmatched_right(left_width, right)));
+                    }
+                    continue;
+                }
+                Phase::Done => return Ok(None),
+            }
+
+            if self.current.is_none() {
+                let Some(left) = self.left.next()? else {
+                    self.phase = if self.join_type.keeps_unmatched_right() {
+                        self.right.init()?;
+                        self.right_pos = 0;
+                        Phase::UnmatchedRight
+                    } else {
+                        Phase::Done
+                    };
+                    continue;
+                };
+                self.right.init()?;
+                self.right_pos = 0;
+                self.current = Some((left, false));
+            }
+            let (left, matched) = self.current.as_mut().unwrap();
+
+            let Some(right) = self.right.next()? else {
+                let (left, matched) = self.current.take().unwrap();
+                if !matched && self.join_type.keeps_unmatched_left() {
+                    let right_width = self.right.schema().column_count();
+                    return Ok(Some(unmatched_left(self.join_type, left, right_width)));
+                }
+                continue;
+            };
+            let pos = self.right_pos;
+            self.right_pos += 1;
+            if !condition_holds(self.condition.as_ref(), left, &right)? {
+                continue;
+            }
+            *matched = true;
+            match self.join_type {
+                JoinType::Semi => return Ok(self.current.take().map(|(left, _)| left)),
+                JoinType::Anti => self.current = None,
+                _ => {
+                    if self.join_type.keeps_unmatched_right() {
+                        if self.right_matched.len() <= pos {
+                            self.right_matched.resize(pos + 1, false);
+                        }
+                        self.right_matched[pos] = true;
+                    }
+                    return Ok(Some(joined(left, right)));
+                }
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sql::ast::BinaryOp, types::data_type::DataType};
+
+    use super::{
+        super::join::test_util::{collect, input},
+        *,
+    };
+
+    /// The condition `l.k <op> r.k`.
+    fn compare_keys(op: BinaryOp) -> Expr {
+        Expr::binary(
+            op,
+            Expr::column(0, DataType::Int64),
+            Expr::column(2, DataType::Int64),
+            DataType::Boolean,
+        )
+    }
+
+    /// Run `join` twice, to check that `init` starts it over, as sorted rows of space-separated
+    /// values.
+    fn run(join: &mut NestedLoopJoinExecutor) -> Vec<String> {
+        let rows = collect(join);
+        assert_eq!(collect(join), rows);
+        rows
+    }
+
+    const JOIN_TYPES: [JoinType; 6] = [
+        JoinType::Inner,
+        JoinType::Left,
+        JoinType::Right,
+        JoinType::Full,
+        JoinType::Semi,
+        JoinType::Anti,
+    ];
+
+    #[test]
+    fn test_nested_loop_join() {
+        let l = [
+            (Some(1), "l1"),
+            (Some(2), "l2"),
+            (Some(2), "l2b"),
+            (None, "ln"),
+            (Some(4), "l4"),
+        ];
+        let r = [
+            (Some(2), "r2"),
+            (Some(2), "r2b"),
+            (Some(3), "r3"),
+            (None, "rn"),
+            (Some(1), "r1"),
+        ];
+
+        let inner = [
+            "1 l1 1 r1",
+            "2 l2 2 r2",
+            "2 l2 2 r2b",
+            "2 l2b 2 r2",
+            "2 l2b 2 r2b",
+        ];
+        let unmatched_left = ["4 l4 NULL NULL", "NULL ln NULL NULL"];
+        let unmatched_right = ["NULL NULL 3 r3", "NULL NULL NULL rn"];
+        for join_type in JOIN_TYPES {
+            let mut expected = match join_type {
+                JoinType::Inner => inner.to_vec(),
+                JoinType::Left => [&inner[..], &unmatched_left].concat(),
+                JoinType::Right => [&inner[..], &unmatched_right].concat(),
+                JoinType::Full => [&inner[..], &unmatched_left, &unmatched_right].concat(),
+                JoinType::Semi => vec!["1 l1", "2 l2", "2 l2b"],
+                JoinType::Anti => vec!["4 l4", "NULL ln"],
+            };
+            expected.sort();
+            let condition = Some(compare_keys(BinaryOp::Eq));
+            let mut join = NestedLoopJoinExecutor::new(input(&l), input(&r), join_type, condition);
+            assert_eq!(run(&mut join), expected, "{join_type:?}");
+        }
+    }
+
+    #[test]
+    fn test_nested_loop_join_conditions() {
+        let l = [(Some(1), "l1"), (Some(3), "l3"), (None, "ln")];
+        let r = [(Some(2), "r2"), (Some(3), "r3"), (None, "rn")];
+
+        // Any condition works, and a comparison with NULL never holds.
+        for (join_type, expected) in [
+            (JoinType::Inner, vec!["1 l1 2 r2", "1 l1 3 r3"]),
+            (
+                JoinType::Full,
+                vec![
+                    "1 l1 2 r2",
+                    "1 l1 3 r3",
+                    "3 l3 NULL NULL",
+                    "NULL NULL NULL rn",
+                    "NULL ln NULL NULL",
+                ],
+            ),
+            (JoinType::Semi, vec!["1 l1"]),
+            (JoinType::Anti, vec!["3 l3", "NULL ln"]),
+        ] {
+            let condition = Some(compare_keys(BinaryOp::Lt));
+            let mut join = NestedLoopJoinExecutor::new(input(&l), input(&r), join_type, condition);
+            assert_eq!(run(&mut join), expected, "{join_type:?}");
+        }
+
+        // Without a condition, every pair joins.
+        let mut join = NestedLoopJoinExecutor::new(input(&l), input(&r), JoinType::Inner, None);
+        assert_eq!(run(&mut join).len(), 9);
+        let mut join =
+            NestedLoopJoinExecutor::new(input(&l), input::<&str>(&[]), JoinType::Left, None);
+        assert_eq!(
+            run(&mut join),
+            ["1 l1 NULL NULL", "3 l3 NULL NULL", "NULL ln NULL NULL"]
+        );
+    }
+}
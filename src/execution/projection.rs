@@ -0,0 +1,107 @@
+use crate::{
+    catalog::schema::Schema, planner::expr::Expr, storage::table::tuple::Tuple, types::value::Value,
+};
+
+use super::{
+    eval::evaluate,
+    executor::{BoxedExecutor, Executor},
+};
+
+/// Computes one output column per expression over each tuple of its child.
+pub struct ProjectionExecutor<'a> {
+    child: BoxedExecutor<'a>,
+    exprs: Vec<Expr>,
+    schema: Schema,
+}
+
+impl<'a> ProjectionExecutor<'a> {
+    pub fn new(child: BoxedExecutor<'a>, exprs: Vec<Expr>, schema: Schema) -> Self {
+        Self {
+            child,
+            exprs,
+            schema,
+        }
+    }
+}
+
+impl Executor for ProjectionExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        let Some(tuple) = self.child.next()? else {
+            return Ok(None);
+        };
+        let values = self
+            .exprs
+            .iter()
+            .map(|expr| evaluate(expr, tuple.values()))
+            .collect::<anyhow::Result<Vec<Value>>>()?;
+
+        Ok(Some(Tuple::new(values)))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{catalog::schema::Column, sql::ast::BinaryOp, types::data_type::DataType};
+
+    use super::{super::values::ValuesExecutor, *};
+
+    #[test]
+    fn test_projection() {
+        let rows = [(1, "a"), (2, "b")]
+            .into_iter()
+            .map(|(k, name)| {
+                vec![
+                    Expr::literal(Value::Int64(k), DataType::Int64),
+                    Expr::literal(Value::Varchar(name.into()), DataType::Varchar(None)),
+                ]
+            })
+            .collect();
+        let child = ValuesExecutor::new(
+            rows,
+            Schema::new(vec![
+                Column::new("k", DataType::Int64),
+                Column::new("name", DataType::Varchar(None)),
+            ]),
+        );
+        let schema = Schema::new(vec![
+            Column::new("name", DataType::Varchar(None)),
+            Column::new("double", DataType::Int64),
+            Column::new("k", DataType::Int64),
+        ]);
+        let double = Expr::binary(
+            BinaryOp::Multiply,
+            Expr::column(0, DataType::Int64),
+            Expr::literal(Value::Int64(2), DataType::Int64),
+            DataType::Int64,
+        );
+        let exprs = vec![
+            Expr::column(1, DataType::Varchar(None)),
+            double,
+            Expr::column(0, DataType::Int64),
+        ];
+        let mut projection = ProjectionExecutor::new(Box::new(child), exprs, schema.clone());
+        assert_eq!(projection.schema(), &schema);
+        for _ in 0..2 {
+            projection.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = projection.next().unwrap() {
+                rows.push(tuple.into_values());
+            }
+            assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Varchar("a".into()), Value::Int64(2), Value::Int64(1)],
+                    vec![Value::Varchar("b".into()), Value::Int64(4), Value::Int64(2)],
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{catalog::TableInfo, schema::Schema},
+    storage::table::{
+        table_heap::{HeapTuple, TableHeap},
+        tuple::{Tuple, TupleRef},
+    },
+    PageId,
+};
+
+use super::executor::Executor;
+
+/// Produces every live tuple of a table in page order, with its record id.
+///
+/// The tuples of one page are copied out at a time, like [`TableHeap::iter`] does, so no page
+/// stays pinned between calls to `next`.
+pub struct SeqScanExecutor<'a, B: BufferPoolManager + ?Sized> {
+    table: Arc<TableInfo>,
+    heap: TableHeap<'a, B>,
+    /// The columns decoded, or None for all of them.
+    columns: Option<Vec<usize>>,
+    schema: Schema,
+    next_page_id: Option<PageId>,
+    buffered: VecDeque<HeapTuple>,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> SeqScanExecutor<'a, B> {
+    pub fn new(table: Arc<TableInfo>, heap: TableHeap<'a, B>) -> Self {
+        Self {
+            schema: table.schema.clone(),
+            table,
+            heap,
+            columns: None,
+            next_page_id: None,
+            buffered: VecDeque::new(),
+        }
+    }
+
+    /// Produce only the columns at `columns` of each tuple, as rows of `schema`, without
+    /// decoding the others.
+    pub fn with_columns(mut self, columns: Vec<usize>, schema: Schema) -> Self {
+        self.columns = Some(columns);
+        self.schema = schema;
+        self
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Executor for SeqScanExecutor<'_, B> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.next_page_id = Some(self.heap.first_page_id()?);
+        self.buffered.clear();
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            if let Some((rid, data)) = self.buffered.pop_front() {
+                let tuple = TupleRef::new(&self.table.schema, &data)?;
+                let tuple = match &self.columns {
+                    Some(columns) => tuple.project(columns)?,
+                    None => tuple.to_tuple()?,
+                };
+                return Ok(Some(tuple.with_rid(rid)));
+            }
+            let Some(page_id) = self.next_page_id.take() else {
+                return Ok(None);
+            };
+            let (tuples, next_page_id) = self.heap.read_page(page_id)?;
+            self.buffered.extend(tuples);
+            self.next_page_id = next_page_id;
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::{catalog::Catalog, schema::Column},
+        execution::filter::FilterExecutor,
+        planner::expr::Expr,
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_seq_scan() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let schema = Schema::new(vec![
+            Column::new("k", DataType::Int64),
+            Column::new("v", DataType::Varchar(None)),
+        ]);
+        let table = catalog.create_table("t", schema.clone()).unwrap();
+        let heap = catalog.table_heap(&table).unwrap();
+        // Enough rows for several pages, every third of which is deleted again.
+        let mut rids = Vec::new();
+        for i in 0..1000 {
+            let tuple = Tuple::new(vec![Value::Int64(i), Value::Varchar(format!("row {i}"))]);
+            let rid = heap
+                .insert_tuple(&tuple.to_bytes(&schema).unwrap())
+                .unwrap();
+            if i % 3 == 0 {
+                assert!(heap.mark_delete(rid).unwrap());
+            } else {
+                rids.push((rid, i));
+            }
+        }
+        assert!(rids.first().unwrap().0.page_id != rids.last().unwrap().0.page_id);
+
+        let mut scan = SeqScanExecutor::new(table.clone(), catalog.table_heap(&table).unwrap());
+        assert_eq!(scan.schema(), &schema);
+        for _ in 0..2 {
+            scan.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = scan.next().unwrap() {
+                rows.push((tuple.rid().unwrap(), tuple.value(0).as_i64().unwrap()));
+            }
+            assert_eq!(rows, rids);
+        }
+
+        // A filter on top passes the record ids on.
+        let predicate = Expr::binary(
+            BinaryOp::Eq,
+            Expr::column(0, DataType::Int64),
+            Expr::literal(Value::Int64(500), DataType::Int64),
+            DataType::Boolean,
+        );
+        let scan = SeqScanExecutor::new(table.clone(), catalog.table_heap(&table).unwrap());
+        let mut filter = FilterExecutor::new(Box::new(scan), predicate);
+        filter.init().unwrap();
+        let tuple = filter.next().unwrap().unwrap();
+        let (rid, _) = rids.iter().find(|(_, i)| *i == 500).unwrap();
+        assert_eq!(tuple.rid(), Some(*rid));
+        assert!(filter.next().unwrap().is_none());
+
+        // A scan of some columns decodes only those, in the order asked for.
+        let mut scan = SeqScanExecutor::new(table.clone(), catalog.table_heap(&table).unwrap())
+            .with_columns(vec![1, 0], schema.project(&[1, 0]));
+        assert_eq!(scan.schema(), &schema.project(&[1, 0]));
+        scan.init().unwrap();
+        let tuple = scan.next().unwrap().unwrap();
+        let (rid, i) = rids[0];
+        assert_eq!(tuple.rid(), Some(rid));
+        assert_eq!(
+            tuple.into_values(),
+            [Value::Varchar(format!("row {i}")), Value::Int64(i)]
+        );
+
+        // An empty table has no rows.
+        let empty = catalog.create_table("empty", schema).unwrap();
+        let mut scan = SeqScanExecutor::new(empty.clone(), catalog.table_heap(&empty).unwrap());
+        scan.init().unwrap();
+        assert!(scan.next().unwrap().is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! `ORDER BY`.
+//!
+//! The tuples of the input are sorted with an [`ExternalSorter`], which spills runs to
+//! temporary pages beyond `work_mem` tuples. Each sorted entry is the values of the sort keys
+//! followed by the tuple, both encoded as tuples:
+//!
+//! | offset | size      | field                 |
+//! |--------|-----------|-----------------------|
+//! | 0      | 4         | length of the keys    |
+//! | 4      | keys len  | keys, by `key_schema` |
+//! | ...    | remainder | tuple                 |
+//!
+//! Keys compare as SQL values, and NULL is larger than every other value, as in PostgreSQL:
+//! NULLs come last in ascending order and first in descending order.
+
+use std::{cmp::Ordering, rc::Rc};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::schema::{Column, Schema},
+    planner::logical_plan::SortKey,
+    storage::{
+        external_sort::{ExternalSorter, SortedEntries},
+        table::tuple::{Tuple, TupleRef},
+    },
+};
+
+use super::{
+    eval::{compare, evaluate},
+    executor::{BoxedExecutor, Executor},
+};
+
+type EntryOrder<'a> = Box<dyn Fn(&Vec<u8>, &Vec<u8>) -> Ordering + 'a>;
+
+pub struct SortExecutor<'a, T: BufferPoolManager + ?Sized> {
+    child: BoxedExecutor<'a>,
+    keys: Rc<Vec<SortKey>>,
+    /// The schema of the encoded keys.
+    key_schema: Rc<Schema>,
+    temp: &'a T,
+    work_mem: usize,
+    sorted: Option<SortedEntries<'a, Vec<u8>, T, EntryOrder<'a>>>,
+}
+
+impl<'a, T: BufferPoolManager + ?Sized> SortExecutor<'a, T> {
+    /// Sort by `keys`, spilling to `temp` beyond `work_mem` tuples.
+    pub fn new(child: BoxedExecutor<'a>, keys: Vec<SortKey>, temp: &'a T, work_mem: usize) -> Self {
+        let key_schema = Schema::new(
+            keys.iter()
+                .enumerate()
+                .map(|(i, key)| Column::new(format!("key{i}"), key.expr.data_type))
+                .collect(),
+        );
+        Self {
+            child,
+            keys: Rc::new(keys),
+            key_schema: Rc::new(key_schema),
+            temp,
+            work_mem,
+            sorted: None,
+        }
+    }
+
+    fn sort(&mut self) -> anyhow::Result<SortedEntries<'a, Vec<u8>, T, EntryOrder<'a>>> {
+        let (keys, key_schema) = (self.keys.clone(), self.key_schema.clone());
+        let order: EntryOrder<'a> = Box::new(move |a, b| compare_entries(&keys, &key_schema, a, b));
+        let mut sorter = ExternalSorter::new(self.temp, self.work_mem, order);
+        while let Some(tuple) = self.child.next()? {
+            let key = self
+                .keys
+                .iter()
+                .map(|key| evaluate(&key.expr, tuple.values()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let key = Tuple::new(key).to_bytes(&self.key_schema)?;
+            let mut entry = (key.len() as u32).to_le_bytes().to_vec();
+            entry.extend(key);
+            entry.extend(tuple.to_bytes(self.child.schema())?);
+            sorter.push(entry)?;
+        }
+        sorter.finish()
+    }
+}
+
+impl<T: BufferPoolManager + ?Sized> Executor for SortExecutor<'_, T> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.sorted = None;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.sorted.is_none() {
+            self.sorted = Some(self.sort()?);
+        }
+        let Some(entry) = self.sorted.as_mut().unwrap().next().transpose()? else {
+            return Ok(None);
+        };
+        let (_, tuple) = split_entry(&entry);
+        Ok(Some(Tuple::from_bytes(self.child.schema(), tuple)?))
+    }
+
+    fn schema(&self) -> &Schema {
+        self.child.schema()
+    }
+}
+
+/// The keys and the tuple of a sorted entry.
+fn split_entry(entry: &[u8]) -> (&[u8], &[u8]) {
+    let len = u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize;
+    entry[4..].split_at(len)
+}
+
+fn compare_entries(keys: &[SortKey], key_schema: &Schema, a: &[u8], b: &[u8]) -> Ordering {
+    // The entries were encoded by the executor, so they decode.
+    let a = TupleRef::new(key_schema, split_entry(a).0).unwrap();
+    let b = TupleRef::new(key_schema, split_entry(b).0).unwrap();
+    for (i, key) in keys.iter().enumerate() {
+        let (a, b) = (a.value(i).unwrap(), b.value(i).unwrap());
+        let order = match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => compare(&a, &b).unwrap_or(Ordering::Equal),
+        };
+        let order = if key.descending {
+            order.reverse()
+        } else {
+            order
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::TempBufferPool,
+        planner::expr::Expr,
+        storage::{
+            disk::{DiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::{super::values::ValuesExecutor, *};
+
+    #[test]
+    fn test_sort() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.tmp")).unwrap();
+        let temp = TempBufferPool::new(4, disk_manager);
+        let schema = Schema::new(vec![
+            Column::new("a", DataType::Int32),
+            Column::new("b", DataType::Varchar(None)),
+        ]);
+        let rows: Vec<Vec<Value>> = (0..500)
+            .map(|i| {
+                let a = if i % 50 == 0 {
+                    Value::Null
+                } else {
+                    Value::Int32(i % 7)
+                };
+                vec![a, Value::Varchar(format!("{:03}", (i * 37) % 500))]
+            })
+            .collect();
+        let keys = vec![
+            SortKey {
+                expr: Expr::column(0, DataType::Int32),
+                descending: true,
+            },
+            SortKey {
+                expr: Expr::column(1, DataType::Varchar(None)),
+                descending: false,
+            },
+        ];
+        // Few enough tuples in memory to spill runs.
+        let exprs = rows
+            .iter()
+            .map(|row| {
+                let types = [DataType::Int32, DataType::Varchar(None)];
+                row.iter()
+                    .zip(types)
+                    .map(|(value, data_type)| Expr::literal(value.clone(), data_type))
+                    .collect()
+            })
+            .collect();
+        let values = ValuesExecutor::new(exprs, schema);
+        let mut sort = SortExecutor::new(Box::new(values), keys, &temp, 16);
+        let mut expected = rows;
+        expected.sort_by(|a, b| {
+            let a_key = a[0].as_i64().unwrap_or(i64::MAX);
+            let b_key = b[0].as_i64().unwrap_or(i64::MAX);
+            b_key
+                .cmp(&a_key)
+                .then_with(|| compare(&a[1], &b[1]).unwrap())
+        });
+        for _ in 0..2 {
+            sort.init().unwrap();
+            let mut sorted = Vec::new();
+            while let Some(tuple) = sort.next().unwrap() {
+                sorted.push(tuple.into_values());
+            }
+            assert_eq!(sorted, expected);
+        }
+    }
+}
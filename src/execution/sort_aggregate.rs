@@ -0,0 +1,237 @@
+//! Aggregation of an input that is already sorted on its group-by expressions.
+//!
+//! Rows of one group are adjacent, so only the current group is kept and it is output as soon
+//! as a row of another group arrives. This needs no memory beyond one group and keeps the input
+//! order, but supports a single grouping set of all the group-by expressions. Without group-by
+//! expressions the whole input is one group, which is output even if the input is empty.
+
+use crate::{
+    catalog::schema::Schema,
+    planner::expr::{AggregateExpr, Expr},
+    storage::table::tuple::Tuple,
+};
+
+use super::{
+    aggregate::{group_values, Group},
+    executor::{BoxedExecutor, Executor},
+};
+
+pub struct SortAggregateExecutor<'a> {
+    input: BoxedExecutor<'a>,
+    group_by: Vec<Expr>,
+    /// Every position of `group_by`.
+    set: Vec<usize>,
+    aggregates: Vec<AggregateExpr>,
+    schema: Schema,
+    /// The group of the last input row with its key.
+    current: Option<(Vec<u8>, Group)>,
+    /// Whether any group has been output.
+    emitted: bool,
+    done: bool,
+}
+
+impl<'a> SortAggregateExecutor<'a> {
+    /// Aggregate `input`, whose rows with equal `group_by` values must be adjacent.
+    pub fn new(
+        input: BoxedExecutor<'a>,
+        group_by: Vec<Expr>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    ) -> Self {
+        let set = (0..group_by.len()).collect();
+        Self {
+            input,
+            group_by,
+            set,
+            aggregates,
+            schema,
+            current: None,
+            emitted: false,
+            done: false,
+        }
+    }
+}
+
+impl Executor for SortAggregateExecutor<'_> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.input.init()?;
+        self.current = None;
+        self.emitted = false;
+        self.done = false;
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.done {
+            return Ok(None);
+        }
+        while let Some(tuple) = self.input.next()? {
+            let (key, values) = group_values(&self.group_by, 0, &self.set, tuple.values())?;
+            let finished = match &mut self.current {
+                Some((current_key, group)) if *current_key == key => {
+                    group.update(&self.aggregates, tuple.values())?;
+                    continue;
+                }
+                current => current.take(),
+            };
+            let mut group = Group::new(values, &self.aggregates);
+            group.update(&self.aggregates, tuple.values())?;
+            self.current = Some((key, group));
+            if let Some((_, finished)) = finished {
+                self.emitted = true;
+                return finished.finish(&self.aggregates).map(Some);
+            }
+        }
+
+        self.done = true;
+        match self.current.take() {
+            Some((_, group)) => group.finish(&self.aggregates).map(Some),
+            None if self.group_by.is_empty() && !self.emitted => {
+                Group::new(Vec::new(), &self.aggregates)
+                    .finish(&self.aggregates)
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        catalog::schema::Column,
+        execution::values::ValuesExecutor,
+        planner::expr::AggregateFunction,
+        sql::ast::BinaryOp,
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::*;
+
+    /// Rows `(g, h, v)`, where `v` is a value or an expression that fails to evaluate.
+    fn input(rows: &[(Option<i64>, i64, Option<Expr>)]) -> BoxedExecutor<'static> {
+        let rows = rows
+            .iter()
+            .map(|(g, h, v)| {
+                vec![
+                    Expr::literal(g.map_or(Value::Null, Value::Int64), DataType::Int64),
+                    Expr::literal(Value::Int64(*h), DataType::Int64),
+                    v.clone()
+                        .unwrap_or(Expr::literal(Value::Null, DataType::Int64)),
+                ]
+            })
+            .collect();
+        let schema = Schema::new(vec![
+            Column::new("g", DataType::Int64),
+            Column::new("h", DataType::Int64),
+            Column::new("v", DataType::Int64),
+        ]);
+        Box::new(ValuesExecutor::new(rows, schema))
+    }
+
+    fn int(v: i64) -> Option<Expr> {
+        Some(Expr::literal(Value::Int64(v), DataType::Int64))
+    }
+
+    /// `count(v)` and `sum(v)` grouped by the first `group_by` columns.
+    fn sort_aggregate(input: BoxedExecutor, group_by: usize) -> SortAggregateExecutor {
+        let v = Expr::column(2, DataType::Int64);
+        let aggregates: Vec<AggregateExpr> = [AggregateFunction::Count, AggregateFunction::Sum]
+            .into_iter()
+            .map(|func| AggregateExpr {
+                func,
+                arg: Some(v.clone()),
+                distinct: false,
+                data_type: DataType::Int64,
+            })
+            .collect();
+        let group_by: Vec<Expr> = (0..group_by)
+            .map(|i| Expr::column(i, DataType::Int64))
+            .collect();
+        let mut columns: Vec<Column> = ["g", "h"][..group_by.len()]
+            .iter()
+            .map(|name| Column::new(*name, DataType::Int64))
+            .collect();
+        columns.push(Column::new("count", DataType::Int64));
+        columns.push(Column::new("sum", DataType::Int64));
+        SortAggregateExecutor::new(input, group_by, aggregates, Schema::new(columns))
+    }
+
+    fn next(executor: &mut SortAggregateExecutor) -> Option<String> {
+        let tuple = executor.next().unwrap()?;
+        let values: Vec<String> = tuple.values().iter().map(Value::to_string).collect();
+        Some(values.join(" "))
+    }
+
+    fn collect(mut executor: SortAggregateExecutor) -> Vec<String> {
+        executor.init().unwrap();
+        std::iter::from_fn(|| next(&mut executor)).collect()
+    }
+
+    #[test]
+    fn test_sort_aggregate() {
+        let rows = [
+            (None, 1, int(5)),
+            (None, 1, None),
+            (Some(1), 1, int(1)),
+            (Some(1), 2, int(2)),
+            (Some(1), 2, int(3)),
+            (Some(2), 1, None),
+        ];
+        // Groups come out in input order, and the NULL keys form one group.
+        assert_eq!(
+            collect(sort_aggregate(input(&rows), 1)),
+            ["NULL 1 5", "1 3 6", "2 0 NULL"]
+        );
+        assert_eq!(
+            collect(sort_aggregate(input(&rows), 2)),
+            ["NULL 1 1 5", "1 1 1 1", "1 2 2 5", "2 1 0 NULL"]
+        );
+        assert_eq!(collect(sort_aggregate(input(&rows), 0)), ["4 11"]);
+
+        // Only adjacent rows are grouped, as the input is taken to be sorted.
+        let unsorted = [
+            (Some(1), 0, int(1)),
+            (Some(2), 0, int(2)),
+            (Some(1), 0, int(3)),
+        ];
+        assert_eq!(
+            collect(sort_aggregate(input(&unsorted), 1)),
+            ["1 1 1", "2 1 2", "1 1 3"]
+        );
+
+        // An empty input has no groups, unless there are no group-by expressions.
+        assert!(collect(sort_aggregate(input(&[]), 1)).is_empty());
+        assert_eq!(collect(sort_aggregate(input(&[]), 0)), ["0 NULL"]);
+    }
+
+    #[test]
+    fn test_sort_aggregate_streams() {
+        // The last row fails to evaluate, but the groups before it are complete by the time it
+        // is read, and each is output as soon as the next one starts.
+        let division = Expr::binary(
+            BinaryOp::Divide,
+            Expr::literal(Value::Int64(1), DataType::Int64),
+            Expr::literal(Value::Int64(0), DataType::Int64),
+            DataType::Int64,
+        );
+        let rows = [
+            (Some(1), 0, int(1)),
+            (Some(1), 0, int(2)),
+            (Some(2), 0, int(3)),
+            (Some(3), 0, int(4)),
+            (Some(3), 0, Some(division)),
+        ];
+        let mut executor = sort_aggregate(input(&rows), 1);
+        for _ in 0..2 {
+            executor.init().unwrap();
+            assert_eq!(next(&mut executor).unwrap(), "1 2 3");
+            assert_eq!(next(&mut executor).unwrap(), "2 1 3");
+            assert!(executor.next().is_err());
+        }
+    }
+}
@@ -0,0 +1,447 @@
+//! A sort-merge join.
+//!
+//! Both inputs are sorted by their [`join_key`] with an [`ExternalSorter`], which spills runs
+//! to temporary pages beyond `work_mem` tuples, and are then merged. Keys are ordered by their
+//! encoding rather than as SQL values, which keeps equal keys together just as well. The right
+//! tuples that share the key of the current left tuple are kept in memory, so that every left
+//! tuple with that key can be joined with all of them.
+//!
+//! Each sorted entry is the key followed by the encoded tuple:
+//!
+//! | offset | size      | field                                    |
+//! |--------|-----------|------------------------------------------|
+//! | 0      | 4         | key length, or `u32::MAX` for a NULL key |
+//! | 4      | key len   | key                                      |
+//! | ...    | remainder | tuple                                    |
+//!
+//! NULL keys sort first, and their tuples never match.
+
+use std::{cmp::Ordering, collections::VecDeque};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::schema::Schema,
+    planner::{
+        expr::Expr,
+        logical_plan::{join_schema, JoinType},
+    },
+    storage::{
+        external_sort::{ExternalSorter, SortedEntries},
+        table::tuple::Tuple,
+    },
+};
+
+use super::{
+    executor::{BoxedExecutor, Executor},
+    join::{condition_holds, join_key, joined, unmatched_left, unmatched_right, EquiJoinKeys},
+};
+
+type EntryOrder = fn(&Vec<u8>, &Vec<u8>) -> Ordering;
+type SortedInput<'a, T> = SortedEntries<'a, Vec<u8>, T, EntryOrder>;
+
+const NULL_KEY: u32 = u32::MAX;
+
+pub struct SortMergeJoinExecutor<'a, T: BufferPoolManager + ?Sized> {
+    left: BoxedExecutor<'a>,
+    right: BoxedExecutor<'a>,
+    join_type: JoinType,
+    keys: EquiJoinKeys,
+    schema: Schema,
+    temp: &'a T,
+    work_mem: usize,
+    sorted: Option<(SortedInput<'a, T>, SortedInput<'a, T>)>,
+    /// The next tuple of each sorted input with its key.
+    left_peek: Option<(Option<Vec<u8>>, Tuple)>,
+    right_peek: Option<(Option<Vec<u8>>, Tuple)>,
+    /// The right tuples with the key of the last left tuple, and whether they have matched.
+    group: Vec<(Tuple, bool)>,
+    group_key: Option<Vec<u8>>,
+    pending: VecDeque<Tuple>,
+}
+
+impl<'a, T: BufferPoolManager + ?Sized> SortMergeJoinExecutor<'a, T> {
+    /// Join by the equalities of `keys`, sorting both inputs with `temp` for spilled runs.
+    pub fn new(
+        left: BoxedExecutor<'a>,
+        right: BoxedExecutor<'a>,
+        join_type: JoinType,
+        keys: EquiJoinKeys,
+        temp: &'a T,
+        work_mem: usize,
+    ) -> Self {
+        let schema = join_schema(left.schema(), right.schema(), join_type);
+        Self {
+            left,
+            right,
+            join_type,
+            keys,
+            schema,
+            temp,
+            work_mem,
+            sorted: None,
+            left_peek: None,
+            right_peek: None,
+            group: Vec::new(),
+            group_key: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Advance the sorted left input.
+    fn advance_left(&mut self) -> anyhow::Result<()> {
+        let (left, _) = self.sorted.as_mut().unwrap();
+        self.left_peek = left
+            .next()
+            .transpose()?
+            .map(|entry| decode_entry(self.left.schema(), &entry))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// Advance the sorted right input.
+    fn advance_right(&mut self) -> anyhow::Result<()> {
+        let (_, right) = self.sorted.as_mut().unwrap();
+        self.right_peek = right
+            .next()
+            .transpose()?
+            .map(|entry| decode_entry(self.right.schema(), &entry))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// Queue the right tuples of the group that did not match, and forget the group.
+    fn flush_group(&mut self) {
+        let left_width = self.left.schema().column_count();
+        for (right, matched) in self.group.drain(..) {
+            if !matched && self.join_type.keeps_unmatched_right() {
+                self.pending.push_back(unmatched_right(left_width, right));
+            }
+        }
+        self.group_key = None;
+    }
+
+    /// Queue the output for a left tuple with the key of the group.
+    fn join_group(&mut self, left: Tuple) -> anyhow::Result<()> {
+        let mut matched = false;
+        for (right, right_matched) in &mut self.group {
+            if !condition_holds(self.keys.residual.as_ref(), &left, right)? {
+                continue;
+            }
+            matched = true;
+            if !self.join_type.outputs_right() {
+                break;
+            }
+            *right_matched = true;
+            self.pending.push_back(joined(&left, right.clone()));
+        }
+        if matched && self.join_type == JoinType::Semi {
+            self.pending.push_back(left);
+        } else if !matched {
+            self.queue_unmatched_left(left);
+        }
+        Ok(())
+    }
+
+    fn queue_unmatched_left(&mut self, left: Tuple) {
+        if self.join_type.keeps_unmatched_left() {
+            let right_width = self.right.schema().column_count();
+            self.pending
+                .push_back(unmatched_left(self.join_type, left, right_width));
+        }
+    }
+
+    fn queue_unmatched_right(&mut self, right: Tuple) {
+        if self.join_type.keeps_unmatched_right() {
+            let left_width = self.left.schema().column_count();
+            self.pending.push_back(unmatched_right(left_width, right));
+        }
+    }
+}
+
+impl<T: BufferPoolManager + ?Sized> Executor for SortMergeJoinExecutor<'_, T> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.left.init()?;
+        self.right.init()?;
+        let left = sort(&mut self.left, &self.keys.left, self.temp, self.work_mem)?;
+        let right = sort(&mut self.right, &self.keys.right, self.temp, self.work_mem)?;
+        self.sorted = Some((left, right));
+        self.group.clear();
+        self.group_key = None;
+        self.pending.clear();
+        self.advance_left()?;
+        self.advance_right()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        loop {
+            if let Some(tuple) = self.pending.pop_front() {
+                return Ok(Some(tuple));
+            }
+            let Some((left_key, _)) = &self.left_peek else {
+                // The remaining right tuples have no match.
+                if !self.join_type.keeps_unmatched_right() {
+                    return Ok(None);
+                }
+                if self.group_key.is_some() {
+                    self.flush_group();
+                    continue;
+                }
+                let Some((_, right)) = self.right_peek.take() else {
+                    return Ok(None);
+                };
+                self.advance_right()?;
+                self.queue_unmatched_right(right);
+                continue;
+            };
+            let Some(left_key) = left_key.clone() else {
+                let (_, left) = self.left_peek.take().unwrap();
+                self.advance_left()?;
+                self.queue_unmatched_left(left);
+                continue;
+            };
+
+            if self.group_key.as_ref() == Some(&left_key) {
+                let (_, left) = self.left_peek.take().unwrap();
+                self.advance_left()?;
+                self.join_group(left)?;
+                continue;
+            }
+            if self.group_key.is_some() {
+                self.flush_group();
+                continue;
+            }
+            let right_order = match &self.right_peek {
+                None => Ordering::Greater,
+                Some((None, _)) => Ordering::Less,
+                Some((Some(right_key), _)) => right_key.cmp(&left_key),
+            };
+            match right_order {
+                Ordering::Less => {
+                    let (_, right) = self.right_peek.take().unwrap();
+                    self.advance_right()?;
+                    self.queue_unmatched_right(right);
+                }
+                Ordering::Greater => {
+                    let (_, left) = self.left_peek.take().unwrap();
+                    self.advance_left()?;
+                    self.queue_unmatched_left(left);
+                }
+                Ordering::Equal => {
+                    while let Some((Some(right_key), _)) = &self.right_peek {
+                        if *right_key != left_key {
+                            break;
+                        }
+                        let (_, right) = self.right_peek.take().unwrap();
+                        self.group.push((right, false));
+                        self.advance_right()?;
+                    }
+                    self.group_key = Some(left_key);
+                }
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// Sort the tuples of `input` by the keys `exprs`.
+fn sort<'a, T: BufferPoolManager + ?Sized>(
+    input: &mut BoxedExecutor<'a>,
+    exprs: &[Expr],
+    temp: &'a T,
+    work_mem: usize,
+) -> anyhow::Result<SortedInput<'a, T>> {
+    let order: EntryOrder = |a, b| entry_key(a).cmp(&entry_key(b));
+    let mut sorter = ExternalSorter::new(temp, work_mem, order);
+    while let Some(tuple) = input.next()? {
+        let key = join_key(exprs, tuple.values())?;
+        let mut entry = Vec::new();
+        match &key {
+            Some(key) => {
+                entry.extend((key.len() as u32).to_le_bytes());
+                entry.extend(key);
+            }
+            None => entry.extend(NULL_KEY.to_le_bytes()),
+        }
+        entry.extend(tuple.to_bytes(input.schema())?);
+        sorter.push(entry)?;
+    }
+    sorter.finish()
+}
+
+/// The key of a sorted entry, None if it is NULL.
+fn entry_key(entry: &[u8]) -> Option<&[u8]> {
+    let len = u32::from_le_bytes(entry[..4].try_into().unwrap());
+    (len != NULL_KEY).then(|| &entry[4..4 + len as usize])
+}
+
+fn decode_entry(schema: &Schema, entry: &[u8]) -> anyhow::Result<(Option<Vec<u8>>, Tuple)> {
+    let key = entry_key(entry);
+    let offset = 4 + key.map_or(0, |key| key.len());
+    Ok((
+        key.map(|key| key.to_vec()),
+        Tuple::from_bytes(schema, &entry[offset..])?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::TempBufferPool,
+        execution::nested_loop_join::NestedLoopJoinExecutor,
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::{
+        super::join::test_util::{collect, input, rows},
+        *,
+    };
+
+    const JOIN_TYPES: [JoinType; 6] = [
+        JoinType::Inner,
+        JoinType::Left,
+        JoinType::Right,
+        JoinType::Full,
+        JoinType::Semi,
+        JoinType::Anti,
+    ];
+
+    /// The condition `l.k = r.k`, with `residual` if any.
+    fn condition(residual: Option<Expr>) -> Expr {
+        let equal = Expr::binary(
+            BinaryOp::Eq,
+            Expr::column(0, DataType::Int64),
+            Expr::column(2, DataType::Int64),
+            DataType::Boolean,
+        );
+        Expr::conjunction([Some(equal), residual].into_iter().flatten().collect()).unwrap()
+    }
+
+    fn temp_pool(tempdir: &tempfile::TempDir) -> TempBufferPool {
+        let disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.tmp")).unwrap();
+        TempBufferPool::new(8, disk_manager)
+    }
+
+    #[test]
+    fn test_sort_merge_join() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let temp = temp_pool(&tempdir);
+        let l = rows(&[
+            (Some(1), "l1"),
+            (Some(2), "l2"),
+            (Some(2), "l2b"),
+            (None, "ln"),
+            (Some(4), "l4"),
+        ]);
+        let r = rows(&[
+            (Some(2), "r2"),
+            (Some(2), "r2b"),
+            (Some(3), "r3"),
+            (None, "rn"),
+            (Some(1), "r1"),
+        ]);
+        let keys = EquiJoinKeys::split(Some(&condition(None)), 2);
+
+        let inner = [
+            "1 l1 1 r1",
+            "2 l2 2 r2",
+            "2 l2 2 r2b",
+            "2 l2b 2 r2",
+            "2 l2b 2 r2b",
+        ];
+        let unmatched_left = ["4 l4 NULL NULL", "NULL ln NULL NULL"];
+        let unmatched_right = ["NULL NULL 3 r3", "NULL NULL NULL rn"];
+        for join_type in JOIN_TYPES {
+            let mut expected = match join_type {
+                JoinType::Inner => inner.to_vec(),
+                JoinType::Left => [&inner[..], &unmatched_left].concat(),
+                JoinType::Right => [&inner[..], &unmatched_right].concat(),
+                JoinType::Full => [&inner[..], &unmatched_left, &unmatched_right].concat(),
+                JoinType::Semi => vec!["1 l1", "2 l2", "2 l2b"],
+                JoinType::Anti => vec!["4 l4", "NULL ln"],
+            };
+            expected.sort();
+            let mut join = SortMergeJoinExecutor::new(
+                input(&l),
+                input(&r),
+                join_type,
+                keys.clone(),
+                &temp,
+                16,
+            );
+            assert_eq!(collect(&mut join), expected, "{join_type:?}");
+        }
+
+        // A residual condition splits a group of equal keys: r2b matches no left tuple, and l2b
+        // no right tuple.
+        let residual = Expr::binary(
+            BinaryOp::Lt,
+            Expr::column(1, DataType::Varchar(None)),
+            Expr::column(3, DataType::Varchar(None)),
+            DataType::Boolean,
+        );
+        let keys = EquiJoinKeys::split(Some(&condition(Some(residual))), 2);
+        assert!(keys.residual.is_some());
+        let l = rows(&[(Some(2), "b"), (Some(2), "d")]);
+        let r = rows(&[(Some(2), "c"), (Some(2), "a")]);
+        for (join_type, expected) in [
+            (
+                JoinType::Full,
+                vec!["2 b 2 c", "2 d NULL NULL", "NULL NULL 2 a"],
+            ),
+            (JoinType::Semi, vec!["2 b"]),
+            (JoinType::Anti, vec!["2 d"]),
+        ] {
+            let mut join = SortMergeJoinExecutor::new(
+                input(&l),
+                input(&r),
+                join_type,
+                keys.clone(),
+                &temp,
+                16,
+            );
+            assert_eq!(collect(&mut join), expected, "{join_type:?}");
+        }
+    }
+
+    #[test]
+    fn test_spilled_sort_merge_join() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let temp = temp_pool(&tempdir);
+        let key = |i: i64, modulus: i64| (i % 11 != 0).then_some(i % modulus);
+        let l: Vec<_> = (0..300).map(|i| (key(i, 40), format!("l{i}"))).collect();
+        let r: Vec<_> = (0..200).map(|i| (key(i, 60), format!("r{i}"))).collect();
+        let residual = Expr::binary(
+            BinaryOp::NotEq,
+            Expr::column(3, DataType::Varchar(None)),
+            Expr::literal(Value::Varchar("r7".into()), DataType::Varchar(None)),
+            DataType::Boolean,
+        );
+        let condition = condition(Some(residual));
+        let keys = EquiJoinKeys::split(Some(&condition), 2);
+
+        // Sorting with room for only a few tuples spills runs, which must not change the
+        // output of any join type.
+        for join_type in JOIN_TYPES {
+            let expected = collect(&mut NestedLoopJoinExecutor::new(
+                input(&l),
+                input(&r),
+                join_type,
+                Some(condition.clone()),
+            ));
+            assert!(!expected.is_empty());
+            let mut join =
+                SortMergeJoinExecutor::new(input(&l), input(&r), join_type, keys.clone(), &temp, 4);
+            assert_eq!(collect(&mut join), expected, "{join_type:?}");
+        }
+        assert!(temp.disk_manager().num_pages() > 0);
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{catalog::TableInfo, schema::Schema},
+    planner::expr::Expr,
+    planner::logical_plan::modification_schema,
+    storage::table::{record_id::RecordId, table_heap::TableHeap, tuple::Tuple},
+    types::value::Value,
+};
+
+use super::{
+    eval::evaluate,
+    executor::{BoxedExecutor, Executor},
+    index_maintenance::IndexMaintenance,
+};
+
+/// Updates the rows its child reads from a table, then produces the number of updated rows.
+///
+/// A new version that does not fit into its page moves to another one, where a scan below
+/// could meet it again; the record ids of new versions are remembered so that no row is
+/// updated twice.
+pub struct UpdateExecutor<'a, B: BufferPoolManager + ?Sized> {
+    child: BoxedExecutor<'a>,
+    table: Arc<TableInfo>,
+    heap: TableHeap<'a, B>,
+    indexes: IndexMaintenance<'a>,
+    /// The column each expression, evaluated over the old row, is stored into.
+    assignments: Vec<(usize, Expr)>,
+    updated: HashSet<RecordId>,
+    schema: Schema,
+    done: bool,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> UpdateExecutor<'a, B> {
+    pub fn new(
+        child: BoxedExecutor<'a>,
+        table: Arc<TableInfo>,
+        heap: TableHeap<'a, B>,
+        indexes: IndexMaintenance<'a>,
+        assignments: Vec<(usize, Expr)>,
+    ) -> Self {
+        Self {
+            child,
+            table,
+            heap,
+            indexes,
+            assignments,
+            updated: HashSet::new(),
+            schema: modification_schema(),
+            done: false,
+        }
+    }
+
+    /// Update the row `rid`, returning false if it has been deleted meanwhile.
+    fn update(&mut self, old: &[Value], rid: RecordId) -> anyhow::Result<bool> {
+        let mut new = old.to_vec();
+        for (column, expr) in &self.assignments {
+            new[*column] = evaluate(expr, old)?;
+        }
+        let data = Tuple::new(new.clone()).to_bytes(&self.table.schema)?;
+        self.indexes.check_unique(&new, Some(rid))?;
+
+        let new_rid = if self.heap.update_tuple(rid, &data)? {
+            rid
+        } else {
+            if !self.heap.mark_delete(rid)? {
+                return Ok(false);
+            }
+            self.heap.insert_tuple(&data)?
+        };
+        self.indexes.update(old, rid, &new, new_rid)?;
+        self.updated.insert(new_rid);
+        Ok(true)
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Executor for UpdateExecutor<'_, B> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.done = false;
+        self.updated.clear();
+        self.child.init()
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+
+        let mut count = 0;
+        while let Some(tuple) = self.child.next()? {
+            let Some(rid) = tuple.rid() else {
+                anyhow::bail!("UPDATE input has no record ids");
+            };
+            if self.updated.contains(&rid) {
+                continue;
+            }
+            if self.update(tuple.values(), rid)? {
+                count += 1;
+            }
+        }
+
+        Ok(Some(Tuple::new(vec![Value::Int64(count)])))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::catalog::Catalog,
+        execution::filter::FilterExecutor,
+        sql::ast::BinaryOp,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::{
+        super::index_maintenance::tests::{
+            check_indexes, create_table, lookup, rows, run, scan, Row,
+        },
+        *,
+    };
+
+    /// `UPDATE t SET <assignments> WHERE <column> <op> <value>`.
+    fn update(
+        catalog: &Catalog<BufferPoolManagerImpl>,
+        table: &Arc<TableInfo>,
+        assignments: Vec<(usize, Expr)>,
+        (column, op, value): (usize, BinaryOp, i64),
+    ) -> anyhow::Result<i64> {
+        let predicate = Expr::binary(
+            op,
+            Expr::column(column, DataType::Int64),
+            Expr::literal(Value::Int64(value), DataType::Int64),
+            DataType::Boolean,
+        );
+        let child = FilterExecutor::new(scan(catalog, table), predicate);
+        let indexes = IndexMaintenance::open(catalog, table)?;
+        let heap = catalog.table_heap(table)?;
+        run(UpdateExecutor::new(
+            Box::new(child),
+            table.clone(),
+            heap,
+            indexes,
+            assignments,
+        ))
+    }
+
+    fn int(value: Option<i64>) -> Expr {
+        Expr::literal(value.map_or(Value::Null, Value::Int64), DataType::Int64)
+    }
+
+    #[test]
+    fn test_update() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let names: Vec<String> = (1..=20).map(|id| format!("n{id}")).collect();
+        let initial: Vec<Row> = (1..=20)
+            .map(|id| {
+                (
+                    id,
+                    Some(id % 3),
+                    Some(id * 10),
+                    names[id as usize - 1].as_str(),
+                )
+            })
+            .collect();
+        let table = create_table(&catalog, &initial);
+
+        let count = update(
+            &catalog,
+            &table,
+            vec![(1, int(Some(5)))],
+            (0, BinaryOp::LtEq, 3),
+        );
+        assert_eq!(count.unwrap(), 3);
+        let stored = rows(&catalog, &table);
+        let rids: Vec<RecordId> = stored[..3].iter().map(|(rid, _)| *rid).collect();
+        assert_eq!(lookup(&catalog, "t_grp", &[Value::Int64(5)]), rids);
+        assert_eq!(lookup(&catalog, "t_grp", &[Value::Int64(1)]).len(), 6);
+        check_indexes(&catalog, &table);
+
+        // Rows that grow out of their page move, and are still updated only once.
+        let long = Expr::literal(Value::Varchar("x".repeat(600)), DataType::Varchar(None));
+        let count = update(&catalog, &table, vec![(3, long)], (0, BinaryOp::Gt, 0));
+        assert_eq!(count.unwrap(), 20);
+        let moved = rows(&catalog, &table);
+        assert_eq!(moved.len(), 20);
+        assert!(moved.iter().all(|(_, row)| row[3].to_string().len() == 600));
+        assert!(moved.iter().any(|(rid, _)| !rids.contains(rid)));
+        check_indexes(&catalog, &table);
+
+        // A unique violation leaves the row and every index as they were.
+        for assignments in [
+            vec![(1, int(Some(7))), (2, int(Some(10)))],
+            vec![(0, int(Some(1))), (1, int(Some(7)))],
+        ] {
+            let err = update(&catalog, &table, assignments, (0, BinaryOp::Eq, 2)).unwrap_err();
+            assert!(err.to_string().contains("unique constraint"), "{err}");
+            assert_eq!(rows(&catalog, &table), moved);
+            assert!(lookup(&catalog, "t_grp", &[Value::Int64(7)]).is_empty());
+            check_indexes(&catalog, &table);
+        }
+
+        // Setting a key to NULL takes the row out of the index.
+        let count = update(&catalog, &table, vec![(1, int(None))], (1, BinaryOp::Eq, 0));
+        assert_eq!(count.unwrap(), 5);
+        assert!(lookup(&catalog, "t_grp", &[Value::Int64(0)]).is_empty());
+        check_indexes(&catalog, &table);
+    }
+}
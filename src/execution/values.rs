@@ -0,0 +1,105 @@
+use crate::{
+    catalog::schema::Schema, planner::expr::Expr, storage::table::tuple::Tuple, types::value::Value,
+};
+
+use super::{eval::evaluate, executor::Executor};
+
+/// Produces rows of constant expressions, as in `VALUES (1, 'a'), (2, 'b')`.
+pub struct ValuesExecutor {
+    rows: Vec<Vec<Expr>>,
+    schema: Schema,
+    next_row: usize,
+}
+
+impl ValuesExecutor {
+    pub fn new(rows: Vec<Vec<Expr>>, schema: Schema) -> Self {
+        Self {
+            rows,
+            schema,
+            next_row: 0,
+        }
+    }
+}
+
+impl Executor for ValuesExecutor {
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.next_row = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> anyhow::Result<Option<Tuple>> {
+        let Some(row) = self.rows.get(self.next_row) else {
+            return Ok(None);
+        };
+        self.next_row += 1;
+        let values = row
+            .iter()
+            .map(|expr| evaluate(expr, &[]))
+            .collect::<anyhow::Result<Vec<Value>>>()?;
+
+        Ok(Some(Tuple::new(values)))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{catalog::schema::Column, sql::ast::BinaryOp, types::data_type::DataType};
+
+    use super::*;
+
+    #[test]
+    fn test_values() {
+        let schema = Schema::new(vec![
+            Column::new("a", DataType::Int64),
+            Column::new("b", DataType::Varchar(None)),
+        ]);
+        let sum = Expr::binary(
+            BinaryOp::Plus,
+            Expr::literal(Value::Int64(1), DataType::Int64),
+            Expr::literal(Value::Int64(2), DataType::Int64),
+            DataType::Int64,
+        );
+        let rows = vec![
+            vec![
+                sum,
+                Expr::literal(Value::Varchar("x".into()), DataType::Varchar(None)),
+            ],
+            vec![
+                Expr::literal(Value::Null, DataType::Int64),
+                Expr::literal(Value::Varchar("y".into()), DataType::Varchar(None)),
+            ],
+        ];
+        let mut values = ValuesExecutor::new(rows, schema.clone());
+        assert_eq!(values.schema(), &schema);
+        // Every init starts over from the first row.
+        for _ in 0..2 {
+            values.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = values.next().unwrap() {
+                assert!(tuple.rid().is_none());
+                rows.push(tuple.into_values());
+            }
+            assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Int64(3), Value::Varchar("x".into())],
+                    vec![Value::Null, Value::Varchar("y".into())],
+                ]
+            );
+        }
+
+        let division = Expr::binary(
+            BinaryOp::Divide,
+            Expr::literal(Value::Int64(1), DataType::Int64),
+            Expr::literal(Value::Int64(0), DataType::Int64),
+            DataType::Int64,
+        );
+        let mut values = ValuesExecutor::new(vec![vec![division]], Schema::new(vec![]));
+        values.init().unwrap();
+        assert!(values.next().is_err());
+    }
+}
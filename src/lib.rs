@@ -1,4 +1,20 @@
 pub mod buffer;
+pub mod catalog;
+pub mod database;
+pub mod execution;
+pub mod optimizer;
+pub mod planner;
+pub mod recovery;
+pub mod server;
+pub mod shell;
+pub mod sql;
 pub mod storage;
+pub mod types;
 
+pub use database::{
+    connection::Connection,
+    database::{Database, Options},
+    row::{FromRow, FromValue, Row, Rows},
+    statement::{Params, Statement, ToValue},
+};
 pub use storage::page::page::{Page, PageId};
@@ -1,3 +1,106 @@
-fn main() {
-    println!("Hello, world!");
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader, IsTerminal},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use limebase::{
+    shell::{line_editor::LineEditor, shell::Shell},
+    Database, Options,
+};
+
+const USAGE: &str = "\
+usage: limebase DBFILE [-f FILE]
+
+Open the database DBFILE, creating it if it does not exist, and run SQL statements read from
+FILE, from standard input if it is not a terminal, or interactively. Type .help in the shell
+for its commands.
+";
+
+/// The pages of the database kept in memory.
+const POOL_SIZE: usize = 1024;
+/// The pages of sort runs and spilled hash tables kept in memory.
+const TEMP_POOL_SIZE: usize = 256;
+/// The history of the interactive shell, under the home directory.
+const HISTORY_FILE: &str = ".limebase_history";
+
+struct Args {
+    db_path: PathBuf,
+    script: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprint!("limebase: {message}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("limebase: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The arguments, or None if help was asked for.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut db_path = None;
+    let mut script = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" => match args.next() {
+                Some(path) => script = Some(PathBuf::from(path)),
+                None => return Err("-f needs a file".to_string()),
+            },
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if db_path.is_none() => db_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    let db_path = db_path.ok_or_else(|| "missing the database file".to_string())?;
+    Ok(Some(Args { db_path, script }))
+}
+
+/// Run the shell on the database, returning whether every statement of a script succeeded.
+fn run(args: &Args) -> anyhow::Result<bool> {
+    let options = Options::new()
+        .with_pool_size(POOL_SIZE)
+        .with_temp_pool_size(TEMP_POOL_SIZE);
+    let db = Database::open(&args.db_path, options)?;
+    let catalog = db.catalog();
+    let mut shell = Shell::new(&catalog, db.temp());
+
+    let (mut out, mut err) = (io::stdout(), io::stderr());
+    let ok = if let Some(script) = &args.script {
+        let file = File::open(script)
+            .map_err(|error| anyhow::anyhow!("cannot open {}: {error}", script.display()))?;
+        shell.run_script(BufReader::new(file), &mut out, &mut err)?
+    } else if io::stdin().is_terminal() {
+        let mut editor = LineEditor::new();
+        if let Some(home) = env::var_os("HOME") {
+            editor = editor.with_history_file(Path::new(&home).join(HISTORY_FILE));
+        }
+        println!(
+            "limebase {}. Type .help for help.",
+            env!("CARGO_PKG_VERSION")
+        );
+        shell.run_interactive(&mut editor)?;
+        true
+    } else {
+        shell.run_script(io::stdin().lock(), &mut out, &mut err)?
+    };
+    db.flush()?;
+    Ok(ok)
 }
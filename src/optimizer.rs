@@ -0,0 +1,9 @@
+pub mod constant_folding;
+pub mod cost;
+pub mod decorrelation;
+pub mod index_selection;
+pub mod join_order;
+#[allow(clippy::module_inception)]
+pub mod optimizer;
+pub mod predicate_pushdown;
+pub mod projection_pruning;
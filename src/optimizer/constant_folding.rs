@@ -0,0 +1,109 @@
+//! Evaluates the expressions that read no column once, at planning time.
+//!
+//! A constant expression becomes its value, unless evaluating it fails: then it is left for the
+//! executor, which reports the error only if a row reaches it. `AND` and `OR` with a constant
+//! operand are simplified, and a filter or join condition that is always true is removed.
+
+use crate::{
+    execution::eval::evaluate,
+    planner::{
+        expr::{Expr, ExprKind},
+        logical_plan::LogicalPlan,
+    },
+    sql::ast::BinaryOp,
+    types::value::Value,
+};
+
+pub fn fold_constants(plan: LogicalPlan) -> anyhow::Result<LogicalPlan> {
+    let plan = plan.map_children(&mut fold_constants)?;
+    Ok(match plan.map_exprs(&mut fold_expr) {
+        LogicalPlan::Filter { input, predicate } if is_true(&predicate) => *input,
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            condition: Some(condition),
+        } if is_true(&condition) => LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            condition: None,
+        },
+        plan => plan,
+    })
+}
+
+pub fn fold_expr(expr: Expr) -> Expr {
+    expr.transform_up(&mut |expr| {
+        if let ExprKind::Binary { op, left, right } = &expr.kind {
+            match (op, literal(left), literal(right)) {
+                (BinaryOp::And, Some(Value::Boolean(false)), _)
+                | (BinaryOp::And, _, Some(Value::Boolean(false)))
+                | (BinaryOp::Or, Some(Value::Boolean(true)), _)
+                | (BinaryOp::Or, _, Some(Value::Boolean(true))) => {
+                    let value = *op == BinaryOp::Or;
+                    return Expr::literal(Value::Boolean(value), expr.data_type);
+                }
+                (BinaryOp::And, Some(Value::Boolean(true)), _)
+                | (BinaryOp::Or, Some(Value::Boolean(false)), _) => return *right.clone(),
+                (BinaryOp::And, _, Some(Value::Boolean(true)))
+                | (BinaryOp::Or, _, Some(Value::Boolean(false))) => return *left.clone(),
+                _ => {}
+            }
+        }
+        if matches!(expr.kind, ExprKind::Literal(_)) || !expr.is_constant() {
+            return expr;
+        }
+        match evaluate(&expr, &[]) {
+            Ok(value) => Expr::literal(value, expr.data_type),
+            Err(_) => expr,
+        }
+    })
+}
+
+fn literal(expr: &Expr) -> Option<&Value> {
+    match &expr.kind {
+        ExprKind::Literal(value) => Some(value),
+        _ => None,
+    }
+}
+
+fn is_true(expr: &Expr) -> bool {
+    literal(expr) == Some(&Value::Boolean(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::data_type::DataType;
+
+    use super::*;
+
+    #[test]
+    fn test_fold_expr() {
+        let int = |i| Expr::literal(Value::Int32(i), DataType::Int32);
+        let column = Expr::column(0, DataType::Boolean);
+        // 1 + 2 > 2 AND #0
+        let sum = Expr::binary(BinaryOp::Plus, int(1), int(2), DataType::Int32);
+        let greater = Expr::binary(BinaryOp::Gt, sum, int(2), DataType::Boolean);
+        let and = Expr::binary(BinaryOp::And, greater, column.clone(), DataType::Boolean);
+        assert_eq!(fold_expr(and), column);
+
+        // 1 / 0 fails, so it is left to the executor.
+        let division = Expr::binary(BinaryOp::Divide, int(1), int(0), DataType::Int32);
+        assert_eq!(fold_expr(division.clone()), division);
+
+        let or = Expr::binary(BinaryOp::Or, column, division, DataType::Boolean);
+        let not_null = Expr::new(
+            ExprKind::IsNull {
+                expr: Box::new(int(1)),
+                negated: true,
+            },
+            DataType::Boolean,
+        );
+        let or = Expr::binary(BinaryOp::Or, or, not_null, DataType::Boolean);
+        assert_eq!(
+            fold_expr(or),
+            Expr::literal(Value::Boolean(true), DataType::Boolean)
+        );
+    }
+}
@@ -0,0 +1,843 @@
+//! Estimates of how many rows a plan produces and what it costs to run.
+//!
+//! Costs are counted in pages read through the buffer pool, which dominate once the data does
+//! not fit in memory: a scan reads every page of its table, an index scan descends the index
+//! and then reads one page for every row it fetches, and a nested-loop join reads its right
+//! input again for every left row. A hash join, an aggregation or a sort whose input exceeds
+//! `work_mem` rows writes it to temporary pages and reads it back, which costs twice its
+//! pages. Intermediate results are converted to pages by the estimated width of their rows.
+//!
+//! After `ANALYZE`, the number of rows and distinct values come from the
+//! [statistics](crate::catalog::statistics) of the table. A comparison of a column with a
+//! constant then keeps the rows of the most common values it matches, plus the part of the
+//! histogram it covers, interpolated within a bucket for numbers. `IS NULL` keeps the fraction
+//! of NULLs.
+//!
+//! Without statistics, the size of a table comes from its free-space map, a column has at most
+//! [`DEFAULT_DISTINCT`] values unless a unique index says otherwise, and the selectivity of the
+//! other predicates is a fixed guess, as in PostgreSQL.
+
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, ops::Bound, sync::Arc};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{Catalog, Oid, TableInfo},
+        schema::Schema,
+        statistics::{ColumnStatistics, TableStatistics},
+    },
+    execution::{eval::compare, join::EquiJoinKeys},
+    planner::{
+        expr::{Expr, ExprKind},
+        logical_plan::{JoinType, LogicalPlan},
+    },
+    sql::ast::BinaryOp,
+    storage::{page::page::DEFAULT_PAGE_SIZE, page::slotted_page::SLOT_SIZE},
+    types::value::Value,
+};
+
+/// The number of distinct values assumed for a column without a unique index.
+pub const DEFAULT_DISTINCT: f64 = 200.0;
+/// The selectivity of `<`, `<=`, `>` and `>=`.
+pub const RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+/// The selectivity of `IS NULL`.
+pub const NULL_SELECTIVITY: f64 = 0.005;
+/// The selectivity of `LIKE`.
+pub const LIKE_SELECTIVITY: f64 = 0.1;
+/// The selectivity of predicates the model cannot analyze.
+pub const DEFAULT_SELECTIVITY: f64 = 0.25;
+/// The pages read to descend an index to its first entry.
+pub const INDEX_DESCENT_COST: f64 = 3.0;
+/// The width in bytes assumed for a variable-length value.
+const VARIABLE_WIDTH: usize = 32;
+
+/// The estimated output and cost of a plan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub rows: f64,
+    /// Pages read through the buffer pool.
+    pub cost: f64,
+}
+
+/// The size of a table heap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableSize {
+    pub pages: f64,
+    pub rows: f64,
+}
+
+pub struct CostModel<'c, 'a, B: BufferPoolManager + ?Sized> {
+    catalog: &'c Catalog<'a, B>,
+    work_mem: usize,
+    /// Table sizes already read from the free-space maps.
+    sizes: RefCell<HashMap<Oid, TableSize>>,
+}
+
+impl<'c, 'a, B: BufferPoolManager + ?Sized> CostModel<'c, 'a, B> {
+    /// A model of executors that keep up to `work_mem` rows in memory.
+    pub fn new(catalog: &'c Catalog<'a, B>, work_mem: usize) -> Self {
+        Self {
+            catalog,
+            work_mem,
+            sizes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn catalog(&self) -> &'c Catalog<'a, B> {
+        self.catalog
+    }
+
+    /// The pages of the table, and its rows when it was last analyzed or else the rows that
+    /// fit in the used space of the pages.
+    pub fn table_size(&self, table: &TableInfo) -> anyhow::Result<TableSize> {
+        if let Some(size) = self.sizes.borrow().get(&table.oid) {
+            return Ok(*size);
+        }
+        let heap = self.catalog.table_heap(table)?;
+        let fsm = heap.free_space_map();
+        let rows = match self.catalog.table_statistics(table.oid) {
+            Some(statistics) => statistics.row_count as f64,
+            None => (fsm.used_bytes() / (width(&table.schema) + SLOT_SIZE)) as f64,
+        };
+        let size = TableSize {
+            pages: fsm.page_count() as f64,
+            rows,
+        };
+        self.sizes.borrow_mut().insert(table.oid, size);
+        Ok(size)
+    }
+
+    pub fn estimate(&self, plan: &LogicalPlan) -> anyhow::Result<Estimate> {
+        let estimate = match plan {
+            LogicalPlan::SeqScan { table } => {
+                let size = self.table_size(table)?;
+                Estimate {
+                    rows: size.rows,
+                    cost: size.pages,
+                }
+            }
+            LogicalPlan::IndexScan {
+                table,
+                index,
+                lower,
+                upper,
+            } => {
+                let size = self.table_size(table)?;
+                let equal = lower == upper && matches!(lower, Bound::Included(_));
+                let selectivity = match self.column_statistics(&[plan], index.columns[0]) {
+                    Some((statistics, column)) => {
+                        let column = &statistics.columns[column];
+                        match (lower, upper) {
+                            (Bound::Included(key), _) if equal => equal_fraction(column, key),
+                            _ => {
+                                let below = |bound: &Bound<Value>, upper: bool| match bound {
+                                    Bound::Included(key) => below_fraction(column, key, upper),
+                                    Bound::Excluded(key) => below_fraction(column, key, !upper),
+                                    Bound::Unbounded if upper => 1.0 - column.null_fraction,
+                                    Bound::Unbounded => 0.0,
+                                };
+                                (below(upper, true) - below(lower, false)).max(0.0)
+                            }
+                        }
+                    }
+                    None if equal => 1.0 / self.distinct_values(&[plan], index.columns[0])?,
+                    None => match (lower, upper) {
+                        (Bound::Unbounded, Bound::Unbounded) => 1.0,
+                        (Bound::Unbounded, _) | (_, Bound::Unbounded) => RANGE_SELECTIVITY,
+                        _ => RANGE_SELECTIVITY * RANGE_SELECTIVITY,
+                    },
+                };
+                let rows = size.rows * selectivity;
+                Estimate {
+                    rows,
+                    cost: INDEX_DESCENT_COST + rows,
+                }
+            }
+            LogicalPlan::Values { rows, .. } => Estimate {
+                rows: rows.len() as f64,
+                cost: 0.0,
+            },
+            LogicalPlan::Filter { input, predicate } => {
+                let input_estimate = self.estimate(input)?;
+                Estimate {
+                    rows: input_estimate.rows * self.selectivity(predicate, &[input])?,
+                    ..input_estimate
+                }
+            }
+            LogicalPlan::Projection { input, .. } | LogicalPlan::Explain { input, .. } => {
+                self.estimate(input)?
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => self.estimate_join(
+                (left, self.estimate(left)?),
+                (right, self.estimate(right)?),
+                *join_type,
+                condition.as_ref(),
+            )?,
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                grouping_sets,
+                ..
+            } => {
+                let input_estimate = self.estimate(input)?;
+                let mut rows = 0.0;
+                for set in grouping_sets {
+                    let mut groups = 1.0;
+                    for &position in set {
+                        groups *= match column_of(&group_by[position]) {
+                            Some(column) => self.distinct_values(&[input], column)?,
+                            None => DEFAULT_DISTINCT,
+                        };
+                    }
+                    rows += groups.min(input_estimate.rows.max(1.0));
+                }
+                Estimate {
+                    rows,
+                    cost: input_estimate.cost
+                        + self.spill_cost(rows, input_estimate.rows, &input.schema()),
+                }
+            }
+            LogicalPlan::Sort { input, .. } => {
+                let input_estimate = self.estimate(input)?;
+                Estimate {
+                    cost: input_estimate.cost
+                        + self.spill_cost(input_estimate.rows, input_estimate.rows, &plan.schema()),
+                    ..input_estimate
+                }
+            }
+            LogicalPlan::Distinct { input } => {
+                let input_estimate = self.estimate(input)?;
+                let mut groups = 1.0;
+                for column in 0..input.schema().column_count() {
+                    groups *= self.distinct_values(&[input], column)?;
+                }
+                let rows = groups.min(input_estimate.rows);
+                Estimate {
+                    rows,
+                    cost: input_estimate.cost
+                        + self.spill_cost(rows, input_estimate.rows, &plan.schema()),
+                }
+            }
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => {
+                let input_estimate = self.estimate(input)?;
+                let rows = (input_estimate.rows - *offset as f64).max(0.0);
+                Estimate {
+                    rows: limit.map_or(rows, |limit| rows.min(limit as f64)),
+                    ..input_estimate
+                }
+            }
+            LogicalPlan::Insert { input, .. }
+            | LogicalPlan::Update { input, .. }
+            | LogicalPlan::Delete { input, .. } => {
+                let input_estimate = self.estimate(input)?;
+                // Every changed row dirties a page.
+                Estimate {
+                    rows: 1.0,
+                    cost: input_estimate.cost + input_estimate.rows,
+                }
+            }
+            LogicalPlan::CreateTable { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::DropIndex { .. }
+            | LogicalPlan::Analyze { .. } => Estimate {
+                rows: 0.0,
+                cost: 0.0,
+            },
+        };
+        Ok(estimate)
+    }
+
+    /// The estimate of a join of inputs with the given estimates as the builder executes it: a
+    /// hash join if the condition has an equality between the inputs, and a nested-loop join
+    /// otherwise.
+    pub fn estimate_join(
+        &self,
+        (left, left_estimate): (&LogicalPlan, Estimate),
+        (right, right_estimate): (&LogicalPlan, Estimate),
+        join_type: JoinType,
+        condition: Option<&Expr>,
+    ) -> anyhow::Result<Estimate> {
+        let selectivity = match condition {
+            Some(condition) => self.selectivity(condition, &[left, right])?,
+            None => 1.0,
+        };
+        let rows = self.join_rows(
+            join_type,
+            left_estimate.rows,
+            right_estimate.rows,
+            selectivity,
+        );
+        let left_width = left.schema().column_count();
+        let cost = if EquiJoinKeys::split(condition, left_width).is_empty() {
+            left_estimate.cost + left_estimate.rows.max(1.0) * right_estimate.cost
+        } else {
+            let spill = if right_estimate.rows > self.work_mem as f64 {
+                2.0 * (pages(left_estimate.rows, &left.schema())
+                    + pages(right_estimate.rows, &right.schema()))
+            } else {
+                0.0
+            };
+            left_estimate.cost + right_estimate.cost + spill
+        };
+        Ok(Estimate { rows, cost })
+    }
+
+    /// The rows of a join of `left` and `right` rows whose pairs match with `selectivity`.
+    pub fn join_rows(&self, join_type: JoinType, left: f64, right: f64, selectivity: f64) -> f64 {
+        let inner = left * right * selectivity;
+        // The fraction of left rows with a match.
+        let matched = (right * selectivity).min(1.0);
+        match join_type {
+            JoinType::Inner => inner,
+            JoinType::Left => inner.max(left),
+            JoinType::Right => inner.max(right),
+            JoinType::Full => inner.max(left).max(right),
+            JoinType::Semi => left * matched,
+            JoinType::Anti => left * (1.0 - matched),
+        }
+    }
+
+    /// The fraction of rows for which `predicate` holds. It is evaluated over the columns of
+    /// the plans of `row` one after another.
+    pub fn selectivity(&self, predicate: &Expr, row: &[&LogicalPlan]) -> anyhow::Result<f64> {
+        let selectivity = match &predicate.kind {
+            ExprKind::Literal(Value::Boolean(true)) => 1.0,
+            // FALSE or NULL
+            ExprKind::Literal(_) => 0.0,
+            ExprKind::Not(inner) => 1.0 - self.selectivity(inner, row)?,
+            ExprKind::Binary {
+                op: BinaryOp::And,
+                left,
+                right,
+            } => self.selectivity(left, row)? * self.selectivity(right, row)?,
+            ExprKind::Binary {
+                op: BinaryOp::Or,
+                left,
+                right,
+            } => {
+                let left = self.selectivity(left, row)?;
+                let right = self.selectivity(right, row)?;
+                left + right - left * right
+            }
+            ExprKind::Binary {
+                op: BinaryOp::Eq,
+                left,
+                right,
+            } => self.equality_selectivity(left, right, row)?,
+            ExprKind::Binary {
+                op: BinaryOp::NotEq,
+                left,
+                right,
+            } => 1.0 - self.equality_selectivity(left, right, row)?,
+            ExprKind::Binary { op, left, right } if op.is_comparison() => {
+                self.range_selectivity(*op, left, right, row)
+            }
+            ExprKind::IsNull { expr, negated } => {
+                let null =
+                    match column_of(expr).and_then(|column| self.column_statistics(row, column)) {
+                        Some((statistics, column)) => statistics.columns[column].null_fraction,
+                        None => NULL_SELECTIVITY,
+                    };
+                negate(null, *negated)
+            }
+            ExprKind::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let equal = match column_of(expr) {
+                    Some(column) => 1.0 / self.distinct_values(row, column)?,
+                    None => 1.0 / DEFAULT_DISTINCT,
+                };
+                negate((equal * list.len() as f64).min(1.0), *negated)
+            }
+            ExprKind::Like { negated, .. } => negate(LIKE_SELECTIVITY, *negated),
+            _ => DEFAULT_SELECTIVITY,
+        };
+        Ok(selectivity.clamp(0.0, 1.0))
+    }
+
+    fn equality_selectivity(
+        &self,
+        left: &Expr,
+        right: &Expr,
+        row: &[&LogicalPlan],
+    ) -> anyhow::Result<f64> {
+        if let Some((column, value)) = column_and_literal(left, right) {
+            if let Some((statistics, column)) = self.column_statistics(row, column) {
+                return Ok(equal_fraction(&statistics.columns[column], value));
+            }
+        }
+        Ok(match (column_of(left), column_of(right)) {
+            // Only rows that are not NULL on both sides match.
+            (Some(left), Some(right)) => {
+                self.non_null_fraction(row, left) * self.non_null_fraction(row, right)
+                    / self
+                        .distinct_values(row, left)?
+                        .max(self.distinct_values(row, right)?)
+            }
+            (Some(column), None) | (None, Some(column)) => {
+                1.0 / self.distinct_values(row, column)?
+            }
+            (None, None) => 1.0 / DEFAULT_DISTINCT,
+        })
+    }
+
+    /// The selectivity of `left op right` for `<`, `<=`, `>` and `>=`.
+    fn range_selectivity(
+        &self,
+        op: BinaryOp,
+        left: &Expr,
+        right: &Expr,
+        row: &[&LogicalPlan],
+    ) -> f64 {
+        let (op, column, value) = match (column_and_literal(left, right), column_of(left)) {
+            (Some((column, value)), Some(_)) => (op, column, value),
+            (Some((column, value)), None) => (op.commuted(), column, value),
+            (None, _) => return RANGE_SELECTIVITY,
+        };
+        let Some((statistics, column)) = self.column_statistics(row, column) else {
+            return RANGE_SELECTIVITY;
+        };
+        let column = &statistics.columns[column];
+        if value.is_null() {
+            return 0.0;
+        }
+        let non_null = 1.0 - column.null_fraction;
+        match op {
+            BinaryOp::Lt => below_fraction(column, value, false),
+            BinaryOp::LtEq => below_fraction(column, value, true),
+            BinaryOp::Gt => non_null - below_fraction(column, value, true),
+            BinaryOp::GtEq => non_null - below_fraction(column, value, false),
+            _ => RANGE_SELECTIVITY,
+        }
+    }
+
+    /// The fraction of the rows whose column `column` of the plans of `row` is not NULL.
+    fn non_null_fraction(&self, row: &[&LogicalPlan], column: usize) -> f64 {
+        self.column_statistics(row, column)
+            .map_or(1.0, |(statistics, column)| {
+                1.0 - statistics.columns[column].null_fraction
+            })
+    }
+
+    /// The statistics of the table that column `column` of the plans of `row` reads unchanged,
+    /// if it was analyzed, and the position of the column there.
+    fn column_statistics(
+        &self,
+        row: &[&LogicalPlan],
+        column: usize,
+    ) -> Option<(Arc<TableStatistics>, usize)> {
+        let (plan, column) = locate(row, column);
+        let (table, column) = column_origin(plan, column)?;
+        Some((self.catalog.table_statistics(table.oid)?, column))
+    }
+
+    /// The number of distinct values of `column` of the plans of `row` one after another.
+    pub fn distinct_values(&self, row: &[&LogicalPlan], column: usize) -> anyhow::Result<f64> {
+        if let Some((statistics, column)) = self.column_statistics(row, column) {
+            return Ok((statistics.columns[column].distinct_count as f64).max(1.0));
+        }
+        let (plan, column) = locate(row, column);
+        let distinct = match column_origin(plan, column) {
+            Some((table, column)) => {
+                let rows = self.table_size(&table)?.rows;
+                let unique = self
+                    .catalog
+                    .table_indexes(table.oid)
+                    .iter()
+                    .any(|index| index.unique && index.columns == [column]);
+                if unique {
+                    rows
+                } else {
+                    rows.min(DEFAULT_DISTINCT)
+                }
+            }
+            None => self.estimate(plan)?.rows.min(DEFAULT_DISTINCT),
+        };
+        Ok(distinct.max(1.0))
+    }
+
+    /// The cost of spilling `input` rows of `schema` if `kept` rows are more than fit in
+    /// memory.
+    fn spill_cost(&self, kept: f64, input: f64, schema: &Schema) -> f64 {
+        if kept > self.work_mem as f64 {
+            2.0 * pages(input, schema)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The table and column that column `column` of `plan` reads unchanged, if any.
+pub fn column_origin(plan: &LogicalPlan, column: usize) -> Option<(Arc<TableInfo>, usize)> {
+    match plan {
+        LogicalPlan::SeqScan { table } | LogicalPlan::IndexScan { table, .. } => {
+            Some((table.clone(), column))
+        }
+        LogicalPlan::Filter { input, .. }
+        | LogicalPlan::Sort { input, .. }
+        | LogicalPlan::Distinct { input }
+        | LogicalPlan::Limit { input, .. } => column_origin(input, column),
+        LogicalPlan::Projection { input, exprs, .. } => {
+            column_origin(input, column_of(&exprs[column])?)
+        }
+        LogicalPlan::Join { left, right, .. } => {
+            let (plan, column) = locate(&[left, right], column);
+            column_origin(plan, column)
+        }
+        LogicalPlan::Aggregate {
+            input, group_by, ..
+        } => column_origin(input, column_of(group_by.get(column)?)?),
+        _ => None,
+    }
+}
+
+/// The plan of `row` that column `column` of their concatenated rows belongs to, and its
+/// position there.
+fn locate<'p>(row: &[&'p LogicalPlan], mut column: usize) -> (&'p LogicalPlan, usize) {
+    for plan in &row[..row.len() - 1] {
+        let width = plan.schema().column_count();
+        if column < width {
+            return (plan, column);
+        }
+        column -= width;
+    }
+    (row[row.len() - 1], column)
+}
+
+/// The column an expression reads, looking through conversions.
+pub fn column_of(expr: &Expr) -> Option<usize> {
+    match &expr.kind {
+        ExprKind::Column(column) => Some(*column),
+        ExprKind::Cast(inner) => column_of(inner),
+        _ => None,
+    }
+}
+
+/// The column and the constant of a comparison of a column with a constant, in either order.
+fn column_and_literal<'e>(left: &'e Expr, right: &'e Expr) -> Option<(usize, &'e Value)> {
+    match (&left.kind, &right.kind) {
+        (_, ExprKind::Literal(value)) => Some((column_of(left)?, value)),
+        (ExprKind::Literal(value), _) => Some((column_of(right)?, value)),
+        _ => None,
+    }
+}
+
+/// The fraction of the rows of an analyzed column that are equal to `value`.
+fn equal_fraction(statistics: &ColumnStatistics, value: &Value) -> f64 {
+    if value.is_null() {
+        return 0.0;
+    }
+    let common = statistics
+        .most_common_values
+        .iter()
+        .zip(&statistics.most_common_frequencies)
+        .find(|(common, _)| compare(common, value) == Some(Ordering::Equal));
+    if let Some((_, frequency)) = common {
+        return *frequency;
+    }
+    // The other values share the rows of the histogram evenly.
+    let others = statistics.distinct_count as f64 - statistics.most_common_values.len() as f64;
+    statistics.histogram_fraction() / others.max(1.0)
+}
+
+/// The fraction of the rows of an analyzed column that are less than `value`, or equal to it
+/// if `inclusive`.
+fn below_fraction(statistics: &ColumnStatistics, value: &Value, inclusive: bool) -> f64 {
+    let common: f64 = statistics
+        .most_common_values
+        .iter()
+        .zip(&statistics.most_common_frequencies)
+        .filter(|(common, _)| match compare(common, value) {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => inclusive,
+            _ => false,
+        })
+        .map(|(_, frequency)| frequency)
+        .sum();
+    common
+        + statistics.histogram_fraction() * histogram_position(&statistics.histogram_bounds, value)
+}
+
+/// The fraction of the values of a histogram with `bounds` that are less than `value`.
+fn histogram_position(bounds: &[Value], value: &Value) -> f64 {
+    if bounds.len() < 2 {
+        return RANGE_SELECTIVITY;
+    }
+    let below = |bound: &Value| compare(bound, value).map(Ordering::is_le);
+    match (below(&bounds[0]), below(&bounds[bounds.len() - 1])) {
+        (None, _) | (_, None) => return RANGE_SELECTIVITY,
+        (Some(false), _) => return 0.0,
+        (_, Some(true)) => return 1.0,
+        _ => {}
+    }
+    // The bucket that holds `value`, between bounds `bucket` and `bucket + 1`.
+    let bucket = bounds.partition_point(|bound| below(bound) == Some(true)) - 1;
+    let within = match (
+        number(&bounds[bucket]),
+        number(&bounds[bucket + 1]),
+        number(value),
+    ) {
+        (Some(low), Some(high), Some(value)) if high > low => (value - low) / (high - low),
+        _ => 0.5,
+    };
+    (bucket as f64 + within.clamp(0.0, 1.0)) / (bounds.len() - 1) as f64
+}
+
+/// A value as a number, to interpolate between histogram bounds.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float64(f) => Some(*f),
+        value => value.as_i64().map(|i| i as f64),
+    }
+}
+
+/// The estimated width in bytes of an encoded row of `schema`.
+pub fn width(schema: &Schema) -> usize {
+    schema
+        .columns()
+        .iter()
+        .map(|column| {
+            let data_type = column.data_type;
+            data_type.fixed_size()
+                + if data_type.is_variable_length() {
+                    VARIABLE_WIDTH
+                } else {
+                    0
+                }
+        })
+        .sum::<usize>()
+        .max(1)
+}
+
+/// The pages `rows` rows of `schema` fill.
+pub fn pages(rows: f64, schema: &Schema) -> f64 {
+    (rows * (width(schema) + SLOT_SIZE) as f64 / DEFAULT_PAGE_SIZE as f64).ceil()
+}
+
+fn negate(selectivity: f64, negated: bool) -> f64 {
+    if negated {
+        1.0 - selectivity
+    } else {
+        selectivity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::schema::Column,
+        execution::{
+            analyze::{analyze_table, AnalyzeConfig},
+            builder::build_executor,
+            context::{ExecutorContext, DEFAULT_WORK_MEM},
+        },
+        optimizer::{
+            constant_folding::fold_constants, index_selection::select_indexes,
+            predicate_pushdown::push_down_predicates,
+        },
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            index::index::IndexKind,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    fn bind(catalog: &Catalog<BufferPoolManagerImpl>, sql: &str) -> LogicalPlan {
+        let statement = parse_statement(sql).unwrap();
+        Binder::new(catalog).bind(&statement).unwrap()
+    }
+
+    #[test]
+    fn test_estimate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        for (name, rows) in [("big", 2000), ("small", 10)] {
+            catalog
+                .create_table(
+                    name,
+                    Schema::new(vec![
+                        Column::new("id", DataType::Int64).not_null(),
+                        Column::new("ref", DataType::Int64),
+                        Column::new("v", DataType::Int32),
+                    ]),
+                )
+                .unwrap();
+            let values: Vec<String> = (0..rows)
+                .map(|i| format!("({i}, {}, {})", i % 200, i % 7))
+                .collect();
+            let insert = bind(
+                &catalog,
+                &format!("INSERT INTO {name} VALUES {}", values.join(", ")),
+            );
+            let mut executor = build_executor(&ctx, &insert).unwrap();
+            executor.init().unwrap();
+            while executor.next().unwrap().is_some() {}
+        }
+        catalog
+            .create_index("big_pkey", "big", &["id"], IndexKind::BPlusTree, true)
+            .unwrap();
+        let plan = |sql: &str| {
+            fold_constants(bind(&catalog, sql))
+                .and_then(push_down_predicates)
+                .unwrap()
+        };
+        // Few enough rows in memory that hashing or sorting the big table spills.
+        let model = CostModel::new(&catalog, 100);
+        let estimate = |sql: &str| model.estimate(&plan(sql)).unwrap();
+        let assert_rows = |sql: &str, rows: f64| {
+            let estimate = estimate(sql);
+            assert!((estimate.rows - rows).abs() < 1e-6, "{sql}: {estimate:?}");
+        };
+
+        // Without statistics, the rows are those that fit in the used space of the pages.
+        let big = estimate("SELECT * FROM big");
+        assert_eq!(big.cost, 8.0);
+        assert!((big.rows - 2000.0).abs() < 100.0);
+        let small = estimate("SELECT * FROM small");
+        assert_eq!(small.cost, 1.0);
+
+        // A unique index has a value for each row, and other columns the default number.
+        assert_rows("SELECT * FROM big WHERE id = 5", 1.0);
+        assert_rows("SELECT * FROM big WHERE v = 1", big.rows / DEFAULT_DISTINCT);
+        assert_rows(
+            "SELECT * FROM big WHERE v IN (1, 2, 3)",
+            big.rows * 3.0 / DEFAULT_DISTINCT,
+        );
+        assert_rows(
+            "SELECT * FROM big WHERE NOT (v = 1)",
+            big.rows * (1.0 - 1.0 / DEFAULT_DISTINCT),
+        );
+        let equal = 1.0 / DEFAULT_DISTINCT;
+        assert_rows(
+            "SELECT * FROM big WHERE v = 1 OR v = 2",
+            big.rows * (2.0 * equal - equal * equal),
+        );
+        assert_rows(
+            "SELECT * FROM big WHERE ref IS NULL",
+            big.rows * NULL_SELECTIVITY,
+        );
+        assert_rows(
+            "SELECT * FROM big WHERE v < 3",
+            big.rows * RANGE_SELECTIVITY,
+        );
+        assert_rows("SELECT * FROM big LIMIT 10 OFFSET 1995", 10.0);
+        // A filter reads as many pages as its input, and the index scan descends the tree
+        // before reading a page for each row.
+        let filtered = plan("SELECT * FROM big WHERE id = 5");
+        assert_eq!(model.estimate(&filtered).unwrap().cost, big.cost);
+        let indexed = select_indexes(filtered, &model).unwrap();
+        assert_eq!(
+            model.estimate(&indexed).unwrap(),
+            Estimate {
+                rows: 1.0,
+                cost: INDEX_DESCENT_COST + 1.0
+            }
+        );
+
+        // The hash join reads each input once, and spills both when the right one does not
+        // fit in memory.
+        let join_rows = big.rows * small.rows / DEFAULT_DISTINCT;
+        assert_eq!(
+            estimate("SELECT * FROM big b JOIN small s ON b.ref = s.id"),
+            Estimate {
+                rows: join_rows,
+                cost: big.cost + small.cost
+            }
+        );
+        let spilled = big.cost + small.cost + 2.0 * (big.cost + small.cost);
+        assert_eq!(
+            estimate("SELECT * FROM small s JOIN big b ON b.ref = s.id"),
+            Estimate {
+                rows: join_rows,
+                cost: spilled
+            }
+        );
+        assert_eq!(
+            estimate("SELECT * FROM small s LEFT JOIN big b ON b.ref = s.id").cost,
+            spilled
+        );
+        let unspilled = CostModel::new(&catalog, DEFAULT_WORK_MEM)
+            .estimate(&plan("SELECT * FROM small s JOIN big b ON b.ref = s.id"))
+            .unwrap();
+        assert_eq!(unspilled.cost, big.cost + small.cost);
+        // The nested-loop join reads the right input once for each left row.
+        assert_eq!(
+            estimate("SELECT * FROM small s JOIN big b ON b.v < s.v"),
+            Estimate {
+                rows: big.rows * small.rows * RANGE_SELECTIVITY,
+                cost: small.cost + small.rows * big.cost
+            }
+        );
+        // Aggregation and sorting spill their input past the rows that fit in memory.
+        assert_eq!(
+            estimate("SELECT v, count(*) FROM big GROUP BY v"),
+            Estimate {
+                rows: DEFAULT_DISTINCT,
+                cost: 3.0 * big.cost
+            }
+        );
+        assert_eq!(
+            estimate("SELECT * FROM big ORDER BY v").cost,
+            3.0 * big.cost
+        );
+
+        assert_eq!(model.join_rows(JoinType::Inner, 100.0, 10.0, 0.01), 10.0);
+        assert_eq!(model.join_rows(JoinType::Left, 100.0, 10.0, 0.01), 100.0);
+        assert_eq!(model.join_rows(JoinType::Right, 100.0, 10.0, 0.01), 10.0);
+        assert_eq!(model.join_rows(JoinType::Full, 100.0, 10.0, 0.01), 100.0);
+        assert_eq!(model.join_rows(JoinType::Semi, 100.0, 10.0, 0.01), 10.0);
+        assert_eq!(model.join_rows(JoinType::Anti, 100.0, 10.0, 0.01), 90.0);
+        assert_eq!(model.join_rows(JoinType::Anti, 100.0, 1000.0, 0.01), 0.0);
+
+        // After ANALYZE, the estimates come from the table's statistics. The model keeps the
+        // sizes it read, so a new one is needed.
+        let LogicalPlan::Analyze { tables } = bind(&catalog, "ANALYZE big") else {
+            panic!()
+        };
+        analyze_table(&catalog, &tables[0], &AnalyzeConfig::new()).unwrap();
+        let model = CostModel::new(&catalog, 100);
+        let estimate = |sql: &str| model.estimate(&plan(sql)).unwrap();
+        assert_eq!(estimate("SELECT * FROM big").rows, 2000.0);
+        assert_eq!(estimate("SELECT * FROM big WHERE v = 1").rows, 286.0);
+        assert_eq!(estimate("SELECT * FROM big WHERE v < 3").rows, 858.0);
+        assert_eq!(estimate("SELECT * FROM big WHERE ref IS NULL").rows, 0.0);
+        assert_eq!(
+            estimate("SELECT v, count(*) FROM big GROUP BY v"),
+            Estimate {
+                rows: 7.0,
+                cost: big.cost
+            }
+        );
+        assert_eq!(
+            estimate("SELECT * FROM big b JOIN small s ON b.ref = s.id").rows,
+            2000.0 * small.rows / 200.0
+        );
+    }
+}
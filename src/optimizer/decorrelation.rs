@@ -0,0 +1,328 @@
+//! Turns the EXISTS and IN subqueries of filters into semi and anti joins.
+//!
+//! The binder allows a subquery only as a condition of a WHERE joined to the rest by AND, so
+//! every row the filter keeps must satisfy it, which is what a semi join checks:
+//!
+//! | condition              | join | join condition                                       |
+//! |------------------------|------|------------------------------------------------------|
+//! | `EXISTS (q)`           | SEMI | the correlated conditions of `q`                     |
+//! | `NOT EXISTS (q)`       | ANTI | the correlated conditions of `q`                     |
+//! | `x IN (q)`             | SEMI | `x = v` and the correlated conditions                |
+//! | `x NOT IN (q)`         | ANTI | `(x = v OR x IS NULL OR v IS NULL)` and the rest     |
+//!
+//! where `v` is the column `q` selects. The extra conditions of NOT IN keep SQL's semantics:
+//! a NULL on either side makes the comparison unknown, so the row is not returned unless `q`
+//! is empty.
+//!
+//! The correlated conditions are those of the filters at the top of the subquery that read
+//! the enclosing row. They move to the join condition, where the enclosing row is the left
+//! row. Correlation anywhere else, such as below an aggregation, is not supported.
+
+use crate::{
+    catalog::schema::Schema,
+    planner::{
+        expr::{Expr, ExprKind},
+        logical_plan::{JoinType, LogicalPlan},
+    },
+    sql::ast::BinaryOp,
+    types::data_type::DataType,
+};
+
+pub fn decorrelate(plan: LogicalPlan) -> anyhow::Result<LogicalPlan> {
+    let plan = plan.map_children(&mut decorrelate)?;
+    let LogicalPlan::Filter { input, predicate } = plan else {
+        return Ok(plan);
+    };
+    if !predicate.has_subquery() {
+        return Ok(LogicalPlan::Filter { input, predicate });
+    }
+
+    let outer_schema = input.schema();
+    let mut plan = *input;
+    let mut rest = Vec::new();
+    for conjunct in predicate.split_conjunction() {
+        let (join_type, subquery, operand) = match conjunct.kind {
+            ExprKind::Not(inner) => match inner.kind {
+                ExprKind::Exists(subquery) => (JoinType::Anti, subquery, None),
+                ExprKind::InSubquery {
+                    expr,
+                    subquery,
+                    negated,
+                } => (join_type(!negated), subquery, Some(*expr)),
+                kind => {
+                    rest.push(Expr::new(
+                        ExprKind::Not(Box::new(Expr::new(kind, inner.data_type))),
+                        conjunct.data_type,
+                    ));
+                    continue;
+                }
+            },
+            ExprKind::Exists(subquery) => (JoinType::Semi, subquery, None),
+            ExprKind::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => (join_type(negated), subquery, Some(*expr)),
+            kind => {
+                rest.push(Expr::new(kind, conjunct.data_type));
+                continue;
+            }
+        };
+        let (right, condition) =
+            unnest(decorrelate(*subquery)?, operand, join_type, &outer_schema)?;
+        plan = LogicalPlan::Join {
+            left: Box::new(plan),
+            right: Box::new(right),
+            join_type,
+            condition,
+        };
+    }
+    Ok(match Expr::conjunction(rest) {
+        Some(predicate) => LogicalPlan::Filter {
+            input: Box::new(plan),
+            predicate,
+        },
+        None => plan,
+    })
+}
+
+fn join_type(negated: bool) -> JoinType {
+    if negated {
+        JoinType::Anti
+    } else {
+        JoinType::Semi
+    }
+}
+
+/// The right input of the join that replaces `subquery` and the join condition, for IN if
+/// there is an `operand`.
+fn unnest(
+    mut subquery: LogicalPlan,
+    operand: Option<Expr>,
+    join_type: JoinType,
+    outer_schema: &Schema,
+) -> anyhow::Result<(LogicalPlan, Option<Expr>)> {
+    let offset = outer_schema.column_count();
+    // What the subquery selects does not matter to EXISTS, and neither its order nor its
+    // duplicates matter to either.
+    let mut value = None;
+    loop {
+        match subquery {
+            LogicalPlan::Sort { input, .. } | LogicalPlan::Distinct { input } => subquery = *input,
+            LogicalPlan::Projection {
+                input, mut exprs, ..
+            } if value.is_none() => {
+                value = Some(exprs.swap_remove(0));
+                subquery = *input;
+            }
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset: 0,
+            } if operand.is_none() && limit != Some(0) => subquery = *input,
+            plan => {
+                subquery = plan;
+                break;
+            }
+        }
+    }
+
+    let mut correlated = Vec::new();
+    while let LogicalPlan::Filter { input, predicate } = subquery {
+        let mut uncorrelated = Vec::new();
+        for conjunct in predicate.split_conjunction() {
+            if conjunct.is_correlated() {
+                correlated.push(conjunct.unnest(offset));
+            } else {
+                uncorrelated.push(conjunct);
+            }
+        }
+        subquery = *input;
+        if let Some(predicate) = Expr::conjunction(uncorrelated) {
+            // Filters below this one stay where they are.
+            subquery = LogicalPlan::Filter {
+                input: Box::new(subquery),
+                predicate,
+            };
+            break;
+        }
+    }
+    if is_correlated(&subquery) {
+        anyhow::bail!("correlated subqueries are only supported in the WHERE of the subquery");
+    }
+
+    let mut condition = Vec::new();
+    if let Some(operand) = operand {
+        let right_schema = subquery.schema();
+        let value = value.unwrap_or_else(|| Expr::column(0, right_schema.column(0).data_type));
+        let nullable = |expr: &Expr, schema: &Schema| match expr.kind {
+            ExprKind::Column(i) => schema.column(i).nullable,
+            _ => true,
+        };
+        let may_be_null = nullable(&operand, outer_schema) || nullable(&value, &right_schema);
+        let value = value.unnest(offset);
+        let equal = Expr::binary(
+            BinaryOp::Eq,
+            operand.clone(),
+            value.clone(),
+            DataType::Boolean,
+        );
+        if join_type == JoinType::Anti && may_be_null {
+            let is_null = |expr: Expr| {
+                Expr::new(
+                    ExprKind::IsNull {
+                        expr: Box::new(expr),
+                        negated: false,
+                    },
+                    DataType::Boolean,
+                )
+            };
+            let or = |left, right| Expr::binary(BinaryOp::Or, left, right, DataType::Boolean);
+            condition.push(or(or(equal, is_null(operand)), is_null(value)));
+        } else {
+            condition.push(equal);
+        }
+    }
+    condition.extend(correlated);
+    Ok((subquery, Expr::conjunction(condition)))
+}
+
+fn is_correlated(plan: &LogicalPlan) -> bool {
+    plan.exprs().iter().any(|expr| expr.is_correlated())
+        || plan.children().into_iter().any(is_correlated)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::{catalog::Catalog, schema::Column},
+        execution::{builder::build_executor, context::ExecutorContext},
+        optimizer::{constant_folding::fold_constants, optimizer::Optimizer},
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    fn bind(catalog: &Catalog<BufferPoolManagerImpl>, sql: &str) -> LogicalPlan {
+        let statement = parse_statement(sql).unwrap();
+        fold_constants(Binder::new(catalog).bind(&statement).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_decorrelate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        let run = |sql: &str| {
+            let plan = Optimizer::new(&catalog)
+                .optimize(bind(&catalog, sql))
+                .unwrap();
+            let mut executor = build_executor(&ctx, &plan).unwrap();
+            executor.init().unwrap();
+            let mut rows = Vec::new();
+            while let Some(tuple) = executor.next().unwrap() {
+                rows.push(tuple.value(0).as_i64().unwrap());
+            }
+            rows.sort();
+            rows
+        };
+        for name in ["t", "u"] {
+            let schema = Schema::new(vec![
+                Column::new("a", DataType::Int64).not_null(),
+                Column::new("b", DataType::Int64),
+                Column::new("c", DataType::Int32),
+            ]);
+            catalog.create_table(name, schema).unwrap();
+        }
+        run("INSERT INTO t VALUES (1, 1, 0), (2, NULL, 0), (3, 3, 1)");
+        run("INSERT INTO u VALUES (10, 1, 0), (11, NULL, 1), (12, 3, 0)");
+
+        let check = |sql: &str, before: &str, after: &str| {
+            let plan = bind(&catalog, sql);
+            assert_eq!(plan.to_string(), before);
+            assert_eq!(decorrelate(plan).unwrap().to_string(), after);
+        };
+        check(
+            "SELECT a FROM t WHERE EXISTS (SELECT * FROM u WHERE u.b = t.b AND u.c = 0)",
+            "Projection #0\n  Filter EXISTS (subquery)\n    SeqScan t\n",
+            "Projection #0\n\
+             \x20 Join Semi on (#4 = #1)\n\
+             \x20   SeqScan t\n\
+             \x20   Filter (#2 = 0)\n\
+             \x20     SeqScan u\n",
+        );
+        check(
+            "SELECT a FROM t WHERE c = 0 AND NOT EXISTS (SELECT * FROM u WHERE u.b = t.b)",
+            "Projection #0\n  Filter ((#2 = 0) AND NOT EXISTS (subquery))\n    SeqScan t\n",
+            "Projection #0\n\
+             \x20 Filter (#2 = 0)\n\
+             \x20   Join Anti on (#4 = #1)\n\
+             \x20     SeqScan t\n\
+             \x20     SeqScan u\n",
+        );
+        check(
+            "SELECT a FROM t WHERE b IN (SELECT b FROM u)",
+            "Projection #0\n  Filter #1 IN (subquery)\n    SeqScan t\n",
+            "Projection #0\n\
+             \x20 Join Semi on (#1 = #4)\n\
+             \x20   SeqScan t\n\
+             \x20   SeqScan u\n",
+        );
+        // NOT IN keeps a row only if the comparison is false for every value, not unknown.
+        check(
+            "SELECT a FROM t WHERE b NOT IN (SELECT b FROM u WHERE u.c = t.c)",
+            "Projection #0\n  Filter #1 NOT IN (subquery)\n    SeqScan t\n",
+            "Projection #0\n\
+             \x20 Join Anti on ((((#1 = #4) OR #1 IS NULL) OR #4 IS NULL) AND (#5 = #2))\n\
+             \x20   SeqScan t\n\
+             \x20   SeqScan u\n",
+        );
+
+        // A NULL in the subquery makes NOT IN unknown for every row, while a NULL on the left is
+        // unknown unless the subquery is empty.
+        assert_eq!(run("SELECT a FROM t WHERE b NOT IN (SELECT b FROM u)"), []);
+        assert_eq!(
+            run("SELECT a FROM t WHERE b NOT IN (SELECT b FROM u WHERE a = 10)"),
+            [3]
+        );
+        assert_eq!(
+            run("SELECT a FROM t WHERE b NOT IN (SELECT b FROM u WHERE a > 100)"),
+            [1, 2, 3]
+        );
+        // With correlation, a NULL of u only counts for the rows of t with the same c, and the
+        // rows whose subquery is empty are kept.
+        assert_eq!(
+            run("SELECT a FROM t WHERE b NOT IN (SELECT b FROM u WHERE u.c = t.c)"),
+            []
+        );
+        assert_eq!(
+            run("SELECT a FROM t WHERE b NOT IN (SELECT b FROM u WHERE u.c = t.c AND u.b > 1)"),
+            [1, 3]
+        );
+        assert_eq!(run("SELECT a FROM t WHERE b IN (SELECT b FROM u)"), [1, 3]);
+        // NOT EXISTS has no such rule: a NULL matches nothing, so its row has no match.
+        assert_eq!(
+            run("SELECT a FROM t WHERE NOT EXISTS (SELECT * FROM u WHERE u.b = t.b)"),
+            [2]
+        );
+        assert_eq!(
+            run("SELECT a FROM t WHERE NOT EXISTS (SELECT * FROM u WHERE u.b = t.b AND u.c = 1)"),
+            [1, 2, 3]
+        );
+    }
+}
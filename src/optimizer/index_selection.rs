@@ -0,0 +1,386 @@
+//! Replaces a filtered scan with a scan of an index on a column the filter compares to a
+//! constant, when the cost model estimates it reads fewer pages.
+//!
+//! The comparisons of the first column of an index with literals bound the scan: `=` on both
+//! sides, `<` and `<=` from above and `>` and `>=` from below. Only literals that a value of the
+//! column equals exactly are used, converted to the column's type. The tightest bound on each
+//! side is taken, and the comparisons it comes from are dropped from the filter, while the
+//! others stay. A hash index takes only `=`, and only if it has a single column, since it is
+//! looked up by its whole key.
+//!
+//! An UPDATE never scans an index on a column it assigns, since an updated row could then be
+//! found again further along the index.
+
+use std::{cmp::Ordering, ops::Bound, sync::Arc};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{IndexInfo, TableInfo},
+        table_index::exact_key,
+    },
+    execution::eval::compare,
+    planner::{
+        expr::{Expr, ExprKind},
+        logical_plan::LogicalPlan,
+    },
+    sql::ast::BinaryOp,
+    types::{data_type::DataType, value::Value},
+};
+
+use super::{cost::CostModel, predicate_pushdown::filter};
+
+pub fn select_indexes<B: BufferPoolManager + ?Sized>(
+    plan: LogicalPlan,
+    model: &CostModel<'_, '_, B>,
+) -> anyhow::Result<LogicalPlan> {
+    select(plan, model, &[])
+}
+
+/// `plan` with index scans where they are cheaper, except on columns in `assigned`.
+fn select<B: BufferPoolManager + ?Sized>(
+    plan: LogicalPlan,
+    model: &CostModel<'_, '_, B>,
+    assigned: &[usize],
+) -> anyhow::Result<LogicalPlan> {
+    match plan {
+        LogicalPlan::Filter { input, predicate } => match *input {
+            LogicalPlan::SeqScan { table } => best_scan(table, predicate, model, assigned),
+            input => Ok(LogicalPlan::Filter {
+                input: Box::new(select(input, model, assigned)?),
+                predicate,
+            }),
+        },
+        LogicalPlan::Update {
+            table,
+            input,
+            assignments,
+        } => {
+            let assigned: Vec<usize> = assignments.iter().map(|(column, _)| *column).collect();
+            Ok(LogicalPlan::Update {
+                table,
+                input: Box::new(select(*input, model, &assigned)?),
+                assignments,
+            })
+        }
+        plan => plan.map_children(&mut |input| select(input, model, assigned)),
+    }
+}
+
+/// The cheapest plan of the rows of `table` for which `predicate` holds.
+fn best_scan<B: BufferPoolManager + ?Sized>(
+    table: Arc<TableInfo>,
+    predicate: Expr,
+    model: &CostModel<'_, '_, B>,
+    assigned: &[usize],
+) -> anyhow::Result<LogicalPlan> {
+    let conjuncts = predicate.split_conjunction();
+    let mut best = filter(
+        LogicalPlan::SeqScan {
+            table: table.clone(),
+        },
+        conjuncts.clone(),
+    );
+    let mut best_cost = model.estimate(&best)?.cost;
+    for index in model.catalog().table_indexes(table.oid) {
+        if index.columns.iter().any(|column| assigned.contains(column)) {
+            continue;
+        }
+        let Some((lower, upper, used)) = bounds(&table, &index, &conjuncts) else {
+            continue;
+        };
+        let rest = conjuncts
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !used.contains(i))
+            .map(|(_, conjunct)| conjunct.clone())
+            .collect();
+        let scan = LogicalPlan::IndexScan {
+            table: table.clone(),
+            index,
+            lower,
+            upper,
+        };
+        let plan = filter(scan, rest);
+        let cost = model.estimate(&plan)?.cost;
+        if cost < best_cost {
+            best = plan;
+            best_cost = cost;
+        }
+    }
+    Ok(best)
+}
+
+/// The bounds `conjuncts` put on the first column of `index` and the positions of the
+/// conjuncts they come from, or None if they put none the index can scan.
+fn bounds(
+    table: &TableInfo,
+    index: &IndexInfo,
+    conjuncts: &[Expr],
+) -> Option<(Bound<Value>, Bound<Value>, Vec<usize>)> {
+    let column = index.columns[0];
+    let data_type = table.schema.column(column).data_type;
+    // Each bound with the conjunct it comes from.
+    let mut lower: Option<(Bound<Value>, usize)> = None;
+    let mut upper: Option<(Bound<Value>, usize)> = None;
+    for (i, conjunct) in conjuncts.iter().enumerate() {
+        let Some((op, key)) = comparison(conjunct, column, data_type) else {
+            continue;
+        };
+        if op != BinaryOp::Eq && !index.kind.supports_range() {
+            continue;
+        }
+        let (new_lower, new_upper) = match op {
+            BinaryOp::Eq => (
+                Some(Bound::Included(key.clone())),
+                Some(Bound::Included(key)),
+            ),
+            BinaryOp::Lt => (None, Some(Bound::Excluded(key))),
+            BinaryOp::LtEq => (None, Some(Bound::Included(key))),
+            BinaryOp::Gt => (Some(Bound::Excluded(key)), None),
+            BinaryOp::GtEq => (Some(Bound::Included(key)), None),
+            _ => continue,
+        };
+        if let Some(bound) = new_lower {
+            if lower
+                .as_ref()
+                .map_or(true, |(current, _)| tighter_lower(&bound, current))
+            {
+                lower = Some((bound, i));
+            }
+        }
+        if let Some(bound) = new_upper {
+            if upper
+                .as_ref()
+                .map_or(true, |(current, _)| tighter_upper(&bound, current))
+            {
+                upper = Some((bound, i));
+            }
+        }
+    }
+    if !index.kind.supports_range() {
+        // A hash index needs the same key on both sides.
+        let (Some((lower, i)), Some((upper, j))) = (lower, upper) else {
+            return None;
+        };
+        if i != j || index.columns.len() != 1 {
+            return None;
+        }
+        return Some((lower, upper, vec![i]));
+    }
+    if lower.is_none() && upper.is_none() {
+        return None;
+    }
+    let mut used: Vec<usize> = lower.iter().chain(&upper).map(|(_, i)| *i).collect();
+    used.dedup();
+    Some((
+        lower.map_or(Bound::Unbounded, |(bound, _)| bound),
+        upper.map_or(Bound::Unbounded, |(bound, _)| bound),
+        used,
+    ))
+}
+
+/// The comparison `conjunct` makes of `column`, of type `data_type`, with a literal, as
+/// `column op key` with the key converted to the column's type.
+fn comparison(conjunct: &Expr, column: usize, data_type: DataType) -> Option<(BinaryOp, Value)> {
+    let ExprKind::Binary { op, left, right } = &conjunct.kind else {
+        return None;
+    };
+    let (op, key) = if is_column(left, column) {
+        (*op, right)
+    } else if is_column(right, column) {
+        (op.commuted(), left)
+    } else {
+        return None;
+    };
+    match &key.kind {
+        ExprKind::Literal(value) => Some((op, exact_key(value, data_type)?)),
+        _ => None,
+    }
+}
+
+/// Whether `expr` is `column`, possibly widened to another integer type.
+fn is_column(expr: &Expr, column: usize) -> bool {
+    match &expr.kind {
+        ExprKind::Column(index) => *index == column,
+        ExprKind::Cast(inner) => expr.data_type.is_integer() && is_column(inner, column),
+        _ => false,
+    }
+}
+
+fn tighter_lower(bound: &Bound<Value>, than: &Bound<Value>) -> bool {
+    match (bound, than) {
+        (_, Bound::Unbounded) => true,
+        (Bound::Unbounded, _) => false,
+        (Bound::Excluded(a), Bound::Included(b)) => compare(a, b) != Some(Ordering::Less),
+        (Bound::Included(a), Bound::Excluded(b))
+        | (Bound::Included(a), Bound::Included(b))
+        | (Bound::Excluded(a), Bound::Excluded(b)) => compare(a, b) == Some(Ordering::Greater),
+    }
+}
+
+fn tighter_upper(bound: &Bound<Value>, than: &Bound<Value>) -> bool {
+    match (bound, than) {
+        (_, Bound::Unbounded) => true,
+        (Bound::Unbounded, _) => false,
+        (Bound::Excluded(a), Bound::Included(b)) => compare(a, b) != Some(Ordering::Greater),
+        (Bound::Included(a), Bound::Excluded(b))
+        | (Bound::Included(a), Bound::Included(b))
+        | (Bound::Excluded(a), Bound::Excluded(b)) => compare(a, b) == Some(Ordering::Less),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        execution::{
+            analyze::{analyze_table, AnalyzeConfig},
+            builder::build_executor,
+            context::{ExecutorContext, DEFAULT_WORK_MEM},
+        },
+        optimizer::{constant_folding::fold_constants, predicate_pushdown::push_down_predicates},
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    fn bind(catalog: &Catalog<BufferPoolManagerImpl>, sql: &str) -> LogicalPlan {
+        let statement = parse_statement(sql).unwrap();
+        Binder::new(catalog).bind(&statement).unwrap()
+    }
+
+    #[test]
+    fn test_select_indexes() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        catalog
+            .create_table(
+                "t",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("code", DataType::Int64),
+                    Column::new("v", DataType::Int32),
+                    Column::new("payload", DataType::Varchar(None)),
+                ]),
+            )
+            .unwrap();
+        // Wide rows, so that the table has more pages than a few rows read through an index.
+        let payload = "x".repeat(100);
+        let values: Vec<String> = (0..2000)
+            .map(|i| format!("({i}, {}, {}, '{payload}')", i * 10, i % 7))
+            .collect();
+        let insert = bind(
+            &catalog,
+            &format!("INSERT INTO t VALUES {}", values.join(", ")),
+        );
+        let mut executor = build_executor(&ctx, &insert).unwrap();
+        executor.init().unwrap();
+        while executor.next().unwrap().is_some() {}
+        catalog
+            .create_index("t_id", "t", &["id"], IndexKind::BPlusTree, true)
+            .unwrap();
+        catalog
+            .create_index("t_code", "t", &["code"], IndexKind::Hash, true)
+            .unwrap();
+        let check = |sql: &str, before: &str, after: &str| {
+            let plan = fold_constants(bind(&catalog, sql))
+                .and_then(push_down_predicates)
+                .unwrap();
+            assert_eq!(plan.to_string(), before);
+            let model = CostModel::new(&catalog, DEFAULT_WORK_MEM);
+            assert_eq!(select_indexes(plan, &model).unwrap().to_string(), after);
+        };
+
+        // The equality bounds the scan from both sides, and the other conjunct stays.
+        check(
+            "SELECT * FROM t WHERE id = 5 AND v = 1",
+            "Projection #0, #1, #2, #3\n\
+             \x20 Filter ((#0 = 5) AND (#2 = 1))\n\
+             \x20   SeqScan t\n",
+            "Projection #0, #1, #2, #3\n\
+             \x20 Filter (#2 = 1)\n\
+             \x20   IndexScan t using t_id [5, 5]\n",
+        );
+        // A hash index takes only equalities.
+        check(
+            "SELECT * FROM t WHERE code = 30",
+            "Projection #0, #1, #2, #3\n  Filter (#1 = 30)\n    SeqScan t\n",
+            "Projection #0, #1, #2, #3\n  IndexScan t using t_code [30, 30]\n",
+        );
+        let less = "Projection #0, #1, #2, #3\n  Filter (#1 < 30)\n    SeqScan t\n";
+        check("SELECT * FROM t WHERE code < 30", less, less);
+        // A hash index on two columns is looked up by both, which a scan does not bind.
+        catalog
+            .create_index(
+                "t_payload_v",
+                "t",
+                &["payload", "v"],
+                IndexKind::Hash,
+                false,
+            )
+            .unwrap();
+        let prefix = "Projection #0, #1, #2, #3\n  Filter (#3 = 'x')\n    SeqScan t\n";
+        check("SELECT * FROM t WHERE payload = 'x'", prefix, prefix);
+        // No value of the column equals 1.5.
+        let inexact = "Projection #0, #1, #2, #3\n\
+                       \x20 Filter (CAST(#0 AS DECIMAL(38, 1)) = 1.5)\n\
+                       \x20   SeqScan t\n";
+        check("SELECT * FROM t WHERE id = 1.5", inexact, inexact);
+        // An UPDATE scans the index on a column it leaves alone, but not on one it assigns.
+        check(
+            "UPDATE t SET v = 0 WHERE id = 5",
+            "Update t #2=0\n  Filter (#0 = 5)\n    SeqScan t\n",
+            "Update t #2=0\n  IndexScan t using t_id [5, 5]\n",
+        );
+        let assigned = "Update t #0=0\n  Filter (#0 = 5)\n    SeqScan t\n";
+        check("UPDATE t SET id = 0 WHERE id = 5", assigned, assigned);
+
+        // Without statistics, a range keeps a third of the rows, which costs more through the
+        // index than the table has pages, and two bounds still keep a ninth.
+        let range = "Projection #0, #1, #2, #3\n\
+                     \x20 Filter (((#0 > 10) AND (#0 <= 20)) AND (#0 > 15))\n\
+                     \x20   SeqScan t\n";
+        check(
+            "SELECT * FROM t WHERE id > 10 AND id <= 20 AND id > 15",
+            range,
+            range,
+        );
+        let above = "Projection #0, #1, #2, #3\n  Filter (#0 > 10)\n    SeqScan t\n";
+        check("SELECT * FROM t WHERE id > 10", above, above);
+        let LogicalPlan::Analyze { tables } = bind(&catalog, "ANALYZE t") else {
+            panic!()
+        };
+        analyze_table(&catalog, &tables[0], &AnalyzeConfig::new()).unwrap();
+        // With them, a few rows are in range. The tightest bound on each side is scanned, and
+        // the looser one stays in the filter.
+        check(
+            "SELECT * FROM t WHERE id > 10 AND id <= 20 AND id > 15",
+            range,
+            "Projection #0, #1, #2, #3\n\
+             \x20 Filter (#0 > 10)\n\
+             \x20   IndexScan t using t_id (15, 20]\n",
+        );
+        check("SELECT * FROM t WHERE id > 10", above, above);
+    }
+}
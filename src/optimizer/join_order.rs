@@ -0,0 +1,439 @@
+//! Chooses the order of a tree of inner joins by the estimated cost of the whole tree.
+//!
+//! The joins are flattened into their inputs and the conjuncts of their conditions, and the
+//! tree is built again bottom-up. With up to [`DP_LIMIT`] inputs, dynamic programming finds
+//! the cheapest join of every subset of the inputs from the cheapest joins of its two parts,
+//! which covers every tree shape. Above that, a greedy search joins the pair of partial trees
+//! whose join is cheapest until one tree is left. Each conjunct is evaluated at the lowest
+//! join that has all its columns, so it takes part in choosing a hash join there.
+//!
+//! Since the builder hashes the right input of a join, the cost model usually puts the smaller
+//! input on the right. A projection above the new tree restores the original column order.
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    planner::{
+        expr::Expr,
+        logical_plan::{JoinType, LogicalPlan},
+    },
+};
+
+use super::{
+    cost::{CostModel, Estimate},
+    predicate_pushdown::filter,
+};
+
+/// The most inputs whose order dynamic programming searches exhaustively.
+pub const DP_LIMIT: usize = 8;
+
+pub fn reorder_joins<B: BufferPoolManager + ?Sized>(
+    plan: LogicalPlan,
+    model: &CostModel<'_, '_, B>,
+) -> anyhow::Result<LogicalPlan> {
+    if !matches!(
+        plan,
+        LogicalPlan::Join {
+            join_type: JoinType::Inner,
+            ..
+        }
+    ) {
+        return plan.map_children(&mut |input| reorder_joins(input, model));
+    }
+
+    let schema = plan.schema();
+    let mut inputs = Vec::new();
+    let mut conjuncts = Vec::new();
+    flatten(plan, 0, &mut inputs, &mut conjuncts);
+
+    if inputs.len() > u64::BITS as usize {
+        anyhow::bail!("joins of more than {} tables are not supported", u64::BITS);
+    }
+    let widths: Vec<usize> = inputs
+        .iter()
+        .map(|input| input.schema().column_count())
+        .collect();
+    // Where the columns of each input start in the original row.
+    let mut offsets = Vec::new();
+    let mut width = 0;
+    for input_width in &widths {
+        offsets.push(width);
+        width += input_width;
+    }
+    let input_of = |column: usize| {
+        offsets
+            .iter()
+            .rposition(|&offset| offset <= column)
+            .unwrap()
+    };
+
+    // Conditions on a single input filter it; the others are placed in the tree.
+    let mut local = vec![Vec::new(); inputs.len()];
+    let mut placed = Vec::new();
+    for conjunct in conjuncts {
+        let mask = conjunct
+            .columns()
+            .into_iter()
+            .fold(0u64, |mask, column| mask | 1 << input_of(column));
+        if mask.count_ones() == 1 {
+            let input = mask.trailing_zeros() as usize;
+            let offset = offsets[input];
+            local[input].push(conjunct.map_columns(&|column| column - offset));
+        } else {
+            placed.push((mask, conjunct));
+        }
+    }
+    let mut trees = Vec::new();
+    for (i, (input, conditions)) in inputs.into_iter().zip(local).enumerate() {
+        let plan = filter(reorder_joins(input, model)?, conditions);
+        trees.push(Tree {
+            estimate: model.estimate(&plan)?,
+            plan,
+            mask: 1 << i,
+            inputs: vec![i],
+        });
+    }
+
+    let planner = Planner {
+        model,
+        offsets: &offsets,
+        widths: &widths,
+        conjuncts: &placed,
+    };
+    let tree = if trees.len() <= DP_LIMIT {
+        planner.dynamic_programming(trees)?
+    } else {
+        planner.greedy(trees)?
+    };
+
+    // Conjuncts that read no column go to the top join.
+    let constant: Vec<Expr> = placed
+        .iter()
+        .filter(|(mask, _)| *mask == 0)
+        .map(|(_, conjunct)| planner.remap(&tree.inputs, conjunct))
+        .collect();
+    let mut plan = tree.plan;
+    if !constant.is_empty() {
+        let LogicalPlan::Join { condition, .. } = &mut plan else {
+            unreachable!("a tree of several inputs is a join")
+        };
+        *condition = Expr::conjunction(condition.take().into_iter().chain(constant).collect());
+    }
+
+    let order: Vec<usize> = (0..width)
+        .map(|column| planner.position(&tree.inputs, column))
+        .collect();
+    if order.iter().enumerate().all(|(i, &position)| i == position) {
+        return Ok(plan);
+    }
+    let exprs = order
+        .into_iter()
+        .zip(schema.columns())
+        .map(|(position, column)| Expr::column(position, column.data_type))
+        .collect();
+    Ok(LogicalPlan::Projection {
+        input: Box::new(plan),
+        exprs,
+        schema,
+    })
+}
+
+/// Add the inputs of the inner joins of `plan` and the conjuncts of their conditions, over the
+/// row of all the inputs, whose columns start at `offset`.
+fn flatten(
+    plan: LogicalPlan,
+    offset: usize,
+    inputs: &mut Vec<LogicalPlan>,
+    conjuncts: &mut Vec<Expr>,
+) {
+    match plan {
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type: JoinType::Inner,
+            condition,
+        } => {
+            let left_width = left.schema().column_count();
+            flatten(*left, offset, inputs, conjuncts);
+            flatten(*right, offset + left_width, inputs, conjuncts);
+            if let Some(condition) = condition {
+                conjuncts.extend(
+                    condition
+                        .split_conjunction()
+                        .into_iter()
+                        .map(|conjunct| conjunct.map_columns(&|column| column + offset)),
+                );
+            }
+        }
+        plan => inputs.push(plan),
+    }
+}
+
+/// A join of some of the inputs.
+#[derive(Clone)]
+struct Tree {
+    plan: LogicalPlan,
+    estimate: Estimate,
+    /// The inputs it joins, as bits.
+    mask: u64,
+    /// The inputs in the order of their columns.
+    inputs: Vec<usize>,
+}
+
+struct Planner<'p, 'm, 'c, 'a, B: BufferPoolManager + ?Sized> {
+    model: &'m CostModel<'c, 'a, B>,
+    /// Where the columns of each input start in the original row.
+    offsets: &'p [usize],
+    widths: &'p [usize],
+    /// The conjuncts over several inputs with the inputs they read, as bits.
+    conjuncts: &'p [(u64, Expr)],
+}
+
+impl<B: BufferPoolManager + ?Sized> Planner<'_, '_, '_, '_, B> {
+    fn dynamic_programming(&self, inputs: Vec<Tree>) -> anyhow::Result<Tree> {
+        let n = inputs.len();
+        let mut best: Vec<Option<Tree>> = vec![None; 1 << n];
+        for tree in inputs {
+            let mask = tree.mask as usize;
+            best[mask] = Some(tree);
+        }
+        for mask in 1..1usize << n {
+            if mask.count_ones() < 2 {
+                continue;
+            }
+            // Every split into two non-empty parts, in both orders.
+            let mut left = (mask - 1) & mask;
+            while left > 0 {
+                let right = mask & !left;
+                if let (Some(l), Some(r)) = (&best[left], &best[right]) {
+                    let tree = self.join(l, r)?;
+                    if best[mask]
+                        .as_ref()
+                        .map_or(true, |current| tree.estimate.cost < current.estimate.cost)
+                    {
+                        best[mask] = Some(tree);
+                    }
+                }
+                left = (left - 1) & mask;
+            }
+        }
+        Ok(best.pop().flatten().unwrap())
+    }
+
+    fn greedy(&self, mut trees: Vec<Tree>) -> anyhow::Result<Tree> {
+        while trees.len() > 1 {
+            let mut cheapest: Option<(usize, usize, Tree)> = None;
+            for i in 0..trees.len() {
+                for j in 0..trees.len() {
+                    if i == j {
+                        continue;
+                    }
+                    let tree = self.join(&trees[i], &trees[j])?;
+                    if cheapest.as_ref().map_or(true, |(_, _, current)| {
+                        tree.estimate.cost < current.estimate.cost
+                    }) {
+                        cheapest = Some((i, j, tree));
+                    }
+                }
+            }
+            let (i, j, tree) = cheapest.unwrap();
+            trees.remove(i.max(j));
+            trees.remove(i.min(j));
+            trees.push(tree);
+        }
+        Ok(trees.pop().unwrap())
+    }
+
+    /// The join of two trees with the conjuncts that read both.
+    fn join(&self, left: &Tree, right: &Tree) -> anyhow::Result<Tree> {
+        let mask = left.mask | right.mask;
+        let mut inputs = left.inputs.clone();
+        inputs.extend(&right.inputs);
+        let condition = Expr::conjunction(
+            self.conjuncts
+                .iter()
+                .filter(|(conjunct_mask, _)| {
+                    *conjunct_mask & !mask == 0
+                        && *conjunct_mask & left.mask != 0
+                        && *conjunct_mask & right.mask != 0
+                })
+                .map(|(_, conjunct)| self.remap(&inputs, conjunct))
+                .collect(),
+        );
+        let estimate = self.model.estimate_join(
+            (&left.plan, left.estimate),
+            (&right.plan, right.estimate),
+            JoinType::Inner,
+            condition.as_ref(),
+        )?;
+        Ok(Tree {
+            plan: LogicalPlan::Join {
+                left: Box::new(left.plan.clone()),
+                right: Box::new(right.plan.clone()),
+                join_type: JoinType::Inner,
+                condition,
+            },
+            estimate,
+            mask,
+            inputs,
+        })
+    }
+
+    /// A conjunct over the original row as one over the row of `inputs` in that order.
+    fn remap(&self, inputs: &[usize], conjunct: &Expr) -> Expr {
+        conjunct
+            .clone()
+            .map_columns(&|column| self.position(inputs, column))
+    }
+
+    /// The position of a column of the original row in the row of `inputs` in that order.
+    fn position(&self, inputs: &[usize], column: usize) -> usize {
+        let input = self
+            .offsets
+            .iter()
+            .rposition(|&offset| offset <= column)
+            .unwrap();
+        let mut position = column - self.offsets[input];
+        for &other in inputs.iter().take_while(|&&other| other != input) {
+            position += self.widths[other];
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        execution::{builder::build_executor, context::ExecutorContext},
+        optimizer::{
+            constant_folding::fold_constants, decorrelation::decorrelate,
+            predicate_pushdown::push_down_predicates,
+        },
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    fn bind(catalog: &Catalog<BufferPoolManagerImpl>, sql: &str) -> LogicalPlan {
+        let statement = parse_statement(sql).unwrap();
+        Binder::new(catalog).bind(&statement).unwrap()
+    }
+
+    #[test]
+    fn test_reorder_joins() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        for (name, rows) in [("big", 2000), ("mid", 200), ("small", 10)] {
+            catalog
+                .create_table(
+                    name,
+                    Schema::new(vec![
+                        Column::new("id", DataType::Int64).not_null(),
+                        Column::new("ref", DataType::Int64),
+                        Column::new("v", DataType::Int32),
+                    ]),
+                )
+                .unwrap();
+            let values: Vec<String> = (0..rows)
+                .map(|i| format!("({i}, {}, {})", i % 200, i % 7))
+                .collect();
+            let insert = bind(
+                &catalog,
+                &format!("INSERT INTO {name} VALUES {}", values.join(", ")),
+            );
+            let mut executor = build_executor(&ctx, &insert).unwrap();
+            executor.init().unwrap();
+            while executor.next().unwrap().is_some() {}
+        }
+        // Few enough rows in memory that hashing the big table would spill.
+        let model = CostModel::new(&catalog, 100);
+        // The plan of `sql` as the rules before this one leave it.
+        let plan = |sql: &str| {
+            fold_constants(bind(&catalog, sql))
+                .and_then(decorrelate)
+                .and_then(push_down_predicates)
+                .unwrap()
+        };
+        let check = |sql: &str, before: &str, after: &str| {
+            let plan = plan(sql);
+            assert_eq!(plan.to_string(), before);
+            let reordered = reorder_joins(plan.clone(), &model).unwrap();
+            assert_eq!(reordered.to_string(), after);
+            // Reordering never makes the plan more expensive, nor changes its output.
+            assert!(
+                model.estimate(&reordered).unwrap().cost <= model.estimate(&plan).unwrap().cost
+            );
+            assert_eq!(reordered.schema(), plan.schema());
+        };
+
+        // Written with the small table first, the big one would be hashed. Reordered, each
+        // input hashes the smaller one, and the conditions move to the joins of their tables.
+        check(
+            "SELECT * FROM small s, mid m, big b WHERE b.ref = m.id AND m.ref = s.id",
+            "Projection #0, #1, #2, #3, #4, #5, #6, #7, #8\n\
+             \x20 Join Inner on (#7 = #3)\n\
+             \x20   Join Inner on (#4 = #0)\n\
+             \x20     SeqScan small\n\
+             \x20     SeqScan mid\n\
+             \x20   SeqScan big\n",
+            "Projection #0, #1, #2, #3, #4, #5, #6, #7, #8\n\
+             \x20 Projection #6, #7, #8, #3, #4, #5, #0, #1, #2\n\
+             \x20   Join Inner on (#1 = #3)\n\
+             \x20     SeqScan big\n\
+             \x20     Join Inner on (#1 = #3)\n\
+             \x20       SeqScan mid\n\
+             \x20       SeqScan small\n",
+        );
+        // Without an equality to hash on, the nested loop runs over the small table and scans the
+        // big one once for each of its rows.
+        check(
+            "SELECT * FROM big b JOIN small s ON b.v < s.v",
+            "Projection #0, #1, #2, #3, #4, #5\n\
+             \x20 Join Inner on (#2 < #5)\n\
+             \x20   SeqScan big\n\
+             \x20   SeqScan small\n",
+            "Projection #0, #1, #2, #3, #4, #5\n\
+             \x20 Projection #3, #4, #5, #0, #1, #2\n\
+             \x20   Join Inner on (#5 < #2)\n\
+             \x20     SeqScan small\n\
+             \x20     SeqScan big\n",
+        );
+        // An outer join is not reordered, but joined as one input.
+        check(
+            "SELECT * FROM small s LEFT JOIN big b ON b.ref = s.id JOIN mid m ON m.id = s.id",
+            "Projection #0, #1, #2, #3, #4, #5, #6, #7, #8\n\
+             \x20 Join Inner on (#6 = #0)\n\
+             \x20   Join Left on (#4 = #0)\n\
+             \x20     SeqScan small\n\
+             \x20     SeqScan big\n\
+             \x20   SeqScan mid\n",
+            "Projection #0, #1, #2, #3, #4, #5, #6, #7, #8\n\
+             \x20 Projection #3, #4, #5, #6, #7, #8, #0, #1, #2\n\
+             \x20   Join Inner on (#0 = #3)\n\
+             \x20     SeqScan mid\n\
+             \x20     Join Left on (#4 = #0)\n\
+             \x20       SeqScan small\n\
+             \x20       SeqScan big\n",
+        );
+    }
+}
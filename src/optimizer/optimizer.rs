@@ -0,0 +1,326 @@
+//! The optimizer turns a bound plan into an equivalent one that is cheaper to execute.
+//!
+//! It applies its rules once each, in an order where every rule leaves the plan in the shape
+//! the next expects:
+//!
+//! | rule                                                | why here                                     |
+//! |-----------------------------------------------------|----------------------------------------------|
+//! | [constant folding](super::constant_folding)         | simplifies the conditions the others analyze |
+//! | [decorrelation](super::decorrelation)               | subqueries become joins the others can move  |
+//! | [predicate pushdown](super::predicate_pushdown)     | puts conditions right above the scans        |
+//! | [index selection](super::index_selection)           | needs the conditions on the scans            |
+//! | [join ordering](super::join_order)                  | costs the inputs as they will be scanned     |
+//! | [projection pruning](super::projection_pruning)     | last, as it adds projections between nodes   |
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager, catalog::catalog::Catalog,
+    execution::context::DEFAULT_WORK_MEM, planner::logical_plan::LogicalPlan,
+};
+
+use super::{
+    constant_folding::fold_constants, cost::CostModel, decorrelation::decorrelate,
+    index_selection::select_indexes, join_order::reorder_joins,
+    predicate_pushdown::push_down_predicates, projection_pruning::prune_projections,
+};
+
+pub struct Optimizer<'c, 'a, B: BufferPoolManager + ?Sized> {
+    catalog: &'c Catalog<'a, B>,
+    /// The number of tuples an executor keeps in memory, as in
+    /// [`ExecutorContext`](crate::execution::context::ExecutorContext).
+    work_mem: usize,
+}
+
+impl<'c, 'a, B: BufferPoolManager + ?Sized> Optimizer<'c, 'a, B> {
+    pub fn new(catalog: &'c Catalog<'a, B>) -> Self {
+        Self {
+            catalog,
+            work_mem: DEFAULT_WORK_MEM,
+        }
+    }
+
+    pub fn with_work_mem(self, work_mem: usize) -> Self {
+        Self { work_mem, ..self }
+    }
+
+    pub fn optimize(&self, plan: LogicalPlan) -> anyhow::Result<LogicalPlan> {
+        let model = CostModel::new(self.catalog, self.work_mem);
+        let plan = fold_constants(plan)?;
+        let plan = decorrelate(plan)?;
+        let plan = push_down_predicates(plan)?;
+        let plan = select_indexes(plan, &model)?;
+        let plan = reorder_joins(plan, &model)?;
+        prune_projections(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        catalog::schema::{Column, Schema},
+        execution::{
+            analyze::{analyze_table, AnalyzeConfig},
+            builder::build_executor,
+            context::ExecutorContext,
+        },
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    fn bind(catalog: &Catalog<BufferPoolManagerImpl>, sql: &str) -> LogicalPlan {
+        let statement = parse_statement(sql).unwrap();
+        Binder::new(catalog).bind(&statement).unwrap()
+    }
+
+    #[test]
+    fn test_optimize() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        for (name, rows) in [("big", 2000), ("mid", 200), ("small", 10)] {
+            catalog
+                .create_table(
+                    name,
+                    Schema::new(vec![
+                        Column::new("id", DataType::Int64).not_null(),
+                        Column::new("ref", DataType::Int64),
+                        Column::new("v", DataType::Int32),
+                    ]),
+                )
+                .unwrap();
+            let values: Vec<String> = (0..rows)
+                .map(|i| format!("({i}, {}, {})", i % 200, i % 7))
+                .collect();
+            let insert = bind(
+                &catalog,
+                &format!("INSERT INTO {name} VALUES {}", values.join(", ")),
+            );
+            let mut executor = build_executor(&ctx, &insert).unwrap();
+            executor.init().unwrap();
+            while executor.next().unwrap().is_some() {}
+        }
+        catalog
+            .create_index("big_pkey", "big", &["id"], IndexKind::BPlusTree, true)
+            .unwrap();
+        // Few enough rows in memory that hashing the big table would spill.
+        let optimize = |sql: &str| {
+            Optimizer::new(&catalog)
+                .with_work_mem(100)
+                .optimize(bind(&catalog, sql))
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            optimize("SELECT v FROM big WHERE id = 42 AND 1 + 1 = 2"),
+            "Projection #2\n  IndexScan big using big_pkey [42, 42]\n"
+        );
+        // A third of the table costs more through the index than the table has pages.
+        assert_eq!(
+            optimize("SELECT v FROM big WHERE id > 1000"),
+            "Projection #1\n  Filter (#0 > 1000)\n    Projection #0, #2\n      SeqScan big\n"
+        );
+        assert_eq!(
+            optimize("UPDATE big SET v = 0 WHERE id = 42"),
+            "Update big #2=0\n  IndexScan big using big_pkey [42, 42]\n"
+        );
+        // The scan must not find the rows it moves further along the index.
+        assert_eq!(
+            optimize("UPDATE big SET id = id + 1 WHERE id = 42"),
+            "Update big #0=(#0 + 1)\n  Filter (#0 = 42)\n    SeqScan big\n"
+        );
+
+        assert_eq!(
+            optimize(
+                "SELECT s.v FROM big b JOIN small s ON b.ref = s.id WHERE s.v > 2 AND b.v = 1"
+            ),
+            "Projection #1\n\
+             \x20 Join Inner on (#2 = #0)\n\
+             \x20   Filter (#1 > 2)\n\
+             \x20     Projection #0, #2\n\
+             \x20       SeqScan small\n\
+             \x20   Filter (#1 = 1)\n\
+             \x20     Projection #1, #2\n\
+             \x20       SeqScan big\n"
+        );
+        // Written with the small table first, the joins are ordered so that the big one is
+        // not hashed, and the projection that restores the order of the columns is merged
+        // into the one above.
+        assert_eq!(
+            optimize(
+                "SELECT b.id, s.v FROM small s, mid m, big b WHERE b.ref = m.id AND m.ref = s.id"
+            ),
+            "Projection #0, #5\n\
+             \x20 Join Inner on (#1 = #2)\n\
+             \x20   Projection #0, #1\n\
+             \x20     SeqScan big\n\
+             \x20   Join Inner on (#1 = #2)\n\
+             \x20     Projection #0, #1\n\
+             \x20       SeqScan mid\n\
+             \x20     Projection #0, #2\n\
+             \x20       SeqScan small\n"
+        );
+        // The condition on the padded side stays above the outer join, the one in ON goes
+        // below it.
+        assert_eq!(
+            optimize(
+                "SELECT b.id FROM big b LEFT JOIN small s ON b.ref = s.id AND s.v = 1 \
+                 WHERE s.id IS NULL"
+            ),
+            "Projection #0\n\
+             \x20 Filter #2 IS NULL\n\
+             \x20   Join Left on (#1 = #2)\n\
+             \x20     Projection #0, #1\n\
+             \x20       SeqScan big\n\
+             \x20     Filter (#1 = 1)\n\
+             \x20       Projection #0, #2\n\
+             \x20         SeqScan small\n"
+        );
+        assert_eq!(
+            optimize("SELECT s.id FROM small s WHERE s.id IN (SELECT ref FROM mid WHERE v = 1)"),
+            "Projection #0\n\
+             \x20 Join Semi on (#0 = #1)\n\
+             \x20   Projection #0\n\
+             \x20     SeqScan small\n\
+             \x20   Filter (#1 = 1)\n\
+             \x20     Projection #1, #2\n\
+             \x20       SeqScan mid\n"
+        );
+        // Only the condition on the group goes below the aggregation.
+        assert_eq!(
+            optimize("SELECT v, count(*) FROM mid GROUP BY v HAVING v > 2 AND count(*) > 1"),
+            "Projection #0, #1\n\
+             \x20 Filter (#1 > 1)\n\
+             \x20   Aggregate group_by=[#0] aggregates=[count(*)]\n\
+             \x20     Filter (#0 > 2)\n\
+             \x20       Projection #2\n\
+             \x20         SeqScan mid\n"
+        );
+    }
+
+    #[test]
+    fn test_statistics() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager = TempDiskManager::new(
+            DEFAULT_PAGE_SIZE,
+            TempDiskManager::path_for(tempdir.path().join("test.db")),
+        )
+        .unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let ctx = ExecutorContext::new(&catalog, &temp);
+        catalog
+            .create_table(
+                "events",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("kind", DataType::Int64),
+                    Column::new("payload", DataType::Varchar(None)),
+                ]),
+            )
+            .unwrap();
+        // Almost every event is of kind 0, and the rest of a kind of their own.
+        let payload = "x".repeat(100);
+        let values: Vec<String> = (0..2000)
+            .map(|i| format!("({i}, {}, '{payload}')", if i % 20 == 0 { i } else { 0 }))
+            .collect();
+        let insert = bind(
+            &catalog,
+            &format!("INSERT INTO events VALUES {}", values.join(", ")),
+        );
+        let mut executor = build_executor(&ctx, &insert).unwrap();
+        executor.init().unwrap();
+        while executor.next().unwrap().is_some() {}
+        catalog
+            .create_index("events_id", "events", &["id"], IndexKind::BPlusTree, false)
+            .unwrap();
+        catalog
+            .create_index(
+                "events_kind",
+                "events",
+                &["kind"],
+                IndexKind::BPlusTree,
+                false,
+            )
+            .unwrap();
+        catalog
+            .create_index(
+                "events_payload_id",
+                "events",
+                &["payload", "id"],
+                IndexKind::BPlusTree,
+                true,
+            )
+            .unwrap();
+        let optimize = |sql: &str| {
+            Optimizer::new(&catalog)
+                .optimize(bind(&catalog, sql))
+                .unwrap()
+                .to_string()
+        };
+
+        // Without statistics, every kind is as common as any other, and a range keeps a third.
+        assert_eq!(
+            optimize("SELECT id FROM events WHERE kind = 0"),
+            "Projection #0\n  IndexScan events using events_kind [0, 0]\n"
+        );
+        assert_eq!(
+            optimize("SELECT kind FROM events WHERE id >= 1990"),
+            "Projection #1\n  Filter (#0 >= 1990)\n    Projection #0, #1\n      SeqScan events\n"
+        );
+        // A composite index is scanned by its first column, of any type.
+        assert_eq!(
+            optimize("SELECT id FROM events WHERE payload = 'y'"),
+            "Projection #0\n  IndexScan events using events_payload_id [y, y]\n"
+        );
+
+        let LogicalPlan::Analyze { tables } = bind(&catalog, "ANALYZE") else {
+            panic!()
+        };
+        assert_eq!(tables.len(), 1);
+        analyze_table(&catalog, &tables[0], &AnalyzeConfig::new()).unwrap();
+        assert_eq!(
+            optimize("SELECT id FROM events WHERE kind = 0"),
+            "Projection #0\n  Filter (#1 = 0)\n    Projection #0, #1\n      SeqScan events\n"
+        );
+        assert_eq!(
+            optimize("SELECT id FROM events WHERE kind = 40"),
+            "Projection #0\n  IndexScan events using events_kind [40, 40]\n"
+        );
+        assert_eq!(
+            optimize("SELECT kind FROM events WHERE id >= 1990"),
+            "Projection #1\n  IndexScan events using events_id [1990, +inf)\n"
+        );
+        let model = CostModel::new(&catalog, DEFAULT_WORK_MEM);
+        let rows = |sql: &str| {
+            let plan = Optimizer::new(&catalog)
+                .optimize(bind(&catalog, sql))
+                .unwrap();
+            model.estimate(&plan).unwrap().rows
+        };
+        assert_eq!(rows("SELECT * FROM events"), 2000.0);
+        assert!((rows("SELECT * FROM events WHERE kind = 0") - 1901.0).abs() < 1.0);
+        assert!((rows("SELECT * FROM events WHERE id < 500") - 500.0).abs() < 20.0);
+        assert_eq!(rows("SELECT * FROM events WHERE kind IS NULL"), 0.0);
+    }
+}
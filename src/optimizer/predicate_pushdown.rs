@@ -0,0 +1,356 @@
+//! Moves the conditions of filters as close to the scans as they can go, so that fewer rows
+//! flow through the operators above them.
+//!
+//! A condition moves below an operator only if that keeps the same rows:
+//!
+//! - through projections, sorts and DISTINCT, rewritten over the input of a projection;
+//! - through an aggregation if it reads only group-by values that every grouping set has,
+//!   since it then keeps or drops whole groups;
+//! - into the input of an inner join whose columns it reads, or into the join condition if it
+//!   reads both;
+//! - into the preserved input of an outer join, and conditions of an outer join on the other
+//!   input into that input, but nothing through a FULL join;
+//! - into the left input of a semi or anti join, whose output is the left rows.
+//!
+//! It never moves below a LIMIT, which would change which rows are counted.
+
+use crate::planner::{
+    expr::Expr,
+    logical_plan::{JoinType, LogicalPlan},
+};
+
+pub fn push_down_predicates(plan: LogicalPlan) -> anyhow::Result<LogicalPlan> {
+    push(plan, Vec::new())
+}
+
+/// `plan` filtered by `predicates`, which are over its output.
+fn push(plan: LogicalPlan, mut predicates: Vec<Expr>) -> anyhow::Result<LogicalPlan> {
+    Ok(match plan {
+        LogicalPlan::Filter { input, predicate } => {
+            predicates.extend(predicate.split_conjunction());
+            push(*input, predicates)?
+        }
+        LogicalPlan::Projection {
+            input,
+            exprs,
+            schema,
+        } => {
+            let predicates = predicates
+                .into_iter()
+                .map(|predicate| predicate.substitute(&exprs))
+                .collect();
+            LogicalPlan::Projection {
+                input: Box::new(push(*input, predicates)?),
+                exprs,
+                schema,
+            }
+        }
+        LogicalPlan::Sort { input, keys } => LogicalPlan::Sort {
+            input: Box::new(push(*input, predicates)?),
+            keys,
+        },
+        LogicalPlan::Distinct { input } => LogicalPlan::Distinct {
+            input: Box::new(push(*input, predicates)?),
+        },
+        LogicalPlan::Aggregate {
+            input,
+            group_by,
+            grouping_sets,
+            aggregates,
+            schema,
+        } => {
+            let in_every_set =
+                |column: &usize| grouping_sets.iter().all(|set| set.contains(column));
+            let (below, above): (Vec<_>, Vec<_>) = predicates.into_iter().partition(|predicate| {
+                // A constant condition would make an aggregation without groups output
+                // its row regardless.
+                let columns = predicate.columns();
+                !columns.is_empty() && columns.iter().all(in_every_set)
+            });
+            let below = below
+                .into_iter()
+                .map(|predicate| predicate.substitute(&group_by))
+                .collect();
+            filter(
+                LogicalPlan::Aggregate {
+                    input: Box::new(push(*input, below)?),
+                    group_by,
+                    grouping_sets,
+                    aggregates,
+                    schema,
+                },
+                above,
+            )
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            condition,
+        } => {
+            let left_width = left.schema().column_count();
+            let side = |expr: &Expr| {
+                let columns = expr.columns();
+                if columns.iter().all(|&column| column < left_width) {
+                    Side::Left
+                } else if columns.iter().all(|&column| column >= left_width) {
+                    Side::Right
+                } else {
+                    Side::Both
+                }
+            };
+            let conditions = condition.map_or_else(Vec::new, Expr::split_conjunction);
+            let (mut to_left, mut to_right, mut on, mut above) =
+                (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+            // Where a filter above the join and a condition of the join can go.
+            let (filter_sides, condition_sides): (&[Side], &[Side]) = match join_type {
+                JoinType::Inner => (&[Side::Left, Side::Right], &[Side::Left, Side::Right]),
+                JoinType::Left => (&[Side::Left], &[Side::Right]),
+                JoinType::Right => (&[Side::Right], &[Side::Left]),
+                JoinType::Full => (&[], &[]),
+                JoinType::Semi => (&[Side::Left], &[Side::Left, Side::Right]),
+                JoinType::Anti => (&[Side::Left], &[Side::Right]),
+            };
+            for (exprs, sides, is_condition) in [
+                (predicates, filter_sides, false),
+                (conditions, condition_sides, true),
+            ] {
+                for expr in exprs {
+                    let expr_side = side(&expr);
+                    if !sides.contains(&expr_side) {
+                        if is_condition || join_type == JoinType::Inner {
+                            on.push(expr);
+                        } else {
+                            above.push(expr);
+                        }
+                    } else if expr_side == Side::Left {
+                        to_left.push(expr);
+                    } else {
+                        to_right.push(expr.map_columns(&|column| column - left_width));
+                    }
+                }
+            }
+            filter(
+                LogicalPlan::Join {
+                    left: Box::new(push(*left, to_left)?),
+                    right: Box::new(push(*right, to_right)?),
+                    join_type,
+                    condition: Expr::conjunction(on),
+                },
+                above,
+            )
+        }
+        LogicalPlan::SeqScan { .. }
+        | LogicalPlan::IndexScan { .. }
+        | LogicalPlan::Values { .. }
+        | LogicalPlan::Limit { .. }
+        | LogicalPlan::Insert { .. }
+        | LogicalPlan::Update { .. }
+        | LogicalPlan::Delete { .. }
+        | LogicalPlan::Explain { .. }
+        | LogicalPlan::CreateTable { .. }
+        | LogicalPlan::DropTable { .. }
+        | LogicalPlan::CreateIndex { .. }
+        | LogicalPlan::DropIndex { .. }
+        | LogicalPlan::Analyze { .. } => filter(
+            plan.map_children(&mut |input| push(input, Vec::new()))?,
+            predicates,
+        ),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+    /// Columns of both inputs.
+    Both,
+}
+
+/// `plan` filtered by the AND of `predicates`, or `plan` itself if there are none.
+pub fn filter(plan: LogicalPlan, predicates: Vec<Expr>) -> LogicalPlan {
+    match Expr::conjunction(predicates) {
+        Some(predicate) => LogicalPlan::Filter {
+            input: Box::new(plan),
+            predicate,
+        },
+        None => plan,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        optimizer::{constant_folding::fold_constants, decorrelation::decorrelate},
+        planner::binder::Binder,
+        sql::{ast::BinaryOp, parser::parse_statement},
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::{data_type::DataType, value::Value},
+    };
+
+    use super::*;
+
+    /// The plan of `sql` as decorrelated, the rule before this one.
+    fn plan(catalog: &Catalog<BufferPoolManagerImpl>, sql: &str) -> LogicalPlan {
+        let statement = parse_statement(sql).unwrap();
+        let plan = Binder::new(catalog).bind(&statement).unwrap();
+        decorrelate(fold_constants(plan).unwrap()).unwrap()
+    }
+
+    /// Check the plan before and after pushing down its predicates.
+    fn check(plan: LogicalPlan, before: &str, after: &str) {
+        assert_eq!(plan.to_string(), before);
+        assert_eq!(push_down_predicates(plan).unwrap().to_string(), after);
+    }
+
+    #[test]
+    fn test_push_down_predicates() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        for name in ["t", "u"] {
+            let schema = Schema::new(vec![
+                Column::new("a", DataType::Int64).not_null(),
+                Column::new("b", DataType::Int64),
+                Column::new("c", DataType::Int32),
+            ]);
+            catalog.create_table(name, schema).unwrap();
+        }
+
+        // Each side's conditions go into its input, and the one over both into the join.
+        check(
+            plan(
+                &catalog,
+                "SELECT * FROM t JOIN u ON t.a = u.a WHERE t.b = 1 AND u.c = 2 AND t.c = u.c",
+            ),
+            "Projection #0, #1, #2, #3, #4, #5\n\
+             \x20 Filter (((#1 = 1) AND (#5 = 2)) AND (#2 = #5))\n\
+             \x20   Join Inner on (#0 = #3)\n\
+             \x20     SeqScan t\n\
+             \x20     SeqScan u\n",
+            "Projection #0, #1, #2, #3, #4, #5\n\
+             \x20 Join Inner on ((#2 = #5) AND (#0 = #3))\n\
+             \x20   Filter (#1 = 1)\n\
+             \x20     SeqScan t\n\
+             \x20   Filter (#2 = 2)\n\
+             \x20     SeqScan u\n",
+        );
+
+        // Of a LEFT join, a WHERE condition on the padded side stays above it, as it would
+        // drop the padded rows, and so does an ON condition on the preserved side, which only
+        // decides what matches. The others go into the inputs.
+        check(
+            plan(
+                &catalog,
+                "SELECT t.a FROM t LEFT JOIN u ON t.a = u.a AND u.c = 2 AND t.c = 3 \
+                 WHERE t.b = 1 AND u.b = 1",
+            ),
+            "Projection #0\n\
+             \x20 Filter ((#1 = 1) AND (#4 = 1))\n\
+             \x20   Join Left on (((#0 = #3) AND (#5 = 2)) AND (#2 = 3))\n\
+             \x20     SeqScan t\n\
+             \x20     SeqScan u\n",
+            "Projection #0\n\
+             \x20 Filter (#4 = 1)\n\
+             \x20   Join Left on ((#0 = #3) AND (#2 = 3))\n\
+             \x20     Filter (#1 = 1)\n\
+             \x20       SeqScan t\n\
+             \x20     Filter (#2 = 2)\n\
+             \x20       SeqScan u\n",
+        );
+        // RIGHT joins mirror that.
+        check(
+            plan(
+                &catalog,
+                "SELECT u.a FROM t RIGHT JOIN u ON t.a = u.a AND t.c = 2 WHERE t.b = 1 AND u.b = 1",
+            ),
+            "Projection #3\n\
+             \x20 Filter ((#1 = 1) AND (#4 = 1))\n\
+             \x20   Join Right on ((#0 = #3) AND (#2 = 2))\n\
+             \x20     SeqScan t\n\
+             \x20     SeqScan u\n",
+            "Projection #3\n\
+             \x20 Filter (#1 = 1)\n\
+             \x20   Join Right on (#0 = #3)\n\
+             \x20     Filter (#2 = 2)\n\
+             \x20       SeqScan t\n\
+             \x20     Filter (#1 = 1)\n\
+             \x20       SeqScan u\n",
+        );
+        // Nothing goes through a FULL join.
+        let full = "Projection #0\n\
+                    \x20 Filter ((#1 = 1) AND (#4 = 1))\n\
+                    \x20   Join Full on ((#0 = #3) AND (#2 = 2))\n\
+                    \x20     SeqScan t\n\
+                    \x20     SeqScan u\n";
+        check(
+            plan(
+                &catalog,
+                "SELECT t.a FROM t FULL JOIN u ON t.a = u.a AND t.c = 2 WHERE t.b = 1 AND u.b = 1",
+            ),
+            full,
+            full,
+        );
+
+        // The output of a semi join is its left rows.
+        check(
+            plan(
+                &catalog,
+                "SELECT a FROM t WHERE b IN (SELECT b FROM u) AND c = 1",
+            ),
+            "Projection #0\n\
+             \x20 Filter (#2 = 1)\n\
+             \x20   Join Semi on (#1 = #4)\n\
+             \x20     SeqScan t\n\
+             \x20     SeqScan u\n",
+            "Projection #0\n\
+             \x20 Join Semi on (#1 = #4)\n\
+             \x20   Filter (#2 = 1)\n\
+             \x20     SeqScan t\n\
+             \x20   SeqScan u\n",
+        );
+
+        // Of a ROLLUP, only a condition on a column every grouping set has moves below it.
+        check(
+            plan(
+                &catalog,
+                "SELECT b, c, count(*) FROM t GROUP BY b, ROLLUP (c) HAVING b > 1 AND c > 1",
+            ),
+            "Projection #0, #1, #2\n\
+             \x20 Filter ((#0 > 1) AND (#1 > 1))\n\
+             \x20   Aggregate group_by=[#1, #2] grouping_sets=[(0, 1), (0)] aggregates=[count(*)]\n\
+             \x20     SeqScan t\n",
+            "Projection #0, #1, #2\n\
+             \x20 Filter (#1 > 1)\n\
+             \x20   Aggregate group_by=[#1, #2] grouping_sets=[(0, 1), (0)] aggregates=[count(*)]\n\
+             \x20     Filter (#1 > 1)\n\
+             \x20       SeqScan t\n",
+        );
+
+        // A condition above a LIMIT stays there.
+        let limited = filter(
+            plan(&catalog, "SELECT a, b FROM t LIMIT 5"),
+            vec![Expr::binary(
+                BinaryOp::Eq,
+                Expr::column(1, DataType::Int64),
+                Expr::literal(Value::Int64(1), DataType::Int64),
+                DataType::Boolean,
+            )],
+        );
+        let limit = "Filter (#1 = 1)\n\
+                     \x20 Limit 5 offset=0\n\
+                     \x20   Projection #0, #1\n\
+                     \x20     SeqScan t\n";
+        check(limited, limit, limit);
+    }
+}
@@ -0,0 +1,334 @@
+//! Removes the columns no operator above reads, so that joins, aggregations and their spilled
+//! rows carry only what is needed.
+//!
+//! Every operator is asked for the columns its parent reads and returns a plan of at least
+//! those columns, in their original order, with the list of the original columns it kept, by
+//! which the parent rewrites its expressions. Scans are narrowed by a projection, projections
+//! drop the expressions nobody reads and absorb a projection below them, and aggregations drop
+//! the aggregates nobody reads. DISTINCT needs all its columns, since they decide which rows
+//! are duplicates, and so do modifications, which write whole rows.
+
+use std::collections::BTreeSet;
+
+use crate::planner::{
+    expr::{AggregateExpr, Expr},
+    logical_plan::{LogicalPlan, SortKey},
+};
+
+pub fn prune_projections(plan: LogicalPlan) -> anyhow::Result<LogicalPlan> {
+    let required = (0..plan.schema().column_count()).collect();
+    Ok(prune(plan, &required)?.0)
+}
+
+/// `plan` with the columns in `required` and possibly more, and the original positions of the
+/// columns it has.
+fn prune(
+    plan: LogicalPlan,
+    required: &BTreeSet<usize>,
+) -> anyhow::Result<(LogicalPlan, Vec<usize>)> {
+    Ok(match plan {
+        LogicalPlan::SeqScan { .. } | LogicalPlan::IndexScan { .. } => {
+            let schema = plan.schema();
+            if required.len() == schema.column_count() {
+                let columns = (0..schema.column_count()).collect();
+                return Ok((plan, columns));
+            }
+            let columns: Vec<usize> = required.iter().copied().collect();
+            let exprs = columns
+                .iter()
+                .map(|&column| Expr::column(column, schema.column(column).data_type))
+                .collect();
+            let projection = LogicalPlan::Projection {
+                input: Box::new(plan),
+                exprs,
+                schema: schema.project(&columns),
+            };
+            (projection, columns)
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            let mut required = required.clone();
+            required.extend(predicate.columns());
+            let (input, columns) = prune(*input, &required)?;
+            let filter = LogicalPlan::Filter {
+                input: Box::new(input),
+                predicate: remap(predicate, &columns),
+            };
+            (filter, columns)
+        }
+        LogicalPlan::Projection {
+            input,
+            exprs,
+            schema,
+        } => {
+            let columns: Vec<usize> = required.iter().copied().collect();
+            let kept: Vec<Expr> = exprs
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| required.contains(i))
+                .map(|(_, expr)| expr)
+                .collect();
+            let input_required = kept.iter().flat_map(Expr::columns).collect();
+            let (input, input_columns) = prune(*input, &input_required)?;
+            let exprs: Vec<Expr> = kept
+                .into_iter()
+                .map(|expr| remap(expr, &input_columns))
+                .collect();
+            let projection = match input {
+                // Two projections in a row compute the outer expressions over the inner ones.
+                LogicalPlan::Projection {
+                    input,
+                    exprs: inner,
+                    ..
+                } => LogicalPlan::Projection {
+                    input,
+                    exprs: exprs
+                        .into_iter()
+                        .map(|expr| expr.substitute(&inner))
+                        .collect(),
+                    schema: schema.project(&columns),
+                },
+                input => LogicalPlan::Projection {
+                    input: Box::new(input),
+                    exprs,
+                    schema: schema.project(&columns),
+                },
+            };
+            (projection, columns)
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            condition,
+        } => {
+            let left_width = left.schema().column_count();
+            let mut left_required = BTreeSet::new();
+            let mut right_required = BTreeSet::new();
+            for column in required
+                .iter()
+                .copied()
+                .chain(condition.iter().flat_map(Expr::columns))
+            {
+                if column < left_width {
+                    left_required.insert(column);
+                } else {
+                    right_required.insert(column - left_width);
+                }
+            }
+            let (left, left_columns) = prune(*left, &left_required)?;
+            let (right, right_columns) = prune(*right, &right_required)?;
+            let mut joined = left_columns.clone();
+            joined.extend(right_columns.iter().map(|column| column + left_width));
+            let join = LogicalPlan::Join {
+                left: Box::new(left),
+                right: Box::new(right),
+                join_type,
+                condition: condition.map(|condition| remap(condition, &joined)),
+            };
+            let columns = if join_type.outputs_right() {
+                joined
+            } else {
+                left_columns
+            };
+            (join, columns)
+        }
+        LogicalPlan::Aggregate {
+            input,
+            group_by,
+            grouping_sets,
+            aggregates,
+            schema,
+        } => {
+            // The grouping sets refer to every group-by expression.
+            let groups = group_by.len();
+            let mut columns: Vec<usize> = (0..groups).collect();
+            let mut kept: Vec<AggregateExpr> = Vec::new();
+            for (i, aggregate) in aggregates.into_iter().enumerate() {
+                if required.contains(&(groups + i)) {
+                    columns.push(groups + i);
+                    kept.push(aggregate);
+                }
+            }
+            let input_required = group_by
+                .iter()
+                .chain(kept.iter().filter_map(|aggregate| aggregate.arg.as_ref()))
+                .flat_map(Expr::columns)
+                .collect();
+            let (input, input_columns) = prune(*input, &input_required)?;
+            let aggregate = LogicalPlan::Aggregate {
+                input: Box::new(input),
+                group_by: group_by
+                    .into_iter()
+                    .map(|expr| remap(expr, &input_columns))
+                    .collect(),
+                grouping_sets,
+                aggregates: kept
+                    .into_iter()
+                    .map(|aggregate| AggregateExpr {
+                        arg: aggregate.arg.map(|arg| remap(arg, &input_columns)),
+                        ..aggregate
+                    })
+                    .collect(),
+                schema: schema.project(&columns),
+            };
+            (aggregate, columns)
+        }
+        LogicalPlan::Sort { input, keys } => {
+            let mut required = required.clone();
+            required.extend(keys.iter().flat_map(|key| key.expr.columns()));
+            let (input, columns) = prune(*input, &required)?;
+            let sort = LogicalPlan::Sort {
+                input: Box::new(input),
+                keys: keys
+                    .into_iter()
+                    .map(|key| SortKey {
+                        expr: remap(key.expr, &columns),
+                        ..key
+                    })
+                    .collect(),
+            };
+            (sort, columns)
+        }
+        LogicalPlan::Limit {
+            input,
+            limit,
+            offset,
+        } => {
+            let (input, columns) = prune(*input, required)?;
+            let limit = LogicalPlan::Limit {
+                input: Box::new(input),
+                limit,
+                offset,
+            };
+            (limit, columns)
+        }
+        plan => {
+            // Every other operator reads all the columns of its inputs.
+            let columns = (0..plan.schema().column_count()).collect();
+            let plan = plan.map_children(&mut |input| {
+                let all = (0..input.schema().column_count()).collect();
+                Ok(prune(input, &all)?.0)
+            })?;
+            (plan, columns)
+        }
+    })
+}
+
+/// An expression over the original columns as one over the row of `columns`, which has them.
+fn remap(expr: Expr, columns: &[usize]) -> Expr {
+    expr.map_columns(&|column| columns.binary_search(&column).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        catalog::{
+            catalog::Catalog,
+            schema::{Column, Schema},
+        },
+        optimizer::{
+            constant_folding::fold_constants, decorrelation::decorrelate,
+            predicate_pushdown::push_down_predicates,
+        },
+        planner::binder::Binder,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        types::data_type::DataType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_prune_projections() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        for name in ["t", "u"] {
+            let schema = Schema::new(vec![
+                Column::new("a", DataType::Int64).not_null(),
+                Column::new("b", DataType::Int64),
+                Column::new("c", DataType::Int32),
+            ]);
+            catalog.create_table(name, schema).unwrap();
+        }
+        // Check the plan of `sql` before and after pruning, with the rules before it applied.
+        let check = |sql: &str, before: &str, after: &str| {
+            let statement = parse_statement(sql).unwrap();
+            let plan = Binder::new(&catalog).bind(&statement).unwrap();
+            let plan = fold_constants(plan)
+                .and_then(decorrelate)
+                .and_then(push_down_predicates)
+                .unwrap();
+            assert_eq!(plan.to_string(), before);
+            assert_eq!(prune_projections(plan).unwrap().to_string(), after);
+        };
+
+        // Each input of a join keeps the columns the join and the operators above it read.
+        check(
+            "SELECT t.c FROM t JOIN u ON t.a = u.b ORDER BY t.b",
+            "Projection #2\n\
+             \x20 Sort #1\n\
+             \x20   Join Inner on (#0 = #4)\n\
+             \x20     SeqScan t\n\
+             \x20     SeqScan u\n",
+            "Projection #2\n\
+             \x20 Sort #1\n\
+             \x20   Join Inner on (#0 = #3)\n\
+             \x20     SeqScan t\n\
+             \x20     Projection #1\n\
+             \x20       SeqScan u\n",
+        );
+        check(
+            "SELECT a FROM t WHERE NOT EXISTS (SELECT * FROM u WHERE u.a = t.b AND u.c > 1)",
+            "Projection #0\n\
+             \x20 Join Anti on (#3 = #1)\n\
+             \x20   SeqScan t\n\
+             \x20   Filter (#2 > 1)\n\
+             \x20     SeqScan u\n",
+            "Projection #0\n\
+             \x20 Join Anti on (#2 = #1)\n\
+             \x20   Projection #0, #1\n\
+             \x20     SeqScan t\n\
+             \x20   Filter (#1 > 1)\n\
+             \x20     Projection #0, #2\n\
+             \x20       SeqScan u\n",
+        );
+        // An aggregation reads its group-by columns only.
+        check(
+            "SELECT c, count(*) FROM t GROUP BY ROLLUP (b, c) HAVING c > 1",
+            "Projection #1, #2\n\
+             \x20 Filter (#1 > 1)\n\
+             \x20   Aggregate group_by=[#1, #2] grouping_sets=[(0, 1), (0), ()] \
+             aggregates=[count(*)]\n\
+             \x20     SeqScan t\n",
+            "Projection #1, #2\n\
+             \x20 Filter (#1 > 1)\n\
+             \x20   Aggregate group_by=[#0, #1] grouping_sets=[(0, 1), (0), ()] \
+             aggregates=[count(*)]\n\
+             \x20     Projection #1, #2\n\
+             \x20       SeqScan t\n",
+        );
+        // DISTINCT needs every column it has, but not the ones it does not.
+        check(
+            "SELECT DISTINCT b FROM t WHERE c = 1",
+            "Distinct\n\
+             \x20 Projection #1\n\
+             \x20   Filter (#2 = 1)\n\
+             \x20     SeqScan t\n",
+            "Distinct\n\
+             \x20 Projection #0\n\
+             \x20   Filter (#1 = 1)\n\
+             \x20     Projection #1, #2\n\
+             \x20       SeqScan t\n",
+        );
+        // A modification writes whole rows.
+        let delete = "Delete t\n  Filter (#1 = 1)\n    SeqScan t\n";
+        check("DELETE FROM t WHERE b = 1", delete, delete);
+    }
+}
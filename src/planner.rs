@@ -0,0 +1,3 @@
+pub mod binder;
+pub mod expr;
+pub mod logical_plan;
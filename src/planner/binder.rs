@@ -0,0 +1,1995 @@
+//! Name resolution and type checking of parsed statements.
+//!
+//! The binder resolves table names through the [`Catalog`] and column names through the
+//! scope of the FROM clause, expands `*`, type-checks every expression and makes implicit
+//! conversions explicit casts. The result is a [`LogicalPlan`] in which columns are positions.
+//!
+//! Numeric types are widened along `INT8 < INT16 < INT32 < INT64 < DECIMAL < FLOAT64` when
+//! they meet. String literals and NULL take the type of whatever they are compared with or
+//! assigned to, so that `d = '2024-01-01'` compares dates.
+
+use std::sync::Arc;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    catalog::{
+        catalog::{Catalog, TableInfo},
+        schema::{Column, Schema},
+    },
+    sql::{
+        ast::{self, BinaryOp, ExprKind as AstExprKind, Literal, Statement, UnaryOp},
+        error::{Span, SqlError, SqlResult},
+    },
+    types::{data_type::DataType, decimal::Decimal, value::Value},
+};
+
+use super::{
+    expr::{AggregateExpr, AggregateFunction, Expr, ExprKind, ScalarFunction},
+    logical_plan::{JoinType, LogicalPlan, SortKey},
+};
+
+/// A column visible to expressions, with the name of the table or alias it comes from.
+#[derive(Debug, Clone)]
+struct ScopeColumn {
+    table: Option<String>,
+    name: String,
+    data_type: DataType,
+    nullable: bool,
+}
+
+/// The columns of the row an expression is evaluated over, in order.
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    columns: Vec<ScopeColumn>,
+    /// The scope of the query a subquery is nested in, whose columns it may also read.
+    outer: Option<Box<Scope>>,
+}
+
+impl Scope {
+    fn of_table(table: &TableInfo, qualifier: &str) -> Self {
+        Self {
+            columns: table
+                .schema
+                .columns()
+                .iter()
+                .map(|column| ScopeColumn {
+                    table: Some(qualifier.to_string()),
+                    name: column.name.clone(),
+                    data_type: column.data_type,
+                    nullable: column.nullable,
+                })
+                .collect(),
+            outer: None,
+        }
+    }
+
+    fn has_table(&self, table: &str) -> bool {
+        self.columns
+            .iter()
+            .any(|column| column.table.as_deref() == Some(table))
+    }
+
+    /// The column `table.column` names, looking into the scope of the enclosing query if this
+    /// one has none.
+    fn resolve(&self, table: Option<&ast::Ident>, column: &ast::Ident) -> SqlResult<Expr> {
+        if let Some(found) = self.find(table, column)? {
+            return Ok(found);
+        }
+        if let Some(outer) = &self.outer {
+            if let Some(found) = outer.find(table, column)? {
+                let ExprKind::Column(index) = found.kind else {
+                    unreachable!("a scope only has columns")
+                };
+                return Ok(Expr::new(ExprKind::OuterColumn(index), found.data_type));
+            }
+        }
+
+        if let Some(table) = table {
+            let in_outer = self
+                .outer
+                .as_ref()
+                .map_or(false, |outer| outer.has_table(&table.value));
+            if !self.has_table(&table.value) && !in_outer {
+                return Err(SqlError::new(
+                    format!("missing FROM-clause entry for table \"{}\"", table.value),
+                    table.span,
+                ));
+            }
+        }
+        let span = table.map_or(column.span, |table| table.span.to(column.span));
+        Err(SqlError::new(
+            format!("column \"{}\" does not exist", display_name(table, column)),
+            span,
+        ))
+    }
+
+    /// The column of this scope `table.column` names, if any.
+    fn find(&self, table: Option<&ast::Ident>, column: &ast::Ident) -> SqlResult<Option<Expr>> {
+        let mut matches = self.columns.iter().enumerate().filter(|(_, c)| {
+            c.name.eq_ignore_ascii_case(&column.value)
+                && table.map_or(true, |table| c.table.as_deref() == Some(&table.value))
+        });
+        let Some((index, found)) = matches.next() else {
+            return Ok(None);
+        };
+        if matches.next().is_some() {
+            let span = table.map_or(column.span, |table| table.span.to(column.span));
+            return Err(SqlError::new(
+                format!(
+                    "column reference \"{}\" is ambiguous",
+                    display_name(table, column)
+                ),
+                span,
+            ));
+        }
+        Ok(Some(Expr::column(index, found.data_type)))
+    }
+}
+
+/// The state of binding the expressions above a GROUP BY.
+struct Aggregation {
+    group_by: Vec<Expr>,
+    aggregates: Vec<AggregateExpr>,
+}
+
+struct ExprContext<'s> {
+    scope: &'s Scope,
+    /// Set above a GROUP BY, where expressions are bound over the groups and the aggregates
+    /// rather than over the rows of `scope`.
+    aggregation: Option<Aggregation>,
+    /// The clause being bound, for errors about misplaced aggregates and subqueries.
+    clause: &'static str,
+    /// Whether EXISTS and IN subqueries are allowed, which they are only in the WHERE clause
+    /// of a SELECT.
+    subqueries: bool,
+}
+
+impl<'s> ExprContext<'s> {
+    fn row(scope: &'s Scope, clause: &'static str) -> Self {
+        Self {
+            scope,
+            aggregation: None,
+            clause,
+            subqueries: false,
+        }
+    }
+}
+
+pub struct Binder<'c, 'a, B: BufferPoolManager + ?Sized> {
+    catalog: &'c Catalog<'a, B>,
+    /// The values of the parameters of the statement, by position.
+    parameters: Vec<Value>,
+}
+
+impl<'c, 'a, B: BufferPoolManager + ?Sized> Binder<'c, 'a, B> {
+    pub fn new(catalog: &'c Catalog<'a, B>) -> Self {
+        Self {
+            catalog,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Bind the parameters of statements to `parameters`, which become literals.
+    pub fn with_parameters(self, parameters: Vec<Value>) -> Self {
+        Self { parameters, ..self }
+    }
+
+    pub fn bind(&self, statement: &Statement) -> SqlResult<LogicalPlan> {
+        match statement {
+            Statement::Select(select) => self.bind_select(select, None),
+            Statement::Insert(insert) => self.bind_insert(insert),
+            Statement::Update(update) => self.bind_update(update),
+            Statement::Delete(delete) => self.bind_delete(delete),
+            Statement::CreateTable(create) => self.bind_create_table(create),
+            Statement::DropTable(drop) => Ok(LogicalPlan::DropTable {
+                name: drop.name.value.clone(),
+                if_exists: drop.if_exists,
+            }),
+            Statement::CreateIndex(create) => self.bind_create_index(create),
+            Statement::DropIndex(drop) => Ok(LogicalPlan::DropIndex {
+                name: drop.name.value.clone(),
+                if_exists: drop.if_exists,
+            }),
+            Statement::Analyze(analyze) => {
+                let tables = match &analyze.table {
+                    Some(name) => vec![self.table(name)?],
+                    None => self
+                        .catalog
+                        .tables()
+                        .into_iter()
+                        .filter(|table| !table.is_system())
+                        .collect(),
+                };
+                Ok(LogicalPlan::Analyze { tables })
+            }
+            Statement::Explain(explain) => {
+                if !matches!(
+                    *explain.statement,
+                    Statement::Select(_)
+                        | Statement::Insert(_)
+                        | Statement::Update(_)
+                        | Statement::Delete(_)
+                ) {
+                    return Err(SqlError::new(
+                        "EXPLAIN only supports SELECT, INSERT, UPDATE and DELETE",
+                        explain.span,
+                    ));
+                }
+                Ok(LogicalPlan::Explain {
+                    input: Box::new(self.bind(&explain.statement)?),
+                    analyze: explain.analyze,
+                    format: explain.format,
+                })
+            }
+        }
+    }
+
+    fn table(&self, name: &ast::Ident) -> SqlResult<Arc<TableInfo>> {
+        self.catalog.table(&name.value).ok_or_else(|| {
+            SqlError::new(
+                format!("relation \"{}\" does not exist", name.value),
+                name.span,
+            )
+        })
+    }
+
+    /// A table that INSERT, UPDATE or DELETE may change.
+    fn user_table(&self, name: &ast::Ident) -> SqlResult<Arc<TableInfo>> {
+        let table = self.table(name)?;
+        if table.is_system() {
+            return Err(SqlError::new(
+                format!("cannot modify system table \"{}\"", table.name),
+                name.span,
+            ));
+        }
+        Ok(table)
+    }
+
+    /// Bind a query, which is a subquery of a query with the scope `outer` if there is one.
+    fn bind_select(&self, select: &ast::Select, outer: Option<&Scope>) -> SqlResult<LogicalPlan> {
+        let (mut plan, mut scope) = match &select.from {
+            Some(from) => self.bind_table_ref(from)?,
+            None => (
+                LogicalPlan::Values {
+                    rows: vec![Vec::new()],
+                    schema: Schema::new(Vec::new()),
+                },
+                Scope::default(),
+            ),
+        };
+        scope.outer = outer.cloned().map(Box::new);
+
+        if let Some(selection) = &select.selection {
+            check_subqueries(selection, true)?;
+            let mut ctx = ExprContext::row(&scope, "WHERE");
+            ctx.subqueries = true;
+            let predicate = self.bind_expr(&mut ctx, selection)?;
+            let predicate = coerce_boolean(predicate, "WHERE", selection.span)?;
+            plan = LogicalPlan::Filter {
+                input: Box::new(plan),
+                predicate,
+            };
+        }
+
+        let aggregating = !select.group_by.is_empty()
+            || select.grouping_sets.is_some()
+            || select.having.is_some()
+            || select.projection.iter().any(|item| {
+                matches!(item, ast::SelectItem::Expr { expr, .. } if contains_aggregate(expr))
+            })
+            || select
+                .order_by
+                .iter()
+                .any(|key| contains_aggregate(&key.expr));
+        let mut ctx = ExprContext::row(&scope, "SELECT");
+        let mut grouping_sets = Vec::new();
+        if aggregating {
+            // Expressions that appear more than once, as grouping sets often repeat them, are
+            // grouped by once.
+            let mut group_by: Vec<Expr> = Vec::new();
+            let mut positions = Vec::new();
+            for expr in &select.group_by {
+                let bound = self.bind_expr(&mut ExprContext::row(&scope, "GROUP BY"), expr)?;
+                positions.push(match group_by.iter().position(|g| *g == bound) {
+                    Some(position) => position,
+                    None => {
+                        group_by.push(bound);
+                        group_by.len() - 1
+                    }
+                });
+            }
+            grouping_sets = match &select.grouping_sets {
+                Some(sets) => sets
+                    .iter()
+                    .map(|set| {
+                        let mut set: Vec<usize> = set.iter().map(|&i| positions[i]).collect();
+                        set.sort_unstable();
+                        set.dedup();
+                        set
+                    })
+                    .collect(),
+                None => vec![(0..group_by.len()).collect()],
+            };
+            ctx.aggregation = Some(Aggregation {
+                group_by,
+                aggregates: Vec::new(),
+            });
+        }
+
+        // The projected expressions, over the rows or the groups, with their output names.
+        let mut projection: Vec<(Expr, String, Span)> = Vec::new();
+        for item in &select.projection {
+            match item {
+                ast::SelectItem::Wildcard(span) => {
+                    for (i, column) in scope.columns.iter().enumerate() {
+                        let expr = self.wildcard_column(&ctx, i, column, *span)?;
+                        projection.push((expr, column.name.clone(), *span));
+                    }
+                    if scope.columns.is_empty() {
+                        return Err(SqlError::new("SELECT * with no tables specified", *span));
+                    }
+                }
+                ast::SelectItem::QualifiedWildcard(table) => {
+                    if !scope.has_table(&table.value) {
+                        return Err(SqlError::new(
+                            format!("missing FROM-clause entry for table \"{}\"", table.value),
+                            table.span,
+                        ));
+                    }
+                    for (i, column) in scope.columns.iter().enumerate() {
+                        if column.table.as_deref() == Some(&table.value) {
+                            let expr = self.wildcard_column(&ctx, i, column, table.span)?;
+                            projection.push((expr, column.name.clone(), table.span));
+                        }
+                    }
+                }
+                ast::SelectItem::Expr { expr, alias } => {
+                    let bound = self.bind_expr(&mut ctx, expr)?;
+                    let name = alias
+                        .as_ref()
+                        .map_or_else(|| output_name(expr), |alias| alias.value.clone());
+                    projection.push((bound, name, expr.span));
+                }
+            }
+        }
+
+        let having = match &select.having {
+            Some(having) => {
+                ctx.clause = "HAVING";
+                let predicate = self.bind_expr(&mut ctx, having)?;
+                Some(coerce_boolean(predicate, "HAVING", having.span)?)
+            }
+            None => None,
+        };
+
+        // ORDER BY may name an output column, give its position or use any expression over
+        // the input, except after DISTINCT, which leaves only the output columns to sort by.
+        ctx.clause = "ORDER BY";
+        let mut sort_keys = Vec::new();
+        for key in &select.order_by {
+            let output = self.order_by_output_column(&projection, &key.expr)?;
+            let expr = match output {
+                Some(i) if select.distinct => Expr::column(i, projection[i].0.data_type),
+                Some(i) => projection[i].0.clone(),
+                None => {
+                    let expr = self.bind_expr(&mut ctx, &key.expr)?;
+                    if !select.distinct {
+                        expr
+                    } else if let Some(i) = projection.iter().position(|(e, _, _)| *e == expr) {
+                        Expr::column(i, expr.data_type)
+                    } else {
+                        return Err(SqlError::new(
+                            "for SELECT DISTINCT, ORDER BY expressions must appear in select list",
+                            key.expr.span,
+                        ));
+                    }
+                }
+            };
+            sort_keys.push(SortKey {
+                expr,
+                descending: key.descending,
+            });
+        }
+
+        let mut nullable: Vec<bool> = scope.columns.iter().map(|c| c.nullable).collect();
+        if let Some(Aggregation {
+            group_by,
+            aggregates,
+        }) = ctx.aggregation
+        {
+            let mut columns = Vec::new();
+            for (position, expr) in group_by.iter().enumerate() {
+                let (name, nullable) = match expr.kind {
+                    ExprKind::Column(i) => (scope.columns[i].name.clone(), nullable[i]),
+                    _ => ("?column?".to_string(), true),
+                };
+                // The groups of sets without the expression have NULL in its place.
+                let in_every_set = grouping_sets.iter().all(|set| set.contains(&position));
+                columns.push(Column {
+                    name,
+                    data_type: expr.data_type,
+                    nullable: nullable || !in_every_set,
+                });
+            }
+            for aggregate in &aggregates {
+                columns.push(Column {
+                    name: aggregate.func.name().to_string(),
+                    data_type: aggregate.data_type,
+                    nullable: aggregate.func != AggregateFunction::Count,
+                });
+            }
+            nullable = columns.iter().map(|c| c.nullable).collect();
+            plan = LogicalPlan::Aggregate {
+                input: Box::new(plan),
+                group_by,
+                grouping_sets,
+                aggregates,
+                schema: Schema::new(columns),
+            };
+        }
+        if let Some(predicate) = having {
+            plan = LogicalPlan::Filter {
+                input: Box::new(plan),
+                predicate,
+            };
+        }
+        if !select.distinct && !sort_keys.is_empty() {
+            plan = LogicalPlan::Sort {
+                input: Box::new(plan),
+                keys: std::mem::take(&mut sort_keys),
+            };
+        }
+
+        let schema = Schema::new(
+            projection
+                .iter()
+                .map(|(expr, name, _)| Column {
+                    name: name.clone(),
+                    data_type: expr.data_type,
+                    nullable: match expr.kind {
+                        ExprKind::Column(i) => nullable[i],
+                        _ => true,
+                    },
+                })
+                .collect(),
+        );
+        plan = LogicalPlan::Projection {
+            input: Box::new(plan),
+            exprs: projection.into_iter().map(|(expr, _, _)| expr).collect(),
+            schema,
+        };
+        if select.distinct {
+            plan = LogicalPlan::Distinct {
+                input: Box::new(plan),
+            };
+            if !sort_keys.is_empty() {
+                plan = LogicalPlan::Sort {
+                    input: Box::new(plan),
+                    keys: sort_keys,
+                };
+            }
+        }
+
+        if select.limit.is_some() || select.offset.is_some() {
+            let limit = select
+                .limit
+                .as_ref()
+                .map(|limit| constant_count(limit, "LIMIT"))
+                .transpose()?;
+            let offset = select
+                .offset
+                .as_ref()
+                .map(|offset| constant_count(offset, "OFFSET"))
+                .transpose()?
+                .unwrap_or(0);
+            plan = LogicalPlan::Limit {
+                input: Box::new(plan),
+                limit,
+                offset,
+            };
+        }
+
+        Ok(plan)
+    }
+
+    /// Column `index` of the scope as expanded from a `*`, which above a GROUP BY must be one
+    /// of the groups.
+    fn wildcard_column(
+        &self,
+        ctx: &ExprContext,
+        index: usize,
+        column: &ScopeColumn,
+        span: Span,
+    ) -> SqlResult<Expr> {
+        let expr = Expr::column(index, column.data_type);
+        let Some(aggregation) = &ctx.aggregation else {
+            return Ok(expr);
+        };
+        match aggregation.group_by.iter().position(|g| *g == expr) {
+            Some(i) => Ok(Expr::column(i, column.data_type)),
+            None => Err(not_grouped(&qualified_name(column), span)),
+        }
+    }
+
+    /// The output column an ORDER BY key refers to by position or by name.
+    fn order_by_output_column(
+        &self,
+        projection: &[(Expr, String, Span)],
+        key: &ast::Expr,
+    ) -> SqlResult<Option<usize>> {
+        match &key.kind {
+            AstExprKind::Literal(Literal::Number(number)) => match number.parse::<usize>() {
+                Ok(position) if (1..=projection.len()).contains(&position) => {
+                    Ok(Some(position - 1))
+                }
+                _ => Err(SqlError::new(
+                    format!("ORDER BY position {number} is not in select list"),
+                    key.span,
+                )),
+            },
+            AstExprKind::Column {
+                table: None,
+                column,
+            } => {
+                let mut matches = projection
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, name, _))| name.eq_ignore_ascii_case(&column.value));
+                let found = matches.next().map(|(i, _)| i);
+                if found.is_some() && matches.next().is_some() {
+                    return Err(SqlError::new(
+                        format!("ORDER BY \"{}\" is ambiguous", column.value),
+                        column.span,
+                    ));
+                }
+                Ok(found)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn bind_table_ref(&self, table_ref: &ast::TableRef) -> SqlResult<(LogicalPlan, Scope)> {
+        match table_ref {
+            ast::TableRef::Table { name, alias } => {
+                let table = self.table(name)?;
+                let qualifier = alias.as_ref().unwrap_or(name);
+                let scope = Scope::of_table(&table, &qualifier.value);
+                Ok((LogicalPlan::SeqScan { table }, scope))
+            }
+            ast::TableRef::Join {
+                left,
+                right,
+                kind,
+                on,
+                ..
+            } => {
+                let (left_plan, left_scope) = self.bind_table_ref(left)?;
+                let (right_plan, right_scope) = self.bind_table_ref(right)?;
+                for column in &right_scope.columns {
+                    if let Some(table) = &column.table {
+                        if left_scope.has_table(table) {
+                            return Err(SqlError::new(
+                                format!("table name \"{table}\" specified more than once"),
+                                right.span(),
+                            ));
+                        }
+                    }
+                }
+                let join_type = match kind {
+                    ast::JoinKind::Inner | ast::JoinKind::Cross => JoinType::Inner,
+                    ast::JoinKind::Left => JoinType::Left,
+                    ast::JoinKind::Right => JoinType::Right,
+                    ast::JoinKind::Full => JoinType::Full,
+                };
+                let mut scope = Scope::default();
+                for (columns, padded) in [
+                    (left_scope.columns, join_type.pads_left()),
+                    (right_scope.columns, join_type.pads_right()),
+                ] {
+                    scope
+                        .columns
+                        .extend(columns.into_iter().map(|column| ScopeColumn {
+                            nullable: column.nullable || padded,
+                            ..column
+                        }));
+                }
+                let condition = on
+                    .as_ref()
+                    .map(|on| self.bind_predicate(&scope, on, "JOIN conditions"))
+                    .transpose()?;
+
+                let plan = LogicalPlan::Join {
+                    left: Box::new(left_plan),
+                    right: Box::new(right_plan),
+                    join_type,
+                    condition,
+                };
+                Ok((plan, scope))
+            }
+        }
+    }
+
+    /// A boolean condition over the rows of `scope`, in which aggregates are not allowed.
+    fn bind_predicate(
+        &self,
+        scope: &Scope,
+        expr: &ast::Expr,
+        clause: &'static str,
+    ) -> SqlResult<Expr> {
+        let predicate = self.bind_expr(&mut ExprContext::row(scope, clause), expr)?;
+        coerce_boolean(predicate, clause, expr.span)
+    }
+
+    fn bind_expr(&self, ctx: &mut ExprContext, expr: &ast::Expr) -> SqlResult<Expr> {
+        if let Some(aggregation) = &ctx.aggregation {
+            // Above a GROUP BY, a grouped expression becomes a reference to its group.
+            if !contains_aggregate(expr) {
+                let bound = self.bind_expr(&mut ExprContext::row(ctx.scope, ctx.clause), expr)?;
+                if let Some(i) = aggregation.group_by.iter().position(|g| *g == bound) {
+                    return Ok(Expr::column(i, bound.data_type));
+                }
+                if let AstExprKind::Column { table, column } = &expr.kind {
+                    return Err(not_grouped(
+                        &display_name(table.as_ref(), column),
+                        expr.span,
+                    ));
+                }
+            }
+        }
+
+        let span = expr.span;
+        let bound = match &expr.kind {
+            AstExprKind::Literal(literal) => bind_literal(literal, false, span)?,
+            AstExprKind::Parameter(position) => match self.parameters.get(*position) {
+                Some(value) => bind_parameter(value),
+                None => {
+                    return Err(SqlError::new(
+                        format!("there is no parameter ${}", position + 1),
+                        span,
+                    ))
+                }
+            },
+            AstExprKind::Column { table, column } => ctx.scope.resolve(table.as_ref(), column)?,
+            AstExprKind::Unary { op, expr: inner } => match op {
+                UnaryOp::Not => {
+                    let inner = self.bind_expr(ctx, inner)?;
+                    let inner = coerce_boolean(inner, "NOT", span)?;
+                    Expr::new(ExprKind::Not(Box::new(inner)), DataType::Boolean)
+                }
+                UnaryOp::Minus => {
+                    if let AstExprKind::Literal(literal @ Literal::Number(_)) = &inner.kind {
+                        return bind_literal(literal, true, span);
+                    }
+                    let inner = self.bind_expr(ctx, inner)?;
+                    check_numeric(&inner, "-", span)?;
+                    let data_type = inner.data_type;
+                    Expr::new(ExprKind::Negate(Box::new(inner)), data_type)
+                }
+                UnaryOp::Plus => {
+                    let inner = self.bind_expr(ctx, inner)?;
+                    check_numeric(&inner, "+", span)?;
+                    inner
+                }
+            },
+            AstExprKind::Binary { op, left, right } => {
+                let left = self.bind_expr(ctx, left)?;
+                let right = self.bind_expr(ctx, right)?;
+                bind_binary(*op, left, right, span)?
+            }
+            AstExprKind::IsNull { expr, negated } => {
+                let expr = self.bind_expr(ctx, expr)?;
+                Expr::new(
+                    ExprKind::IsNull {
+                        expr: Box::new(expr),
+                        negated: *negated,
+                    },
+                    DataType::Boolean,
+                )
+            }
+            AstExprKind::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let expr = self.bind_expr(ctx, expr)?;
+                let low = self.bind_expr(ctx, low)?;
+                let high = self.bind_expr(ctx, high)?;
+                let lower = bind_binary(BinaryOp::GtEq, expr.clone(), low, span)?;
+                let upper = bind_binary(BinaryOp::LtEq, expr, high, span)?;
+                let between = Expr::binary(BinaryOp::And, lower, upper, DataType::Boolean);
+                if *negated {
+                    Expr::new(ExprKind::Not(Box::new(between)), DataType::Boolean)
+                } else {
+                    between
+                }
+            }
+            AstExprKind::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let mut exprs = vec![self.bind_expr(ctx, expr)?];
+                for item in list {
+                    exprs.push(self.bind_expr(ctx, item)?);
+                }
+                let mut exprs = unify_all(exprs, "IN", span)?.into_iter();
+                let expr = exprs.next().unwrap();
+                Expr::new(
+                    ExprKind::InList {
+                        expr: Box::new(expr),
+                        list: exprs.collect(),
+                        negated: *negated,
+                    },
+                    DataType::Boolean,
+                )
+            }
+            AstExprKind::Like {
+                expr,
+                pattern,
+                negated,
+            } => {
+                let expr = self.bind_expr(ctx, expr)?;
+                let pattern = self.bind_expr(ctx, pattern)?;
+                let expr = coerce(expr, DataType::Varchar(None), "LIKE", span)?;
+                let pattern = coerce(pattern, DataType::Varchar(None), "LIKE", span)?;
+                Expr::new(
+                    ExprKind::Like {
+                        expr: Box::new(expr),
+                        pattern: Box::new(pattern),
+                        negated: *negated,
+                    },
+                    DataType::Boolean,
+                )
+            }
+            AstExprKind::Function {
+                name,
+                args,
+                star,
+                distinct,
+            } => {
+                if let Some(func) = AggregateFunction::from_name(&name.value) {
+                    return self.bind_aggregate(ctx, func, args, *star, *distinct, span);
+                }
+                let Some(func) = ScalarFunction::from_name(&name.value) else {
+                    return Err(SqlError::new(
+                        format!("function \"{}\" does not exist", name.value),
+                        name.span,
+                    ));
+                };
+                if *star || *distinct {
+                    return Err(SqlError::new(
+                        format!("{}() is not an aggregate function", name.value),
+                        span,
+                    ));
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| self.bind_expr(ctx, arg))
+                    .collect::<SqlResult<Vec<_>>>()?;
+                bind_scalar_function(func, args, span)?
+            }
+            AstExprKind::Exists(subquery) => {
+                check_subquery_allowed(ctx, span)?;
+                let subquery = self.bind_select(subquery, Some(ctx.scope))?;
+                Expr::new(ExprKind::Exists(Box::new(subquery)), DataType::Boolean)
+            }
+            AstExprKind::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                check_subquery_allowed(ctx, span)?;
+                let expr = self.bind_expr(ctx, expr)?;
+                let subquery = self.bind_select(subquery, Some(ctx.scope))?;
+                let schema = subquery.schema();
+                if schema.column_count() != 1 {
+                    return Err(SqlError::new("subquery has too many columns", span));
+                }
+                let column = schema.column(0);
+                let (expr, value) = unify(expr, Expr::column(0, column.data_type), "IN", span)?;
+                // A conversion of the values of the subquery becomes a projection over it.
+                let subquery = if matches!(value.kind, ExprKind::Column(_)) {
+                    subquery
+                } else {
+                    LogicalPlan::Projection {
+                        input: Box::new(subquery),
+                        schema: Schema::new(vec![Column {
+                            data_type: value.data_type,
+                            ..column.clone()
+                        }]),
+                        exprs: vec![value],
+                    }
+                };
+                Expr::new(
+                    ExprKind::InSubquery {
+                        expr: Box::new(expr),
+                        subquery: Box::new(subquery),
+                        negated: *negated,
+                    },
+                    DataType::Boolean,
+                )
+            }
+            AstExprKind::Cast { expr, data_type } => {
+                let inner = self.bind_expr(ctx, expr)?;
+                if inner.data_type == *data_type {
+                    inner
+                } else if is_untyped_literal(&inner) || can_cast(inner.data_type, *data_type) {
+                    inner.cast(*data_type)
+                } else {
+                    return Err(SqlError::new(
+                        format!("cannot cast type {} to {data_type}", inner.data_type),
+                        span,
+                    ));
+                }
+            }
+        };
+        Ok(bound)
+    }
+
+    fn bind_aggregate(
+        &self,
+        ctx: &mut ExprContext,
+        func: AggregateFunction,
+        args: &[ast::Expr],
+        star: bool,
+        distinct: bool,
+        span: Span,
+    ) -> SqlResult<Expr> {
+        if ctx.aggregation.is_none() {
+            return Err(SqlError::new(
+                format!("aggregate functions are not allowed in {}", ctx.clause),
+                span,
+            ));
+        }
+        if args.iter().any(contains_aggregate) {
+            return Err(SqlError::new(
+                "aggregate function calls cannot be nested",
+                span,
+            ));
+        }
+        let arg = match (star, args) {
+            (true, []) if func == AggregateFunction::Count => None,
+            (false, [arg]) => {
+                Some(self.bind_expr(&mut ExprContext::row(ctx.scope, ctx.clause), arg)?)
+            }
+            _ => {
+                return Err(SqlError::new(
+                    format!("{}() takes exactly one argument", func.name()),
+                    span,
+                ))
+            }
+        };
+        let data_type = match (func, arg.as_ref().map(|arg| arg.data_type)) {
+            (AggregateFunction::Count, _) => DataType::Int64,
+            (AggregateFunction::Sum, Some(t)) if t.is_integer() => DataType::Int64,
+            // The sum of many values needs more digits than any one of them.
+            (AggregateFunction::Sum, Some(DataType::Decimal { scale, .. })) => DataType::Decimal {
+                precision: DataType::MAX_DECIMAL_PRECISION,
+                scale,
+            },
+            (AggregateFunction::Sum, Some(DataType::Float64)) => DataType::Float64,
+            (AggregateFunction::Avg, Some(t)) if t.is_numeric() => DataType::Float64,
+            (AggregateFunction::Min | AggregateFunction::Max, Some(t)) => t,
+            (_, Some(t)) => {
+                return Err(SqlError::new(
+                    format!("function {}({t}) does not exist", func.name()),
+                    span,
+                ))
+            }
+            (_, None) => unreachable!("only count(*) has no argument"),
+        };
+        let aggregate = AggregateExpr {
+            func,
+            arg,
+            distinct,
+            data_type,
+        };
+
+        let aggregation = ctx.aggregation.as_mut().unwrap();
+        let index = match aggregation.aggregates.iter().position(|a| *a == aggregate) {
+            Some(index) => index,
+            None => {
+                aggregation.aggregates.push(aggregate);
+                aggregation.aggregates.len() - 1
+            }
+        };
+        Ok(Expr::column(aggregation.group_by.len() + index, data_type))
+    }
+
+    fn bind_insert(&self, insert: &ast::Insert) -> SqlResult<LogicalPlan> {
+        let table = self.user_table(&insert.table)?;
+        let schema = &table.schema;
+        let targets: Vec<usize> = if insert.columns.is_empty() {
+            (0..schema.column_count()).collect()
+        } else {
+            let mut targets = Vec::new();
+            for column in &insert.columns {
+                let Some(index) = schema.index_of(&column.value) else {
+                    return Err(SqlError::new(
+                        format!(
+                            "column \"{}\" of relation \"{}\" does not exist",
+                            column.value, table.name
+                        ),
+                        column.span,
+                    ));
+                };
+                if targets.contains(&index) {
+                    return Err(SqlError::new(
+                        format!("column \"{}\" specified more than once", column.value),
+                        column.span,
+                    ));
+                }
+                targets.push(index);
+            }
+            targets
+        };
+        for (i, column) in schema.columns().iter().enumerate() {
+            if !column.nullable && !targets.contains(&i) {
+                return Err(SqlError::new(
+                    format!(
+                        "null value in column \"{}\" of relation \"{}\" violates not-null constraint",
+                        column.name, table.name
+                    ),
+                    insert.table.span,
+                ));
+            }
+        }
+
+        // Arrange the values of the target columns into full rows of the table.
+        let full_row = |values: Vec<(Expr, Span)>| -> SqlResult<Vec<Expr>> {
+            let mut row: Vec<Option<Expr>> = vec![None; schema.column_count()];
+            for ((value, span), &target) in values.into_iter().zip(&targets) {
+                let column = schema.column(target);
+                row[target] = Some(assign(value, column, span)?);
+            }
+            Ok(row
+                .into_iter()
+                .zip(schema.columns())
+                .map(|(value, column)| {
+                    value.unwrap_or_else(|| Expr::literal(Value::Null, column.data_type))
+                })
+                .collect())
+        };
+        let count_error = |len: usize, span: Span| {
+            let more = if len > targets.len() {
+                "expressions than target columns"
+            } else {
+                "target columns than expressions"
+            };
+            SqlError::new(format!("INSERT has more {more}"), span)
+        };
+
+        let input = match &insert.source {
+            ast::InsertSource::Values(rows) => {
+                let empty = Scope::default();
+                let mut bound_rows = Vec::new();
+                for row in rows {
+                    if row.len() != targets.len() {
+                        let span = row[0].span.to(row[row.len() - 1].span);
+                        return Err(count_error(row.len(), span));
+                    }
+                    let values = row
+                        .iter()
+                        .map(|expr| {
+                            let ctx = &mut ExprContext::row(&empty, "VALUES");
+                            Ok((self.bind_expr(ctx, expr)?, expr.span))
+                        })
+                        .collect::<SqlResult<Vec<_>>>()?;
+                    bound_rows.push(full_row(values)?);
+                }
+                LogicalPlan::Values {
+                    rows: bound_rows,
+                    schema: schema.clone(),
+                }
+            }
+            ast::InsertSource::Select(select) => {
+                let input = self.bind_select(select, None)?;
+                let input_schema = input.schema();
+                if input_schema.column_count() != targets.len() {
+                    return Err(count_error(input_schema.column_count(), select.span));
+                }
+                let values = input_schema
+                    .columns()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| (Expr::column(i, column.data_type), select.span))
+                    .collect();
+                LogicalPlan::Projection {
+                    input: Box::new(input),
+                    exprs: full_row(values)?,
+                    schema: schema.clone(),
+                }
+            }
+        };
+
+        Ok(LogicalPlan::Insert {
+            table,
+            input: Box::new(input),
+        })
+    }
+
+    fn bind_update(&self, update: &ast::Update) -> SqlResult<LogicalPlan> {
+        let table = self.user_table(&update.table)?;
+        let scope = Scope::of_table(&table, &table.name);
+        let mut assignments: Vec<(usize, Expr)> = Vec::new();
+        for assignment in &update.assignments {
+            let name = &assignment.column;
+            let Some(index) = table.schema.index_of(&name.value) else {
+                return Err(SqlError::new(
+                    format!(
+                        "column \"{}\" of relation \"{}\" does not exist",
+                        name.value, table.name
+                    ),
+                    name.span,
+                ));
+            };
+            if assignments.iter().any(|(i, _)| *i == index) {
+                return Err(SqlError::new(
+                    format!("multiple assignments to same column \"{}\"", name.value),
+                    name.span,
+                ));
+            }
+            let value =
+                self.bind_expr(&mut ExprContext::row(&scope, "UPDATE"), &assignment.value)?;
+            let value = assign(value, table.schema.column(index), assignment.value.span)?;
+            assignments.push((index, value));
+        }
+        let input = self.scan_where(&table, &scope, update.selection.as_ref())?;
+
+        Ok(LogicalPlan::Update {
+            table,
+            input: Box::new(input),
+            assignments,
+        })
+    }
+
+    fn bind_delete(&self, delete: &ast::Delete) -> SqlResult<LogicalPlan> {
+        let table = self.user_table(&delete.table)?;
+        let scope = Scope::of_table(&table, &table.name);
+        let input = self.scan_where(&table, &scope, delete.selection.as_ref())?;
+
+        Ok(LogicalPlan::Delete {
+            table,
+            input: Box::new(input),
+        })
+    }
+
+    /// A scan of `table` filtered by `selection`.
+    fn scan_where(
+        &self,
+        table: &Arc<TableInfo>,
+        scope: &Scope,
+        selection: Option<&ast::Expr>,
+    ) -> SqlResult<LogicalPlan> {
+        let scan = LogicalPlan::SeqScan {
+            table: table.clone(),
+        };
+        let Some(selection) = selection else {
+            return Ok(scan);
+        };
+        Ok(LogicalPlan::Filter {
+            input: Box::new(scan),
+            predicate: self.bind_predicate(scope, selection, "WHERE")?,
+        })
+    }
+
+    fn bind_create_table(&self, create: &ast::CreateTable) -> SqlResult<LogicalPlan> {
+        let mut columns: Vec<Column> = Vec::new();
+        for def in &create.columns {
+            if columns
+                .iter()
+                .any(|column| column.name.eq_ignore_ascii_case(&def.name.value))
+            {
+                return Err(SqlError::new(
+                    format!("column \"{}\" specified more than once", def.name.value),
+                    def.name.span,
+                ));
+            }
+            let column = Column::new(def.name.value.clone(), def.data_type);
+            columns.push(if def.not_null {
+                column.not_null()
+            } else {
+                column
+            });
+        }
+
+        Ok(LogicalPlan::CreateTable {
+            name: create.name.value.clone(),
+            schema: Schema::new(columns),
+            if_not_exists: create.if_not_exists,
+        })
+    }
+
+    fn bind_create_index(&self, create: &ast::CreateIndex) -> SqlResult<LogicalPlan> {
+        let table = self.user_table(&create.table)?;
+        let mut columns: Vec<String> = Vec::new();
+        for column in &create.columns {
+            let Some(index) = table.schema.index_of(&column.value) else {
+                return Err(SqlError::new(
+                    format!("column \"{}\" does not exist", column.value),
+                    column.span,
+                ));
+            };
+            let name = &table.schema.column(index).name;
+            if columns.contains(name) {
+                return Err(SqlError::new(
+                    format!("column \"{name}\" appears more than once in the index"),
+                    column.span,
+                ));
+            }
+            columns.push(name.clone());
+        }
+
+        Ok(LogicalPlan::CreateIndex {
+            name: create.name.value.clone(),
+            table: table.name.clone(),
+            columns,
+            kind: create.kind,
+            unique: create.unique,
+        })
+    }
+}
+
+fn contains_aggregate(expr: &ast::Expr) -> bool {
+    match &expr.kind {
+        AstExprKind::Literal(_) | AstExprKind::Parameter(_) | AstExprKind::Column { .. } => false,
+        AstExprKind::Function { name, args, .. } => {
+            AggregateFunction::from_name(&name.value).is_some()
+                || args.iter().any(contains_aggregate)
+        }
+        AstExprKind::Unary { expr, .. }
+        | AstExprKind::IsNull { expr, .. }
+        | AstExprKind::Cast { expr, .. } => contains_aggregate(expr),
+        AstExprKind::Binary { left, right, .. } => {
+            contains_aggregate(left) || contains_aggregate(right)
+        }
+        AstExprKind::Between {
+            expr, low, high, ..
+        } => contains_aggregate(expr) || contains_aggregate(low) || contains_aggregate(high),
+        AstExprKind::InList { expr, list, .. } => {
+            contains_aggregate(expr) || list.iter().any(contains_aggregate)
+        }
+        AstExprKind::Like { expr, pattern, .. } => {
+            contains_aggregate(expr) || contains_aggregate(pattern)
+        }
+        // The aggregates of a subquery belong to it.
+        AstExprKind::Exists(_) => false,
+        AstExprKind::InSubquery { expr, .. } => contains_aggregate(expr),
+    }
+}
+
+/// Check that the subqueries of a WHERE condition are operands of its top-level ANDs, maybe
+/// negated, where the optimizer can turn them into semi and anti joins.
+fn check_subqueries(expr: &ast::Expr, top_level: bool) -> SqlResult<()> {
+    match &expr.kind {
+        AstExprKind::Exists(_) | AstExprKind::InSubquery { .. } if !top_level => {
+            Err(SqlError::new(
+                "subqueries are only supported as conditions joined by AND",
+                expr.span,
+            ))
+        }
+        AstExprKind::Exists(_) => Ok(()),
+        AstExprKind::Literal(_) | AstExprKind::Parameter(_) | AstExprKind::Column { .. } => Ok(()),
+        AstExprKind::Binary {
+            op: BinaryOp::And,
+            left,
+            right,
+        } => {
+            check_subqueries(left, top_level)?;
+            check_subqueries(right, top_level)
+        }
+        AstExprKind::Unary {
+            op: UnaryOp::Not,
+            expr,
+        } if matches!(
+            expr.kind,
+            AstExprKind::Exists(_) | AstExprKind::InSubquery { .. }
+        ) =>
+        {
+            check_subqueries(expr, top_level)
+        }
+        AstExprKind::Unary { expr, .. }
+        | AstExprKind::IsNull { expr, .. }
+        | AstExprKind::Cast { expr, .. }
+        | AstExprKind::InSubquery { expr, .. } => check_subqueries(expr, false),
+        AstExprKind::Binary { left, right, .. }
+        | AstExprKind::Like {
+            expr: left,
+            pattern: right,
+            ..
+        } => {
+            check_subqueries(left, false)?;
+            check_subqueries(right, false)
+        }
+        AstExprKind::Between {
+            expr, low, high, ..
+        } => [expr, low, high]
+            .into_iter()
+            .try_for_each(|expr| check_subqueries(expr, false)),
+        AstExprKind::InList { expr, list, .. } => std::iter::once(expr.as_ref())
+            .chain(list)
+            .try_for_each(|expr| check_subqueries(expr, false)),
+        AstExprKind::Function { args, .. } => args
+            .iter()
+            .try_for_each(|expr| check_subqueries(expr, false)),
+    }
+}
+
+fn check_subquery_allowed(ctx: &ExprContext, span: Span) -> SqlResult<()> {
+    if ctx.subqueries {
+        Ok(())
+    } else {
+        Err(SqlError::new(
+            format!("subqueries are not supported in {}", ctx.clause),
+            span,
+        ))
+    }
+}
+
+fn not_grouped(name: &str, span: Span) -> SqlError {
+    SqlError::new(
+        format!(
+            "column \"{name}\" must appear in the GROUP BY clause or be used in an aggregate function"
+        ),
+        span,
+    )
+}
+
+fn qualified_name(column: &ScopeColumn) -> String {
+    match &column.table {
+        Some(table) => format!("{table}.{}", column.name),
+        None => column.name.clone(),
+    }
+}
+
+/// A column reference as written.
+fn display_name(table: Option<&ast::Ident>, column: &ast::Ident) -> String {
+    match table {
+        Some(table) => format!("{}.{}", table.value, column.value),
+        None => column.value.clone(),
+    }
+}
+
+/// The name of an output column that has no alias, as PostgreSQL derives it.
+fn output_name(expr: &ast::Expr) -> String {
+    match &expr.kind {
+        AstExprKind::Column { column, .. } => column.value.clone(),
+        AstExprKind::Function { name, .. } => name.value.clone(),
+        AstExprKind::Cast { expr, .. } => output_name(expr),
+        _ => "?column?".to_string(),
+    }
+}
+
+/// A LIMIT or OFFSET count, which must be a non-negative integer literal.
+fn constant_count(expr: &ast::Expr, clause: &str) -> SqlResult<usize> {
+    match &expr.kind {
+        AstExprKind::Literal(Literal::Number(number)) => number.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        SqlError::new(
+            format!("{clause} must be a non-negative integer constant"),
+            expr.span,
+        )
+    })
+}
+
+/// Type a literal. Integers become the narrowest of INT32 and INT64 that holds them, other
+/// numbers DECIMAL, or FLOAT64 if they have an exponent.
+fn bind_literal(literal: &Literal, negative: bool, span: Span) -> SqlResult<Expr> {
+    let value = match literal {
+        Literal::Null => return Ok(Expr::literal(Value::Null, DataType::Varchar(None))),
+        Literal::Boolean(b) => Value::Boolean(*b),
+        Literal::String(s) => Value::Varchar(s.clone()),
+        Literal::Number(number) => {
+            let text = if negative {
+                format!("-{number}")
+            } else {
+                number.clone()
+            };
+            let out_of_range = || SqlError::new(format!("number {text} is out of range"), span);
+            if text.contains(['e', 'E']) {
+                Value::Float64(text.parse().map_err(|_| out_of_range())?)
+            } else if let Ok(v) = text.parse::<i32>() {
+                Value::Int32(v)
+            } else if let Ok(v) = text.parse::<i64>() {
+                Value::Int64(v)
+            } else {
+                let decimal = Decimal::parse(&text)
+                    .filter(|d| d.precision() <= DataType::MAX_DECIMAL_PRECISION)
+                    .ok_or_else(out_of_range)?;
+                Value::Decimal(decimal)
+            }
+        }
+    };
+    let data_type = value.data_type().unwrap();
+    Ok(Expr::literal(value, data_type))
+}
+
+/// Type the value of a parameter as a literal of the same value would be, so that, like
+/// literals, NULL and strings adapt to their context and integers take the narrowest type.
+fn bind_parameter(value: &Value) -> Expr {
+    match value {
+        Value::Null => Expr::literal(Value::Null, DataType::Varchar(None)),
+        Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_) => {
+            let v = value.as_i64().unwrap();
+            match i32::try_from(v) {
+                Ok(v) => Expr::literal(Value::Int32(v), DataType::Int32),
+                Err(_) => Expr::literal(Value::Int64(v), DataType::Int64),
+            }
+        }
+        value => Expr::literal(value.clone(), value.data_type().unwrap()),
+    }
+}
+
+/// NULL and string literals, which adapt to the type of their context.
+fn is_untyped_literal(expr: &Expr) -> bool {
+    matches!(
+        expr.kind,
+        ExprKind::Literal(Value::Null) | ExprKind::Literal(Value::Varchar(_))
+    )
+}
+
+/// The position of a numeric type in the widening order.
+fn numeric_rank(data_type: DataType) -> Option<u8> {
+    Some(match data_type {
+        DataType::Int8 => 1,
+        DataType::Int16 => 2,
+        DataType::Int32 => 3,
+        DataType::Int64 => 4,
+        DataType::Decimal { .. } => 5,
+        DataType::Float64 => 6,
+        _ => return None,
+    })
+}
+
+/// The type two types are implicitly converted to when they meet, if there is one.
+fn common_type(a: DataType, b: DataType) -> Option<DataType> {
+    if a == b {
+        return Some(a);
+    }
+    match (a, b) {
+        (DataType::Varchar(_), DataType::Varchar(_)) => Some(DataType::Varchar(None)),
+        (DataType::Date | DataType::Timestamp, DataType::Date | DataType::Timestamp) => {
+            Some(DataType::Timestamp)
+        }
+        (DataType::Decimal { scale: sa, .. }, DataType::Decimal { scale: sb, .. }) => {
+            Some(DataType::Decimal {
+                precision: DataType::MAX_DECIMAL_PRECISION,
+                scale: sa.max(sb),
+            })
+        }
+        (DataType::Decimal { scale, .. }, t) | (t, DataType::Decimal { scale, .. })
+            if t.is_integer() =>
+        {
+            Some(DataType::Decimal {
+                precision: DataType::MAX_DECIMAL_PRECISION,
+                scale,
+            })
+        }
+        _ => {
+            let (ra, rb) = (numeric_rank(a)?, numeric_rank(b)?);
+            Some(if ra >= rb { a } else { b })
+        }
+    }
+}
+
+/// Whether `CAST` can convert `from` into `to`.
+fn can_cast(from: DataType, to: DataType) -> bool {
+    from.is_numeric() && to.is_numeric()
+        || matches!(from, DataType::Varchar(_))
+        || matches!(to, DataType::Varchar(_))
+        || matches!(
+            (from, to),
+            (
+                DataType::Date | DataType::Timestamp,
+                DataType::Date | DataType::Timestamp
+            )
+        )
+        || std::mem::discriminant(&from) == std::mem::discriminant(&to)
+}
+
+/// Implicitly convert `expr` to `to`, as an operand of `what`.
+fn coerce(expr: Expr, to: DataType, what: &str, span: Span) -> SqlResult<Expr> {
+    if expr.data_type == to {
+        return Ok(expr);
+    }
+    if matches!(expr.kind, ExprKind::Literal(Value::Null)) {
+        return Ok(Expr::literal(Value::Null, to));
+    }
+    if is_untyped_literal(&expr) || common_type(expr.data_type, to) == Some(to) {
+        return Ok(expr.cast(to));
+    }
+    Err(SqlError::new(
+        format!(
+            "argument of {what} must be type {to}, not type {}",
+            expr.data_type
+        ),
+        span,
+    ))
+}
+
+fn coerce_boolean(expr: Expr, what: &str, span: Span) -> SqlResult<Expr> {
+    coerce(expr, DataType::Boolean, what, span)
+}
+
+/// Convert two operands to one type, letting an untyped literal take the type of the other.
+fn unify(left: Expr, right: Expr, what: &str, span: Span) -> SqlResult<(Expr, Expr)> {
+    let target = match (is_untyped_literal(&left), is_untyped_literal(&right)) {
+        (true, false) => Some(right.data_type),
+        (false, true) => Some(left.data_type),
+        _ => common_type(left.data_type, right.data_type),
+    };
+    let Some(target) = target else {
+        return Err(SqlError::new(
+            format!(
+                "operator does not exist: {} {what} {}",
+                left.data_type, right.data_type
+            ),
+            span,
+        ));
+    };
+    Ok((
+        coerce(left, target, what, span)?,
+        coerce(right, target, what, span)?,
+    ))
+}
+
+fn unify_all(exprs: Vec<Expr>, what: &str, span: Span) -> SqlResult<Vec<Expr>> {
+    let mut target: Option<DataType> = None;
+    for expr in exprs.iter().filter(|expr| !is_untyped_literal(expr)) {
+        target = match target {
+            None => Some(expr.data_type),
+            Some(t) => Some(common_type(t, expr.data_type).ok_or_else(|| {
+                SqlError::new(
+                    format!("{what} types {t} and {} cannot be matched", expr.data_type),
+                    span,
+                )
+            })?),
+        };
+    }
+    let target = target.unwrap_or(DataType::Varchar(None));
+    exprs
+        .into_iter()
+        .map(|expr| coerce(expr, target, what, span))
+        .collect()
+}
+
+fn check_numeric(expr: &Expr, op: &str, span: Span) -> SqlResult<()> {
+    if expr.data_type.is_numeric() {
+        Ok(())
+    } else {
+        Err(SqlError::new(
+            format!("operator does not exist: {op}{}", expr.data_type),
+            span,
+        ))
+    }
+}
+
+fn bind_binary(op: BinaryOp, left: Expr, right: Expr, span: Span) -> SqlResult<Expr> {
+    let symbol = op.symbol();
+    match op {
+        BinaryOp::And | BinaryOp::Or => {
+            let left = coerce_boolean(left, symbol, span)?;
+            let right = coerce_boolean(right, symbol, span)?;
+            Ok(Expr::binary(op, left, right, DataType::Boolean))
+        }
+        BinaryOp::Concat => {
+            let left = left.cast_to_varchar();
+            let right = right.cast_to_varchar();
+            Ok(Expr::binary(op, left, right, DataType::Varchar(None)))
+        }
+        _ if op.is_comparison() => {
+            let (left, right) = unify(left, right, symbol, span)?;
+            Ok(Expr::binary(op, left, right, DataType::Boolean))
+        }
+        _ => {
+            // Arithmetic; a string literal operand is read as a number of the other type.
+            let (left, right) = unify(left, right, symbol, span)?;
+            let (a, b) = (left.data_type, right.data_type);
+            if !a.is_numeric() {
+                return Err(SqlError::new(
+                    format!("operator does not exist: {a} {symbol} {b}"),
+                    span,
+                ));
+            }
+            // Products keep every fractional digit, quotients at least six.
+            let data_type = match (op, a, b) {
+                (
+                    BinaryOp::Multiply,
+                    DataType::Decimal { scale: sa, .. },
+                    DataType::Decimal { scale: sb, .. },
+                ) => DataType::Decimal {
+                    precision: DataType::MAX_DECIMAL_PRECISION,
+                    scale: (sa + sb).min(DataType::MAX_DECIMAL_PRECISION),
+                },
+                (BinaryOp::Divide, DataType::Decimal { scale, .. }, _) => DataType::Decimal {
+                    precision: DataType::MAX_DECIMAL_PRECISION,
+                    scale: scale.max(6),
+                },
+                _ => a,
+            };
+            Ok(Expr::binary(op, left, right, data_type))
+        }
+    }
+}
+
+impl Expr {
+    fn cast_to_varchar(self) -> Expr {
+        if matches!(self.data_type, DataType::Varchar(_)) {
+            self
+        } else {
+            self.cast(DataType::Varchar(None))
+        }
+    }
+}
+
+fn bind_scalar_function(func: ScalarFunction, args: Vec<Expr>, span: Span) -> SqlResult<Expr> {
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(SqlError::new(
+                format!(
+                    "{}() takes {n} argument{}",
+                    func.name(),
+                    if n == 1 { "" } else { "s" }
+                ),
+                span,
+            ))
+        }
+    };
+    let no_such_function = |args: &[Expr]| {
+        let types: Vec<String> = args.iter().map(|arg| arg.data_type.to_string()).collect();
+        SqlError::new(
+            format!(
+                "function {}({}) does not exist",
+                func.name(),
+                types.join(", ")
+            ),
+            span,
+        )
+    };
+    let data_type = match func {
+        ScalarFunction::Lower | ScalarFunction::Upper | ScalarFunction::Length => {
+            arity(1)?;
+            if !matches!(args[0].data_type, DataType::Varchar(_)) {
+                return Err(no_such_function(&args));
+            }
+            if func == ScalarFunction::Length {
+                DataType::Int64
+            } else {
+                DataType::Varchar(None)
+            }
+        }
+        ScalarFunction::Abs => {
+            arity(1)?;
+            if !args[0].data_type.is_numeric() {
+                return Err(no_such_function(&args));
+            }
+            args[0].data_type
+        }
+        ScalarFunction::Coalesce => {
+            if args.is_empty() {
+                return Err(SqlError::new(
+                    "coalesce() needs at least one argument",
+                    span,
+                ));
+            }
+            let args = unify_all(args, "COALESCE", span)?;
+            let data_type = args[0].data_type;
+            return Ok(Expr::new(ExprKind::Function { func, args }, data_type));
+        }
+    };
+    Ok(Expr::new(ExprKind::Function { func, args }, data_type))
+}
+
+/// Convert a value stored into `column`. Unlike [`coerce`], numbers may also narrow and
+/// strings shrink, which is checked when the value is stored.
+fn assign(expr: Expr, column: &Column, span: Span) -> SqlResult<Expr> {
+    let to = column.data_type;
+    if expr.data_type == to {
+        return Ok(expr);
+    }
+    if matches!(expr.kind, ExprKind::Literal(Value::Null)) {
+        return Ok(Expr::literal(Value::Null, to));
+    }
+    let from = expr.data_type;
+    let allowed = is_untyped_literal(&expr)
+        || from.is_numeric() && to.is_numeric()
+        || matches!((from, to), (DataType::Varchar(_), DataType::Varchar(_)))
+        || common_type(from, to).is_some();
+    if allowed {
+        return Ok(expr.cast(to));
+    }
+    Err(SqlError::new(
+        format!(
+            "column \"{}\" is of type {to} but expression is of type {from}",
+            column.name
+        ),
+        span,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        sql::parser::parse_statement,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            index::index::IndexKind,
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    fn with_catalog(f: impl FnOnce(&Binder<BufferPoolManagerImpl>)) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        catalog
+            .create_table(
+                "users",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("name", DataType::Varchar(Some(32))),
+                    Column::new("age", DataType::Int16),
+                    Column::new("joined", DataType::Date),
+                ]),
+            )
+            .unwrap();
+        catalog
+            .create_table(
+                "orders",
+                Schema::new(vec![
+                    Column::new("id", DataType::Int64).not_null(),
+                    Column::new("user_id", DataType::Int64),
+                    Column::new(
+                        "total",
+                        DataType::Decimal {
+                            precision: 10,
+                            scale: 2,
+                        },
+                    ),
+                ]),
+            )
+            .unwrap();
+        catalog
+            .create_index(
+                "orders_user_id",
+                "orders",
+                &["user_id"],
+                IndexKind::BPlusTree,
+                false,
+            )
+            .unwrap();
+        f(&Binder::new(&catalog));
+    }
+
+    fn bind(binder: &Binder<BufferPoolManagerImpl>, sql: &str) -> SqlResult<LogicalPlan> {
+        binder.bind(&parse_statement(sql).unwrap())
+    }
+
+    fn plan(binder: &Binder<BufferPoolManagerImpl>, sql: &str) -> String {
+        bind(binder, sql)
+            .unwrap_or_else(|error| panic!("{}", error.render(sql)))
+            .to_string()
+    }
+
+    fn error(binder: &Binder<BufferPoolManagerImpl>, sql: &str) -> (String, String) {
+        let error = bind(binder, sql).unwrap_err();
+        (
+            error.message.clone(),
+            sql[error.span.start..error.span.end].to_string(),
+        )
+    }
+
+    #[test]
+    fn test_select() {
+        with_catalog(|binder| {
+            assert_eq!(
+                plan(
+                    binder,
+                    "SELECT u.name, o.total * 2 AS doubled FROM users u \
+                     JOIN orders o ON o.user_id = u.id WHERE u.joined >= '2024-01-01' \
+                     ORDER BY doubled DESC, 1 LIMIT 5"
+                ),
+                "Limit 5 offset=0\n\
+                 \x20 Projection #1, (CAST(#6 AS DECIMAL(38, 2)) * CAST(2 AS DECIMAL(38, 2)))\n\
+                 \x20   Sort (CAST(#6 AS DECIMAL(38, 2)) * CAST(2 AS DECIMAL(38, 2))) DESC, #1\n\
+                 \x20     Filter (#3 >= CAST('2024-01-01' AS DATE))\n\
+                 \x20       Join Inner on (#5 = #0)\n\
+                 \x20         SeqScan users\n\
+                 \x20         SeqScan orders\n"
+            );
+
+            let schema = bind(
+                binder,
+                "SELECT *, age + 1 FROM users LEFT JOIN orders ON true",
+            )
+            .unwrap()
+            .schema();
+            assert_eq!(schema.column_count(), 8);
+            assert_eq!(schema.column(4).name, "id");
+            // Padded by the outer join.
+            assert!(schema.column(4).nullable);
+            assert!(!schema.column(0).nullable);
+            assert_eq!(schema.column(7).name, "?column?");
+            assert_eq!(schema.column(7).data_type, DataType::Int32);
+
+            assert_eq!(
+                plan(binder, "SELECT DISTINCT age FROM users ORDER BY age"),
+                "Sort #0\n  Distinct\n    Projection #2\n      SeqScan users\n"
+            );
+            assert_eq!(
+                plan(binder, "SELECT 1 + 2.5, -9223372036854775808"),
+                "Projection (CAST(1 AS DECIMAL(38, 1)) + 2.5), -9223372036854775808\n  Values rows=1\n"
+            );
+        });
+    }
+
+    #[test]
+    fn test_aggregate() {
+        with_catalog(|binder| {
+            assert_eq!(
+                plan(
+                    binder,
+                    "SELECT user_id, count(*), sum(total) / count(*) FROM orders \
+                     GROUP BY user_id HAVING count(*) > 1 ORDER BY max(total)"
+                ),
+                "Projection #0, #1, (#2 / CAST(#1 AS DECIMAL(38, 2)))\n\
+                 \x20 Sort #3\n\
+                 \x20   Filter (#1 > CAST(1 AS INT64))\n\
+                 \x20     Aggregate group_by=[#1] aggregates=[count(*), sum(#2), max(#2)]\n\
+                 \x20       SeqScan orders\n"
+            );
+            let schema = bind(
+                binder,
+                "SELECT user_id, id, count(*) FROM orders GROUP BY ROLLUP (user_id, id), id",
+            )
+            .unwrap()
+            .schema();
+            assert!(schema.column(0).nullable);
+            assert!(!schema.column(1).nullable);
+            assert_eq!(
+                plan(
+                    binder,
+                    "SELECT count(*) FROM orders GROUP BY GROUPING SETS ((user_id, id), (id), ())"
+                ),
+                "Projection #2\n\
+                 \x20 Aggregate group_by=[#1, #0] grouping_sets=[(0, 1), (1), ()] \
+                 aggregates=[count(*)]\n\
+                 \x20   SeqScan orders\n"
+            );
+            assert_eq!(
+                error(binder, "SELECT id, count(*) FROM orders GROUP BY user_id"),
+                (
+                    "column \"id\" must appear in the GROUP BY clause or be used in an aggregate function"
+                        .to_string(),
+                    "id".to_string()
+                )
+            );
+            assert_eq!(
+                error(binder, "SELECT * FROM orders WHERE count(*) > 1").0,
+                "aggregate functions are not allowed in WHERE"
+            );
+            assert_eq!(
+                error(binder, "SELECT sum(name) FROM users").0,
+                "function sum(VARCHAR(32)) does not exist"
+            );
+        });
+    }
+
+    #[test]
+    fn test_name_errors() {
+        with_catalog(|binder| {
+            assert_eq!(
+                error(binder, "SELECT id FROM users, orders"),
+                (
+                    "column reference \"id\" is ambiguous".to_string(),
+                    "id".to_string()
+                )
+            );
+            assert_eq!(
+                error(binder, "SELECT * FROM users JOIN missing ON true"),
+                (
+                    "relation \"missing\" does not exist".to_string(),
+                    "missing".to_string()
+                )
+            );
+            assert_eq!(
+                error(binder, "SELECT u.nope FROM users u"),
+                (
+                    "column \"u.nope\" does not exist".to_string(),
+                    "u.nope".to_string()
+                )
+            );
+            assert_eq!(error(binder, "SELECT users.id FROM users u").1, "users");
+            assert_eq!(
+                error(binder, "SELECT * FROM users, users").0,
+                "table name \"users\" specified more than once"
+            );
+            assert_eq!(
+                error(binder, "SELECT name + 1 FROM users").0,
+                "operator does not exist: VARCHAR(32) + INT32"
+            );
+            assert_eq!(
+                error(binder, "SELECT * FROM users WHERE age").0,
+                "argument of WHERE must be type BOOLEAN, not type INT16"
+            );
+            assert!(bind(binder, "SELECT * FROM users u JOIN users v ON u.id = v.id").is_ok());
+        });
+    }
+
+    #[test]
+    fn test_subquery() {
+        with_catalog(|binder| {
+            assert_eq!(
+                plan(
+                    binder,
+                    "SELECT name FROM users u WHERE id IN (SELECT user_id FROM orders) \
+                     AND NOT EXISTS (SELECT 1 FROM orders o WHERE o.user_id = u.id)"
+                ),
+                "Projection #1\n\
+                 \x20 Filter (#0 IN (subquery) AND NOT EXISTS (subquery))\n\
+                 \x20   SeqScan users\n"
+            );
+            assert_eq!(
+                error(
+                    binder,
+                    "SELECT * FROM users WHERE age > 1 OR EXISTS (SELECT * FROM orders)"
+                ),
+                (
+                    "subqueries are only supported as conditions joined by AND".to_string(),
+                    "EXISTS (SELECT * FROM orders)".to_string()
+                )
+            );
+            assert_eq!(
+                error(binder, "SELECT EXISTS (SELECT * FROM orders) FROM users").0,
+                "subqueries are not supported in SELECT"
+            );
+            assert_eq!(
+                error(
+                    binder,
+                    "SELECT * FROM users WHERE id IN (SELECT id, total FROM orders)"
+                )
+                .0,
+                "subquery has too many columns"
+            );
+            // The subquery sees the columns of the query, but not the other way around.
+            assert_eq!(
+                error(
+                    binder,
+                    "SELECT * FROM users WHERE EXISTS (SELECT * FROM orders WHERE total > age) \
+                     AND user_id = 1"
+                )
+                .0,
+                "column \"user_id\" does not exist"
+            );
+        });
+    }
+
+    #[test]
+    fn test_modifications() {
+        with_catalog(|binder| {
+            assert_eq!(
+                plan(
+                    binder,
+                    "INSERT INTO users (name, id) VALUES ('a', 1), (NULL, 2)"
+                ),
+                "Insert users\n  Values rows=2\n"
+            );
+            let LogicalPlan::Insert { input, .. } =
+                bind(binder, "INSERT INTO users (name, id) VALUES ('a', 1)").unwrap()
+            else {
+                panic!()
+            };
+            let LogicalPlan::Values { rows, .. } = *input else {
+                panic!()
+            };
+            assert_eq!(
+                rows[0].iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+                vec![
+                    "CAST(1 AS INT64)",
+                    "CAST('a' AS VARCHAR(32))",
+                    "NULL",
+                    "NULL"
+                ]
+            );
+            assert_eq!(
+                error(binder, "INSERT INTO users (name) VALUES ('a')").0,
+                "null value in column \"id\" of relation \"users\" violates not-null constraint"
+            );
+            assert_eq!(
+                error(binder, "INSERT INTO users (id, name) VALUES (1)").0,
+                "INSERT has more target columns than expressions"
+            );
+            assert_eq!(
+                error(binder, "INSERT INTO users (id, age) VALUES (1, true)"),
+                (
+                    "column \"age\" is of type INT16 but expression is of type BOOLEAN".to_string(),
+                    "true".to_string()
+                )
+            );
+            assert_eq!(
+                plan(binder, "INSERT INTO orders (id, user_id) SELECT id, id FROM users"),
+                "Insert orders\n  Projection #0, #1, NULL\n    Projection #0, #0\n      SeqScan users\n"
+            );
+            assert_eq!(
+                plan(binder, "UPDATE users SET age = age + 1 WHERE id = 3"),
+                // INT16 + INT32 widens to INT32 and is narrowed back on assignment.
+                "Update users #2=CAST((CAST(#2 AS INT32) + 1) AS INT16)\n  Filter (#0 = CAST(3 AS INT64))\n    SeqScan users\n"
+            );
+            assert_eq!(
+                plan(binder, "DELETE FROM orders"),
+                "Delete orders\n  SeqScan orders\n"
+            );
+            assert_eq!(
+                error(binder, "DELETE FROM lime_tables").0,
+                "cannot modify system table \"lime_tables\""
+            );
+            assert_eq!(
+                error(binder, "CREATE INDEX i ON users (id, name, ID)"),
+                (
+                    "column \"id\" appears more than once in the index".to_string(),
+                    "ID".to_string()
+                )
+            );
+            assert_eq!(
+                error(binder, "CREATE TABLE t (a INT, A INT)").0,
+                "column \"a\" specified more than once"
+            );
+            // Without a table, every table but the system tables.
+            assert_eq!(plan(binder, "ANALYZE"), "Analyze users, orders\n");
+            assert_eq!(plan(binder, "ANALYZE orders"), "Analyze orders\n");
+            assert_eq!(
+                error(binder, "ANALYZE missing").0,
+                "relation \"missing\" does not exist"
+            );
+            assert_eq!(
+                plan(binder, "EXPLAIN ANALYZE DELETE FROM orders"),
+                "Explain ANALYZE\n  Delete orders\n    SeqScan orders\n"
+            );
+            assert_eq!(
+                plan(binder, "EXPLAIN (FORMAT JSON) SELECT id FROM users"),
+                "Explain FORMAT JSON\n  Projection #0\n    SeqScan users\n"
+            );
+            assert_eq!(
+                error(binder, "EXPLAIN CREATE TABLE t (a INT)"),
+                (
+                    "EXPLAIN only supports SELECT, INSERT, UPDATE and DELETE".to_string(),
+                    "EXPLAIN CREATE TABLE t (a INT)".to_string()
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn test_parameters() {
+        with_catalog(|binder| {
+            let binder = Binder::new(binder.catalog).with_parameters(vec![
+                Value::Int64(30),
+                Value::Varchar("alice".to_string()),
+                Value::Null,
+            ]);
+            assert_eq!(
+                plan(
+                    &binder,
+                    "SELECT id FROM users WHERE age > $1 AND name = ? AND joined = ?"
+                ),
+                "Projection #0\n\
+                 \x20 Filter (((CAST(#2 AS INT32) > 30) AND (#1 = CAST('alice' AS VARCHAR(32)))) AND (#3 = NULL))\n\
+                 \x20   SeqScan users\n"
+            );
+            assert_eq!(
+                error(&binder, "SELECT $4"),
+                ("there is no parameter $4".to_string(), "$4".to_string())
+            );
+        });
+    }
+}
@@ -0,0 +1,392 @@
+//! Expressions after binding: columns are positions in the input row, every node is typed and
+//! every implicit conversion is an explicit [`ExprKind::Cast`].
+
+use std::fmt;
+
+use crate::{
+    sql::ast::BinaryOp,
+    types::{data_type::DataType, value::Value},
+};
+
+use super::logical_plan::LogicalPlan;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub data_type: DataType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// The value at this position of the input row.
+    Column(usize),
+    /// The value at this position of the row of the enclosing query, in a correlated
+    /// subquery.
+    OuterColumn(usize),
+    Literal(Value),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    /// Convert the value to the type of this expression.
+    Cast(Box<Expr>),
+    Function {
+        func: ScalarFunction,
+        args: Vec<Expr>,
+    },
+    /// Whether the subquery has any row. Subqueries are not evaluated: the optimizer turns
+    /// them into joins.
+    Exists(Box<LogicalPlan>),
+    /// Whether `expr` equals a value of the single column of the subquery.
+    InSubquery {
+        expr: Box<Expr>,
+        subquery: Box<LogicalPlan>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScalarFunction {
+    Lower,
+    Upper,
+    Length,
+    Abs,
+    Coalesce,
+}
+
+impl ScalarFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "lower" => Self::Lower,
+            "upper" => Self::Upper,
+            "length" => Self::Length,
+            "abs" => Self::Abs,
+            "coalesce" => Self::Coalesce,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lower => "lower",
+            Self::Upper => "upper",
+            Self::Length => "length",
+            Self::Abs => "abs",
+            Self::Coalesce => "coalesce",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "count" => Self::Count,
+            "sum" => Self::Sum,
+            "avg" => Self::Avg,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+}
+
+/// An aggregate call, computed over the input of an aggregation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateExpr {
+    pub func: AggregateFunction,
+    /// None for `count(*)`.
+    pub arg: Option<Expr>,
+    pub distinct: bool,
+    pub data_type: DataType,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, data_type: DataType) -> Self {
+        Self { kind, data_type }
+    }
+
+    pub fn column(index: usize, data_type: DataType) -> Self {
+        Self::new(ExprKind::Column(index), data_type)
+    }
+
+    pub fn literal(value: Value, data_type: DataType) -> Self {
+        Self::new(ExprKind::Literal(value), data_type)
+    }
+
+    pub fn cast(self, data_type: DataType) -> Self {
+        Self::new(ExprKind::Cast(Box::new(self)), data_type)
+    }
+
+    pub fn binary(op: BinaryOp, left: Expr, right: Expr, data_type: DataType) -> Self {
+        Self::new(
+            ExprKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+            data_type,
+        )
+    }
+
+    /// The direct subexpressions.
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Column(_)
+            | ExprKind::OuterColumn(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Exists(_) => Vec::new(),
+            ExprKind::Not(expr)
+            | ExprKind::Negate(expr)
+            | ExprKind::Cast(expr)
+            | ExprKind::IsNull { expr, .. }
+            | ExprKind::InSubquery { expr, .. } => vec![expr],
+            ExprKind::Binary { left, right, .. } => vec![left, right],
+            ExprKind::InList { expr, list, .. } => {
+                std::iter::once(expr.as_ref()).chain(list).collect()
+            }
+            ExprKind::Like { expr, pattern, .. } => vec![expr, pattern],
+            ExprKind::Function { args, .. } => args.iter().collect(),
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match &mut self.kind {
+            ExprKind::Column(_)
+            | ExprKind::OuterColumn(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Exists(_) => Vec::new(),
+            ExprKind::Not(expr)
+            | ExprKind::Negate(expr)
+            | ExprKind::Cast(expr)
+            | ExprKind::IsNull { expr, .. }
+            | ExprKind::InSubquery { expr, .. } => vec![expr],
+            ExprKind::Binary { left, right, .. } => vec![left, right],
+            ExprKind::InList { expr, list, .. } => {
+                std::iter::once(expr.as_mut()).chain(list).collect()
+            }
+            ExprKind::Like { expr, pattern, .. } => vec![expr, pattern],
+            ExprKind::Function { args, .. } => args.iter_mut().collect(),
+        }
+    }
+
+    /// The same expression reading column `f(i)` wherever it read column `i`.
+    pub fn map_columns(mut self, f: &impl Fn(usize) -> usize) -> Expr {
+        self.rewrite_columns(f);
+        self
+    }
+
+    fn rewrite_columns(&mut self, f: &impl Fn(usize) -> usize) {
+        if let ExprKind::Column(index) = &mut self.kind {
+            *index = f(*index);
+        }
+        for child in self.children_mut() {
+            child.rewrite_columns(f);
+        }
+    }
+
+    /// The expression with `f` applied to every node, children before their parent.
+    pub fn transform_up(mut self, f: &mut impl FnMut(Expr) -> Expr) -> Expr {
+        for child in self.children_mut() {
+            let taken = std::mem::replace(child, Expr::literal(Value::Null, DataType::Boolean));
+            *child = taken.transform_up(f);
+        }
+        f(self)
+    }
+
+    /// The same expression with every column `i` replaced by `exprs[i]`.
+    pub fn substitute(self, exprs: &[Expr]) -> Expr {
+        self.transform_up(&mut |expr| match expr.kind {
+            ExprKind::Column(index) => exprs[index].clone(),
+            _ => expr,
+        })
+    }
+
+    /// A correlated expression of a subquery, as an expression over the row of the enclosing
+    /// query followed by the row of the subquery, which starts at column `offset`.
+    pub fn unnest(mut self, offset: usize) -> Expr {
+        self.rewrite_unnest(offset);
+        self
+    }
+
+    fn rewrite_unnest(&mut self, offset: usize) {
+        match self.kind {
+            ExprKind::Column(index) => self.kind = ExprKind::Column(offset + index),
+            ExprKind::OuterColumn(index) => self.kind = ExprKind::Column(index),
+            _ => {}
+        }
+        for child in self.children_mut() {
+            child.rewrite_unnest(offset);
+        }
+    }
+
+    /// Whether the expression reads a column of an enclosing query.
+    pub fn is_correlated(&self) -> bool {
+        matches!(self.kind, ExprKind::OuterColumn(_))
+            || self.children().iter().any(|child| child.is_correlated())
+    }
+
+    /// Whether the expression has an EXISTS or IN subquery.
+    pub fn has_subquery(&self) -> bool {
+        matches!(self.kind, ExprKind::Exists(_) | ExprKind::InSubquery { .. })
+            || self.children().iter().any(|child| child.has_subquery())
+    }
+
+    /// Whether the expression reads nothing but literals, so that it has the same value for
+    /// every row.
+    pub fn is_constant(&self) -> bool {
+        !matches!(
+            self.kind,
+            ExprKind::Column(_)
+                | ExprKind::OuterColumn(_)
+                | ExprKind::Exists(_)
+                | ExprKind::InSubquery { .. }
+        ) && self.children().iter().all(|child| child.is_constant())
+    }
+
+    /// The operands of a chain of ANDs, or the expression itself if it is not one.
+    pub fn split_conjunction(self) -> Vec<Expr> {
+        match self.kind {
+            ExprKind::Binary {
+                op: BinaryOp::And,
+                left,
+                right,
+            } => {
+                let mut conjuncts = left.split_conjunction();
+                conjuncts.extend(right.split_conjunction());
+                conjuncts
+            }
+            kind => vec![Expr::new(kind, self.data_type)],
+        }
+    }
+
+    /// The AND of `conjuncts`, or None if there are none.
+    pub fn conjunction(conjuncts: Vec<Expr>) -> Option<Expr> {
+        conjuncts
+            .into_iter()
+            .reduce(|left, right| Expr::binary(BinaryOp::And, left, right, DataType::Boolean))
+    }
+
+    /// Every column the expression reads, in order of appearance.
+    pub fn columns(&self) -> Vec<usize> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns(&self, columns: &mut Vec<usize>) {
+        if let ExprKind::Column(index) = self.kind {
+            columns.push(index);
+        }
+        for child in self.children() {
+            child.collect_columns(columns);
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Column(index) => write!(f, "#{index}"),
+            ExprKind::OuterColumn(index) => write!(f, "outer#{index}"),
+            ExprKind::Literal(Value::Varchar(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            ExprKind::Literal(value) => write!(f, "{value}"),
+            ExprKind::Not(expr) => write!(f, "NOT {expr}"),
+            ExprKind::Negate(expr) => write!(f, "-{expr}"),
+            ExprKind::Binary { op, left, right } => write!(f, "({left} {} {right})", op.symbol()),
+            ExprKind::IsNull { expr, negated } => {
+                write!(f, "{expr} IS {}NULL", if *negated { "NOT " } else { "" })
+            }
+            ExprKind::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                write!(f, "{expr} {}IN (", if *negated { "NOT " } else { "" })?;
+                write_list(f, list)?;
+                write!(f, ")")
+            }
+            ExprKind::Like {
+                expr,
+                pattern,
+                negated,
+            } => write!(
+                f,
+                "{expr} {}LIKE {pattern}",
+                if *negated { "NOT " } else { "" }
+            ),
+            ExprKind::Cast(expr) => write!(f, "CAST({expr} AS {})", self.data_type),
+            ExprKind::Function { func, args } => {
+                write!(f, "{}(", func.name())?;
+                write_list(f, args)?;
+                write!(f, ")")
+            }
+            ExprKind::Exists(_) => write!(f, "EXISTS (subquery)"),
+            ExprKind::InSubquery { expr, negated, .. } => {
+                write!(
+                    f,
+                    "{expr} {}IN (subquery)",
+                    if *negated { "NOT " } else { "" }
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for AggregateExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.func.name())?;
+        if self.distinct {
+            write!(f, "DISTINCT ")?;
+        }
+        match &self.arg {
+            Some(arg) => write!(f, "{arg})"),
+            None => write!(f, "*)"),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, exprs: &[Expr]) -> fmt::Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{expr}")?;
+    }
+    Ok(())
+}
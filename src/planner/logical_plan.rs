@@ -0,0 +1,579 @@
+//! The bound logical plan: a tree of relational operators whose expressions refer to the
+//! columns of their input by position, so nothing is looked up by name after binding.
+
+use std::{fmt, ops::Bound, sync::Arc};
+
+use crate::{
+    catalog::{
+        catalog::{IndexInfo, TableInfo},
+        schema::{Column, Schema},
+    },
+    sql::ast::ExplainFormat,
+    storage::index::index::IndexKind,
+    types::{data_type::DataType, value::Value},
+};
+
+use super::expr::{AggregateExpr, Expr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+    /// The left rows that have a match, each once.
+    Semi,
+    /// The left rows that have no match.
+    Anti,
+}
+
+impl JoinType {
+    /// Whether the join outputs the columns of the right input.
+    pub fn outputs_right(&self) -> bool {
+        !matches!(self, JoinType::Semi | JoinType::Anti)
+    }
+
+    /// Whether the left columns can be NULL padding, for right rows without a match.
+    pub fn pads_left(&self) -> bool {
+        matches!(self, JoinType::Right | JoinType::Full)
+    }
+
+    /// Whether the right columns can be NULL padding, for left rows without a match.
+    pub fn pads_right(&self) -> bool {
+        matches!(self, JoinType::Left | JoinType::Full)
+    }
+
+    /// Whether left rows without a match are output.
+    pub fn keeps_unmatched_left(&self) -> bool {
+        self.pads_right() || *self == JoinType::Anti
+    }
+
+    /// Whether right rows without a match are output.
+    pub fn keeps_unmatched_right(&self) -> bool {
+        self.pads_left()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalPlan {
+    SeqScan {
+        table: Arc<TableInfo>,
+    },
+    /// The rows of `table` whose column indexed by `index` lies between the bounds.
+    IndexScan {
+        table: Arc<TableInfo>,
+        index: Arc<IndexInfo>,
+        /// The bounds of the first indexed column.
+        lower: Bound<Value>,
+        upper: Bound<Value>,
+    },
+    /// Rows of constant expressions.
+    Values {
+        rows: Vec<Vec<Expr>>,
+        schema: Schema,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: Expr,
+    },
+    Projection {
+        input: Box<LogicalPlan>,
+        exprs: Vec<Expr>,
+        schema: Schema,
+    },
+    /// The columns of the left input followed by those of the right input, unless the join
+    /// type [does not output them](JoinType::outputs_right).
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        join_type: JoinType,
+        /// Evaluated over the joined row; None joins every pair.
+        condition: Option<Expr>,
+    },
+    /// The group-by values followed by the aggregates of each group.
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<Expr>,
+        /// The rows are grouped by each set of positions in `group_by` in turn, and the values
+        /// outside the set are NULL in its groups. A plain GROUP BY has the single set of every
+        /// position, and an aggregation without GROUP BY the empty set.
+        grouping_sets: Vec<Vec<usize>>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<SortKey>,
+    },
+    Distinct {
+        input: Box<LogicalPlan>,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        limit: Option<usize>,
+        offset: usize,
+    },
+    /// Insert the rows of `input`, which have the columns of the table in order.
+    Insert {
+        table: Arc<TableInfo>,
+        input: Box<LogicalPlan>,
+    },
+    /// Update the rows of `input`, which scans the table, setting each column to its
+    /// expression over the old row.
+    Update {
+        table: Arc<TableInfo>,
+        input: Box<LogicalPlan>,
+        assignments: Vec<(usize, Expr)>,
+    },
+    /// Delete the rows of `input`, which scans the table.
+    Delete {
+        table: Arc<TableInfo>,
+        input: Box<LogicalPlan>,
+    },
+    CreateTable {
+        name: String,
+        schema: Schema,
+        if_not_exists: bool,
+    },
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    CreateIndex {
+        name: String,
+        table: String,
+        columns: Vec<String>,
+        kind: IndexKind,
+        unique: bool,
+    },
+    DropIndex {
+        name: String,
+        if_exists: bool,
+    },
+    /// Compute the statistics of `tables` and store them in the catalog.
+    Analyze {
+        tables: Vec<Arc<TableInfo>>,
+    },
+    /// The operators of `input` with their estimates, and what they did if `analyze`, as rows
+    /// of text.
+    Explain {
+        input: Box<LogicalPlan>,
+        analyze: bool,
+        format: ExplainFormat,
+    },
+}
+
+impl LogicalPlan {
+    /// The columns of the rows the plan produces. Modifications produce the number of rows
+    /// they changed, and other statements produce nothing.
+    pub fn schema(&self) -> Schema {
+        match self {
+            LogicalPlan::SeqScan { table } | LogicalPlan::IndexScan { table, .. } => {
+                table.schema.clone()
+            }
+            LogicalPlan::Values { schema, .. }
+            | LogicalPlan::Projection { schema, .. }
+            | LogicalPlan::Aggregate { schema, .. } => schema.clone(),
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Distinct { input }
+            | LogicalPlan::Limit { input, .. } => input.schema(),
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                ..
+            } => join_schema(&left.schema(), &right.schema(), *join_type),
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. } => {
+                Schema::new(vec![Column::new("count", DataType::Int64).not_null()])
+            }
+            LogicalPlan::CreateTable { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::DropIndex { .. }
+            | LogicalPlan::Analyze { .. } => Schema::new(Vec::new()),
+            LogicalPlan::Explain { .. } => explain_schema(),
+        }
+    }
+
+    pub fn children(&self) -> Vec<&LogicalPlan> {
+        match self {
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Projection { input, .. }
+            | LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Distinct { input }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::Insert { input, .. }
+            | LogicalPlan::Update { input, .. }
+            | LogicalPlan::Delete { input, .. }
+            | LogicalPlan::Explain { input, .. } => vec![input],
+            LogicalPlan::Join { left, right, .. } => vec![left, right],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The same operator over the inputs `f` makes of its inputs.
+    pub fn map_children(
+        self,
+        f: &mut impl FnMut(LogicalPlan) -> anyhow::Result<LogicalPlan>,
+    ) -> anyhow::Result<LogicalPlan> {
+        let mut map = |input: Box<LogicalPlan>| f(*input).map(Box::new);
+        Ok(match self {
+            LogicalPlan::Filter { input, predicate } => LogicalPlan::Filter {
+                input: map(input)?,
+                predicate,
+            },
+            LogicalPlan::Projection {
+                input,
+                exprs,
+                schema,
+            } => LogicalPlan::Projection {
+                input: map(input)?,
+                exprs,
+                schema,
+            },
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => LogicalPlan::Join {
+                left: map(left)?,
+                right: map(right)?,
+                join_type,
+                condition,
+            },
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                grouping_sets,
+                aggregates,
+                schema,
+            } => LogicalPlan::Aggregate {
+                input: map(input)?,
+                group_by,
+                grouping_sets,
+                aggregates,
+                schema,
+            },
+            LogicalPlan::Sort { input, keys } => LogicalPlan::Sort {
+                input: map(input)?,
+                keys,
+            },
+            LogicalPlan::Distinct { input } => LogicalPlan::Distinct { input: map(input)? },
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => LogicalPlan::Limit {
+                input: map(input)?,
+                limit,
+                offset,
+            },
+            LogicalPlan::Insert { table, input } => LogicalPlan::Insert {
+                table,
+                input: map(input)?,
+            },
+            LogicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => LogicalPlan::Update {
+                table,
+                input: map(input)?,
+                assignments,
+            },
+            LogicalPlan::Delete { table, input } => LogicalPlan::Delete {
+                table,
+                input: map(input)?,
+            },
+            LogicalPlan::Explain {
+                input,
+                analyze,
+                format,
+            } => LogicalPlan::Explain {
+                input: map(input)?,
+                analyze,
+                format,
+            },
+            plan => plan,
+        })
+    }
+
+    /// The expressions of the operator itself, without those of its inputs.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            LogicalPlan::Values { rows, .. } => rows.iter().flatten().collect(),
+            LogicalPlan::Filter { predicate, .. } => vec![predicate],
+            LogicalPlan::Projection { exprs, .. } => exprs.iter().collect(),
+            LogicalPlan::Join { condition, .. } => condition.iter().collect(),
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by
+                .iter()
+                .chain(
+                    aggregates
+                        .iter()
+                        .filter_map(|aggregate| aggregate.arg.as_ref()),
+                )
+                .collect(),
+            LogicalPlan::Sort { keys, .. } => keys.iter().map(|key| &key.expr).collect(),
+            LogicalPlan::Update { assignments, .. } => {
+                assignments.iter().map(|(_, expr)| expr).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The same operator with `f` applied to each of its own expressions.
+    pub fn map_exprs(self, f: &mut impl FnMut(Expr) -> Expr) -> LogicalPlan {
+        match self {
+            LogicalPlan::Values { rows, schema } => LogicalPlan::Values {
+                rows: rows
+                    .into_iter()
+                    .map(|row| row.into_iter().map(&mut *f).collect())
+                    .collect(),
+                schema,
+            },
+            LogicalPlan::Filter { input, predicate } => LogicalPlan::Filter {
+                input,
+                predicate: f(predicate),
+            },
+            LogicalPlan::Projection {
+                input,
+                exprs,
+                schema,
+            } => LogicalPlan::Projection {
+                input,
+                exprs: exprs.into_iter().map(f).collect(),
+                schema,
+            },
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition: condition.map(f),
+            },
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                grouping_sets,
+                aggregates,
+                schema,
+            } => LogicalPlan::Aggregate {
+                input,
+                group_by: group_by.into_iter().map(&mut *f).collect(),
+                grouping_sets,
+                aggregates: aggregates
+                    .into_iter()
+                    .map(|aggregate| AggregateExpr {
+                        arg: aggregate.arg.map(&mut *f),
+                        ..aggregate
+                    })
+                    .collect(),
+                schema,
+            },
+            LogicalPlan::Sort { input, keys } => LogicalPlan::Sort {
+                input,
+                keys: keys
+                    .into_iter()
+                    .map(|key| SortKey {
+                        expr: f(key.expr),
+                        ..key
+                    })
+                    .collect(),
+            },
+            LogicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => LogicalPlan::Update {
+                table,
+                input,
+                assignments: assignments
+                    .into_iter()
+                    .map(|(column, expr)| (column, f(expr)))
+                    .collect(),
+            },
+            plan => plan,
+        }
+    }
+
+    /// A one-line description of the operator itself, without its inputs.
+    pub fn describe(&self) -> String {
+        match self {
+            LogicalPlan::SeqScan { table } => format!("SeqScan {}", table.name),
+            LogicalPlan::IndexScan {
+                table,
+                index,
+                lower,
+                upper,
+            } => {
+                let lower = match lower {
+                    Bound::Included(key) => format!("[{key}"),
+                    Bound::Excluded(key) => format!("({key}"),
+                    Bound::Unbounded => "(-inf".to_string(),
+                };
+                let upper = match upper {
+                    Bound::Included(key) => format!("{key}]"),
+                    Bound::Excluded(key) => format!("{key})"),
+                    Bound::Unbounded => "+inf)".to_string(),
+                };
+                format!(
+                    "IndexScan {} using {} {lower}, {upper}",
+                    table.name, index.name
+                )
+            }
+            LogicalPlan::Values { rows, .. } => format!("Values rows={}", rows.len()),
+            LogicalPlan::Filter { predicate, .. } => format!("Filter {predicate}"),
+            LogicalPlan::Projection { exprs, .. } => format!("Projection {}", join(exprs)),
+            LogicalPlan::Join {
+                join_type,
+                condition,
+                ..
+            } => match condition {
+                Some(condition) => format!("Join {join_type:?} on {condition}"),
+                None => format!("Join {join_type:?}"),
+            },
+            LogicalPlan::Aggregate {
+                group_by,
+                grouping_sets,
+                aggregates,
+                ..
+            } => {
+                let mut description = format!("Aggregate group_by=[{}]", join(group_by));
+                if *grouping_sets != [(0..group_by.len()).collect::<Vec<_>>()] {
+                    let sets: Vec<String> = grouping_sets
+                        .iter()
+                        .map(|set| format!("({})", join(set)))
+                        .collect();
+                    description += &format!(" grouping_sets=[{}]", sets.join(", "));
+                }
+                description + &format!(" aggregates=[{}]", join(aggregates))
+            }
+            LogicalPlan::Sort { keys, .. } => format!(
+                "Sort {}",
+                keys.iter()
+                    .map(|key| format!("{}{}", key.expr, if key.descending { " DESC" } else { "" }))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            LogicalPlan::Distinct { .. } => "Distinct".to_string(),
+            LogicalPlan::Limit { limit, offset, .. } => match limit {
+                Some(limit) => format!("Limit {limit} offset={offset}"),
+                None => format!("Limit ALL offset={offset}"),
+            },
+            LogicalPlan::Insert { table, .. } => format!("Insert {}", table.name),
+            LogicalPlan::Update {
+                table, assignments, ..
+            } => format!(
+                "Update {} {}",
+                table.name,
+                assignments
+                    .iter()
+                    .map(|(i, expr)| format!("#{i}={expr}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            LogicalPlan::Delete { table, .. } => format!("Delete {}", table.name),
+            LogicalPlan::CreateTable { name, .. } => format!("CreateTable {name}"),
+            LogicalPlan::DropTable { name, .. } => format!("DropTable {name}"),
+            LogicalPlan::CreateIndex { name, .. } => format!("CreateIndex {name}"),
+            LogicalPlan::DropIndex { name, .. } => format!("DropIndex {name}"),
+            LogicalPlan::Analyze { tables } => format!(
+                "Analyze {}",
+                tables
+                    .iter()
+                    .map(|table| table.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            LogicalPlan::Explain {
+                analyze, format, ..
+            } => {
+                let mut description = "Explain".to_string();
+                if *analyze {
+                    description += " ANALYZE";
+                }
+                if *format == ExplainFormat::Json {
+                    description += " FORMAT JSON";
+                }
+                description
+            }
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{}{}", "  ".repeat(depth), self.describe())?;
+        for child in self.children() {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every operator on its own line, inputs indented below it.
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// The output of INSERT, UPDATE and DELETE: the number of rows they changed.
+pub fn modification_schema() -> Schema {
+    Schema::new(vec![Column::new("count", DataType::Int64).not_null()])
+}
+
+/// The output of EXPLAIN: a line of the plan per row, or the whole JSON document in one.
+pub fn explain_schema() -> Schema {
+    Schema::new(vec![
+        Column::new("QUERY PLAN", DataType::Varchar(None)).not_null()
+    ])
+}
+
+/// The output columns of a join. Columns of the side an outer join pads with NULLs become
+/// nullable.
+pub fn join_schema(left: &Schema, right: &Schema, join_type: JoinType) -> Schema {
+    let nullable = |column: &Column, padded: bool| Column {
+        nullable: column.nullable || padded,
+        ..column.clone()
+    };
+    let mut columns: Vec<Column> = left
+        .columns()
+        .iter()
+        .map(|column| nullable(column, join_type.pads_left()))
+        .collect();
+    if join_type.outputs_right() {
+        columns.extend(
+            right
+                .columns()
+                .iter()
+                .map(|column| nullable(column, join_type.pads_right())),
+        );
+    }
+    Schema::new(columns)
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,4 @@
+pub mod log_manager;
+pub mod log_record;
+#[allow(clippy::module_inception)]
+pub mod recovery;
@@ -0,0 +1,516 @@
+//! The write-ahead log: an append-only file of records numbered by log sequence numbers.
+//!
+//! A change to a page is appended to the log first, and the page is stamped with the LSN of
+//! the record. The buffer pool flushes the log up to the LSN of a dirty page before writing
+//! the page, so that a page on disk never holds a change the log could have lost.
+//!
+//! Records are appended to a buffer in memory and reach the file when the log is flushed.
+//! Flushes are grouped: while one thread writes and syncs the buffer, threads that want their
+//! records durable wait, and the next of them to run writes everything appended meanwhile
+//! with a single sync for all of them.
+//!
+//! Every record is framed as follows, little-endian:
+//!
+//! | offset | size | field                          |
+//! |--------|------|--------------------------------|
+//! | 0      | 4    | length of the payload          |
+//! | 4      | 8    | LSN                            |
+//! | 12     | 4    | CRC-32 of the LSN and payload  |
+//! | 16     | n    | payload                        |
+//!
+//! A record cut short by a crash fails its checksum, and the log is truncated before it when
+//! it is opened again.
+//!
+//! Once the pages are written up to some LSN, a checkpoint truncates the records before it
+//! with [`LogManager::truncate_before`], so that the log does not grow without bound.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+};
+
+use crate::storage::page::page::Lsn;
+
+/// The LSN of no record. Pages that were never logged carry it.
+pub const INVALID_LSN: Lsn = 0;
+/// The bytes of the frame of a record before its payload.
+pub const RECORD_HEADER_SIZE: usize = 16;
+/// Appended records are flushed once their buffer grows past this many bytes.
+pub const LOG_BUFFER_SIZE: usize = 1 << 20;
+
+/// A record read back from the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    pub lsn: Lsn,
+    pub payload: Vec<u8>,
+}
+
+/// Counts of what a log did since it was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogStats {
+    /// Records appended.
+    pub appends: u64,
+    /// Times the file was synced, each for any number of records.
+    pub syncs: u64,
+}
+
+pub struct LogManager {
+    path: PathBuf,
+    /// Records appended but not yet written to the file.
+    buffer: Mutex<LogBuffer>,
+    flush_state: Mutex<FlushState>,
+    /// Signaled when a flush ends.
+    flushed: Condvar,
+}
+
+struct LogBuffer {
+    bytes: Vec<u8>,
+    /// The LSN the next record gets.
+    next_lsn: Lsn,
+    appends: u64,
+}
+
+struct FlushState {
+    file: File,
+    /// Where the records written so far end, and the next are written.
+    end: u64,
+    /// Every record up to this LSN is durable.
+    flushed_lsn: Lsn,
+    /// Whether a thread is writing the buffer, which the others wait for.
+    flushing: bool,
+    syncs: u64,
+}
+
+impl LogManager {
+    /// The path of the log of the database file `db_path`.
+    pub fn path_for(db_path: impl AsRef<Path>) -> PathBuf {
+        let mut path = db_path.as_ref().as_os_str().to_owned();
+        path.push(".wal");
+        PathBuf::from(path)
+    }
+
+    /// Open the log at `path`, creating it if it does not exist. A torn record at the end,
+    /// left by a crash in the middle of a write, is cut off along with anything after it.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let (records, end) = read_records(&mut file)?;
+        if end < file.metadata()?.len() {
+            file.set_len(end)?;
+            file.sync_data()?;
+        }
+        let last_lsn = records.last().map_or(INVALID_LSN, |record| record.lsn);
+        Ok(Self {
+            path,
+            buffer: Mutex::new(LogBuffer {
+                bytes: Vec::new(),
+                next_lsn: last_lsn + 1,
+                appends: 0,
+            }),
+            flush_state: Mutex::new(FlushState {
+                file,
+                end,
+                flushed_lsn: last_lsn,
+                flushing: false,
+                syncs: 0,
+            }),
+            flushed: Condvar::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record and return its LSN. The record is durable once the log is flushed up
+    /// to it.
+    pub fn append(&self, payload: &[u8]) -> anyhow::Result<Lsn> {
+        let (lsn, full) = self.push(payload)?;
+        if full {
+            self.flush(lsn)?;
+        }
+        Ok(lsn)
+    }
+
+    /// Append a record like [`append`](Self::append), but leave it in memory even if the
+    /// buffer is full, for callers that cannot handle an I/O error. It is written by the next
+    /// flush, which the buffer pool does at the latest before it writes a page stamped with
+    /// the LSN. Only fails if the record is too long.
+    pub fn append_buffered(&self, payload: &[u8]) -> anyhow::Result<Lsn> {
+        Ok(self.push(payload)?.0)
+    }
+
+    /// Add a record to the buffer and return its LSN and whether the buffer is full.
+    fn push(&self, payload: &[u8]) -> anyhow::Result<(Lsn, bool)> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| anyhow::anyhow!("log record of {} bytes is too long", payload.len()))?;
+        let mut buffer = self.buffer.lock().unwrap();
+        let lsn = buffer.next_lsn;
+        buffer.next_lsn += 1;
+        buffer.appends += 1;
+        frame(&mut buffer.bytes, len, lsn, payload);
+        Ok((lsn, buffer.bytes.len() >= LOG_BUFFER_SIZE))
+    }
+
+    /// The LSN of the last record appended, or [`INVALID_LSN`] if there is none.
+    pub fn last_lsn(&self) -> Lsn {
+        self.buffer.lock().unwrap().next_lsn - 1
+    }
+
+    /// The LSN up to which every record is durable.
+    pub fn flushed_lsn(&self) -> Lsn {
+        self.flush_state.lock().unwrap().flushed_lsn
+    }
+
+    pub fn stats(&self) -> LogStats {
+        let appends = self.buffer.lock().unwrap().appends;
+        LogStats {
+            appends,
+            syncs: self.flush_state.lock().unwrap().syncs,
+        }
+    }
+
+    /// Make every record up to `lsn` durable, waiting for a flush in progress rather than
+    /// syncing the file again if that flush covers it.
+    pub fn flush(&self, lsn: Lsn) -> anyhow::Result<()> {
+        let last_lsn = self.last_lsn();
+        if lsn > last_lsn {
+            anyhow::bail!("cannot flush the log up to LSN {lsn}, past its last record {last_lsn}");
+        }
+        let mut state = self.flush_state.lock().unwrap();
+        loop {
+            if state.flushed_lsn >= lsn {
+                return Ok(());
+            }
+            if !state.flushing {
+                break;
+            }
+            state = self.flushed.wait(state).unwrap();
+        }
+        let mut file = state.file.try_clone()?;
+        let end = state.end;
+        state.flushing = true;
+        // Take everything appended so far, including the records of threads that are waiting.
+        let (bytes, last_lsn) = {
+            let mut buffer = self.buffer.lock().unwrap();
+            (mem::take(&mut buffer.bytes), buffer.next_lsn - 1)
+        };
+        // Write without holding the state, so that others can see that a flush is running. A
+        // write that failed halfway is overwritten when the records are written again.
+        drop(state);
+        let result = file
+            .seek(io::SeekFrom::Start(end))
+            .and_then(|_| file.write_all(&bytes))
+            .and_then(|()| file.sync_data());
+
+        let mut state = self.flush_state.lock().unwrap();
+        state.flushing = false;
+        match &result {
+            Ok(()) => {
+                state.flushed_lsn = last_lsn;
+                state.end += bytes.len() as u64;
+                state.syncs += 1;
+            }
+            Err(_) => {
+                // Put the records back in front of those appended since, to be written again.
+                let mut buffer = self.buffer.lock().unwrap();
+                let appended = mem::replace(&mut buffer.bytes, bytes);
+                buffer.bytes.extend_from_slice(&appended);
+            }
+        }
+        self.flushed.notify_all();
+        Ok(result?)
+    }
+
+    /// Flush every record appended so far.
+    pub fn flush_all(&self) -> anyhow::Result<()> {
+        self.flush(self.last_lsn())
+    }
+
+    /// The bytes of the records in the file and of those appended since.
+    pub fn size(&self) -> u64 {
+        let buffered = self.buffer.lock().unwrap().bytes.len() as u64;
+        self.flush_state.lock().unwrap().end + buffered
+    }
+
+    /// Drop the records before `lsn` from the file, once the pages they changed are on disk.
+    /// The file is rewritten with the records from `lsn` on, which must include a durable one
+    /// so that the LSNs go on from there when the log is opened again. Records appended but
+    /// not yet flushed are left to the next flush.
+    pub fn truncate_before(&self, lsn: Lsn) -> anyhow::Result<()> {
+        let mut state = self.flush_state.lock().unwrap();
+        while state.flushing {
+            state = self.flushed.wait(state).unwrap();
+        }
+        if lsn > state.flushed_lsn {
+            anyhow::bail!(
+                "cannot truncate the log before LSN {lsn}, past its durable records up to {}",
+                state.flushed_lsn
+            );
+        }
+        let (records, _) = read_records(&mut state.file)?;
+        let mut bytes = Vec::new();
+        for record in records.iter().filter(|record| record.lsn >= lsn) {
+            frame(
+                &mut bytes,
+                record.payload.len() as u32,
+                record.lsn,
+                &record.payload,
+            );
+        }
+        // Written aside and renamed over the log, so that a crash leaves one or the other.
+        let mut tmp_path = self.path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&bytes)?;
+        tmp.sync_data()?;
+        drop(tmp);
+        std::fs::rename(&tmp_path, &self.path)?;
+        state.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        state.end = bytes.len() as u64;
+
+        Ok(())
+    }
+
+    /// Read every record that has been flushed, in order.
+    pub fn records(&self) -> anyhow::Result<Vec<LogRecord>> {
+        let mut file = File::open(&self.path)?;
+        let (records, _) = read_records(&mut file)?;
+        Ok(records)
+    }
+}
+
+impl Drop for LogManager {
+    fn drop(&mut self) {
+        // Pages are only written after their records are durable, so a record that cannot be
+        // written here only describes a change that is lost along with its page.
+        let _ = self.flush_all();
+    }
+}
+
+/// Read the records of `file` up to the first one that is torn or out of sequence, and
+/// return them with the offset where they end.
+fn read_records(file: &mut File) -> anyhow::Result<(Vec<LogRecord>, u64)> {
+    let mut bytes = Vec::new();
+    file.seek(io::SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while let Some(header) = bytes.get(pos..pos + RECORD_HEADER_SIZE) {
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let lsn = u64::from_le_bytes(header[4..12].try_into().unwrap());
+        let crc = u32::from_le_bytes(header[12..16].try_into().unwrap());
+        let start = pos + RECORD_HEADER_SIZE;
+        let Some(payload) = bytes.get(start..start + len) else {
+            break;
+        };
+        let expected_lsn = records
+            .last()
+            .map_or(lsn, |record: &LogRecord| record.lsn + 1);
+        if lsn == INVALID_LSN || lsn != expected_lsn || checksum(lsn, payload) != crc {
+            break;
+        }
+        records.push(LogRecord {
+            lsn,
+            payload: payload.to_vec(),
+        });
+        pos = start + len;
+    }
+    Ok((records, pos as u64))
+}
+
+/// Append the record `lsn` with `payload` of `len` bytes to `bytes`.
+fn frame(bytes: &mut Vec<u8>, len: u32, lsn: Lsn, payload: &[u8]) {
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(&lsn.to_le_bytes());
+    bytes.extend_from_slice(&checksum(lsn, payload).to_le_bytes());
+    bytes.extend_from_slice(payload);
+}
+
+/// The CRC-32 (IEEE) of the LSN and the payload of a record.
+fn checksum(lsn: Lsn, payload: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in lsn.to_le_bytes().iter().chain(payload) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Barrier, thread};
+
+    use super::*;
+
+    #[test]
+    fn test_append_and_reopen() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = LogManager::path_for(tempdir.path().join("test.db"));
+        assert_eq!(path, tempdir.path().join("test.db.wal"));
+        {
+            let log = LogManager::open(&path).unwrap();
+            assert_eq!(log.last_lsn(), INVALID_LSN);
+            assert_eq!(log.append(b"first").unwrap(), 1);
+            assert_eq!(log.append(b"").unwrap(), 2);
+            assert_eq!(log.append(b"third").unwrap(), 3);
+            assert_eq!(log.flushed_lsn(), INVALID_LSN);
+            log.flush(2).unwrap();
+            // A flush writes everything appended before it.
+            assert_eq!(log.flushed_lsn(), 3);
+            assert_eq!(
+                log.stats(),
+                LogStats {
+                    appends: 3,
+                    syncs: 1
+                }
+            );
+            log.flush(1).unwrap();
+            assert_eq!(log.stats().syncs, 1);
+            assert!(log.flush(4).is_err());
+            assert_eq!(log.records().unwrap().len(), 3);
+            log.append(b"fourth").unwrap();
+        }
+
+        // Dropping the log flushed the last record.
+        let log = LogManager::open(&path).unwrap();
+        assert_eq!(log.flushed_lsn(), 4);
+        assert_eq!(log.append(b"fifth").unwrap(), 5);
+        log.flush_all().unwrap();
+        let records = log.records().unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.lsn, record.payload.as_slice()))
+                .collect::<Vec<_>>(),
+            [
+                (1, b"first".as_slice()),
+                (2, b""),
+                (3, b"third"),
+                (4, b"fourth"),
+                (5, b"fifth")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_torn_record() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.wal");
+        {
+            let log = LogManager::open(&path).unwrap();
+            log.append(b"kept").unwrap();
+            log.append(b"torn").unwrap();
+        }
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 1).unwrap();
+        drop(file);
+
+        let log = LogManager::open(&path).unwrap();
+        assert_eq!(log.last_lsn(), 1);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            (RECORD_HEADER_SIZE + 4) as u64
+        );
+        assert_eq!(log.append(b"next").unwrap(), 2);
+        log.flush_all().unwrap();
+        let payloads: Vec<_> = log
+            .records()
+            .unwrap()
+            .into_iter()
+            .map(|record| record.payload)
+            .collect();
+        assert_eq!(payloads, [b"kept".to_vec(), b"next".to_vec()]);
+
+        // A corrupted payload is cut off as well.
+        drop(log);
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(LogManager::open(&path).unwrap().last_lsn(), 1);
+    }
+
+    #[test]
+    fn test_truncate_before() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.wal");
+        let log = LogManager::open(&path).unwrap();
+        for i in 0..10 {
+            log.append(&[i; 100]).unwrap();
+        }
+        log.flush(8).unwrap();
+        let size = log.size();
+        assert_eq!(size, 10 * (RECORD_HEADER_SIZE as u64 + 100));
+        assert!(log.truncate_before(12).is_err());
+
+        log.truncate_before(9).unwrap();
+        assert_eq!(log.size(), 2 * (RECORD_HEADER_SIZE as u64 + 100));
+        // The records appended later are written after those kept.
+        log.append(b"next").unwrap();
+        log.flush_all().unwrap();
+        let lsns: Vec<_> = log
+            .records()
+            .unwrap()
+            .iter()
+            .map(|record| record.lsn)
+            .collect();
+        assert_eq!(lsns, [9, 10, 11]);
+        drop(log);
+
+        // The LSNs go on from the records kept.
+        let log = LogManager::open(&path).unwrap();
+        assert_eq!(log.last_lsn(), 11);
+        log.truncate_before(11).unwrap();
+        drop(log);
+        assert_eq!(
+            LogManager::open(&path).unwrap().append(b"last").unwrap(),
+            12
+        );
+    }
+
+    #[test]
+    fn test_group_commit() {
+        const THREADS: usize = 8;
+        const COMMITS: usize = 20;
+        let tempdir = tempfile::tempdir().unwrap();
+        let log = LogManager::open(tempdir.path().join("test.wal")).unwrap();
+        let barrier = Barrier::new(THREADS);
+        thread::scope(|scope| {
+            for i in 0..THREADS {
+                let (log, barrier) = (&log, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    for j in 0..COMMITS {
+                        let lsn = log.append(format!("{i}-{j}").as_bytes()).unwrap();
+                        log.flush(lsn).unwrap();
+                        assert!(log.flushed_lsn() >= lsn);
+                    }
+                });
+            }
+        });
+        let stats = log.stats();
+        assert_eq!(stats.appends, (THREADS * COMMITS) as u64);
+        assert!(stats.syncs <= stats.appends);
+        let records = log.records().unwrap();
+        assert_eq!(records.len(), THREADS * COMMITS);
+        assert!(records
+            .iter()
+            .enumerate()
+            .all(|(i, record)| record.lsn == i as u64 + 1));
+    }
+}
@@ -0,0 +1,247 @@
+//! The payloads of the records that changes to pages are logged with.
+//!
+//! A change is logged as the byte ranges of the page that differ from what it held before, or
+//! that were written in place, when the
+//! [`WritePageGuard`](crate::buffer::page_guard::WritePageGuard) it was made through is dropped. Every layer that writes pages through the buffer pool is logged that way: table
+//! heaps, overflow chains, indexes and the catalog alike. Applying the deltas of a page in LSN
+//! order to the page as it was before them redoes the changes. The delta of a new page zeroes
+//! it first, since the file may still hold a page that was freed under the same id.
+//!
+//! A delta is encoded as follows, little-endian:
+//!
+//! | offset | size | field                                  |
+//! |--------|------|----------------------------------------|
+//! | 0      | 4    | record kind: 1, or 2 for a new page    |
+//! | 4      | 8    | page id                                |
+//! | 12     | 4    | number of ranges                       |
+//! | 16     | n    | ranges                                 |
+//!
+//! Each range is its offset in the page and its length, 4 bytes each, and then its bytes.
+//!
+//! A [`Checkpoint`] is the record kind, 3, and the LSN that redo starts from, 8 bytes.
+
+use crate::{
+    storage::page::{bytes, page::Lsn},
+    PageId,
+};
+
+const KIND_PAGE_DELTA: u32 = 1;
+const KIND_NEW_PAGE_DELTA: u32 = 2;
+const KIND_CHECKPOINT: u32 = 3;
+const DELTA_HEADER_SIZE: usize = 16;
+const RANGE_HEADER_SIZE: usize = 8;
+const CHECKPOINT_SIZE: usize = 12;
+
+/// A record of the log, by its kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    PageDelta(PageDelta),
+    Checkpoint(Checkpoint),
+}
+
+impl Record {
+    pub fn decode(payload: &[u8]) -> anyhow::Result<Self> {
+        if payload.len() < 4 {
+            anyhow::bail!("log record is truncated");
+        }
+        match bytes::read_u32(payload, 0) {
+            KIND_PAGE_DELTA | KIND_NEW_PAGE_DELTA => {
+                Ok(Self::PageDelta(PageDelta::decode(payload)?))
+            }
+            KIND_CHECKPOINT => Ok(Self::Checkpoint(Checkpoint::decode(payload)?)),
+            kind => anyhow::bail!("unknown log record kind {kind}"),
+        }
+    }
+}
+
+/// The bytes of a page that a change wrote, by their offset in the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageDelta {
+    pub page_id: PageId,
+    /// Whether the page is new, and zeroed before the ranges are written.
+    pub new_page: bool,
+    pub ranges: Vec<(usize, Vec<u8>)>,
+}
+
+impl PageDelta {
+    /// The delta of a new page that was left zeroed.
+    pub fn new_page(page_id: PageId) -> Self {
+        Self {
+            page_id,
+            new_page: true,
+            ranges: Vec::new(),
+        }
+    }
+
+    /// The ranges where `after` differs from `before`, or `None` if they are equal. Ranges
+    /// closer than the header of a range are merged, which takes fewer bytes.
+    pub fn diff(page_id: PageId, before: &[u8], after: &[u8]) -> Option<Self> {
+        assert_eq!(before.len(), after.len());
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut offset = 0;
+        while offset < after.len() {
+            if before[offset] == after[offset] {
+                offset += 1;
+                continue;
+            }
+            let start = offset;
+            while offset < after.len() && before[offset] != after[offset] {
+                offset += 1;
+            }
+            match ranges.last_mut() {
+                Some((_, end)) if start - *end <= RANGE_HEADER_SIZE => *end = offset,
+                _ => ranges.push((start, offset)),
+            }
+        }
+        if ranges.is_empty() {
+            return None;
+        }
+        Some(Self {
+            page_id,
+            new_page: false,
+            ranges: ranges
+                .into_iter()
+                .map(|(start, end)| (start, after[start..end].to_vec()))
+                .collect(),
+        })
+    }
+
+    /// Write the ranges into `data`, the page the delta was taken of.
+    pub fn apply(&self, data: &mut [u8]) {
+        if self.new_page {
+            data.fill(0);
+        }
+        for (offset, range) in &self.ranges {
+            data[*offset..*offset + range.len()].copy_from_slice(range);
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let len = self
+            .ranges
+            .iter()
+            .map(|(_, range)| RANGE_HEADER_SIZE + range.len())
+            .sum::<usize>();
+        let mut payload = vec![0; DELTA_HEADER_SIZE + len];
+        let kind = if self.new_page {
+            KIND_NEW_PAGE_DELTA
+        } else {
+            KIND_PAGE_DELTA
+        };
+        bytes::write_u32(&mut payload, 0, kind);
+        bytes::write_u64(&mut payload, 4, self.page_id.to_u64());
+        bytes::write_u32(&mut payload, 12, self.ranges.len() as u32);
+        let mut offset = DELTA_HEADER_SIZE;
+        for (start, range) in &self.ranges {
+            bytes::write_u32(&mut payload, offset, *start as u32);
+            bytes::write_u32(&mut payload, offset + 4, range.len() as u32);
+            offset += RANGE_HEADER_SIZE;
+            payload[offset..offset + range.len()].copy_from_slice(range);
+            offset += range.len();
+        }
+        payload
+    }
+
+    pub fn decode(payload: &[u8]) -> anyhow::Result<Self> {
+        let kind = (payload.len() >= DELTA_HEADER_SIZE).then(|| bytes::read_u32(payload, 0));
+        if kind != Some(KIND_PAGE_DELTA) && kind != Some(KIND_NEW_PAGE_DELTA) {
+            anyhow::bail!("not a page delta record");
+        }
+        let page_id = PageId::from_u64(bytes::read_u64(payload, 4));
+        let count = bytes::read_u32(payload, 12) as usize;
+        let mut ranges = Vec::with_capacity(count);
+        let mut offset = DELTA_HEADER_SIZE;
+        for _ in 0..count {
+            if payload.len() < offset + RANGE_HEADER_SIZE {
+                anyhow::bail!("page delta record is truncated");
+            }
+            let start = bytes::read_u32(payload, offset) as usize;
+            let len = bytes::read_u32(payload, offset + 4) as usize;
+            offset += RANGE_HEADER_SIZE;
+            let Some(range) = payload.get(offset..offset + len) else {
+                anyhow::bail!("page delta record is truncated");
+            };
+            ranges.push((start, range.to_vec()));
+            offset += len;
+        }
+        Ok(Self {
+            page_id,
+            new_page: kind == Some(KIND_NEW_PAGE_DELTA),
+            ranges,
+        })
+    }
+}
+
+/// Marks that every change logged before `redo_lsn` is in the database file, so that redo
+/// can start there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub redo_lsn: Lsn,
+}
+
+impl Checkpoint {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = vec![0; CHECKPOINT_SIZE];
+        bytes::write_u32(&mut payload, 0, KIND_CHECKPOINT);
+        bytes::write_u64(&mut payload, 4, self.redo_lsn);
+        payload
+    }
+
+    pub fn decode(payload: &[u8]) -> anyhow::Result<Self> {
+        if payload.len() != CHECKPOINT_SIZE || bytes::read_u32(payload, 0) != KIND_CHECKPOINT {
+            anyhow::bail!("not a checkpoint record");
+        }
+        Ok(Self {
+            redo_lsn: bytes::read_u64(payload, 4),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_delta() {
+        let before = vec![0u8; 64];
+        assert_eq!(PageDelta::diff(PageId::new(3), &before, &before), None);
+
+        let mut after = before.clone();
+        after[2] = 1;
+        after[5..7].copy_from_slice(&[2, 3]);
+        after[40] = 4;
+        let delta = PageDelta::diff(PageId::new(3), &before, &after).unwrap();
+        // The first two changes are close enough to share a range.
+        assert_eq!(delta.ranges, [(2, vec![1, 0, 0, 2, 3]), (40, vec![4])]);
+
+        let decoded = PageDelta::decode(&delta.encode()).unwrap();
+        assert_eq!(decoded, delta);
+        let mut redone = before.clone();
+        decoded.apply(&mut redone);
+        assert_eq!(redone, after);
+        assert!(PageDelta::decode(&delta.encode()[..20]).is_err());
+        assert!(PageDelta::decode(b"page").is_err());
+
+        // A new page is zeroed before its ranges are written.
+        let mut new_page = PageDelta::new_page(PageId::new(3));
+        new_page.ranges.push((5, vec![2, 3]));
+        let decoded = PageDelta::decode(&new_page.encode()).unwrap();
+        assert_eq!(decoded, new_page);
+        decoded.apply(&mut redone);
+        let mut expected = before.clone();
+        expected[5..7].copy_from_slice(&[2, 3]);
+        assert_eq!(redone, expected);
+
+        let checkpoint = Checkpoint { redo_lsn: 42 };
+        assert_eq!(
+            Record::decode(&checkpoint.encode()).unwrap(),
+            Record::Checkpoint(checkpoint)
+        );
+        assert_eq!(
+            Record::decode(&delta.encode()).unwrap(),
+            Record::PageDelta(delta)
+        );
+        assert!(Record::decode(&[9, 0, 0, 0]).is_err());
+        assert!(Checkpoint::decode(&new_page.encode()).is_err());
+    }
+}
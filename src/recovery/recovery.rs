@@ -0,0 +1,191 @@
+//! Redo after a crash, and the checkpoints that bound how much of the log it replays.
+//!
+//! The log only holds redo records, so recovery replays the page deltas from the last
+//! checkpoint on, in LSN order, into the pages of the database file. A delta is skipped if the
+//! page on disk is already stamped with its LSN or a later one, which makes redo idempotent: a
+//! crash during recovery is recovered from by running it again.
+//!
+//! A change is committed once the log holds it: [`commit`] only flushes the log, and the pages
+//! reach the file later, when they are evicted or checkpointed.
+//!
+//! A checkpoint writes every dirty page, syncs the database file and then truncates the log
+//! before the first record that might not be in it, leaving a [`Checkpoint`] record to start
+//! redo from.
+
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use crate::{
+    buffer::buffer_pool_manager::{BufferPoolManager, BufferPoolManagerImpl},
+    storage::{
+        disk::DiskManager,
+        page::{bytes, page::PAGE_LSN_OFFSET},
+    },
+};
+
+use super::{
+    log_manager::{LogManager, INVALID_LSN},
+    log_record::{Checkpoint, Record},
+};
+
+/// Redo the changes in `log` that did not reach the pages of `disk_manager`, and return how
+/// many records were applied. Must run before a buffer pool reads the file.
+pub fn recover<D: DiskManager>(disk_manager: &D, log: &LogManager) -> anyhow::Result<usize> {
+    let records = log.records()?;
+    let mut redo_lsn = INVALID_LSN;
+    for record in &records {
+        if let Record::Checkpoint(checkpoint) = Record::decode(&record.payload)? {
+            redo_lsn = checkpoint.redo_lsn;
+        }
+    }
+
+    // The pages changed so far, written back once all the records are applied.
+    let mut pages: BTreeMap<_, Vec<u8>> = BTreeMap::new();
+    let mut redone = 0;
+    for record in records.iter().filter(|record| record.lsn >= redo_lsn) {
+        let Record::PageDelta(delta) = Record::decode(&record.payload)? else {
+            continue;
+        };
+        let page = match pages.entry(delta.page_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut data = vec![0; disk_manager.page_size()];
+                // A page past the end of the file was allocated but never written.
+                if delta.page_id.as_usize() < disk_manager.num_pages() {
+                    disk_manager.read_page(delta.page_id, &mut data)?;
+                }
+                entry.insert(data)
+            }
+        };
+        if bytes::read_u64(page, PAGE_LSN_OFFSET) >= record.lsn {
+            continue;
+        }
+        delta.apply(page);
+        bytes::write_u64(page, PAGE_LSN_OFFSET, record.lsn);
+        redone += 1;
+    }
+    if redone > 0 {
+        for (page_id, data) in &pages {
+            disk_manager.write_page(*page_id, data)?;
+        }
+        disk_manager.sync()?;
+    }
+
+    Ok(redone)
+}
+
+/// Make every change made through `bpm` so far durable, by flushing its log up to the last
+/// record. Without a log, the dirty pages are written instead.
+pub fn commit<B: BufferPoolManager + ?Sized>(bpm: &B) -> anyhow::Result<()> {
+    match bpm.log_manager() {
+        Some(log) => log.flush(log.last_lsn()),
+        None => bpm.flush_all_pages(),
+    }
+}
+
+/// Write every dirty page of `bpm` to disk and truncate its log, which then only has to be
+/// redone from here. Changes made meanwhile are kept in the log.
+pub fn checkpoint<D: DiskManager>(bpm: &BufferPoolManagerImpl<D>) -> anyhow::Result<()> {
+    let Some(log) = bpm.log_manager() else {
+        bpm.flush_all_pages()?;
+        return bpm.disk_manager().sync();
+    };
+    // Every change logged so far is in a page of the pool or of the file.
+    let redo_lsn = log.last_lsn() + 1;
+    bpm.flush_all_pages()?;
+    bpm.disk_manager().sync()?;
+    let lsn = log.append(&Checkpoint { redo_lsn }.encode())?;
+    log.flush(lsn)?;
+    log.truncate_before(redo_lsn)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::storage::{
+        disk::LimeBaseDiskManager, page::page::DEFAULT_PAGE_SIZE, table::table_heap::TableHeap,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_recover() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        let open = || {
+            let log = Arc::new(LogManager::open(LogManager::path_for(&path)).unwrap());
+            let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &path).unwrap();
+            (disk_manager, log)
+        };
+
+        let (disk_manager, log) = open();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager).with_log_manager(log.clone());
+        // Filled and checkpointed, so that the pages are on disk and the log is empty.
+        let page_ids: Vec<_> = (0..3)
+            .map(|_| {
+                let mut guard = bpm.new_page_write().unwrap();
+                guard.data_mut()[PAGE_LSN_OFFSET + 8..].fill(0xff);
+                guard.page_id()
+            })
+            .collect();
+        checkpoint(&bpm).unwrap();
+        let records = log.records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(matches!(
+            Record::decode(&records[0].payload).unwrap(),
+            Record::Checkpoint(_)
+        ));
+
+        // A heap that only reaches the log, and a page reused under the id of a freed one.
+        let heap = TableHeap::create(&bpm).unwrap();
+        let rids: Vec<_> = (0..100u8)
+            .map(|i| heap.insert_tuple(&[i; 200]).unwrap())
+            .collect();
+        assert!(bpm.delete_page(page_ids[1]));
+        let reused = {
+            let mut guard = bpm.new_page_write().unwrap();
+            guard.data_mut()[100] = 1;
+            guard.page_id()
+        };
+        assert_eq!(reused, page_ids[1]);
+        let header_page_id = heap.header_page_id();
+        drop(heap);
+        log.flush_all().unwrap();
+        // Crash: nothing is written back.
+        std::mem::forget(bpm);
+        drop(log);
+
+        let (disk_manager, log) = open();
+        assert!(recover(&disk_manager, &log).unwrap() > 0);
+        // Running it again redoes nothing.
+        assert_eq!(recover(&disk_manager, &log).unwrap(), 0);
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager).with_log_manager(log.clone());
+        let heap = TableHeap::open(&bpm, header_page_id).unwrap();
+        for (i, rid) in rids.into_iter().enumerate() {
+            assert_eq!(heap.get_tuple(rid).unwrap(), Some(vec![i as u8; 200]));
+        }
+        let guard = bpm.fetch_page_read(reused).unwrap();
+        let mut expected = vec![0; DEFAULT_PAGE_SIZE];
+        expected[100] = 1;
+        assert_eq!(
+            guard.data()[PAGE_LSN_OFFSET + 8..],
+            expected[PAGE_LSN_OFFSET + 8..]
+        );
+        drop(guard);
+        assert_eq!(
+            bpm.fetch_page_read(page_ids[0]).unwrap().data()[DEFAULT_PAGE_SIZE - 1],
+            0xff
+        );
+
+        // A checkpoint leaves nothing to redo.
+        checkpoint(&bpm).unwrap();
+        assert_eq!(log.records().unwrap().len(), 1);
+        let last_lsn = log.last_lsn();
+        drop(heap);
+        drop(bpm);
+        drop(log);
+        let (disk_manager, log) = open();
+        assert_eq!(recover(&disk_manager, &log).unwrap(), 0);
+        assert_eq!(log.last_lsn(), last_lsn);
+    }
+}
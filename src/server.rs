@@ -0,0 +1,6 @@
+pub mod protocol;
+#[allow(clippy::module_inception)]
+pub mod server;
+pub mod session;
+pub mod sqlstate;
+pub mod types;
@@ -0,0 +1,625 @@
+//! Messages of the PostgreSQL frontend/backend protocol, version 3.0.
+//!
+//! After the startup packet, every message is a type byte followed by a big-endian `i32`
+//! length, which counts itself but not the type byte, and the body. Strings are
+//! NUL-terminated. Only the messages of startup, the simple query protocol and the extended
+//! query protocol are understood; COPY, function calls and authentication exchanges are not.
+
+use std::io::{self, Read, Write};
+
+/// Version 3.0, as sent in a startup packet.
+pub const PROTOCOL_VERSION: i32 = 3 << 16;
+/// The version code of a startup packet asking for TLS.
+pub const SSL_REQUEST_CODE: i32 = 80877103;
+/// The version code of a startup packet asking for GSSAPI encryption.
+pub const GSSENC_REQUEST_CODE: i32 = 80877104;
+/// The version code of a startup packet asking to cancel a query of another session.
+pub const CANCEL_REQUEST_CODE: i32 = 80877102;
+/// The longest message accepted, to refuse garbage before allocating for it.
+pub const MAX_MESSAGE_LEN: usize = 64 << 20;
+
+/// What a client sends first, before it has a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupMessage {
+    Startup {
+        version: i32,
+        /// Such as `user`, `database` and `application_name`.
+        parameters: Vec<(String, String)>,
+    },
+    SslRequest,
+    GssEncRequest,
+    CancelRequest {
+        process_id: i32,
+        secret_key: i32,
+    },
+}
+
+/// Whether values are sent as text or in the binary format of their type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+impl Format {
+    pub fn from_code(code: i16) -> io::Result<Self> {
+        match code {
+            0 => Ok(Format::Text),
+            1 => Ok(Format::Binary),
+            _ => Err(invalid(format!("unknown format code {code}"))),
+        }
+    }
+
+    pub fn code(self) -> i16 {
+        match self {
+            Format::Text => 0,
+            Format::Binary => 1,
+        }
+    }
+
+    /// The formats of `count` values from the format codes of a Bind message: none means all
+    /// text, a single one applies to all, and otherwise there is one per value.
+    pub fn expand(formats: &[Format], count: usize) -> Result<Vec<Format>, String> {
+        match formats {
+            [] => Ok(vec![Format::Text; count]),
+            [format] => Ok(vec![*format; count]),
+            formats if formats.len() == count => Ok(formats.to_vec()),
+            formats => Err(format!(
+                "{} format codes were given for {count} values",
+                formats.len()
+            )),
+        }
+    }
+}
+
+/// Whether a Describe or Close message is about a prepared statement or a portal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Statement,
+    Portal,
+}
+
+/// A message from the client in a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrontendMessage {
+    /// Run every statement of the string with the simple query protocol.
+    Query(String),
+    Parse {
+        name: String,
+        sql: String,
+        /// The OIDs of the types of the first parameters, 0 for those left unspecified.
+        parameter_types: Vec<u32>,
+    },
+    Bind {
+        portal: String,
+        statement: String,
+        parameter_formats: Vec<Format>,
+        /// None is NULL.
+        parameters: Vec<Option<Vec<u8>>>,
+        result_formats: Vec<Format>,
+    },
+    Describe {
+        target: Target,
+        name: String,
+    },
+    Execute {
+        portal: String,
+        /// The most rows to return, 0 for all of them.
+        max_rows: u32,
+    },
+    Close {
+        target: Target,
+        name: String,
+    },
+    Sync,
+    Flush,
+    Terminate,
+}
+
+/// Read the startup packet, or None if the client closed the connection before sending one.
+pub fn read_startup(reader: &mut impl Read) -> io::Result<Option<StartupMessage>> {
+    let Some(len) = read_len(reader)? else {
+        return Ok(None);
+    };
+    let mut body = Body::read(reader, len)?;
+    let version = body.i32()?;
+    let message = match version {
+        SSL_REQUEST_CODE => StartupMessage::SslRequest,
+        GSSENC_REQUEST_CODE => StartupMessage::GssEncRequest,
+        CANCEL_REQUEST_CODE => StartupMessage::CancelRequest {
+            process_id: body.i32()?,
+            secret_key: body.i32()?,
+        },
+        version => {
+            let mut parameters = Vec::new();
+            loop {
+                let name = body.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                parameters.push((name, body.string()?));
+            }
+            StartupMessage::Startup {
+                version,
+                parameters,
+            }
+        }
+    };
+    Ok(Some(message))
+}
+
+/// Read a message of a session, or None if the client closed the connection between
+/// messages.
+pub fn read_message(reader: &mut impl Read) -> io::Result<Option<FrontendMessage>> {
+    let mut tag = [0; 1];
+    if reader.read(&mut tag)? == 0 {
+        return Ok(None);
+    }
+    let len = read_len(reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    let mut body = Body::read(reader, len)?;
+    let message = match tag[0] {
+        b'Q' => FrontendMessage::Query(body.string()?),
+        b'P' => {
+            let name = body.string()?;
+            let sql = body.string()?;
+            let count = body.count()?;
+            let parameter_types = (0..count)
+                .map(|_| body.i32().map(|oid| oid as u32))
+                .collect::<io::Result<_>>()?;
+            FrontendMessage::Parse {
+                name,
+                sql,
+                parameter_types,
+            }
+        }
+        b'B' => {
+            let portal = body.string()?;
+            let statement = body.string()?;
+            let parameter_formats = body.formats()?;
+            let count = body.count()?;
+            let parameters = (0..count)
+                .map(|_| body.value())
+                .collect::<io::Result<_>>()?;
+            let result_formats = body.formats()?;
+            FrontendMessage::Bind {
+                portal,
+                statement,
+                parameter_formats,
+                parameters,
+                result_formats,
+            }
+        }
+        b'D' => FrontendMessage::Describe {
+            target: body.target()?,
+            name: body.string()?,
+        },
+        b'E' => FrontendMessage::Execute {
+            portal: body.string()?,
+            max_rows: body.i32()?.max(0) as u32,
+        },
+        b'C' => FrontendMessage::Close {
+            target: body.target()?,
+            name: body.string()?,
+        },
+        b'S' => FrontendMessage::Sync,
+        b'H' => FrontendMessage::Flush,
+        b'X' => FrontendMessage::Terminate,
+        tag => {
+            return Err(invalid(format!(
+                "unsupported message type {:?}",
+                tag as char
+            )))
+        }
+    };
+    if !body.is_empty() {
+        return Err(invalid(format!(
+            "message {:?} is longer than its fields",
+            tag[0] as char
+        )));
+    }
+    Ok(Some(message))
+}
+
+/// The status of the transaction a session is in, reported when it is ready for a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+    Idle,
+    InTransaction,
+    Failed,
+}
+
+/// A column of a RowDescription message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescription {
+    pub name: String,
+    /// The table the column comes from, 0 if it is not a column of a table.
+    pub table_oid: u32,
+    /// The number of the column in that table, 0 if it is not one.
+    pub column_number: i16,
+    pub type_oid: u32,
+    /// The size of the type, negative for variable-length types.
+    pub type_size: i16,
+    pub type_modifier: i32,
+    pub format: Format,
+}
+
+/// The fields of an ErrorResponse or NoticeResponse message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorFields {
+    /// `ERROR`, `FATAL` or `PANIC`.
+    pub severity: &'static str,
+    /// The SQLSTATE code.
+    pub code: &'static str,
+    pub message: String,
+    /// The position of the error in the query, in characters counted from 1.
+    pub position: Option<usize>,
+}
+
+/// A message to the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendMessage<'a> {
+    AuthenticationOk,
+    ParameterStatus {
+        name: &'a str,
+        value: &'a str,
+    },
+    BackendKeyData {
+        process_id: i32,
+        secret_key: i32,
+    },
+    ReadyForQuery(TransactionStatus),
+    RowDescription(&'a [FieldDescription]),
+    /// None is NULL.
+    DataRow(&'a [Option<Vec<u8>>]),
+    CommandComplete(&'a str),
+    EmptyQueryResponse,
+    ErrorResponse(&'a ErrorFields),
+    ParseComplete,
+    BindComplete,
+    CloseComplete,
+    ParameterDescription(&'a [u32]),
+    NoData,
+    PortalSuspended,
+}
+
+/// Write `message`, which may stay in the buffers of `writer` until it is flushed.
+pub fn write_message(writer: &mut impl Write, message: &BackendMessage<'_>) -> io::Result<()> {
+    let mut body = Vec::new();
+    let tag = match message {
+        BackendMessage::AuthenticationOk => {
+            body.extend_from_slice(&0i32.to_be_bytes());
+            b'R'
+        }
+        BackendMessage::ParameterStatus { name, value } => {
+            put_string(&mut body, name);
+            put_string(&mut body, value);
+            b'S'
+        }
+        BackendMessage::BackendKeyData {
+            process_id,
+            secret_key,
+        } => {
+            body.extend_from_slice(&process_id.to_be_bytes());
+            body.extend_from_slice(&secret_key.to_be_bytes());
+            b'K'
+        }
+        BackendMessage::ReadyForQuery(status) => {
+            body.push(match status {
+                TransactionStatus::Idle => b'I',
+                TransactionStatus::InTransaction => b'T',
+                TransactionStatus::Failed => b'E',
+            });
+            b'Z'
+        }
+        BackendMessage::RowDescription(fields) => {
+            body.extend_from_slice(&(fields.len() as i16).to_be_bytes());
+            for field in *fields {
+                put_string(&mut body, &field.name);
+                body.extend_from_slice(&field.table_oid.to_be_bytes());
+                body.extend_from_slice(&field.column_number.to_be_bytes());
+                body.extend_from_slice(&field.type_oid.to_be_bytes());
+                body.extend_from_slice(&field.type_size.to_be_bytes());
+                body.extend_from_slice(&field.type_modifier.to_be_bytes());
+                body.extend_from_slice(&field.format.code().to_be_bytes());
+            }
+            b'T'
+        }
+        BackendMessage::DataRow(values) => {
+            body.extend_from_slice(&(values.len() as i16).to_be_bytes());
+            for value in *values {
+                match value {
+                    Some(value) => {
+                        body.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                        body.extend_from_slice(value);
+                    }
+                    None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+            b'D'
+        }
+        BackendMessage::CommandComplete(tag) => {
+            put_string(&mut body, tag);
+            b'C'
+        }
+        BackendMessage::EmptyQueryResponse => b'I',
+        BackendMessage::ErrorResponse(fields) => {
+            let position = fields.position.map(|position| position.to_string());
+            let mut put_field = |code: u8, value: &str| {
+                body.push(code);
+                put_string(&mut body, value);
+            };
+            put_field(b'S', fields.severity);
+            put_field(b'V', fields.severity);
+            put_field(b'C', fields.code);
+            put_field(b'M', &fields.message);
+            if let Some(position) = &position {
+                put_field(b'P', position);
+            }
+            body.push(0);
+            b'E'
+        }
+        BackendMessage::ParseComplete => b'1',
+        BackendMessage::BindComplete => b'2',
+        BackendMessage::CloseComplete => b'3',
+        BackendMessage::ParameterDescription(types) => {
+            body.extend_from_slice(&(types.len() as i16).to_be_bytes());
+            for oid in *types {
+                body.extend_from_slice(&oid.to_be_bytes());
+            }
+            b't'
+        }
+        BackendMessage::NoData => b'n',
+        BackendMessage::PortalSuspended => b's',
+    };
+    writer.write_all(&[tag])?;
+    writer.write_all(&(body.len() as i32 + 4).to_be_bytes())?;
+    writer.write_all(&body)
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read the length of a message, which counts itself, and return the length of the body.
+fn read_len(reader: &mut impl Read) -> io::Result<Option<usize>> {
+    let mut buf = [0; 4];
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => filled += n,
+        }
+    }
+    let len = i32::from_be_bytes(buf);
+    if len < 4 || len as usize - 4 > MAX_MESSAGE_LEN {
+        return Err(invalid(format!("invalid message length {len}")));
+    }
+    Ok(Some(len as usize - 4))
+}
+
+/// The body of a message, read field by field.
+struct Body {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Body {
+    fn read(reader: &mut impl Read, len: usize) -> io::Result<Self> {
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf)?;
+        Ok(Self { buf, pos: 0 })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.buf.len() - self.pos < n {
+            return Err(invalid("message is shorter than its fields".to_string()));
+        }
+        self.pos += n;
+        Ok(&self.buf[self.pos - n..self.pos])
+    }
+
+    fn i16(&mut self) -> io::Result<i16> {
+        Ok(i16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// The number of the items that follow.
+    fn count(&mut self) -> io::Result<usize> {
+        let count = self.i16()?;
+        usize::try_from(count).map_err(|_| invalid(format!("negative count {count}")))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let rest = &self.buf[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("string is not terminated".to_string()))?;
+        let s = String::from_utf8(rest[..len].to_vec())
+            .map_err(|_| invalid("string is not valid UTF-8".to_string()))?;
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn formats(&mut self) -> io::Result<Vec<Format>> {
+        let count = self.count()?;
+        (0..count).map(|_| Format::from_code(self.i16()?)).collect()
+    }
+
+    fn value(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let len = self.i32()?;
+        match usize::try_from(len) {
+            Ok(len) => Ok(Some(self.bytes(len)?.to_vec())),
+            Err(_) if len == -1 => Ok(None),
+            Err(_) => Err(invalid(format!("invalid value length {len}"))),
+        }
+    }
+
+    fn target(&mut self) -> io::Result<Target> {
+        match self.bytes(1)?[0] {
+            b'S' => Ok(Target::Statement),
+            b'P' => Ok(Target::Portal),
+            target => Err(invalid(format!(
+                "expected 'S' or 'P', got {:?}",
+                target as char
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A message as a client would send it.
+    fn frontend(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    #[test]
+    fn test_read_startup() {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        packet.extend_from_slice(b"user\0alice\0database\0app\0\0");
+        let mut input = (packet.len() as i32 + 4).to_be_bytes().to_vec();
+        input.extend_from_slice(&packet);
+        input.extend_from_slice(&8i32.to_be_bytes());
+        input.extend_from_slice(&SSL_REQUEST_CODE.to_be_bytes());
+
+        let mut reader = input.as_slice();
+        assert_eq!(
+            read_startup(&mut reader).unwrap(),
+            Some(StartupMessage::Startup {
+                version: PROTOCOL_VERSION,
+                parameters: vec![
+                    ("user".to_string(), "alice".to_string()),
+                    ("database".to_string(), "app".to_string())
+                ],
+            })
+        );
+        assert_eq!(
+            read_startup(&mut reader).unwrap(),
+            Some(StartupMessage::SslRequest)
+        );
+        assert_eq!(read_startup(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut input = frontend(b'Q', b"SELECT 1\0");
+        input.extend(frontend(b'P', b"s1\0SELECT $1\0\0\x01\0\0\0\x17"));
+        input.extend(frontend(
+            b'B',
+            b"\0s1\0\0\x01\0\x01\0\x02\0\0\0\x04\0\0\0\x07\xff\xff\xff\xff\0\0",
+        ));
+        input.extend(frontend(b'D', b"P\0"));
+        input.extend(frontend(b'E', b"\0\0\0\0\x0a"));
+        input.extend(frontend(b'C', b"Ss1\0"));
+        input.extend(frontend(b'S', b""));
+
+        let mut reader = input.as_slice();
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            messages.push(message);
+        }
+        assert_eq!(
+            messages,
+            vec![
+                FrontendMessage::Query("SELECT 1".to_string()),
+                FrontendMessage::Parse {
+                    name: "s1".to_string(),
+                    sql: "SELECT $1".to_string(),
+                    parameter_types: vec![23],
+                },
+                FrontendMessage::Bind {
+                    portal: String::new(),
+                    statement: "s1".to_string(),
+                    parameter_formats: vec![Format::Binary],
+                    parameters: vec![Some(vec![0, 0, 0, 7]), None],
+                    result_formats: vec![],
+                },
+                FrontendMessage::Describe {
+                    target: Target::Portal,
+                    name: String::new(),
+                },
+                FrontendMessage::Execute {
+                    portal: String::new(),
+                    max_rows: 10,
+                },
+                FrontendMessage::Close {
+                    target: Target::Statement,
+                    name: "s1".to_string(),
+                },
+                FrontendMessage::Sync,
+            ]
+        );
+
+        for bad in [
+            frontend(b'Q', b"SELECT 1"),
+            frontend(b'Q', b"SELECT 1\0\0"),
+            frontend(b'E', b"\0\0"),
+            frontend(b'?', b""),
+            vec![b'Q', 0, 0, 0, 1],
+        ] {
+            assert_eq!(
+                read_message(&mut bad.as_slice()).unwrap_err().kind(),
+                io::ErrorKind::InvalidData
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut out = Vec::new();
+        write_message(
+            &mut out,
+            &BackendMessage::ReadyForQuery(TransactionStatus::Idle),
+        )
+        .unwrap();
+        write_message(
+            &mut out,
+            &BackendMessage::DataRow(&[Some(b"1".to_vec()), None]),
+        )
+        .unwrap();
+        write_message(
+            &mut out,
+            &BackendMessage::ErrorResponse(&ErrorFields {
+                severity: "ERROR",
+                code: "42601",
+                message: "oops".to_string(),
+                position: Some(8),
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            [
+                b"Z\0\0\0\x05I".as_slice(),
+                b"D\0\0\0\x0f\0\x02\0\0\0\x011\xff\xff\xff\xff",
+                b"E\0\0\0\x23SERROR\0VERROR\0C42601\0Moops\0P8\0\0",
+            ]
+            .concat()
+        );
+        assert_eq!(
+            Format::expand(&[Format::Binary], 2).unwrap(),
+            [Format::Binary, Format::Binary]
+        );
+        assert!(Format::expand(&[Format::Text, Format::Text], 3).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! A TCP server speaking the PostgreSQL protocol, with a thread per client.
+//!
+//! ```no_run
+//! use limebase::{server::server::Server, Database, Options};
+//!
+//! let db = Database::open("app.db", Options::new())?;
+//! let server = Server::bind(&db, "127.0.0.1:5432")?;
+//! server.serve()?;
+//! # anyhow::Ok(())
+//! ```
+
+use std::{
+    io::{self, BufReader, BufWriter},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::atomic::{AtomicI32, Ordering},
+    thread,
+};
+
+use crate::database::database::Database;
+
+use super::session::Session;
+
+/// Serves clients from a listening socket, every session sharing the buffer pool and the
+/// catalog of one [`Database`].
+pub struct Server<'db> {
+    db: &'db Database,
+    listener: TcpListener,
+    /// The process id of the next session, which clients only use to cancel queries.
+    next_process_id: AtomicI32,
+}
+
+impl<'db> Server<'db> {
+    pub fn bind(db: &'db Database, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            db,
+            listener: TcpListener::bind(addr)?,
+            next_process_id: AtomicI32::new(1),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept clients and serve each on a thread of its own, forever. Errors of accepting
+    /// a client and of sessions are reported on standard error and do not stop the server.
+    pub fn serve(&self) -> io::Result<()> {
+        thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(error) => {
+                        eprintln!("limebase-server: cannot accept a client: {error}");
+                        continue;
+                    }
+                };
+                let process_id = self.next_process_id.fetch_add(1, Ordering::Relaxed);
+                let db = self.db;
+                scope.spawn(move || {
+                    let peer = stream.peer_addr().ok();
+                    if let Err(error) = serve_client(db, stream, process_id) {
+                        if !is_disconnect(&error) {
+                            let peer = peer.map_or("unknown".to_string(), |p| p.to_string());
+                            eprintln!("limebase-server: session with {peer} failed: {error}");
+                        }
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+}
+
+fn serve_client(db: &Database, stream: TcpStream, process_id: i32) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let writer = BufWriter::new(stream);
+    Session::new(db.connect(), reader, writer)
+        .with_process_id(process_id)
+        .run()
+}
+
+/// Whether the client went away without terminating its session, which is not worth
+/// reporting.
+fn is_disconnect(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::{database::database::Options, server::protocol::PROTOCOL_VERSION};
+
+    use super::*;
+
+    /// Send `sql` as a simple query and return the command tags of the answer.
+    fn query(stream: &mut TcpStream, sql: &str) -> Vec<String> {
+        let mut message = vec![b'Q'];
+        message.extend_from_slice(&(sql.len() as i32 + 5).to_be_bytes());
+        message.extend_from_slice(sql.as_bytes());
+        message.push(0);
+        stream.write_all(&message).unwrap();
+        read_until_ready(stream)
+    }
+
+    fn read_until_ready(stream: &mut TcpStream) -> Vec<String> {
+        let mut tags = Vec::new();
+        loop {
+            let mut header = [0; 5];
+            stream.read_exact(&mut header).unwrap();
+            let len = i32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+            let mut body = vec![0; len - 4];
+            stream.read_exact(&mut body).unwrap();
+            match header[0] {
+                b'Z' => return tags,
+                b'C' => tags.push(String::from_utf8(body[..body.len() - 1].to_vec()).unwrap()),
+                b'E' => panic!("error: {}", String::from_utf8_lossy(&body)),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_sessions() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = Options::new().with_pool_size(64).with_temp_pool_size(8);
+        let db = Database::open(tempdir.path().join("test.db"), options).unwrap();
+        db.connect()
+            .execute_batch("CREATE TABLE t (session INT NOT NULL, n INT NOT NULL)")
+            .unwrap();
+        // The server runs until the process exits, so it needs the database for as long.
+        let db: &'static Database = Box::leak(Box::new(db));
+        let server = Server::bind(db, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+
+        const SESSIONS: i32 = 4;
+        const ROWS: i32 = 50;
+        thread::scope(|scope| {
+            for session in 0..SESSIONS {
+                scope.spawn(move || {
+                    let mut stream = TcpStream::connect(addr).unwrap();
+                    let mut startup = 16i32.to_be_bytes().to_vec();
+                    startup.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+                    startup.extend_from_slice(b"user\0u\0\0");
+                    stream.write_all(&startup).unwrap();
+                    read_until_ready(&mut stream);
+                    for n in 0..ROWS {
+                        let sql = format!("INSERT INTO t VALUES ({session}, {n})");
+                        assert_eq!(query(&mut stream, &sql), ["INSERT 0 1"]);
+                    }
+                    let sql = format!("SELECT n FROM t WHERE session = {session}");
+                    assert_eq!(query(&mut stream, &sql), [format!("SELECT {ROWS}")]);
+                    stream.write_all(b"X\0\0\0\x04").unwrap();
+                });
+            }
+        });
+        let count = db
+            .connect()
+            .query_as::<(i64,)>("SELECT count(*) FROM t", ())
+            .unwrap();
+        assert_eq!(count, [((SESSIONS * ROWS) as i64,)]);
+    }
+}
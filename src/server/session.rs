@@ -0,0 +1,1018 @@
+//! A client session, from its startup packet to its Terminate message.
+//!
+//! Every statement commits on its own, so the session is always idle between queries and
+//! portals live until the next Sync. Authentication always succeeds and cancel requests are
+//! ignored: the server only listens on the local host.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    rc::Rc,
+};
+
+use crate::{
+    database::{connection::Connection, row::Rows},
+    sql::{
+        ast,
+        error::SqlError,
+        parser::{parse, parse_with_parameters},
+    },
+    types::{data_type::DataType, value::Value},
+};
+
+use super::{
+    protocol::{
+        read_message, read_startup, write_message, BackendMessage, ErrorFields, FieldDescription,
+        Format, FrontendMessage, StartupMessage, Target, TransactionStatus,
+    },
+    sqlstate::{self, classify},
+    types::{decode, encode, oid, type_modifier, type_oid, type_size},
+};
+
+/// The PostgreSQL version reported to clients, which decide what they may send by it.
+pub const SERVER_VERSION: &str = "14.0";
+
+pub struct Session<'db, R, W> {
+    connection: Connection<'db>,
+    reader: R,
+    writer: W,
+    process_id: i32,
+    secret_key: i32,
+    statements: HashMap<String, Rc<PreparedStatement>>,
+    portals: HashMap<String, Portal<'db>>,
+    /// Set by an error in the extended query protocol, after which messages are skipped until
+    /// the next Sync.
+    skipping: bool,
+}
+
+struct PreparedStatement {
+    sql: String,
+    /// None for an empty query.
+    statement: Option<ast::Statement>,
+    /// The OIDs of the types of all the parameters, 0 for those left unspecified.
+    parameter_types: Vec<u32>,
+}
+
+/// A prepared statement bound to the values of its parameters, run by Execute messages.
+struct Portal<'db> {
+    statement: Rc<PreparedStatement>,
+    parameters: Vec<Value>,
+    result_formats: Vec<Format>,
+    state: PortalState<'db>,
+}
+
+enum PortalState<'db> {
+    Ready,
+    /// A query whose rows have been sent up to the row limit of an Execute message.
+    Running {
+        rows: Rows<'db>,
+        formats: Vec<Format>,
+    },
+    Done,
+}
+
+/// An error reported to the client, after which the session goes on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StatementError {
+    code: &'static str,
+    message: String,
+    /// The position in the query, in characters counted from 1.
+    position: Option<usize>,
+}
+
+impl StatementError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    /// An error of the parser in `sql`.
+    fn syntax(error: SqlError, sql: &str) -> Self {
+        Self {
+            code: sqlstate::SYNTAX_ERROR,
+            position: Some(position(&error, sql)),
+            message: error.message,
+        }
+    }
+
+    /// An error of binding or running a statement of `sql`.
+    fn from_error(error: anyhow::Error, sql: &str) -> Self {
+        Self {
+            code: classify(&error),
+            position: error
+                .downcast_ref::<SqlError>()
+                .map(|error| position(error, sql)),
+            message: format!("{error:#}"),
+        }
+    }
+}
+
+fn position(error: &SqlError, sql: &str) -> usize {
+    sql.get(..error.span.start)
+        .map_or(0, |before| before.chars().count())
+        + 1
+}
+
+/// Why a message was not handled.
+#[derive(Debug)]
+enum SessionError {
+    Statement(StatementError),
+    /// Writing to the client failed, which ends the session.
+    Disconnected(io::Error),
+}
+
+impl From<StatementError> for SessionError {
+    fn from(error: StatementError) -> Self {
+        SessionError::Statement(error)
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> Self {
+        SessionError::Disconnected(error)
+    }
+}
+
+impl fmt::Display for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+type SessionResult<T> = Result<T, SessionError>;
+
+impl<'db, R: Read, W: Write> Session<'db, R, W> {
+    pub fn new(connection: Connection<'db>, reader: R, writer: W) -> Self {
+        Self {
+            connection,
+            reader,
+            writer,
+            process_id: 0,
+            secret_key: RandomState::new().build_hasher().finish() as i32,
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+            skipping: false,
+        }
+    }
+
+    /// The process id reported in BackendKeyData.
+    pub fn with_process_id(self, process_id: i32) -> Self {
+        Self { process_id, ..self }
+    }
+
+    /// Serve the client until it terminates the session or disconnects. A malformed message
+    /// is reported to the client and ends the session with an error of kind `InvalidData`.
+    pub fn run(mut self) -> io::Result<()> {
+        if !self.startup()? {
+            return Ok(());
+        }
+        loop {
+            let message = match read_message(&mut self.reader) {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok(()),
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                    self.fatal(sqlstate::PROTOCOL_VIOLATION, &error.to_string())?;
+                    return Err(error);
+                }
+                Err(error) => return Err(error),
+            };
+            let result = match message {
+                FrontendMessage::Terminate => return self.writer.flush(),
+                FrontendMessage::Query(sql) => {
+                    self.simple_query(&sql).and_then(|()| Ok(self.ready()?))
+                }
+                FrontendMessage::Sync => {
+                    self.skipping = false;
+                    self.portals.clear();
+                    self.ready().map_err(SessionError::from)
+                }
+                FrontendMessage::Flush => self.writer.flush().map_err(SessionError::from),
+                _ if self.skipping => Ok(()),
+                message => match self.extended_query(message) {
+                    Err(SessionError::Statement(error)) => {
+                        self.skipping = true;
+                        self.send_error(&error)
+                    }
+                    result => result,
+                },
+            };
+            match result {
+                Ok(()) => {}
+                Err(SessionError::Disconnected(error)) => return Err(error),
+                Err(SessionError::Statement(error)) => unreachable!("unreported error {error}"),
+            }
+        }
+    }
+
+    /// Answer the startup packet, returning whether the session started.
+    fn startup(&mut self) -> io::Result<bool> {
+        let parameters = loop {
+            match read_startup(&mut self.reader)? {
+                None | Some(StartupMessage::CancelRequest { .. }) => return Ok(false),
+                Some(StartupMessage::SslRequest | StartupMessage::GssEncRequest) => {
+                    // Neither is supported, so the client goes on unencrypted or gives up.
+                    self.writer.write_all(b"N")?;
+                    self.writer.flush()?;
+                }
+                Some(StartupMessage::Startup {
+                    version,
+                    parameters,
+                }) => {
+                    if version >> 16 != 3 {
+                        let message = format!(
+                            "unsupported frontend protocol {}.{}: server supports 3.0",
+                            version >> 16,
+                            version & 0xffff
+                        );
+                        self.fatal(sqlstate::FEATURE_NOT_SUPPORTED, &message)?;
+                        return Ok(false);
+                    }
+                    break parameters;
+                }
+            }
+        };
+
+        let parameter = |name: &str| {
+            parameters
+                .iter()
+                .find(|(n, _)| n == name)
+                .map_or("", |(_, value)| value.as_str())
+        };
+        let server_version = format!("{SERVER_VERSION} (limebase {})", env!("CARGO_PKG_VERSION"));
+        let statuses = [
+            ("application_name", parameter("application_name")),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, MDY"),
+            ("integer_datetimes", "on"),
+            ("IntervalStyle", "postgres"),
+            ("is_superuser", "on"),
+            ("server_encoding", "UTF8"),
+            ("server_version", &server_version),
+            ("session_authorization", parameter("user")),
+            ("standard_conforming_strings", "on"),
+            ("TimeZone", "UTC"),
+        ];
+        write_message(&mut self.writer, &BackendMessage::AuthenticationOk)?;
+        for (name, value) in statuses {
+            write_message(
+                &mut self.writer,
+                &BackendMessage::ParameterStatus { name, value },
+            )?;
+        }
+        write_message(
+            &mut self.writer,
+            &BackendMessage::BackendKeyData {
+                process_id: self.process_id,
+                secret_key: self.secret_key,
+            },
+        )?;
+        self.ready()?;
+        Ok(true)
+    }
+
+    /// Run every statement of `sql` until one fails, reporting the failure.
+    fn simple_query(&mut self, sql: &str) -> SessionResult<()> {
+        // As in PostgreSQL, a simple query drops the unnamed statement and portal.
+        self.statements.remove("");
+        self.portals.remove("");
+        let statements = match parse(sql) {
+            Ok(statements) => statements,
+            Err(error) => return self.send_error(&StatementError::syntax(error, sql)),
+        };
+        if statements.is_empty() {
+            return self.send(&BackendMessage::EmptyQueryResponse);
+        }
+        for statement in &statements {
+            match self.run_statement(statement, sql) {
+                Err(SessionError::Statement(error)) => return self.send_error(&error),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `statement` of `sql` without parameters and send its rows as text.
+    fn run_statement(&mut self, statement: &ast::Statement, sql: &str) -> SessionResult<()> {
+        let rows = self
+            .connection
+            .run(statement, Vec::new())
+            .map_err(|error| StatementError::from_error(error, sql))?;
+        if !returns_rows(statement) {
+            let count = rows
+                .finish()
+                .map_err(|error| StatementError::from_error(error, sql))?;
+            return self.send(&BackendMessage::CommandComplete(&command_tag(
+                statement, count,
+            )));
+        }
+        let formats = vec![Format::Text; rows.columns().len()];
+        let fields = field_descriptions(rows.columns(), rows.column_types(), &formats);
+        self.send(&BackendMessage::RowDescription(&fields))?;
+        let mut rows = rows;
+        let (count, _) = self.send_rows(&mut rows, &formats, 0, sql)?;
+        self.send(&BackendMessage::CommandComplete(&command_tag(
+            statement, count,
+        )))
+    }
+
+    fn extended_query(&mut self, message: FrontendMessage) -> SessionResult<()> {
+        match message {
+            FrontendMessage::Parse {
+                name,
+                sql,
+                parameter_types,
+            } => self.parse(name, sql, parameter_types),
+            FrontendMessage::Bind {
+                portal,
+                statement,
+                parameter_formats,
+                parameters,
+                result_formats,
+            } => self.bind(
+                portal,
+                &statement,
+                &parameter_formats,
+                parameters,
+                result_formats,
+            ),
+            FrontendMessage::Describe {
+                target: Target::Statement,
+                name,
+            } => self.describe_statement(&name),
+            FrontendMessage::Describe {
+                target: Target::Portal,
+                name,
+            } => self.describe_portal(&name),
+            FrontendMessage::Execute { portal, max_rows } => self.execute(portal, max_rows),
+            FrontendMessage::Close { target, name } => {
+                match target {
+                    Target::Statement => self.statements.remove(&name).map(drop),
+                    Target::Portal => self.portals.remove(&name).map(drop),
+                };
+                self.send(&BackendMessage::CloseComplete)
+            }
+            FrontendMessage::Query(_)
+            | FrontendMessage::Sync
+            | FrontendMessage::Flush
+            | FrontendMessage::Terminate => unreachable!("handled by run"),
+        }
+    }
+
+    fn parse(&mut self, name: String, sql: String, parameter_types: Vec<u32>) -> SessionResult<()> {
+        if !name.is_empty() && self.statements.contains_key(&name) {
+            return Err(StatementError::new(
+                sqlstate::DUPLICATE_PSTATEMENT,
+                format!("prepared statement \"{name}\" already exists"),
+            )
+            .into());
+        }
+        let (mut statements, names) =
+            parse_with_parameters(&sql).map_err(|error| StatementError::syntax(error, &sql))?;
+        if statements.len() > 1 {
+            return Err(StatementError::new(
+                sqlstate::SYNTAX_ERROR,
+                "cannot insert multiple commands into a prepared statement",
+            )
+            .into());
+        }
+        let mut parameter_types = parameter_types;
+        if parameter_types.len() < names.len() {
+            parameter_types.resize(names.len(), oid::UNSPECIFIED);
+        }
+        let statement = PreparedStatement {
+            statement: statements.pop(),
+            sql,
+            parameter_types,
+        };
+        self.statements.insert(name, Rc::new(statement));
+        self.send(&BackendMessage::ParseComplete)
+    }
+
+    fn bind(
+        &mut self,
+        portal: String,
+        statement: &str,
+        parameter_formats: &[Format],
+        parameters: Vec<Option<Vec<u8>>>,
+        result_formats: Vec<Format>,
+    ) -> SessionResult<()> {
+        let statement = self.statement(statement)?;
+        if !portal.is_empty() && self.portals.contains_key(&portal) {
+            return Err(StatementError::new(
+                sqlstate::DUPLICATE_CURSOR,
+                format!("portal \"{portal}\" already exists"),
+            )
+            .into());
+        }
+        let types = &statement.parameter_types;
+        if parameters.len() != types.len() {
+            return Err(StatementError::new(
+                sqlstate::PROTOCOL_VIOLATION,
+                format!(
+                    "bind message supplies {} parameters, but prepared statement requires {}",
+                    parameters.len(),
+                    types.len()
+                ),
+            )
+            .into());
+        }
+        let formats = Format::expand(parameter_formats, parameters.len())
+            .map_err(|message| StatementError::new(sqlstate::PROTOCOL_VIOLATION, message))?;
+        let parameters = parameters
+            .iter()
+            .zip(types.iter().zip(formats))
+            .map(|(value, (&oid, format))| match value {
+                Some(bytes) => decode(bytes, oid, format)
+                    .map_err(|error| StatementError::from_error(error, &statement.sql)),
+                None => Ok(Value::Null),
+            })
+            .collect::<Result<_, _>>()?;
+        self.portals.insert(
+            portal,
+            Portal {
+                statement,
+                parameters,
+                result_formats,
+                state: PortalState::Ready,
+            },
+        );
+        self.send(&BackendMessage::BindComplete)
+    }
+
+    fn describe_statement(&mut self, name: &str) -> SessionResult<()> {
+        let statement = self.statement(name)?;
+        // Parameters of unspecified types take strings, which adapt to their context.
+        let types: Vec<u32> = statement
+            .parameter_types
+            .iter()
+            .map(|&oid| match oid {
+                oid::UNSPECIFIED => oid::TEXT,
+                oid => oid,
+            })
+            .collect();
+        self.send(&BackendMessage::ParameterDescription(&types))?;
+        let parameters = vec![Value::Null; types.len()];
+        // Result formats are only known once the statement is bound.
+        self.describe_rows(&statement, parameters, &[])
+    }
+
+    fn describe_portal(&mut self, name: &str) -> SessionResult<()> {
+        let portal = self.portals.get(name).ok_or_else(|| no_portal(name))?;
+        let statement = portal.statement.clone();
+        let parameters = portal.parameters.clone();
+        let formats = portal.result_formats.clone();
+        self.describe_rows(&statement, parameters, &formats)
+    }
+
+    /// Send the RowDescription of the rows `statement` returns with `parameters`, or NoData.
+    fn describe_rows(
+        &mut self,
+        statement: &PreparedStatement,
+        parameters: Vec<Value>,
+        result_formats: &[Format],
+    ) -> SessionResult<()> {
+        let Some(ast) = statement.statement.as_ref().filter(|s| returns_rows(s)) else {
+            return self.send(&BackendMessage::NoData);
+        };
+        let schema = self
+            .connection
+            .describe(ast, parameters)
+            .map_err(|error| StatementError::from_error(error, &statement.sql))?;
+        let names: Vec<String> = schema.columns().iter().map(|c| c.name.clone()).collect();
+        let types: Vec<DataType> = schema.columns().iter().map(|c| c.data_type).collect();
+        let formats = result_formats_for(result_formats, names.len())?;
+        let fields = field_descriptions(&names, &types, &formats);
+        self.send(&BackendMessage::RowDescription(&fields))
+    }
+
+    fn execute(&mut self, name: String, max_rows: u32) -> SessionResult<()> {
+        // Taken out while it runs, and dropped if it fails.
+        let mut portal = self.portals.remove(&name).ok_or_else(|| no_portal(&name))?;
+        let statement = portal.statement.clone();
+        let Some(ast) = &statement.statement else {
+            self.portals.insert(name, portal);
+            return self.send(&BackendMessage::EmptyQueryResponse);
+        };
+        let sql = &statement.sql;
+
+        if let PortalState::Ready = portal.state {
+            let rows = self
+                .connection
+                .run(ast, portal.parameters.clone())
+                .map_err(|error| StatementError::from_error(error, sql))?;
+            if !returns_rows(ast) {
+                let count = rows
+                    .finish()
+                    .map_err(|error| StatementError::from_error(error, sql))?;
+                portal.state = PortalState::Done;
+                self.portals.insert(name, portal);
+                return self.send(&BackendMessage::CommandComplete(&command_tag(ast, count)));
+            }
+            let formats = result_formats_for(&portal.result_formats, rows.columns().len())?;
+            portal.state = PortalState::Running { rows, formats };
+        }
+
+        let count = match &mut portal.state {
+            PortalState::Running { rows, formats } => {
+                let (count, suspended) = self.send_rows(rows, formats, max_rows, sql)?;
+                if suspended {
+                    self.portals.insert(name, portal);
+                    return self.send(&BackendMessage::PortalSuspended);
+                }
+                count
+            }
+            _ => 0,
+        };
+        portal.state = PortalState::Done;
+        self.portals.insert(name, portal);
+        self.send(&BackendMessage::CommandComplete(&command_tag(ast, count)))
+    }
+
+    /// Send rows until there are no more or `max_rows` of them, unless it is 0, returning how
+    /// many were sent and whether more may be left.
+    fn send_rows(
+        &mut self,
+        rows: &mut Rows<'db>,
+        formats: &[Format],
+        max_rows: u32,
+        sql: &str,
+    ) -> SessionResult<(u64, bool)> {
+        let types = rows.column_types().to_vec();
+        let mut count = 0;
+        while max_rows == 0 || count < max_rows as u64 {
+            let Some(row) = rows.next() else {
+                return Ok((count, false));
+            };
+            let row = row.map_err(|error| StatementError::from_error(error, sql))?;
+            let values = row
+                .values()
+                .iter()
+                .zip(types.iter().zip(formats))
+                .map(|(value, (data_type, &format))| encode(value, data_type, format))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(|error| StatementError::from_error(error, sql))?;
+            self.send(&BackendMessage::DataRow(&values))?;
+            count += 1;
+        }
+        Ok((count, true))
+    }
+
+    fn statement(&self, name: &str) -> Result<Rc<PreparedStatement>, StatementError> {
+        self.statements.get(name).cloned().ok_or_else(|| {
+            let message = match name {
+                "" => "unnamed prepared statement does not exist".to_string(),
+                name => format!("prepared statement \"{name}\" does not exist"),
+            };
+            StatementError::new(sqlstate::UNDEFINED_PSTATEMENT, message)
+        })
+    }
+
+    fn send(&mut self, message: &BackendMessage<'_>) -> SessionResult<()> {
+        Ok(write_message(&mut self.writer, message)?)
+    }
+
+    fn send_error(&mut self, error: &StatementError) -> SessionResult<()> {
+        self.send(&BackendMessage::ErrorResponse(&ErrorFields {
+            severity: "ERROR",
+            code: error.code,
+            message: error.message.clone(),
+            position: error.position,
+        }))
+    }
+
+    /// Report an error that ends the session.
+    fn fatal(&mut self, code: &'static str, message: &str) -> io::Result<()> {
+        let fields = ErrorFields {
+            severity: "FATAL",
+            code,
+            message: message.to_string(),
+            position: None,
+        };
+        write_message(&mut self.writer, &BackendMessage::ErrorResponse(&fields))?;
+        self.writer.flush()
+    }
+
+    fn ready(&mut self) -> io::Result<()> {
+        write_message(
+            &mut self.writer,
+            &BackendMessage::ReadyForQuery(TransactionStatus::Idle),
+        )?;
+        self.writer.flush()
+    }
+}
+
+fn no_portal(name: &str) -> StatementError {
+    let message = match name {
+        "" => "unnamed portal does not exist".to_string(),
+        name => format!("portal \"{name}\" does not exist"),
+    };
+    StatementError::new(sqlstate::INVALID_CURSOR_NAME, message)
+}
+
+/// The formats of `count` columns from the result format codes of a Bind message.
+fn result_formats_for(formats: &[Format], count: usize) -> Result<Vec<Format>, StatementError> {
+    Format::expand(formats, count)
+        .map_err(|message| StatementError::new(sqlstate::PROTOCOL_VIOLATION, message))
+}
+
+fn field_descriptions(
+    names: &[String],
+    types: &[DataType],
+    formats: &[Format],
+) -> Vec<FieldDescription> {
+    names
+        .iter()
+        .zip(types)
+        .zip(formats)
+        .map(|((name, data_type), &format)| FieldDescription {
+            name: name.clone(),
+            table_oid: 0,
+            column_number: 0,
+            type_oid: type_oid(data_type),
+            type_size: type_size(data_type),
+            type_modifier: type_modifier(data_type),
+            format,
+        })
+        .collect()
+}
+
+/// Whether the client gets rows from the statement, rather than only a command tag.
+fn returns_rows(statement: &ast::Statement) -> bool {
+    matches!(
+        statement,
+        ast::Statement::Select(_) | ast::Statement::Explain(_)
+    )
+}
+
+/// What CommandComplete reports for `statement`, which returned or changed `count` rows.
+fn command_tag(statement: &ast::Statement, count: u64) -> String {
+    match statement {
+        ast::Statement::Select(_) => format!("SELECT {count}"),
+        ast::Statement::Insert(_) => format!("INSERT 0 {count}"),
+        ast::Statement::Update(_) => format!("UPDATE {count}"),
+        ast::Statement::Delete(_) => format!("DELETE {count}"),
+        ast::Statement::CreateTable(_) => "CREATE TABLE".to_string(),
+        ast::Statement::DropTable(_) => "DROP TABLE".to_string(),
+        ast::Statement::CreateIndex(_) => "CREATE INDEX".to_string(),
+        ast::Statement::DropIndex(_) => "DROP INDEX".to_string(),
+        ast::Statement::Analyze(_) => "ANALYZE".to_string(),
+        ast::Statement::Explain(_) => "EXPLAIN".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        database::database::{Database, Options},
+        server::protocol::PROTOCOL_VERSION,
+    };
+
+    use super::*;
+
+    /// Messages as a client sends them.
+    #[derive(Default)]
+    struct Client {
+        input: Vec<u8>,
+    }
+
+    impl Client {
+        fn startup() -> Self {
+            let mut body = PROTOCOL_VERSION.to_be_bytes().to_vec();
+            body.extend_from_slice(b"user\0alice\0\0");
+            let mut input = (body.len() as i32 + 4).to_be_bytes().to_vec();
+            input.extend_from_slice(&body);
+            Self { input }
+        }
+
+        fn message(mut self, tag: u8, body: &[u8]) -> Self {
+            self.input.push(tag);
+            self.input
+                .extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+            self.input.extend_from_slice(body);
+            self
+        }
+
+        fn query(self, sql: &str) -> Self {
+            self.message(b'Q', &cstr(sql))
+        }
+
+        fn parse(self, name: &str, sql: &str, types: &[u32]) -> Self {
+            let mut body = cstr(name);
+            body.extend(cstr(sql));
+            body.extend_from_slice(&(types.len() as i16).to_be_bytes());
+            for oid in types {
+                body.extend_from_slice(&oid.to_be_bytes());
+            }
+            self.message(b'P', &body)
+        }
+
+        fn bind(
+            self,
+            portal: &str,
+            statement: &str,
+            parameters: &[Option<&[u8]>],
+            binary: bool,
+        ) -> Self {
+            let mut body = cstr(portal);
+            body.extend(cstr(statement));
+            body.extend_from_slice(&[0, 1, 0, binary as u8]);
+            body.extend_from_slice(&(parameters.len() as i16).to_be_bytes());
+            for parameter in parameters {
+                match parameter {
+                    Some(bytes) => {
+                        body.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+                        body.extend_from_slice(bytes);
+                    }
+                    None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+            body.extend_from_slice(&[0, 1, 0, binary as u8]);
+            self.message(b'B', &body)
+        }
+
+        fn describe(self, target: u8, name: &str) -> Self {
+            let mut body = vec![target];
+            body.extend(cstr(name));
+            self.message(b'D', &body)
+        }
+
+        fn execute(self, portal: &str, max_rows: i32) -> Self {
+            let mut body = cstr(portal);
+            body.extend_from_slice(&max_rows.to_be_bytes());
+            self.message(b'E', &body)
+        }
+
+        fn sync(self) -> Self {
+            self.message(b'S', b"")
+        }
+
+        /// Run a session over the messages and return what the server answered after
+        /// startup, a line per message.
+        fn run(self, db: &Database) -> Vec<String> {
+            let mut output = Vec::new();
+            let input = self.message(b'X', b"").input;
+            Session::new(db.connect(), input.as_slice(), &mut output)
+                .run()
+                .unwrap();
+            let messages = decode_output(&output);
+            let ready = messages.iter().position(|m| m == "Z I").unwrap();
+            messages[ready + 1..].to_vec()
+        }
+    }
+
+    fn cstr(s: &str) -> Vec<u8> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.push(0);
+        bytes
+    }
+
+    /// Render backend messages compactly: the type byte and the interesting fields.
+    fn decode_output(mut output: &[u8]) -> Vec<String> {
+        let mut messages = Vec::new();
+        while let [tag, rest @ ..] = output {
+            let len = i32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let body = &rest[4..len];
+            output = &rest[len..];
+            let strings = || {
+                body.split(|&b| b == 0)
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .collect::<Vec<_>>()
+            };
+            let i16_at = |i: usize| i16::from_be_bytes(body[i..i + 2].try_into().unwrap());
+            let i32_at = |i: usize| i32::from_be_bytes(body[i..i + 4].try_into().unwrap());
+            let text = match tag {
+                b'T' => {
+                    let mut fields = Vec::new();
+                    let mut pos = 2;
+                    for _ in 0..i16_at(0) {
+                        let end = pos + body[pos..].iter().position(|&b| b == 0).unwrap();
+                        let name = String::from_utf8_lossy(&body[pos..end]);
+                        let oid = i32_at(end + 7);
+                        let format = i16_at(end + 17);
+                        fields.push(format!("{name}:{oid}:{format}"));
+                        pos = end + 19;
+                    }
+                    fields.join(" ")
+                }
+                b'D' => {
+                    let mut values = Vec::new();
+                    let mut pos = 2;
+                    for _ in 0..i16_at(0) {
+                        let len = i32_at(pos);
+                        pos += 4;
+                        if len < 0 {
+                            values.push("NULL".to_string());
+                        } else {
+                            let value = &body[pos..pos + len as usize];
+                            values.push(match std::str::from_utf8(value) {
+                                Ok(s) if s.bytes().all(|b| b.is_ascii_graphic() || b == b' ') => {
+                                    s.to_string()
+                                }
+                                _ => format!("{value:?}"),
+                            });
+                            pos += len as usize;
+                        }
+                    }
+                    values.join("|")
+                }
+                b't' => (0..i16_at(0))
+                    .map(|i| i32_at(2 + i as usize * 4).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                b'E' => strings()
+                    .iter()
+                    .filter(|field| field.starts_with(['C', 'M', 'P']))
+                    .map(|field| field[1..].to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                b'C' | b'S' => strings().join(" ").trim_end().to_string(),
+                b'Z' => String::from(body[0] as char),
+                _ => String::new(),
+            };
+            messages.push(format!("{} {text}", *tag as char).trim_end().to_string());
+        }
+        messages
+    }
+
+    fn open() -> (tempfile::TempDir, Database) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let options = Options::new().with_pool_size(32).with_temp_pool_size(8);
+        let db = Database::open(tempdir.path().join("test.db"), options).unwrap();
+        (tempdir, db)
+    }
+
+    #[test]
+    fn test_startup() {
+        let (_tempdir, db) = open();
+        let mut ssl_request = 8i32.to_be_bytes().to_vec();
+        ssl_request.extend_from_slice(&80877103i32.to_be_bytes());
+        let mut input = ssl_request;
+        input.extend(Client::startup().input);
+        let mut output = Vec::new();
+        Session::new(db.connect(), input.as_slice(), &mut output)
+            .with_process_id(7)
+            .run()
+            .unwrap();
+        assert_eq!(output[0], b'N');
+        let messages = decode_output(&output[1..]);
+        assert_eq!(messages[0], "R");
+        assert!(messages.contains(&"S server_encoding UTF8".to_string()));
+        assert!(messages.contains(&"S session_authorization alice".to_string()));
+        assert_eq!(messages[messages.len() - 2], "K");
+        assert_eq!(messages[messages.len() - 1], "Z I");
+
+        let mut old = 12i32.to_be_bytes().to_vec();
+        old.extend_from_slice(&(2i32 << 16).to_be_bytes());
+        old.extend_from_slice(b"\0\0\0\0");
+        let mut output = Vec::new();
+        Session::new(db.connect(), old.as_slice(), &mut output)
+            .run()
+            .unwrap();
+        assert_eq!(
+            decode_output(&output),
+            ["E 0A000 unsupported frontend protocol 2.0: server supports 3.0"]
+        );
+    }
+
+    #[test]
+    fn test_simple_query() {
+        let (_tempdir, db) = open();
+        let messages = Client::startup()
+            .query(
+                "CREATE TABLE users (id BIGINT NOT NULL, name VARCHAR(16), joined DATE);
+                 INSERT INTO users VALUES (1, 'alice', '2024-01-02'), (2, NULL, NULL);",
+            )
+            .query("SELECT id, name, joined FROM users ORDER BY id; UPDATE users SET id = 3 WHERE id = 2")
+            .query(" ; ")
+            .query("SELECT 1; SELECT nope FROM users; SELECT 2")
+            .query("SELEKT 1")
+            .query("INSERT INTO users VALUES (NULL, 'x', NULL)")
+            .query("SELECT 1 / 0")
+            .run(&db);
+        assert_eq!(
+            messages,
+            [
+                "C CREATE TABLE",
+                "C INSERT 0 2",
+                "Z I",
+                "T id:20:0 name:1043:0 joined:1082:0",
+                "D 1|alice|2024-01-02",
+                "D 2|NULL|NULL",
+                "C SELECT 2",
+                "C UPDATE 1",
+                "Z I",
+                "I",
+                "Z I",
+                "T ?column?:23:0",
+                "D 1",
+                "C SELECT 1",
+                "E 42703 column \"nope\" does not exist 18",
+                "Z I",
+                "E 42601 unexpected SELEKT, expected a statement 1",
+                "Z I",
+                "E 23502 column \"id\" must not be NULL",
+                "Z I",
+                "T ?column?:23:0",
+                "E 22012 division by zero",
+                "Z I",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extended_query() {
+        let (_tempdir, db) = open();
+        db.connect()
+            .execute_batch(
+                "CREATE TABLE t (id INT NOT NULL, score DECIMAL(6, 2));
+                 INSERT INTO t VALUES (1, 1.5), (2, 2.25), (3, NULL);",
+            )
+            .unwrap();
+        let messages = Client::startup()
+            .parse(
+                "q",
+                "SELECT id, score FROM t WHERE id >= $1 ORDER BY id",
+                &[oid::INT4],
+            )
+            .describe(b'S', "q")
+            .bind("p", "q", &[Some(b"2")], false)
+            .describe(b'P', "p")
+            .execute("p", 1)
+            .execute("p", 0)
+            .sync()
+            // Binary parameters and results.
+            .bind("", "q", &[Some(&3i32.to_be_bytes())], true)
+            .execute("", 0)
+            .parse("", "INSERT INTO t VALUES ($1, ?)", &[])
+            .describe(b'S', "")
+            .bind("", "", &[Some(b"4"), None], false)
+            .describe(b'P', "")
+            .execute("", 0)
+            .sync()
+            // An error skips messages until Sync. Tables are looked up when the statement
+            // runs.
+            .parse("", "SELECT * FROM nope", &[])
+            .bind("", "", &[], false)
+            .execute("", 0)
+            .sync()
+            .bind("", "missing", &[], false)
+            .sync()
+            .parse("q", "SELECT 1", &[])
+            .sync()
+            .bind("", "q", &[Some(b"x")], false)
+            .sync()
+            .parse("", "", &[])
+            .bind("", "", &[], false)
+            .execute("", 0)
+            .sync()
+            .run(&db);
+        assert_eq!(
+            messages,
+            [
+                "1",
+                "t 23",
+                "T id:23:0 score:1700:0",
+                "2",
+                "T id:23:0 score:1700:0",
+                "D 2|2.25",
+                "s",
+                "D 3|NULL",
+                "C SELECT 1",
+                "Z I",
+                "2",
+                "D [0, 0, 0, 3]|NULL",
+                "C SELECT 1",
+                "1",
+                "t 25 25",
+                "n",
+                "2",
+                "n",
+                "C INSERT 0 1",
+                "Z I",
+                "1",
+                "2",
+                "E 42P01 relation \"nope\" does not exist 15",
+                "Z I",
+                "E 26000 prepared statement \"missing\" does not exist",
+                "Z I",
+                "E 42P05 prepared statement \"q\" already exists",
+                "Z I",
+                "E 22P02 invalid input syntax for type integer: \"x\"",
+                "Z I",
+                "1",
+                "2",
+                "I",
+                "Z I",
+            ]
+        );
+        let count = db
+            .connect()
+            .query_as::<(i64,)>("SELECT count(*) FROM t", ());
+        assert_eq!(count.unwrap(), [(4,)]);
+    }
+}
@@ -0,0 +1,159 @@
+//! SQLSTATE codes for the errors of statements.
+//!
+//! Errors in limebase carry a message, not a code, but their messages follow PostgreSQL's, so
+//! the code is found from the message. Errors of the parser are syntax errors whatever they
+//! say, so they are classified where they are parsed.
+
+use crate::sql::error::SqlError;
+
+pub const PROTOCOL_VIOLATION: &str = "08P01";
+pub const FEATURE_NOT_SUPPORTED: &str = "0A000";
+pub const STRING_DATA_RIGHT_TRUNCATION: &str = "22001";
+pub const NUMERIC_VALUE_OUT_OF_RANGE: &str = "22003";
+pub const DIVISION_BY_ZERO: &str = "22012";
+pub const INVALID_TEXT_REPRESENTATION: &str = "22P02";
+pub const INVALID_BINARY_REPRESENTATION: &str = "22P03";
+pub const NOT_NULL_VIOLATION: &str = "23502";
+pub const UNIQUE_VIOLATION: &str = "23505";
+pub const INVALID_CURSOR_NAME: &str = "34000";
+pub const SYNTAX_ERROR_OR_ACCESS_RULE_VIOLATION: &str = "42000";
+pub const SYNTAX_ERROR: &str = "42601";
+pub const INSUFFICIENT_PRIVILEGE: &str = "42501";
+pub const GROUPING_ERROR: &str = "42803";
+pub const DATATYPE_MISMATCH: &str = "42804";
+pub const CANNOT_COERCE: &str = "42846";
+pub const UNDEFINED_COLUMN: &str = "42703";
+pub const UNDEFINED_FUNCTION: &str = "42883";
+pub const UNDEFINED_TABLE: &str = "42P01";
+pub const UNDEFINED_PARAMETER: &str = "42P02";
+pub const UNDEFINED_OBJECT: &str = "42704";
+pub const UNDEFINED_PSTATEMENT: &str = "26000";
+pub const AMBIGUOUS_COLUMN: &str = "42702";
+pub const DUPLICATE_COLUMN: &str = "42701";
+pub const DUPLICATE_TABLE: &str = "42P07";
+pub const DUPLICATE_ALIAS: &str = "42712";
+pub const DUPLICATE_PSTATEMENT: &str = "42P05";
+pub const DUPLICATE_CURSOR: &str = "42P03";
+pub const INTERNAL_ERROR: &str = "XX000";
+
+/// The code of an error of binding or running a statement.
+pub fn classify(error: &anyhow::Error) -> &'static str {
+    let message = error.to_string();
+    let from_query = error.downcast_ref::<SqlError>().is_some();
+    let code = [
+        ("violates not-null constraint", NOT_NULL_VIOLATION),
+        ("must not be NULL", NOT_NULL_VIOLATION),
+        ("violates unique constraint", UNIQUE_VIOLATION),
+        ("division by zero", DIVISION_BY_ZERO),
+        ("out of range", NUMERIC_VALUE_OUT_OF_RANGE),
+        ("numeric field overflow", NUMERIC_VALUE_OUT_OF_RANGE),
+        ("invalid input syntax", INVALID_TEXT_REPRESENTATION),
+        (
+            "incorrect binary data format",
+            INVALID_BINARY_REPRESENTATION,
+        ),
+        ("value too long", STRING_DATA_RIGHT_TRUNCATION),
+        ("cannot modify system table", INSUFFICIENT_PRIVILEGE),
+        ("cannot drop system table", INSUFFICIENT_PRIVILEGE),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| message.contains(pattern))
+    .map(|(_, code)| code);
+    if let Some(code) = code {
+        return code;
+    }
+
+    if message.starts_with("relation ") || message.starts_with("table ") {
+        if message.ends_with("does not exist") {
+            return UNDEFINED_TABLE;
+        }
+        if message.ends_with("already exists") {
+            return DUPLICATE_TABLE;
+        }
+    }
+    if !from_query {
+        return match message.as_str() {
+            m if m.starts_with("index ") && m.ends_with("does not exist") => UNDEFINED_OBJECT,
+            m if m.ends_with("specified more than once") => DUPLICATE_COLUMN,
+            _ => INTERNAL_ERROR,
+        };
+    }
+    let code = [
+        ("missing FROM-clause entry", UNDEFINED_TABLE),
+        ("table name", DUPLICATE_ALIAS),
+        ("specified more than once", DUPLICATE_COLUMN),
+        ("multiple assignments", DUPLICATE_COLUMN),
+        ("is ambiguous", AMBIGUOUS_COLUMN),
+        ("GROUP BY", GROUPING_ERROR),
+        ("aggregate function", GROUPING_ERROR),
+        ("operator does not exist", UNDEFINED_FUNCTION),
+        ("there is no parameter", UNDEFINED_PARAMETER),
+        ("cannot cast", CANNOT_COERCE),
+        ("not supported", FEATURE_NOT_SUPPORTED),
+        ("only supports", FEATURE_NOT_SUPPORTED),
+        ("hash indexes cover", FEATURE_NOT_SUPPORTED),
+        ("type", DATATYPE_MISMATCH),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| message.contains(pattern))
+    .map(|(_, code)| code);
+    match code {
+        Some(code) => code,
+        None if message.starts_with("column ") => UNDEFINED_COLUMN,
+        None if message.starts_with("function ") => UNDEFINED_FUNCTION,
+        None => SYNTAX_ERROR_OR_ACCESS_RULE_VIOLATION,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::error::Span;
+
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let query = |message: &str| classify(&SqlError::new(message, Span::default()).into());
+        let runtime = |message: &str| classify(&anyhow::anyhow!(message.to_string()));
+        assert_eq!(query("relation \"t\" does not exist"), UNDEFINED_TABLE);
+        assert_eq!(query("column \"a\" does not exist"), UNDEFINED_COLUMN);
+        assert_eq!(
+            query("column \"a\" of relation \"t\" does not exist"),
+            UNDEFINED_COLUMN
+        );
+        assert_eq!(
+            query("column reference \"a\" is ambiguous"),
+            AMBIGUOUS_COLUMN
+        );
+        assert_eq!(
+            query("column \"a\" must appear in the GROUP BY clause or be used in an aggregate function"),
+            GROUPING_ERROR
+        );
+        assert_eq!(query("function nope() does not exist"), UNDEFINED_FUNCTION);
+        assert_eq!(
+            query("column \"a\" is of type INT32 but expression is of type VARCHAR"),
+            DATATYPE_MISMATCH
+        );
+        assert_eq!(
+            query("null value in column \"id\" of relation \"t\" violates not-null constraint"),
+            NOT_NULL_VIOLATION
+        );
+        assert_eq!(
+            query("subqueries are not supported in LIMIT"),
+            FEATURE_NOT_SUPPORTED
+        );
+        assert_eq!(
+            runtime("duplicate key value violates unique constraint \"t_id\""),
+            UNIQUE_VIOLATION
+        );
+        assert_eq!(
+            runtime("column \"id\" must not be NULL"),
+            NOT_NULL_VIOLATION
+        );
+        assert_eq!(runtime("division by zero"), DIVISION_BY_ZERO);
+        assert_eq!(runtime("INT32 out of range"), NUMERIC_VALUE_OUT_OF_RANGE);
+        assert_eq!(runtime("relation \"t\" already exists"), DUPLICATE_TABLE);
+        assert_eq!(runtime("index \"i\" does not exist"), UNDEFINED_OBJECT);
+        assert_eq!(runtime("page 7 is corrupt"), INTERNAL_ERROR);
+    }
+}
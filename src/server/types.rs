@@ -0,0 +1,453 @@
+//! The PostgreSQL types of limebase values and their text and binary wire formats.
+
+use anyhow::Context;
+
+use crate::types::{
+    data_type::DataType,
+    date_time::{Date, Timestamp},
+    decimal::Decimal,
+    value::Value,
+};
+
+use super::protocol::Format;
+
+/// The OIDs of the PostgreSQL types limebase values are sent as or can be received as.
+pub mod oid {
+    /// A parameter whose type the client leaves to the server.
+    pub const UNSPECIFIED: u32 = 0;
+    pub const BOOL: u32 = 16;
+    pub const BYTEA: u32 = 17;
+    pub const INT8: u32 = 20;
+    pub const INT2: u32 = 21;
+    pub const INT4: u32 = 23;
+    pub const TEXT: u32 = 25;
+    pub const FLOAT4: u32 = 700;
+    pub const FLOAT8: u32 = 701;
+    pub const UNKNOWN: u32 = 705;
+    pub const VARCHAR: u32 = 1043;
+    pub const DATE: u32 = 1082;
+    pub const TIMESTAMP: u32 = 1114;
+    pub const NUMERIC: u32 = 1700;
+}
+
+/// Days from 1970-01-01, where limebase dates start, to 2000-01-01, where PostgreSQL's do.
+const POSTGRES_EPOCH_DAYS: i32 = 10_957;
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// NUMERIC digits are base 10000, four decimal digits each.
+const NUMERIC_BASE_DIGITS: usize = 4;
+const NUMERIC_NEGATIVE: u16 = 0x4000;
+
+/// The type a column of `data_type` is described as. INT8, which PostgreSQL lacks, is sent as
+/// a `smallint`.
+pub fn type_oid(data_type: &DataType) -> u32 {
+    match data_type {
+        DataType::Boolean => oid::BOOL,
+        DataType::Int8 | DataType::Int16 => oid::INT2,
+        DataType::Int32 => oid::INT4,
+        DataType::Int64 => oid::INT8,
+        DataType::Float64 => oid::FLOAT8,
+        DataType::Decimal { .. } => oid::NUMERIC,
+        DataType::Varchar(_) => oid::VARCHAR,
+        DataType::Bytea => oid::BYTEA,
+        DataType::Date => oid::DATE,
+        DataType::Timestamp => oid::TIMESTAMP,
+    }
+}
+
+/// The `typlen` of the type, -1 for variable-length types.
+pub fn type_size(data_type: &DataType) -> i16 {
+    match data_type {
+        DataType::Boolean => 1,
+        DataType::Int8 | DataType::Int16 => 2,
+        DataType::Int32 | DataType::Date => 4,
+        DataType::Int64 | DataType::Float64 | DataType::Timestamp => 8,
+        DataType::Decimal { .. } | DataType::Varchar(_) | DataType::Bytea => -1,
+    }
+}
+
+/// The `atttypmod` of the type: the length or the precision and scale plus the 4 bytes of a
+/// header, as PostgreSQL counts it, or -1.
+pub fn type_modifier(data_type: &DataType) -> i32 {
+    match data_type {
+        DataType::Varchar(Some(len)) => *len as i32 + 4,
+        DataType::Decimal { precision, scale } => ((*precision as i32) << 16 | *scale as i32) + 4,
+        _ => -1,
+    }
+}
+
+/// The name of the type as PostgreSQL spells it, for errors.
+fn type_name(oid: u32) -> &'static str {
+    match oid {
+        oid::BOOL => "boolean",
+        oid::BYTEA => "bytea",
+        oid::INT8 => "bigint",
+        oid::INT2 => "smallint",
+        oid::INT4 => "integer",
+        oid::FLOAT4 => "real",
+        oid::FLOAT8 => "double precision",
+        oid::VARCHAR => "character varying",
+        oid::DATE => "date",
+        oid::TIMESTAMP => "timestamp without time zone",
+        oid::NUMERIC => "numeric",
+        _ => "text",
+    }
+}
+
+/// Encode `value` of a column of `data_type` in `format`, None for NULL.
+pub fn encode(
+    value: &Value,
+    data_type: &DataType,
+    format: Format,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let bytes = match format {
+        Format::Text => encode_text(value).into_bytes(),
+        Format::Binary => encode_binary(value, data_type)?,
+    };
+    Ok(Some(bytes))
+}
+
+fn encode_text(value: &Value) -> String {
+    match value {
+        Value::Boolean(v) => (if *v { "t" } else { "f" }).to_string(),
+        Value::Float64(v) if v.is_nan() => "NaN".to_string(),
+        Value::Float64(v) if v.is_infinite() => {
+            (if *v > 0.0 { "Infinity" } else { "-Infinity" }).to_string()
+        }
+        // PostgreSQL leaves out trailing zeros of the fraction of a second.
+        Value::Timestamp(_) => {
+            let text = value.to_string();
+            if text.contains('.') {
+                text.trim_end_matches('0').to_string()
+            } else {
+                text
+            }
+        }
+        value => value.to_string(),
+    }
+}
+
+fn encode_binary(value: &Value, data_type: &DataType) -> anyhow::Result<Vec<u8>> {
+    let mismatch = || anyhow::anyhow!("cannot send {value} as {data_type}");
+    Ok(match data_type {
+        DataType::Boolean => vec![value.as_bool().ok_or_else(mismatch)? as u8],
+        DataType::Int8 | DataType::Int16 => {
+            let v = value.as_i64().ok_or_else(mismatch)?;
+            i16::try_from(v)?.to_be_bytes().to_vec()
+        }
+        DataType::Int32 => {
+            let v = value.as_i64().ok_or_else(mismatch)?;
+            i32::try_from(v)?.to_be_bytes().to_vec()
+        }
+        DataType::Int64 => value.as_i64().ok_or_else(mismatch)?.to_be_bytes().to_vec(),
+        DataType::Float64 => match value {
+            Value::Float64(v) => v.to_be_bytes().to_vec(),
+            value => (value.as_i64().ok_or_else(mismatch)? as f64)
+                .to_be_bytes()
+                .to_vec(),
+        },
+        DataType::Decimal { .. } => match value {
+            Value::Decimal(v) => encode_numeric(v),
+            value => encode_numeric(&Decimal::from_i64(value.as_i64().ok_or_else(mismatch)?)),
+        },
+        DataType::Varchar(_) => encode_text(value).into_bytes(),
+        DataType::Bytea => match value {
+            Value::Bytea(v) => v.clone(),
+            _ => return Err(mismatch()),
+        },
+        DataType::Date => match value {
+            Value::Date(v) => (v.0 - POSTGRES_EPOCH_DAYS).to_be_bytes().to_vec(),
+            _ => return Err(mismatch()),
+        },
+        DataType::Timestamp => match value {
+            Value::Timestamp(v) => (v.0 - POSTGRES_EPOCH_DAYS as i64 * MICROS_PER_DAY)
+                .to_be_bytes()
+                .to_vec(),
+            _ => return Err(mismatch()),
+        },
+    })
+}
+
+/// Decode a parameter sent in `format` as a value of the type `oid`. Text of an unspecified
+/// type becomes a string, which adapts to its context as a string literal does.
+pub fn decode(bytes: &[u8], oid: u32, format: Format) -> anyhow::Result<Value> {
+    match format {
+        Format::Text => {
+            let text = std::str::from_utf8(bytes).context("invalid byte sequence for UTF8")?;
+            decode_text(text, oid).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "invalid input syntax for type {}: \"{text}\"",
+                    type_name(oid)
+                )
+            })
+        }
+        Format::Binary => decode_binary(bytes, oid).ok_or_else(|| {
+            anyhow::anyhow!("incorrect binary data format for type {}", type_name(oid))
+        }),
+    }
+}
+
+fn decode_text(text: &str, oid: u32) -> Option<Value> {
+    Some(match oid {
+        oid::BOOL => Value::Boolean(match text.trim().to_ascii_lowercase().as_str() {
+            "t" | "true" | "y" | "yes" | "on" | "1" => true,
+            "f" | "false" | "n" | "no" | "off" | "0" => false,
+            _ => return None,
+        }),
+        oid::INT2 => Value::Int16(text.trim().parse().ok()?),
+        oid::INT4 => Value::Int32(text.trim().parse().ok()?),
+        oid::INT8 => Value::Int64(text.trim().parse().ok()?),
+        oid::FLOAT4 | oid::FLOAT8 => Value::Float64(match text.trim() {
+            "Infinity" | "inf" => f64::INFINITY,
+            "-Infinity" | "-inf" => f64::NEG_INFINITY,
+            text => text.parse().ok()?,
+        }),
+        oid::NUMERIC => Value::Decimal(Decimal::parse(text)?),
+        oid::BYTEA => Value::Bytea(match text.strip_prefix("\\x") {
+            Some(hex) => decode_hex(hex)?,
+            None => text.as_bytes().to_vec(),
+        }),
+        oid::DATE => Value::Date(Date::parse(text)?),
+        oid::TIMESTAMP => Value::Timestamp(Timestamp::parse(text)?),
+        _ => Value::Varchar(text.to_string()),
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_binary(bytes: &[u8], oid: u32) -> Option<Value> {
+    Some(match oid {
+        oid::BOOL => match bytes {
+            [b] => Value::Boolean(*b != 0),
+            _ => return None,
+        },
+        oid::INT2 => Value::Int16(i16::from_be_bytes(bytes.try_into().ok()?)),
+        oid::INT4 => Value::Int32(i32::from_be_bytes(bytes.try_into().ok()?)),
+        oid::INT8 => Value::Int64(i64::from_be_bytes(bytes.try_into().ok()?)),
+        oid::FLOAT4 => Value::Float64(f32::from_be_bytes(bytes.try_into().ok()?) as f64),
+        oid::FLOAT8 => Value::Float64(f64::from_be_bytes(bytes.try_into().ok()?)),
+        oid::NUMERIC => Value::Decimal(decode_numeric(bytes)?),
+        oid::BYTEA => Value::Bytea(bytes.to_vec()),
+        oid::DATE => {
+            let days = i32::from_be_bytes(bytes.try_into().ok()?);
+            Value::Date(Date(days.checked_add(POSTGRES_EPOCH_DAYS)?))
+        }
+        oid::TIMESTAMP => {
+            let micros = i64::from_be_bytes(bytes.try_into().ok()?);
+            Value::Timestamp(Timestamp(
+                micros.checked_add(POSTGRES_EPOCH_DAYS as i64 * MICROS_PER_DAY)?,
+            ))
+        }
+        oid::TEXT | oid::VARCHAR | oid::UNKNOWN => {
+            Value::Varchar(String::from_utf8(bytes.to_vec()).ok()?)
+        }
+        _ => return None,
+    })
+}
+
+/// The binary NUMERIC format: the number of base-10000 digits, the weight of the first one,
+/// the sign, the number of decimal digits after the point, and the digits, all big-endian.
+fn encode_numeric(value: &Decimal) -> Vec<u8> {
+    let scale = value.scale() as usize;
+    let digits = format!(
+        "{:0>width$}",
+        value.mantissa().unsigned_abs(),
+        width = scale + 1
+    );
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    // Pad both parts to whole base-10000 digits around the point.
+    let integer_pad =
+        (NUMERIC_BASE_DIGITS - integer.len() % NUMERIC_BASE_DIGITS) % NUMERIC_BASE_DIGITS;
+    let fraction_pad =
+        (NUMERIC_BASE_DIGITS - fraction.len() % NUMERIC_BASE_DIGITS) % NUMERIC_BASE_DIGITS;
+    let padded = format!(
+        "{}{integer}{fraction}{}",
+        "0".repeat(integer_pad),
+        "0".repeat(fraction_pad)
+    );
+    let mut groups: Vec<u16> = padded
+        .as_bytes()
+        .chunks(NUMERIC_BASE_DIGITS)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap().parse().unwrap())
+        .collect();
+    let mut weight = ((integer.len() + integer_pad) / NUMERIC_BASE_DIGITS) as i16 - 1;
+    let leading = groups.iter().take_while(|&&g| g == 0).count();
+    groups.drain(..leading);
+    weight -= leading as i16;
+    while groups.last() == Some(&0) {
+        groups.pop();
+    }
+    if groups.is_empty() {
+        weight = 0;
+    }
+    let sign = if value.mantissa() < 0 {
+        NUMERIC_NEGATIVE
+    } else {
+        0
+    };
+
+    let mut bytes = Vec::with_capacity(8 + groups.len() * 2);
+    bytes.extend_from_slice(&(groups.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&weight.to_be_bytes());
+    bytes.extend_from_slice(&sign.to_be_bytes());
+    bytes.extend_from_slice(&(scale as u16).to_be_bytes());
+    for group in groups {
+        bytes.extend_from_slice(&group.to_be_bytes());
+    }
+    bytes
+}
+
+fn decode_numeric(bytes: &[u8]) -> Option<Decimal> {
+    let field = |i: usize| {
+        Some(u16::from_be_bytes(
+            bytes.get(i * 2..i * 2 + 2)?.try_into().ok()?,
+        ))
+    };
+    let count = field(0)? as usize;
+    let weight = field(1)? as i16 as i32;
+    let sign = field(2)?;
+    let scale = field(3)?;
+    if bytes.len() != 8 + count * 2 || !matches!(sign, 0 | NUMERIC_NEGATIVE) {
+        // NaN and infinities have no Decimal.
+        return None;
+    }
+    let scale = u8::try_from(scale)
+        .ok()
+        .filter(|&s| s <= DataType::MAX_DECIMAL_PRECISION)?;
+    let mut mantissa: i128 = 0;
+    for i in 0..count {
+        let group = field(4 + i)?;
+        if group >= 10_000 {
+            return None;
+        }
+        // The power of ten of the last decimal digit of the group, relative to the scale.
+        let exponent = (weight - i as i32) * NUMERIC_BASE_DIGITS as i32 + scale as i32;
+        let term = if exponent >= 0 {
+            (group as i128).checked_mul(10i128.checked_pow(exponent as u32)?)?
+        } else {
+            group as i128 / 10i128.checked_pow(exponent.unsigned_abs())?
+        };
+        mantissa = mantissa.checked_add(term)?;
+    }
+    if sign == NUMERIC_NEGATIVE {
+        mantissa = -mantissa;
+    }
+    Some(Decimal::new(mantissa, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let text = |value: Value| {
+            let data_type = value.data_type().unwrap();
+            String::from_utf8(encode(&value, &data_type, Format::Text).unwrap().unwrap()).unwrap()
+        };
+        assert_eq!(text(Value::Boolean(true)), "t");
+        assert_eq!(text(Value::Float64(1.5)), "1.5");
+        assert_eq!(text(Value::Float64(f64::NEG_INFINITY)), "-Infinity");
+        assert_eq!(text(Value::Bytea(vec![0xde, 0xad])), "\\xdead");
+        assert_eq!(
+            text(Value::Timestamp(
+                Timestamp::parse("2024-02-29 12:34:56.5").unwrap()
+            )),
+            "2024-02-29 12:34:56.5"
+        );
+        assert_eq!(
+            encode(&Value::Null, &DataType::Int32, Format::Binary).unwrap(),
+            None
+        );
+
+        let binary = |value: Value, data_type: DataType| {
+            encode(&value, &data_type, Format::Binary).unwrap().unwrap()
+        };
+        assert_eq!(binary(Value::Int8(-2), DataType::Int8), [0xff, 0xfe]);
+        assert_eq!(binary(Value::Int32(7), DataType::Int64), 7i64.to_be_bytes());
+        assert_eq!(
+            binary(
+                Value::Date(Date::from_ymd(2000, 1, 2).unwrap()),
+                DataType::Date
+            ),
+            1i32.to_be_bytes()
+        );
+        assert!(encode(&Value::Int64(1 << 40), &DataType::Int32, Format::Binary).is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode(b"42", oid::INT4, Format::Text).unwrap(),
+            Value::Int32(42)
+        );
+        assert_eq!(
+            decode(b"42", oid::UNSPECIFIED, Format::Text).unwrap(),
+            Value::Varchar("42".to_string())
+        );
+        assert_eq!(
+            decode(b"\\x0aff", oid::BYTEA, Format::Text).unwrap(),
+            Value::Bytea(vec![0x0a, 0xff])
+        );
+        assert_eq!(
+            decode(b"yes", oid::BOOL, Format::Text).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            decode(b"x", oid::INT8, Format::Text)
+                .unwrap_err()
+                .to_string(),
+            "invalid input syntax for type bigint: \"x\""
+        );
+        assert_eq!(
+            decode(&(-3i64).to_be_bytes(), oid::INT8, Format::Binary).unwrap(),
+            Value::Int64(-3)
+        );
+        assert_eq!(
+            decode(&0i64.to_be_bytes(), oid::TIMESTAMP, Format::Binary).unwrap(),
+            Value::Timestamp(Timestamp::parse("2000-01-01").unwrap())
+        );
+        assert!(decode(&[0, 1], oid::INT4, Format::Binary).is_err());
+        assert!(decode(b"", oid::UNSPECIFIED, Format::Binary).is_err());
+    }
+
+    #[test]
+    fn test_numeric() {
+        for s in [
+            "0",
+            "0.00",
+            "1",
+            "-1",
+            "12345.678",
+            "0.0001",
+            "-0.00012",
+            "10000",
+            "100000000.5",
+            "99999999999999999999999999999999999999",
+        ] {
+            let decimal = Decimal::parse(s).unwrap();
+            let bytes = encode_numeric(&decimal);
+            let decoded = decode_numeric(&bytes).unwrap();
+            assert_eq!(decoded.to_string(), s);
+            assert_eq!(decoded.scale(), decimal.scale());
+        }
+        // 12345.678 is 1|2345.6780: two digits before the point, one after.
+        assert_eq!(
+            encode_numeric(&Decimal::parse("12345.678").unwrap()),
+            [0, 3, 0, 1, 0, 0, 0, 3, 0, 1, 0x09, 0x29, 0x1a, 0x7c]
+        );
+        let nan = [0, 0, 0, 0, 0xc0, 0, 0, 0];
+        assert!(decode_numeric(&nan).is_none());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod line_editor;
+#[allow(clippy::module_inception)]
+pub mod shell;
+pub mod table;
@@ -0,0 +1,431 @@
+//! Reading lines with editing and history, without a terminal library.
+//!
+//! When standard input is a terminal, `stty` switches it out of canonical mode for the length
+//! of a line, so that keys arrive one at a time, and the line is redrawn after every key. The
+//! usual Emacs-style keys work:
+//!
+//! | key                    | action                                      |
+//! |------------------------|---------------------------------------------|
+//! | Left, Right, ^B, ^F    | move by a character                         |
+//! | Home, End, ^A, ^E      | move to the start or the end                |
+//! | Up, Down, ^P, ^N       | recall the previous or the next history entry |
+//! | Backspace, Delete, ^H  | delete before or under the cursor           |
+//! | ^K, ^U, ^W             | delete to the end, to the start, or a word  |
+//! | ^L                     | clear the screen                            |
+//! | ^C                     | abandon the line                            |
+//! | ^D                     | end of input on an empty line               |
+//!
+//! Every character is taken to be one column wide, and lines longer than the terminal are not
+//! wrapped specially. Otherwise, as when input is piped or `stty` is missing, lines are read
+//! as they are.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, IsTerminal, Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// The most history entries kept.
+pub const MAX_HISTORY: usize = 1000;
+
+/// What reading a line ended with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadLine {
+    Line(String),
+    /// The line was abandoned with ^C.
+    Interrupted,
+    Eof,
+}
+
+pub struct LineEditor {
+    history: Vec<String>,
+    /// The file the history is loaded from and appended to.
+    history_path: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            history: Vec::new(),
+            history_path: None,
+        }
+    }
+
+    /// Load the history from `path` if it exists, and append new entries to it.
+    pub fn with_history_file(self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut history = self.history;
+        if let Ok(file) = File::open(&path) {
+            history.extend(BufReader::new(file).lines().map_while(Result::ok));
+            let excess = history.len().saturating_sub(MAX_HISTORY);
+            history.drain(..excess);
+        }
+        Self {
+            history,
+            history_path: Some(path),
+        }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Remember `entry`, unless it is empty or repeats the last entry. Line breaks become
+    /// spaces, so that the entry is one line of the history file.
+    pub fn add_history(&mut self, entry: &str) -> io::Result<()> {
+        let entry = entry.trim().replace(['\r', '\n'], " ");
+        if entry.is_empty() || self.history.last() == Some(&entry) {
+            return Ok(());
+        }
+        if let Some(path) = &self.history_path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{entry}")?;
+        }
+        self.history.push(entry);
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Read a line from standard input after printing `prompt`.
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<ReadLine> {
+        let stdin = io::stdin();
+        if stdin.is_terminal() {
+            if let Ok(_raw) = RawMode::enable() {
+                return self.read_edited(prompt, &mut stdin.lock().bytes());
+            }
+        }
+        let mut stdout = io::stdout();
+        write!(stdout, "{prompt}")?;
+        stdout.flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(ReadLine::Eof);
+        }
+        Ok(ReadLine::Line(
+            line.trim_end_matches(['\r', '\n']).to_string(),
+        ))
+    }
+
+    fn read_edited(
+        &mut self,
+        prompt: &str,
+        input: &mut impl Iterator<Item = io::Result<u8>>,
+    ) -> io::Result<ReadLine> {
+        let mut stdout = io::stdout();
+        let mut editing = Editing::new(&self.history);
+        write!(stdout, "{}", editing.render(prompt))?;
+        stdout.flush()?;
+        loop {
+            let Some(key) = read_key(input)? else {
+                writeln!(stdout)?;
+                return Ok(ReadLine::Eof);
+            };
+            if key == Key::ClearScreen {
+                write!(stdout, "\x1b[H\x1b[2J")?;
+            }
+            let outcome = editing.handle(key);
+            write!(stdout, "{}", editing.render(prompt))?;
+            if let Some(outcome) = outcome {
+                writeln!(stdout)?;
+                stdout.flush()?;
+                return Ok(outcome);
+            }
+            stdout.flush()?;
+        }
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The terminal out of canonical mode and echo, until dropped.
+struct RawMode {
+    /// The settings to restore, as `stty -g` prints them.
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "min", "1"])?;
+        Ok(Self {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Run `stty` on the terminal of standard input and return what it prints.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(io::ErrorKind::Other, "stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillToEnd,
+    KillToStart,
+    KillWord,
+    ClearScreen,
+    Interrupt,
+    /// ^D, which ends the input on an empty line and deletes otherwise.
+    EndOfInput,
+    /// A key or an escape sequence without an action.
+    Ignored,
+}
+
+/// Decode the next key from the bytes a terminal sends, or None at the end of the input.
+fn read_key(input: &mut impl Iterator<Item = io::Result<u8>>) -> io::Result<Option<Key>> {
+    let Some(byte) = input.next().transpose()? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::Interrupt,
+        0x04 => Key::EndOfInput,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0b => Key::KillToEnd,
+        0x0c => Key::ClearScreen,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::KillToStart,
+        0x17 => Key::KillWord,
+        0x1b => read_escape(input)?,
+        byte if byte < 0x20 => Key::Ignored,
+        byte => {
+            // The continuation bytes of a UTF-8 character follow its first byte.
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                match input.next().transpose()? {
+                    Some(byte) => bytes.push(byte),
+                    None => break,
+                }
+            }
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map_or(Key::Ignored, Key::Char)
+        }
+    };
+    Ok(Some(key))
+}
+
+/// Decode the rest of an escape sequence: `ESC [` or `ESC O`, optional digits, and a final
+/// character.
+fn read_escape(input: &mut impl Iterator<Item = io::Result<u8>>) -> io::Result<Key> {
+    let Some(b'[' | b'O') = input.next().transpose()? else {
+        return Ok(Key::Ignored);
+    };
+    let mut parameter = String::new();
+    loop {
+        let Some(byte) = input.next().transpose()? else {
+            return Ok(Key::Ignored);
+        };
+        if byte.is_ascii_digit() || byte == b';' {
+            parameter.push(byte as char);
+            continue;
+        }
+        return Ok(match (byte, parameter.as_str()) {
+            (b'A', _) => Key::Up,
+            (b'B', _) => Key::Down,
+            (b'C', _) => Key::Right,
+            (b'D', _) => Key::Left,
+            (b'H', _) | (b'~', "1" | "7") => Key::Home,
+            (b'F', _) | (b'~', "4" | "8") => Key::End,
+            (b'~', "3") => Key::Delete,
+            _ => Key::Ignored,
+        });
+    }
+}
+
+/// The state of a line being edited.
+struct Editing<'h> {
+    history: &'h [String],
+    line: Vec<char>,
+    cursor: usize,
+    /// The history entry shown, or `history.len()` for the new line.
+    entry: usize,
+    /// The new line, while a history entry is shown.
+    draft: Vec<char>,
+}
+
+impl<'h> Editing<'h> {
+    fn new(history: &'h [String]) -> Self {
+        Self {
+            history,
+            line: Vec::new(),
+            cursor: 0,
+            entry: history.len(),
+            draft: Vec::new(),
+        }
+    }
+
+    /// Apply `key`, and return how reading ended if it did.
+    fn handle(&mut self, key: Key) -> Option<ReadLine> {
+        match key {
+            Key::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Enter => return Some(ReadLine::Line(self.line.iter().collect())),
+            Key::Interrupt => return Some(ReadLine::Interrupted),
+            Key::EndOfInput if self.line.is_empty() => return Some(ReadLine::Eof),
+            Key::EndOfInput | Key::Delete => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                }
+            }
+            Key::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.line.len(),
+            Key::KillToEnd => self.line.truncate(self.cursor),
+            Key::KillToStart => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::KillWord => {
+                let mut start = self.cursor;
+                while start > 0 && self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Up if self.entry > 0 => self.show_entry(self.entry - 1),
+            Key::Down if self.entry < self.history.len() => self.show_entry(self.entry + 1),
+            Key::Up | Key::Down | Key::ClearScreen | Key::Ignored => {}
+        }
+        None
+    }
+
+    fn show_entry(&mut self, entry: usize) {
+        if self.entry == self.history.len() {
+            self.draft = std::mem::take(&mut self.line);
+        }
+        self.entry = entry;
+        self.line = match self.history.get(entry) {
+            Some(line) => line.chars().collect(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.cursor = self.line.len();
+    }
+
+    /// What redraws the line after `prompt`, with the cursor in place.
+    fn render(&self, prompt: &str) -> String {
+        let line: String = self.line.iter().collect();
+        let mut out = format!("\r{prompt}{line}\x1b[K");
+        let back = self.line.len() - self.cursor;
+        if back > 0 {
+            out += &format!("\x1b[{back}D");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply the keys of `input` and return how reading ended, with the cursor position.
+    fn edit(history: &[String], input: &[u8]) -> (Option<ReadLine>, String, usize) {
+        let mut editing = Editing::new(history);
+        let mut bytes = input.iter().map(|&b| Ok(b));
+        while let Some(key) = read_key(&mut bytes).unwrap() {
+            if let Some(outcome) = editing.handle(key) {
+                return (Some(outcome), editing.line.iter().collect(), editing.cursor);
+            }
+        }
+        (None, editing.line.iter().collect(), editing.cursor)
+    }
+
+    #[test]
+    fn test_editing() {
+        let line = |s: &str| Some(ReadLine::Line(s.to_string()));
+        // Insert in the middle, delete with Backspace and Delete, and move by keys and
+        // escape sequences.
+        assert_eq!(edit(&[], b"SELEC 1\x1b[D\x1b[DT\r").0, line("SELECT 1"));
+        assert_eq!(edit(&[], b"abcd\x7f\x01\x1b[3~\r").0, line("bc"));
+        assert_eq!(edit(&[], b"abc\x1b[H\x1b[C\x0b").1, "a");
+        assert_eq!(edit(&[], b"abc\x02\x15").1, "c");
+        assert_eq!(edit(&[], b"select * from  t\x17").1, "select * from  ");
+        assert_eq!(edit(&[], b"a b\x17\x17").1, "");
+        assert_eq!(
+            edit(&[], "héllo\x02\x02\x7f".as_bytes()),
+            (None, "hélo".to_string(), 2)
+        );
+
+        assert_eq!(edit(&[], b"abc\x03").0, Some(ReadLine::Interrupted));
+        assert_eq!(edit(&[], b"\x04").0, Some(ReadLine::Eof));
+        assert_eq!(edit(&[], b"ab\x01\x04\r").0, line("b"));
+
+        // Going up keeps the line being typed for when coming back down.
+        let history = vec!["SELECT 1;".to_string(), "SELECT 2;".to_string()];
+        assert_eq!(edit(&history, b"\x1b[A\r").0, line("SELECT 2;"));
+        assert_eq!(edit(&history, b"\x1b[A\x1b[A\x1b[A\r").0, line("SELECT 1;"));
+        assert_eq!(edit(&history, b"new\x10\x10\x0e\x0e\r").0, line("new"));
+        assert_eq!(edit(&history, b"\x1bOA\x7f\r").0, line("SELECT 2"));
+    }
+
+    #[test]
+    fn test_history_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("history");
+        let mut editor = LineEditor::new().with_history_file(&path);
+        editor.add_history("SELECT 1;").unwrap();
+        editor.add_history("SELECT 1;").unwrap();
+        editor.add_history("  ").unwrap();
+        editor.add_history("SELECT\n  2;").unwrap();
+        assert_eq!(editor.history(), ["SELECT 1;", "SELECT   2;"]);
+
+        let editor = LineEditor::new().with_history_file(&path);
+        assert_eq!(editor.history(), ["SELECT 1;", "SELECT   2;"]);
+    }
+}
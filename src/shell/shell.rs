@@ -0,0 +1,525 @@
+//! The SQL shell of the `limebase` binary.
+//!
+//! Input is read a line at a time and collected until it ends a statement with `;`, outside of
+//! string literals and comments, so a statement may span several lines. Then every statement
+//! it holds is parsed, bound, optimized and run in turn, and its rows are printed as a
+//! [table](super::table). Lines starting with `.` at the start of a statement are meta-commands
+//! (see [`HELP`]).
+//!
+//! Each statement that changes the database is committed once it is done, so that what the
+//! shell reports as done survives a crash.
+
+use std::{
+    io::{self, BufRead, Write},
+    time::Instant,
+};
+
+use crate::{
+    buffer::buffer_pool_manager::{BufferPoolManager, BufferPoolStats, TempBufferPool},
+    catalog::catalog::{Catalog, IndexInfo, TableInfo},
+    execution::{
+        analyze::AnalyzeConfig,
+        builder::build_executor,
+        context::ExecutorContext,
+        ddl::{execute_ddl, is_ddl},
+    },
+    optimizer::optimizer::Optimizer,
+    planner::{binder::Binder, logical_plan::LogicalPlan},
+    recovery::recovery::commit,
+    sql::{
+        ast::Statement,
+        error::SqlError,
+        lexer::{tokenize, TokenKind},
+        parser::parse,
+    },
+    storage::index::index::IndexKind,
+};
+
+use super::{
+    line_editor::{LineEditor, ReadLine},
+    table::format_table,
+};
+
+pub const HELP: &str = "\
+.help              show this help
+.tables            list the tables
+.schema [TABLE]    show the CREATE statements of a table, or of every table
+.indexes [TABLE]   list the indexes of a table, or of every table
+.stats             show the buffer pool counters
+.timing [on|off]   show how long each statement takes
+.quit, .exit       leave the shell
+";
+
+const PROMPT: &str = "limebase> ";
+const CONTINUATION_PROMPT: &str = "     ...> ";
+
+/// Whether the shell should keep reading after a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    /// A statement or a meta-command failed.
+    Failed,
+    Quit,
+}
+
+pub struct Shell<'c, 'a, B: BufferPoolManager + ?Sized> {
+    catalog: &'c Catalog<'a, B>,
+    temp: &'a TempBufferPool,
+    timing: bool,
+    /// The lines of the statement being entered.
+    buffer: String,
+}
+
+impl<'c, 'a, B: BufferPoolManager + ?Sized> Shell<'c, 'a, B> {
+    pub fn new(catalog: &'c Catalog<'a, B>, temp: &'a TempBufferPool) -> Self {
+        Self {
+            catalog,
+            temp,
+            timing: false,
+            buffer: String::new(),
+        }
+    }
+
+    pub fn with_timing(self, timing: bool) -> Self {
+        Self { timing, ..self }
+    }
+
+    /// The prompt for the next line, which shows whether a statement is being continued.
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        }
+    }
+
+    /// Read statements from the terminal until `.quit` or the end of input.
+    pub fn run_interactive(&mut self, editor: &mut LineEditor) -> io::Result<()> {
+        let (mut out, mut err) = (io::stdout(), io::stderr());
+        // The lines of the statement being entered, which go into the history together.
+        let mut entry = String::new();
+        loop {
+            let line = match editor.read_line(self.prompt())? {
+                ReadLine::Line(line) => line,
+                ReadLine::Interrupted => {
+                    self.buffer.clear();
+                    entry.clear();
+                    continue;
+                }
+                ReadLine::Eof => return Ok(()),
+            };
+            entry.push_str(&line);
+            entry.push('\n');
+            let control = self.feed_line(&line, &mut out, &mut err)?;
+            if self.buffer.is_empty() {
+                editor.add_history(&entry)?;
+                entry.clear();
+            }
+            if control == Control::Quit {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Run the statements of a script, stopping at the first that fails. Return whether every
+    /// statement succeeded.
+    pub fn run_script(
+        &mut self,
+        input: impl BufRead,
+        out: &mut impl Write,
+        err: &mut impl Write,
+    ) -> io::Result<bool> {
+        for line in input.lines() {
+            match self.feed_line(&line?, out, err)? {
+                Control::Continue => {}
+                Control::Failed => return Ok(false),
+                Control::Quit => return Ok(true),
+            }
+        }
+        if self.buffer.trim().is_empty() {
+            return Ok(true);
+        }
+        // A last statement without a semicolon still runs.
+        let sql = std::mem::take(&mut self.buffer);
+        Ok(self.run_sql(&sql, out, err)? == Control::Continue)
+    }
+
+    /// Take a line of input: run a meta-command, or add it to the statement being entered and
+    /// run the statements it completes. Results go to `out` and errors to `err`.
+    pub fn feed_line(
+        &mut self,
+        line: &str,
+        out: &mut impl Write,
+        err: &mut impl Write,
+    ) -> io::Result<Control> {
+        if self.buffer.is_empty() && line.trim_start().starts_with('.') {
+            return match self.meta_command(line.trim(), out) {
+                Ok(control) => Ok(control),
+                Err(error) => {
+                    writeln!(err, "error: {error:#}")?;
+                    Ok(Control::Failed)
+                }
+            };
+        }
+        if self.buffer.is_empty() && line.trim().is_empty() {
+            return Ok(Control::Continue);
+        }
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        if !ends_statement(&self.buffer) {
+            return Ok(Control::Continue);
+        }
+        let sql = std::mem::take(&mut self.buffer);
+        self.run_sql(&sql, out, err)
+    }
+
+    /// Run every statement of `sql` until one fails.
+    fn run_sql(
+        &mut self,
+        sql: &str,
+        out: &mut impl Write,
+        err: &mut impl Write,
+    ) -> io::Result<Control> {
+        let statements = match parse(sql) {
+            Ok(statements) => statements,
+            Err(error) => {
+                writeln!(err, "{}", error.render(sql))?;
+                return Ok(Control::Failed);
+            }
+        };
+        for statement in &statements {
+            let start = Instant::now();
+            match self.run_statement(statement) {
+                Ok(output) => write!(out, "{output}")?,
+                Err(error) => {
+                    match error.downcast_ref::<SqlError>() {
+                        Some(error) => writeln!(err, "{}", error.render(sql))?,
+                        None => writeln!(err, "error: {error:#}")?,
+                    }
+                    return Ok(Control::Failed);
+                }
+            }
+            if self.timing {
+                let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+                writeln!(out, "Time: {elapsed:.3} ms")?;
+            }
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Run `statement` and return what to print.
+    fn run_statement(&mut self, statement: &Statement) -> anyhow::Result<String> {
+        // Declared first to be dropped last, once the executor is done with the temp space.
+        let _temp_statement = self.temp.begin_statement();
+        let plan = Binder::new(self.catalog).bind(statement)?;
+        if is_ddl(&plan) {
+            execute_ddl(self.catalog, self.temp, &plan, &AnalyzeConfig::new())?;
+            self.catalog.free_dropped()?;
+            commit(self.catalog.buffer_pool())?;
+            return Ok(format!("{}\n", command_tag(&plan)));
+        }
+
+        let plan = Optimizer::new(self.catalog).optimize(plan)?;
+        let ctx = ExecutorContext::new(self.catalog, self.temp);
+        let mut executor = build_executor(&ctx, &plan)?;
+        executor.init()?;
+        let mut rows = Vec::new();
+        while let Some(tuple) = executor.next()? {
+            rows.push(tuple.into_values());
+        }
+        if !matches!(
+            plan,
+            LogicalPlan::Insert { .. } | LogicalPlan::Update { .. } | LogicalPlan::Delete { .. }
+        ) {
+            return Ok(format_table(executor.schema(), &rows));
+        }
+        self.catalog.free_dropped()?;
+        commit(self.catalog.buffer_pool())?;
+        let count = rows
+            .first()
+            .and_then(|row| row.first())
+            .and_then(|count| count.as_i64())
+            .unwrap_or(0);
+        Ok(format!("{} {count}\n", command_tag(&plan)))
+    }
+
+    fn meta_command(&mut self, line: &str, out: &mut impl Write) -> anyhow::Result<Control> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next();
+        if words.next().is_some() {
+            anyhow::bail!("too many arguments to {command}");
+        }
+        match (command, argument) {
+            (".help", None) => write!(out, "{HELP}")?,
+            (".quit" | ".exit", None) => return Ok(Control::Quit),
+            (".tables", None) => {
+                for table in self.user_tables() {
+                    writeln!(out, "{}", table.name)?;
+                }
+            }
+            (".schema", table) => {
+                let tables = match table {
+                    Some(name) => vec![self.user_table(name)?],
+                    None => self.user_tables(),
+                };
+                for table in tables {
+                    writeln!(out, "{}", create_table_sql(&table))?;
+                    for index in self.catalog.table_indexes(table.oid) {
+                        writeln!(out, "{}", create_index_sql(&table, &index))?;
+                    }
+                }
+            }
+            (".indexes", table) => {
+                let tables = match table {
+                    Some(name) => vec![self.user_table(name)?],
+                    None => self.user_tables(),
+                };
+                for table in tables {
+                    for index in self.catalog.table_indexes(table.oid) {
+                        let kind = match index.kind {
+                            IndexKind::BPlusTree => "btree",
+                            IndexKind::Hash => "hash",
+                        };
+                        let unique = if index.unique { ", unique" } else { "" };
+                        let columns = index_columns(&table, &index);
+                        writeln!(
+                            out,
+                            "{} on {} ({columns}) using {kind}{unique}",
+                            index.name, table.name
+                        )?;
+                    }
+                }
+            }
+            (".stats", None) => {
+                write_stats(out, "database", self.catalog.buffer_pool().stats())?;
+                write_stats(out, "temporary", self.temp.stats())?;
+            }
+            (".timing", None) => {
+                writeln!(out, "Timing is {}.", if self.timing { "on" } else { "off" })?
+            }
+            (".timing", Some(setting)) => {
+                self.timing = match setting {
+                    "on" => true,
+                    "off" => false,
+                    _ => anyhow::bail!("expected on or off, got \"{setting}\""),
+                };
+            }
+            (".help" | ".quit" | ".exit" | ".tables" | ".stats", Some(_)) => {
+                anyhow::bail!("{command} takes no arguments")
+            }
+            _ => anyhow::bail!("unknown command \"{command}\", try .help"),
+        }
+        Ok(Control::Continue)
+    }
+
+    fn user_tables(&self) -> Vec<std::sync::Arc<TableInfo>> {
+        let mut tables: Vec<_> = self
+            .catalog
+            .tables()
+            .into_iter()
+            .filter(|table| !table.is_system())
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        tables
+    }
+
+    fn user_table(&self, name: &str) -> anyhow::Result<std::sync::Arc<TableInfo>> {
+        match self.catalog.table(name) {
+            Some(table) if !table.is_system() => Ok(table),
+            _ => anyhow::bail!("table \"{name}\" does not exist"),
+        }
+    }
+}
+
+/// Whether `sql` ends with a semicolon that ends a statement. Input that does not tokenize
+/// because a string or a comment is still open continues on the next line; any other error is
+/// reported when the statement runs.
+fn ends_statement(sql: &str) -> bool {
+    match tokenize(sql) {
+        Ok(tokens) => tokens
+            .iter()
+            .rev()
+            .find(|token| token.kind != TokenKind::Eof)
+            .is_some_and(|token| token.kind == TokenKind::Semicolon),
+        Err(error) => !error.message.starts_with("unterminated"),
+    }
+}
+
+/// What is printed after a statement without rows, as in PostgreSQL.
+fn command_tag(plan: &LogicalPlan) -> &'static str {
+    match plan {
+        LogicalPlan::Insert { .. } => "INSERT",
+        LogicalPlan::Update { .. } => "UPDATE",
+        LogicalPlan::Delete { .. } => "DELETE",
+        LogicalPlan::CreateTable { .. } => "CREATE TABLE",
+        LogicalPlan::DropTable { .. } => "DROP TABLE",
+        LogicalPlan::CreateIndex { .. } => "CREATE INDEX",
+        LogicalPlan::DropIndex { .. } => "DROP INDEX",
+        LogicalPlan::Analyze { .. } => "ANALYZE",
+        _ => "SELECT",
+    }
+}
+
+fn create_table_sql(table: &TableInfo) -> String {
+    let columns: Vec<String> = table
+        .schema
+        .columns()
+        .iter()
+        .map(|column| {
+            let not_null = if column.nullable { "" } else { " NOT NULL" };
+            format!("{} {}{not_null}", column.name, column.data_type)
+        })
+        .collect();
+    format!("CREATE TABLE {} ({});", table.name, columns.join(", "))
+}
+
+fn create_index_sql(table: &TableInfo, index: &IndexInfo) -> String {
+    let unique = if index.unique { "UNIQUE " } else { "" };
+    let using = match index.kind {
+        IndexKind::BPlusTree => "",
+        IndexKind::Hash => " USING hash",
+    };
+    format!(
+        "CREATE {unique}INDEX {} ON {}{using} ({});",
+        index.name,
+        table.name,
+        index_columns(table, index)
+    )
+}
+
+/// The names of the columns of `index`, separated by commas.
+fn index_columns(table: &TableInfo, index: &IndexInfo) -> String {
+    let names: Vec<&str> = index
+        .columns
+        .iter()
+        .map(|&position| table.schema.column(position).name.as_str())
+        .collect();
+    names.join(", ")
+}
+
+fn write_stats(out: &mut impl Write, pool: &str, stats: BufferPoolStats) -> io::Result<()> {
+    let fetches = stats.hits + stats.misses;
+    let hit_ratio = if fetches == 0 {
+        0.0
+    } else {
+        stats.hits as f64 * 100.0 / fetches as f64
+    };
+    writeln!(
+        out,
+        "{pool} buffer pool: hits={} misses={} reads={} writes={} hit ratio={hit_ratio:.1}%",
+        stats.hits, stats.misses, stats.reads, stats.writes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, TempBufferPool},
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_shell() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("test.db");
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &path).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let catalog = Catalog::create(&bpm).unwrap();
+        let temp_disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, TempDiskManager::path_for(&path)).unwrap();
+        let temp = TempBufferPool::new(4, temp_disk_manager);
+        let mut shell = Shell::new(&catalog, &temp);
+        let mut run = |input: &str| {
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let ok = shell
+                .run_script(input.as_bytes(), &mut out, &mut err)
+                .unwrap();
+            (
+                ok,
+                String::from_utf8(out).unwrap(),
+                String::from_utf8(err).unwrap(),
+            )
+        };
+
+        let script = "
+            CREATE TABLE users (
+                id BIGINT NOT NULL,
+                name TEXT  -- a comment; not the end
+            );
+            CREATE UNIQUE INDEX users_id ON users (id);
+            INSERT INTO users VALUES (2, 'b;c'), (1, 'it''s
+            two lines'), (3, NULL);
+            SELECT * FROM users ORDER BY id; UPDATE users SET name = 'x' WHERE id = 3;
+        ";
+        assert_eq!(
+            run(script),
+            (
+                true,
+                concat!(
+                    "CREATE TABLE\n",
+                    "CREATE INDEX\n",
+                    "INSERT 3\n",
+                    " id | name\n",
+                    "----+-----------------------\n",
+                    "  1 | it's\n",
+                    "    |             two lines\n",
+                    "  2 | b;c\n",
+                    "  3 | NULL\n",
+                    "(3 rows)\n",
+                    "UPDATE 1\n",
+                )
+                .to_string(),
+                String::new()
+            )
+        );
+
+        let (ok, out, _) = run(".tables\n.schema users\n.indexes\n.stats\n");
+        assert!(ok);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[..4],
+            [
+                "users",
+                "CREATE TABLE users (id INT64 NOT NULL, name VARCHAR);",
+                "CREATE UNIQUE INDEX users_id ON users (id);",
+                "users_id on users (id) using btree, unique",
+            ]
+        );
+        assert!(lines[4].starts_with("database buffer pool: hits="));
+        assert!(lines[5].starts_with("temporary buffer pool: hits="));
+
+        // A script stops at the first error.
+        let (ok, out, err) = run("SELECT nope FROM users;\nSELECT 1;\n");
+        assert!(!ok);
+        assert_eq!(out, "");
+        assert!(err.starts_with("error at line 1, column 8: column \"nope\" does not exist"));
+        let (ok, _, err) = run(".timing maybe\n");
+        assert!(!ok);
+        assert_eq!(err, "error: expected on or off, got \"maybe\"\n");
+
+        // The last statement does not need a semicolon, and .quit stops the script.
+        let (ok, out, _) = run(".timing on\nSELECT count(*) FROM users");
+        assert!(ok);
+        assert!(out.starts_with(" count\n-------\n     3\n(1 row)\nTime: "));
+        assert_eq!(
+            run(".quit\nSELECT nope;"),
+            (true, String::new(), String::new())
+        );
+    }
+
+    #[test]
+    fn test_ends_statement() {
+        assert!(ends_statement("SELECT 1;\n"));
+        assert!(ends_statement("SELECT 1; -- done\n"));
+        assert!(!ends_statement("SELECT 1\n"));
+        assert!(!ends_statement("SELECT ';\n"));
+        assert!(!ends_statement("SELECT 1 /* ; \n"));
+        assert!(ends_statement("SELECT 1 # 2;\n"));
+    }
+}
@@ -0,0 +1,132 @@
+//! Results printed as an aligned table, the way `psql` does:
+//!
+//! ```text
+//!  id | name
+//! ----+-------
+//!   1 | alice
+//!   2 | NULL
+//! (2 rows)
+//! ```
+//!
+//! Numbers are right-aligned and everything else left-aligned. A value with line breaks spans
+//! several lines of its row.
+
+use crate::{catalog::schema::Schema, types::value::Value};
+
+/// Format `rows`, which have the columns of `schema`, followed by their count.
+pub fn format_table(schema: &Schema, rows: &[Vec<Value>]) -> String {
+    let columns = schema.columns();
+    let cells: Vec<Vec<Vec<String>>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.to_string().lines().map(str::to_string).collect())
+                .collect()
+        })
+        .collect();
+    let mut widths: Vec<usize> = columns.iter().map(|column| width(&column.name)).collect();
+    for row in &cells {
+        for (i, lines) in row.iter().enumerate() {
+            for line in lines {
+                widths[i] = widths[i].max(width(line));
+            }
+        }
+    }
+    let right_aligned: Vec<bool> = columns
+        .iter()
+        .map(|column| column.data_type.is_numeric())
+        .collect();
+
+    let mut out = String::new();
+    let header: Vec<String> = columns
+        .iter()
+        .zip(&widths)
+        .map(|(column, &width)| pad(&column.name, width, false))
+        .collect();
+    push_line(&mut out, &header);
+    let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width + 2)).collect();
+    out.push_str(rule.join("+").trim_end());
+    out.push('\n');
+    for row in &cells {
+        let height = row.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        for line in 0..height {
+            let fields: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, lines)| {
+                    let text = lines.get(line).map_or("", String::as_str);
+                    pad(text, widths[i], right_aligned[i])
+                })
+                .collect();
+            push_line(&mut out, &fields);
+        }
+    }
+    out.push_str(&row_count(rows.len()));
+    out.push('\n');
+    out
+}
+
+/// `(1 row)` or `(n rows)`.
+pub fn row_count(rows: usize) -> String {
+    if rows == 1 {
+        "(1 row)".to_string()
+    } else {
+        format!("({rows} rows)")
+    }
+}
+
+fn push_line(out: &mut String, fields: &[String]) {
+    let line: Vec<String> = fields.iter().map(|field| format!(" {field} ")).collect();
+    out.push_str(line.join("|").trim_end());
+    out.push('\n');
+}
+
+fn pad(text: &str, width: usize, right: bool) -> String {
+    let fill = " ".repeat(width - self::width(text));
+    if right {
+        fill + text
+    } else {
+        text.to_string() + &fill
+    }
+}
+
+/// The number of columns `text` takes up, counting every character as one.
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{catalog::schema::Column, types::data_type::DataType};
+
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        let schema = Schema::new(vec![
+            Column::new("id", DataType::Int64),
+            Column::new("name", DataType::Varchar(None)),
+        ]);
+        let rows = vec![
+            vec![Value::Int64(1), Value::Varchar("alice".to_string())],
+            vec![Value::Int64(100), Value::Null],
+            vec![Value::Int64(7), Value::Varchar("two\nlines".to_string())],
+        ];
+        assert_eq!(
+            format_table(&schema, &rows),
+            concat!(
+                " id  | name\n",
+                "-----+-------\n",
+                "   1 | alice\n",
+                " 100 | NULL\n",
+                "   7 | two\n",
+                "     | lines\n",
+                "(3 rows)\n",
+            )
+        );
+        assert_eq!(
+            format_table(&schema, &[]),
+            " id | name\n----+------\n(0 rows)\n"
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! The syntax tree of a SQL statement, as written. Names are not resolved and literals are not
+//! typed yet; every node keeps the span of the text it was parsed from.
+
+use crate::{storage::index::index::IndexKind, types::data_type::DataType};
+
+use super::error::Span;
+
+/// An identifier. Unquoted identifiers are folded to lower case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident {
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Select(Box<Select>),
+    Insert(Insert),
+    Update(Update),
+    Delete(Delete),
+    CreateTable(CreateTable),
+    DropTable(DropTable),
+    CreateIndex(CreateIndex),
+    DropIndex(DropIndex),
+    Analyze(Analyze),
+    Explain(Explain),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub distinct: bool,
+    pub projection: Vec<SelectItem>,
+    pub from: Option<TableRef>,
+    pub selection: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    /// The sets of `group_by` to group by, if GROUPING SETS, ROLLUP or CUBE expanded it into
+    /// several. Each set holds positions in `group_by`.
+    pub grouping_sets: Option<Vec<Vec<usize>>>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderByExpr>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    /// `*`
+    Wildcard(Span),
+    /// `table.*`
+    QualifiedWildcard(Ident),
+    Expr {
+        expr: Expr,
+        alias: Option<Ident>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+    /// `CROSS JOIN` or a comma in the FROM list.
+    Cross,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableRef {
+    Table {
+        name: Ident,
+        alias: Option<Ident>,
+    },
+    Join {
+        left: Box<TableRef>,
+        right: Box<TableRef>,
+        kind: JoinKind,
+        /// Absent only for cross joins.
+        on: Option<Expr>,
+        span: Span,
+    },
+}
+
+impl TableRef {
+    pub fn span(&self) -> Span {
+        match self {
+            TableRef::Table { name, alias } => alias
+                .as_ref()
+                .map_or(name.span, |alias| name.span.to(alias.span)),
+            TableRef::Join { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByExpr {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expr>>),
+    Select(Box<Select>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    pub table: Ident,
+    /// The target columns, or empty for all columns in schema order.
+    pub columns: Vec<Ident>,
+    pub source: InsertSource,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub column: Ident,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub table: Ident,
+    pub assignments: Vec<Assignment>,
+    pub selection: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    pub table: Ident,
+    pub selection: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: Ident,
+    pub data_type: DataType,
+    pub not_null: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    pub name: Ident,
+    pub columns: Vec<ColumnDef>,
+    pub if_not_exists: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropTable {
+    pub name: Ident,
+    pub if_exists: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateIndex {
+    pub name: Ident,
+    pub table: Ident,
+    /// The indexed columns, in key order.
+    pub columns: Vec<Ident>,
+    pub unique: bool,
+    /// `USING btree` or `USING hash`, a B+ tree by default.
+    pub kind: IndexKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropIndex {
+    pub name: Ident,
+    pub if_exists: bool,
+    pub span: Span,
+}
+
+/// `ANALYZE [table]`, which analyzes every table if none is named.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analyze {
+    pub table: Option<Ident>,
+    pub span: Span,
+}
+
+/// `EXPLAIN [ANALYZE] statement` or `EXPLAIN (option, ...) statement`, with the options
+/// `ANALYZE [TRUE | FALSE]` and `FORMAT {TEXT | JSON}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explain {
+    pub statement: Box<Statement>,
+    /// Whether to run the statement and report what each operator did.
+    pub analyze: bool,
+    pub format: ExplainFormat,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    /// An indented line for every operator.
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Literal),
+    /// A parameter of a prepared statement, `$1`, `?` or `:name`, by its position from 0.
+    Parameter(usize),
+    /// A possibly qualified column reference.
+    Column {
+        table: Option<Ident>,
+        column: Ident,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    /// A function call such as `lower(name)`, `count(*)` or `count(DISTINCT x)`.
+    Function {
+        name: Ident,
+        args: Vec<Expr>,
+        /// `f(*)`, whose `args` are empty.
+        star: bool,
+        distinct: bool,
+    },
+    /// `CAST(expr AS type)`, or a typed literal such as `DATE '2024-01-01'`.
+    Cast {
+        expr: Box<Expr>,
+        data_type: DataType,
+    },
+    /// `EXISTS (SELECT ...)`
+    Exists(Box<Select>),
+    /// `expr [NOT] IN (SELECT ...)`
+    InSubquery {
+        expr: Box<Expr>,
+        subquery: Box<Select>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Boolean(bool),
+    /// A number as written; the binder decides whether it is an integer, a decimal or a
+    /// float.
+    Number(String),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Not,
+    Minus,
+    Plus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+}
+
+impl BinaryOp {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq
+                | BinaryOp::NotEq
+                | BinaryOp::Lt
+                | BinaryOp::LtEq
+                | BinaryOp::Gt
+                | BinaryOp::GtEq
+        )
+    }
+
+    /// The comparison that gives the same result with the operands swapped, like `>` for `<`.
+    /// `=` and `<>` are their own, and other operators are returned as they are.
+    pub fn commuted(&self) -> BinaryOp {
+        match self {
+            BinaryOp::Lt => BinaryOp::Gt,
+            BinaryOp::LtEq => BinaryOp::GtEq,
+            BinaryOp::Gt => BinaryOp::Lt,
+            BinaryOp::GtEq => BinaryOp::LtEq,
+            op => *op,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Or => "OR",
+            BinaryOp::And => "AND",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Concat => "||",
+        }
+    }
+}
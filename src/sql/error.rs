@@ -0,0 +1,93 @@
+use std::fmt;
+
+/// A byte range of the query text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both spans.
+    pub fn to(self, other: Span) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// The 1-based line and column, counted in characters, where the span starts in `sql`.
+    pub fn line_col(&self, sql: &str) -> (usize, usize) {
+        let before = &sql[..self.start.min(sql.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+/// An error in a query, pointing at the part of the query text that caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl SqlError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Render the error with its position and the offending line of `sql`, underlined:
+    ///
+    /// ```text
+    /// error at line 1, column 8: unexpected FORM, expected an expression
+    /// SELECT FORM t
+    ///        ^^^^
+    /// ```
+    pub fn render(&self, sql: &str) -> String {
+        let (line, column) = self.span.line_col(sql);
+        let text = sql.lines().nth(line - 1).unwrap_or("");
+        let line_start = sql[..self.span.start.min(sql.len())]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let underlined = sql
+            .get(self.span.start..self.span.end.min(line_start + text.len()))
+            .map_or(1, |s| s.chars().count().max(1));
+        format!(
+            "error at line {line}, column {column}: {}\n{text}\n{}{}",
+            self.message,
+            " ".repeat(column - 1),
+            "^".repeat(underlined)
+        )
+    }
+}
+
+impl fmt::Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SqlError {}
+
+pub type SqlResult<T> = Result<T, SqlError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let sql = "SELECT a\nFROM t WHERE b = ?";
+        let error = SqlError::new("unexpected character '?'", Span::new(26, 27));
+        assert_eq!(Span::new(26, 27).line_col(sql), (2, 18));
+        assert_eq!(
+            error.render(sql),
+            "error at line 2, column 18: unexpected character '?'\nFROM t WHERE b = ?\n                 ^"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Splits query text into tokens.
+//!
+//! Keywords are not distinguished from identifiers here: an unquoted word becomes
+//! [`TokenKind::Word`] and the parser decides from its position whether it is a keyword.
+//! Quoted identifiers keep their case and are never keywords.
+
+use super::error::{Span, SqlError, SqlResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// An unquoted identifier or keyword, as written.
+    Word(String),
+    /// A `"quoted"` identifier.
+    QuotedIdent(String),
+    /// A numeric literal, kept as written so that the binder can pick its type.
+    Number(String),
+    /// A `'string'` literal with `''` unescaped.
+    String(String),
+    /// A parameter of a prepared statement, `?`, `$1` or `:name`, as written.
+    Parameter(String),
+    Comma,
+    Semicolon,
+    LParen,
+    RParen,
+    Dot,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Concat,
+    Eof,
+}
+
+impl TokenKind {
+    /// How the token is described in error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Word(word) => word.to_ascii_uppercase(),
+            TokenKind::QuotedIdent(ident) => format!("\"{ident}\""),
+            TokenKind::Number(number) => number.clone(),
+            TokenKind::String(_) => "a string literal".to_string(),
+            TokenKind::Parameter(parameter) => parameter.clone(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::Semicolon => "';'".to_string(),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Dot => "'.'".to_string(),
+            TokenKind::Star => "'*'".to_string(),
+            TokenKind::Plus => "'+'".to_string(),
+            TokenKind::Minus => "'-'".to_string(),
+            TokenKind::Slash => "'/'".to_string(),
+            TokenKind::Percent => "'%'".to_string(),
+            TokenKind::Eq => "'='".to_string(),
+            TokenKind::NotEq => "'<>'".to_string(),
+            TokenKind::Lt => "'<'".to_string(),
+            TokenKind::LtEq => "'<='".to_string(),
+            TokenKind::Gt => "'>'".to_string(),
+            TokenKind::GtEq => "'>='".to_string(),
+            TokenKind::Concat => "'||'".to_string(),
+            TokenKind::Eof => "end of input".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Tokenize `sql`. The last token is always [`TokenKind::Eof`].
+pub fn tokenize(sql: &str) -> SqlResult<Vec<Token>> {
+    let mut lexer = Lexer { sql, pos: 0 };
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token()?;
+        let is_eof = token.kind == TokenKind::Eof;
+        tokens.push(token);
+        if is_eof {
+            return Ok(tokens);
+        }
+    }
+}
+
+struct Lexer<'s> {
+    sql: &'s str,
+    /// Byte offset of the next character.
+    pos: usize,
+}
+
+impl Lexer<'_> {
+    fn peek(&self) -> Option<char> {
+        self.sql[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.sql[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(&pred) {
+            self.bump();
+        }
+        &self.sql[start..self.pos]
+    }
+
+    /// Skip whitespace, `-- line` comments and `/* block */` comments.
+    fn skip_trivia(&mut self) -> SqlResult<()> {
+        loop {
+            match (self.peek(), self.peek_second()) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('-'), Some('-')) => {
+                    self.take_while(|c| c != '\n');
+                }
+                (Some('/'), Some('*')) => {
+                    let start = self.pos;
+                    self.pos += 2;
+                    let Some(len) = self.sql[self.pos..].find("*/") else {
+                        return Err(SqlError::new(
+                            "unterminated block comment",
+                            Span::new(start, start + 2),
+                        ));
+                    };
+                    self.pos += len + 2;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> SqlResult<Token> {
+        self.skip_trivia()?;
+        let start = self.pos;
+        let Some(c) = self.bump() else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                span: Span::new(start, start),
+            });
+        };
+        let kind = match c {
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '*' => TokenKind::Star,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '=' => TokenKind::Eq,
+            '<' => match self.peek() {
+                Some('=') => {
+                    self.bump();
+                    TokenKind::LtEq
+                }
+                Some('>') => {
+                    self.bump();
+                    TokenKind::NotEq
+                }
+                _ => TokenKind::Lt,
+            },
+            '>' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                    TokenKind::GtEq
+                } else {
+                    TokenKind::Gt
+                }
+            }
+            '!' if self.peek() == Some('=') => {
+                self.bump();
+                TokenKind::NotEq
+            }
+            '|' if self.peek() == Some('|') => {
+                self.bump();
+                TokenKind::Concat
+            }
+            '.' if self.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                self.pos = start;
+                self.number()
+            }
+            '.' => TokenKind::Dot,
+            '?' => TokenKind::Parameter("?".to_string()),
+            '$' if self.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                self.take_while(|c| c.is_ascii_digit());
+                TokenKind::Parameter(self.sql[start..self.pos].to_string())
+            }
+            ':' if self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') => {
+                self.take_while(|c| c.is_alphanumeric() || c == '_');
+                TokenKind::Parameter(self.sql[start..self.pos].to_string())
+            }
+            '\'' => TokenKind::String(self.quoted(start, '\'')?),
+            '"' => TokenKind::QuotedIdent(self.quoted(start, '"')?),
+            c if c.is_ascii_digit() => {
+                self.pos = start;
+                self.number()
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                self.take_while(|c| c.is_alphanumeric() || c == '_');
+                TokenKind::Word(self.sql[start..self.pos].to_string())
+            }
+            c => {
+                return Err(SqlError::new(
+                    format!("unexpected character '{c}'"),
+                    Span::new(start, self.pos),
+                ))
+            }
+        };
+
+        Ok(Token {
+            kind,
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    /// The body of a literal or identifier quoted with `quote`, in which a doubled quote
+    /// stands for itself. The opening quote has been consumed.
+    fn quoted(&mut self, start: usize, quote: char) -> SqlResult<String> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => {
+                    if self.peek() != Some(quote) {
+                        return Ok(text);
+                    }
+                    self.bump();
+                    text.push(quote);
+                }
+                Some(c) => text.push(c),
+                None => {
+                    let what = if quote == '\'' {
+                        "string literal"
+                    } else {
+                        "quoted identifier"
+                    };
+                    return Err(SqlError::new(
+                        format!("unterminated {what}"),
+                        Span::new(start, self.pos),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// `digits [. digits] [e [+-] digits]`, where either side of the point may be empty.
+    fn number(&mut self) -> TokenKind {
+        let start = self.pos;
+        self.take_while(|c| c.is_ascii_digit());
+        if self.peek() == Some('.') {
+            self.bump();
+            self.take_while(|c| c.is_ascii_digit());
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let before_exponent = self.pos;
+            self.bump();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.bump();
+            }
+            if self.take_while(|c| c.is_ascii_digit()).is_empty() {
+                // Not an exponent after all, as in `1e` or `1east`.
+                self.pos = before_exponent;
+            }
+        }
+        TokenKind::Number(self.sql[start..self.pos].to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<TokenKind> {
+        tokenize(sql)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("SELECT t.\"Name\", 'it''s' || x FROM t -- trailing\nWHERE a<>1.5e3/*c*/AND b != .5;"),
+            vec![
+                Word("SELECT".into()),
+                Word("t".into()),
+                Dot,
+                QuotedIdent("Name".into()),
+                Comma,
+                String("it's".into()),
+                Concat,
+                Word("x".into()),
+                Word("FROM".into()),
+                Word("t".into()),
+                Word("WHERE".into()),
+                Word("a".into()),
+                NotEq,
+                Number("1.5e3".into()),
+                Word("AND".into()),
+                Word("b".into()),
+                NotEq,
+                Number(".5".into()),
+                Semicolon,
+                Eof,
+            ]
+        );
+
+        assert_eq!(
+            kinds("?, $12 :name_1"),
+            vec![
+                Parameter("?".into()),
+                Comma,
+                Parameter("$12".into()),
+                Parameter(":name_1".into()),
+                Eof,
+            ]
+        );
+
+        let tokens = tokenize("a <= 10").unwrap();
+        assert_eq!(tokens[1].span, Span::new(2, 4));
+        assert_eq!(tokens[2].span, Span::new(5, 7));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = tokenize("SELECT 'abc").unwrap_err();
+        assert_eq!(error.message, "unterminated string literal");
+        assert_eq!(error.span, Span::new(7, 11));
+
+        let error = tokenize("SELECT a # b").unwrap_err();
+        assert_eq!(error.span.line_col("SELECT a # b"), (1, 10));
+        assert!(tokenize("/* open").is_err());
+        assert_eq!(
+            tokenize("$x").unwrap_err().message,
+            "unexpected character '$'"
+        );
+    }
+}
@@ -0,0 +1,1564 @@
+//! A recursive-descent parser from tokens to the [`ast`](super::ast).
+//!
+//! Expressions are parsed by precedence, from loosest to tightest binding:
+//!
+//! | level | operators                                               |
+//! |-------|---------------------------------------------------------|
+//! | 1     | `OR`                                                    |
+//! | 2     | `AND`                                                   |
+//! | 3     | `NOT`                                                   |
+//! | 4     | comparisons, `IS [NOT] NULL`, `BETWEEN`, `IN`, `LIKE`   |
+//! | 5     | `+`, `-`, `\|\|`                                        |
+//! | 6     | `*`, `/`, `%`                                           |
+//! | 7     | unary `+` and `-`                                       |
+
+use crate::{storage::index::index::IndexKind, types::data_type::DataType};
+
+use super::{
+    ast::*,
+    error::{Span, SqlError, SqlResult},
+    lexer::{tokenize, Token, TokenKind},
+};
+
+/// Words that cannot be used as unquoted identifiers, because an alias could not be told
+/// apart from the clause that follows it.
+const RESERVED: &[&str] = &[
+    "all", "and", "as", "asc", "between", "by", "cast", "create", "cross", "delete", "desc",
+    "distinct", "drop", "false", "from", "full", "group", "having", "in", "index", "inner",
+    "insert", "into", "is", "join", "left", "like", "limit", "not", "null", "offset", "on", "or",
+    "order", "outer", "right", "select", "set", "table", "true", "update", "using", "values",
+    "where",
+];
+
+/// The most elements of a CUBE, which groups by 2^n sets.
+const MAX_CUBE_ELEMENTS: usize = 12;
+
+/// The expressions of a GROUP BY clause, and its grouping sets as positions in them if it has
+/// any.
+type GroupBy = (Vec<Expr>, Option<Vec<Vec<usize>>>);
+
+/// Parse every statement of `sql`, separated by semicolons.
+pub fn parse(sql: &str) -> SqlResult<Vec<Statement>> {
+    parse_with_parameters(sql).map(|(statements, _)| statements)
+}
+
+/// Parse every statement of `sql`, and return the names of its parameters by position, None
+/// for those only referred to by number. `$n` is the parameter at position `n`, `?` the one
+/// after the last position so far, and every `:name` of the same name the same parameter,
+/// after the last position so far when the name first appears.
+pub fn parse_with_parameters(sql: &str) -> SqlResult<(Vec<Statement>, Vec<Option<String>>)> {
+    let mut parser = Parser::new(sql)?;
+    let mut statements = Vec::new();
+    loop {
+        while parser.eat(&TokenKind::Semicolon) {}
+        if parser.peek().kind == TokenKind::Eof {
+            return Ok((statements, parser.parameters));
+        }
+        statements.push(parser.statement()?);
+        if parser.peek().kind != TokenKind::Eof {
+            parser.expect(&TokenKind::Semicolon, "';' or end of input")?;
+        }
+    }
+}
+
+/// Parse `sql`, which must hold exactly one statement.
+pub fn parse_statement(sql: &str) -> SqlResult<Statement> {
+    single_statement(parse(sql)?, sql)
+}
+
+/// Parse `sql`, which must hold exactly one statement, with the names of its parameters as
+/// [`parse_with_parameters`] returns them.
+pub fn parse_prepared(sql: &str) -> SqlResult<(Statement, Vec<Option<String>>)> {
+    let (statements, parameters) = parse_with_parameters(sql)?;
+    Ok((single_statement(statements, sql)?, parameters))
+}
+
+fn single_statement(mut statements: Vec<Statement>, sql: &str) -> SqlResult<Statement> {
+    match statements.len() {
+        1 => Ok(statements.remove(0)),
+        0 => Err(SqlError::new(
+            "empty query",
+            Span::new(sql.len(), sql.len()),
+        )),
+        _ => Err(SqlError::new(
+            "expected a single statement",
+            Span::new(0, sql.len()),
+        )),
+    }
+}
+
+/// The most parameters of a statement, as in PostgreSQL.
+const MAX_PARAMETERS: usize = 65535;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// End of the last consumed token, where the span of the node being parsed ends.
+    prev_end: usize,
+    /// The names of the parameters seen so far, by position.
+    parameters: Vec<Option<String>>,
+}
+
+impl Parser {
+    fn new(sql: &str) -> SqlResult<Self> {
+        Ok(Self {
+            tokens: tokenize(sql)?,
+            pos: 0,
+            prev_end: 0,
+            parameters: Vec::new(),
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn peek_nth(&self, n: usize) -> &Token {
+        &self.tokens[(self.pos + n).min(self.tokens.len() - 1)]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+            self.prev_end = token.span.end;
+        }
+        token
+    }
+
+    /// The span from `start` to the end of the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        Span::new(start.start, self.prev_end.max(start.end))
+    }
+
+    fn unexpected(&self, expected: &str) -> SqlError {
+        let token = self.peek();
+        SqlError::new(
+            format!("unexpected {}, expected {expected}", token.kind.describe()),
+            token.span,
+        )
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind, expected: &str) -> SqlResult<Span> {
+        if &self.peek().kind == kind {
+            Ok(self.next().span)
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn is_keyword_at(&self, n: usize, keyword: &str) -> bool {
+        matches!(&self.peek_nth(n).kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.is_keyword_at(0, keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> SqlResult<Span> {
+        if self.is_keyword(keyword) {
+            Ok(self.next().span)
+        } else {
+            Err(self.unexpected(&keyword.to_ascii_uppercase()))
+        }
+    }
+
+    /// Consume `keywords` if they all follow, in order.
+    fn eat_keywords(&mut self, keywords: &[&str]) -> bool {
+        if keywords
+            .iter()
+            .enumerate()
+            .all(|(i, keyword)| self.is_keyword_at(i, keyword))
+        {
+            self.pos += keywords.len() - 1;
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_ident(&self) -> bool {
+        match &self.peek().kind {
+            TokenKind::Word(word) => !RESERVED.contains(&word.to_ascii_lowercase().as_str()),
+            TokenKind::QuotedIdent(_) => true,
+            _ => false,
+        }
+    }
+
+    fn ident(&mut self) -> SqlResult<Ident> {
+        if !self.is_ident() {
+            return Err(self.unexpected("an identifier"));
+        }
+        let token = self.next();
+        let value = match token.kind {
+            TokenKind::Word(word) => word.to_ascii_lowercase(),
+            TokenKind::QuotedIdent(ident) => ident,
+            _ => unreachable!(),
+        };
+        Ok(Ident {
+            value,
+            span: token.span,
+        })
+    }
+
+    /// `[AS] alias`, where AS is needed only before reserved words.
+    fn alias(&mut self) -> SqlResult<Option<Ident>> {
+        if self.eat_keyword("as") {
+            return self.ident().map(Some);
+        }
+        if self.is_ident() {
+            return self.ident().map(Some);
+        }
+        Ok(None)
+    }
+
+    fn comma_separated<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> SqlResult<T>,
+    ) -> SqlResult<Vec<T>> {
+        let mut items = vec![f(self)?];
+        while self.eat(&TokenKind::Comma) {
+            items.push(f(self)?);
+        }
+        Ok(items)
+    }
+
+    fn parenthesized<T>(&mut self, f: impl FnMut(&mut Self) -> SqlResult<T>) -> SqlResult<Vec<T>> {
+        self.expect(&TokenKind::LParen, "'('")?;
+        let items = self.comma_separated(f)?;
+        self.expect(&TokenKind::RParen, "',' or ')'")?;
+        Ok(items)
+    }
+
+    fn statement(&mut self) -> SqlResult<Statement> {
+        let start = self.peek().span;
+        if self.is_keyword("select") {
+            return Ok(Statement::Select(Box::new(self.select()?)));
+        }
+        if self.eat_keyword("insert") {
+            return self.insert(start).map(Statement::Insert);
+        }
+        if self.eat_keyword("update") {
+            return self.update(start).map(Statement::Update);
+        }
+        if self.eat_keyword("delete") {
+            return self.delete(start).map(Statement::Delete);
+        }
+        if self.eat_keyword("create") {
+            let unique = self.eat_keyword("unique");
+            if !unique && self.eat_keyword("table") {
+                return self.create_table(start).map(Statement::CreateTable);
+            }
+            if self.eat_keyword("index") {
+                return self.create_index(start, unique).map(Statement::CreateIndex);
+            }
+            return Err(self.unexpected(if unique { "INDEX" } else { "TABLE or INDEX" }));
+        }
+        if self.eat_keyword("drop") {
+            if self.eat_keyword("table") {
+                let if_exists = self.eat_keywords(&["if", "exists"]);
+                let name = self.ident()?;
+                return Ok(Statement::DropTable(DropTable {
+                    name,
+                    if_exists,
+                    span: self.span_from(start),
+                }));
+            }
+            if self.eat_keyword("index") {
+                let if_exists = self.eat_keywords(&["if", "exists"]);
+                let name = self.ident()?;
+                return Ok(Statement::DropIndex(DropIndex {
+                    name,
+                    if_exists,
+                    span: self.span_from(start),
+                }));
+            }
+            return Err(self.unexpected("TABLE or INDEX"));
+        }
+        if self.eat_keyword("explain") {
+            return self.explain(start).map(Statement::Explain);
+        }
+        if self.eat_keyword("analyze") {
+            let table = if self.is_ident() {
+                Some(self.ident()?)
+            } else {
+                None
+            };
+            return Ok(Statement::Analyze(Analyze {
+                table,
+                span: self.span_from(start),
+            }));
+        }
+
+        Err(self.unexpected("a statement"))
+    }
+
+    fn select(&mut self) -> SqlResult<Select> {
+        let start = self.expect_keyword("select")?;
+        let distinct = self.eat_keyword("distinct");
+        if !distinct {
+            self.eat_keyword("all");
+        }
+        let projection = self.comma_separated(Self::select_item)?;
+
+        let from = if self.eat_keyword("from") {
+            Some(self.from()?)
+        } else {
+            None
+        };
+        let selection = if self.eat_keyword("where") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        let (group_by, grouping_sets) = if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            self.group_by()?
+        } else {
+            (Vec::new(), None)
+        };
+        let having = if self.eat_keyword("having") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        let order_by = if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            self.comma_separated(|p| {
+                let expr = p.expr()?;
+                let descending = if p.eat_keyword("desc") {
+                    true
+                } else {
+                    p.eat_keyword("asc");
+                    false
+                };
+                Ok(OrderByExpr { expr, descending })
+            })?
+        } else {
+            Vec::new()
+        };
+        let mut limit = None;
+        let mut offset = None;
+        // LIMIT and OFFSET may come in either order, as in PostgreSQL.
+        loop {
+            if limit.is_none() && self.eat_keyword("limit") {
+                limit = Some(self.expr()?);
+            } else if offset.is_none() && self.eat_keyword("offset") {
+                offset = Some(self.expr()?);
+            } else {
+                break;
+            }
+        }
+
+        Ok(Select {
+            distinct,
+            projection,
+            from,
+            selection,
+            group_by,
+            grouping_sets,
+            having,
+            order_by,
+            limit,
+            offset,
+            span: self.span_from(start),
+        })
+    }
+
+    /// The items after GROUP BY. Plain expressions group by themselves, GROUPING SETS by each of
+    /// its sets in turn, `ROLLUP (a, b)` by `(a, b)`, `(a)` and `()`, and `CUBE (a, b)` by every
+    /// subset of `(a, b)`. Several items group by every combination of their sets.
+    fn group_by(&mut self) -> SqlResult<GroupBy> {
+        let mut group_by = Vec::new();
+        let mut sets: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut expanded = false;
+        for item in self.comma_separated(|p| p.grouping_item(&mut group_by))? {
+            expanded |= item.len() != 1;
+            sets = sets
+                .iter()
+                .flat_map(|set| {
+                    item.iter()
+                        .map(move |item_set| [set.as_slice(), item_set].concat())
+                })
+                .collect();
+        }
+        Ok((group_by, expanded.then_some(sets)))
+    }
+
+    /// The sets of one GROUP BY item, as positions of the expressions it adds to `group_by`.
+    fn grouping_item(&mut self, group_by: &mut Vec<Expr>) -> SqlResult<Vec<Vec<usize>>> {
+        let mut push = |exprs: Vec<Expr>| -> Vec<usize> {
+            let start = group_by.len();
+            group_by.extend(exprs);
+            (start..group_by.len()).collect()
+        };
+        if self.eat_keywords(&["grouping", "sets"]) {
+            let sets = self.parenthesized(Self::grouping_set)?;
+            return Ok(sets.into_iter().map(push).collect());
+        }
+        let is_rollup = self.is_keyword("rollup");
+        if (is_rollup || self.is_keyword("cube")) && self.peek_nth(1).kind == TokenKind::LParen {
+            let span = self.next().span;
+            let elements: Vec<Vec<usize>> = self
+                .parenthesized(Self::grouping_set)?
+                .into_iter()
+                .map(push)
+                .collect();
+            if is_rollup {
+                return Ok((0..=elements.len())
+                    .rev()
+                    .map(|n| elements[..n].concat())
+                    .collect());
+            }
+            if elements.len() > MAX_CUBE_ELEMENTS {
+                return Err(SqlError::new(
+                    format!("CUBE is limited to {MAX_CUBE_ELEMENTS} elements"),
+                    span,
+                ));
+            }
+            return Ok((0..1usize << elements.len())
+                .rev()
+                .map(|mask| {
+                    (0..elements.len())
+                        .filter(|i| mask & (1 << (elements.len() - 1 - i)) != 0)
+                        .flat_map(|i| elements[i].clone())
+                        .collect()
+                })
+                .collect());
+        }
+        let expr = self.expr()?;
+        Ok(vec![push(vec![expr])])
+    }
+
+    /// `(a, b)`, `()` or a single expression.
+    fn grouping_set(&mut self) -> SqlResult<Vec<Expr>> {
+        if self.peek().kind == TokenKind::LParen && self.peek_nth(1).kind == TokenKind::RParen {
+            self.next();
+            self.next();
+            return Ok(Vec::new());
+        }
+        if self.peek().kind == TokenKind::LParen {
+            return self.parenthesized(Self::expr);
+        }
+        Ok(vec![self.expr()?])
+    }
+
+    fn select_item(&mut self) -> SqlResult<SelectItem> {
+        if self.peek().kind == TokenKind::Star {
+            return Ok(SelectItem::Wildcard(self.next().span));
+        }
+        if self.is_ident()
+            && self.peek_nth(1).kind == TokenKind::Dot
+            && self.peek_nth(2).kind == TokenKind::Star
+        {
+            let table = self.ident()?;
+            self.next();
+            self.next();
+            return Ok(SelectItem::QualifiedWildcard(table));
+        }
+        let expr = self.expr()?;
+        let alias = self.alias()?;
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+    /// A FROM list, in which commas are cross joins and joins associate to the left.
+    fn from(&mut self) -> SqlResult<TableRef> {
+        let mut from = self.joined_table()?;
+        while self.eat(&TokenKind::Comma) {
+            let right = self.joined_table()?;
+            let span = from.span().to(right.span());
+            from = TableRef::Join {
+                left: Box::new(from),
+                right: Box::new(right),
+                kind: JoinKind::Cross,
+                on: None,
+                span,
+            };
+        }
+        Ok(from)
+    }
+
+    fn joined_table(&mut self) -> SqlResult<TableRef> {
+        let mut left = self.table_factor()?;
+        loop {
+            let kind = if self.eat_keywords(&["cross", "join"]) {
+                JoinKind::Cross
+            } else if self.eat_keyword("join") || self.eat_keywords(&["inner", "join"]) {
+                JoinKind::Inner
+            } else if let Some(kind) = self.outer_join_kind()? {
+                kind
+            } else {
+                return Ok(left);
+            };
+            let right = self.table_factor()?;
+            let on = if kind == JoinKind::Cross {
+                None
+            } else {
+                self.expect_keyword("on")?;
+                Some(self.expr()?)
+            };
+            let span = self.span_from(left.span());
+            left = TableRef::Join {
+                left: Box::new(left),
+                right: Box::new(right),
+                kind,
+                on,
+                span,
+            };
+        }
+    }
+
+    /// `LEFT [OUTER] JOIN`, `RIGHT [OUTER] JOIN` or `FULL [OUTER] JOIN`.
+    fn outer_join_kind(&mut self) -> SqlResult<Option<JoinKind>> {
+        let kind = if self.eat_keyword("left") {
+            JoinKind::Left
+        } else if self.eat_keyword("right") {
+            JoinKind::Right
+        } else if self.eat_keyword("full") {
+            JoinKind::Full
+        } else {
+            return Ok(None);
+        };
+        self.eat_keyword("outer");
+        self.expect_keyword("join")?;
+        Ok(Some(kind))
+    }
+
+    fn table_factor(&mut self) -> SqlResult<TableRef> {
+        if self.eat(&TokenKind::LParen) {
+            let table = self.from()?;
+            self.expect(&TokenKind::RParen, "')'")?;
+            return Ok(table);
+        }
+        let name = self.ident()?;
+        let alias = self.alias()?;
+        Ok(TableRef::Table { name, alias })
+    }
+
+    fn insert(&mut self, start: Span) -> SqlResult<Insert> {
+        self.expect_keyword("into")?;
+        let table = self.ident()?;
+        let columns = if self.peek().kind == TokenKind::LParen {
+            self.parenthesized(Self::ident)?
+        } else {
+            Vec::new()
+        };
+        let source = if self.eat_keyword("values") {
+            InsertSource::Values(self.comma_separated(|p| p.parenthesized(Self::expr))?)
+        } else if self.is_keyword("select") {
+            InsertSource::Select(Box::new(self.select()?))
+        } else {
+            return Err(self.unexpected("VALUES or SELECT"));
+        };
+
+        Ok(Insert {
+            table,
+            columns,
+            source,
+            span: self.span_from(start),
+        })
+    }
+
+    fn update(&mut self, start: Span) -> SqlResult<Update> {
+        let table = self.ident()?;
+        self.expect_keyword("set")?;
+        let assignments = self.comma_separated(|p| {
+            let column = p.ident()?;
+            p.expect(&TokenKind::Eq, "'='")?;
+            Ok(Assignment {
+                column,
+                value: p.expr()?,
+            })
+        })?;
+        let selection = if self.eat_keyword("where") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(Update {
+            table,
+            assignments,
+            selection,
+            span: self.span_from(start),
+        })
+    }
+
+    fn delete(&mut self, start: Span) -> SqlResult<Delete> {
+        self.expect_keyword("from")?;
+        let table = self.ident()?;
+        let selection = if self.eat_keyword("where") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(Delete {
+            table,
+            selection,
+            span: self.span_from(start),
+        })
+    }
+
+    fn create_table(&mut self, start: Span) -> SqlResult<CreateTable> {
+        let if_not_exists = self.eat_keywords(&["if", "not", "exists"]);
+        let name = self.ident()?;
+        let columns = self.parenthesized(|p| {
+            let name = p.ident()?;
+            let data_type = p.data_type()?;
+            let mut not_null = false;
+            loop {
+                if p.eat_keywords(&["not", "null"]) {
+                    not_null = true;
+                } else if !p.eat_keyword("null") {
+                    break;
+                }
+            }
+            Ok(ColumnDef {
+                span: p.span_from(name.span),
+                name,
+                data_type,
+                not_null,
+            })
+        })?;
+
+        Ok(CreateTable {
+            name,
+            columns,
+            if_not_exists,
+            span: self.span_from(start),
+        })
+    }
+
+    fn explain(&mut self, start: Span) -> SqlResult<Explain> {
+        let mut analyze = false;
+        let mut format = ExplainFormat::Text;
+        if self.eat(&TokenKind::LParen) {
+            loop {
+                if self.eat_keyword("analyze") {
+                    analyze = !self.eat_keyword("false");
+                    if analyze {
+                        self.eat_keyword("true");
+                    }
+                } else if self.eat_keyword("format") {
+                    format = if self.eat_keyword("text") {
+                        ExplainFormat::Text
+                    } else if self.eat_keyword("json") {
+                        ExplainFormat::Json
+                    } else {
+                        return Err(self.unexpected("TEXT or JSON"));
+                    };
+                } else {
+                    return Err(self.unexpected("ANALYZE or FORMAT"));
+                }
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RParen, "',' or ')'")?;
+        } else {
+            analyze = self.eat_keyword("analyze");
+        }
+        let statement = self.statement()?;
+
+        Ok(Explain {
+            statement: Box::new(statement),
+            analyze,
+            format,
+            span: self.span_from(start),
+        })
+    }
+
+    fn create_index(&mut self, start: Span, unique: bool) -> SqlResult<CreateIndex> {
+        let name = self.ident()?;
+        self.expect_keyword("on")?;
+        let table = self.ident()?;
+        let kind = if self.eat_keyword("using") {
+            let method = self.ident()?;
+            match method.value.as_str() {
+                "btree" => IndexKind::BPlusTree,
+                "hash" => IndexKind::Hash,
+                _ => {
+                    return Err(SqlError::new(
+                        format!("unknown index method \"{}\"", method.value),
+                        method.span,
+                    ))
+                }
+            }
+        } else {
+            IndexKind::BPlusTree
+        };
+        let columns = self.parenthesized(Self::ident)?;
+
+        Ok(CreateIndex {
+            name,
+            table,
+            columns,
+            unique,
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    fn data_type(&mut self) -> SqlResult<DataType> {
+        let start = self.peek().span;
+        let TokenKind::Word(word) = &self.peek().kind else {
+            return Err(self.unexpected("a data type"));
+        };
+        let word = word.to_ascii_lowercase();
+        self.next();
+        let data_type = match word.as_str() {
+            "boolean" | "bool" => DataType::Boolean,
+            "tinyint" | "int8" => DataType::Int8,
+            "smallint" | "int16" => DataType::Int16,
+            "int" | "integer" | "int32" => DataType::Int32,
+            "bigint" | "int64" => DataType::Int64,
+            "float" | "real" | "float64" => DataType::Float64,
+            "double" => {
+                self.eat_keyword("precision");
+                DataType::Float64
+            }
+            "decimal" | "numeric" => {
+                let (precision, scale) = if self.peek().kind == TokenKind::LParen {
+                    let args = self.parenthesized(Self::type_argument)?;
+                    match args[..] {
+                        [precision] => (precision, 0),
+                        [precision, scale] => (precision, scale),
+                        _ => {
+                            return Err(SqlError::new(
+                                "DECIMAL takes a precision and a scale",
+                                self.span_from(start),
+                            ))
+                        }
+                    }
+                } else {
+                    (u32::from(DataType::MAX_DECIMAL_PRECISION), 0)
+                };
+                if precision == 0
+                    || precision > u32::from(DataType::MAX_DECIMAL_PRECISION)
+                    || scale > precision
+                {
+                    return Err(SqlError::new(
+                        format!(
+                            "DECIMAL precision must be between 1 and {} and at least the scale",
+                            DataType::MAX_DECIMAL_PRECISION
+                        ),
+                        self.span_from(start),
+                    ));
+                }
+                DataType::Decimal {
+                    precision: precision as u8,
+                    scale: scale as u8,
+                }
+            }
+            "varchar" | "character" | "char" | "text" => {
+                if word == "character" || word == "char" {
+                    self.expect_keyword("varying")?;
+                }
+                if word != "text" && self.peek().kind == TokenKind::LParen {
+                    self.next();
+                    let len = self.type_argument()?;
+                    self.expect(&TokenKind::RParen, "')'")?;
+                    DataType::Varchar(Some(len))
+                } else {
+                    DataType::Varchar(None)
+                }
+            }
+            "bytea" => DataType::Bytea,
+            "date" => DataType::Date,
+            "timestamp" => DataType::Timestamp,
+            _ => {
+                return Err(SqlError::new(
+                    format!("unknown data type \"{word}\""),
+                    start,
+                ))
+            }
+        };
+        Ok(data_type)
+    }
+
+    fn type_argument(&mut self) -> SqlResult<u32> {
+        let token = self.peek().clone();
+        if let TokenKind::Number(number) = &token.kind {
+            if let Ok(value) = number.parse() {
+                self.next();
+                return Ok(value);
+            }
+        }
+        Err(self.unexpected("a non-negative integer"))
+    }
+
+    fn expr(&mut self) -> SqlResult<Expr> {
+        self.or()
+    }
+
+    fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+        Expr {
+            span: left.span.to(right.span),
+            kind: ExprKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+        }
+    }
+
+    fn or(&mut self) -> SqlResult<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            let right = self.and()?;
+            expr = Self::binary(expr, BinaryOp::Or, right);
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> SqlResult<Expr> {
+        let mut expr = self.not()?;
+        while self.eat_keyword("and") {
+            let right = self.not()?;
+            expr = Self::binary(expr, BinaryOp::And, right);
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> SqlResult<Expr> {
+        let start = self.peek().span;
+        if self.eat_keyword("not") {
+            let expr = self.not()?;
+            return Ok(Expr {
+                span: start.to(expr.span),
+                kind: ExprKind::Unary {
+                    op: UnaryOp::Not,
+                    expr: Box::new(expr),
+                },
+            });
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> SqlResult<Expr> {
+        let expr = self.additive()?;
+        let start = expr.span;
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr {
+                kind: ExprKind::IsNull {
+                    expr: Box::new(expr),
+                    negated,
+                },
+                span: self.span_from(start),
+            });
+        }
+
+        let negated = self.is_keyword("not")
+            && ["between", "in", "like"]
+                .iter()
+                .any(|keyword| self.is_keyword_at(1, keyword));
+        if negated {
+            self.next();
+        }
+        if self.eat_keyword("between") {
+            let low = self.additive()?;
+            self.expect_keyword("and")?;
+            let high = self.additive()?;
+            return Ok(Expr {
+                kind: ExprKind::Between {
+                    expr: Box::new(expr),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated,
+                },
+                span: self.span_from(start),
+            });
+        }
+        if self.eat_keyword("in") {
+            if self.peek().kind == TokenKind::LParen && self.is_keyword_at(1, "select") {
+                let subquery = self.subquery()?;
+                return Ok(Expr {
+                    kind: ExprKind::InSubquery {
+                        expr: Box::new(expr),
+                        subquery: Box::new(subquery),
+                        negated,
+                    },
+                    span: self.span_from(start),
+                });
+            }
+            let list = self.parenthesized(Self::expr)?;
+            return Ok(Expr {
+                kind: ExprKind::InList {
+                    expr: Box::new(expr),
+                    list,
+                    negated,
+                },
+                span: self.span_from(start),
+            });
+        }
+        if self.eat_keyword("like") {
+            let pattern = self.additive()?;
+            return Ok(Expr {
+                kind: ExprKind::Like {
+                    expr: Box::new(expr),
+                    pattern: Box::new(pattern),
+                    negated,
+                },
+                span: self.span_from(start),
+            });
+        }
+
+        let op = match self.peek().kind {
+            TokenKind::Eq => BinaryOp::Eq,
+            TokenKind::NotEq => BinaryOp::NotEq,
+            TokenKind::Lt => BinaryOp::Lt,
+            TokenKind::LtEq => BinaryOp::LtEq,
+            TokenKind::Gt => BinaryOp::Gt,
+            TokenKind::GtEq => BinaryOp::GtEq,
+            _ => return Ok(expr),
+        };
+        self.next();
+        let right = self.additive()?;
+        Ok(Self::binary(expr, op, right))
+    }
+
+    fn additive(&mut self) -> SqlResult<Expr> {
+        let mut expr = self.multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Plus,
+                TokenKind::Minus => BinaryOp::Minus,
+                TokenKind::Concat => BinaryOp::Concat,
+                _ => return Ok(expr),
+            };
+            self.next();
+            let right = self.multiplicative()?;
+            expr = Self::binary(expr, op, right);
+        }
+    }
+
+    fn multiplicative(&mut self) -> SqlResult<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Multiply,
+                TokenKind::Slash => BinaryOp::Divide,
+                TokenKind::Percent => BinaryOp::Modulo,
+                _ => return Ok(expr),
+            };
+            self.next();
+            let right = self.unary()?;
+            expr = Self::binary(expr, op, right);
+        }
+    }
+
+    fn unary(&mut self) -> SqlResult<Expr> {
+        let op = match self.peek().kind {
+            TokenKind::Minus => UnaryOp::Minus,
+            TokenKind::Plus => UnaryOp::Plus,
+            _ => return self.primary(),
+        };
+        let start = self.next().span;
+        let expr = self.unary()?;
+        Ok(Expr {
+            span: start.to(expr.span),
+            kind: ExprKind::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+        })
+    }
+
+    /// The position of the parameter written as `parameter`.
+    fn parameter(&mut self, parameter: &str, span: Span) -> SqlResult<usize> {
+        let out_of_range = || {
+            SqlError::new(
+                format!("parameter {parameter} is out of range, the most is ${MAX_PARAMETERS}"),
+                span,
+            )
+        };
+        let position = if let Some(number) = parameter.strip_prefix('$') {
+            let number: usize = number.parse().unwrap_or(usize::MAX);
+            if number == 0 || number > MAX_PARAMETERS {
+                return Err(out_of_range());
+            }
+            if self.parameters.len() < number {
+                self.parameters.resize(number, None);
+            }
+            number - 1
+        } else if let Some(name) = parameter.strip_prefix(':') {
+            let named = self
+                .parameters
+                .iter()
+                .position(|other| other.as_deref() == Some(name));
+            named.unwrap_or_else(|| {
+                self.parameters.push(Some(name.to_string()));
+                self.parameters.len() - 1
+            })
+        } else {
+            self.parameters.push(None);
+            self.parameters.len() - 1
+        };
+        if self.parameters.len() > MAX_PARAMETERS {
+            return Err(out_of_range());
+        }
+        Ok(position)
+    }
+
+    fn primary(&mut self) -> SqlResult<Expr> {
+        let token = self.peek().clone();
+        let start = token.span;
+        let literal = |literal| Expr {
+            kind: ExprKind::Literal(literal),
+            span: start,
+        };
+        match token.kind {
+            TokenKind::Number(number) => {
+                self.next();
+                return Ok(literal(Literal::Number(number)));
+            }
+            TokenKind::String(s) => {
+                self.next();
+                return Ok(literal(Literal::String(s)));
+            }
+            TokenKind::Parameter(parameter) => {
+                self.next();
+                return Ok(Expr {
+                    kind: ExprKind::Parameter(self.parameter(&parameter, start)?),
+                    span: start,
+                });
+            }
+            TokenKind::LParen => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect(&TokenKind::RParen, "')'")?;
+                return Ok(Expr {
+                    kind: expr.kind,
+                    span: self.span_from(start),
+                });
+            }
+            _ => {}
+        }
+
+        if self.eat_keyword("null") {
+            return Ok(literal(Literal::Null));
+        }
+        if self.eat_keyword("true") {
+            return Ok(literal(Literal::Boolean(true)));
+        }
+        if self.eat_keyword("false") {
+            return Ok(literal(Literal::Boolean(false)));
+        }
+        if self.is_keyword("exists") && self.peek_nth(1).kind == TokenKind::LParen {
+            self.next();
+            let subquery = self.subquery()?;
+            return Ok(Expr {
+                kind: ExprKind::Exists(Box::new(subquery)),
+                span: self.span_from(start),
+            });
+        }
+        if self.eat_keyword("cast") {
+            self.expect(&TokenKind::LParen, "'('")?;
+            let expr = self.expr()?;
+            self.expect_keyword("as")?;
+            let data_type = self.data_type()?;
+            self.expect(&TokenKind::RParen, "')'")?;
+            return Ok(Expr {
+                kind: ExprKind::Cast {
+                    expr: Box::new(expr),
+                    data_type,
+                },
+                span: self.span_from(start),
+            });
+        }
+        // Typed literals such as DATE '2024-01-01'.
+        if matches!(self.peek_nth(1).kind, TokenKind::String(_))
+            && (self.is_keyword("date") || self.is_keyword("timestamp"))
+        {
+            let data_type = self.data_type()?;
+            let TokenKind::String(s) = self.next().kind else {
+                unreachable!()
+            };
+            let string = Expr {
+                kind: ExprKind::Literal(Literal::String(s)),
+                span: self.span_from(start),
+            };
+            return Ok(Expr {
+                kind: ExprKind::Cast {
+                    expr: Box::new(string),
+                    data_type,
+                },
+                span: self.span_from(start),
+            });
+        }
+
+        if !self.is_ident() {
+            return Err(self.unexpected("an expression"));
+        }
+        let name = self.ident()?;
+        if self.eat(&TokenKind::LParen) {
+            return self.function(name);
+        }
+        if self.eat(&TokenKind::Dot) {
+            let column = self.ident()?;
+            return Ok(Expr {
+                span: name.span.to(column.span),
+                kind: ExprKind::Column {
+                    table: Some(name),
+                    column,
+                },
+            });
+        }
+        Ok(Expr {
+            span: name.span,
+            kind: ExprKind::Column {
+                table: None,
+                column: name,
+            },
+        })
+    }
+
+    /// A parenthesized SELECT.
+    fn subquery(&mut self) -> SqlResult<Select> {
+        self.expect(&TokenKind::LParen, "'('")?;
+        let select = self.select()?;
+        self.expect(&TokenKind::RParen, "')'")?;
+        Ok(select)
+    }
+
+    /// The arguments of a function call, after the opening parenthesis.
+    fn function(&mut self, name: Ident) -> SqlResult<Expr> {
+        let mut star = false;
+        let mut distinct = false;
+        let mut args = Vec::new();
+        if self.eat(&TokenKind::Star) {
+            star = true;
+        } else if self.peek().kind != TokenKind::RParen {
+            distinct = self.eat_keyword("distinct");
+            args = self.comma_separated(Self::expr)?;
+        }
+        self.expect(&TokenKind::RParen, "',' or ')'")?;
+
+        Ok(Expr {
+            span: self.span_from(name.span),
+            kind: ExprKind::Function {
+                name,
+                args,
+                star,
+                distinct,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(value: &str, start: usize) -> Ident {
+        Ident {
+            value: value.to_string(),
+            span: Span::new(start, start + value.len()),
+        }
+    }
+
+    fn select(sql: &str) -> Select {
+        match parse_statement(sql).unwrap() {
+            Statement::Select(select) => *select,
+            statement => panic!("expected a SELECT, got {statement:?}"),
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let sql = "SELECT DISTINCT u.name AS n, count(*), o.* \
+                   FROM users u JOIN orders o ON u.id = o.user_id LEFT OUTER JOIN items i ON i.id = o.item_id, tags \
+                   WHERE u.age >= 18 AND NOT o.total IS NULL \
+                   GROUP BY u.name HAVING count(*) > 1 \
+                   ORDER BY n DESC, 2 LIMIT 10 OFFSET 5";
+        let select = select(sql);
+        assert!(select.distinct);
+        assert_eq!(select.span, Span::new(0, sql.len()));
+        assert_eq!(select.projection.len(), 3);
+        let SelectItem::Expr { expr, alias } = &select.projection[0] else {
+            panic!()
+        };
+        assert_eq!(alias.as_ref().unwrap().value, "n");
+        assert_eq!(expr.span, Span::new(16, 22));
+        assert!(matches!(
+            &select.projection[1],
+            SelectItem::Expr {
+                expr: Expr {
+                    kind: ExprKind::Function { star: true, .. },
+                    ..
+                },
+                alias: None
+            }
+        ));
+        assert_eq!(
+            select.projection[2],
+            SelectItem::QualifiedWildcard(ident("o", 39))
+        );
+
+        // ((users JOIN orders) LEFT JOIN items) CROSS JOIN tags
+        let Some(TableRef::Join { left, kind, .. }) = &select.from else {
+            panic!()
+        };
+        assert_eq!(*kind, JoinKind::Cross);
+        let TableRef::Join { left, kind, on, .. } = left.as_ref() else {
+            panic!()
+        };
+        assert_eq!(*kind, JoinKind::Left);
+        assert!(on.is_some());
+        assert!(matches!(
+            left.as_ref(),
+            TableRef::Join {
+                kind: JoinKind::Inner,
+                ..
+            }
+        ));
+
+        let selection = select.selection.unwrap();
+        let ExprKind::Binary {
+            op: BinaryOp::And,
+            right,
+            ..
+        } = selection.kind
+        else {
+            panic!()
+        };
+        assert!(matches!(
+            right.kind,
+            ExprKind::Unary {
+                op: UnaryOp::Not,
+                ..
+            }
+        ));
+        assert_eq!(select.group_by.len(), 1);
+        assert!(select.having.is_some());
+        assert!(select.order_by[0].descending);
+        assert!(!select.order_by[1].descending);
+        assert_eq!(
+            select.limit.unwrap().kind,
+            ExprKind::Literal(Literal::Number("10".into()))
+        );
+        assert!(select.offset.is_some());
+    }
+
+    #[test]
+    fn test_grouping_sets() {
+        let sets = |sql: &str| select(sql).grouping_sets;
+        assert_eq!(sets("SELECT 1 FROM t GROUP BY a, b"), None);
+        assert_eq!(
+            sets("SELECT 1 FROM t GROUP BY GROUPING SETS ((a, b), c, ())"),
+            Some(vec![vec![0, 1], vec![2], vec![]])
+        );
+        assert_eq!(
+            sets("SELECT 1 FROM t GROUP BY ROLLUP (a, (b, c))"),
+            Some(vec![vec![0, 1, 2], vec![0], vec![]])
+        );
+        assert_eq!(
+            sets("SELECT 1 FROM t GROUP BY a, CUBE (b, c)"),
+            Some(vec![vec![0, 1, 2], vec![0, 1], vec![0, 2], vec![0]])
+        );
+        // Without parentheses, ROLLUP and CUBE are column names.
+        let select = select("SELECT 1 FROM t GROUP BY cube");
+        assert_eq!(select.group_by.len(), 1);
+        assert_eq!(select.grouping_sets, None);
+
+        let columns = vec!["c"; 13].join(", ");
+        let error = parse(&format!("SELECT 1 FROM t GROUP BY CUBE ({columns})")).unwrap_err();
+        assert_eq!(error.message, "CUBE is limited to 12 elements");
+    }
+
+    #[test]
+    fn test_left_join_requires_on() {
+        let error = parse("SELECT * FROM a LEFT JOIN b").unwrap_err();
+        assert_eq!(error.message, "unexpected end of input, expected ON");
+    }
+
+    #[test]
+    fn test_precedence() {
+        let select = select("SELECT 1 + 2 * -3 || 'x', a OR b AND c, x NOT BETWEEN 1 AND 2 + 3");
+        let SelectItem::Expr { expr, .. } = &select.projection[0] else {
+            panic!()
+        };
+        // (1 + (2 * (-3))) || 'x'
+        let ExprKind::Binary {
+            op: BinaryOp::Concat,
+            left,
+            ..
+        } = &expr.kind
+        else {
+            panic!("{expr:?}")
+        };
+        let ExprKind::Binary {
+            op: BinaryOp::Plus,
+            right,
+            ..
+        } = &left.kind
+        else {
+            panic!()
+        };
+        let ExprKind::Binary {
+            op: BinaryOp::Multiply,
+            right,
+            ..
+        } = &right.kind
+        else {
+            panic!()
+        };
+        assert!(matches!(
+            right.kind,
+            ExprKind::Unary {
+                op: UnaryOp::Minus,
+                ..
+            }
+        ));
+
+        let SelectItem::Expr { expr, .. } = &select.projection[1] else {
+            panic!()
+        };
+        assert!(matches!(
+            expr.kind,
+            ExprKind::Binary {
+                op: BinaryOp::Or,
+                ..
+            }
+        ));
+
+        let SelectItem::Expr { expr, .. } = &select.projection[2] else {
+            panic!()
+        };
+        let ExprKind::Between {
+            high,
+            negated: true,
+            ..
+        } = &expr.kind
+        else {
+            panic!()
+        };
+        assert!(matches!(
+            high.kind,
+            ExprKind::Binary {
+                op: BinaryOp::Plus,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_dml_and_ddl() {
+        let statements = parse(
+            "INSERT INTO t (a, \"B\") VALUES (1, 'x'), (2, NULL);
+             INSERT INTO t SELECT * FROM s;
+             UPDATE t SET a = a + 1, b = DATE '2024-01-01' WHERE a IN (1, 2);
+             DELETE FROM t WHERE b LIKE 'x%';
+             CREATE TABLE IF NOT EXISTS t (a INT NOT NULL, b VARCHAR(10), c DECIMAL(10, 2), d DOUBLE PRECISION);
+             CREATE UNIQUE INDEX t_a ON t USING hash (a);
+             DROP INDEX IF EXISTS t_a;
+             DROP TABLE t;
+             ANALYZE t;
+             ANALYZE",
+        )
+        .unwrap();
+        assert_eq!(statements.len(), 10);
+
+        let Statement::Insert(insert) = &statements[0] else {
+            panic!()
+        };
+        assert_eq!(insert.columns[1].value, "B");
+        assert!(matches!(&insert.source, InsertSource::Values(rows) if rows.len() == 2));
+        assert!(matches!(
+            &statements[1],
+            Statement::Insert(Insert {
+                source: InsertSource::Select(_),
+                ..
+            })
+        ));
+
+        let Statement::Update(update) = &statements[2] else {
+            panic!()
+        };
+        assert!(matches!(
+            update.assignments[1].value.kind,
+            ExprKind::Cast {
+                data_type: DataType::Date,
+                ..
+            }
+        ));
+        assert!(matches!(&statements[3], Statement::Delete(d) if d.selection.is_some()));
+
+        let Statement::CreateTable(create) = &statements[4] else {
+            panic!()
+        };
+        assert!(create.if_not_exists);
+        assert!(create.columns[0].not_null);
+        assert_eq!(
+            create
+                .columns
+                .iter()
+                .map(|column| column.data_type)
+                .collect::<Vec<_>>(),
+            vec![
+                DataType::Int32,
+                DataType::Varchar(Some(10)),
+                DataType::Decimal {
+                    precision: 10,
+                    scale: 2
+                },
+                DataType::Float64,
+            ]
+        );
+
+        let Statement::CreateIndex(index) = &statements[5] else {
+            panic!()
+        };
+        assert!(index.unique);
+        assert_eq!(index.kind, IndexKind::Hash);
+        assert_eq!(index.columns.len(), 1);
+        assert_eq!(index.columns[0].value, "a");
+        assert!(matches!(
+            &statements[6],
+            Statement::DropIndex(DropIndex {
+                if_exists: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            &statements[7],
+            Statement::DropTable(DropTable {
+                if_exists: false,
+                ..
+            })
+        ));
+        assert!(matches!(
+            &statements[8],
+            Statement::Analyze(Analyze { table: Some(table), .. }) if table.value == "t"
+        ));
+        assert!(matches!(
+            &statements[9],
+            Statement::Analyze(Analyze { table: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_explain() {
+        let statements = parse(
+            "EXPLAIN SELECT 1;
+             EXPLAIN ANALYZE DELETE FROM t;
+             EXPLAIN (FORMAT JSON, ANALYZE) SELECT 1;
+             EXPLAIN (ANALYZE FALSE) SELECT 1",
+        )
+        .unwrap();
+        let options: Vec<_> = statements
+            .iter()
+            .map(|statement| {
+                let Statement::Explain(explain) = statement else {
+                    panic!()
+                };
+                (explain.analyze, explain.format)
+            })
+            .collect();
+        assert_eq!(
+            options,
+            vec![
+                (false, ExplainFormat::Text),
+                (true, ExplainFormat::Text),
+                (true, ExplainFormat::Json),
+                (false, ExplainFormat::Text),
+            ]
+        );
+        let Statement::Explain(explain) = &statements[1] else {
+            panic!()
+        };
+        assert!(matches!(*explain.statement, Statement::Delete(_)));
+
+        assert_eq!(
+            parse("EXPLAIN (FORMAT YAML) SELECT 1").unwrap_err().message,
+            "unexpected YAML, expected TEXT or JSON"
+        );
+    }
+
+    #[test]
+    fn test_parameters() {
+        let (statement, parameters) =
+            parse_prepared("SELECT ?, :name, $4, ?, :other, :name FROM t").unwrap();
+        let Statement::Select(select) = statement else {
+            panic!()
+        };
+        let positions: Vec<usize> = select
+            .projection
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expr {
+                    expr:
+                        Expr {
+                            kind: ExprKind::Parameter(position),
+                            ..
+                        },
+                    ..
+                } => *position,
+                item => panic!("expected a parameter, got {item:?}"),
+            })
+            .collect();
+        assert_eq!(positions, vec![0, 1, 3, 4, 5, 1]);
+        assert_eq!(
+            parameters,
+            vec![
+                None,
+                Some("name".to_string()),
+                None,
+                None,
+                None,
+                Some("other".to_string())
+            ]
+        );
+
+        let error = parse("SELECT $0").unwrap_err();
+        assert_eq!(
+            error.message,
+            "parameter $0 is out of range, the most is $65535"
+        );
+        assert_eq!(error.span, Span::new(7, 9));
+        assert!(parse("SELECT $65536").is_err());
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let sql = "SELECT a,\n  FROM t";
+        let error = parse(sql).unwrap_err();
+        assert_eq!(error.message, "unexpected FROM, expected an expression");
+        assert_eq!(error.span.line_col(sql), (2, 3));
+
+        let sql = "CREATE TABLE t (a DECIMAL(40, 2))";
+        let error = parse(sql).unwrap_err();
+        assert_eq!(error.span.line_col(sql), (1, 19));
+
+        let error = parse("SELECT * FROM t WHERE").unwrap_err();
+        assert_eq!(
+            error.message,
+            "unexpected end of input, expected an expression"
+        );
+        assert!(parse("SELECT 1 SELECT 2").is_err());
+        assert!(parse_statement("SELECT 1; SELECT 2").is_err());
+    }
+}
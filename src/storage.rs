@@ -1,2 +1,7 @@
 pub mod disk;
+pub mod external_sort;
+pub mod index;
+pub mod overflow;
 pub mod page;
+pub mod spill;
+pub mod table;
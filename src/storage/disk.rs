@@ -1,8 +1,11 @@
 use std::{
-    fs::{File, OpenOptions},
+    fs::{self, File, OpenOptions},
     io::{self, Read, Seek, Write},
-    path::Path,
-    sync::RwLock,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicUsize},
+        RwLock,
+    },
 };
 
 use crate::PageId;
@@ -10,19 +13,25 @@ use crate::PageId;
 pub trait DiskManager: Sized + Sync + Send {
     fn new(page_size: usize, filename: impl AsRef<Path>) -> io::Result<Self>;
     fn page_size(&self) -> usize;
+    /// The number of pages the file spans, including pages that were written out of order.
+    fn num_pages(&self) -> usize;
     fn read_page(&self, page_id: PageId, data: &mut [u8]) -> anyhow::Result<()>;
     fn write_page(&self, page_id: PageId, data: &[u8]) -> anyhow::Result<()>;
+    /// Make the pages written so far durable.
+    fn sync(&self) -> anyhow::Result<()>;
 }
 
 pub struct BasicDiskManager {
     page_size: usize,
+    num_pages: AtomicUsize,
     file: RwLock<File>,
 }
 
 impl DiskManager for BasicDiskManager {
     fn new(page_size: usize, filename: impl AsRef<Path>) -> io::Result<Self> {
         let file = if filename.as_ref().exists() {
-            OpenOptions::new().read(true).append(true).open(filename)?
+            // Not in append mode: pages must be overwritten at their offset.
+            OpenOptions::new().read(true).write(true).open(filename)?
         } else {
             OpenOptions::new()
                 .read(true)
@@ -31,8 +40,10 @@ impl DiskManager for BasicDiskManager {
                 .truncate(true)
                 .open(filename)?
         };
+        let num_pages = file.metadata()?.len().div_ceil(page_size as u64) as usize;
         Ok(Self {
             page_size,
+            num_pages: AtomicUsize::new(num_pages),
             file: RwLock::new(file),
         })
     }
@@ -41,6 +52,10 @@ impl DiskManager for BasicDiskManager {
         self.page_size
     }
 
+    fn num_pages(&self) -> usize {
+        self.num_pages.load(atomic::Ordering::Acquire)
+    }
+
     fn read_page(&self, page_id: PageId, data: &mut [u8]) -> anyhow::Result<()> {
         let offset = page_id.offset(self.page_size()) as u64;
         let Ok(mut file) = self.file.write() else {
@@ -59,6 +74,17 @@ impl DiskManager for BasicDiskManager {
         };
         file.seek(io::SeekFrom::Start(offset))?;
         file.write_all(data)?;
+        self.num_pages
+            .fetch_max(page_id.as_usize() + 1, atomic::Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    fn sync(&self) -> anyhow::Result<()> {
+        let Ok(file) = self.file.read() else {
+            anyhow::bail!("failed to acquire read lock");
+        };
+        file.sync_data()?;
 
         Ok(())
     }
@@ -66,6 +92,70 @@ impl DiskManager for BasicDiskManager {
 
 pub type LimeBaseDiskManager = BasicDiskManager;
 
+/// Pages that only live while a query runs, such as the runs of an external sort or the
+/// partitions of a spilling hash table, kept out of the database file.
+///
+/// The file is unlinked right after it is created, so its space goes away with the process and
+/// it is never synced. A file left behind at the same path, by a crash before unlinking or on a
+/// platform that cannot unlink open files, is wiped when the next one is created.
+pub struct TempDiskManager {
+    inner: BasicDiskManager,
+}
+
+impl TempDiskManager {
+    /// The path of the temporary file that belongs to the database file `db_path`.
+    pub fn path_for(db_path: impl AsRef<Path>) -> PathBuf {
+        let mut path = db_path.as_ref().as_os_str().to_owned();
+        path.push(".tmp");
+        PathBuf::from(path)
+    }
+
+    /// Drop all pages, so that the space can be reused from the start.
+    pub fn truncate(&self) -> anyhow::Result<()> {
+        let Ok(file) = self.inner.file.write() else {
+            anyhow::bail!("failed to acquire write lock");
+        };
+        file.set_len(0)?;
+        self.inner.num_pages.store(0, atomic::Ordering::Release);
+
+        Ok(())
+    }
+}
+
+impl DiskManager for TempDiskManager {
+    fn new(page_size: usize, filename: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::remove_file(&filename) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        let inner = BasicDiskManager::new(page_size, &filename)?;
+        // If the file cannot be unlinked while open, it is wiped on the next startup instead.
+        let _ = fs::remove_file(&filename);
+        Ok(Self { inner })
+    }
+
+    fn page_size(&self) -> usize {
+        self.inner.page_size()
+    }
+
+    fn num_pages(&self) -> usize {
+        self.inner.num_pages()
+    }
+
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> anyhow::Result<()> {
+        self.inner.read_page(page_id, data)
+    }
+
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> anyhow::Result<()> {
+        self.inner.write_page(page_id, data)
+    }
+
+    /// Nothing to do: temp pages do not outlive the process.
+    fn sync(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -89,7 +179,7 @@ mod tests {
         let disk_manager =
             LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
         const N_PAGES: usize = 10;
-        let mut data = [[0; DEFAULT_PAGE_SIZE as usize]; N_PAGES];
+        let mut data = [[0; DEFAULT_PAGE_SIZE]; N_PAGES];
         for (i, page_buf) in data.iter_mut().enumerate() {
             rng.fill(page_buf.as_mut_slice());
             disk_manager.write_page(PageId::new(i), page_buf).unwrap();
@@ -97,7 +187,7 @@ mod tests {
 
         for _ in 0..N_PAGES {
             let i = rng.gen_range(0..N_PAGES);
-            let mut buf = [0; DEFAULT_PAGE_SIZE as usize];
+            let mut buf = [0; DEFAULT_PAGE_SIZE];
             disk_manager.read_page(PageId::new(i), &mut buf).unwrap();
             assert_eq!(buf, data[i]);
 
@@ -117,10 +207,42 @@ mod tests {
         // Reopen the disk manager and check if the data is still there
         let disk_manager =
             LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
-        for i in 0..N_PAGES {
-            let mut buf = [0; DEFAULT_PAGE_SIZE as usize];
+        assert_eq!(disk_manager.num_pages(), N_PAGES);
+        for (i, page_buf) in data.iter().enumerate() {
+            let mut buf = [0; DEFAULT_PAGE_SIZE];
             disk_manager.read_page(PageId::new(i), &mut buf).unwrap();
-            assert_eq!(buf, data[i]);
+            assert_eq!(&buf, page_buf);
         }
+
+        // Overwriting a page after reopening must not append to the file.
+        rng.fill(data[0].as_mut_slice());
+        disk_manager.write_page(PageId::new(0), &data[0]).unwrap();
+        let mut buf = [0; DEFAULT_PAGE_SIZE];
+        disk_manager.read_page(PageId::new(0), &mut buf).unwrap();
+        assert_eq!(buf, data[0]);
+        assert_eq!(disk_manager.num_pages(), N_PAGES);
+    }
+
+    #[test]
+    fn test_temp_disk_manager() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = TempDiskManager::path_for(tempdir.path().join("test.db"));
+        assert_eq!(path, tempdir.path().join("test.db.tmp"));
+        // A file left behind by a crash is wiped.
+        std::fs::write(&path, vec![1; DEFAULT_PAGE_SIZE * 3]).unwrap();
+
+        let disk_manager = TempDiskManager::new(DEFAULT_PAGE_SIZE, &path).unwrap();
+        assert_eq!(disk_manager.num_pages(), 0);
+        assert!(!path.exists());
+        let data = [7; DEFAULT_PAGE_SIZE];
+        disk_manager.write_page(PageId::new(1), &data).unwrap();
+        assert_eq!(disk_manager.num_pages(), 2);
+        let mut buf = [0; DEFAULT_PAGE_SIZE];
+        disk_manager.read_page(PageId::new(1), &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        disk_manager.truncate().unwrap();
+        assert_eq!(disk_manager.num_pages(), 0);
+        assert!(disk_manager.read_page(PageId::new(1), &mut buf).is_err());
     }
 }
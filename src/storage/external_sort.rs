@@ -0,0 +1,476 @@
+//! Sorting of more entries than fit in memory, for `ORDER BY`, sort-merge joins and index
+//! bulk loading.
+//!
+//! [`ExternalSorter`] collects entries in memory up to a limit, and spills each full batch as
+//! a sorted run to a chain of slotted pages allocated with [`BufferPoolManager::new_page`].
+//! Finishing the sort merges the runs, at most `fan_in` of them at a time: while there are
+//! more, groups of runs are first merged into longer ones. A merge copies the entries of one
+//! page of a run out at a time, so it buffers at most `fan_in` pages of entries and pins only
+//! the page it reads and the page it writes, however many runs there are.
+//!
+//! Every run page is deleted with [`BufferPoolManager::delete_page`] as soon as its entries
+//! have been read. Dropping the sorter or its sorted entries early, as a cancelled query does,
+//! deletes the pages of all runs that have not been read.
+
+use std::{cmp::Ordering, collections::VecDeque};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    storage::{index::storable::Storable, page::slotted_page::SlottedPage, spill::free_chain},
+    PageId,
+};
+
+/// The number of runs merged at once unless configured otherwise.
+pub const DEFAULT_FAN_IN: usize = 64;
+
+/// An entry that can be written to the pages of a sorted run.
+pub trait SortEntry: Sized {
+    fn to_sort_bytes(&self) -> Vec<u8>;
+
+    fn from_sort_bytes(bytes: &[u8]) -> Self;
+}
+
+impl<T: Storable> SortEntry for T {
+    fn to_sort_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; T::SIZE];
+        self.write_to(&mut bytes);
+        bytes
+    }
+
+    fn from_sort_bytes(bytes: &[u8]) -> Self {
+        T::read_from(bytes)
+    }
+}
+
+/// Variable-length records, which must fit into a single page.
+impl SortEntry for Vec<u8> {
+    fn to_sort_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_sort_bytes(bytes: &[u8]) -> Self {
+        bytes.to_vec()
+    }
+}
+
+pub struct ExternalSorter<'a, T, B: BufferPoolManager + ?Sized, F> {
+    compare: F,
+    memory_limit: usize,
+    fan_in: usize,
+    buffer: Vec<T>,
+    runs: SpilledRuns<'a, B>,
+}
+
+/// The first pages of the runs spilled by a sorter, which are freed on drop unless a merge
+/// took them over.
+struct SpilledRuns<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    page_ids: Vec<PageId>,
+}
+
+impl<'a, T, B, F> ExternalSorter<'a, T, B, F>
+where
+    T: SortEntry,
+    B: BufferPoolManager + ?Sized,
+    F: Fn(&T, &T) -> Ordering,
+{
+    /// Create a sorter that orders entries by `compare` and keeps at most `memory_limit`
+    /// entries in memory.
+    pub fn new(bpm: &'a B, memory_limit: usize, compare: F) -> Self {
+        assert!(memory_limit > 0, "memory limit must be positive");
+        Self {
+            compare,
+            memory_limit,
+            fan_in: DEFAULT_FAN_IN,
+            buffer: Vec::new(),
+            runs: SpilledRuns {
+                bpm,
+                page_ids: Vec::new(),
+            },
+        }
+    }
+
+    /// Merge at most `fan_in` runs at once.
+    pub fn with_fan_in(self, fan_in: usize) -> Self {
+        assert!(fan_in >= 2, "at least two runs must be merged at once");
+        Self { fan_in, ..self }
+    }
+
+    /// The number of runs spilled so far.
+    pub fn num_runs(&self) -> usize {
+        self.runs.page_ids.len()
+    }
+
+    pub fn push(&mut self, entry: T) -> anyhow::Result<()> {
+        self.buffer.push(entry);
+        if self.buffer.len() >= self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Sort the buffered entries and write them out as a new run.
+    fn spill(&mut self) -> anyhow::Result<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_by(&self.compare);
+        write_run(
+            self.runs.bpm,
+            &mut self.runs.page_ids,
+            buffer.into_iter().map(Ok),
+        )
+    }
+
+    /// Return the entries in sorted order, merging the spilled runs if there are any.
+    pub fn finish(mut self) -> anyhow::Result<SortedEntries<'a, T, B, F>> {
+        let bpm = self.runs.bpm;
+        if self.runs.page_ids.is_empty() {
+            self.buffer.sort_by(&self.compare);
+            let run = Run {
+                next_page_id: None,
+                buffered: self.buffer.drain(..).collect(),
+            };
+            return Ok(SortedEntries {
+                compare: self.compare,
+                merge: Merge {
+                    bpm,
+                    runs: vec![run],
+                    failed: false,
+                },
+            });
+        }
+
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        // Merge the oldest runs into a new one until few enough are left. The new run is
+        // recorded before it is written, so that it is freed if the sort fails or is dropped.
+        while self.runs.page_ids.len() > self.fan_in {
+            let mut merge = Merge::new(bpm, self.runs.page_ids.drain(..self.fan_in).collect());
+            let entries = std::iter::from_fn(|| merge.next(&self.compare));
+            write_run(bpm, &mut self.runs.page_ids, entries)?;
+        }
+        Ok(SortedEntries {
+            merge: Merge::new(bpm, self.runs.page_ids.drain(..).collect()),
+            compare: self.compare,
+        })
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Drop for SpilledRuns<'_, B> {
+    fn drop(&mut self) {
+        for page_id in self.page_ids.drain(..) {
+            free_chain(self.bpm, Some(page_id));
+        }
+    }
+}
+
+/// Write `entries`, which must be sorted, to a new chain of pages and record its first page in
+/// `page_ids`.
+fn write_run<T: SortEntry, B: BufferPoolManager + ?Sized>(
+    bpm: &B,
+    page_ids: &mut Vec<PageId>,
+    entries: impl IntoIterator<Item = anyhow::Result<T>>,
+) -> anyhow::Result<()> {
+    let mut guard = bpm.new_page_write()?;
+    SlottedPage::init(guard.data_mut());
+    page_ids.push(guard.page_id());
+    for entry in entries {
+        let bytes = entry?.to_sort_bytes();
+        if SlottedPage::new(guard.data_mut()).insert(&bytes).is_some() {
+            continue;
+        }
+        let mut next_guard = bpm.new_page_write()?;
+        SlottedPage::init(next_guard.data_mut());
+        SlottedPage::new(guard.data_mut()).set_next_page_id(Some(next_guard.page_id()));
+        guard = next_guard;
+        if SlottedPage::new(guard.data_mut()).insert(&bytes).is_none() {
+            anyhow::bail!("an entry of {} bytes is too large to sort", bytes.len());
+        }
+    }
+    Ok(())
+}
+
+/// The unread part of one run: the entries of the page read last and the pages after it.
+struct Run<T> {
+    next_page_id: Option<PageId>,
+    buffered: VecDeque<T>,
+}
+
+impl<T: SortEntry> Run<T> {
+    /// Make sure the next entry of the run is buffered, reading and deleting the next page if
+    /// needed. Return false if the run is exhausted.
+    fn fill<B: BufferPoolManager + ?Sized>(&mut self, bpm: &B) -> anyhow::Result<bool> {
+        while self.buffered.is_empty() {
+            let Some(page_id) = self.next_page_id else {
+                return Ok(false);
+            };
+            let guard = bpm.fetch_page_read(page_id)?;
+            let page = SlottedPage::new(guard.data());
+            self.buffered
+                .extend(page.iter().map(|(_, bytes)| T::from_sort_bytes(bytes)));
+            self.next_page_id = page.next_page_id();
+            drop(guard);
+            bpm.delete_page(page_id);
+        }
+        Ok(true)
+    }
+}
+
+/// A k-way merge of runs, which frees the pages of the runs it has not read on drop.
+struct Merge<'a, T, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    runs: Vec<Run<T>>,
+    failed: bool,
+}
+
+impl<'a, T: SortEntry, B: BufferPoolManager + ?Sized> Merge<'a, T, B> {
+    fn new(bpm: &'a B, page_ids: Vec<PageId>) -> Self {
+        let runs = page_ids
+            .into_iter()
+            .map(|page_id| Run {
+                next_page_id: Some(page_id),
+                buffered: VecDeque::new(),
+            })
+            .collect();
+        Self {
+            bpm,
+            runs,
+            failed: false,
+        }
+    }
+
+    /// Return the smallest next entry of all runs.
+    fn next(&mut self, compare: impl Fn(&T, &T) -> Ordering) -> Option<anyhow::Result<T>> {
+        if self.failed {
+            return None;
+        }
+        let mut min: Option<usize> = None;
+        let mut i = 0;
+        while i < self.runs.len() {
+            match self.runs[i].fill(self.bpm) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.runs.swap_remove(i);
+                    continue;
+                }
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+            let is_smaller = min.map_or(true, |min| {
+                compare(&self.runs[i].buffered[0], &self.runs[min].buffered[0]).is_lt()
+            });
+            if is_smaller {
+                min = Some(i);
+            }
+            i += 1;
+        }
+        min.map(|min| Ok(self.runs[min].buffered.pop_front().unwrap()))
+    }
+}
+
+impl<T, B: BufferPoolManager + ?Sized> Drop for Merge<'_, T, B> {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            free_chain(self.bpm, run.next_page_id);
+        }
+    }
+}
+
+/// Yields the entries of an [`ExternalSorter`] in order.
+pub struct SortedEntries<'a, T, B: BufferPoolManager + ?Sized, F> {
+    compare: F,
+    merge: Merge<'a, T, B>,
+}
+
+impl<T, B, F> Iterator for SortedEntries<'_, T, B, F>
+where
+    T: SortEntry,
+    B: BufferPoolManager + ?Sized,
+    F: Fn(&T, &T) -> Ordering,
+{
+    type Item = anyhow::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.merge.next(&self.compare)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::{self, AtomicUsize},
+            Mutex, RwLock,
+        },
+    };
+
+    use rand::{seq::SliceRandom, SeedableRng};
+
+    use crate::{
+        buffer::buffer_pool_manager::{BufferPoolManagerImpl, BufferPoolStats},
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+        Page,
+    };
+
+    use super::*;
+
+    /// Tracks the pages that are allocated and not deleted, and the most pages pinned at once.
+    struct TrackingBufferPool {
+        inner: BufferPoolManagerImpl,
+        live: Mutex<BTreeSet<PageId>>,
+        pinned: AtomicUsize,
+        max_pinned: AtomicUsize,
+    }
+
+    impl TrackingBufferPool {
+        fn new(inner: BufferPoolManagerImpl) -> Self {
+            Self {
+                inner,
+                live: Mutex::new(BTreeSet::new()),
+                pinned: AtomicUsize::new(0),
+                max_pinned: AtomicUsize::new(0),
+            }
+        }
+
+        fn pin(&self) {
+            let pinned = self.pinned.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+            self.max_pinned.fetch_max(pinned, atomic::Ordering::SeqCst);
+        }
+    }
+
+    impl BufferPoolManager for TrackingBufferPool {
+        fn get_pool_size(&self) -> usize {
+            self.inner.get_pool_size()
+        }
+
+        fn stats(&self) -> BufferPoolStats {
+            self.inner.stats()
+        }
+
+        fn get_pages(&self) -> &[RwLock<Page>] {
+            self.inner.get_pages()
+        }
+
+        fn new_page(&self) -> anyhow::Result<Option<(PageId, &RwLock<Page>)>> {
+            let page = self.inner.new_page()?;
+            if let Some((page_id, _)) = page {
+                self.live.lock().unwrap().insert(page_id);
+                self.pin();
+            }
+            Ok(page)
+        }
+
+        fn fetch_page(&self, page_id: PageId) -> anyhow::Result<Option<&RwLock<Page>>> {
+            let page = self.inner.fetch_page(page_id)?;
+            if page.is_some() {
+                self.pin();
+            }
+            Ok(page)
+        }
+
+        fn unpin_page(&self, page_id: PageId, is_dirty: bool) -> bool {
+            let unpinned = self.inner.unpin_page(page_id, is_dirty);
+            if unpinned {
+                self.pinned.fetch_sub(1, atomic::Ordering::SeqCst);
+            }
+            unpinned
+        }
+
+        fn flush_page(&self, page_id: PageId) -> anyhow::Result<bool> {
+            self.inner.flush_page(page_id)
+        }
+
+        fn flush_all_pages(&self) -> anyhow::Result<()> {
+            self.inner.flush_all_pages()
+        }
+
+        fn delete_page(&self, page_id: PageId) -> bool {
+            let deleted = self.inner.delete_page(page_id);
+            if deleted {
+                self.live.lock().unwrap().remove(&page_id);
+            }
+            deleted
+        }
+    }
+
+    #[test]
+    fn test_sort_with_spilled_runs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = TrackingBufferPool::new(BufferPoolManagerImpl::new(8, disk_manager));
+
+        let mut values = (0..20000u64).collect::<Vec<_>>();
+        values.shuffle(&mut rand::rngs::StdRng::seed_from_u64(0));
+        let mut sorter = ExternalSorter::new(&bpm, 3000, |a: &u64, b: &u64| b.cmp(a));
+        for value in &values {
+            sorter.push(*value).unwrap();
+        }
+        assert_eq!(sorter.num_runs(), 6);
+        let sorted = sorter
+            .finish()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, (0..20000).rev().collect::<Vec<_>>());
+        assert!(bpm.live.lock().unwrap().is_empty());
+
+        // Small inputs are sorted in memory.
+        let mut sorter = ExternalSorter::new(&bpm, 3000, u64::cmp);
+        for value in [3, 1, 2] {
+            sorter.push(value).unwrap();
+        }
+        assert_eq!(sorter.num_runs(), 0);
+        let sorted = sorter.finish().unwrap().map(|e| e.unwrap());
+        assert_eq!(sorted.collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_merge_passes_and_cancellation() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = TrackingBufferPool::new(BufferPoolManagerImpl::new(4, disk_manager));
+
+        let mut records = (0..10000u32)
+            .map(|i| format!("record-{}", i * 7919 % 10000).into_bytes())
+            .collect::<Vec<_>>();
+        records.shuffle(&mut rand::rngs::StdRng::seed_from_u64(1));
+        let mut expected = records.clone();
+        expected.sort();
+        let sorter = || {
+            let mut sorter = ExternalSorter::new(&bpm, 250, Vec::<u8>::cmp).with_fan_in(3);
+            for record in &records {
+                sorter.push(record.clone()).unwrap();
+            }
+            assert_eq!(sorter.num_runs(), 40);
+            sorter
+        };
+
+        // Forty runs are merged three at a time while pinning only a few frames.
+        let sorted = sorter()
+            .finish()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, expected);
+        assert!(bpm.max_pinned.load(atomic::Ordering::SeqCst) <= 3);
+        assert!(bpm.live.lock().unwrap().is_empty());
+
+        // Cancelling before or during the merge deletes every temporary page.
+        drop(sorter());
+        assert!(bpm.live.lock().unwrap().is_empty());
+        let mut sorted = sorter().finish().unwrap();
+        assert_eq!(sorted.next().unwrap().unwrap(), expected[0]);
+        assert!(!bpm.live.lock().unwrap().is_empty());
+        drop(sorted);
+        assert!(bpm.live.lock().unwrap().is_empty());
+        assert_eq!(bpm.pinned.load(atomic::Ordering::SeqCst), 0);
+    }
+}
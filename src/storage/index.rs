@@ -0,0 +1,7 @@
+pub mod b_plus_tree;
+pub mod b_plus_tree_iterator;
+pub mod extendible_hash_table;
+#[allow(clippy::module_inception)]
+pub mod index;
+pub mod index_key;
+pub mod storable;
@@ -0,0 +1,1349 @@
+//! A B+ tree index whose nodes are buffer-pool pages.
+//!
+//! The tree is identified by its header page, which records the root page id so that the tree
+//! can be reopened after a restart, together with the node size and the value size it was
+//! created with. Keys are unique and compared by their [`IndexKey`] encoding, so nodes hold
+//! keys of any length. Nodes split and merge by the bytes they use: a split divides a node
+//! into two halves of about the same size, and nodes other than the root are merged with or
+//! refilled from a sibling once they use less than a quarter of their capacity. Leaves share
+//! the prefix of their keys, and separators in internal nodes are only as long as needed to
+//! tell the two neighbouring leaves apart. Leaves are linked left to right for range scans.
+//! See [`b_plus_tree_page`](crate::storage::page::b_plus_tree_page) for the node layout.
+//!
+//! Concurrent operations synchronize with latch crabbing on the page latches, starting from
+//! the header page. Readers latch each node before releasing its parent. Inserts first try to
+//! descend the same way and write-latch only the leaf; when the leaf is full, they descend
+//! again with write latches, releasing the ancestors of every node that cannot split, as
+//! removes always do for nodes that cannot underflow.
+//!
+//! | offset | size | field                  |
+//! |--------|------|------------------------|
+//! | 0      | 16   | common page header     |
+//! | 16     | 8    | root page id           |
+//! | 24     | 4    | node size              |
+//! | 28     | 4    | value size             |
+
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    buffer::{
+        buffer_pool_manager::BufferPoolManager,
+        page_guard::{ReadPageGuard, WritePageGuard},
+    },
+    storage::{
+        external_sort::ExternalSorter,
+        page::{
+            b_plus_tree_page::{
+                common_prefix_len, shortest_separator, BPlusTreePage, InternalPage, LeafPage,
+                B_PLUS_TREE_SLOT_SIZE,
+            },
+            bytes,
+            page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+        },
+    },
+    PageId,
+};
+
+use super::{b_plus_tree_iterator::BPlusTreeIterator, index_key::IndexKey, storable::Storable};
+
+const ROOT_PAGE_ID_OFFSET: usize = COMMON_HEADER_SIZE;
+const NODE_SIZE_OFFSET: usize = ROOT_PAGE_ID_OFFSET + 8;
+const VALUE_SIZE_OFFSET: usize = NODE_SIZE_OFFSET + 4;
+
+/// The smallest node size a tree can be created with.
+pub const MIN_NODE_SIZE: usize = 256;
+
+fn root_page_id(header: &[u8]) -> Option<PageId> {
+    let page_id = PageId::from_u64(bytes::read_u64(header, ROOT_PAGE_ID_OFFSET));
+    page_id.is_valid().then_some(page_id)
+}
+
+fn set_root_page_id(header: &mut [u8], root_page_id: Option<PageId>) {
+    let page_id = root_page_id.unwrap_or_else(PageId::new_invalid);
+    bytes::write_u64(header, ROOT_PAGE_ID_OFFSET, page_id.to_u64());
+}
+
+/// Entries with their keys encoded, as nodes store them.
+type Entries<V> = Vec<(Vec<u8>, V)>;
+
+/// The operation a writer descends the tree for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Insert,
+    Remove,
+}
+
+/// The latches a writer holds after descending the tree: the nodes from the topmost one that
+/// may change down to the leaf, and the header page if the root may change.
+struct WritePath<'a, B: BufferPoolManager + ?Sized> {
+    header: Option<WritePageGuard<'a, B>>,
+    nodes: Vec<WritePageGuard<'a, B>>,
+}
+
+/// Options for [`BPlusTree::bulk_load`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BulkLoadConfig {
+    /// The fraction of every node that is filled, leaving room for later inserts.
+    pub fill_factor: f64,
+    /// The number of entries sorted in memory before sorted runs are spilled to pages.
+    pub sort_memory: usize,
+}
+
+impl BulkLoadConfig {
+    /// Fill nodes to 90% like PostgreSQL's default B-tree fill factor.
+    pub fn new() -> Self {
+        Self {
+            fill_factor: 0.9,
+            sort_memory: 1 << 16,
+        }
+    }
+
+    pub fn with_fill_factor(self, fill_factor: f64) -> Self {
+        Self {
+            fill_factor,
+            ..self
+        }
+    }
+
+    pub fn with_sort_memory(self, sort_memory: usize) -> Self {
+        Self {
+            sort_memory,
+            ..self
+        }
+    }
+}
+
+impl Default for BulkLoadConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cuts a sorted stream of entries into the nodes of one level. Nodes are filled up to
+/// `target` bytes, but a node is only cut off once twice as much is pending, so that the
+/// remainder can be divided into one or two balanced nodes.
+struct NodePacker<V> {
+    is_leaf: bool,
+    capacity: usize,
+    target: usize,
+    pending: Entries<V>,
+    /// The sum of the key lengths of `pending`.
+    pending_key_bytes: usize,
+}
+
+impl<V: Storable> NodePacker<V> {
+    fn new(is_leaf: bool, capacity: usize, fill_factor: f64) -> Self {
+        Self {
+            is_leaf,
+            capacity,
+            target: ((capacity as f64 * fill_factor).ceil() as usize).clamp(capacity / 2, capacity),
+            pending: Vec::new(),
+            pending_key_bytes: 0,
+        }
+    }
+
+    /// The bytes a node holding all pending entries would use.
+    fn pending_size(&self) -> usize {
+        let n = self.pending.len();
+        let total = n * BPlusTreePage::<&[u8], V>::ENTRY_OVERHEAD + self.pending_key_bytes;
+        match self.pending.as_slice() {
+            [] => 0,
+            [(first, _), .., (last, _)] if self.is_leaf => {
+                total - (n - 1) * common_prefix_len(first, last)
+            }
+            [(first, _), ..] if !self.is_leaf => total - first.len(),
+            _ => total,
+        }
+    }
+
+    /// Add an entry, returning the entries of a node once one is complete.
+    fn push(&mut self, entry: (Vec<u8>, V)) -> Option<Entries<V>> {
+        self.pending_key_bytes += entry.0.len();
+        self.pending.push(entry);
+        if self.pending_size() <= 2 * self.target {
+            return None;
+        }
+        // The size of a prefix of the entries grows with its length.
+        let len = (1..self.pending.len())
+            .take_while(|len| {
+                BPlusTreePage::size_of(&self.pending[..*len], self.is_leaf) <= self.target
+            })
+            .last()
+            .unwrap_or(1);
+        let node = self.pending.drain(..len).collect::<Vec<_>>();
+        self.pending_key_bytes -= node.iter().map(|(key, _)| key.len()).sum::<usize>();
+        Some(node)
+    }
+
+    /// Return the entries of the remaining nodes.
+    fn finish(mut self) -> Vec<Entries<V>> {
+        if self.pending.is_empty() {
+            Vec::new()
+        } else if self.pending_size() <= self.capacity {
+            vec![self.pending]
+        } else {
+            let mid = BPlusTreePage::split_point(&self.pending, self.is_leaf, self.capacity)
+                .expect("the entries of two nodes can be split");
+            let second = self.pending.split_off(mid);
+            vec![self.pending, second]
+        }
+    }
+}
+
+pub struct BPlusTree<'a, K, V, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    header_page_id: PageId,
+    node_size: usize,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'a, K, V, B> BPlusTree<'a, K, V, B>
+where
+    K: IndexKey,
+    V: Storable + Clone,
+    B: BufferPoolManager + ?Sized,
+{
+    /// Create an empty tree whose nodes are as large as a page allows.
+    pub fn create(bpm: &'a B) -> anyhow::Result<Self> {
+        let guard = bpm.new_page_write()?;
+        let node_size = LeafPage::<&[u8], V>::max_capacity(guard.page_size());
+        Self::init(bpm, guard, node_size)
+    }
+
+    /// Create an empty tree whose nodes use at most `node_size` bytes for their entries.
+    pub fn create_with_node_size(bpm: &'a B, node_size: usize) -> anyhow::Result<Self> {
+        let guard = bpm.new_page_write()?;
+        let max_node_size = LeafPage::<&[u8], V>::max_capacity(guard.page_size());
+        if !(MIN_NODE_SIZE..=max_node_size).contains(&node_size) {
+            let page_id = guard.page_id();
+            drop(guard);
+            bpm.delete_page(page_id);
+            anyhow::bail!("node size must be within {MIN_NODE_SIZE}..={max_node_size}");
+        }
+        Self::init(bpm, guard, node_size)
+    }
+
+    fn init(
+        bpm: &'a B,
+        mut guard: WritePageGuard<'a, B>,
+        node_size: usize,
+    ) -> anyhow::Result<Self> {
+        let header = guard.data_mut();
+        bytes::write_u32(header, PAGE_TYPE_OFFSET, PageType::BPlusTreeHeader as u32);
+        set_root_page_id(header, None);
+        bytes::write_u32(header, NODE_SIZE_OFFSET, node_size as u32);
+        bytes::write_u32(header, VALUE_SIZE_OFFSET, V::SIZE as u32);
+
+        Ok(Self {
+            bpm,
+            header_page_id: guard.page_id(),
+            node_size,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Open an existing tree through its header page.
+    pub fn open(bpm: &'a B, header_page_id: PageId) -> anyhow::Result<Self> {
+        let guard = bpm.fetch_page_read(header_page_id)?;
+        let header = guard.data();
+        if PageType::of(header) != Some(PageType::BPlusTreeHeader) {
+            anyhow::bail!("{header_page_id:?} is not a B+ tree header page");
+        }
+        let value_size = bytes::read_u32(header, VALUE_SIZE_OFFSET) as usize;
+        if value_size != V::SIZE {
+            anyhow::bail!("B+ tree stores {value_size}-byte values, not {}", V::SIZE);
+        }
+
+        Ok(Self {
+            bpm,
+            header_page_id,
+            node_size: bytes::read_u32(header, NODE_SIZE_OFFSET) as usize,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn header_page_id(&self) -> PageId {
+        self.header_page_id
+    }
+
+    /// The longest encoded key the tree accepts. Every node holds at least eight entries, so
+    /// that a node that overflows by one entry can always be split in two.
+    pub fn max_key_size(&self) -> usize {
+        self.node_size / 8 - B_PLUS_TREE_SLOT_SIZE - V::SIZE.max(PageId::SIZE)
+    }
+
+    /// The most bytes a node can grow by when an entry is inserted or a key is replaced.
+    fn max_entry_size(&self) -> usize {
+        B_PLUS_TREE_SLOT_SIZE + self.max_key_size() + V::SIZE.max(PageId::SIZE)
+    }
+
+    /// The root page, or None if the tree is empty.
+    pub fn root_page_id(&self) -> anyhow::Result<Option<PageId>> {
+        let guard = self.bpm.fetch_page_read(self.header_page_id)?;
+        Ok(root_page_id(guard.data()))
+    }
+
+    pub fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(self.root_page_id()?.is_none())
+    }
+
+    /// Free every node of the tree and its header for the buffer pool to reuse. The nodes are
+    /// all found first, so that failing to read one frees none of them. Nobody else may use
+    /// the tree.
+    pub fn free_pages(&self) -> anyhow::Result<()> {
+        let mut page_ids = Vec::new();
+        let mut level: Vec<PageId> = self.root_page_id()?.into_iter().collect();
+        while !level.is_empty() {
+            let mut children = Vec::new();
+            for &page_id in &level {
+                let guard = self.bpm.fetch_page_read(page_id)?;
+                let node = InternalPage::new(guard.data());
+                if !node.is_leaf() {
+                    children.extend(node.entries().into_iter().map(|(_, child)| child));
+                }
+            }
+            page_ids.append(&mut level);
+            level = children;
+        }
+        page_ids.push(self.header_page_id);
+        self.bpm.delete_pages(&page_ids)
+    }
+
+    pub fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        let key = key.to_key_bytes();
+        let header = self.bpm.fetch_page_read(self.header_page_id)?;
+        let Some(guard) = self.find_leaf(header, Some(&key))? else {
+            return Ok(None);
+        };
+        let leaf = LeafPage::<_, V>::new(guard.data());
+        Ok(leaf.search(&key).ok().map(|index| leaf.value_at(index)))
+    }
+
+    /// Descend to the leaf that may contain `key`, or to the leftmost leaf if `key` is None,
+    /// latching each node before releasing its parent.
+    fn find_leaf(
+        &self,
+        header: ReadPageGuard<'a, B>,
+        key: Option<&[u8]>,
+    ) -> anyhow::Result<Option<ReadPageGuard<'a, B>>> {
+        let Some(root_page_id) = root_page_id(header.data()) else {
+            return Ok(None);
+        };
+        let mut guard = self.bpm.fetch_page_read(root_page_id)?;
+        drop(header);
+        loop {
+            let node = InternalPage::new(guard.data());
+            if node.is_leaf() {
+                return Ok(Some(guard));
+            }
+            let child = node.value_at(key.map_or(0, |key| node.child_index(key)));
+            guard = self.bpm.fetch_page_read(child)?;
+        }
+    }
+
+    /// Whether `operation` with `key` on a descendant of `node` cannot split or underflow it,
+    /// so that the latches on its ancestors can be released. Removes may also replace a
+    /// separator in the topmost latched node with a longer one.
+    fn is_safe(&self, guard: &[u8], operation: Operation, is_root: bool, key: &[u8]) -> bool {
+        let max_entry_size = self.max_entry_size();
+        if InternalPage::new(guard).is_leaf() {
+            let leaf = LeafPage::<_, V>::new(guard);
+            return match operation {
+                Operation::Insert => leaf.can_insert(key),
+                // A root leaf goes away once it is empty.
+                Operation::Remove if is_root => leaf.size() > 1,
+                Operation::Remove => leaf.used() >= leaf.capacity() / 4 + max_entry_size,
+            };
+        }
+        let node = InternalPage::new(guard);
+        let has_room = node.used() + max_entry_size <= node.capacity();
+        match operation {
+            Operation::Insert => has_room,
+            // A root internal node goes away once it has a single child.
+            Operation::Remove if is_root => node.size() > 2 && has_room,
+            Operation::Remove => {
+                node.size() > 2 && has_room && node.used() >= node.capacity() / 4 + max_entry_size
+            }
+        }
+    }
+
+    /// Write-latch the path from the root to the leaf that may contain `key`, releasing the
+    /// ancestors of every node that is safe for `operation`. The header page stays latched
+    /// only while the root may change.
+    fn find_path(
+        &self,
+        header: WritePageGuard<'a, B>,
+        root_page_id: PageId,
+        key: &[u8],
+        operation: Operation,
+    ) -> anyhow::Result<WritePath<'a, B>> {
+        let mut header = Some(header);
+        let mut path = Vec::new();
+        let mut page_id = root_page_id;
+        loop {
+            let guard = self.bpm.fetch_page_write(page_id)?;
+            if self.is_safe(guard.data(), operation, page_id == root_page_id, key) {
+                header = None;
+                path.clear();
+            }
+            let node = InternalPage::new(guard.data());
+            let child = (!node.is_leaf()).then(|| node.value_at(node.child_index(key)));
+            path.push(guard);
+            match child {
+                Some(child) => page_id = child,
+                None => {
+                    return Ok(WritePath {
+                        header,
+                        nodes: path,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Insert `key` with `value`. Return false if the key is already present, and fail if its
+    /// encoding is longer than [`Self::max_key_size`].
+    pub fn insert(&self, key: &K, value: &V) -> anyhow::Result<bool> {
+        let key = key.to_key_bytes();
+        if key.len() > self.max_key_size() {
+            anyhow::bail!(
+                "key of {} bytes exceeds the maximum of {} bytes for this B+ tree",
+                key.len(),
+                self.max_key_size()
+            );
+        }
+        if let Some(inserted) = self.insert_optimistic(&key, value)? {
+            return Ok(inserted);
+        }
+
+        let mut header = self.bpm.fetch_page_write(self.header_page_id)?;
+        let Some(root_page_id) = root_page_id(header.data()) else {
+            let mut guard = self.bpm.new_page_write()?;
+            let mut leaf =
+                LeafPage::<_, V>::init(guard.data_mut(), PageType::BPlusTreeLeaf, self.node_size);
+            leaf.insert_at(0, &key, value);
+            set_root_page_id(header.data_mut(), Some(guard.page_id()));
+            return Ok(true);
+        };
+
+        let WritePath {
+            header,
+            nodes: mut path,
+        } = self.find_path(header, root_page_id, &key, Operation::Insert)?;
+        let mut leaf_guard = path.pop().unwrap();
+        let index = match LeafPage::<_, V>::new(leaf_guard.data()).search(&key) {
+            Ok(_) => return Ok(false),
+            Err(index) => index,
+        };
+        let left_page_id = leaf_guard.page_id();
+        let mut leaf = LeafPage::<_, V>::new(leaf_guard.data_mut());
+        if leaf.insert_at(index, &key, value) {
+            return Ok(true);
+        }
+
+        // Split the full leaf into two of about the same size, moving the upper part of the
+        // entries to a new right sibling.
+        let mut entries = leaf.entries();
+        entries.insert(index, (key, value.clone()));
+        let mid = LeafPage::split_point(&entries, true, self.node_size)
+            .expect("a node that overflows by one entry can be split");
+        let mut right_guard = self.bpm.new_page_write()?;
+        let right_page_id = right_guard.page_id();
+        let mut right = LeafPage::<_, V>::init(
+            right_guard.data_mut(),
+            PageType::BPlusTreeLeaf,
+            self.node_size,
+        );
+        right.set_entries(&entries[mid..]);
+        right.set_next_page_id(leaf.next_page_id());
+        leaf.set_entries(&entries[..mid]);
+        leaf.set_next_page_id(Some(right_page_id));
+        drop(right_guard);
+        drop(leaf_guard);
+
+        let separator = shortest_separator(&entries[mid - 1].0, &entries[mid].0);
+        self.insert_into_parent(header, path, left_page_id, separator, right_page_id)?;
+        Ok(true)
+    }
+
+    /// Descend with read latches only and write-latch just the leaf, which is enough unless
+    /// the leaf is full. Return None if the insert has to be retried with [`Self::find_path`].
+    fn insert_optimistic(&self, key: &[u8], value: &V) -> anyhow::Result<Option<bool>> {
+        let header = self.bpm.fetch_page_read(self.header_page_id)?;
+        let Some(root_page_id) = root_page_id(header.data()) else {
+            return Ok(None);
+        };
+        // The parent stays read-latched while the leaf is re-latched for writing, so that the
+        // leaf cannot be split or merged in between.
+        let mut parent = header;
+        let mut page_id = root_page_id;
+        loop {
+            let guard = self.bpm.fetch_page_read(page_id)?;
+            let node = InternalPage::new(guard.data());
+            if node.is_leaf() {
+                break;
+            }
+            page_id = node.value_at(node.child_index(key));
+            parent = guard;
+        }
+        let mut guard = self.bpm.fetch_page_write(page_id)?;
+        drop(parent);
+
+        let mut leaf = LeafPage::<_, V>::new(guard.data_mut());
+        let index = match leaf.search(key) {
+            Ok(_) => return Ok(Some(false)),
+            Err(index) => index,
+        };
+        Ok(leaf.insert_at(index, key, value).then_some(true))
+    }
+
+    /// Insert `separator` and the new node `right_page_id` split off from `left_page_id` into
+    /// the parent at the end of `path`, splitting ancestors as long as they are full.
+    fn insert_into_parent(
+        &self,
+        header: Option<WritePageGuard<'a, B>>,
+        mut path: Vec<WritePageGuard<'a, B>>,
+        mut left_page_id: PageId,
+        mut separator: Vec<u8>,
+        mut right_page_id: PageId,
+    ) -> anyhow::Result<()> {
+        loop {
+            let Some(mut parent_guard) = path.pop() else {
+                let mut header = header.expect("the header stays latched while the root is full");
+                let mut guard = self.bpm.new_page_write()?;
+                let mut root = InternalPage::init(
+                    guard.data_mut(),
+                    PageType::BPlusTreeInternal,
+                    self.node_size,
+                );
+                root.set_entries(&[(Vec::new(), left_page_id), (separator, right_page_id)]);
+                set_root_page_id(header.data_mut(), Some(guard.page_id()));
+                return Ok(());
+            };
+
+            let parent_page_id = parent_guard.page_id();
+            let mut parent = InternalPage::new(parent_guard.data_mut());
+            // The separator routes to the left node, so the new node goes right after it.
+            let index = parent.child_index(&separator) + 1;
+            if parent.insert_at(index, &separator, &right_page_id) {
+                return Ok(());
+            }
+
+            let mut entries = parent.entries();
+            entries.insert(index, (separator, right_page_id));
+            let mid = InternalPage::split_point(&entries, false, self.node_size)
+                .expect("a node that overflows by one entry can be split");
+            let mut new_guard = self.bpm.new_page_write()?;
+            let new_page_id = new_guard.page_id();
+            let mut new_node = InternalPage::init(
+                new_guard.data_mut(),
+                PageType::BPlusTreeInternal,
+                self.node_size,
+            );
+            // The first key of the new node is unused; it moves up as the separator.
+            new_node.set_entries(&entries[mid..]);
+            parent.set_entries(&entries[..mid]);
+
+            separator = entries.swap_remove(mid).0;
+            left_page_id = parent_page_id;
+            right_page_id = new_page_id;
+        }
+    }
+
+    /// Remove `key`. Return false if it is not present.
+    pub fn remove(&self, key: &K) -> anyhow::Result<bool> {
+        let key = key.to_key_bytes();
+        let header = self.bpm.fetch_page_write(self.header_page_id)?;
+        let Some(root_page_id) = root_page_id(header.data()) else {
+            return Ok(false);
+        };
+
+        let WritePath {
+            header,
+            nodes: mut path,
+        } = self.find_path(header, root_page_id, &key, Operation::Remove)?;
+        let leaf_guard = path.last_mut().unwrap();
+        let Ok(index) = LeafPage::<_, V>::new(leaf_guard.data()).search(&key) else {
+            return Ok(false);
+        };
+        LeafPage::<_, V>::new(leaf_guard.data_mut()).remove_at(index);
+        self.rebalance(header, path, &key)?;
+        Ok(true)
+    }
+
+    /// Restore the occupancy of the last node of `path`, which `key` routes to, by merging it
+    /// with a sibling or moving entries over from one. Merges remove an entry from the parent,
+    /// so this walks up the path for as long as nodes underflow.
+    ///
+    /// Entries are only moved between siblings when the new separator fits into the parent,
+    /// so with long keys a node may stay below the minimum occupancy, but it never stays empty.
+    fn rebalance(
+        &self,
+        header: Option<WritePageGuard<'a, B>>,
+        mut path: Vec<WritePageGuard<'a, B>>,
+        key: &[u8],
+    ) -> anyhow::Result<()> {
+        while let Some(guard) = path.pop() {
+            let node = InternalPage::new(guard.data());
+            let (is_leaf, size) = (node.is_leaf(), node.size());
+
+            let Some(parent_guard) = path.last_mut() else {
+                // The topmost latched node is the root if the header is still latched, and
+                // otherwise a node that was safe to remove from.
+                let Some(mut header) = header else {
+                    return Ok(());
+                };
+                // The root may hold fewer entries; it only goes away once it is empty, or is
+                // replaced by its only child.
+                let new_root_page_id = match (is_leaf, size) {
+                    (true, 0) => None,
+                    (false, 1) => Some(node.value_at(0)),
+                    _ => return Ok(()),
+                };
+                set_root_page_id(header.data_mut(), new_root_page_id);
+                let page_id = guard.page_id();
+                drop(guard);
+                self.bpm.delete_page(page_id);
+                return Ok(());
+            };
+            if !node.is_underfull() && (is_leaf || size >= 2) {
+                return Ok(());
+            }
+
+            // Pair the node with its left sibling, or with its right one if it is the first
+            // child. Siblings are only latched while their parent is write-latched.
+            let parent = InternalPage::new(parent_guard.data());
+            if parent.size() < 2 {
+                return Ok(());
+            }
+            let index = parent.child_index(key);
+            let right_index = index.max(1);
+            let sibling_page_id = parent.value_at(if index > 0 { index - 1 } else { 1 });
+            let sibling_guard = self.bpm.fetch_page_write(sibling_page_id)?;
+            let (mut left, mut right) = if index > 0 {
+                (sibling_guard, guard)
+            } else {
+                (guard, sibling_guard)
+            };
+
+            let merged = if is_leaf {
+                Self::rebalance_leaves(parent_guard, right_index, &mut left, &mut right)
+            } else {
+                Self::rebalance_internals(parent_guard, right_index, &mut left, &mut right)
+            };
+            if !merged {
+                return Ok(());
+            }
+            let page_id = right.page_id();
+            drop(left);
+            drop(right);
+            self.bpm.delete_page(page_id);
+        }
+
+        Ok(())
+    }
+
+    /// Merge the leaf `right` into `left` if they fit into one node, or divide their entries
+    /// evenly between them. Return whether they were merged.
+    fn rebalance_leaves(
+        parent: &mut WritePageGuard<'a, B>,
+        right_index: usize,
+        left: &mut WritePageGuard<'a, B>,
+        right: &mut WritePageGuard<'a, B>,
+    ) -> bool {
+        let mut parent = InternalPage::new(parent.data_mut());
+        let mut left = LeafPage::<_, V>::new(left.data_mut());
+        let mut right = LeafPage::<_, V>::new(right.data_mut());
+        let left_size = left.size();
+        let mut entries = left.entries();
+        entries.extend(right.entries());
+
+        if LeafPage::size_of(&entries, true) <= left.capacity() {
+            left.set_entries(&entries);
+            left.set_next_page_id(right.next_page_id());
+            parent.remove_at(right_index);
+            return true;
+        }
+        let Some(mid) = LeafPage::split_point(&entries, true, left.capacity()) else {
+            return false;
+        };
+        if mid == left_size
+            || !parent.set_key_at(
+                right_index,
+                &shortest_separator(&entries[mid - 1].0, &entries[mid].0),
+            )
+        {
+            return false;
+        }
+        left.set_entries(&entries[..mid]);
+        right.set_entries(&entries[mid..]);
+        false
+    }
+
+    /// Like [`Self::rebalance_leaves`] for internal nodes, where the separator in the parent
+    /// moves down into the merged node, or rotates through the parent when redistributing.
+    fn rebalance_internals(
+        parent: &mut WritePageGuard<'a, B>,
+        right_index: usize,
+        left: &mut WritePageGuard<'a, B>,
+        right: &mut WritePageGuard<'a, B>,
+    ) -> bool {
+        let mut parent = InternalPage::new(parent.data_mut());
+        let mut left = InternalPage::new(left.data_mut());
+        let mut right = InternalPage::new(right.data_mut());
+        let left_size = left.size();
+        let mut right_entries = right.entries();
+        right_entries[0].0 = parent.key_at(right_index);
+        let mut entries = left.entries();
+        entries.extend(right_entries);
+
+        if InternalPage::size_of(&entries, false) <= left.capacity() {
+            left.set_entries(&entries);
+            parent.remove_at(right_index);
+            return true;
+        }
+        let Some(mid) = InternalPage::split_point(&entries, false, left.capacity()) else {
+            return false;
+        };
+        if mid == left_size || !parent.set_key_at(right_index, &entries[mid].0) {
+            return false;
+        }
+        left.set_entries(&entries[..mid]);
+        right.set_entries(&entries[mid..]);
+        false
+    }
+
+    /// Copy the entries after `lower` out of the first leaf that has any.
+    pub(crate) fn scan_leaf(&self, lower: Bound<&[u8]>) -> anyhow::Result<Entries<V>> {
+        loop {
+            if let Some(entries) = self.try_scan_leaf(lower)? {
+                return Ok(entries);
+            }
+            // A writer holds the next leaf and may be waiting for the one we held.
+            std::thread::yield_now();
+        }
+    }
+
+    /// Like [`Self::scan_leaf`], but give up with None instead of waiting for the latch of a
+    /// right sibling. Writers latch a left sibling while holding its right neighbour, so
+    /// waiting for leaves left to right could deadlock.
+    fn try_scan_leaf(&self, lower: Bound<&[u8]>) -> anyhow::Result<Option<Entries<V>>> {
+        let header = self.bpm.fetch_page_read(self.header_page_id)?;
+        let key = match lower {
+            Bound::Included(key) | Bound::Excluded(key) => Some(key),
+            Bound::Unbounded => None,
+        };
+        let Some(mut guard) = self.find_leaf(header, key)? else {
+            return Ok(Some(Vec::new()));
+        };
+        loop {
+            let leaf = LeafPage::<_, V>::new(guard.data());
+            let start = match lower {
+                Bound::Included(key) => leaf.search(key).unwrap_or_else(|index| index),
+                Bound::Excluded(key) => leaf.search(key).map_or_else(|index| index, |i| i + 1),
+                Bound::Unbounded => 0,
+            };
+            if start < leaf.size() {
+                return Ok(Some(
+                    (start..leaf.size())
+                        .map(|i| (leaf.key_at(i), leaf.value_at(i)))
+                        .collect(),
+                ));
+            }
+            let Some(next_page_id) = leaf.next_page_id() else {
+                return Ok(Some(Vec::new()));
+            };
+            match self.bpm.try_fetch_page_read(next_page_id)? {
+                Some(next) => guard = next,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Iterate over the entries whose keys are in `range`, in key order.
+    pub fn range(&self, range: impl RangeBounds<K>) -> BPlusTreeIterator<'_, 'a, K, V, B> {
+        BPlusTreeIterator::new(
+            self,
+            range.start_bound().map(K::to_key_bytes),
+            range.end_bound().map(K::to_key_bytes),
+        )
+    }
+
+    pub fn iter(&self) -> BPlusTreeIterator<'_, 'a, K, V, B> {
+        self.range(..)
+    }
+
+    /// Fill an empty tree with `entries`, which may come in any order and must have unique
+    /// keys. The entries are sorted, spilling to pages if they exceed `config.sort_memory`, and
+    /// the tree is then built bottom-up, one level at a time, from nodes filled to
+    /// `config.fill_factor` of their bytes. The header stays latched until the tree is
+    /// complete, and the nodes written so far are freed if it cannot be.
+    pub fn bulk_load(
+        &self,
+        entries: impl IntoIterator<Item = (K, V)>,
+        config: BulkLoadConfig,
+    ) -> anyhow::Result<()> {
+        self.bulk_load_with_temp(self.bpm, entries, config)
+    }
+
+    /// Like [`Self::bulk_load`], but spill the sorted runs to `temp`, such as a
+    /// [`TempBufferPool`](crate::buffer::buffer_pool_manager::TempBufferPool), instead of the
+    /// pages of the tree.
+    pub fn bulk_load_with_temp<T: BufferPoolManager + ?Sized>(
+        &self,
+        temp: &T,
+        entries: impl IntoIterator<Item = (K, V)>,
+        config: BulkLoadConfig,
+    ) -> anyhow::Result<()> {
+        if !(config.fill_factor > 0.0 && config.fill_factor <= 1.0) {
+            anyhow::bail!("fill factor must be within (0, 1]");
+        }
+        let mut header = self.bpm.fetch_page_write(self.header_page_id)?;
+        if root_page_id(header.data()).is_some() {
+            anyhow::bail!("cannot bulk load into a non-empty B+ tree");
+        }
+
+        // Entries are sorted as their encoded key followed by the fixed-width value.
+        let mut sorter =
+            ExternalSorter::new(temp, config.sort_memory, |a: &Vec<u8>, b: &Vec<u8>| {
+                a[..a.len() - V::SIZE].cmp(&b[..b.len() - V::SIZE])
+            });
+        for (key, value) in entries {
+            let mut record = key.to_key_bytes();
+            if record.len() > self.max_key_size() {
+                anyhow::bail!(
+                    "key of {} bytes exceeds the maximum of {} bytes for this B+ tree",
+                    record.len(),
+                    self.max_key_size()
+                );
+            }
+            record.resize(record.len() + V::SIZE, 0);
+            let len = record.len();
+            value.write_to(&mut record[len - V::SIZE..]);
+            sorter.push(record)?;
+        }
+        let sorted = sorter.finish()?.map(|record| {
+            record.map(|record| {
+                let (key, value) = record.split_at(record.len() - V::SIZE);
+                (key.to_vec(), V::read_from(value))
+            })
+        });
+        let mut written = Vec::new();
+        let build = || {
+            let mut level = self.write_leaves(sorted, config.fill_factor, &mut written)?;
+            while level.len() > 1 {
+                level = self.write_internals(level, config.fill_factor, &mut written)?;
+            }
+            anyhow::Ok(level)
+        };
+        let level = match build() {
+            Ok(level) => level,
+            Err(e) => {
+                // Nothing reaches the nodes without a root.
+                self.bpm.delete_pages(&written)?;
+                return Err(e);
+            }
+        };
+        set_root_page_id(
+            header.data_mut(),
+            level.first().map(|(_, page_id)| *page_id),
+        );
+        Ok(())
+    }
+
+    /// Write the sorted entries to linked leaves and return the separator before each leaf
+    /// with its page id. The page ids are added to `written` as the leaves are created.
+    fn write_leaves(
+        &self,
+        entries: impl Iterator<Item = anyhow::Result<(Vec<u8>, V)>>,
+        fill_factor: f64,
+        written: &mut Vec<PageId>,
+    ) -> anyhow::Result<Vec<(Vec<u8>, PageId)>> {
+        let mut packer = NodePacker::new(true, self.node_size, fill_factor);
+        let mut children = Vec::new();
+        let mut previous: Option<(WritePageGuard<'a, B>, Vec<u8>)> = None;
+        let mut write = |entries: Vec<(Vec<u8>, V)>| -> anyhow::Result<()> {
+            let mut guard = self.bpm.new_page_write()?;
+            written.push(guard.page_id());
+            let mut leaf =
+                LeafPage::<_, V>::init(guard.data_mut(), PageType::BPlusTreeLeaf, self.node_size);
+            leaf.set_entries(&entries);
+            let separator = match previous.take() {
+                Some((mut previous, last_key)) => {
+                    LeafPage::<_, V>::new(previous.data_mut())
+                        .set_next_page_id(Some(guard.page_id()));
+                    shortest_separator(&last_key, &entries[0].0)
+                }
+                None => Vec::new(),
+            };
+            children.push((separator, guard.page_id()));
+            previous = Some((guard, entries.last().unwrap().0.clone()));
+            Ok(())
+        };
+
+        let mut last_key: Option<Vec<u8>> = None;
+        for entry in entries {
+            let (key, value) = entry?;
+            if last_key.as_ref() == Some(&key) {
+                anyhow::bail!("cannot bulk load duplicate keys into a B+ tree");
+            }
+            last_key = Some(key.clone());
+            if let Some(node) = packer.push((key, value)) {
+                write(node)?;
+            }
+        }
+        for node in packer.finish() {
+            write(node)?;
+        }
+        Ok(children)
+    }
+
+    /// Write internal nodes over `children` and return the separator before each node with
+    /// its page id. The page ids are added to `written` as the nodes are created.
+    fn write_internals(
+        &self,
+        children: Vec<(Vec<u8>, PageId)>,
+        fill_factor: f64,
+        written: &mut Vec<PageId>,
+    ) -> anyhow::Result<Vec<(Vec<u8>, PageId)>> {
+        let mut packer = NodePacker::new(false, self.node_size, fill_factor);
+        let mut parents = Vec::new();
+        let mut write = |mut entries: Vec<(Vec<u8>, PageId)>| -> anyhow::Result<()> {
+            let mut guard = self.bpm.new_page_write()?;
+            written.push(guard.page_id());
+            let mut node = InternalPage::init(
+                guard.data_mut(),
+                PageType::BPlusTreeInternal,
+                self.node_size,
+            );
+            node.set_entries(&entries);
+            // The first key of the node is unused; it moves up as the separator.
+            parents.push((std::mem::take(&mut entries[0].0), guard.page_id()));
+            Ok(())
+        };
+
+        for child in children {
+            if let Some(node) = packer.push(child) {
+                write(node)?;
+            }
+        }
+        for node in packer.finish() {
+            write(node)?;
+        }
+        Ok(parents)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::{seq::SliceRandom, Rng, SeedableRng};
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    /// Check the structural invariants of the tree: sorted keys within the bounds given by the
+    /// parents, nodes within their capacity, no empty nodes besides the root, uniform depth and
+    /// leaf links in key order. Return the number of entries.
+    pub(crate) fn check_invariants<K, V, B>(tree: &BPlusTree<'_, K, V, B>) -> usize
+    where
+        K: IndexKey,
+        V: Storable + Clone,
+        B: BufferPoolManager + ?Sized,
+    {
+        /// Return the number of entries and the height of the subtree.
+        fn check<K, V, B>(
+            tree: &BPlusTree<'_, K, V, B>,
+            page_id: PageId,
+            is_root: bool,
+            lower: Option<&[u8]>,
+            upper: Option<&[u8]>,
+            leaves: &mut Vec<PageId>,
+        ) -> (usize, usize)
+        where
+            K: IndexKey,
+            V: Storable + Clone,
+            B: BufferPoolManager + ?Sized,
+        {
+            let guard = tree.bpm.fetch_page_read(page_id).unwrap();
+            let node = InternalPage::new(guard.data());
+            assert!(node.used() <= node.capacity());
+            assert!(is_root || node.size() > 0, "{page_id:?} is empty");
+            if node.is_leaf() {
+                let leaf = LeafPage::<_, V>::new(guard.data());
+                let keys = (0..leaf.size()).map(|i| leaf.key_at(i)).collect::<Vec<_>>();
+                assert!(keys.windows(2).all(|w| w[0] < w[1]), "{keys:?}");
+                assert!(keys
+                    .iter()
+                    .all(|key| lower.map_or(true, |lower| key.as_slice() >= lower)
+                        && upper.map_or(true, |upper| key.as_slice() < upper)));
+                leaves.push(page_id);
+                return (keys.len(), 0);
+            }
+
+            let entries = node.entries();
+            drop(guard);
+            assert!(!is_root || entries.len() >= 2);
+            let mut count = 0;
+            let mut height = None;
+            for (i, (_, child)) in entries.iter().enumerate() {
+                let child_lower = if i == 0 {
+                    lower
+                } else {
+                    Some(entries[i].0.as_slice())
+                };
+                let child_upper = entries.get(i + 1).map(|(key, _)| key.as_slice()).or(upper);
+                let (child_count, child_height) =
+                    check(tree, *child, false, child_lower, child_upper, leaves);
+                count += child_count;
+                assert_eq!(*height.get_or_insert(child_height), child_height);
+            }
+            (count, height.unwrap() + 1)
+        }
+
+        let Some(root_page_id) = tree.root_page_id().unwrap() else {
+            return 0;
+        };
+        let mut leaves = Vec::new();
+        let (count, _) = check(tree, root_page_id, true, None, None, &mut leaves);
+        for pair in leaves.windows(2) {
+            let guard = tree.bpm.fetch_page_read(pair[0]).unwrap();
+            assert_eq!(
+                LeafPage::<_, V>::new(guard.data()).next_page_id(),
+                Some(pair[1])
+            );
+        }
+        let guard = tree.bpm.fetch_page_read(*leaves.last().unwrap()).unwrap();
+        assert_eq!(LeafPage::<_, V>::new(guard.data()).next_page_id(), None);
+        count
+    }
+
+    /// The pages of the nodes on each level of the tree, from the root down to the leaves.
+    fn levels<K, V, B>(tree: &BPlusTree<'_, K, V, B>) -> Vec<Vec<PageId>>
+    where
+        K: IndexKey,
+        V: Storable + Clone,
+        B: BufferPoolManager + ?Sized,
+    {
+        let mut levels = vec![vec![tree.root_page_id().unwrap().unwrap()]];
+        loop {
+            let mut children = Vec::new();
+            for page_id in levels.last().unwrap() {
+                let guard = tree.bpm.fetch_page_read(*page_id).unwrap();
+                let node = InternalPage::new(guard.data());
+                if node.is_leaf() {
+                    return levels;
+                }
+                children.extend(node.entries().into_iter().map(|(_, child)| child));
+            }
+            levels.push(children);
+        }
+    }
+
+    #[test]
+    fn test_insert_get_iterate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        // The tree spans far more pages than the buffer pool holds.
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let tree = BPlusTree::<u64, u64, _>::create_with_node_size(&bpm, MIN_NODE_SIZE).unwrap();
+        assert!(tree.is_empty().unwrap());
+        assert_eq!(tree.get(&1).unwrap(), None);
+
+        const N: u64 = 3000;
+        let mut keys = (0..N).collect::<Vec<_>>();
+        keys.shuffle(&mut rand::rngs::StdRng::seed_from_u64(0));
+        for key in &keys {
+            assert!(tree.insert(&(key * 2), &(key + 100)).unwrap());
+        }
+        assert!(!tree.insert(&10, &0).unwrap());
+        assert!(bpm.disk_manager().num_pages() > 16 * 10);
+        assert_eq!(check_invariants(&tree), N as usize);
+
+        for key in 0..N {
+            assert_eq!(tree.get(&(key * 2)).unwrap(), Some(key + 100));
+            assert_eq!(tree.get(&(key * 2 + 1)).unwrap(), None);
+        }
+        let all = tree.iter().map(|e| e.unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            all,
+            (0..N).map(|key| (key * 2, key + 100)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let tree = BPlusTree::<i64, u32, _>::create_with_node_size(&bpm, MIN_NODE_SIZE).unwrap();
+        for key in (-100..100).step_by(2) {
+            tree.insert(&key, &(key as u32)).unwrap();
+        }
+
+        let keys = |iter: BPlusTreeIterator<'_, '_, i64, u32, _>| {
+            iter.map(|e| e.unwrap().0).collect::<Vec<_>>()
+        };
+        assert_eq!(keys(tree.range(-5..5)), vec![-4, -2, 0, 2, 4]);
+        assert_eq!(keys(tree.range(-4..=4)), vec![-4, -2, 0, 2, 4]);
+        assert_eq!(
+            keys(tree.range((Bound::Excluded(-4), Bound::Excluded(4)))),
+            vec![-2, 0, 2]
+        );
+        assert_eq!(keys(tree.range(95..)), vec![96, 98]);
+        assert_eq!(keys(tree.range(..-95)), vec![-100, -98, -96]);
+        assert_eq!(keys(tree.range(100..)), Vec::<i64>::new());
+        assert_eq!(keys(tree.range(3..3)), Vec::<i64>::new());
+        assert_eq!(tree.iter().count(), 100);
+    }
+
+    #[test]
+    fn test_remove() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let tree = BPlusTree::<u32, u32, _>::create_with_node_size(&bpm, MIN_NODE_SIZE).unwrap();
+
+        const N: u32 = 2000;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let mut keys = (0..N).collect::<Vec<_>>();
+        keys.shuffle(&mut rng);
+        for key in &keys {
+            tree.insert(key, &(key * 3)).unwrap();
+        }
+
+        // Removing every other key exercises both borrowing from and merging with siblings.
+        keys.shuffle(&mut rng);
+        let (removed, kept) = keys.split_at(N as usize / 2);
+        for key in removed {
+            assert!(tree.remove(key).unwrap());
+            assert!(!tree.remove(key).unwrap());
+        }
+        assert_eq!(check_invariants(&tree), kept.len());
+        for key in removed {
+            assert_eq!(tree.get(key).unwrap(), None);
+        }
+        for key in kept {
+            assert_eq!(tree.get(key).unwrap(), Some(key * 3));
+        }
+        let mut expected = kept.to_vec();
+        expected.sort();
+        assert_eq!(
+            tree.iter().map(|e| e.unwrap().0).collect::<Vec<_>>(),
+            expected
+        );
+
+        for key in kept {
+            assert!(tree.remove(key).unwrap());
+        }
+        assert!(tree.is_empty().unwrap());
+        assert_eq!(tree.iter().count(), 0);
+
+        // The tree can grow again after becoming empty.
+        tree.insert(&7, &7).unwrap();
+        assert_eq!(tree.get(&7).unwrap(), Some(7));
+    }
+
+    #[test]
+    fn test_reopen_after_restart() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let header_page_id = {
+            let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+            let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+            let tree = BPlusTree::<u64, [u8; 8], _>::create(&bpm).unwrap();
+            for key in 0..5000u64 {
+                tree.insert(&key, &key.to_be_bytes()).unwrap();
+            }
+            tree.header_page_id()
+        };
+
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        assert!(BPlusTree::<u64, [u8; 4], _>::open(&bpm, header_page_id).is_err());
+        let tree = BPlusTree::<u64, [u8; 8], _>::open(&bpm, header_page_id).unwrap();
+        assert_eq!(check_invariants(&tree), 5000);
+        for key in 0..5000u64 {
+            assert_eq!(tree.get(&key).unwrap(), Some(key.to_be_bytes()));
+        }
+        assert!(BPlusTree::<u64, u64, _>::create_with_node_size(&bpm, 100).is_err());
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let tree = BPlusTree::<u64, u64, _>::create_with_node_size(&bpm, MIN_NODE_SIZE).unwrap();
+
+        const N: u64 = 20000;
+        let mut keys = (0..N).collect::<Vec<_>>();
+        keys.shuffle(&mut rand::rngs::StdRng::seed_from_u64(2));
+        let config = BulkLoadConfig::new()
+            .with_fill_factor(0.75)
+            .with_sort_memory(1000);
+        tree.bulk_load(keys.iter().map(|key| (key * 2, key + 1)), config)
+            .unwrap();
+        assert_eq!(check_invariants(&tree), N as usize);
+        for key in 0..N {
+            assert_eq!(tree.get(&(key * 2)).unwrap(), Some(key + 1));
+        }
+
+        // Every leaf but the last two is filled to three quarters of its bytes, give or take
+        // an entry.
+        let leaves = levels(&tree).pop().unwrap();
+        for page_id in &leaves[..leaves.len() - 2] {
+            let guard = bpm.fetch_page_read(*page_id).unwrap();
+            let used = LeafPage::<_, u64>::new(guard.data()).used();
+            assert!((MIN_NODE_SIZE * 3 / 4 - 20..=MIN_NODE_SIZE * 3 / 4).contains(&used));
+        }
+
+        // The loaded tree stays fully usable.
+        for key in 0..N {
+            tree.insert(&(key * 2 + 1), &0).unwrap();
+        }
+        for key in 0..N / 2 {
+            assert!(tree.remove(&(key * 2)).unwrap());
+        }
+        assert_eq!(check_invariants(&tree), (N + N / 2) as usize);
+
+        assert!(tree.bulk_load([(0, 0)], BulkLoadConfig::new()).is_err());
+        let tree = BPlusTree::<u64, u64, _>::create(&bpm).unwrap();
+        assert!(tree
+            .bulk_load([(1, 1), (1, 2)], BulkLoadConfig::new())
+            .is_err());
+        assert!(tree
+            .bulk_load([(1, 1)], BulkLoadConfig::new().with_fill_factor(0.0))
+            .is_err());
+        tree.bulk_load([], BulkLoadConfig::new()).unwrap();
+        assert!(tree.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_concurrent_insert_remove_scan() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(128, disk_manager);
+        let tree = BPlusTree::<u64, u64, _>::create_with_node_size(&bpm, MIN_NODE_SIZE).unwrap();
+
+        const THREADS: u64 = 8;
+        const KEYS_PER_THREAD: u64 = 1500;
+        std::thread::scope(|s| {
+            for thread in 0..THREADS {
+                let tree = &tree;
+                s.spawn(move || {
+                    let mut rng = rand::rngs::StdRng::seed_from_u64(thread);
+                    // Every thread owns the keys congruent to its number, so that each thread
+                    // knows which of its own keys must be present.
+                    let mut keys = (0..KEYS_PER_THREAD)
+                        .map(|i| i * THREADS + thread)
+                        .collect::<Vec<_>>();
+                    keys.shuffle(&mut rng);
+                    for (i, key) in keys.iter().enumerate() {
+                        assert!(tree.insert(key, &(key * 10)).unwrap());
+                        // Remove the keys divisible by 3 again, some time after inserting them.
+                        if i >= 20 && keys[i - 20] % 3 == 0 {
+                            assert!(tree.remove(&keys[i - 20]).unwrap());
+                        }
+                        if i % 50 == 0 {
+                            let start = rng.gen_range(0..KEYS_PER_THREAD * THREADS);
+                            let mut previous = None;
+                            for entry in tree.range(start..start + 200) {
+                                let (key, value) = entry.unwrap();
+                                assert_eq!(value, key * 10);
+                                assert!(previous < Some(key));
+                                previous = Some(key);
+                            }
+                            assert_eq!(tree.get(key).unwrap(), Some(key * 10));
+                        }
+                    }
+                    for key in &keys[keys.len() - 20..] {
+                        if key % 3 == 0 {
+                            assert!(tree.remove(key).unwrap());
+                        }
+                    }
+                });
+            }
+        });
+
+        let expected = (0..KEYS_PER_THREAD * THREADS)
+            .filter(|key| key % 3 != 0)
+            .collect::<Vec<_>>();
+        assert_eq!(check_invariants(&tree), expected.len());
+        assert_eq!(
+            tree.iter().map(|e| e.unwrap().0).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_variable_length_keys() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(32, disk_manager);
+
+        // Keys that share a long prefix are stored once per leaf, so that the fan-out stays
+        // close to that of short keys.
+        let tree = BPlusTree::<String, u32, _>::create(&bpm).unwrap();
+        let url = |i: u32| format!("https://example.com/{}/item/{i:05}", "path/".repeat(30));
+        const N: u32 = 5000;
+        let mut ids = (0..N).collect::<Vec<_>>();
+        ids.shuffle(&mut rand::rngs::StdRng::seed_from_u64(3));
+        for i in &ids {
+            assert!(tree.insert(&url(*i), i).unwrap());
+        }
+        assert_eq!(check_invariants(&tree), N as usize);
+        let leaves = levels(&tree).pop().unwrap();
+        let uncompressed_size = N as usize * (url(0).len() + 2 + 8);
+        assert!(leaves.len() < uncompressed_size / DEFAULT_PAGE_SIZE / 4);
+        assert_eq!(tree.get(&url(1234)).unwrap(), Some(1234));
+        assert_eq!(tree.get(&url(N)).unwrap(), None);
+        let range = tree.range(url(10)..url(13)).map(|e| e.unwrap().1);
+        assert_eq!(range.collect::<Vec<_>>(), vec![10, 11, 12]);
+
+        // Separators are truncated to the bytes that tell neighbouring leaves apart, however
+        // long the keys are.
+        let tree = BPlusTree::<(u32, String), u32, _>::create(&bpm).unwrap();
+        let tail = "x".repeat(500);
+        for i in &ids {
+            assert!(tree.insert(&(*i, tail.clone()), i).unwrap());
+        }
+        assert_eq!(check_invariants(&tree), N as usize);
+        let levels = levels(&tree);
+        assert_eq!(levels.len(), 2);
+        let guard = bpm.fetch_page_read(levels[0][0]).unwrap();
+        let root = InternalPage::new(guard.data());
+        assert!(root.size() > 100);
+        assert!((1..root.size()).all(|i| root.key_at(i).len() <= 5));
+        drop(guard);
+
+        // Removing keys of different lengths merges and refills nodes by their bytes.
+        ids.shuffle(&mut rand::rngs::StdRng::seed_from_u64(4));
+        let (removed, kept) = ids.split_at(N as usize - 100);
+        for i in removed {
+            assert!(tree.remove(&(*i, tail.clone())).unwrap());
+        }
+        assert_eq!(check_invariants(&tree), kept.len());
+        for i in kept {
+            assert_eq!(tree.get(&(*i, tail.clone())).unwrap(), Some(*i));
+        }
+
+        let too_long = "x".repeat(tree.max_key_size());
+        assert!(tree.insert(&(0, too_long), &0).is_err());
+    }
+}
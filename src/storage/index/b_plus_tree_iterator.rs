@@ -0,0 +1,87 @@
+use std::{collections::VecDeque, ops::Bound};
+
+use crate::buffer::buffer_pool_manager::BufferPoolManager;
+
+use super::{b_plus_tree::BPlusTree, index_key::IndexKey, storable::Storable};
+
+/// Yields the entries of a [`BPlusTree`] in a key range, in key order.
+///
+/// The entries of one leaf are copied out at a time. The next batch is found by descending
+/// from the root again with the last key returned, so no page stays pinned or latched between
+/// calls to `next` and the tree can be modified while it is being iterated. Bounds and keys
+/// are compared in their encoded form and decoded only when returned.
+pub struct BPlusTreeIterator<'t, 'a, K, V, B: BufferPoolManager + ?Sized> {
+    tree: &'t BPlusTree<'a, K, V, B>,
+    lower: Bound<Vec<u8>>,
+    upper: Bound<Vec<u8>>,
+    buffered: VecDeque<(Vec<u8>, V)>,
+    done: bool,
+}
+
+impl<'t, 'a, K, V, B> BPlusTreeIterator<'t, 'a, K, V, B>
+where
+    K: IndexKey,
+    V: Storable + Clone,
+    B: BufferPoolManager + ?Sized,
+{
+    pub(crate) fn new(
+        tree: &'t BPlusTree<'a, K, V, B>,
+        lower: Bound<Vec<u8>>,
+        upper: Bound<Vec<u8>>,
+    ) -> Self {
+        Self {
+            tree,
+            lower,
+            upper,
+            buffered: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn is_below_upper(&self, key: &[u8]) -> bool {
+        match &self.upper {
+            Bound::Included(upper) => key <= upper.as_slice(),
+            Bound::Excluded(upper) => key < upper.as_slice(),
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<K, V, B> Iterator for BPlusTreeIterator<'_, '_, K, V, B>
+where
+    K: IndexKey,
+    V: Storable + Clone,
+    B: BufferPoolManager + ?Sized,
+{
+    type Item = anyhow::Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, value)) = self.buffered.pop_front() {
+                if !self.is_below_upper(&key) {
+                    self.done = true;
+                    self.buffered.clear();
+                    return None;
+                }
+                return Some(Ok((K::from_key_bytes(&key), value)));
+            }
+            if self.done {
+                return None;
+            }
+            match self.tree.scan_leaf(self.lower.as_ref().map(Vec::as_slice)) {
+                Ok(entries) => {
+                    let Some((last, _)) = entries.last() else {
+                        self.done = true;
+                        return None;
+                    };
+                    self.lower = Bound::Excluded(last.clone());
+                    self.buffered.extend(entries);
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
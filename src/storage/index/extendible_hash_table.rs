@@ -0,0 +1,790 @@
+//! An extendible hash index whose directory and buckets are buffer-pool pages.
+//!
+//! The table is identified by its directory page, which maps the low bits of a key's hash to
+//! bucket pages; see [`hash_table_page`](crate::storage::page::hash_table_page) for the
+//! layouts. A full bucket is split in two by one more hash bit, doubling the directory when the
+//! bucket already uses all of its bits. A bucket that becomes empty is merged with its split
+//! image, and the directory is halved again once no bucket needs all of its bits.
+//!
+//! Keys are unique when entries are added with [`insert`](ExtendibleHashTable::insert), while
+//! [`insert_entry`](ExtendibleHashTable::insert_entry) only keeps entries unique and lets a key
+//! have several values. A full bucket whose entries all share one hash, which no split can
+//! separate, or that uses every bit the directory can have, continues in a chain of overflow
+//! pages instead. Only the last page of a chain is ever not full.
+//!
+//! Keys are hashed from their stored bytes, so the hash of a key does not change across
+//! restarts. Readers latch the directory and then the bucket, releasing the directory as soon
+//! as the bucket is latched. Writers that do not split or merge a bucket do the same with a
+//! write latch on the bucket; the others hold the directory write latch throughout. Overflow
+//! pages are only reached while the first page of their bucket stays latched.
+
+use std::marker::PhantomData;
+
+use crate::{
+    buffer::{
+        buffer_pool_manager::BufferPoolManager,
+        page_guard::{ReadPageGuard, WritePageGuard},
+    },
+    storage::page::{
+        hash_table_page::{HashBucketPage, HashDirectoryPage},
+        page::PageType,
+    },
+    PageId,
+};
+
+use super::storable::Storable;
+
+/// Hash the stored bytes of `key` with FNV-1a, followed by the MurmurHash3 finalizer so that
+/// the low bits used by the directory depend on every input byte.
+fn hash_key<K: Storable>(key: &K) -> u64 {
+    let mut buf = vec![0; K::SIZE];
+    key.write_to(&mut buf);
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in buf {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+pub struct ExtendibleHashTable<'a, K, V, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    directory_page_id: PageId,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'a, K, V, B> ExtendibleHashTable<'a, K, V, B>
+where
+    K: Storable + Eq + Clone,
+    V: Storable + Clone,
+    B: BufferPoolManager + ?Sized,
+{
+    /// Create an empty table whose buckets are as large as a page allows.
+    pub fn create(bpm: &'a B) -> anyhow::Result<Self> {
+        let directory = bpm.new_page_write()?;
+        let page_size = directory.page_size();
+        Self::init(
+            bpm,
+            directory,
+            HashBucketPage::<&[u8], K, V>::capacity(page_size),
+        )
+    }
+
+    /// Create an empty table whose buckets hold at most `bucket_max_size` entries.
+    pub fn create_with_bucket_max_size(bpm: &'a B, bucket_max_size: usize) -> anyhow::Result<Self> {
+        let directory = bpm.new_page_write()?;
+        let capacity = HashBucketPage::<&[u8], K, V>::capacity(directory.page_size());
+        if !(1..=capacity).contains(&bucket_max_size) {
+            let page_id = directory.page_id();
+            drop(directory);
+            bpm.delete_page(page_id);
+            anyhow::bail!("bucket max size must be within 1..={capacity}");
+        }
+        Self::init(bpm, directory, bucket_max_size)
+    }
+
+    fn init(
+        bpm: &'a B,
+        mut directory: WritePageGuard<'a, B>,
+        bucket_max_size: usize,
+    ) -> anyhow::Result<Self> {
+        let mut bucket = bpm.new_page_write()?;
+        HashBucketPage::<_, K, V>::init(bucket.data_mut(), bucket_max_size);
+        let max_depth = HashDirectoryPage::<&[u8]>::max_depth_for(directory.page_size());
+        HashDirectoryPage::init(
+            directory.data_mut(),
+            max_depth,
+            bucket_max_size,
+            (K::SIZE, V::SIZE),
+            bucket.page_id(),
+        );
+
+        Ok(Self {
+            bpm,
+            directory_page_id: directory.page_id(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Open an existing table through its directory page.
+    pub fn open(bpm: &'a B, directory_page_id: PageId) -> anyhow::Result<Self> {
+        let guard = bpm.fetch_page_read(directory_page_id)?;
+        if PageType::of(guard.data()) != Some(PageType::HashDirectory) {
+            anyhow::bail!("{directory_page_id:?} is not a hash table directory page");
+        }
+        let (key_size, value_size) = HashDirectoryPage::new(guard.data()).entry_sizes();
+        if key_size != K::SIZE || value_size != V::SIZE {
+            anyhow::bail!(
+                "hash table stores {key_size}-byte keys and {value_size}-byte values, \
+                 not {} and {}",
+                K::SIZE,
+                V::SIZE
+            );
+        }
+
+        Ok(Self {
+            bpm,
+            directory_page_id,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn header_page_id(&self) -> PageId {
+        self.directory_page_id
+    }
+
+    /// Free the directory and every bucket page for the buffer pool to reuse. Nobody else may
+    /// use the table.
+    pub fn free_pages(&self) -> anyhow::Result<()> {
+        let guard = self.bpm.fetch_page_read(self.directory_page_id)?;
+        let directory = HashDirectoryPage::new(guard.data());
+        let mut buckets: Vec<PageId> = (0..directory.size())
+            .map(|slot| directory.bucket_page_id(slot))
+            .collect();
+        drop(guard);
+        // Buckets of a lower local depth are referred to by several slots.
+        buckets.sort();
+        buckets.dedup();
+        let mut page_ids = Vec::new();
+        for page_id in buckets {
+            let guard = self.bpm.fetch_page_read(page_id)?;
+            self.search_chain((page_id, guard.data()), |page_id, _| {
+                page_ids.push(page_id);
+                None::<()>
+            })?;
+        }
+        page_ids.push(self.directory_page_id);
+        self.bpm.delete_pages(&page_ids)
+    }
+
+    /// The number of hash bits the directory currently uses.
+    pub fn global_depth(&self) -> anyhow::Result<u32> {
+        let guard = self.bpm.fetch_page_read(self.directory_page_id)?;
+        Ok(HashDirectoryPage::new(guard.data()).global_depth())
+    }
+
+    /// The value of the first entry with `key`.
+    pub fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        let guard = self.read_bucket(hash_key(key))?;
+        self.search_chain((guard.page_id(), guard.data()), |_, bucket| {
+            bucket.find(key).map(|index| bucket.value_at(index))
+        })
+    }
+
+    /// The values of every entry with `key`, of which [`Self::insert_entry`] may have added
+    /// several.
+    pub fn get_all(&self, key: &K) -> anyhow::Result<Vec<V>> {
+        let guard = self.read_bucket(hash_key(key))?;
+        let mut values = Vec::new();
+        self.search_chain((guard.page_id(), guard.data()), |_, bucket| {
+            values.extend(
+                (0..bucket.size())
+                    .filter(|&index| bucket.key_at(index) == *key)
+                    .map(|index| bucket.value_at(index)),
+            );
+            None::<()>
+        })?;
+        Ok(values)
+    }
+
+    /// Read-latch the first page of the bucket that `hash` maps to.
+    fn read_bucket(&self, hash: u64) -> anyhow::Result<ReadPageGuard<'a, B>> {
+        let directory_guard = self.bpm.fetch_page_read(self.directory_page_id)?;
+        let directory = HashDirectoryPage::new(directory_guard.data());
+        self.bpm
+            .fetch_page_read(directory.bucket_page_id(directory.slot_of(hash)))
+    }
+
+    /// Insert an entry, returning false without changing anything if `key` is present.
+    pub fn insert(&self, key: &K, value: &V) -> anyhow::Result<bool> {
+        self.insert_unless(key, value, |other, _| other == key)
+    }
+
+    /// Insert an entry even if `key` is present, returning false without changing anything if
+    /// the same entry is.
+    pub fn insert_entry(&self, key: &K, value: &V) -> anyhow::Result<bool>
+    where
+        V: Eq,
+    {
+        self.insert_unless(key, value, |other_key, other_value| {
+            other_key == key && other_value == value
+        })
+    }
+
+    /// Insert an entry unless `conflicts` holds for an entry of the bucket it belongs to.
+    fn insert_unless(
+        &self,
+        key: &K,
+        value: &V,
+        conflicts: impl Fn(&K, &V) -> bool,
+    ) -> anyhow::Result<bool> {
+        let hash = hash_key(key);
+        {
+            let directory_guard = self.bpm.fetch_page_read(self.directory_page_id)?;
+            let directory = HashDirectoryPage::new(directory_guard.data());
+            let bucket_page_id = directory.bucket_page_id(directory.slot_of(hash));
+            let mut guard = self.bpm.fetch_page_write(bucket_page_id)?;
+            drop(directory_guard);
+
+            match self.find_slot(&guard, &conflicts)? {
+                Slot::Conflict => return Ok(false),
+                Slot::Free(page_id) => {
+                    self.with_bucket(&mut guard, page_id, |bucket| bucket.push(key, value))?;
+                    return Ok(true);
+                }
+                Slot::Full(_) => {}
+            }
+        }
+
+        // The bucket is full: split it, and the buckets the key lands in afterwards, while
+        // holding the directory exclusively. A bucket that splitting cannot relieve continues
+        // in an overflow page instead.
+        let mut directory_guard = self.bpm.fetch_page_write(self.directory_page_id)?;
+        loop {
+            let directory = HashDirectoryPage::new(directory_guard.data());
+            let slot = directory.slot_of(hash);
+            let mut guard = self.bpm.fetch_page_write(directory.bucket_page_id(slot))?;
+            let last = match self.find_slot(&guard, &conflicts)? {
+                Slot::Conflict => return Ok(false),
+                Slot::Free(page_id) => {
+                    self.with_bucket(&mut guard, page_id, |bucket| bucket.push(key, value))?;
+                    return Ok(true);
+                }
+                Slot::Full(last) => last,
+            };
+            // Entries with the same hash stay together however often their bucket is split.
+            let entries = self.chain_entries((guard.page_id(), guard.data()))?;
+            if directory.local_depth(slot) == directory.max_depth()
+                || entries.iter().all(|(other, _)| hash_key(other) == hash)
+            {
+                self.push_overflow(&mut guard, last, key, value)?;
+                return Ok(true);
+            }
+            self.split(&mut directory_guard, slot, &mut guard, entries)?;
+        }
+    }
+
+    /// Split the full bucket `guard`, holding `entries`, that directory slot `slot` points at
+    /// by one more hash bit, growing the directory if needed. The caller must have checked
+    /// that the bucket does not use all the bits the directory can have.
+    fn split(
+        &self,
+        directory_guard: &mut WritePageGuard<'a, B>,
+        slot: usize,
+        guard: &mut WritePageGuard<'a, B>,
+        entries: Vec<(K, V)>,
+    ) -> anyhow::Result<()> {
+        let mut directory = HashDirectoryPage::new(directory_guard.data_mut());
+        let local_depth = directory.local_depth(slot);
+        debug_assert!(local_depth < directory.max_depth());
+        if local_depth == directory.global_depth() {
+            directory.grow();
+        }
+
+        let mut image_guard = self.bpm.new_page_write()?;
+        let overflow = self.chain_page_ids(guard)?.split_off(1);
+        let mut bucket = HashBucketPage::<_, K, V>::new(guard.data_mut());
+        HashBucketPage::<_, K, V>::init(image_guard.data_mut(), bucket.max_size());
+        bucket.clear();
+        bucket.set_next_page_id(None);
+        self.bpm.delete_pages(&overflow)?;
+        let bit = 1 << local_depth;
+        for (key, value) in &entries {
+            if hash_key(key) & bit == 0 {
+                self.push(guard, key, value)?;
+            } else {
+                self.push(&mut image_guard, key, value)?;
+            }
+        }
+
+        let page_id = guard.page_id();
+        for i in 0..directory.size() {
+            if directory.bucket_page_id(i) == page_id {
+                directory.set_local_depth(i, local_depth + 1);
+                if i as u64 & bit != 0 {
+                    directory.set_bucket_page_id(i, image_guard.page_id());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the entry with `key`, returning whether it was present.
+    pub fn remove(&self, key: &K) -> anyhow::Result<bool> {
+        self.remove_where(hash_key(key), |other, _| other == key)
+    }
+
+    /// Remove the entry with `key` and `value`, returning whether it was present.
+    pub fn remove_entry(&self, key: &K, value: &V) -> anyhow::Result<bool>
+    where
+        V: Eq,
+    {
+        self.remove_where(hash_key(key), |other_key, other_value| {
+            other_key == key && other_value == value
+        })
+    }
+
+    /// Remove the first entry that `matches` holds for from the bucket that `hash` maps to,
+    /// returning whether there was one.
+    fn remove_where(&self, hash: u64, matches: impl Fn(&K, &V) -> bool) -> anyhow::Result<bool> {
+        {
+            let directory_guard = self.bpm.fetch_page_read(self.directory_page_id)?;
+            let directory = HashDirectoryPage::new(directory_guard.data());
+            let bucket_page_id = directory.bucket_page_id(directory.slot_of(hash));
+            let mut guard = self.bpm.fetch_page_write(bucket_page_id)?;
+            drop(directory_guard);
+
+            let Some(position) = self.find_entry(&guard, &matches)? else {
+                return Ok(false);
+            };
+            let bucket = HashBucketPage::<_, K, V>::new(guard.data());
+            if bucket.size() > 1 || bucket.next_page_id().is_some() {
+                self.remove_at(&mut guard, position)?;
+                return Ok(true);
+            }
+        }
+
+        // The bucket would become empty: remove the entry and merge while holding the
+        // directory exclusively.
+        let mut directory_guard = self.bpm.fetch_page_write(self.directory_page_id)?;
+        let directory = HashDirectoryPage::new(directory_guard.data());
+        let mut guard = self
+            .bpm
+            .fetch_page_write(directory.bucket_page_id(directory.slot_of(hash)))?;
+        let Some(position) = self.find_entry(&guard, &matches)? else {
+            return Ok(false);
+        };
+        self.remove_at(&mut guard, position)?;
+        drop(guard);
+        self.merge(&mut directory_guard, hash)?;
+        Ok(true)
+    }
+
+    /// Merge the bucket that `hash` maps to with its split images for as long as one of the
+    /// two is empty, then shrink the directory as far as possible.
+    fn merge(&self, directory_guard: &mut WritePageGuard<'a, B>, hash: u64) -> anyhow::Result<()> {
+        let mut directory = HashDirectoryPage::new(directory_guard.data_mut());
+        loop {
+            let slot = directory.slot_of(hash);
+            let local_depth = directory.local_depth(slot);
+            if local_depth == 0 {
+                break;
+            }
+            let image_slot = directory.split_image_of(slot);
+            if directory.local_depth(image_slot) != local_depth {
+                break;
+            }
+            let page_id = directory.bucket_page_id(slot);
+            let image_page_id = directory.bucket_page_id(image_slot);
+            // Only the last page of a chain is ever not full, so an empty bucket has no
+            // overflow pages.
+            let is_empty = |page_id| -> anyhow::Result<bool> {
+                let guard = self.bpm.fetch_page_read(page_id)?;
+                Ok(HashBucketPage::<_, K, V>::new(guard.data()).size() == 0)
+            };
+            let (kept, removed) = if is_empty(page_id)? {
+                (image_page_id, page_id)
+            } else if is_empty(image_page_id)? {
+                (page_id, image_page_id)
+            } else {
+                break;
+            };
+
+            for i in 0..directory.size() {
+                let bucket_page_id = directory.bucket_page_id(i);
+                if bucket_page_id == page_id || bucket_page_id == image_page_id {
+                    directory.set_bucket_page_id(i, kept);
+                    directory.set_local_depth(i, local_depth - 1);
+                }
+            }
+            self.bpm.delete_page(removed);
+        }
+        while directory.can_shrink() {
+            directory.shrink();
+        }
+        Ok(())
+    }
+
+    /// Call `f` with each page of the bucket chain that starts at `first`, a page id and its
+    /// data, which the caller keeps latched, until `f` returns Some.
+    fn search_chain<R>(
+        &self,
+        first: (PageId, &[u8]),
+        mut f: impl FnMut(PageId, &HashBucketPage<&[u8], K, V>) -> Option<R>,
+    ) -> anyhow::Result<Option<R>> {
+        let bucket = HashBucketPage::new(first.1);
+        if let Some(result) = f(first.0, &bucket) {
+            return Ok(Some(result));
+        }
+        let mut next_page_id = bucket.next_page_id();
+        while let Some(page_id) = next_page_id {
+            let guard = self.bpm.fetch_page_read(page_id)?;
+            let bucket = HashBucketPage::new(guard.data());
+            if let Some(result) = f(page_id, &bucket) {
+                return Ok(Some(result));
+            }
+            next_page_id = bucket.next_page_id();
+        }
+        Ok(None)
+    }
+
+    /// The entries of the bucket chain that starts at `first`.
+    fn chain_entries(&self, first: (PageId, &[u8])) -> anyhow::Result<Vec<(K, V)>> {
+        let mut entries = Vec::new();
+        self.search_chain(first, |_, bucket| {
+            entries.extend(bucket.entries());
+            None::<()>
+        })?;
+        Ok(entries)
+    }
+
+    /// The pages of the bucket chain that starts at `first`, in order.
+    fn chain_page_ids(&self, first: &WritePageGuard<'a, B>) -> anyhow::Result<Vec<PageId>> {
+        let mut page_ids = Vec::new();
+        self.search_chain((first.page_id(), first.data()), |page_id, _| {
+            page_ids.push(page_id);
+            None::<()>
+        })?;
+        Ok(page_ids)
+    }
+
+    /// Where an entry goes in the bucket chain that starts at `first`, unless `conflicts`
+    /// holds for one of its entries.
+    fn find_slot(
+        &self,
+        first: &WritePageGuard<'a, B>,
+        conflicts: impl Fn(&K, &V) -> bool,
+    ) -> anyhow::Result<Slot> {
+        let mut last = (first.page_id(), true);
+        let conflict = self.search_chain((first.page_id(), first.data()), |page_id, bucket| {
+            last = (page_id, bucket.is_full());
+            bucket.position(&conflicts)
+        })?;
+        Ok(match (conflict, last) {
+            (Some(_), _) => Slot::Conflict,
+            (None, (page_id, false)) => Slot::Free(page_id),
+            (None, (page_id, true)) => Slot::Full(page_id),
+        })
+    }
+
+    /// The page and the index in it of the first entry of the bucket chain that starts at
+    /// `first` that `matches` holds for.
+    fn find_entry(
+        &self,
+        first: &WritePageGuard<'a, B>,
+        matches: impl Fn(&K, &V) -> bool,
+    ) -> anyhow::Result<Option<(PageId, usize)>> {
+        self.search_chain((first.page_id(), first.data()), |page_id, bucket| {
+            bucket.position(&matches).map(|index| (page_id, index))
+        })
+    }
+
+    /// Call `f` with page `page_id` of the bucket chain whose first page the caller holds as
+    /// `first`, write-latched.
+    fn with_bucket<R>(
+        &self,
+        first: &mut WritePageGuard<'a, B>,
+        page_id: PageId,
+        f: impl FnOnce(&mut HashBucketPage<&mut [u8], K, V>) -> R,
+    ) -> anyhow::Result<R> {
+        if page_id == first.page_id() {
+            return Ok(f(&mut HashBucketPage::new(first.data_mut())));
+        }
+        let mut guard = self.bpm.fetch_page_write(page_id)?;
+        Ok(f(&mut HashBucketPage::new(guard.data_mut())))
+    }
+
+    /// Append an entry to the bucket chain that starts at `first`, continuing it in an
+    /// overflow page if it is full.
+    fn push(&self, first: &mut WritePageGuard<'a, B>, key: &K, value: &V) -> anyhow::Result<()> {
+        match self.find_slot(first, |_, _| false)? {
+            Slot::Free(page_id) => {
+                self.with_bucket(first, page_id, |bucket| bucket.push(key, value))
+            }
+            Slot::Full(last) => self.push_overflow(first, last, key, value),
+            Slot::Conflict => unreachable!("nothing conflicts"),
+        }
+    }
+
+    /// Continue the full bucket chain that starts at `first` and ends at `last` in a new
+    /// overflow page holding the entry.
+    fn push_overflow(
+        &self,
+        first: &mut WritePageGuard<'a, B>,
+        last: PageId,
+        key: &K,
+        value: &V,
+    ) -> anyhow::Result<()> {
+        let max_size = HashBucketPage::<_, K, V>::new(first.data()).max_size();
+        let mut guard = self.bpm.new_page_write()?;
+        HashBucketPage::<_, K, V>::init(guard.data_mut(), max_size).push(key, value);
+        let page_id = guard.page_id();
+        drop(guard);
+        self.with_bucket(first, last, |bucket| bucket.set_next_page_id(Some(page_id)))
+    }
+
+    /// Remove the entry at `position` of the bucket chain that starts at `first`, moving the
+    /// last entry of the chain into its place so that only the last page is ever not full.
+    /// An overflow page left empty is freed.
+    fn remove_at(
+        &self,
+        first: &mut WritePageGuard<'a, B>,
+        (page_id, index): (PageId, usize),
+    ) -> anyhow::Result<()> {
+        let mut previous = None;
+        let mut last = first.page_id();
+        self.search_chain((first.page_id(), first.data()), |page_id, _| {
+            if page_id != last {
+                previous = Some(last);
+                last = page_id;
+            }
+            None::<()>
+        })?;
+
+        let remaining = if page_id == last {
+            self.with_bucket(first, last, |bucket| {
+                bucket.swap_remove(index);
+                bucket.size()
+            })?
+        } else {
+            let (key, value, remaining) = self.with_bucket(first, last, |bucket| {
+                let last_index = bucket.size() - 1;
+                let (key, value) = (bucket.key_at(last_index), bucket.value_at(last_index));
+                bucket.swap_remove(last_index);
+                (key, value, bucket.size())
+            })?;
+            self.with_bucket(first, page_id, |bucket| {
+                bucket.swap_remove(index);
+                bucket.push(&key, &value);
+            })?;
+            remaining
+        };
+        if let (Some(previous), 0) = (previous, remaining) {
+            self.with_bucket(first, previous, |bucket| bucket.set_next_page_id(None))?;
+            self.bpm.delete_pages(&[last])?;
+        }
+        Ok(())
+    }
+}
+
+/// Where [`ExtendibleHashTable::find_slot`] found an entry to go.
+enum Slot {
+    /// An entry conflicts with it.
+    Conflict,
+    /// In this page, the last of the chain, which has room.
+    Free(PageId),
+    /// After this page, the last of the chain, which is full.
+    Full(PageId),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::{seq::SliceRandom, SeedableRng};
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    /// Check that every bucket chain holds only keys agreeing with its directory slots on the
+    /// local depth bits, and that each bucket is referenced by exactly `2^(global - local)`
+    /// slots. Return the number of entries.
+    fn check_invariants<K, V, B>(table: &ExtendibleHashTable<'_, K, V, B>) -> usize
+    where
+        K: Storable + Eq + Clone,
+        V: Storable + Clone,
+        B: BufferPoolManager + ?Sized,
+    {
+        let guard = table.bpm.fetch_page_read(table.directory_page_id).unwrap();
+        let directory = HashDirectoryPage::new(guard.data());
+        let global_depth = directory.global_depth();
+        let mut seen = HashSet::new();
+        let mut count = 0;
+        for slot in 0..directory.size() {
+            let local_depth = directory.local_depth(slot);
+            assert!(local_depth <= global_depth);
+            let page_id = directory.bucket_page_id(slot);
+            let references = (0..directory.size())
+                .filter(|i| directory.bucket_page_id(*i) == page_id)
+                .count();
+            assert_eq!(references, 1 << (global_depth - local_depth));
+            if !seen.insert(page_id) {
+                continue;
+            }
+            let bucket_guard = table.bpm.fetch_page_read(page_id).unwrap();
+            let entries = table.chain_entries((page_id, bucket_guard.data())).unwrap();
+            let mask = (1u64 << local_depth) - 1;
+            for (key, _) in &entries {
+                assert_eq!(hash_key(key) & mask, slot as u64 & mask);
+            }
+            count += entries.len();
+        }
+        count
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        let table =
+            ExtendibleHashTable::<u64, u64, _>::create_with_bucket_max_size(&bpm, 8).unwrap();
+        assert_eq!(table.get(&1).unwrap(), None);
+
+        const N: u64 = 500;
+        let mut keys = (0..N).collect::<Vec<_>>();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        keys.shuffle(&mut rng);
+        for key in &keys {
+            assert!(table.insert(key, &(key * 7)).unwrap());
+        }
+        assert!(!table.insert(&3, &0).unwrap());
+        assert!(table.global_depth().unwrap() >= 6);
+        assert!(bpm.disk_manager().num_pages() > N as usize / 8);
+        assert_eq!(check_invariants(&table), N as usize);
+        for key in 0..N {
+            assert_eq!(table.get(&key).unwrap(), Some(key * 7));
+        }
+        assert_eq!(table.get(&N).unwrap(), None);
+
+        keys.shuffle(&mut rng);
+        let (removed, kept) = keys.split_at(N as usize - 10);
+        for key in removed {
+            assert!(table.remove(key).unwrap());
+            assert!(!table.remove(key).unwrap());
+        }
+        assert_eq!(check_invariants(&table), kept.len());
+        for key in kept {
+            assert_eq!(table.get(key).unwrap(), Some(key * 7));
+        }
+        for key in kept {
+            assert!(table.remove(key).unwrap());
+        }
+        assert_eq!(table.global_depth().unwrap(), 0);
+        assert_eq!(check_invariants(&table), 0);
+    }
+
+    #[test]
+    fn test_duplicate_keys_overflow() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        let table =
+            ExtendibleHashTable::<u64, u64, _>::create_with_bucket_max_size(&bpm, 4).unwrap();
+
+        // Far more entries per key than a bucket holds, which no split can separate.
+        for value in 0..50 {
+            for key in 0..3 {
+                assert!(table.insert_entry(&key, &value).unwrap());
+            }
+        }
+        assert!(!table.insert_entry(&1, &7).unwrap());
+        assert!(!table.insert(&1, &100).unwrap());
+        assert!(table.insert(&3, &0).unwrap());
+        assert_eq!(check_invariants(&table), 151);
+        // Split only until keys 0 and 1, whose hashes share their lowest 3 bits, are apart.
+        assert_eq!(table.global_depth().unwrap(), 4);
+        for key in 0..3 {
+            let mut values = table.get_all(&key).unwrap();
+            values.sort();
+            assert_eq!(values, (0..50).collect::<Vec<_>>());
+        }
+        assert_eq!(table.get_all(&3).unwrap(), [0]);
+        assert!(table.get_all(&4).unwrap().is_empty());
+
+        for value in (0..50).rev() {
+            assert!(table.remove_entry(&1, &value).unwrap());
+            assert!(!table.remove_entry(&1, &value).unwrap());
+        }
+        assert!(table.get_all(&1).unwrap().is_empty());
+        assert_eq!(check_invariants(&table), 101);
+        for value in 0..50 {
+            assert!(table.remove_entry(&0, &value).unwrap());
+            assert!(table.remove(&2).unwrap());
+        }
+        assert!(table.remove(&3).unwrap());
+        assert_eq!(check_invariants(&table), 0);
+        assert_eq!(table.global_depth().unwrap(), 0);
+
+        // The overflow pages were freed along the way.
+        table.free_pages().unwrap();
+        let page_ids: Vec<_> = (0..3)
+            .map(|_| bpm.new_page_write().unwrap().page_id().as_usize())
+            .collect();
+        assert_eq!(page_ids, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_reopen_after_restart() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let directory_page_id = {
+            let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+            let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+            let table = ExtendibleHashTable::<u32, [u8; 4], _>::create(&bpm).unwrap();
+            for key in 0..20000u32 {
+                table.insert(&key, &key.to_be_bytes()).unwrap();
+            }
+            table.header_page_id()
+        };
+
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+        let bpm = BufferPoolManagerImpl::new(8, disk_manager);
+        assert!(ExtendibleHashTable::<u64, [u8; 4], _>::open(&bpm, directory_page_id).is_err());
+        let table = ExtendibleHashTable::<u32, [u8; 4], _>::open(&bpm, directory_page_id).unwrap();
+        assert_eq!(check_invariants(&table), 20000);
+        for key in 0..20000u32 {
+            assert_eq!(table.get(&key).unwrap(), Some(key.to_be_bytes()));
+        }
+        assert!(ExtendibleHashTable::<u32, u32, _>::create_with_bucket_max_size(&bpm, 0).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_insert_remove() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(64, disk_manager);
+        let table =
+            ExtendibleHashTable::<u64, u64, _>::create_with_bucket_max_size(&bpm, 64).unwrap();
+
+        const THREADS: u64 = 8;
+        const KEYS_PER_THREAD: u64 = 1000;
+        std::thread::scope(|s| {
+            for thread in 0..THREADS {
+                let table = &table;
+                s.spawn(move || {
+                    for i in 0..KEYS_PER_THREAD {
+                        let key = i * THREADS + thread;
+                        assert!(table.insert(&key, &key).unwrap());
+                        assert_eq!(table.get(&key).unwrap(), Some(key));
+                        if i % 2 == 1 {
+                            assert!(table.remove(&(key - THREADS)).unwrap());
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            check_invariants(&table),
+            (THREADS * KEYS_PER_THREAD / 2) as usize
+        );
+        for key in 0..THREADS * KEYS_PER_THREAD {
+            let expected = (key / THREADS % 2 == 1).then_some(key);
+            assert_eq!(table.get(&key).unwrap(), expected);
+        }
+    }
+}
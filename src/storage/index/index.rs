@@ -0,0 +1,240 @@
+//! The interface shared by all index structures, so that the layers above can pick an index
+//! kind per predicate without depending on its implementation.
+
+use std::ops::Bound;
+
+use crate::{buffer::buffer_pool_manager::BufferPoolManager, PageId};
+
+use super::{
+    b_plus_tree::{BPlusTree, BulkLoadConfig},
+    extendible_hash_table::ExtendibleHashTable,
+    index_key::IndexKey,
+    storable::Storable,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexKind {
+    BPlusTree,
+    Hash,
+}
+
+impl IndexKind {
+    /// Whether indexes of this kind can answer range predicates, or only `=`.
+    pub fn supports_range(&self) -> bool {
+        match self {
+            IndexKind::BPlusTree => true,
+            IndexKind::Hash => false,
+        }
+    }
+}
+
+pub type IndexScan<'i, K, V> = Box<dyn Iterator<Item = anyhow::Result<(K, V)>> + 'i>;
+
+/// A disk-backed map from unique keys to values, identified by its header page.
+pub trait Index<K, V> {
+    fn kind(&self) -> IndexKind;
+
+    /// The page to reopen the index from.
+    fn header_page_id(&self) -> PageId;
+
+    /// The value stored under `key`.
+    fn get(&self, key: &K) -> anyhow::Result<Option<V>>;
+
+    /// Insert an entry, returning false without changing anything if `key` is present.
+    fn insert(&self, key: &K, value: &V) -> anyhow::Result<bool>;
+
+    /// Remove the entry with `key`, returning whether it was present.
+    fn remove(&self, key: &K) -> anyhow::Result<bool>;
+
+    /// Iterate over the entries whose keys lie between the bounds, in key order. Fails for
+    /// kinds that do not [support ranges](IndexKind::supports_range).
+    fn scan(&self, lower: Bound<K>, upper: Bound<K>) -> anyhow::Result<IndexScan<'_, K, V>>;
+
+    /// Free every page of the index for the buffer pool to reuse. Nobody else may use it.
+    fn free_pages(&self) -> anyhow::Result<()>;
+}
+
+/// Open an existing index of `kind` through its header page.
+pub fn open_index<'a, K, V, B>(
+    bpm: &'a B,
+    kind: IndexKind,
+    header_page_id: PageId,
+) -> anyhow::Result<Box<dyn Index<K, V> + 'a>>
+where
+    K: IndexKey + Storable + 'a,
+    V: Storable + Clone + 'a,
+    B: BufferPoolManager + ?Sized,
+{
+    Ok(match kind {
+        IndexKind::BPlusTree => Box::new(BPlusTree::<K, V, B>::open(bpm, header_page_id)?),
+        IndexKind::Hash => Box::new(ExtendibleHashTable::<K, V, B>::open(bpm, header_page_id)?),
+    })
+}
+
+/// Create an index of `kind` over existing `entries`, as `CREATE INDEX` does. B+ trees are
+/// bulk loaded bottom-up; hash indexes are filled one entry at a time. On error, the pages of
+/// the index are freed.
+pub fn build_index<'a, K, V, B>(
+    bpm: &'a B,
+    kind: IndexKind,
+    entries: impl IntoIterator<Item = (K, V)>,
+    config: BulkLoadConfig,
+) -> anyhow::Result<Box<dyn Index<K, V> + 'a>>
+where
+    K: IndexKey + Storable + 'a,
+    V: Storable + Clone + 'a,
+    B: BufferPoolManager + ?Sized,
+{
+    match kind {
+        IndexKind::BPlusTree => {
+            let tree = BPlusTree::<K, V, B>::create(bpm)?;
+            if let Err(e) = tree.bulk_load(entries, config) {
+                tree.free_pages()?;
+                return Err(e);
+            }
+            Ok(Box::new(tree))
+        }
+        IndexKind::Hash => {
+            let table = ExtendibleHashTable::<K, V, B>::create(bpm)?;
+            let insert_all = || {
+                for (key, value) in entries {
+                    if !table.insert(&key, &value)? {
+                        anyhow::bail!("cannot build a unique index over duplicate keys");
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = insert_all() {
+                table.free_pages()?;
+                return Err(e);
+            }
+            Ok(Box::new(table))
+        }
+    }
+}
+
+impl<K, V, B> Index<K, V> for BPlusTree<'_, K, V, B>
+where
+    K: IndexKey,
+    V: Storable + Clone,
+    B: BufferPoolManager + ?Sized,
+{
+    fn kind(&self) -> IndexKind {
+        IndexKind::BPlusTree
+    }
+
+    fn header_page_id(&self) -> PageId {
+        BPlusTree::header_page_id(self)
+    }
+
+    fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        BPlusTree::get(self, key)
+    }
+
+    fn insert(&self, key: &K, value: &V) -> anyhow::Result<bool> {
+        BPlusTree::insert(self, key, value)
+    }
+
+    fn remove(&self, key: &K) -> anyhow::Result<bool> {
+        BPlusTree::remove(self, key)
+    }
+
+    fn scan(&self, lower: Bound<K>, upper: Bound<K>) -> anyhow::Result<IndexScan<'_, K, V>> {
+        Ok(Box::new(self.range((lower, upper))))
+    }
+
+    fn free_pages(&self) -> anyhow::Result<()> {
+        BPlusTree::free_pages(self)
+    }
+}
+
+impl<K, V, B> Index<K, V> for ExtendibleHashTable<'_, K, V, B>
+where
+    K: Storable + Eq + Clone,
+    V: Storable + Clone,
+    B: BufferPoolManager + ?Sized,
+{
+    fn kind(&self) -> IndexKind {
+        IndexKind::Hash
+    }
+
+    fn header_page_id(&self) -> PageId {
+        ExtendibleHashTable::header_page_id(self)
+    }
+
+    fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        ExtendibleHashTable::get(self, key)
+    }
+
+    fn insert(&self, key: &K, value: &V) -> anyhow::Result<bool> {
+        ExtendibleHashTable::insert(self, key, value)
+    }
+
+    fn remove(&self, key: &K) -> anyhow::Result<bool> {
+        ExtendibleHashTable::remove(self, key)
+    }
+
+    fn scan(&self, _lower: Bound<K>, _upper: Bound<K>) -> anyhow::Result<IndexScan<'_, K, V>> {
+        anyhow::bail!("hash indexes only support equality lookups")
+    }
+
+    fn free_pages(&self) -> anyhow::Result<()> {
+        ExtendibleHashTable::free_pages(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_index_kinds_behind_trait_objects() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(16, disk_manager);
+        let tree = BPlusTree::<u32, u64, _>::create(&bpm).unwrap();
+        let table = ExtendibleHashTable::<u32, u64, _>::create(&bpm).unwrap();
+        let headers = [
+            (IndexKind::BPlusTree, tree.header_page_id()),
+            (IndexKind::Hash, table.header_page_id()),
+        ];
+
+        for (kind, header_page_id) in headers {
+            let index = open_index::<u32, u64, _>(&bpm, kind, header_page_id).unwrap();
+            assert_eq!(index.kind(), kind);
+            for key in 0..100 {
+                assert!(index.insert(&key, &(key as u64 * 2)).unwrap());
+            }
+            assert!(!index.insert(&5, &0).unwrap());
+            assert!(index.remove(&5).unwrap());
+            assert_eq!(index.get(&5).unwrap(), None);
+            assert_eq!(index.get(&6).unwrap(), Some(12));
+
+            let scan = index.scan(Bound::Included(3), Bound::Excluded(8));
+            if kind.supports_range() {
+                let keys = scan.unwrap().map(|e| e.unwrap().0).collect::<Vec<_>>();
+                assert_eq!(keys, vec![3, 4, 6, 7]);
+            } else {
+                assert!(scan.is_err());
+            }
+        }
+        assert!(open_index::<u32, u64, _>(&bpm, IndexKind::Hash, headers[0].1).is_err());
+
+        for kind in [IndexKind::BPlusTree, IndexKind::Hash] {
+            let entries = (0..1000u32).rev().map(|key| (key, key as u64));
+            let index = build_index(&bpm, kind, entries, BulkLoadConfig::new()).unwrap();
+            assert_eq!(index.get(&999).unwrap(), Some(999));
+            let duplicates = [(1, 1), (1, 2)];
+            assert!(build_index(&bpm, kind, duplicates, BulkLoadConfig::new()).is_err());
+        }
+    }
+}
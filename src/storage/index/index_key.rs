@@ -0,0 +1,195 @@
+//! Order-preserving encoding of B+ tree keys.
+//!
+//! B+ tree pages compare, truncate and share prefixes of keys as plain byte strings, so a key
+//! type must encode to bytes that sort in the same order as the keys themselves. Integers are
+//! written big-endian with the sign bit flipped, and byte strings are escaped and terminated so
+//! that composite keys compare field by field.
+
+use crate::{
+    storage::{page::slotted_page::SlotId, table::record_id::RecordId},
+    PageId,
+};
+
+/// A key whose encoding compares bytewise like the key does.
+pub trait IndexKey: Ord + Clone {
+    /// Append the encoding of the key to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decode a key from the start of `buf` and return it with the number of bytes read.
+    fn decode(buf: &[u8]) -> (Self, usize);
+
+    fn to_key_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+
+    fn from_key_bytes(buf: &[u8]) -> Self {
+        Self::decode(buf).0
+    }
+}
+
+macro_rules! impl_index_key_for_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl IndexKey for $ty {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode(buf: &[u8]) -> (Self, usize) {
+                    const SIZE: usize = std::mem::size_of::<$ty>();
+                    (<$ty>::from_be_bytes(buf[..SIZE].try_into().unwrap()), SIZE)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_index_key_for_signed {
+    ($($ty:ty => $unsigned:ty),*) => {
+        $(
+            impl IndexKey for $ty {
+                fn encode(&self, buf: &mut Vec<u8>) {
+                    ((*self as $unsigned) ^ (1 << (<$unsigned>::BITS - 1))).encode(buf);
+                }
+
+                fn decode(buf: &[u8]) -> (Self, usize) {
+                    let (value, len) = <$unsigned>::decode(buf);
+                    ((value ^ (1 << (<$unsigned>::BITS - 1))) as $ty, len)
+                }
+            }
+        )*
+    };
+}
+
+impl_index_key_for_unsigned!(u8, u16, u32, u64);
+impl_index_key_for_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64);
+
+impl<const N: usize> IndexKey for [u8; N] {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn decode(buf: &[u8]) -> (Self, usize) {
+        (buf[..N].try_into().unwrap(), N)
+    }
+}
+
+/// Escape zero bytes as `00 ff` and end the string with `00 00`, so that a string sorts before
+/// all of its extensions even when followed by more fields.
+fn encode_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    for byte in bytes {
+        buf.push(*byte);
+        if *byte == 0 {
+            buf.push(0xff);
+        }
+    }
+    buf.extend_from_slice(&[0, 0]);
+}
+
+impl IndexKey for Vec<u8> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_bytes(self, buf);
+    }
+
+    fn decode(buf: &[u8]) -> (Self, usize) {
+        let mut value = Vec::new();
+        let mut i = 0;
+        loop {
+            match (buf[i], buf.get(i + 1)) {
+                (0, Some(0)) => return (value, i + 2),
+                (0, _) => {
+                    value.push(0);
+                    i += 2;
+                }
+                (byte, _) => {
+                    value.push(byte);
+                    i += 1;
+                }
+            }
+        }
+    }
+}
+
+impl IndexKey for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), buf);
+    }
+
+    fn decode(buf: &[u8]) -> (Self, usize) {
+        let (bytes, len) = Vec::<u8>::decode(buf);
+        (
+            String::from_utf8(bytes).expect("string keys are encoded from valid UTF-8"),
+            len,
+        )
+    }
+}
+
+impl<A: IndexKey, B: IndexKey> IndexKey for (A, B) {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+    }
+
+    fn decode(buf: &[u8]) -> (Self, usize) {
+        let (a, a_len) = A::decode(buf);
+        let (b, b_len) = B::decode(&buf[a_len..]);
+        ((a, b), a_len + b_len)
+    }
+}
+
+impl IndexKey for RecordId {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.page_id.to_u64().encode(buf);
+        self.slot_id.as_u32().encode(buf);
+    }
+
+    fn decode(buf: &[u8]) -> (Self, usize) {
+        let (page_id, page_id_len) = u64::decode(buf);
+        let (slot_id, slot_id_len) = u32::decode(&buf[page_id_len..]);
+        (
+            RecordId::new(PageId::from_u64(page_id), SlotId::new(slot_id)),
+            page_id_len + slot_id_len,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_order_preserved<K: IndexKey + std::fmt::Debug>(mut keys: Vec<K>) {
+        keys.sort();
+        let encoded = keys.iter().map(K::to_key_bytes).collect::<Vec<_>>();
+        for (key, bytes) in keys.iter().zip(&encoded) {
+            assert_eq!(K::decode(bytes), (key.clone(), bytes.len()));
+        }
+        for pair in encoded.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} >= {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_encoding_preserves_order() {
+        assert_order_preserved(vec![0u32, 1, 255, 256, u32::MAX]);
+        assert_order_preserved(vec![i64::MIN, -256, -1, 0, 1, 255, i64::MAX]);
+        assert_order_preserved(vec![i8::MIN, -1, 0, i8::MAX]);
+        assert_order_preserved(
+            ["", "a", "a\0", "a\0b", "ab", "b", "\u{ff}"]
+                .map(String::from)
+                .to_vec(),
+        );
+        assert_order_preserved(vec![
+            (String::from("a"), -1i32),
+            (String::from("a"), 2),
+            (String::from("a\0"), i32::MIN),
+            (String::from("ab"), 0),
+        ]);
+        assert_order_preserved(vec![
+            RecordId::new(PageId::new(1), SlotId::new(2)),
+            RecordId::new(PageId::new(1), SlotId::new(300)),
+            RecordId::new(PageId::new(2), SlotId::new(0)),
+        ]);
+    }
+}
@@ -0,0 +1,83 @@
+//! Fixed-width encoding of the keys and values stored in index pages.
+
+use crate::{storage::table::record_id::RecordId, PageId};
+
+/// A type that is stored in index pages as exactly `SIZE` bytes.
+pub trait Storable: Sized {
+    const SIZE: usize;
+
+    /// Write the value into `buf`, which is exactly `SIZE` bytes long.
+    fn write_to(&self, buf: &mut [u8]);
+
+    /// Read a value back from the `SIZE` bytes written by [`Storable::write_to`].
+    fn read_from(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_storable_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Storable for $ty {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+
+                fn write_to(&self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_from(buf: &[u8]) -> Self {
+                    <$ty>::from_le_bytes(buf.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_storable_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64);
+
+impl<const N: usize> Storable for [u8; N] {
+    const SIZE: usize = N;
+
+    fn write_to(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+
+    fn read_from(buf: &[u8]) -> Self {
+        buf.try_into().unwrap()
+    }
+}
+
+impl<A: Storable, B: Storable> Storable for (A, B) {
+    const SIZE: usize = A::SIZE + B::SIZE;
+
+    fn write_to(&self, buf: &mut [u8]) {
+        self.0.write_to(&mut buf[..A::SIZE]);
+        self.1.write_to(&mut buf[A::SIZE..]);
+    }
+
+    fn read_from(buf: &[u8]) -> Self {
+        (A::read_from(&buf[..A::SIZE]), B::read_from(&buf[A::SIZE..]))
+    }
+}
+
+impl Storable for PageId {
+    const SIZE: usize = 8;
+
+    fn write_to(&self, buf: &mut [u8]) {
+        self.to_u64().write_to(buf);
+    }
+
+    fn read_from(buf: &[u8]) -> Self {
+        PageId::from_u64(u64::read_from(buf))
+    }
+}
+
+impl Storable for RecordId {
+    const SIZE: usize = RecordId::SIZE;
+
+    fn write_to(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.to_bytes());
+    }
+
+    fn read_from(buf: &[u8]) -> Self {
+        RecordId::from_bytes(buf)
+    }
+}
@@ -0,0 +1,465 @@
+//! Transparent storage of tuples that are too large to be kept inline in a slotted page.
+//!
+//! Similar to PostgreSQL's TOAST, a tuple whose length exceeds [`OverflowConfig::threshold`]
+//! is split into chunks stored in a chain of [`OverflowPage`]s, and the slot only holds an
+//! [`OverflowPointer`] to the head of the chain. [`OverflowStore`] provides tuple-level
+//! operations over a [`SlottedPage`] that follow and free these chains as needed.
+
+use std::borrow::Cow;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    storage::page::{
+        bytes,
+        overflow_page::OverflowPage,
+        slotted_page::{SlotId, SlottedPage},
+    },
+    PageId,
+};
+
+/// Thresholds that decide which tuples are moved out of line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowConfig {
+    /// Tuples longer than this many bytes are stored in an overflow chain.
+    pub threshold: usize,
+}
+
+impl OverflowConfig {
+    /// The default threshold allows at least four inline tuples per page, like TOAST does.
+    pub fn new(page_size: usize) -> Self {
+        Self {
+            threshold: SlottedPage::<&[u8]>::max_tuple_size(page_size) / 4,
+        }
+    }
+
+    pub fn with_threshold(self, threshold: usize) -> Self {
+        Self { threshold }
+    }
+}
+
+/// Location and length of a tuple stored in an overflow chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowPointer {
+    pub len: u64,
+    pub first_page_id: PageId,
+}
+
+impl OverflowPointer {
+    pub const SIZE: usize = 16;
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut buf = [0; Self::SIZE];
+        bytes::write_u64(&mut buf, 0, self.len);
+        bytes::write_u64(&mut buf, 8, self.first_page_id.to_u64());
+        buf
+    }
+
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() != Self::SIZE {
+            anyhow::bail!("invalid overflow pointer of {} bytes", data.len());
+        }
+        Ok(Self {
+            len: bytes::read_u64(data, 0),
+            first_page_id: PageId::from_u64(bytes::read_u64(data, 8)),
+        })
+    }
+}
+
+pub struct OverflowStore<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    config: OverflowConfig,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> OverflowStore<'a, B> {
+    pub fn new(bpm: &'a B, config: OverflowConfig) -> Self {
+        Self { bpm, config }
+    }
+
+    pub fn config(&self) -> OverflowConfig {
+        self.config
+    }
+
+    /// Whether a tuple of `len` bytes is stored in an overflow chain when inserted into a page
+    /// of `page_size` bytes. Tuples that cannot fit into an empty page always overflow.
+    pub fn is_overflowing(&self, len: usize, page_size: usize) -> bool {
+        len > self
+            .config
+            .threshold
+            .min(SlottedPage::<&[u8]>::max_tuple_size(page_size))
+    }
+
+    /// Store `data` in a new overflow chain. The chunks are written back to front so that
+    /// each page can link to the already written next one while only one page is pinned. If a
+    /// page cannot be written, the pages written so far are freed.
+    pub fn write_chain(&self, data: &[u8]) -> anyhow::Result<OverflowPointer> {
+        let mut written = Vec::new();
+        let mut write_pages = || {
+            let mut guard = self.bpm.new_page_write()?;
+            written.push(guard.page_id());
+            let capacity = OverflowPage::<&[u8]>::capacity(guard.page_size());
+            let mut chunks = data.chunks(capacity).rev();
+            OverflowPage::init(guard.data_mut(), chunks.next().unwrap_or_default(), None);
+            let mut next_page_id = guard.page_id();
+            drop(guard);
+
+            for chunk in chunks {
+                let mut guard = self.bpm.new_page_write()?;
+                written.push(guard.page_id());
+                OverflowPage::init(guard.data_mut(), chunk, Some(next_page_id));
+                next_page_id = guard.page_id();
+            }
+            anyhow::Ok(next_page_id)
+        };
+
+        match write_pages() {
+            Ok(first_page_id) => Ok(OverflowPointer {
+                len: data.len() as u64,
+                first_page_id,
+            }),
+            Err(e) => {
+                // No slot refers to the partial chain yet.
+                self.bpm.delete_pages(&written)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Read the whole value stored in the chain `pointer` refers to.
+    pub fn read_chain(&self, pointer: OverflowPointer) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(pointer.len as usize);
+        let mut next_page_id = Some(pointer.first_page_id);
+        while let Some(page_id) = next_page_id {
+            let guard = self.bpm.fetch_page_read(page_id)?;
+            let page = OverflowPage::new(guard.data());
+            data.extend_from_slice(page.chunk());
+            next_page_id = page.next_page_id();
+        }
+        if data.len() as u64 != pointer.len {
+            anyhow::bail!(
+                "overflow chain at {:?} holds {} bytes, expected {}",
+                pointer.first_page_id,
+                data.len(),
+                pointer.len
+            );
+        }
+
+        Ok(data)
+    }
+
+    /// Free every page of the chain `pointer` refers to, for the buffer pool to reuse. The
+    /// whole chain is read first, so that failing to read a page frees none of them.
+    pub fn free_chain(&self, pointer: OverflowPointer) -> anyhow::Result<()> {
+        let page_ids = self.chain_page_ids(pointer)?;
+        self.bpm.delete_pages(&page_ids)
+    }
+
+    /// The pages of the chain `pointer` refers to, in chain order.
+    pub fn chain_page_ids(&self, pointer: OverflowPointer) -> anyhow::Result<Vec<PageId>> {
+        let mut page_ids = Vec::new();
+        let mut next_page_id = Some(pointer.first_page_id);
+        while let Some(page_id) = next_page_id {
+            let guard = self.bpm.fetch_page_read(page_id)?;
+            next_page_id = OverflowPage::new(guard.data()).next_page_id();
+            page_ids.push(page_id);
+        }
+        Ok(page_ids)
+    }
+
+    /// Insert `tuple` into `page`, moving it to an overflow chain if it is too large.
+    /// Return None if the page does not have enough space, without allocating any chain.
+    pub fn insert<T: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        page: &mut SlottedPage<T>,
+        tuple: &[u8],
+    ) -> anyhow::Result<Option<SlotId>> {
+        if !self.is_overflowing(tuple.len(), page.page_size()) {
+            return Ok(page.insert(tuple));
+        }
+        if !page.can_insert(OverflowPointer::SIZE) {
+            return Ok(None);
+        }
+        let pointer = self.write_chain(tuple)?;
+        let slot_id = page
+            .insert_overflow(&pointer.to_bytes())
+            .expect("the page was checked to have room for the pointer");
+
+        Ok(Some(slot_id))
+    }
+
+    /// Get the tuple in `slot_id`, following its overflow chain if it has one.
+    pub fn get<'p, T: AsRef<[u8]>>(
+        &self,
+        page: &'p SlottedPage<T>,
+        slot_id: SlotId,
+    ) -> anyhow::Result<Option<Cow<'p, [u8]>>> {
+        let Some(data) = page.get(slot_id) else {
+            return Ok(None);
+        };
+        if !page.is_overflow(slot_id) {
+            return Ok(Some(Cow::Borrowed(data)));
+        }
+        let pointer = OverflowPointer::from_bytes(data)?;
+
+        Ok(Some(Cow::Owned(self.read_chain(pointer)?)))
+    }
+
+    /// Replace the tuple in `slot_id`, freeing its old overflow chain and creating a new one if
+    /// needed. Return false if the slot is not live or the page does not have enough space, in
+    /// which case the tuple is left unchanged.
+    pub fn update<T: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        page: &mut SlottedPage<T>,
+        slot_id: SlotId,
+        tuple: &[u8],
+    ) -> anyhow::Result<bool> {
+        let Some(old) = page.get(slot_id) else {
+            return Ok(false);
+        };
+        let old_pointer = if page.is_overflow(slot_id) {
+            Some(OverflowPointer::from_bytes(old)?)
+        } else {
+            None
+        };
+
+        let updated = if self.is_overflowing(tuple.len(), page.page_size()) {
+            if !page.can_update(slot_id, OverflowPointer::SIZE) {
+                return Ok(false);
+            }
+            let pointer = self.write_chain(tuple)?;
+            page.update_overflow(slot_id, &pointer.to_bytes())
+        } else {
+            page.update(slot_id, tuple)
+        };
+        if !updated {
+            return Ok(false);
+        }
+        if let Some(old_pointer) = old_pointer {
+            self.free_chain(old_pointer)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Delete the tuple in `slot_id` and free its overflow chain, if any. The chain is freed
+    /// first, so that the slot still refers to it if freeing fails.
+    /// Return false if the slot does not hold a live tuple.
+    pub fn delete<T: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        page: &mut SlottedPage<T>,
+        slot_id: SlotId,
+    ) -> anyhow::Result<bool> {
+        let pointer = match page.get(slot_id) {
+            None => return Ok(false),
+            Some(data) if page.is_overflow(slot_id) => Some(OverflowPointer::from_bytes(data)?),
+            Some(_) => None,
+        };
+        if let Some(pointer) = pointer {
+            self.free_chain(pointer)?;
+        }
+        page.delete(slot_id);
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    /// Fails every write while `failing` is set.
+    struct FailingDiskManager {
+        inner: LimeBaseDiskManager,
+        failing: AtomicBool,
+    }
+
+    impl DiskManager for FailingDiskManager {
+        fn new(page_size: usize, filename: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+            Ok(Self {
+                inner: LimeBaseDiskManager::new(page_size, filename)?,
+                failing: AtomicBool::new(false),
+            })
+        }
+
+        fn page_size(&self) -> usize {
+            self.inner.page_size()
+        }
+
+        fn num_pages(&self) -> usize {
+            self.inner.num_pages()
+        }
+
+        fn read_page(&self, page_id: PageId, data: &mut [u8]) -> anyhow::Result<()> {
+            self.inner.read_page(page_id, data)
+        }
+
+        fn write_page(&self, page_id: PageId, data: &[u8]) -> anyhow::Result<()> {
+            if self.failing.load(Ordering::Relaxed) {
+                anyhow::bail!("failed to write {page_id:?}");
+            }
+            self.inner.write_page(page_id, data)
+        }
+
+        fn sync(&self) -> anyhow::Result<()> {
+            self.inner.sync()
+        }
+    }
+
+    fn chain_page_ids(bpm: &impl BufferPoolManager, pointer: OverflowPointer) -> Vec<PageId> {
+        let mut page_ids = Vec::new();
+        let mut next_page_id = Some(pointer.first_page_id);
+        while let Some(page_id) = next_page_id {
+            page_ids.push(page_id);
+            let guard = bpm.fetch_page_read(page_id).unwrap();
+            next_page_id = OverflowPage::new(guard.data()).next_page_id();
+        }
+        page_ids
+    }
+
+    fn is_resident(bpm: &impl BufferPoolManager, page_id: PageId) -> bool {
+        // Frames latched by the test itself are skipped; they never hold chain pages.
+        bpm.get_pages().iter().any(|page| {
+            page.try_read()
+                .is_ok_and(|page| page.page_id() == Some(page_id))
+        })
+    }
+
+    #[test]
+    fn test_large_tuple_round_trip() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        // Much smaller than the number of pages in the chain.
+        let bpm = BufferPoolManagerImpl::new(3, disk_manager);
+        let store = OverflowStore::new(&bpm, OverflowConfig::new(DEFAULT_PAGE_SIZE));
+
+        let large = (0..DEFAULT_PAGE_SIZE * 11 / 2)
+            .map(|_| rand::random::<u8>())
+            .collect::<Vec<_>>();
+        let mut guard = bpm.new_page_write().unwrap();
+        let mut page = SlottedPage::init(guard.data_mut());
+        let small_slot = store.insert(&mut page, b"small").unwrap().unwrap();
+        let large_slot = store.insert(&mut page, &large).unwrap().unwrap();
+        assert!(!page.is_overflow(small_slot));
+        assert!(page.is_overflow(large_slot));
+        assert_eq!(page.get(large_slot).unwrap().len(), OverflowPointer::SIZE);
+
+        assert_eq!(
+            store.get(&page, small_slot).unwrap().as_deref(),
+            Some(&b"small"[..])
+        );
+        assert_eq!(
+            store.get(&page, large_slot).unwrap().as_deref(),
+            Some(&large[..])
+        );
+        let pointer = OverflowPointer::from_bytes(page.get(large_slot).unwrap()).unwrap();
+        assert_eq!(chain_page_ids(&bpm, pointer).len(), 6);
+    }
+
+    #[test]
+    fn test_configurable_threshold() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(3, disk_manager);
+        let store = OverflowStore::new(
+            &bpm,
+            OverflowConfig::new(DEFAULT_PAGE_SIZE).with_threshold(10),
+        );
+        assert!(!store.is_overflowing(10, DEFAULT_PAGE_SIZE));
+        assert!(store.is_overflowing(11, DEFAULT_PAGE_SIZE));
+
+        let mut guard = bpm.new_page_write().unwrap();
+        let mut page = SlottedPage::init(guard.data_mut());
+        let inline = store.insert(&mut page, b"0123456789").unwrap().unwrap();
+        let overflow = store.insert(&mut page, b"0123456789a").unwrap().unwrap();
+        assert!(!page.is_overflow(inline));
+        assert!(page.is_overflow(overflow));
+        assert_eq!(
+            store.get(&page, overflow).unwrap().as_deref(),
+            Some(&b"0123456789a"[..])
+        );
+    }
+
+    #[test]
+    fn test_delete_and_update_free_chain() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(10, disk_manager);
+        let store = OverflowStore::new(&bpm, OverflowConfig::new(DEFAULT_PAGE_SIZE));
+        let large = vec![0xab; DEFAULT_PAGE_SIZE * 2];
+
+        let mut guard = bpm.new_page_write().unwrap();
+        let mut page = SlottedPage::init(guard.data_mut());
+        let slot_id = store.insert(&mut page, &large).unwrap().unwrap();
+        let pointer = OverflowPointer::from_bytes(page.get(slot_id).unwrap()).unwrap();
+        let chain = chain_page_ids(&bpm, pointer);
+        assert!(chain.iter().all(|page_id| is_resident(&bpm, *page_id)));
+
+        // Updating to a small tuple stores it inline and frees the chain.
+        assert!(store.update(&mut page, slot_id, b"small").unwrap());
+        assert!(!page.is_overflow(slot_id));
+        assert!(chain.iter().all(|page_id| !is_resident(&bpm, *page_id)));
+
+        // Updating back to a large tuple creates a new chain on the freed pages.
+        assert!(store.update(&mut page, slot_id, &large).unwrap());
+        assert_eq!(
+            store.get(&page, slot_id).unwrap().as_deref(),
+            Some(&large[..])
+        );
+        let pointer = OverflowPointer::from_bytes(page.get(slot_id).unwrap()).unwrap();
+        let mut reused = chain_page_ids(&bpm, pointer);
+        reused.sort();
+        let mut freed = chain;
+        freed.sort();
+        assert_eq!(reused, freed);
+        let chain = chain_page_ids(&bpm, pointer);
+
+        // A chain that cannot be freed is still referred to by its slot.
+        let pinned = bpm.fetch_page_read(chain[0]).unwrap();
+        assert!(store.delete(&mut page, slot_id).is_err());
+        drop(pinned);
+        assert!(page.is_live(slot_id));
+        assert_eq!(
+            store.get(&page, slot_id).unwrap().as_deref(),
+            Some(&large[..])
+        );
+
+        assert!(store.delete(&mut page, slot_id).unwrap());
+        assert!(!store.delete(&mut page, slot_id).unwrap());
+        assert_eq!(store.get(&page, slot_id).unwrap(), None);
+        assert!(chain.iter().all(|page_id| !is_resident(&bpm, *page_id)));
+    }
+
+    #[test]
+    fn test_partial_chain_is_freed() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            FailingDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(2, disk_manager);
+        let store = OverflowStore::new(&bpm, OverflowConfig::new(DEFAULT_PAGE_SIZE));
+
+        // The third page of the chain needs a frame, and the dirty first one cannot be evicted.
+        bpm.disk_manager().failing.store(true, Ordering::Relaxed);
+        let error = store
+            .write_chain(&[0xab; DEFAULT_PAGE_SIZE * 4])
+            .unwrap_err();
+        assert_eq!(error.to_string(), "failed to write PageId(0)");
+        bpm.disk_manager().failing.store(false, Ordering::Relaxed);
+
+        let page_ids: Vec<_> = (0..3)
+            .map(|_| bpm.new_page_write().unwrap().page_id())
+            .collect();
+        assert_eq!(page_ids, [PageId::new(0), PageId::new(1), PageId::new(2)]);
+    }
+}
@@ -1,2 +1,8 @@
+pub mod b_plus_tree_page;
+pub(crate) mod bytes;
+pub mod free_page_bitmap;
+pub mod hash_table_page;
+pub mod overflow_page;
 #[allow(clippy::module_inception)]
 pub mod page;
+pub mod slotted_page;
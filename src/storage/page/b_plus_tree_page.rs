@@ -0,0 +1,491 @@
+//! Views over the node pages of a B+ tree.
+//!
+//! Internal and leaf nodes share one layout: a header, an array of slots in key order growing
+//! from the front, and the entries they point at growing from the back. Keys are byte strings
+//! of any length (see [`IndexKey`](crate::storage::index::index_key::IndexKey)) and values have
+//! a fixed width. Leaf values are the values stored in the tree, and internal values are the
+//! page ids of the children. An internal node with `n` children has `n` entries whose first
+//! key is unused and stored empty: child `i` holds the keys in `[key_at(i), key_at(i + 1))`.
+//!
+//! Leaves store the prefix shared by all of their keys once, at the very end of the page, and
+//! only the rest of each key in its entry. Nodes are limited by the number of bytes their
+//! prefix, slots and entries use rather than by a number of entries, so that nodes with short
+//! keys have a high fan-out.
+//!
+//! | offset | size | field                                |
+//! |--------|------|--------------------------------------|
+//! | 0      | 16   | common page header                   |
+//! | 16     | 2    | number of entries                    |
+//! | 18     | 2    | prefix length (leaves only)          |
+//! | 20     | 4    | offset of the lowest entry           |
+//! | 24     | 8    | next leaf page id (leaves only)      |
+//! | 32     | 4    | capacity in bytes                    |
+//! | 36     | 4    | used bytes                           |
+//! | 40     | ...  | slots: entry offset and key length   |
+
+use std::{cmp::Ordering, marker::PhantomData};
+
+use crate::{storage::index::storable::Storable, PageId};
+
+use super::{
+    bytes,
+    page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+};
+
+const SIZE_OFFSET: usize = COMMON_HEADER_SIZE;
+const PREFIX_LEN_OFFSET: usize = SIZE_OFFSET + 2;
+const HEAP_START_OFFSET: usize = PREFIX_LEN_OFFSET + 2;
+const NEXT_PAGE_ID_OFFSET: usize = HEAP_START_OFFSET + 4;
+const CAPACITY_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+const USED_OFFSET: usize = CAPACITY_OFFSET + 4;
+pub const B_PLUS_TREE_PAGE_HEADER_SIZE: usize = USED_OFFSET + 4;
+pub const B_PLUS_TREE_SLOT_SIZE: usize = 4;
+
+/// The length of the longest common prefix of `a` and `b`.
+pub fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// The shortest key that is greater than `left` and at most `right`, which must be greater
+/// than `left`. Separators in internal nodes only need to tell the two apart.
+pub fn shortest_separator(left: &[u8], right: &[u8]) -> Vec<u8> {
+    debug_assert!(left < right);
+    right[..common_prefix_len(left, right) + 1].to_vec()
+}
+
+/// Zero-copy view of a B+ tree node. `T` is `&[u8]` or `&mut [u8]` as for
+/// [`SlottedPage`](super::slotted_page::SlottedPage).
+pub struct BPlusTreePage<T, V> {
+    data: T,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub type LeafPage<T, V> = BPlusTreePage<T, V>;
+pub type InternalPage<T> = BPlusTreePage<T, PageId>;
+
+impl<T, V: Storable> BPlusTreePage<T, V> {
+    /// The bytes an entry uses besides its key.
+    pub const ENTRY_OVERHEAD: usize = B_PLUS_TREE_SLOT_SIZE + V::SIZE;
+}
+
+impl<V: Storable> BPlusTreePage<&[u8], V> {
+    /// The largest capacity of a node in a page of `page_size` bytes.
+    pub fn max_capacity(page_size: usize) -> usize {
+        page_size - B_PLUS_TREE_PAGE_HEADER_SIZE
+    }
+
+    /// The bytes a node holding `entries` in key order would use.
+    pub fn size_of(entries: &[(Vec<u8>, V)], is_leaf: bool) -> usize {
+        let keys_len = entries.iter().map(|(key, _)| key.len()).sum::<usize>();
+        let overhead = entries.len() * Self::ENTRY_OVERHEAD;
+        match entries {
+            [] => 0,
+            [(first, _), .., (last, _)] if is_leaf => {
+                let prefix_len = common_prefix_len(first, last);
+                prefix_len + overhead + keys_len - entries.len() * prefix_len
+            }
+            [(only, _)] if is_leaf => overhead + only.len(),
+            [(first, _), ..] => overhead + keys_len - first.len(),
+        }
+    }
+
+    /// The index at which to split `entries` into two nodes that both fit into `capacity`
+    /// bytes, with their sizes as close as possible. For internal nodes, the key at the index
+    /// moves up to the parent. Return None if there is no such index.
+    pub fn split_point(entries: &[(Vec<u8>, V)], is_leaf: bool, capacity: usize) -> Option<usize> {
+        // Internal nodes keep at least two children on each side when possible.
+        let min = if !is_leaf && entries.len() >= 4 { 2 } else { 1 };
+        (min..=entries.len() - min)
+            .filter_map(|mid| {
+                let left = Self::size_of(&entries[..mid], is_leaf);
+                let right = Self::size_of(&entries[mid..], is_leaf);
+                (left <= capacity && right <= capacity).then_some((left.abs_diff(right), mid))
+            })
+            .min()
+            .map(|(_, mid)| mid)
+    }
+}
+
+impl<T: AsRef<[u8]>, V: Storable> BPlusTreePage<T, V> {
+    pub fn new(data: T) -> Self {
+        debug_assert!(matches!(
+            PageType::of(data.as_ref()),
+            Some(PageType::BPlusTreeInternal | PageType::BPlusTreeLeaf)
+        ));
+        Self {
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        PageType::of(self.data()) == Some(PageType::BPlusTreeLeaf)
+    }
+
+    pub fn size(&self) -> usize {
+        bytes::read_u16(self.data(), SIZE_OFFSET) as usize
+    }
+
+    /// The number of bytes the prefix, slots and entries may use together.
+    pub fn capacity(&self) -> usize {
+        bytes::read_u32(self.data(), CAPACITY_OFFSET) as usize
+    }
+
+    /// The number of bytes the prefix, slots and entries use.
+    pub fn used(&self) -> usize {
+        bytes::read_u32(self.data(), USED_OFFSET) as usize
+    }
+
+    /// Nodes other than the root should use at least a quarter of their capacity. Splits can
+    /// leave leaves emptier than that when the halves share longer prefixes than the whole.
+    pub fn is_underfull(&self) -> bool {
+        self.used() < self.capacity() / 4
+    }
+
+    fn heap_start(&self) -> usize {
+        bytes::read_u32(self.data(), HEAP_START_OFFSET) as usize
+    }
+
+    /// The prefix shared by all keys of a leaf.
+    pub fn prefix(&self) -> &[u8] {
+        let len = bytes::read_u16(self.data(), PREFIX_LEN_OFFSET) as usize;
+        let page_size = self.data().len();
+        &self.data()[page_size - len..]
+    }
+
+    /// The leaf to the right of this one.
+    pub fn next_page_id(&self) -> Option<PageId> {
+        let page_id = PageId::from_u64(bytes::read_u64(self.data(), NEXT_PAGE_ID_OFFSET));
+        page_id.is_valid().then_some(page_id)
+    }
+
+    fn slot(&self, index: usize) -> (usize, usize) {
+        debug_assert!(index < self.size());
+        let offset = B_PLUS_TREE_PAGE_HEADER_SIZE + index * B_PLUS_TREE_SLOT_SIZE;
+        (
+            bytes::read_u16(self.data(), offset) as usize,
+            bytes::read_u16(self.data(), offset + 2) as usize,
+        )
+    }
+
+    /// The part of the key at `index` after the prefix.
+    pub fn suffix_at(&self, index: usize) -> &[u8] {
+        let (offset, len) = self.slot(index);
+        &self.data()[offset..offset + len]
+    }
+
+    pub fn key_at(&self, index: usize) -> Vec<u8> {
+        [self.prefix(), self.suffix_at(index)].concat()
+    }
+
+    pub fn value_at(&self, index: usize) -> V {
+        let (offset, len) = self.slot(index);
+        V::read_from(&self.data()[offset + len..offset + len + V::SIZE])
+    }
+
+    /// Copy all entries out of the page.
+    pub fn entries(&self) -> Vec<(Vec<u8>, V)> {
+        (0..self.size())
+            .map(|i| (self.key_at(i), self.value_at(i)))
+            .collect()
+    }
+
+    /// Compare the key at `index` with `key`.
+    pub fn compare_at(&self, index: usize, key: &[u8]) -> Ordering {
+        self.prefix()
+            .iter()
+            .chain(self.suffix_at(index))
+            .cmp(key.iter())
+    }
+
+    /// Binary search the keys of a leaf: `Ok` with the index of `key`, or `Err` with the index
+    /// where it would be inserted.
+    pub fn search(&self, key: &[u8]) -> Result<usize, usize> {
+        self.search_from(0, key)
+    }
+
+    fn search_from(&self, start: usize, key: &[u8]) -> Result<usize, usize> {
+        let (mut low, mut high) = (start, self.size());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.compare_at(mid, key) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+
+    /// The bytes the node would use after inserting `key`, which may shorten the prefix of a
+    /// leaf. This is an upper bound, since a rebuilt leaf may share a longer prefix.
+    pub fn used_after_insert(&self, key: &[u8]) -> usize {
+        let prefix_len = self.prefix().len();
+        let new_prefix_len = common_prefix_len(self.prefix(), key);
+        self.used() - prefix_len
+            + self.size() * (prefix_len - new_prefix_len)
+            + Self::ENTRY_OVERHEAD
+            + key.len()
+    }
+
+    pub fn can_insert(&self, key: &[u8]) -> bool {
+        self.used_after_insert(key) <= self.capacity()
+    }
+}
+
+impl<T: AsRef<[u8]>> InternalPage<T> {
+    /// The index of the child whose subtree may contain `key`.
+    pub fn child_index(&self, key: &[u8]) -> usize {
+        match self.search_from(1, key) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>, V: Storable + Clone> BPlusTreePage<T, V> {
+    /// Format `data` as an empty node whose entries use at most `capacity` bytes.
+    pub fn init(mut data: T, page_type: PageType, capacity: usize) -> Self {
+        debug_assert!(matches!(
+            page_type,
+            PageType::BPlusTreeInternal | PageType::BPlusTreeLeaf
+        ));
+        let buf = data.as_mut();
+        let page_size = buf.len();
+        assert!(
+            page_size <= u16::MAX as usize + 1,
+            "page size is too large for a B+ tree page"
+        );
+        assert!(capacity <= BPlusTreePage::<&[u8], V>::max_capacity(page_size));
+        bytes::write_u32(buf, PAGE_TYPE_OFFSET, page_type as u32);
+        bytes::write_u16(buf, SIZE_OFFSET, 0);
+        bytes::write_u16(buf, PREFIX_LEN_OFFSET, 0);
+        bytes::write_u32(buf, HEAP_START_OFFSET, page_size as u32);
+        bytes::write_u64(buf, NEXT_PAGE_ID_OFFSET, PageId::new_invalid().to_u64());
+        bytes::write_u32(buf, CAPACITY_OFFSET, capacity as u32);
+        bytes::write_u32(buf, USED_OFFSET, 0);
+        Self::new(data)
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+
+    fn set_size(&mut self, size: usize) {
+        bytes::write_u16(self.data_mut(), SIZE_OFFSET, size as u16);
+    }
+
+    fn set_used(&mut self, used: usize) {
+        bytes::write_u32(self.data_mut(), USED_OFFSET, used as u32);
+    }
+
+    fn set_heap_start(&mut self, offset: usize) {
+        bytes::write_u32(self.data_mut(), HEAP_START_OFFSET, offset as u32);
+    }
+
+    fn set_slot(&mut self, index: usize, offset: usize, len: usize) {
+        let slot_offset = B_PLUS_TREE_PAGE_HEADER_SIZE + index * B_PLUS_TREE_SLOT_SIZE;
+        bytes::write_u16(self.data_mut(), slot_offset, offset as u16);
+        bytes::write_u16(self.data_mut(), slot_offset + 2, len as u16);
+    }
+
+    pub fn set_next_page_id(&mut self, next_page_id: Option<PageId>) {
+        let page_id = next_page_id.unwrap_or_else(PageId::new_invalid);
+        bytes::write_u64(self.data_mut(), NEXT_PAGE_ID_OFFSET, page_id.to_u64());
+    }
+
+    /// Copy an entry below the lowest one and return its offset. The caller must have checked
+    /// that the space between the slots and the entries is large enough.
+    fn place(&mut self, suffix: &[u8], value: &V) -> usize {
+        let offset = self.heap_start() - suffix.len() - V::SIZE;
+        let data = self.data_mut();
+        data[offset..offset + suffix.len()].copy_from_slice(suffix);
+        value.write_to(&mut data[offset + suffix.len()..offset + suffix.len() + V::SIZE]);
+        self.set_heap_start(offset);
+        offset
+    }
+
+    /// Replace all entries of the page with `entries`, which must be in key order and fit into
+    /// the capacity. Leaves get the longest prefix their keys share, and the first key of an
+    /// internal node is dropped.
+    pub fn set_entries(&mut self, entries: &[(Vec<u8>, V)]) {
+        let is_leaf = self.is_leaf();
+        let used = BPlusTreePage::<&[u8], V>::size_of(entries, is_leaf);
+        assert!(used <= self.capacity(), "entries do not fit into the node");
+        let prefix_len = match entries {
+            [(first, _), .., (last, _)] if is_leaf => common_prefix_len(first, last),
+            _ => 0,
+        };
+
+        let page_size = self.data().len();
+        if let Some((first, _)) = entries.first() {
+            self.data_mut()[page_size - prefix_len..].copy_from_slice(&first[..prefix_len]);
+        }
+        bytes::write_u16(self.data_mut(), PREFIX_LEN_OFFSET, prefix_len as u16);
+        self.set_heap_start(page_size - prefix_len);
+        for (i, (key, value)) in entries.iter().enumerate() {
+            let suffix = if !is_leaf && i == 0 {
+                &[][..]
+            } else {
+                &key[prefix_len..]
+            };
+            let offset = self.place(suffix, value);
+            self.set_slot(i, offset, suffix.len());
+        }
+        self.set_size(entries.len());
+        self.set_used(used);
+    }
+
+    /// Insert an entry at `index`, shifting the following entries to the right. Return false
+    /// without changing the page if the entry does not fit.
+    pub fn insert_at(&mut self, index: usize, key: &[u8], value: &V) -> bool {
+        let size = self.size();
+        debug_assert!(index <= size && (self.is_leaf() || index > 0));
+        if !self.can_insert(key) {
+            return false;
+        }
+        let prefix_len = self.prefix().len();
+        let slots_end = B_PLUS_TREE_PAGE_HEADER_SIZE + (size + 1) * B_PLUS_TREE_SLOT_SIZE;
+        // Rebuild the page if the key does not share the prefix, or to reclaim the space of
+        // removed entries.
+        if !key.starts_with(self.prefix())
+            || self.heap_start() < slots_end + key.len() - prefix_len + V::SIZE
+        {
+            let mut entries = self.entries();
+            entries.insert(index, (key.to_vec(), value.clone()));
+            self.set_entries(&entries);
+            return true;
+        }
+
+        let suffix = &key[prefix_len..];
+        let used = self.used() + Self::ENTRY_OVERHEAD + suffix.len();
+        let offset = self.place(suffix, value);
+        let start = B_PLUS_TREE_PAGE_HEADER_SIZE + index * B_PLUS_TREE_SLOT_SIZE;
+        let end = B_PLUS_TREE_PAGE_HEADER_SIZE + size * B_PLUS_TREE_SLOT_SIZE;
+        self.data_mut()
+            .copy_within(start..end, start + B_PLUS_TREE_SLOT_SIZE);
+        self.set_slot(index, offset, suffix.len());
+        self.set_size(size + 1);
+        self.set_used(used);
+        true
+    }
+
+    /// Remove the entry at `index`, shifting the following entries to the left. The space of
+    /// the entry is reclaimed when the page runs out of contiguous space.
+    pub fn remove_at(&mut self, index: usize) {
+        let size = self.size();
+        debug_assert!(index < size);
+        let used = self.used() - Self::ENTRY_OVERHEAD - self.slot(index).1;
+        let start = B_PLUS_TREE_PAGE_HEADER_SIZE + (index + 1) * B_PLUS_TREE_SLOT_SIZE;
+        let end = B_PLUS_TREE_PAGE_HEADER_SIZE + size * B_PLUS_TREE_SLOT_SIZE;
+        self.data_mut()
+            .copy_within(start..end, start - B_PLUS_TREE_SLOT_SIZE);
+        self.set_size(size - 1);
+        self.set_used(used);
+    }
+
+    /// Replace the key at `index`, which must keep the keys in order. Return false without
+    /// changing the page if the new key does not fit.
+    pub fn set_key_at(&mut self, index: usize, key: &[u8]) -> bool {
+        let mut entries = self.entries();
+        entries[index].0 = key.to_vec();
+        if BPlusTreePage::<&[u8], V>::size_of(&entries, self.is_leaf()) > self.capacity() {
+            return false;
+        }
+        self.set_entries(&entries);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::page::page::DEFAULT_PAGE_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn test_leaf_and_internal_pages() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut leaf = LeafPage::<_, u32>::init(buf.as_mut_slice(), PageType::BPlusTreeLeaf, 100);
+        assert!(leaf.is_leaf());
+        for key in ["prefix-b", "prefix-a", "prefix-c"] {
+            let index = leaf.search(key.as_bytes()).unwrap_err();
+            assert!(leaf.insert_at(index, key.as_bytes(), &(key.len() as u32)));
+        }
+        // A rebuilt leaf stores the shared prefix once.
+        leaf.set_entries(&leaf.entries());
+        assert_eq!(leaf.prefix(), b"prefix-");
+        assert_eq!(leaf.used(), 7 + 3 * (8 + 1));
+        assert_eq!(leaf.key_at(1), b"prefix-b");
+        assert_eq!(leaf.search(b"prefix-b"), Ok(1));
+        assert_eq!(leaf.search(b"prefix-bb"), Err(2));
+        assert_eq!(leaf.search(b"a"), Err(0));
+
+        // A key outside the prefix shortens it.
+        assert!(leaf.insert_at(0, b"p", &0));
+        assert_eq!(leaf.prefix(), b"p");
+        assert_eq!(
+            leaf.entries()
+                .iter()
+                .map(|e| e.0.clone())
+                .collect::<Vec<_>>(),
+            vec![&b"p"[..], b"prefix-a", b"prefix-b", b"prefix-c"]
+        );
+        leaf.remove_at(0);
+        assert_eq!(leaf.key_at(0), b"prefix-a");
+        assert!(!leaf.insert_at(3, &[b'p'; 90], &0));
+        assert_eq!(leaf.size(), 3);
+        assert_eq!(leaf.next_page_id(), None);
+        leaf.set_next_page_id(Some(PageId::new(9)));
+        assert_eq!(leaf.next_page_id(), Some(PageId::new(9)));
+
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut internal = InternalPage::init(
+            buf.as_mut_slice(),
+            PageType::BPlusTreeInternal,
+            InternalPage::<&[u8]>::max_capacity(DEFAULT_PAGE_SIZE),
+        );
+        assert!(!internal.is_leaf());
+        internal.set_entries(&[
+            (b"unused".to_vec(), PageId::new(1)),
+            (b"b".to_vec(), PageId::new(2)),
+            (b"d".to_vec(), PageId::new(3)),
+        ]);
+        assert_eq!(internal.key_at(0), b"");
+        assert_eq!(internal.child_index(b"a"), 0);
+        assert_eq!(internal.child_index(b"b"), 1);
+        assert_eq!(internal.child_index(b"c"), 1);
+        assert_eq!(internal.child_index(b"z"), 2);
+        assert!(internal.set_key_at(2, b"cc"));
+        assert_eq!(internal.child_index(b"cz"), 2);
+    }
+
+    #[test]
+    fn test_split_point_and_separator() {
+        let entries = (0..10u32)
+            .map(|i| (format!("key-{i:02}").into_bytes(), i))
+            .collect::<Vec<_>>();
+        let size = LeafPage::<&[u8], u32>::size_of(&entries, true);
+        assert_eq!(size, 5 + 10 * (4 + 4 + 1));
+        assert_eq!(
+            LeafPage::<&[u8], u32>::split_point(&entries, true, size),
+            Some(5)
+        );
+        // A long key moves the balanced split point towards it.
+        let mut entries = entries;
+        entries[9].0.extend([b'x'; 40]);
+        let mid = LeafPage::<&[u8], u32>::split_point(&entries, true, 100).unwrap();
+        assert!(mid > 5);
+        assert_eq!(
+            LeafPage::<&[u8], u32>::split_point(&entries, true, 20),
+            None
+        );
+
+        assert_eq!(shortest_separator(b"apple", b"apricot"), b"apr");
+        assert_eq!(shortest_separator(b"ab", b"abc"), b"abc");
+    }
+}
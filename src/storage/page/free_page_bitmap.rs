@@ -0,0 +1,101 @@
+//! A free-page bitmap page records which pages of the file are free, one bit per page. The
+//! bitmap pages form a singly linked list through `next_page_id`, and page `k` of the list
+//! covers the pages `k * capacity..(k + 1) * capacity` of the file.
+//!
+//! | offset | size | field                    |
+//! |--------|------|--------------------------|
+//! | 0      | 16   | common page header       |
+//! | 16     | 8    | next page id             |
+//! | 24     | ...  | bits, lowest page first  |
+
+use crate::PageId;
+
+use super::{
+    bytes,
+    page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+};
+
+pub const NEXT_PAGE_ID_OFFSET: usize = COMMON_HEADER_SIZE;
+const BITS_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+
+#[derive(Debug)]
+pub struct FreePageBitmap<T> {
+    data: T,
+}
+
+impl<T: AsRef<[u8]>> FreePageBitmap<T> {
+    /// View already formatted page data as a free-page bitmap.
+    pub fn new(data: T) -> Self {
+        debug_assert_eq!(PageType::of(data.as_ref()), Some(PageType::FreePageBitmap));
+        Self { data }
+    }
+
+    /// The number of pages one bitmap page of `page_size` bytes covers.
+    pub fn capacity(page_size: usize) -> usize {
+        (page_size - BITS_OFFSET) * 8
+    }
+
+    /// The offset of the byte that holds the bit of the `index`th page covered, and the mask
+    /// of the bit.
+    pub fn bit_location(index: usize) -> (usize, u8) {
+        (BITS_OFFSET + index / 8, 1 << (index % 8))
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /// The next page of the list, or None if this is the last one.
+    pub fn next_page_id(&self) -> Option<PageId> {
+        let page_id = PageId::from_u64(bytes::read_u64(self.data(), NEXT_PAGE_ID_OFFSET));
+        page_id.is_valid().then_some(page_id)
+    }
+
+    /// The indexes of the free pages among the pages covered, in order.
+    pub fn free_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.data()[BITS_OFFSET..]
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte != 0)
+            .flat_map(|(i, byte)| {
+                (0..8)
+                    .filter(move |bit| byte & (1 << bit) != 0)
+                    .map(move |bit| i * 8 + bit)
+            })
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> FreePageBitmap<T> {
+    /// Format `data` as a bitmap page where no page is free, at the end of the list.
+    pub fn init(mut data: T) -> Self {
+        let buf = data.as_mut();
+        buf.fill(0);
+        bytes::write_u32(buf, PAGE_TYPE_OFFSET, PageType::FreePageBitmap as u32);
+        bytes::write_u64(buf, NEXT_PAGE_ID_OFFSET, PageId::new_invalid().to_u64());
+        Self { data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::page::page::DEFAULT_PAGE_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn test_free_indexes() {
+        let mut data = vec![0; DEFAULT_PAGE_SIZE];
+        FreePageBitmap::init(&mut data[..]);
+        let capacity = FreePageBitmap::<&[u8]>::capacity(DEFAULT_PAGE_SIZE);
+        for index in [0, 7, 8, 1000, capacity - 1] {
+            let (offset, mask) = FreePageBitmap::<&[u8]>::bit_location(index);
+            data[offset] |= mask;
+        }
+        let bitmap = FreePageBitmap::new(&data[..]);
+        assert_eq!(bitmap.next_page_id(), None);
+        assert_eq!(
+            bitmap.free_indexes().collect::<Vec<_>>(),
+            [0, 7, 8, 1000, capacity - 1]
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! Views over the pages of an extendible hash table.
+//!
+//! The directory page maps the lowest `global_depth` bits of a hash to a bucket page. Every
+//! slot records the bucket page id and the local depth of that bucket, which is the number of
+//! hash bits all keys in the bucket share.
+//!
+//! | offset | size              | field                                 |
+//! |--------|-------------------|---------------------------------------|
+//! | 0      | 16                | common page header                    |
+//! | 16     | 4                 | global depth                          |
+//! | 20     | 4                 | max depth                             |
+//! | 24     | 4                 | bucket max size                       |
+//! | 28     | 4                 | key size                              |
+//! | 32     | 4                 | value size                            |
+//! | 36     | 2^max depth       | local depths                          |
+//! | ...    | 8 * 2^max depth   | bucket page ids                       |
+//!
+//! A bucket page holds an unsorted array of fixed-width `(key, value)` entries. A bucket that
+//! splitting cannot relieve continues in overflow pages of the same layout, linked from it.
+//!
+//! | offset | size | field                     |
+//! |--------|------|---------------------------|
+//! | 0      | 16   | common page header        |
+//! | 16     | 4    | number of entries         |
+//! | 20     | 4    | maximum number of entries |
+//! | 24     | 8    | next overflow page id     |
+//! | 32     | ...  | entries                   |
+
+use std::marker::PhantomData;
+
+use crate::{storage::index::storable::Storable, PageId};
+
+use super::{
+    bytes,
+    page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+};
+
+const GLOBAL_DEPTH_OFFSET: usize = COMMON_HEADER_SIZE;
+const MAX_DEPTH_OFFSET: usize = GLOBAL_DEPTH_OFFSET + 4;
+const BUCKET_MAX_SIZE_OFFSET: usize = MAX_DEPTH_OFFSET + 4;
+const KEY_SIZE_OFFSET: usize = BUCKET_MAX_SIZE_OFFSET + 4;
+const VALUE_SIZE_OFFSET: usize = KEY_SIZE_OFFSET + 4;
+pub const HASH_DIRECTORY_PAGE_HEADER_SIZE: usize = VALUE_SIZE_OFFSET + 4;
+
+/// Zero-copy view of a hash table directory.
+pub struct HashDirectoryPage<T> {
+    data: T,
+}
+
+impl<T: AsRef<[u8]>> HashDirectoryPage<T> {
+    pub fn new(data: T) -> Self {
+        debug_assert_eq!(PageType::of(data.as_ref()), Some(PageType::HashDirectory));
+        Self { data }
+    }
+
+    /// The largest global depth whose slots fit into a directory page of `page_size` bytes.
+    pub fn max_depth_for(page_size: usize) -> u32 {
+        let slots = (page_size - HASH_DIRECTORY_PAGE_HEADER_SIZE) / (1 + 8);
+        slots.ilog2()
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub fn global_depth(&self) -> u32 {
+        bytes::read_u32(self.data(), GLOBAL_DEPTH_OFFSET)
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        bytes::read_u32(self.data(), MAX_DEPTH_OFFSET)
+    }
+
+    pub fn bucket_max_size(&self) -> usize {
+        bytes::read_u32(self.data(), BUCKET_MAX_SIZE_OFFSET) as usize
+    }
+
+    /// The sizes of the keys and values the table was created with.
+    pub fn entry_sizes(&self) -> (usize, usize) {
+        (
+            bytes::read_u32(self.data(), KEY_SIZE_OFFSET) as usize,
+            bytes::read_u32(self.data(), VALUE_SIZE_OFFSET) as usize,
+        )
+    }
+
+    /// The number of slots in use, `2^global_depth`.
+    pub fn size(&self) -> usize {
+        1 << self.global_depth()
+    }
+
+    /// The slot that `hash` maps to.
+    pub fn slot_of(&self, hash: u64) -> usize {
+        (hash & ((1 << self.global_depth()) - 1)) as usize
+    }
+
+    fn local_depth_offset(&self, slot: usize) -> usize {
+        HASH_DIRECTORY_PAGE_HEADER_SIZE + slot
+    }
+
+    fn bucket_page_id_offset(&self, slot: usize) -> usize {
+        HASH_DIRECTORY_PAGE_HEADER_SIZE + (1 << self.max_depth()) + slot * 8
+    }
+
+    pub fn local_depth(&self, slot: usize) -> u32 {
+        self.data()[self.local_depth_offset(slot)] as u32
+    }
+
+    pub fn bucket_page_id(&self, slot: usize) -> PageId {
+        PageId::from_u64(bytes::read_u64(
+            self.data(),
+            self.bucket_page_id_offset(slot),
+        ))
+    }
+
+    /// The slot of the bucket that `slot`'s bucket was split from, or would merge with.
+    pub fn split_image_of(&self, slot: usize) -> usize {
+        let local_depth = self.local_depth(slot);
+        debug_assert!(local_depth > 0);
+        slot ^ (1 << (local_depth - 1))
+    }
+
+    /// Whether every bucket is referenced by fewer slots than half the directory, so that the
+    /// directory can be halved.
+    pub fn can_shrink(&self) -> bool {
+        let global_depth = self.global_depth();
+        global_depth > 0 && (0..self.size()).all(|slot| self.local_depth(slot) < global_depth)
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> HashDirectoryPage<T> {
+    /// Format `data` as a directory of global depth 0 whose only slot points at `bucket`.
+    pub fn init(
+        mut data: T,
+        max_depth: u32,
+        bucket_max_size: usize,
+        entry_sizes: (usize, usize),
+        bucket: PageId,
+    ) -> Self {
+        let buf = data.as_mut();
+        bytes::write_u32(buf, PAGE_TYPE_OFFSET, PageType::HashDirectory as u32);
+        bytes::write_u32(buf, GLOBAL_DEPTH_OFFSET, 0);
+        bytes::write_u32(buf, MAX_DEPTH_OFFSET, max_depth);
+        bytes::write_u32(buf, BUCKET_MAX_SIZE_OFFSET, bucket_max_size as u32);
+        bytes::write_u32(buf, KEY_SIZE_OFFSET, entry_sizes.0 as u32);
+        bytes::write_u32(buf, VALUE_SIZE_OFFSET, entry_sizes.1 as u32);
+        let mut directory = Self::new(data);
+        directory.set_local_depth(0, 0);
+        directory.set_bucket_page_id(0, bucket);
+        directory
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+
+    pub fn set_local_depth(&mut self, slot: usize, local_depth: u32) {
+        let offset = self.local_depth_offset(slot);
+        self.data_mut()[offset] = local_depth as u8;
+    }
+
+    pub fn set_bucket_page_id(&mut self, slot: usize, page_id: PageId) {
+        let offset = self.bucket_page_id_offset(slot);
+        bytes::write_u64(self.data_mut(), offset, page_id.to_u64());
+    }
+
+    /// Double the directory, making the new upper half mirror the lower one.
+    pub fn grow(&mut self) {
+        let size = self.size();
+        debug_assert!(self.global_depth() < self.max_depth());
+        for slot in 0..size {
+            let (local_depth, page_id) = (self.local_depth(slot), self.bucket_page_id(slot));
+            self.set_local_depth(size + slot, local_depth);
+            self.set_bucket_page_id(size + slot, page_id);
+        }
+        let global_depth = self.global_depth();
+        bytes::write_u32(self.data_mut(), GLOBAL_DEPTH_OFFSET, global_depth + 1);
+    }
+
+    /// Halve the directory. The caller must have checked [`Self::can_shrink`].
+    pub fn shrink(&mut self) {
+        debug_assert!(self.can_shrink());
+        let global_depth = self.global_depth();
+        bytes::write_u32(self.data_mut(), GLOBAL_DEPTH_OFFSET, global_depth - 1);
+    }
+}
+
+const BUCKET_SIZE_OFFSET: usize = COMMON_HEADER_SIZE;
+const BUCKET_MAX_SIZE_IN_BUCKET_OFFSET: usize = BUCKET_SIZE_OFFSET + 4;
+const BUCKET_NEXT_PAGE_ID_OFFSET: usize = BUCKET_MAX_SIZE_IN_BUCKET_OFFSET + 4;
+pub const HASH_BUCKET_PAGE_HEADER_SIZE: usize = BUCKET_NEXT_PAGE_ID_OFFSET + 8;
+
+/// Zero-copy view of a hash table bucket.
+pub struct HashBucketPage<T, K, V> {
+    data: T,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<T: AsRef<[u8]>, K: Storable, V: Storable> HashBucketPage<T, K, V> {
+    pub fn new(data: T) -> Self {
+        debug_assert_eq!(PageType::of(data.as_ref()), Some(PageType::HashBucket));
+        Self {
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    const ENTRY_SIZE: usize = K::SIZE + V::SIZE;
+
+    /// The number of entries that fit into a bucket page of `page_size` bytes.
+    pub fn capacity(page_size: usize) -> usize {
+        (page_size - HASH_BUCKET_PAGE_HEADER_SIZE) / Self::ENTRY_SIZE
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub fn size(&self) -> usize {
+        bytes::read_u32(self.data(), BUCKET_SIZE_OFFSET) as usize
+    }
+
+    pub fn max_size(&self) -> usize {
+        bytes::read_u32(self.data(), BUCKET_MAX_SIZE_IN_BUCKET_OFFSET) as usize
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.size() >= self.max_size()
+    }
+
+    /// The overflow page the bucket continues in, if any.
+    pub fn next_page_id(&self) -> Option<PageId> {
+        let page_id = PageId::from_u64(bytes::read_u64(self.data(), BUCKET_NEXT_PAGE_ID_OFFSET));
+        page_id.is_valid().then_some(page_id)
+    }
+
+    fn entry_offset(index: usize) -> usize {
+        HASH_BUCKET_PAGE_HEADER_SIZE + index * Self::ENTRY_SIZE
+    }
+
+    pub fn key_at(&self, index: usize) -> K {
+        debug_assert!(index < self.size());
+        let offset = Self::entry_offset(index);
+        K::read_from(&self.data()[offset..offset + K::SIZE])
+    }
+
+    pub fn value_at(&self, index: usize) -> V {
+        debug_assert!(index < self.size());
+        let offset = Self::entry_offset(index) + K::SIZE;
+        V::read_from(&self.data()[offset..offset + V::SIZE])
+    }
+
+    pub fn entries(&self) -> Vec<(K, V)> {
+        (0..self.size())
+            .map(|i| (self.key_at(i), self.value_at(i)))
+            .collect()
+    }
+
+    /// The index of the first entry that `predicate` holds for.
+    pub fn position(&self, mut predicate: impl FnMut(&K, &V) -> bool) -> Option<usize> {
+        (0..self.size()).find(|i| predicate(&self.key_at(*i), &self.value_at(*i)))
+    }
+}
+
+impl<T: AsRef<[u8]>, K: Storable + Eq, V: Storable> HashBucketPage<T, K, V> {
+    /// The index of the entry with `key`.
+    pub fn find(&self, key: &K) -> Option<usize> {
+        (0..self.size()).find(|i| self.key_at(*i) == *key)
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>, K: Storable, V: Storable> HashBucketPage<T, K, V> {
+    /// Format `data` as an empty bucket holding at most `max_size` entries.
+    pub fn init(mut data: T, max_size: usize) -> Self {
+        let buf = data.as_mut();
+        bytes::write_u32(buf, PAGE_TYPE_OFFSET, PageType::HashBucket as u32);
+        bytes::write_u32(buf, BUCKET_SIZE_OFFSET, 0);
+        bytes::write_u32(buf, BUCKET_MAX_SIZE_IN_BUCKET_OFFSET, max_size as u32);
+        bytes::write_u64(
+            buf,
+            BUCKET_NEXT_PAGE_ID_OFFSET,
+            PageId::new_invalid().to_u64(),
+        );
+        Self::new(data)
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+
+    fn set_size(&mut self, size: usize) {
+        bytes::write_u32(self.data_mut(), BUCKET_SIZE_OFFSET, size as u32);
+    }
+
+    /// Append an entry. The caller must have checked that the bucket is not full.
+    pub fn push(&mut self, key: &K, value: &V) {
+        let size = self.size();
+        debug_assert!(size < self.max_size());
+        self.set_size(size + 1);
+        let offset = Self::entry_offset(size);
+        key.write_to(&mut self.data_mut()[offset..offset + K::SIZE]);
+        value.write_to(&mut self.data_mut()[offset + K::SIZE..offset + Self::ENTRY_SIZE]);
+    }
+
+    /// Remove the entry at `index` by moving the last entry into its place.
+    pub fn swap_remove(&mut self, index: usize) {
+        let size = self.size();
+        debug_assert!(index < size);
+        let last = Self::entry_offset(size - 1);
+        let offset = Self::entry_offset(index);
+        self.data_mut()
+            .copy_within(last..last + Self::ENTRY_SIZE, offset);
+        self.set_size(size - 1);
+    }
+
+    pub fn clear(&mut self) {
+        self.set_size(0);
+    }
+
+    pub fn set_next_page_id(&mut self, page_id: Option<PageId>) {
+        let page_id = page_id.unwrap_or_else(PageId::new_invalid);
+        bytes::write_u64(
+            self.data_mut(),
+            BUCKET_NEXT_PAGE_ID_OFFSET,
+            page_id.to_u64(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::page::page::DEFAULT_PAGE_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn test_directory() {
+        let max_depth = HashDirectoryPage::<&[u8]>::max_depth_for(DEFAULT_PAGE_SIZE);
+        assert_eq!(max_depth, 9);
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut directory =
+            HashDirectoryPage::init(buf.as_mut_slice(), max_depth, 16, (8, 8), PageId::new(1));
+        assert_eq!(directory.size(), 1);
+        assert_eq!(directory.slot_of(0b1011), 0);
+
+        directory.set_local_depth(0, 1);
+        directory.grow();
+        directory.set_local_depth(1, 1);
+        directory.set_bucket_page_id(1, PageId::new(2));
+        assert_eq!(directory.global_depth(), 1);
+        assert_eq!(directory.slot_of(0b1011), 1);
+        assert_eq!(directory.split_image_of(1), 0);
+        assert!(!directory.can_shrink());
+
+        directory.set_local_depth(0, 0);
+        directory.set_local_depth(1, 0);
+        directory.set_bucket_page_id(1, PageId::new(1));
+        assert!(directory.can_shrink());
+        directory.shrink();
+        assert_eq!(directory.bucket_page_id(0), PageId::new(1));
+    }
+
+    #[test]
+    fn test_bucket() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut bucket = HashBucketPage::<_, u32, u64>::init(buf.as_mut_slice(), 3);
+        for key in [1, 2, 3] {
+            bucket.push(&key, &(key as u64 * 10));
+        }
+        assert!(bucket.is_full());
+        assert_eq!(bucket.find(&2), Some(1));
+        bucket.swap_remove(0);
+        assert_eq!(bucket.entries(), vec![(3, 30), (2, 20)]);
+        assert_eq!(bucket.find(&1), None);
+        assert_eq!(bucket.position(|_, value| *value == 20), Some(1));
+
+        assert_eq!(bucket.next_page_id(), None);
+        bucket.set_next_page_id(Some(PageId::new(7)));
+        assert_eq!(bucket.next_page_id(), Some(PageId::new(7)));
+    }
+}
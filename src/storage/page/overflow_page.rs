@@ -0,0 +1,95 @@
+//! An overflow page holds one chunk of a value that is too large to be stored inline in a
+//! slotted page. Overflow pages form a singly linked list through `next_page_id`.
+//!
+//! | offset | size | field                    |
+//! |--------|------|--------------------------|
+//! | 0      | 16   | common page header       |
+//! | 16     | 8    | next page id             |
+//! | 24     | 4    | length of the chunk      |
+//! | 28     | ...  | chunk                    |
+
+use crate::PageId;
+
+use super::{
+    bytes,
+    page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+};
+
+const NEXT_PAGE_ID_OFFSET: usize = COMMON_HEADER_SIZE;
+const CHUNK_LEN_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+pub const OVERFLOW_PAGE_HEADER_SIZE: usize = CHUNK_LEN_OFFSET + 4;
+
+#[derive(Debug)]
+pub struct OverflowPage<T> {
+    data: T,
+}
+
+impl<T: AsRef<[u8]>> OverflowPage<T> {
+    /// View already formatted page data as an overflow page.
+    pub fn new(data: T) -> Self {
+        debug_assert_eq!(PageType::of(data.as_ref()), Some(PageType::Overflow));
+        Self { data }
+    }
+
+    /// The number of value bytes one overflow page of `page_size` bytes can hold.
+    pub fn capacity(page_size: usize) -> usize {
+        page_size - OVERFLOW_PAGE_HEADER_SIZE
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /// The next page of the chain, or None if this is the last one.
+    pub fn next_page_id(&self) -> Option<PageId> {
+        let page_id = PageId::from_u64(bytes::read_u64(self.data(), NEXT_PAGE_ID_OFFSET));
+        page_id.is_valid().then_some(page_id)
+    }
+
+    pub fn chunk(&self) -> &[u8] {
+        let len = bytes::read_u32(self.data(), CHUNK_LEN_OFFSET) as usize;
+        &self.data()[OVERFLOW_PAGE_HEADER_SIZE..OVERFLOW_PAGE_HEADER_SIZE + len]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> OverflowPage<T> {
+    /// Format `data` as an overflow page holding `chunk` and linking to `next_page_id`.
+    pub fn init(mut data: T, chunk: &[u8], next_page_id: Option<PageId>) -> Self {
+        let buf = data.as_mut();
+        assert!(chunk.len() <= Self::capacity(buf.len()));
+        buf.fill(0);
+        bytes::write_u32(buf, PAGE_TYPE_OFFSET, PageType::Overflow as u32);
+        bytes::write_u64(
+            buf,
+            NEXT_PAGE_ID_OFFSET,
+            next_page_id.unwrap_or_else(PageId::new_invalid).to_u64(),
+        );
+        bytes::write_u32(buf, CHUNK_LEN_OFFSET, chunk.len() as u32);
+        buf[OVERFLOW_PAGE_HEADER_SIZE..OVERFLOW_PAGE_HEADER_SIZE + chunk.len()]
+            .copy_from_slice(chunk);
+        Self { data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::page::page::DEFAULT_PAGE_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn test_overflow_page() {
+        let mut buf = vec![0xaa; DEFAULT_PAGE_SIZE];
+        OverflowPage::init(buf.as_mut_slice(), b"chunk", Some(PageId::new(3)));
+        let page = OverflowPage::new(buf.as_slice());
+        assert_eq!(page.chunk(), b"chunk");
+        assert_eq!(page.next_page_id(), Some(PageId::new(3)));
+
+        let capacity = OverflowPage::<&[u8]>::capacity(DEFAULT_PAGE_SIZE);
+        let chunk = vec![1; capacity];
+        OverflowPage::init(buf.as_mut_slice(), &chunk, None);
+        let page = OverflowPage::new(buf.as_slice());
+        assert_eq!(page.chunk(), chunk);
+        assert_eq!(page.next_page_id(), None);
+    }
+}
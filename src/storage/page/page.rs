@@ -1,7 +1,72 @@
-use std::{pin::Pin, sync::RwLock};
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use super::bytes;
 
 pub const DEFAULT_PAGE_SIZE: usize = 4096 * 2;
 
+/// Log sequence number stamped on every formatted page.
+pub type Lsn = u64;
+
+/// Every formatted page starts with this common header so that the page type and the LSN
+/// can be read without knowing the concrete layout of the page.
+///
+/// | offset | size | field     |
+/// |--------|------|-----------|
+/// | 0      | 4    | page type |
+/// | 4      | 4    | reserved  |
+/// | 8      | 8    | LSN       |
+pub const PAGE_TYPE_OFFSET: usize = 0;
+pub const PAGE_LSN_OFFSET: usize = 8;
+pub const COMMON_HEADER_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PageType {
+    Invalid = 0,
+    Slotted = 1,
+    Overflow = 2,
+    TableHeapHeader = 3,
+    FreeSpaceMap = 4,
+    BPlusTreeHeader = 5,
+    BPlusTreeInternal = 6,
+    BPlusTreeLeaf = 7,
+    HashDirectory = 8,
+    HashBucket = 9,
+    FileHeader = 10,
+    FreePageBitmap = 11,
+}
+
+impl PageType {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Invalid),
+            1 => Some(Self::Slotted),
+            2 => Some(Self::Overflow),
+            3 => Some(Self::TableHeapHeader),
+            4 => Some(Self::FreeSpaceMap),
+            5 => Some(Self::BPlusTreeHeader),
+            6 => Some(Self::BPlusTreeInternal),
+            7 => Some(Self::BPlusTreeLeaf),
+            8 => Some(Self::HashDirectory),
+            9 => Some(Self::HashBucket),
+            10 => Some(Self::FileHeader),
+            11 => Some(Self::FreePageBitmap),
+            _ => None,
+        }
+    }
+
+    /// Read the page type from the common header of `data`.
+    pub fn of(data: &[u8]) -> Option<Self> {
+        Self::from_u32(bytes::read_u32(data, PAGE_TYPE_OFFSET))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PageId(usize);
 
@@ -21,75 +86,148 @@ impl PageId {
     pub fn offset(&self, page_size: usize) -> usize {
         self.0 * page_size
     }
+
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+
+    /// Encode the page id as a fixed-width integer, for storing it inside pages.
+    pub fn to_u64(self) -> u64 {
+        self.0 as u64
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        if value == u64::MAX {
+            Self::new_invalid()
+        } else {
+            Self(value as usize)
+        }
+    }
 }
 
+/// Marks a frame whose page is being replaced, so that it cannot be pinned meanwhile.
+const RESERVED: usize = usize::MAX;
+
+/// Bookkeeping of the buffer-pool frame a [`Page`] lives in. It is shared between the page and
+/// the buffer pool manager, so pinning and unpinning a page never waits for its latch.
 #[derive(Debug)]
-pub struct Page {
-    page_id: PageId,
-    is_dirty: bool,
-    pin_count: usize,
-    data: Pin<Box<[u8]>>,
+pub(crate) struct FrameState {
+    page_id: AtomicUsize,
+    pin_count: AtomicUsize,
+    is_dirty: AtomicBool,
 }
 
-impl Page {
-    pub fn new_raw(page_size: usize) -> Self {
-        let buf = vec![0; page_size].into_boxed_slice();
+impl FrameState {
+    fn new() -> Self {
         Self {
-            page_id: PageId::new_invalid(),
-            is_dirty: false,
-            pin_count: 0,
-            data: Pin::new(buf),
+            page_id: AtomicUsize::new(PageId::new_invalid().0),
+            pin_count: AtomicUsize::new(0),
+            is_dirty: AtomicBool::new(false),
         }
     }
 
-    pub fn new(page_size: usize) -> RwLock<Self> {
-        RwLock::new(Self::new_raw(page_size))
+    pub(crate) fn page_id(&self) -> Option<PageId> {
+        let page_id = PageId(self.page_id.load(Ordering::Acquire));
+        page_id.is_valid().then_some(page_id)
     }
 
-    pub fn allocate_page(&mut self, page_id: PageId) {
-        self.page_id = page_id;
-        self.pin_count += 1;
+    pub(crate) fn set_page_id(&self, page_id: Option<PageId>) {
+        let page_id = page_id.unwrap_or_else(PageId::new_invalid);
+        self.page_id.store(page_id.0, Ordering::Release);
     }
 
-    pub fn is_pinned(&self) -> bool {
-        self.pin_count > 0
+    pub(crate) fn is_pinned(&self) -> bool {
+        !matches!(self.pin_count.load(Ordering::Acquire), 0 | RESERVED)
     }
 
-    pub fn set_dirty(&mut self) {
-        self.is_dirty = true;
+    /// Pin the frame if it holds `page_id`. Fail if it holds another page or is reserved.
+    pub(crate) fn try_pin(&self, page_id: PageId) -> bool {
+        let pinned = self
+            .pin_count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count != RESERVED).then(|| count + 1)
+            })
+            .is_ok();
+        // The frame may have been reused for another page since the caller looked it up.
+        if pinned && self.page_id() != Some(page_id) {
+            self.unpin();
+            return false;
+        }
+        pinned
     }
 
-    pub fn clear_dirty(&mut self) {
-        self.is_dirty = false;
+    /// Return false if the frame was not pinned.
+    pub(crate) fn unpin(&self) -> bool {
+        self.pin_count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count != 0 && count != RESERVED).then(|| count - 1)
+            })
+            .is_ok()
     }
 
-    pub fn is_dirty(&self) -> bool {
-        self.is_dirty
+    /// Reserve an unpinned frame so that nobody can pin it while its page is replaced.
+    pub(crate) fn try_reserve(&self) -> bool {
+        self.pin_count
+            .compare_exchange(0, RESERVED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
     }
 
-    pub fn pin(&mut self) {
-        self.pin_count += 1;
+    /// Hand a reserved frame over with `pin_count` pins.
+    pub(crate) fn release(&self, pin_count: usize) {
+        self.pin_count.store(pin_count, Ordering::Release);
     }
 
-    pub fn unpin(&mut self) {
-        self.pin_count = self.pin_count.saturating_sub(1);
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.is_dirty.load(Ordering::Acquire)
     }
 
-    pub fn deallocate_page(&mut self) {
-        self.page_id = PageId::new_invalid();
-        self.is_dirty = false;
+    pub(crate) fn set_dirty(&self, is_dirty: bool) {
+        self.is_dirty.store(is_dirty, Ordering::Release);
     }
 
-    pub fn page_id(&self) -> Option<PageId> {
-        if self.page_id == PageId::new_invalid() {
-            None
-        } else {
-            Some(self.page_id)
+    /// Clear the dirty flag and return whether it was set.
+    pub(crate) fn take_dirty(&self) -> bool {
+        self.is_dirty.swap(false, Ordering::AcqRel)
+    }
+}
+
+#[derive(Debug)]
+pub struct Page {
+    state: Arc<FrameState>,
+    data: Pin<Box<[u8]>>,
+}
+
+impl Page {
+    pub fn new_raw(page_size: usize) -> Self {
+        let buf = vec![0; page_size].into_boxed_slice();
+        Self {
+            state: Arc::new(FrameState::new()),
+            data: Pin::new(buf),
         }
     }
 
+    pub fn new(page_size: usize) -> RwLock<Self> {
+        RwLock::new(Self::new_raw(page_size))
+    }
+
+    pub(crate) fn frame_state(&self) -> &Arc<FrameState> {
+        &self.state
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.state.is_pinned()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.state.is_dirty()
+    }
+
+    pub fn page_id(&self) -> Option<PageId> {
+        self.state.page_id()
+    }
+
     pub fn is_allocated(&self) -> bool {
-        self.page_id != PageId::new_invalid()
+        self.page_id().is_some()
     }
 
     pub fn page_size(&self) -> usize {
@@ -103,6 +241,15 @@ impl Page {
     pub fn data_mut(&mut self) -> &mut [u8] {
         &mut self.data
     }
+
+    /// The LSN of the last logged change to the page, read from the common header.
+    pub fn lsn(&self) -> Lsn {
+        bytes::read_u64(self.data(), PAGE_LSN_OFFSET)
+    }
+
+    pub fn set_lsn(&mut self, lsn: Lsn) {
+        bytes::write_u64(self.data_mut(), PAGE_LSN_OFFSET, lsn);
+    }
 }
 
 #[cfg(test)]
@@ -115,5 +262,7 @@ mod tests {
         assert_eq!(page_id.0, 42);
         let page_size = 4096;
         assert_eq!(page_id.offset(page_size), 42 * page_size);
+        assert_eq!(PageId::from_u64(page_id.to_u64()), page_id);
+        assert!(!PageId::from_u64(PageId::new_invalid().to_u64()).is_valid());
     }
 }
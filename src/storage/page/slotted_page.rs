@@ -0,0 +1,647 @@
+//! A slotted-page view over the raw bytes of a [`Page`](super::page::Page).
+//!
+//! ```text
+//! +--------------------+----------------------+-------------+--------------------+
+//! | header (32 bytes)  | slot directory -->   | free space  |   <-- tuple data   |
+//! +--------------------+----------------------+-------------+--------------------+
+//!                                                           ^ free space pointer
+//! ```
+//!
+//! The header starts with the common page header (page type and LSN), followed by the id of
+//! the next page, for structures that chain slotted pages, the number of slots and the
+//! free-space pointer. Each slot is 8 bytes: the offset of the tuple
+//! and its length, whose upper bits are used as flags. Slots are never moved, so a
+//! [`SlotId`] stays valid for the lifetime of the tuple; deleted slots become tombstones
+//! that can be reused by later inserts, and their bytes are reclaimed by [`SlottedPage::compact`].
+//! A slot can also be flagged as an overflow pointer, in which case its bytes describe a chain
+//! of overflow pages instead of the tuple itself (see [`crate::storage::overflow`]).
+
+use std::convert::Infallible;
+
+use crate::PageId;
+
+use super::{
+    bytes,
+    page::{Lsn, PageType, COMMON_HEADER_SIZE, PAGE_LSN_OFFSET, PAGE_TYPE_OFFSET},
+};
+
+const NEXT_PAGE_ID_OFFSET: usize = COMMON_HEADER_SIZE;
+const SLOT_COUNT_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+const FREE_SPACE_POINTER_OFFSET: usize = SLOT_COUNT_OFFSET + 4;
+pub const SLOTTED_PAGE_HEADER_SIZE: usize = FREE_SPACE_POINTER_OFFSET + 4;
+pub const SLOT_SIZE: usize = 8;
+
+/// The slot is a tombstone of a deleted tuple.
+const SLOT_FLAG_DELETED: u32 = 1 << 31;
+/// The slot holds a pointer to an overflow chain instead of the tuple.
+const SLOT_FLAG_OVERFLOW: u32 = 1 << 30;
+const SLOT_FLAG_MASK: u32 = SLOT_FLAG_DELETED | SLOT_FLAG_OVERFLOW;
+const SLOT_LEN_MASK: u32 = !SLOT_FLAG_MASK;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlotId(u32);
+
+impl SlotId {
+    pub fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+
+    fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    offset: u32,
+    len: u32,
+    flags: u32,
+}
+
+impl Slot {
+    fn is_deleted(&self) -> bool {
+        self.flags & SLOT_FLAG_DELETED != 0
+    }
+
+    fn is_overflow(&self) -> bool {
+        self.flags & SLOT_FLAG_OVERFLOW != 0
+    }
+
+    fn tombstone() -> Self {
+        Self {
+            offset: 0,
+            len: 0,
+            flags: SLOT_FLAG_DELETED,
+        }
+    }
+}
+
+/// Zero-copy slotted-page view. `T` is `&[u8]` for a read-only view and `&mut [u8]` for a
+/// mutable one, typically borrowed from a page guard of the buffer pool.
+#[derive(Debug)]
+pub struct SlottedPage<T> {
+    data: T,
+}
+
+impl<T: AsRef<[u8]>> SlottedPage<T> {
+    /// View already formatted page data as a slotted page.
+    pub fn new(data: T) -> Self {
+        debug_assert_eq!(PageType::of(data.as_ref()), Some(PageType::Slotted));
+        Self { data }
+    }
+
+    /// The largest tuple that fits into an empty slotted page of `page_size` bytes.
+    pub fn max_tuple_size(page_size: usize) -> usize {
+        page_size - SLOTTED_PAGE_HEADER_SIZE - SLOT_SIZE
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.data().len()
+    }
+
+    pub fn page_type(&self) -> Option<PageType> {
+        PageType::of(self.data())
+    }
+
+    pub fn lsn(&self) -> Lsn {
+        bytes::read_u64(self.data(), PAGE_LSN_OFFSET)
+    }
+
+    /// The next page of the chain this page belongs to, if any.
+    pub fn next_page_id(&self) -> Option<PageId> {
+        let page_id = PageId::from_u64(bytes::read_u64(self.data(), NEXT_PAGE_ID_OFFSET));
+        page_id.is_valid().then_some(page_id)
+    }
+
+    /// The number of slots in the directory, including tombstones.
+    pub fn slot_count(&self) -> u32 {
+        bytes::read_u32(self.data(), SLOT_COUNT_OFFSET)
+    }
+
+    /// The offset where the tuple data region begins.
+    pub fn free_space_pointer(&self) -> usize {
+        bytes::read_u32(self.data(), FREE_SPACE_POINTER_OFFSET) as usize
+    }
+
+    fn slot_directory_end(&self) -> usize {
+        SLOTTED_PAGE_HEADER_SIZE + self.slot_count() as usize * SLOT_SIZE
+    }
+
+    /// Contiguous free bytes between the slot directory and the tuple data.
+    pub fn free_space(&self) -> usize {
+        self.free_space_pointer() - self.slot_directory_end()
+    }
+
+    /// Bytes in the tuple data region that are not referenced by any live tuple and would be
+    /// reclaimed by [`SlottedPage::compact`].
+    pub fn dead_space(&self) -> usize {
+        let used = self.data().len() - self.free_space_pointer();
+        let live: usize = self.live_slots().map(|(_, slot)| slot.len as usize).sum();
+        used - live
+    }
+
+    /// Whether a tuple of `len` bytes can be inserted, possibly after compaction.
+    pub fn can_insert(&self, len: usize) -> bool {
+        if self.free_space() >= len + SLOT_SIZE {
+            return true;
+        }
+        let slot_overhead = if self.find_tombstone().is_some() {
+            0
+        } else {
+            SLOT_SIZE
+        };
+        self.free_space() + self.dead_space() >= len + slot_overhead
+    }
+
+    /// Whether the live tuple in `slot_id` can be replaced by one of `len` bytes.
+    pub fn can_update(&self, slot_id: SlotId, len: usize) -> bool {
+        match self.slot(slot_id) {
+            Some(slot) if !slot.is_deleted() => {
+                let old_len = slot.len as usize;
+                len <= old_len || self.free_space() + self.dead_space() + old_len >= len
+            }
+            _ => false,
+        }
+    }
+
+    fn slot(&self, slot_id: SlotId) -> Option<Slot> {
+        if slot_id.as_u32() >= self.slot_count() {
+            return None;
+        }
+        let offset = SLOTTED_PAGE_HEADER_SIZE + slot_id.index() * SLOT_SIZE;
+        let len_and_flags = bytes::read_u32(self.data(), offset + 4);
+        Some(Slot {
+            offset: bytes::read_u32(self.data(), offset),
+            len: len_and_flags & SLOT_LEN_MASK,
+            flags: len_and_flags & SLOT_FLAG_MASK,
+        })
+    }
+
+    fn live_slots(&self) -> impl Iterator<Item = (SlotId, Slot)> + '_ {
+        (0..self.slot_count())
+            .map(SlotId::new)
+            .filter_map(|slot_id| Some((slot_id, self.slot(slot_id)?)))
+            .filter(|(_, slot)| !slot.is_deleted())
+    }
+
+    fn find_tombstone(&self) -> Option<SlotId> {
+        (0..self.slot_count())
+            .map(SlotId::new)
+            .find(|slot_id| self.slot(*slot_id).is_some_and(|slot| slot.is_deleted()))
+    }
+
+    /// Whether `slot_id` refers to a live tuple.
+    pub fn is_live(&self, slot_id: SlotId) -> bool {
+        self.slot(slot_id).is_some_and(|slot| !slot.is_deleted())
+    }
+
+    /// Whether `slot_id` refers to a live overflow pointer.
+    pub fn is_overflow(&self, slot_id: SlotId) -> bool {
+        self.slot(slot_id)
+            .is_some_and(|slot| !slot.is_deleted() && slot.is_overflow())
+    }
+
+    /// Get the bytes stored in `slot_id`, or None if the slot does not exist or is a tombstone.
+    /// For an overflow slot these are the bytes of the pointer, not of the tuple.
+    pub fn get(&self, slot_id: SlotId) -> Option<&[u8]> {
+        let slot = self.slot(slot_id)?;
+        if slot.is_deleted() {
+            return None;
+        }
+        let start = slot.offset as usize;
+        Some(&self.data()[start..start + slot.len as usize])
+    }
+
+    /// Iterate over every live tuple in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (SlotId, &[u8])> + '_ {
+        self.live_slots().map(|(slot_id, slot)| {
+            let start = slot.offset as usize;
+            (slot_id, &self.data()[start..start + slot.len as usize])
+        })
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> SlottedPage<T> {
+    /// Format `data` as an empty slotted page and return a view over it.
+    pub fn init(mut data: T) -> Self {
+        let buf = data.as_mut();
+        let page_size = buf.len();
+        assert!(
+            page_size <= SLOT_LEN_MASK as usize,
+            "page size is too large for a slotted page"
+        );
+        buf.fill(0);
+        bytes::write_u32(buf, PAGE_TYPE_OFFSET, PageType::Slotted as u32);
+        bytes::write_u64(buf, NEXT_PAGE_ID_OFFSET, PageId::new_invalid().to_u64());
+        bytes::write_u32(buf, FREE_SPACE_POINTER_OFFSET, page_size as u32);
+        Self { data }
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+
+    pub fn set_lsn(&mut self, lsn: Lsn) {
+        bytes::write_u64(self.data_mut(), PAGE_LSN_OFFSET, lsn);
+    }
+
+    pub fn set_next_page_id(&mut self, next_page_id: Option<PageId>) {
+        let page_id = next_page_id.unwrap_or_else(PageId::new_invalid);
+        bytes::write_u64(self.data_mut(), NEXT_PAGE_ID_OFFSET, page_id.to_u64());
+    }
+
+    fn set_slot_count(&mut self, slot_count: u32) {
+        bytes::write_u32(self.data_mut(), SLOT_COUNT_OFFSET, slot_count);
+    }
+
+    fn set_free_space_pointer(&mut self, pointer: usize) {
+        bytes::write_u32(self.data_mut(), FREE_SPACE_POINTER_OFFSET, pointer as u32);
+    }
+
+    fn set_slot(&mut self, slot_id: SlotId, slot: Slot) {
+        let offset = SLOTTED_PAGE_HEADER_SIZE + slot_id.index() * SLOT_SIZE;
+        let data = self.data_mut();
+        bytes::write_u32(data, offset, slot.offset);
+        bytes::write_u32(data, offset + 4, slot.len | slot.flags);
+    }
+
+    /// Copy `tuple` into the free space and return its offset. The caller must have checked
+    /// that enough contiguous space is available.
+    fn place(&mut self, tuple: &[u8]) -> u32 {
+        let start = self.free_space_pointer() - tuple.len();
+        self.data_mut()[start..start + tuple.len()].copy_from_slice(tuple);
+        self.set_free_space_pointer(start);
+        start as u32
+    }
+
+    /// Insert `tuple` into the page, compacting it first if needed. Return None if the page
+    /// does not have enough space even after compaction.
+    pub fn insert(&mut self, tuple: &[u8]) -> Option<SlotId> {
+        self.insert_with_flags(tuple.len(), 0, |buf| {
+            buf.copy_from_slice(tuple);
+            Ok::<_, Infallible>(())
+        })
+        .unwrap_or_else(|never| match never {})
+    }
+
+    /// Insert a tuple of `len` bytes that `write` encodes directly into the page, so the caller
+    /// does not need to build it in a separate buffer first. If `write` fails, the page is left
+    /// unchanged and the error is returned.
+    pub fn insert_with<E>(
+        &mut self,
+        len: usize,
+        write: impl FnOnce(&mut [u8]) -> Result<(), E>,
+    ) -> Result<Option<SlotId>, E> {
+        self.insert_with_flags(len, 0, write)
+    }
+
+    /// Insert the bytes of an overflow pointer, flagging the slot accordingly.
+    pub fn insert_overflow(&mut self, pointer: &[u8]) -> Option<SlotId> {
+        self.insert_with_flags(pointer.len(), SLOT_FLAG_OVERFLOW, |buf| {
+            buf.copy_from_slice(pointer);
+            Ok::<_, Infallible>(())
+        })
+        .unwrap_or_else(|never| match never {})
+    }
+
+    fn insert_with_flags<E>(
+        &mut self,
+        len: usize,
+        flags: u32,
+        write: impl FnOnce(&mut [u8]) -> Result<(), E>,
+    ) -> Result<Option<SlotId>, E> {
+        if !self.can_insert(len) {
+            return Ok(None);
+        }
+        let tombstone = self.find_tombstone();
+        let slot_overhead = if tombstone.is_some() { 0 } else { SLOT_SIZE };
+        // Compact before the slot directory grows, or a new slot would overwrite tuple data
+        // when the free space is mostly dead space.
+        if self.free_space() < len + slot_overhead {
+            self.compact();
+        }
+        let (slot_id, appended) = match tombstone {
+            Some(slot_id) => (slot_id, false),
+            None => {
+                let slot_id = SlotId::new(self.slot_count());
+                self.set_slot_count(slot_id.as_u32() + 1);
+                self.set_slot(slot_id, Slot::tombstone());
+                (slot_id, true)
+            }
+        };
+        let start = self.free_space_pointer() - len;
+        if let Err(e) = write(&mut self.data_mut()[start..start + len]) {
+            if appended {
+                self.set_slot_count(slot_id.as_u32());
+            }
+            return Err(e);
+        }
+        self.set_free_space_pointer(start);
+        self.set_slot(
+            slot_id,
+            Slot {
+                offset: start as u32,
+                len: len as u32,
+                flags,
+            },
+        );
+
+        Ok(Some(slot_id))
+    }
+
+    /// Replace the tuple in `slot_id` with `tuple`. A tuple that is not larger than the old one
+    /// is overwritten in place; a larger one is moved within the page, compacting it if needed.
+    /// Return false if the slot is not live or the page does not have enough space, in which
+    /// case the page is left unchanged.
+    pub fn update(&mut self, slot_id: SlotId, tuple: &[u8]) -> bool {
+        self.update_with_flags(slot_id, tuple, 0)
+    }
+
+    /// Replace the content of `slot_id` with the bytes of an overflow pointer.
+    pub fn update_overflow(&mut self, slot_id: SlotId, pointer: &[u8]) -> bool {
+        self.update_with_flags(slot_id, pointer, SLOT_FLAG_OVERFLOW)
+    }
+
+    fn update_with_flags(&mut self, slot_id: SlotId, tuple: &[u8], flags: u32) -> bool {
+        let Some(slot) = self.slot(slot_id) else {
+            return false;
+        };
+        if slot.is_deleted() {
+            return false;
+        }
+
+        let old_len = slot.len as usize;
+        if tuple.len() <= old_len {
+            let start = slot.offset as usize;
+            self.data_mut()[start..start + tuple.len()].copy_from_slice(tuple);
+            self.set_slot(
+                slot_id,
+                Slot {
+                    offset: slot.offset,
+                    len: tuple.len() as u32,
+                    flags,
+                },
+            );
+            return true;
+        }
+
+        if !self.can_update(slot_id, tuple.len()) {
+            return false;
+        }
+        self.set_slot(slot_id, Slot::tombstone());
+        if self.free_space() < tuple.len() {
+            self.compact();
+        }
+        let offset = self.place(tuple);
+        self.set_slot(
+            slot_id,
+            Slot {
+                offset,
+                len: tuple.len() as u32,
+                flags,
+            },
+        );
+
+        true
+    }
+
+    /// Turn `slot_id` into a tombstone. Its bytes are reclaimed by the next compaction.
+    /// Return false if the slot does not hold a live tuple.
+    pub fn delete(&mut self, slot_id: SlotId) -> bool {
+        if !self.is_live(slot_id) {
+            return false;
+        }
+        self.set_slot(slot_id, Slot::tombstone());
+
+        true
+    }
+
+    /// Move every live tuple to the end of the page so that all dead space becomes contiguous
+    /// free space. Slot ids are preserved.
+    pub fn compact(&mut self) {
+        let mut live = self.live_slots().collect::<Vec<_>>();
+        // Moving tuples in descending offset order never overwrites a tuple that has not been
+        // moved yet, because every tuple only moves towards the end of the page.
+        live.sort_by_key(|(_, slot)| std::cmp::Reverse(slot.offset));
+
+        let mut end = self.data().len();
+        for (slot_id, slot) in live {
+            let start = end - slot.len as usize;
+            let old_start = slot.offset as usize;
+            self.data_mut()
+                .copy_within(old_start..old_start + slot.len as usize, start);
+            self.set_slot(
+                slot_id,
+                Slot {
+                    offset: start as u32,
+                    ..slot
+                },
+            );
+            end = start;
+        }
+        self.set_free_space_pointer(end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::page::page::DEFAULT_PAGE_SIZE, Page};
+
+    use super::*;
+
+    #[test]
+    fn test_insert_get() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        assert_eq!(page.page_type(), Some(PageType::Slotted));
+        assert_eq!(page.slot_count(), 0);
+        assert_eq!(page.next_page_id(), None);
+        page.set_next_page_id(Some(PageId::new(7)));
+
+        let a = page.insert(b"hello").unwrap();
+        let b = page.insert(b"").unwrap();
+        let c = page.insert(b"world!").unwrap();
+        assert_eq!(page.get(a), Some(&b"hello"[..]));
+        assert_eq!(page.get(b), Some(&b""[..]));
+        assert_eq!(page.get(c), Some(&b"world!"[..]));
+        assert_eq!(page.get(SlotId::new(3)), None);
+
+        page.set_lsn(42);
+        // A read-only view over the same bytes sees the same content.
+        let page = SlottedPage::new(buf.as_slice());
+        assert_eq!(page.lsn(), 42);
+        assert_eq!(page.next_page_id(), Some(PageId::new(7)));
+        assert_eq!(
+            page.iter().collect::<Vec<_>>(),
+            vec![(a, &b"hello"[..]), (b, &b""[..]), (c, &b"world!"[..])]
+        );
+    }
+
+    #[test]
+    fn test_view_over_page() {
+        let mut page = Page::new_raw(DEFAULT_PAGE_SIZE);
+        let slot_id = SlottedPage::init(page.data_mut()).insert(b"tuple").unwrap();
+        assert_eq!(
+            SlottedPage::new(page.data()).get(slot_id),
+            Some(&b"tuple"[..])
+        );
+    }
+
+    #[test]
+    fn test_fill_page() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let tuple = [7u8; 100];
+        let mut n = 0;
+        while page.insert(&tuple).is_some() {
+            n += 1;
+        }
+        assert_eq!(
+            n,
+            (DEFAULT_PAGE_SIZE - SLOTTED_PAGE_HEADER_SIZE) / (tuple.len() + SLOT_SIZE)
+        );
+        assert!(page.free_space() < tuple.len() + SLOT_SIZE);
+
+        let max = SlottedPage::<&[u8]>::max_tuple_size(DEFAULT_PAGE_SIZE);
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        assert!(page.insert(&vec![1; max + 1]).is_none());
+        assert!(page.insert(&vec![1; max]).is_some());
+        assert_eq!(page.free_space(), 0);
+    }
+
+    #[test]
+    fn test_delete_and_reuse_tombstone() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let a = page.insert(b"aaaa").unwrap();
+        let b = page.insert(b"bbbb").unwrap();
+        assert!(page.delete(a));
+        assert!(!page.delete(a));
+        assert!(!page.is_live(a));
+        assert_eq!(page.get(a), None);
+        assert_eq!(page.get(b), Some(&b"bbbb"[..]));
+        assert_eq!(page.dead_space(), 4);
+
+        // The tombstone is reused without growing the slot directory.
+        let c = page.insert(b"cc").unwrap();
+        assert_eq!(c, a);
+        assert_eq!(page.slot_count(), 2);
+        assert_eq!(page.get(c), Some(&b"cc"[..]));
+    }
+
+    #[test]
+    fn test_update() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let a = page.insert(b"0123456789").unwrap();
+        let b = page.insert(b"other").unwrap();
+
+        // Shrinking update happens in place.
+        let pointer = page.free_space_pointer();
+        assert!(page.update(a, b"short"));
+        assert_eq!(page.get(a), Some(&b"short"[..]));
+        assert_eq!(page.free_space_pointer(), pointer);
+
+        // Growing update relocates the tuple.
+        assert!(page.update(a, b"a much longer tuple"));
+        assert_eq!(page.get(a), Some(&b"a much longer tuple"[..]));
+        assert_eq!(page.get(b), Some(&b"other"[..]));
+
+        assert!(page.delete(b));
+        assert!(!page.update(b, b"dead"));
+
+        // An update that cannot fit leaves the tuple untouched.
+        let max = SlottedPage::<&[u8]>::max_tuple_size(DEFAULT_PAGE_SIZE);
+        assert!(!page.update(a, &vec![0; max + 1]));
+        assert_eq!(page.get(a), Some(&b"a much longer tuple"[..]));
+    }
+
+    #[test]
+    fn test_compaction() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let tuple_len = 500;
+        let mut slots = Vec::new();
+        for i in 0.. {
+            match page.insert(&vec![i as u8; tuple_len]) {
+                Some(slot_id) => slots.push(slot_id),
+                None => break,
+            }
+        }
+        // Delete every other tuple; the freed space is not contiguous.
+        for slot_id in slots.iter().step_by(2) {
+            assert!(page.delete(*slot_id));
+        }
+        assert!(page.free_space() < 2 * tuple_len);
+        assert!(page.can_insert(2 * tuple_len));
+
+        // Inserting a tuple larger than the contiguous free space compacts the page.
+        let big = page.insert(&vec![0xff; 2 * tuple_len]).unwrap();
+        assert_eq!(page.get(big), Some(&vec![0xff; 2 * tuple_len][..]));
+        for (i, slot_id) in slots.iter().enumerate().skip(1).step_by(2) {
+            assert_eq!(page.get(*slot_id), Some(&vec![i as u8; tuple_len][..]));
+        }
+
+        page.compact();
+        assert_eq!(page.dead_space(), 0);
+    }
+
+    #[test]
+    fn test_insert_into_dead_space_of_full_page() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let mut slots = Vec::new();
+        while let Some(slot_id) = page.insert(&[slots.len() as u8; 100]) {
+            slots.push(slot_id);
+        }
+        let last = page
+            .insert(&vec![0xee; page.free_space() - SLOT_SIZE])
+            .unwrap();
+        assert_eq!(page.free_space(), 0);
+
+        // Shrinking a tuple leaves dead space but no tombstone, so the insert needs a new slot
+        // that only fits once the page is compacted.
+        assert!(page.update(slots[0], &[0xaa; 50]));
+        assert!(page.can_insert(20));
+        let c = page.insert(&[0xcc; 20]).unwrap();
+        assert_eq!(c.as_u32(), page.slot_count() - 1);
+        assert_eq!(page.get(c), Some(&[0xcc; 20][..]));
+        assert_eq!(page.get(slots[0]), Some(&[0xaa; 50][..]));
+        for (i, slot_id) in slots.iter().enumerate().skip(1) {
+            assert_eq!(page.get(*slot_id), Some(&[i as u8; 100][..]));
+        }
+        assert!(page.get(last).unwrap().iter().all(|&b| b == 0xee));
+        assert_eq!(page.free_space(), 50 - 20 - SLOT_SIZE);
+    }
+
+    #[test]
+    fn test_overflow_flag() {
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let a = page.insert(b"inline").unwrap();
+        let b = page.insert_overflow(b"pointer").unwrap();
+        assert!(!page.is_overflow(a));
+        assert!(page.is_overflow(b));
+        assert_eq!(page.get(b), Some(&b"pointer"[..]));
+
+        // The flag survives compaction and is replaced by updates.
+        assert!(page.delete(a));
+        page.compact();
+        assert!(page.is_overflow(b));
+        assert!(page.update(b, b"inline again"));
+        assert!(!page.is_overflow(b));
+        assert!(page.update_overflow(b, b"ptr"));
+        assert!(page.is_overflow(b));
+        assert!(page.delete(b));
+        assert!(!page.is_overflow(b));
+    }
+}
@@ -0,0 +1,172 @@
+//! Records that an operator moves out of memory, such as the partitions of a grace hash join.
+//!
+//! A [`SpillFile`] appends variable-length records to a chain of slotted pages allocated with
+//! [`BufferPoolManager::new_page`], pinning only the page it appends to while it writes. Its
+//! [`SpillReader`] copies the records of one page out at a time and deletes the page right
+//! after, so reading a spilled partition needs memory for a single page of records. Dropping
+//! a file or a reader deletes all of its pages that have not been read.
+
+use std::collections::VecDeque;
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager, storage::page::slotted_page::SlottedPage,
+    PageId,
+};
+
+pub struct SpillFile<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    first_page_id: Option<PageId>,
+    last_page_id: Option<PageId>,
+    len: usize,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> SpillFile<'a, B> {
+    /// Create an empty file, which allocates no page until the first record is pushed.
+    pub fn new(bpm: &'a B) -> Self {
+        Self {
+            bpm,
+            first_page_id: None,
+            last_page_id: None,
+            len: 0,
+        }
+    }
+
+    /// The number of records pushed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append a record, which must fit into a single page.
+    pub fn push(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        let mut guard = match self.last_page_id {
+            Some(page_id) => self.bpm.fetch_page_write(page_id)?,
+            None => {
+                let mut guard = self.bpm.new_page_write()?;
+                SlottedPage::init(guard.data_mut());
+                self.first_page_id = Some(guard.page_id());
+                self.last_page_id = Some(guard.page_id());
+                guard
+            }
+        };
+        if SlottedPage::new(guard.data_mut()).insert(record).is_none() {
+            let mut next_guard = self.bpm.new_page_write()?;
+            SlottedPage::init(next_guard.data_mut());
+            SlottedPage::new(guard.data_mut()).set_next_page_id(Some(next_guard.page_id()));
+            self.last_page_id = Some(next_guard.page_id());
+            guard = next_guard;
+            if SlottedPage::new(guard.data_mut()).insert(record).is_none() {
+                anyhow::bail!("a record of {} bytes is too large to spill", record.len());
+            }
+        }
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Read the records back in the order they were pushed.
+    pub fn into_reader(mut self) -> SpillReader<'a, B> {
+        SpillReader {
+            bpm: self.bpm,
+            next_page_id: self.first_page_id.take(),
+            buffered: VecDeque::new(),
+        }
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Drop for SpillFile<'_, B> {
+    fn drop(&mut self) {
+        free_chain(self.bpm, self.first_page_id);
+    }
+}
+
+/// Yields the records of a [`SpillFile`], deleting every page once it has been read.
+pub struct SpillReader<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    next_page_id: Option<PageId>,
+    buffered: VecDeque<Vec<u8>>,
+}
+
+impl<B: BufferPoolManager + ?Sized> Iterator for SpillReader<'_, B> {
+    type Item = anyhow::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffered.is_empty() {
+            let page_id = self.next_page_id?;
+            let guard = match self.bpm.fetch_page_read(page_id) {
+                Ok(guard) => guard,
+                Err(err) => return Some(Err(err)),
+            };
+            let page = SlottedPage::new(guard.data());
+            self.buffered
+                .extend(page.iter().map(|(_, record)| record.to_vec()));
+            self.next_page_id = page.next_page_id();
+            drop(guard);
+            self.bpm.delete_page(page_id);
+        }
+        self.buffered.pop_front().map(Ok)
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Drop for SpillReader<'_, B> {
+    fn drop(&mut self) {
+        free_chain(self.bpm, self.next_page_id);
+    }
+}
+
+/// Delete every page of a chain of slotted pages starting at `page_id`.
+pub(crate) fn free_chain<B: BufferPoolManager + ?Sized>(bpm: &B, mut page_id: Option<PageId>) {
+    while let Some(current) = page_id {
+        page_id = match bpm.fetch_page_read(current) {
+            Ok(guard) => SlottedPage::new(guard.data()).next_page_id(),
+            Err(_) => None,
+        };
+        bpm.delete_page(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, TempDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_spill_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            TempDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db.tmp")).unwrap();
+        // Two frames: the page appended to and the next one.
+        let bpm = BufferPoolManagerImpl::new(2, disk_manager);
+
+        let records: Vec<Vec<u8>> = (0..2000u32)
+            .map(|i| i.to_be_bytes().repeat(i as usize % 7 + 1))
+            .collect();
+        let mut files = [SpillFile::new(&bpm), SpillFile::new(&bpm)];
+        for (i, record) in records.iter().enumerate() {
+            files[i % 2].push(record).unwrap();
+        }
+        assert_eq!(files[0].len(), 1000);
+        assert!(files[1]
+            .push(&vec![0; DEFAULT_PAGE_SIZE])
+            .unwrap_err()
+            .to_string()
+            .ends_with("too large to spill"));
+
+        let [even, odd] = files;
+        let even: Vec<Vec<u8>> = even.into_reader().map(Result::unwrap).collect();
+        assert_eq!(even, records.iter().step_by(2).cloned().collect::<Vec<_>>());
+        let mut reader = odd.into_reader();
+        assert_eq!(reader.next().unwrap().unwrap(), records[1]);
+        assert_eq!(reader.count(), 999);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod free_space_map;
+pub mod record_id;
+pub mod table_heap;
+pub mod table_iterator;
+pub mod tuple;
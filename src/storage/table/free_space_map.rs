@@ -0,0 +1,345 @@
+//! A persistent free-space map that lets a [`TableHeap`](super::table_heap::TableHeap) find a
+//! page with enough room for a tuple without walking the whole heap.
+//!
+//! Like PostgreSQL's FSM, the free bytes of each heap page are quantized into one of 256
+//! categories of `page_size / 256` bytes. The entries are stored in a chain of dedicated FSM
+//! pages allocated through [`BufferPoolManager::new_page`], and an in-memory index from category
+//! to pages is rebuilt from them when the map is opened. Looking up a page only scans the
+//! constant number of categories, so it is O(1) regardless of the size of the heap.
+//!
+//! The in-memory index also tracks where the next entry goes, so every handle on one map must
+//! share it through [`FreeSpaceMap::shared`] rather than open the map again: two copies would
+//! append their entries to the same slots.
+//!
+//! | offset | size | field                                |
+//! |--------|------|--------------------------------------|
+//! | 0      | 16   | common page header                   |
+//! | 16     | 8    | next FSM page id                     |
+//! | 24     | 4    | number of entries                    |
+//! | 28     | 4    | reserved                             |
+//! | 32     | 9 * n| entries of (heap page id, category)  |
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    storage::page::{
+        bytes,
+        page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+    },
+    PageId,
+};
+
+const NEXT_PAGE_ID_OFFSET: usize = COMMON_HEADER_SIZE;
+const ENTRY_COUNT_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+const ENTRIES_OFFSET: usize = ENTRY_COUNT_OFFSET + 8;
+const ENTRY_SIZE: usize = 9;
+const NUM_CATEGORIES: usize = 256;
+
+/// Where the entry of a heap page is stored.
+#[derive(Debug, Clone, Copy)]
+struct EntryLocation {
+    fsm_page_index: usize,
+    index: usize,
+    category: u8,
+}
+
+#[derive(Debug)]
+struct FsmState {
+    /// FSM pages in chain order.
+    fsm_pages: Vec<PageId>,
+    /// Number of entries in the last FSM page.
+    last_page_entries: usize,
+    entries: HashMap<PageId, EntryLocation>,
+    /// Heap pages by category. Pages with lower ids are preferred to keep the heap dense.
+    categories: Vec<BTreeSet<PageId>>,
+}
+
+pub struct FreeSpaceMap<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    shared: SharedFsm,
+}
+
+/// The in-memory index of a map, to open more handles on the map with.
+#[derive(Debug, Clone)]
+pub struct SharedFsm {
+    page_size: usize,
+    state: Arc<Mutex<FsmState>>,
+}
+
+impl SharedFsm {
+    fn new(page_size: usize, state: FsmState) -> Self {
+        Self {
+            page_size,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> FreeSpaceMap<'a, B> {
+    /// Create an empty map, returning it with the id of its first page.
+    pub fn create(bpm: &'a B) -> anyhow::Result<(Self, PageId)> {
+        let mut guard = bpm.new_page_write()?;
+        init_fsm_page(guard.data_mut());
+        let first_page_id = guard.page_id();
+
+        let fsm = Self {
+            bpm,
+            shared: SharedFsm::new(
+                guard.page_size(),
+                FsmState {
+                    fsm_pages: vec![first_page_id],
+                    last_page_entries: 0,
+                    entries: HashMap::new(),
+                    categories: vec![BTreeSet::new(); NUM_CATEGORIES],
+                },
+            ),
+        };
+        Ok((fsm, first_page_id))
+    }
+
+    /// Load the map whose first page is `first_page_id`.
+    pub fn open(bpm: &'a B, first_page_id: PageId) -> anyhow::Result<Self> {
+        let mut state = FsmState {
+            fsm_pages: Vec::new(),
+            last_page_entries: 0,
+            entries: HashMap::new(),
+            categories: vec![BTreeSet::new(); NUM_CATEGORIES],
+        };
+        let mut page_size = 0;
+        let mut next_page_id = Some(first_page_id);
+        while let Some(page_id) = next_page_id {
+            let guard = bpm.fetch_page_read(page_id)?;
+            let data = guard.data();
+            if PageType::of(data) != Some(PageType::FreeSpaceMap) {
+                anyhow::bail!("{page_id:?} is not a free-space map page");
+            }
+            page_size = guard.page_size();
+            let fsm_page_index = state.fsm_pages.len();
+            state.fsm_pages.push(page_id);
+            let count = bytes::read_u32(data, ENTRY_COUNT_OFFSET) as usize;
+            for index in 0..count {
+                let offset = ENTRIES_OFFSET + index * ENTRY_SIZE;
+                let heap_page_id = PageId::from_u64(bytes::read_u64(data, offset));
+                let category = data[offset + 8];
+                state.entries.insert(
+                    heap_page_id,
+                    EntryLocation {
+                        fsm_page_index,
+                        index,
+                        category,
+                    },
+                );
+                state.categories[category as usize].insert(heap_page_id);
+            }
+            state.last_page_entries = count;
+            let next = PageId::from_u64(bytes::read_u64(data, NEXT_PAGE_ID_OFFSET));
+            next_page_id = next.is_valid().then_some(next);
+        }
+
+        Ok(Self {
+            bpm,
+            shared: SharedFsm::new(page_size, state),
+        })
+    }
+
+    /// Another handle on the map `shared` was taken from, without reading its pages again.
+    pub fn from_shared(bpm: &'a B, shared: SharedFsm) -> Self {
+        Self { bpm, shared }
+    }
+
+    /// The in-memory index of the map, shared with the handles opened from it.
+    pub fn shared(&self) -> SharedFsm {
+        self.shared.clone()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.shared.page_size
+    }
+
+    fn category_size(&self) -> usize {
+        self.page_size() / NUM_CATEGORIES
+    }
+
+    /// The category recorded for `free_bytes`, rounded down so that a page is never believed
+    /// to have more room than it has.
+    fn category_of(&self, free_bytes: usize) -> u8 {
+        (free_bytes / self.category_size()).min(NUM_CATEGORIES - 1) as u8
+    }
+
+    /// Find a heap page that is known to have at least `needed` free bytes.
+    pub fn find(&self, needed: usize) -> Option<PageId> {
+        let min_category = needed.div_ceil(self.category_size());
+        let state = self.shared.state.lock().unwrap();
+        state
+            .categories
+            .iter()
+            .skip(min_category)
+            .find_map(|pages| pages.first().copied())
+    }
+
+    /// The pages the map is stored in, in chain order.
+    pub fn fsm_page_ids(&self) -> Vec<PageId> {
+        self.shared.state.lock().unwrap().fsm_pages.clone()
+    }
+
+    /// The number of heap pages in the map, which is every page of the heap.
+    pub fn page_count(&self) -> usize {
+        self.shared.state.lock().unwrap().entries.len()
+    }
+
+    /// The number of bytes used in the pages of the heap, as an upper bound since free space
+    /// is recorded as a lower bound.
+    pub fn used_bytes(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state
+            .entries
+            .values()
+            .map(|entry| self.page_size() - entry.category as usize * self.category_size())
+            .sum()
+    }
+
+    /// The number of free bytes recorded for `page_id`, as a lower bound.
+    pub fn recorded_free_space(&self, page_id: PageId) -> Option<usize> {
+        let state = self.shared.state.lock().unwrap();
+        let entry = state.entries.get(&page_id)?;
+        Some(entry.category as usize * self.category_size())
+    }
+
+    /// Record that `page_id` has `free_bytes` free bytes, adding it to the map if it is new.
+    pub fn update(&self, page_id: PageId, free_bytes: usize) -> anyhow::Result<()> {
+        let category = self.category_of(free_bytes);
+        let mut state = self.shared.state.lock().unwrap();
+
+        let location = match state.entries.get(&page_id).copied() {
+            Some(entry) if entry.category == category => return Ok(()),
+            Some(entry) => {
+                state.categories[entry.category as usize].remove(&page_id);
+                entry
+            }
+            None => self.append_entry(&mut state)?,
+        };
+        let location = EntryLocation {
+            category,
+            ..location
+        };
+
+        let mut guard = self
+            .bpm
+            .fetch_page_write(state.fsm_pages[location.fsm_page_index])?;
+        let offset = ENTRIES_OFFSET + location.index * ENTRY_SIZE;
+        let mut entry = [0; ENTRY_SIZE];
+        bytes::write_u64(&mut entry, 0, page_id.to_u64());
+        entry[8] = category;
+        guard.write_at(offset, &entry);
+        drop(guard);
+
+        state.entries.insert(page_id, location);
+        state.categories[category as usize].insert(page_id);
+
+        Ok(())
+    }
+
+    /// Reserve the location of a new entry, linking a new FSM page if the last one is full.
+    fn append_entry(&self, state: &mut FsmState) -> anyhow::Result<EntryLocation> {
+        let capacity = (self.page_size() - ENTRIES_OFFSET) / ENTRY_SIZE;
+        let last_page_id = *state.fsm_pages.last().unwrap();
+        if state.last_page_entries == capacity {
+            let mut new_guard = self.bpm.new_page_write()?;
+            init_fsm_page(new_guard.data_mut());
+            let new_page_id = new_guard.page_id();
+            drop(new_guard);
+
+            let mut last_guard = self.bpm.fetch_page_write(last_page_id)?;
+            last_guard.write_at(NEXT_PAGE_ID_OFFSET, &new_page_id.to_u64().to_le_bytes());
+            state.fsm_pages.push(new_page_id);
+            state.last_page_entries = 0;
+        }
+
+        let fsm_page_index = state.fsm_pages.len() - 1;
+        let index = state.last_page_entries;
+        let mut guard = self.bpm.fetch_page_write(state.fsm_pages[fsm_page_index])?;
+        guard.write_at(ENTRY_COUNT_OFFSET, &(index as u32 + 1).to_le_bytes());
+        state.last_page_entries += 1;
+
+        Ok(EntryLocation {
+            fsm_page_index,
+            index,
+            category: 0,
+        })
+    }
+}
+
+fn init_fsm_page(data: &mut [u8]) {
+    data.fill(0);
+    bytes::write_u32(data, PAGE_TYPE_OFFSET, PageType::FreeSpaceMap as u32);
+    bytes::write_u64(data, NEXT_PAGE_ID_OFFSET, PageId::new_invalid().to_u64());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_find_and_update() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let (fsm, _) = FreeSpaceMap::create(&bpm).unwrap();
+        assert_eq!(fsm.find(1), None);
+
+        fsm.update(PageId::new(10), 100).unwrap();
+        fsm.update(PageId::new(11), 5000).unwrap();
+        assert_eq!(fsm.find(0), Some(PageId::new(10)));
+        assert_eq!(fsm.find(96), Some(PageId::new(10)));
+        // 100 bytes are rounded down to 96.
+        assert_eq!(fsm.find(100), Some(PageId::new(11)));
+        assert_eq!(fsm.find(5000), None);
+        assert_eq!(fsm.find(4992), Some(PageId::new(11)));
+
+        fsm.update(PageId::new(11), 0).unwrap();
+        assert_eq!(fsm.find(100), None);
+        assert_eq!(fsm.recorded_free_space(PageId::new(11)), Some(0));
+        assert_eq!(fsm.recorded_free_space(PageId::new(12)), None);
+        assert_eq!(fsm.page_count(), 2);
+        assert_eq!(fsm.used_bytes(), 2 * DEFAULT_PAGE_SIZE - 96);
+    }
+
+    #[test]
+    fn test_persistence_across_fsm_pages() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let (fsm, first_page_id) = FreeSpaceMap::create(&bpm).unwrap();
+
+        // More entries than one FSM page can hold.
+        const N: usize = 2000;
+        for i in 0..N {
+            fsm.update(PageId::new(1000 + i), i * 4).unwrap();
+        }
+        assert!(fsm.shared.state.lock().unwrap().fsm_pages.len() > 1);
+        drop(fsm);
+
+        let fsm = FreeSpaceMap::open(&bpm, first_page_id).unwrap();
+        for i in (0..N).step_by(97) {
+            assert_eq!(
+                fsm.recorded_free_space(PageId::new(1000 + i)),
+                Some((i * 4).min(DEFAULT_PAGE_SIZE - 32) / 32 * 32)
+            );
+        }
+        assert_eq!(fsm.find(N * 4 - 32), Some(PageId::new(1000 + N - 8)));
+    }
+}
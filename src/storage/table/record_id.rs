@@ -0,0 +1,54 @@
+use crate::{
+    storage::page::{bytes, slotted_page::SlotId},
+    PageId,
+};
+
+/// Identifies a tuple in a table heap by the page holding it and its slot in that page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordId {
+    pub page_id: PageId,
+    pub slot_id: SlotId,
+}
+
+impl RecordId {
+    pub const SIZE: usize = 12;
+
+    pub fn new(page_id: PageId, slot_id: SlotId) -> Self {
+        Self { page_id, slot_id }
+    }
+
+    /// The record id that sorts before every other.
+    pub fn min() -> Self {
+        Self::new(PageId::new(0), SlotId::new(0))
+    }
+
+    /// The record id that sorts after every other.
+    pub fn max() -> Self {
+        Self::new(PageId::new_invalid(), SlotId::new(u32::MAX))
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut buf = [0; Self::SIZE];
+        bytes::write_u64(&mut buf, 0, self.page_id.to_u64());
+        bytes::write_u32(&mut buf, 8, self.slot_id.as_u32());
+        buf
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            page_id: PageId::from_u64(bytes::read_u64(data, 0)),
+            slot_id: SlotId::new(bytes::read_u32(data, 8)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_id_bytes() {
+        let rid = RecordId::new(PageId::new(12345), SlotId::new(67));
+        assert_eq!(RecordId::from_bytes(&rid.to_bytes()), rid);
+    }
+}
@@ -0,0 +1,517 @@
+//! A table heap stores the tuples of one table in a linked list of slotted pages.
+//!
+//! The heap is identified by its header page, which records the first and the last page of
+//! the list and the first page of its [`FreeSpaceMap`]. Every page is accessed through the
+//! buffer pool with page guards, so a heap can be much larger than the buffer pool.
+//!
+//! | offset | size | field                   |
+//! |--------|------|-------------------------|
+//! | 0      | 16   | common page header      |
+//! | 16     | 8    | first page id           |
+//! | 24     | 8    | last page id            |
+//! | 32     | 8    | free-space map page id  |
+
+use crate::{
+    buffer::buffer_pool_manager::BufferPoolManager,
+    storage::{
+        overflow::{OverflowConfig, OverflowPointer, OverflowStore},
+        page::{
+            bytes,
+            page::{PageType, COMMON_HEADER_SIZE, PAGE_TYPE_OFFSET},
+            slotted_page::{SlottedPage, SLOT_SIZE},
+        },
+    },
+    PageId,
+};
+
+use super::{
+    free_space_map::{FreeSpaceMap, SharedFsm},
+    record_id::RecordId,
+    table_iterator::TableIterator,
+};
+
+const FIRST_PAGE_ID_OFFSET: usize = COMMON_HEADER_SIZE;
+const LAST_PAGE_ID_OFFSET: usize = FIRST_PAGE_ID_OFFSET + 8;
+const FSM_PAGE_ID_OFFSET: usize = LAST_PAGE_ID_OFFSET + 8;
+
+/// A tuple copied out of the heap, with its record id.
+pub type HeapTuple = (RecordId, Vec<u8>);
+
+pub struct TableHeap<'a, B: BufferPoolManager + ?Sized> {
+    bpm: &'a B,
+    header_page_id: PageId,
+    fsm: FreeSpaceMap<'a, B>,
+    overflow_config: Option<OverflowConfig>,
+}
+
+impl<'a, B: BufferPoolManager + ?Sized> TableHeap<'a, B> {
+    /// Create an empty table heap with a header page, one table page and a free-space map.
+    pub fn create(bpm: &'a B) -> anyhow::Result<Self> {
+        let mut header_guard = bpm.new_page_write()?;
+        let mut first_guard = bpm.new_page_write()?;
+        let first_page = SlottedPage::init(first_guard.data_mut());
+        let first_free_space = free_space_of(&first_page);
+        let first_page_id = first_guard.page_id();
+        drop(first_guard);
+
+        let (fsm, fsm_page_id) = FreeSpaceMap::create(bpm)?;
+        fsm.update(first_page_id, first_free_space)?;
+
+        let header = header_guard.data_mut();
+        bytes::write_u32(header, PAGE_TYPE_OFFSET, PageType::TableHeapHeader as u32);
+        bytes::write_u64(header, FIRST_PAGE_ID_OFFSET, first_page_id.to_u64());
+        bytes::write_u64(header, LAST_PAGE_ID_OFFSET, first_page_id.to_u64());
+        bytes::write_u64(header, FSM_PAGE_ID_OFFSET, fsm_page_id.to_u64());
+
+        Ok(Self {
+            bpm,
+            header_page_id: header_guard.page_id(),
+            fsm,
+            overflow_config: None,
+        })
+    }
+
+    /// Open an existing table heap through its header page.
+    pub fn open(bpm: &'a B, header_page_id: PageId) -> anyhow::Result<Self> {
+        let guard = bpm.fetch_page_read(header_page_id)?;
+        if PageType::of(guard.data()) != Some(PageType::TableHeapHeader) {
+            anyhow::bail!("{header_page_id:?} is not a table heap header page");
+        }
+        let fsm_page_id = PageId::from_u64(bytes::read_u64(guard.data(), FSM_PAGE_ID_OFFSET));
+        drop(guard);
+
+        Ok(Self {
+            bpm,
+            header_page_id,
+            fsm: FreeSpaceMap::open(bpm, fsm_page_id)?,
+            overflow_config: None,
+        })
+    }
+
+    /// Open another handle on the heap whose header page is `header_page_id`, sharing the
+    /// free-space map `fsm` of a handle opened before. Handles that insert into the same heap
+    /// concurrently must be opened this way.
+    pub fn open_with_fsm(bpm: &'a B, header_page_id: PageId, fsm: SharedFsm) -> Self {
+        Self {
+            bpm,
+            header_page_id,
+            fsm: FreeSpaceMap::from_shared(bpm, fsm),
+            overflow_config: None,
+        }
+    }
+
+    /// Use `config` instead of the default thresholds for moving tuples to overflow pages.
+    pub fn with_overflow_config(self, config: OverflowConfig) -> Self {
+        Self {
+            overflow_config: Some(config),
+            ..self
+        }
+    }
+
+    pub fn header_page_id(&self) -> PageId {
+        self.header_page_id
+    }
+
+    pub fn free_space_map(&self) -> &FreeSpaceMap<'a, B> {
+        &self.fsm
+    }
+
+    pub fn first_page_id(&self) -> anyhow::Result<PageId> {
+        let guard = self.bpm.fetch_page_read(self.header_page_id)?;
+        Ok(PageId::from_u64(bytes::read_u64(
+            guard.data(),
+            FIRST_PAGE_ID_OFFSET,
+        )))
+    }
+
+    pub(crate) fn overflow_store(&self, page_size: usize) -> OverflowStore<'a, B> {
+        let config = self
+            .overflow_config
+            .unwrap_or_else(|| OverflowConfig::new(page_size));
+        OverflowStore::new(self.bpm, config)
+    }
+
+    /// Insert `tuple` into a page the free-space map knows to have enough room, or into a new
+    /// page appended to the heap if there is none.
+    pub fn insert_tuple(&self, tuple: &[u8]) -> anyhow::Result<RecordId> {
+        loop {
+            let Some(page_id) = self.fsm.find(self.space_needed(tuple.len())) else {
+                return self.insert_into_new_page(tuple);
+            };
+            let mut guard = self.bpm.fetch_page_write(page_id)?;
+            let store = self.overflow_store(guard.page_size());
+            let mut page = SlottedPage::new(guard.data_mut());
+            let slot_id = store.insert(&mut page, tuple)?;
+            let free_space = free_space_of(&page);
+            drop(guard);
+            // Either record the space left after inserting, or correct an entry that another
+            // inserter made stale before retrying.
+            self.fsm.update(page_id, free_space)?;
+            if let Some(slot_id) = slot_id {
+                return Ok(RecordId::new(page_id, slot_id));
+            }
+        }
+    }
+
+    /// The number of free bytes a page needs to accept a tuple of `len` bytes.
+    fn space_needed(&self, len: usize) -> usize {
+        let page_size = self.fsm.page_size();
+        let stored_len = if self
+            .overflow_store(page_size)
+            .is_overflowing(len, page_size)
+        {
+            OverflowPointer::SIZE
+        } else {
+            len
+        };
+        stored_len + SLOT_SIZE
+    }
+
+    fn insert_into_new_page(&self, tuple: &[u8]) -> anyhow::Result<RecordId> {
+        let mut new_guard = self.bpm.new_page_write()?;
+        let new_page_id = new_guard.page_id();
+        let store = self.overflow_store(new_guard.page_size());
+        let mut new_page = SlottedPage::init(new_guard.data_mut());
+        let Some(slot_id) = store.insert(&mut new_page, tuple)? else {
+            anyhow::bail!(
+                "tuple of {} bytes does not fit into an empty page",
+                tuple.len()
+            );
+        };
+        let free_space = free_space_of(&new_page);
+        drop(new_guard);
+
+        // The header latch serializes appends, so that two inserters never race to link a new
+        // last page.
+        let mut header_guard = self.bpm.fetch_page_write(self.header_page_id)?;
+        let last_page_id =
+            PageId::from_u64(bytes::read_u64(header_guard.data(), LAST_PAGE_ID_OFFSET));
+        let mut last_guard = self.bpm.fetch_page_write(last_page_id)?;
+        SlottedPage::new(last_guard.data_mut()).set_next_page_id(Some(new_page_id));
+        drop(last_guard);
+        bytes::write_u64(
+            header_guard.data_mut(),
+            LAST_PAGE_ID_OFFSET,
+            new_page_id.to_u64(),
+        );
+        drop(header_guard);
+
+        self.fsm.update(new_page_id, free_space)?;
+
+        Ok(RecordId::new(new_page_id, slot_id))
+    }
+
+    /// Get a copy of the tuple `rid` refers to, or None if it has been deleted.
+    pub fn get_tuple(&self, rid: RecordId) -> anyhow::Result<Option<Vec<u8>>> {
+        let guard = self.bpm.fetch_page_read(rid.page_id)?;
+        let store = self.overflow_store(guard.page_size());
+        let page = SlottedPage::new(guard.data());
+        let tuple = store.get(&page, rid.slot_id)?;
+
+        Ok(tuple.map(|tuple| tuple.into_owned()))
+    }
+
+    /// Replace the tuple `rid` refers to without changing its record id. Return false if the
+    /// tuple has been deleted or its page does not have enough space for the new version; the
+    /// caller then has to delete and re-insert the tuple.
+    pub fn update_tuple(&self, rid: RecordId, tuple: &[u8]) -> anyhow::Result<bool> {
+        let mut guard = self.bpm.fetch_page_write(rid.page_id)?;
+        let store = self.overflow_store(guard.page_size());
+        let mut page = SlottedPage::new(guard.data_mut());
+        let updated = store.update(&mut page, rid.slot_id, tuple)?;
+        let free_space = free_space_of(&page);
+        drop(guard);
+        self.fsm.update(rid.page_id, free_space)?;
+
+        Ok(updated)
+    }
+
+    /// Turn the tuple `rid` refers to into a tombstone and free its overflow pages.
+    /// Return false if the tuple has already been deleted.
+    pub fn mark_delete(&self, rid: RecordId) -> anyhow::Result<bool> {
+        let mut guard = self.bpm.fetch_page_write(rid.page_id)?;
+        let store = self.overflow_store(guard.page_size());
+        let mut page = SlottedPage::new(guard.data_mut());
+        let deleted = store.delete(&mut page, rid.slot_id)?;
+        let free_space = free_space_of(&page);
+        drop(guard);
+        self.fsm.update(rid.page_id, free_space)?;
+
+        Ok(deleted)
+    }
+
+    /// Compact the page `page_id` so that its dead space becomes contiguous.
+    pub fn compact_page(&self, page_id: PageId) -> anyhow::Result<()> {
+        let mut guard = self.bpm.fetch_page_write(page_id)?;
+        let mut page = SlottedPage::new(guard.data_mut());
+        page.compact();
+        let free_space = free_space_of(&page);
+        drop(guard);
+
+        self.fsm.update(page_id, free_space)
+    }
+
+    /// Copy out the live tuples of the heap page `page_id`, and return them with the id of the
+    /// next page of the heap.
+    pub fn read_page(&self, page_id: PageId) -> anyhow::Result<(Vec<HeapTuple>, Option<PageId>)> {
+        let guard = self.bpm.fetch_page_read(page_id)?;
+        let store = self.overflow_store(guard.page_size());
+        let page = SlottedPage::new(guard.data());
+        let mut tuples = Vec::new();
+        for (slot_id, _) in page.iter() {
+            if let Some(tuple) = store.get(&page, slot_id)? {
+                tuples.push((RecordId::new(page_id, slot_id), tuple.into_owned()));
+            }
+        }
+
+        Ok((tuples, page.next_page_id()))
+    }
+
+    /// Iterate over every live tuple in page order.
+    pub fn iter(&self) -> anyhow::Result<TableIterator<'_, 'a, B>> {
+        Ok(TableIterator::new(self, self.first_page_id()?))
+    }
+
+    /// Free every page of the heap for the buffer pool to reuse: its pages, the overflow chains
+    /// of their tuples, its free-space map and its header. The pages are all found first, so
+    /// that failing to read one frees none of them. Nobody else may use the heap.
+    pub fn free_pages(&self) -> anyhow::Result<()> {
+        let mut page_ids = Vec::new();
+        let mut next_page_id = Some(self.first_page_id()?);
+        while let Some(page_id) = next_page_id {
+            let guard = self.bpm.fetch_page_read(page_id)?;
+            let store = self.overflow_store(guard.page_size());
+            let page = SlottedPage::new(guard.data());
+            for (slot_id, data) in page.iter() {
+                if page.is_overflow(slot_id) {
+                    page_ids.extend(store.chain_page_ids(OverflowPointer::from_bytes(data)?)?);
+                }
+            }
+            next_page_id = page.next_page_id();
+            page_ids.push(page_id);
+        }
+        page_ids.extend(self.fsm.fsm_page_ids());
+        page_ids.push(self.header_page_id);
+        self.bpm.delete_pages(&page_ids)
+    }
+}
+
+/// The space recorded in the free-space map for `page`. Dead space counts as free because
+/// inserts compact the page when they need it.
+fn free_space_of<T: AsRef<[u8]>>(page: &SlottedPage<T>) -> usize {
+    page.free_space() + page.dead_space()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManagerImpl,
+        storage::{
+            disk::{DiskManager, LimeBaseDiskManager},
+            page::page::DEFAULT_PAGE_SIZE,
+        },
+    };
+
+    use super::*;
+
+    fn tuple(i: usize) -> Vec<u8> {
+        format!("tuple-{i:05}-{}", "x".repeat(i % 200)).into_bytes()
+    }
+
+    #[test]
+    fn test_insert_get_iterate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        // The table spans far more pages than the buffer pool holds.
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let heap = TableHeap::create(&bpm).unwrap();
+
+        const N: usize = 2000;
+        let rids = (0..N)
+            .map(|i| heap.insert_tuple(&tuple(i)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(rids.last().unwrap().page_id.as_usize() > 4 * 4);
+
+        for (i, rid) in rids.iter().enumerate() {
+            assert_eq!(heap.get_tuple(*rid).unwrap(), Some(tuple(i)));
+        }
+        // Smaller tuples may fill gaps in earlier pages, so the scan follows record ids rather
+        // than insertion order.
+        let scanned = heap
+            .iter()
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        let mut expected = rids
+            .iter()
+            .enumerate()
+            .map(|(i, rid)| (*rid, tuple(i)))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(scanned, expected);
+    }
+
+    #[test]
+    fn test_free_space_is_reused() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let heap = TableHeap::create(&bpm).unwrap();
+
+        let fixed = vec![1; 1000];
+        let rids = (0..40)
+            .map(|_| heap.insert_tuple(&fixed).unwrap())
+            .collect::<Vec<_>>();
+        let first_page_id = rids[0].page_id;
+        let last_page_id = rids.last().unwrap().page_id;
+        for rid in rids.iter().filter(|rid| rid.page_id == first_page_id) {
+            assert!(heap.mark_delete(*rid).unwrap());
+        }
+        assert!(
+            heap.free_space_map()
+                .recorded_free_space(first_page_id)
+                .unwrap()
+                > 7 * fixed.len()
+        );
+
+        // The freed space of the first page is found instead of appending a new page.
+        for _ in 0..8 {
+            let rid = heap.insert_tuple(&fixed).unwrap();
+            assert_eq!(rid.page_id, first_page_id);
+        }
+        let rid = heap.insert_tuple(&fixed).unwrap();
+        assert!(rid.page_id > last_page_id);
+
+        heap.compact_page(first_page_id).unwrap();
+        assert_eq!(heap.iter().unwrap().count(), 40 + 1);
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let heap = TableHeap::create(&bpm).unwrap();
+
+        let mut expected = (0..500)
+            .map(|i| (heap.insert_tuple(&tuple(i)).unwrap(), tuple(i)))
+            .collect::<HashMap<_, _>>();
+        let rids = expected.keys().copied().collect::<Vec<_>>();
+        for (i, rid) in rids.iter().enumerate() {
+            if i % 3 == 0 {
+                assert!(heap.mark_delete(*rid).unwrap());
+                assert!(!heap.mark_delete(*rid).unwrap());
+                assert_eq!(heap.get_tuple(*rid).unwrap(), None);
+                expected.remove(rid);
+            } else if i % 3 == 1 {
+                let updated = format!("updated-{i}").into_bytes();
+                assert!(heap.update_tuple(*rid, &updated).unwrap());
+                expected.insert(*rid, updated);
+            }
+        }
+
+        let scanned = heap
+            .iter()
+            .unwrap()
+            .collect::<anyhow::Result<HashMap<_, _>>>()
+            .unwrap();
+        assert_eq!(scanned, expected);
+    }
+
+    #[test]
+    fn test_large_tuples() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let heap = TableHeap::create(&bpm).unwrap();
+
+        let large = vec![0x5a; DEFAULT_PAGE_SIZE * 3];
+        let small_rid = heap.insert_tuple(b"small").unwrap();
+        let large_rid = heap.insert_tuple(&large).unwrap();
+        assert_eq!(small_rid.page_id, large_rid.page_id);
+        assert_eq!(heap.get_tuple(large_rid).unwrap(), Some(large.clone()));
+        assert_eq!(
+            heap.iter()
+                .unwrap()
+                .map(|t| t.unwrap().1)
+                .collect::<Vec<_>>(),
+            vec![b"small".to_vec(), large]
+        );
+        assert!(heap.mark_delete(large_rid).unwrap());
+        assert_eq!(heap.iter().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let disk_manager =
+            LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, tempdir.path().join("test.db")).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let header_page_id = {
+            let heap = TableHeap::create(&bpm).unwrap();
+            for i in 0..300 {
+                heap.insert_tuple(&tuple(i)).unwrap();
+            }
+            heap.header_page_id()
+        };
+
+        let heap = TableHeap::open(&bpm, header_page_id).unwrap();
+        heap.insert_tuple(&tuple(300)).unwrap();
+        let mut scanned = heap
+            .iter()
+            .unwrap()
+            .map(|t| t.unwrap().1)
+            .collect::<Vec<_>>();
+        scanned.sort();
+        assert_eq!(scanned, (0..=300).map(tuple).collect::<Vec<_>>());
+
+        let first_page_id = heap.first_page_id().unwrap();
+        assert!(TableHeap::open(&bpm, first_page_id).is_err());
+    }
+
+    #[test]
+    fn test_reopen_after_restart() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let filename = tempdir.path().join("test.db");
+        let (header_page_id, rids, recorded) = {
+            let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+            let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+            let heap = TableHeap::create(&bpm).unwrap();
+            let rids = (0..300)
+                .map(|i| heap.insert_tuple(&tuple(i)).unwrap())
+                .collect::<Vec<_>>();
+            for rid in &rids[..100] {
+                heap.mark_delete(*rid).unwrap();
+            }
+            let recorded = heap
+                .free_space_map()
+                .recorded_free_space(rids[0].page_id)
+                .unwrap();
+            (heap.header_page_id(), rids, recorded)
+        };
+
+        let disk_manager = LimeBaseDiskManager::new(DEFAULT_PAGE_SIZE, &filename).unwrap();
+        let bpm = BufferPoolManagerImpl::new(4, disk_manager);
+        let heap = TableHeap::open(&bpm, header_page_id).unwrap();
+        for (i, rid) in rids.iter().enumerate() {
+            let expected = (i >= 100).then(|| tuple(i));
+            assert_eq!(heap.get_tuple(*rid).unwrap(), expected);
+        }
+        // The persisted free-space map lets new tuples reuse the space freed before restart.
+        assert_eq!(
+            heap.free_space_map().recorded_free_space(rids[0].page_id),
+            Some(recorded)
+        );
+        let last_page_id = rids.iter().map(|rid| rid.page_id).max().unwrap();
+        for i in 0..100 {
+            let rid = heap.insert_tuple(&tuple(i)).unwrap();
+            assert!(rid.page_id <= last_page_id);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::VecDeque;
+
+use crate::{buffer::buffer_pool_manager::BufferPoolManager, PageId};
+
+use super::table_heap::{HeapTuple, TableHeap};
+
+/// Yields every live tuple of a [`TableHeap`] in page order.
+///
+/// The tuples of one page are copied out at a time, so no page stays pinned or latched
+/// between calls to `next` and the heap can be modified while it is being iterated.
+pub struct TableIterator<'h, 'a, B: BufferPoolManager + ?Sized> {
+    heap: &'h TableHeap<'a, B>,
+    next_page_id: Option<PageId>,
+    buffered: VecDeque<HeapTuple>,
+}
+
+impl<'h, 'a, B: BufferPoolManager + ?Sized> TableIterator<'h, 'a, B> {
+    pub(crate) fn new(heap: &'h TableHeap<'a, B>, first_page_id: PageId) -> Self {
+        Self {
+            heap,
+            next_page_id: Some(first_page_id),
+            buffered: VecDeque::new(),
+        }
+    }
+
+    /// Copy the live tuples of the next page into the buffer.
+    fn load_next_page(&mut self, page_id: PageId) -> anyhow::Result<()> {
+        let (tuples, next_page_id) = self.heap.read_page(page_id)?;
+        self.buffered.extend(tuples);
+        self.next_page_id = next_page_id;
+
+        Ok(())
+    }
+}
+
+impl<B: BufferPoolManager + ?Sized> Iterator for TableIterator<'_, '_, B> {
+    type Item = anyhow::Result<HeapTuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.buffered.pop_front() {
+                return Some(Ok(entry));
+            }
+            let page_id = self.next_page_id.take()?;
+            if let Err(err) = self.load_next_page(page_id) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
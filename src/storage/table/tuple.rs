@@ -0,0 +1,340 @@
+//! Encoding of typed tuples into the bytes stored in table heap slots.
+//!
+//! See [`Schema`] for the layout. [`TupleRef`] decodes columns straight out of the encoded
+//! bytes, for example a slot of a latched page, so projecting a few columns never copies or
+//! decodes the rest of the tuple.
+
+use crate::{
+    catalog::schema::Schema,
+    storage::{page::bytes, table::record_id::RecordId},
+    types::{
+        data_type::DataType,
+        date_time::{Date, Timestamp},
+        decimal::Decimal,
+        value::Value,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuple {
+    values: Vec<Value>,
+    /// Where the tuple is stored, if it was read from a table heap.
+    rid: Option<RecordId>,
+}
+
+impl Tuple {
+    pub fn new(values: Vec<Value>) -> Self {
+        Self { values, rid: None }
+    }
+
+    pub fn with_rid(self, rid: RecordId) -> Self {
+        Self {
+            rid: Some(rid),
+            ..self
+        }
+    }
+
+    pub fn rid(&self) -> Option<RecordId> {
+        self.rid
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn value(&self, index: usize) -> &Value {
+        &self.values[index]
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    /// Check that the tuple can be stored with `schema`.
+    fn validate(&self, schema: &Schema) -> anyhow::Result<()> {
+        if self.values.len() != schema.column_count() {
+            anyhow::bail!(
+                "tuple has {} values but the schema has {} columns",
+                self.values.len(),
+                schema.column_count()
+            );
+        }
+        for (value, column) in self.values.iter().zip(schema.columns()) {
+            if value.is_null() && !column.nullable {
+                anyhow::bail!("column \"{}\" must not be NULL", column.name);
+            }
+            if !value.conforms_to(&column.data_type) {
+                anyhow::bail!(
+                    "value {value} cannot be stored in column \"{}\" of type {}",
+                    column.name,
+                    column.data_type
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The number of bytes the tuple occupies when encoded with `schema`.
+    pub fn encoded_len(&self, schema: &Schema) -> anyhow::Result<usize> {
+        self.validate(schema)?;
+        let var_len: usize = self
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::Varchar(s) => s.len(),
+                Value::Bytea(b) => b.len(),
+                _ => 0,
+            })
+            .sum();
+
+        Ok(schema.fixed_len() + var_len)
+    }
+
+    /// Encode the tuple into `buf`, which must be exactly [`Tuple::encoded_len`] bytes long.
+    pub fn encode_into(&self, schema: &Schema, buf: &mut [u8]) -> anyhow::Result<()> {
+        let len = self.encoded_len(schema)?;
+        if buf.len() != len {
+            anyhow::bail!("buffer of {} bytes for a tuple of {len} bytes", buf.len());
+        }
+        buf[..schema.fixed_len()].fill(0);
+
+        let mut var_offset = schema.fixed_len();
+        for (i, (value, column)) in self.values.iter().zip(schema.columns()).enumerate() {
+            let offset = schema.offset_of(i);
+            let fixed = &mut buf[offset..offset + column.data_type.fixed_size()];
+            match (value, column.data_type) {
+                (Value::Null, _) => buf[i / 8] |= 1 << (i % 8),
+                (Value::Boolean(v), _) => fixed[0] = *v as u8,
+                (Value::Int8(v), _) => fixed.copy_from_slice(&v.to_le_bytes()),
+                (Value::Int16(v), _) => fixed.copy_from_slice(&v.to_le_bytes()),
+                (Value::Int32(v), _) => fixed.copy_from_slice(&v.to_le_bytes()),
+                (Value::Int64(v), _) => fixed.copy_from_slice(&v.to_le_bytes()),
+                (Value::Float64(v), _) => fixed.copy_from_slice(&v.to_le_bytes()),
+                (Value::Date(v), _) => fixed.copy_from_slice(&v.0.to_le_bytes()),
+                (Value::Timestamp(v), _) => fixed.copy_from_slice(&v.0.to_le_bytes()),
+                (Value::Decimal(v), DataType::Decimal { precision, scale }) => {
+                    let v = v.fit(precision, scale).expect("validated above");
+                    fixed.copy_from_slice(&v.mantissa().to_le_bytes());
+                }
+                (Value::Varchar(_) | Value::Bytea(_), _) => {
+                    let data = match value {
+                        Value::Varchar(s) => s.as_bytes(),
+                        Value::Bytea(b) => b.as_slice(),
+                        _ => unreachable!(),
+                    };
+                    bytes::write_u32(fixed, 0, var_offset as u32);
+                    bytes::write_u32(fixed, 4, data.len() as u32);
+                    buf[var_offset..var_offset + data.len()].copy_from_slice(data);
+                    var_offset += data.len();
+                }
+                (value, data_type) => unreachable!("{value:?} validated against {data_type}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self, schema: &Schema) -> anyhow::Result<Vec<u8>> {
+        let mut buf = vec![0; self.encoded_len(schema)?];
+        self.encode_into(schema, &mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn from_bytes(schema: &Schema, data: &[u8]) -> anyhow::Result<Self> {
+        TupleRef::new(schema, data)?.to_tuple()
+    }
+}
+
+impl From<Vec<Value>> for Tuple {
+    fn from(values: Vec<Value>) -> Self {
+        Self::new(values)
+    }
+}
+
+/// A borrowed view over an encoded tuple.
+#[derive(Debug, Clone, Copy)]
+pub struct TupleRef<'a> {
+    schema: &'a Schema,
+    data: &'a [u8],
+}
+
+impl<'a> TupleRef<'a> {
+    pub fn new(schema: &'a Schema, data: &'a [u8]) -> anyhow::Result<Self> {
+        if data.len() < schema.fixed_len() {
+            anyhow::bail!(
+                "encoded tuple of {} bytes is shorter than its fixed-length part of {} bytes",
+                data.len(),
+                schema.fixed_len()
+            );
+        }
+        Ok(Self { schema, data })
+    }
+
+    pub fn is_null(&self, index: usize) -> bool {
+        self.data[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// The bytes of a variable-length column.
+    fn var_bytes(&self, fixed: &[u8]) -> anyhow::Result<&'a [u8]> {
+        let offset = bytes::read_u32(fixed, 0) as usize;
+        let len = bytes::read_u32(fixed, 4) as usize;
+        self.data
+            .get(offset..offset + len)
+            .ok_or_else(|| anyhow::anyhow!("variable-length column out of bounds"))
+    }
+
+    /// Decode the column at `index`.
+    pub fn value(&self, index: usize) -> anyhow::Result<Value> {
+        if self.is_null(index) {
+            return Ok(Value::Null);
+        }
+        let data_type = self.schema.column(index).data_type;
+        let offset = self.schema.offset_of(index);
+        let fixed = &self.data[offset..offset + data_type.fixed_size()];
+        let value = match data_type {
+            DataType::Boolean => Value::Boolean(fixed[0] != 0),
+            DataType::Int8 => Value::Int8(i8::from_le_bytes(fixed.try_into()?)),
+            DataType::Int16 => Value::Int16(i16::from_le_bytes(fixed.try_into()?)),
+            DataType::Int32 => Value::Int32(i32::from_le_bytes(fixed.try_into()?)),
+            DataType::Int64 => Value::Int64(i64::from_le_bytes(fixed.try_into()?)),
+            DataType::Float64 => Value::Float64(f64::from_le_bytes(fixed.try_into()?)),
+            DataType::Date => Value::Date(Date(i32::from_le_bytes(fixed.try_into()?))),
+            DataType::Timestamp => {
+                Value::Timestamp(Timestamp(i64::from_le_bytes(fixed.try_into()?)))
+            }
+            DataType::Decimal { scale, .. } => {
+                Value::Decimal(Decimal::new(i128::from_le_bytes(fixed.try_into()?), scale))
+            }
+            DataType::Varchar(_) => {
+                Value::Varchar(std::str::from_utf8(self.var_bytes(fixed)?)?.to_string())
+            }
+            DataType::Bytea => Value::Bytea(self.var_bytes(fixed)?.to_vec()),
+        };
+
+        Ok(value)
+    }
+
+    /// Decode only the columns at `indices`, in that order.
+    pub fn project(&self, indices: &[usize]) -> anyhow::Result<Tuple> {
+        let values = indices
+            .iter()
+            .map(|i| self.value(*i))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Tuple::new(values))
+    }
+
+    pub fn to_tuple(&self) -> anyhow::Result<Tuple> {
+        let values = (0..self.schema.column_count())
+            .map(|i| self.value(i))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Tuple::new(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        catalog::schema::Column,
+        storage::page::{page::DEFAULT_PAGE_SIZE, slotted_page::SlottedPage},
+    };
+
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Column::new("b", DataType::Boolean),
+            Column::new("i8", DataType::Int8),
+            Column::new("i16", DataType::Int16),
+            Column::new("i32", DataType::Int32).not_null(),
+            Column::new("i64", DataType::Int64),
+            Column::new("f", DataType::Float64),
+            Column::new(
+                "d",
+                DataType::Decimal {
+                    precision: 10,
+                    scale: 2,
+                },
+            ),
+            Column::new("s", DataType::Varchar(Some(16))),
+            Column::new("bytes", DataType::Bytea),
+            Column::new("date", DataType::Date),
+            Column::new("ts", DataType::Timestamp),
+        ])
+    }
+
+    fn tuple() -> Tuple {
+        Tuple::new(vec![
+            Value::Boolean(true),
+            Value::Int8(-8),
+            Value::Int16(1600),
+            Value::Int32(-320000),
+            Value::Null,
+            Value::Float64(6.25),
+            Value::Decimal(Decimal::parse("12.345").unwrap()),
+            Value::Varchar("limebase".into()),
+            Value::Bytea(vec![0, 1, 2, 255]),
+            Value::Date(Date::parse("2024-02-29").unwrap()),
+            Value::Timestamp(Timestamp::parse("2024-02-29 12:34:56.789").unwrap()),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let schema = schema();
+        let tuple = tuple();
+        let bytes = tuple.to_bytes(&schema).unwrap();
+        assert_eq!(bytes.len(), schema.fixed_len() + "limebase".len() + 4);
+
+        let decoded = Tuple::from_bytes(&schema, &bytes).unwrap();
+        // DECIMAL values are rounded to the scale of the column.
+        let mut expected = tuple.into_values();
+        expected[6] = Value::Decimal(Decimal::parse("12.35").unwrap());
+        assert_eq!(decoded.into_values(), expected);
+    }
+
+    #[test]
+    fn test_validation() {
+        let schema = schema();
+        let mut values = tuple().into_values();
+        values[3] = Value::Null;
+        assert!(Tuple::new(values).to_bytes(&schema).is_err());
+
+        let mut values = tuple().into_values();
+        values[7] = Value::Varchar("x".repeat(17));
+        assert!(Tuple::new(values).to_bytes(&schema).is_err());
+
+        let mut values = tuple().into_values();
+        values[4] = Value::Int32(1);
+        assert!(Tuple::new(values).to_bytes(&schema).is_err());
+
+        assert!(Tuple::new(vec![Value::Null]).to_bytes(&schema).is_err());
+        assert!(TupleRef::new(&schema, &[0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_encode_into_slot_and_project() {
+        let schema = schema();
+        let tuple = tuple();
+        let len = tuple.encoded_len(&schema).unwrap();
+
+        let mut buf = vec![0; DEFAULT_PAGE_SIZE];
+        let mut page = SlottedPage::init(buf.as_mut_slice());
+        let slot_id = page
+            .insert_with(len, |slot| tuple.encode_into(&schema, slot))
+            .unwrap()
+            .unwrap();
+
+        // Decode two columns straight out of the slot.
+        let tuple_ref = TupleRef::new(&schema, page.get(slot_id).unwrap()).unwrap();
+        assert!(tuple_ref.is_null(4));
+        assert_eq!(
+            tuple_ref.project(&[7, 3, 4]).unwrap().into_values(),
+            vec![
+                Value::Varchar("limebase".into()),
+                Value::Int32(-320000),
+                Value::Null
+            ]
+        );
+    }
+}
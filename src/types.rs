@@ -0,0 +1,4 @@
+pub mod data_type;
+pub mod date_time;
+pub mod decimal;
+pub mod value;
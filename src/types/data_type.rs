@@ -0,0 +1,92 @@
+use std::fmt;
+
+/// SQL data types supported by limebase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Boolean,
+    /// 8-bit signed integer.
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Float64,
+    /// Fixed-point number with at most `precision` digits, `scale` of which are fractional.
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    /// Character string with an optional maximum length in characters.
+    Varchar(Option<u32>),
+    Bytea,
+    Date,
+    Timestamp,
+}
+
+impl DataType {
+    /// The largest precision of a DECIMAL, bounded by the 128-bit mantissa.
+    pub const MAX_DECIMAL_PRECISION: u8 = 38;
+
+    /// Number of bytes the type occupies in the fixed-length part of an encoded tuple.
+    /// Variable-length types store an (offset, length) pair there.
+    pub fn fixed_size(&self) -> usize {
+        match self {
+            DataType::Boolean | DataType::Int8 => 1,
+            DataType::Int16 => 2,
+            DataType::Int32 | DataType::Date => 4,
+            DataType::Int64 | DataType::Float64 | DataType::Timestamp => 8,
+            DataType::Decimal { .. } => 16,
+            DataType::Varchar(_) | DataType::Bytea => 8,
+        }
+    }
+
+    pub fn is_variable_length(&self) -> bool {
+        matches!(self, DataType::Varchar(_) | DataType::Bytea)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || matches!(self, DataType::Float64 | DataType::Decimal { .. })
+    }
+
+    /// Stable tag used when a type is persisted, for example in the catalog.
+    pub fn type_id(&self) -> u8 {
+        match self {
+            DataType::Boolean => 1,
+            DataType::Int8 => 2,
+            DataType::Int16 => 3,
+            DataType::Int32 => 4,
+            DataType::Int64 => 5,
+            DataType::Float64 => 6,
+            DataType::Decimal { .. } => 7,
+            DataType::Varchar(_) => 8,
+            DataType::Bytea => 9,
+            DataType::Date => 10,
+            DataType::Timestamp => 11,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Boolean => write!(f, "BOOLEAN"),
+            DataType::Int8 => write!(f, "INT8"),
+            DataType::Int16 => write!(f, "INT16"),
+            DataType::Int32 => write!(f, "INT32"),
+            DataType::Int64 => write!(f, "INT64"),
+            DataType::Float64 => write!(f, "FLOAT64"),
+            DataType::Decimal { precision, scale } => write!(f, "DECIMAL({precision}, {scale})"),
+            DataType::Varchar(Some(len)) => write!(f, "VARCHAR({len})"),
+            DataType::Varchar(None) => write!(f, "VARCHAR"),
+            DataType::Bytea => write!(f, "BYTEA"),
+            DataType::Date => write!(f, "DATE"),
+            DataType::Timestamp => write!(f, "TIMESTAMP"),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Calendar types stored as offsets from the Unix epoch in the proleptic Gregorian calendar.
+
+use std::fmt;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// A calendar date, stored as the number of days since 1970-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(pub i32);
+
+impl Date {
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Self(days_from_civil(year, month, day)))
+    }
+
+    pub fn to_ymd(self) -> (i32, u32, u32) {
+        civil_from_days(self.0)
+    }
+
+    /// Parse a date in `YYYY-MM-DD` format.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        Self::from_ymd(year, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.to_ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+/// A date and time of day without time zone, stored as microseconds since
+/// 1970-01-01 00:00:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    pub fn from_date(date: Date) -> Self {
+        Self(date.0 as i64 * MICROS_PER_DAY)
+    }
+
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(MICROS_PER_DAY) as i32)
+    }
+
+    /// Parse a timestamp in `YYYY-MM-DD[ HH:MM:SS[.ffffff]]` format. A `T` may separate the
+    /// date from the time.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (date, time) = match s.find([' ', 'T']) {
+            Some(pos) => (&s[..pos], Some(s[pos + 1..].trim())),
+            None => (s, None),
+        };
+        let date = Date::parse(date)?;
+        let Some(time) = time else {
+            return Some(Self::from_date(date));
+        };
+
+        let (hms, fraction) = match time.split_once('.') {
+            Some((hms, fraction)) => (hms, Some(fraction)),
+            None => (time, None),
+        };
+        let mut parts = hms.splitn(3, ':');
+        let hour: i64 = parts.next()?.parse().ok()?;
+        let minute: i64 = parts.next()?.parse().ok()?;
+        let second: i64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        let micros = match fraction {
+            Some(fraction)
+                if !fraction.is_empty()
+                    && fraction.len() <= 6
+                    && fraction.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                fraction.parse::<i64>().ok()? * 10i64.pow(6 - fraction.len() as u32)
+            }
+            Some(_) => return None,
+            None => 0,
+        };
+
+        Some(Self(
+            Self::from_date(date).0
+                + ((hour * 60 + minute) * 60 + second) * MICROS_PER_SECOND
+                + micros,
+        ))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.0.rem_euclid(MICROS_PER_DAY);
+        let seconds = time / MICROS_PER_SECOND;
+        let micros = time % MICROS_PER_SECOND;
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}",
+            self.date(),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+        if micros != 0 {
+            write!(f, ".{micros:06}")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Howard Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i32;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i32) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i32::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(Date::from_ymd(1970, 1, 1), Some(Date(0)));
+        assert_eq!(Date::parse("2000-03-01").unwrap().to_string(), "2000-03-01");
+        assert_eq!(Date::parse("1969-12-31"), Some(Date(-1)));
+        assert_eq!(Date::parse("2024-02-29").unwrap().to_ymd(), (2024, 2, 29));
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("2023-13-01"), None);
+        assert_eq!(Date::parse("not a date"), None);
+        for days in (-800_000..800_000).step_by(997) {
+            let (year, month, day) = Date(days).to_ymd();
+            assert_eq!(Date::from_ymd(year, month, day), Some(Date(days)));
+        }
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let ts = Timestamp::parse("2024-05-06 07:08:09.5").unwrap();
+        assert_eq!(ts.to_string(), "2024-05-06 07:08:09.500000");
+        assert_eq!(ts.date(), Date::parse("2024-05-06").unwrap());
+        assert_eq!(
+            Timestamp::parse("1969-12-31T23:59:59").unwrap(),
+            Timestamp(-1_000_000)
+        );
+        assert_eq!(
+            Timestamp::parse("2024-05-06").unwrap().to_string(),
+            "2024-05-06 00:00:00"
+        );
+        assert_eq!(Timestamp::parse("2024-05-06 24:00:00"), None);
+        assert_eq!(Timestamp::parse("2024-05-06 10:00:00.1234567"), None);
+    }
+}
@@ -0,0 +1,267 @@
+use std::{cmp::Ordering, fmt};
+
+use super::data_type::DataType;
+
+/// Fixed-point decimal number: `mantissa * 10^-scale`.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u8,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u8) -> Self {
+        Self { mantissa, scale }
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        Self::new(value as i128, 0)
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// Parse a number such as `-12.345`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+            || fraction.len() > DataType::MAX_DECIMAL_PRECISION as usize
+        {
+            return None;
+        }
+        let mut mantissa: i128 = 0;
+        for b in integer.bytes().chain(fraction.bytes()) {
+            mantissa = mantissa.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        Some(Self::new(mantissa, fraction.len() as u8))
+    }
+
+    /// Number of significant digits of the mantissa.
+    pub fn precision(&self) -> u8 {
+        let mut n = self.mantissa.unsigned_abs();
+        let mut digits = 1;
+        while n >= 10 {
+            n /= 10;
+            digits += 1;
+        }
+        digits
+    }
+
+    /// Convert to `scale` fractional digits, rounding half away from zero.
+    /// Return None on overflow.
+    pub fn rescale(&self, scale: u8) -> Option<Self> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Some(*self),
+            Ordering::Greater => {
+                let factor = 10i128.checked_pow((scale - self.scale) as u32)?;
+                Some(Self::new(self.mantissa.checked_mul(factor)?, scale))
+            }
+            Ordering::Less => {
+                let factor = 10i128.checked_pow((self.scale - scale) as u32)?;
+                let quotient = self.mantissa / factor;
+                let remainder = self.mantissa % factor;
+                let rounded = if remainder.abs() * 2 >= factor {
+                    quotient + self.mantissa.signum()
+                } else {
+                    quotient
+                };
+                Some(Self::new(rounded, scale))
+            }
+        }
+    }
+
+    /// Rescale to fit `DECIMAL(precision, scale)`, or return None if the integer part has too
+    /// many digits.
+    pub fn fit(&self, precision: u8, scale: u8) -> Option<Self> {
+        let value = self.rescale(scale)?;
+        (value.precision() <= precision).then_some(value)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    pub fn from_f64(value: f64, scale: u8) -> Option<Self> {
+        let scaled = (value * 10f64.powi(scale as i32)).round();
+        if !scaled.is_finite() || scaled.abs() >= i128::MAX as f64 {
+            return None;
+        }
+        Some(Self::new(scaled as i128, scale))
+    }
+
+    /// Truncate towards zero to an integer.
+    pub fn trunc(&self) -> i128 {
+        self.mantissa / 10i128.pow(self.scale as u32)
+    }
+
+    /// Bring both operands to the larger scale.
+    fn align(&self, other: &Self) -> Option<(i128, i128, u8)> {
+        let scale = self.scale.max(other.scale);
+        Some((
+            self.rescale(scale)?.mantissa,
+            other.rescale(scale)?.mantissa,
+            scale,
+        ))
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let (a, b, scale) = self.align(other)?;
+        Some(Self::new(a.checked_add(b)?, scale))
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        let (a, b, scale) = self.align(other)?;
+        Some(Self::new(a.checked_sub(b)?, scale))
+    }
+
+    pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+        let scale = self.scale.checked_add(other.scale)?;
+        if scale > DataType::MAX_DECIMAL_PRECISION {
+            return self.rescale(self.scale / 2)?.checked_mul(other);
+        }
+        Some(Self::new(self.mantissa.checked_mul(other.mantissa)?, scale))
+    }
+
+    /// Divide, keeping `scale` fractional digits in the result.
+    pub fn checked_div(&self, other: &Self, scale: u8) -> Option<Self> {
+        if other.mantissa == 0 {
+            return None;
+        }
+        // (a / 10^sa) / (b / 10^sb) * 10^scale = a * 10^(scale + sb - sa) / b, computed with one
+        // extra digit for rounding.
+        let shift = scale as i32 + other.scale as i32 - self.scale as i32 + 1;
+        let numerator = if shift >= 0 {
+            self.mantissa
+                .checked_mul(10i128.checked_pow(shift as u32)?)?
+        } else {
+            self.mantissa / 10i128.checked_pow((-shift) as u32)?
+        };
+        Self::new(numerator / other.mantissa, scale + 1).rescale(scale)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.align(other) {
+            Some((a, b, _)) => a.cmp(&b),
+            // Aligning only overflows for huge values, where comparing as floats is accurate.
+            None => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl std::hash::Hash for Decimal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Equal values with different scales must hash alike, so strip trailing zeros.
+        let mut mantissa = self.mantissa;
+        let mut scale = self.scale;
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        mantissa.hash(state);
+        scale.hash(state);
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{integer}.{fraction}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in ["0", "12.345", "-0.05", "100", "-7.10"] {
+            assert_eq!(Decimal::parse(s).unwrap().to_string(), s);
+        }
+        assert_eq!(Decimal::parse(".5").unwrap().to_string(), "0.5");
+        assert_eq!(Decimal::parse("+3.").unwrap().to_string(), "3");
+        assert!(Decimal::parse("1.2.3").is_none());
+        assert!(Decimal::parse("").is_none());
+        assert!(Decimal::parse("abc").is_none());
+    }
+
+    #[test]
+    fn test_rescale_and_fit() {
+        let d = Decimal::parse("12.345").unwrap();
+        assert_eq!(d.rescale(2).unwrap().to_string(), "12.35");
+        assert_eq!(d.rescale(5).unwrap().to_string(), "12.34500");
+        assert_eq!(
+            Decimal::parse("-12.345")
+                .unwrap()
+                .rescale(1)
+                .unwrap()
+                .to_string(),
+            "-12.3"
+        );
+        assert_eq!(d.fit(4, 2).unwrap().to_string(), "12.35");
+        assert!(d.fit(3, 2).is_none());
+        assert_eq!(d.precision(), 5);
+    }
+
+    #[test]
+    fn test_arithmetic_and_ordering() {
+        let a = Decimal::parse("1.5").unwrap();
+        let b = Decimal::parse("0.25").unwrap();
+        assert_eq!(a.checked_add(&b).unwrap().to_string(), "1.75");
+        assert_eq!(a.checked_sub(&b).unwrap().to_string(), "1.25");
+        assert_eq!(a.checked_mul(&b).unwrap().to_string(), "0.375");
+        assert_eq!(a.checked_div(&b, 2).unwrap().to_string(), "6.00");
+        assert_eq!(
+            Decimal::from_i64(2)
+                .checked_div(&Decimal::from_i64(3), 4)
+                .unwrap()
+                .to_string(),
+            "0.6667"
+        );
+        assert!(a.checked_div(&Decimal::from_i64(0), 2).is_none());
+        assert!(a > b);
+        assert_eq!(Decimal::parse("1.50").unwrap(), a);
+        assert_eq!(Decimal::parse("2.5").unwrap().trunc(), 2);
+        assert_eq!(Decimal::from_f64(0.125, 2).unwrap().to_string(), "0.13");
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use super::{
+    data_type::DataType,
+    date_time::{Date, Timestamp},
+    decimal::Decimal,
+};
+
+/// A single SQL value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    Float64(f64),
+    Decimal(Decimal),
+    Varchar(String),
+    Bytea(Vec<u8>),
+    Date(Date),
+    Timestamp(Timestamp),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// The type of the value, or None for NULL, which belongs to every type. VARCHAR values
+    /// report an unbounded length and DECIMAL values their own scale.
+    pub fn data_type(&self) -> Option<DataType> {
+        Some(match self {
+            Value::Null => return None,
+            Value::Boolean(_) => DataType::Boolean,
+            Value::Int8(_) => DataType::Int8,
+            Value::Int16(_) => DataType::Int16,
+            Value::Int32(_) => DataType::Int32,
+            Value::Int64(_) => DataType::Int64,
+            Value::Float64(_) => DataType::Float64,
+            Value::Decimal(d) => DataType::Decimal {
+                precision: DataType::MAX_DECIMAL_PRECISION,
+                scale: d.scale(),
+            },
+            Value::Varchar(_) => DataType::Varchar(None),
+            Value::Bytea(_) => DataType::Bytea,
+            Value::Date(_) => DataType::Date,
+            Value::Timestamp(_) => DataType::Timestamp,
+        })
+    }
+
+    /// Whether the value can be stored in a column of `data_type` as is.
+    pub fn conforms_to(&self, data_type: &DataType) -> bool {
+        match (self, data_type) {
+            (Value::Null, _) => true,
+            (Value::Varchar(s), DataType::Varchar(max_len)) => {
+                max_len.map_or(true, |max_len| s.chars().count() <= max_len as usize)
+            }
+            (Value::Decimal(d), DataType::Decimal { precision, scale }) => {
+                d.fit(*precision, *scale).is_some()
+            }
+            (value, data_type) => value.data_type().as_ref() == Some(data_type),
+        }
+    }
+
+    /// The value as an i64 if it is an integer.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int8(v) => Some(*v as i64),
+            Value::Int16(v) => Some(*v as i64),
+            Value::Int32(v) => Some(*v as i64),
+            Value::Int64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Varchar(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Boolean(v) => write!(f, "{v}"),
+            Value::Int8(v) => write!(f, "{v}"),
+            Value::Int16(v) => write!(f, "{v}"),
+            Value::Int32(v) => write!(f, "{v}"),
+            Value::Int64(v) => write!(f, "{v}"),
+            Value::Float64(v) => write!(f, "{v}"),
+            Value::Decimal(v) => write!(f, "{v}"),
+            Value::Varchar(v) => write!(f, "{v}"),
+            Value::Bytea(v) => {
+                write!(f, "\\x")?;
+                v.iter().try_for_each(|b| write!(f, "{b:02x}"))
+            }
+            Value::Date(v) => write!(f, "{v}"),
+            Value::Timestamp(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conforms_to() {
+        assert!(Value::Null.conforms_to(&DataType::Int32));
+        assert!(Value::Int32(1).conforms_to(&DataType::Int32));
+        assert!(!Value::Int64(1).conforms_to(&DataType::Int32));
+        assert!(Value::Varchar("héllo".into()).conforms_to(&DataType::Varchar(Some(5))));
+        assert!(!Value::Varchar("hello!".into()).conforms_to(&DataType::Varchar(Some(5))));
+        assert!(Value::Varchar("hello!".into()).conforms_to(&DataType::Varchar(None)));
+        let decimal = Value::Decimal(Decimal::parse("123.45").unwrap());
+        assert!(decimal.conforms_to(&DataType::Decimal {
+            precision: 5,
+            scale: 2
+        }));
+        assert!(!decimal.conforms_to(&DataType::Decimal {
+            precision: 4,
+            scale: 2
+        }));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Value::Null.to_string(), "NULL");
+        assert_eq!(Value::Bytea(vec![0xde, 0xad]).to_string(), "\\xdead");
+        assert_eq!(
+            Value::Date(Date::parse("2024-01-02").unwrap()).to_string(),
+            "2024-01-02"
+        );
+    }
+}